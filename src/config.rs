@@ -1,4 +1,4 @@
-use super::*;
+use {super::*, crate::okx::protocol::ChainSchedule, std::collections::HashMap};
 
 #[derive(Deserialize, Default, PartialEq, Debug)]
 #[serde(deny_unknown_fields)]
@@ -6,12 +6,18 @@ pub(crate) struct Config {
   pub(crate) hidden: HashSet<InscriptionId>,
   pub(crate) bitcoin_rpc_pass: Option<String>,
   pub(crate) bitcoin_rpc_user: Option<String>,
+  #[serde(default)]
+  pub(crate) protocols: HashMap<Chain, ChainSchedule>,
 }
 
 impl Config {
   pub(crate) fn is_hidden(&self, inscription_id: InscriptionId) -> bool {
     self.hidden.contains(&inscription_id)
   }
+
+  pub(crate) fn protocols(&self, chain: Chain) -> ChainSchedule {
+    self.protocols.get(&chain).cloned().unwrap_or_default()
+  }
 }
 
 #[cfg(test)]
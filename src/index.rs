@@ -1,37 +1,33 @@
 use {
   self::{
     entry::{BlockHashValue, Entry, InscriptionIdValue, OutPointValue, SatPointValue, SatRange},
+    notification::Notifier,
     reorg::*,
     updater::Updater,
   },
   super::*,
-  bitcoin::block::Header,
-  bitcoincore_rpc::{
-    json::{GetBlockHeaderResult, GetBlockResult},
-    Client,
-  },
+  bitcoin::{block::Header, hashes::sha256},
+  bitcoincore_rpc::{json::GetBlockHeaderResult, Client},
   chrono::SubsecRound,
   indicatif::{ProgressBar, ProgressStyle},
   log::log_enabled,
   okx::{
     datastore::{
-      brc20::{
-        self, redb as brc20_db, redb::try_init_tables as try_init_brc20,
-        DataStoreReadOnly as BRC20DataStoreReadOnly,
-      },
+      brc20::{self, redb as brc20_db, DataStoreReadOnly as BRC20DataStoreReadOnly},
       brc20s::{
-        self, redb as brc20s_db, redb::try_init_tables as try_init_brc20s,
-        DataStoreReadOnly as BRC20SDataStoreReadOnly, PledgedTick,
+        self, redb as brc20s_db, DataStoreReadOnly as BRC20SDataStoreReadOnly, PledgedTick,
       },
-      ord::{self, redb::try_init_tables as try_init_ord, DataStoreReadOnly},
+      keys,
+      ord::{self, DataStoreReadOnly},
+      table::TableReader,
       ScriptKey,
     },
-    protocol::brc20s::params::NATIVE_TOKEN_DECIMAL,
+    protocol::{brc20s::params::NATIVE_TOKEN_DECIMAL, ProtocolConfig, ProtocolInfo},
     reward,
   },
   redb::{
-    Database, MultimapTable, MultimapTableDefinition, ReadableMultimapTable, ReadableTable, Table,
-    TableDefinition, WriteTransaction,
+    Database, MultimapTableDefinition, ReadTransaction, ReadableMultimapTable, ReadableTable,
+    Table, TableDefinition, WriteTransaction,
   },
   std::collections::HashMap,
   std::io::{BufWriter, Read, Write},
@@ -41,16 +37,28 @@ use crate::okx::datastore::ord::{bitmap::District, collections::CollectionKind};
 
 pub(super) use self::{
   entry::{InscriptionEntry, InscriptionEntryValue},
+  notification::Notification,
   updater::BlockData,
 };
 
+mod blk;
 mod entry;
-mod fetcher;
+pub(crate) mod fetcher;
+pub(crate) mod mempool;
+mod migrate;
+pub(crate) mod notification;
+pub(crate) mod ranking;
 mod reorg;
 mod rtx;
+pub(crate) mod simulate;
+pub(crate) mod snapshot;
+pub(crate) mod state_hash;
+pub(crate) mod undo;
 mod updater;
+pub(crate) mod verify;
+pub(crate) mod webhook;
 
-const SCHEMA_VERSION: u64 = 6;
+const SCHEMA_VERSION: u64 = 7;
 
 macro_rules! define_table {
   ($name:ident, $key:ty, $value:ty) => {
@@ -68,7 +76,9 @@ macro_rules! define_multimap_table {
 define_multimap_table! { INSCRIPTION_ID_TO_CHILDREN, &InscriptionIdValue, &InscriptionIdValue }
 define_multimap_table! { SATPOINT_TO_INSCRIPTION_ID, &SatPointValue, &InscriptionIdValue }
 define_multimap_table! { SAT_TO_INSCRIPTION_ID, u64, &InscriptionIdValue }
+define_table! { BLOCK_HASH_TO_HEIGHT, &BlockHashValue, u64 }
 define_table! { HEIGHT_TO_BLOCK_HASH, u64, &BlockHashValue }
+define_table! { HEIGHT_TO_STATE_HASH, u64, &BlockHashValue }
 define_table! { HEIGHT_TO_LAST_INSCRIPTION_NUMBER, u64, (i64, i64) }
 define_table! { INSCRIPTION_ID_TO_INSCRIPTION_ENTRY, &InscriptionIdValue, InscriptionEntryValue }
 define_table! { INSCRIPTION_ID_TO_SATPOINT, &InscriptionIdValue, &SatPointValue }
@@ -95,6 +105,9 @@ pub(crate) enum Statistic {
   OutputsTraversed = 3,
   SatRanges = 4,
   UnboundInscriptions = 5,
+  ChainHeight = 6,
+  MigrationHeight = 7,
+  MigrationProgress = 8,
 }
 
 impl Statistic {
@@ -164,8 +177,12 @@ pub(crate) struct Index {
   genesis_block_coinbase_transaction: Transaction,
   genesis_block_coinbase_txid: Txid,
   height_limit: Option<u64>,
+  mempool: mempool::Mempool,
+  notifier: Notifier,
   options: Options,
   path: PathBuf,
+  protocol_config: ProtocolConfig,
+  brc20_ranking: ranking::Ranking,
   unrecoverably_reorged: AtomicBool,
 }
 
@@ -173,11 +190,7 @@ impl Index {
   pub(crate) fn open(options: &Options) -> Result<Self> {
     let client = options.bitcoin_rpc_client()?;
 
-    let path = if let Some(path) = &options.index {
-      path.clone()
-    } else {
-      options.data_dir()?.join("index.redb")
-    };
+    let path = Self::path(options)?;
 
     if let Err(err) = fs::create_dir_all(path.parent().unwrap()) {
       bail!(
@@ -222,28 +235,7 @@ impl Index {
       .open(&path)
     {
       Ok(database) => {
-        let schema_version = database
-          .begin_read()?
-          .open_table(STATISTIC_TO_COUNT)?
-          .get(&Statistic::Schema.key())?
-          .map(|x| x.value())
-          .unwrap_or(0);
-
-        match schema_version.cmp(&SCHEMA_VERSION) {
-          cmp::Ordering::Less =>
-            bail!(
-              "index at `{}` appears to have been built with an older, incompatible version of ord, consider deleting and rebuilding the index: index schema {schema_version}, ord schema {SCHEMA_VERSION}",
-              path.display()
-            ),
-          cmp::Ordering::Greater =>
-            bail!(
-              "index at `{}` appears to have been built with a newer, incompatible version of ord, consider updating ord: index schema {schema_version}, ord schema {SCHEMA_VERSION}",
-              path.display()
-            ),
-          cmp::Ordering::Equal => {
-          }
-        }
-
+        Self::check_schema_version(&database, &path)?;
         database
       }
       Err(_) => {
@@ -258,8 +250,10 @@ impl Index {
         tx.open_multimap_table(INSCRIPTION_ID_TO_CHILDREN)?;
         tx.open_multimap_table(SATPOINT_TO_INSCRIPTION_ID)?;
         tx.open_multimap_table(SAT_TO_INSCRIPTION_ID)?;
+        tx.open_table(BLOCK_HASH_TO_HEIGHT)?;
         tx.open_table(HEIGHT_TO_BLOCK_HASH)?;
         tx.open_table(HEIGHT_TO_LAST_INSCRIPTION_NUMBER)?;
+        tx.open_table(HEIGHT_TO_STATE_HASH)?;
         tx.open_table(INSCRIPTION_ID_TO_INSCRIPTION_ENTRY)?;
         tx.open_table(INSCRIPTION_ID_TO_SATPOINT)?;
         tx.open_table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID)?;
@@ -283,10 +277,16 @@ impl Index {
     };
     {
       let wtx = database.begin_write()?;
-      let rtx = database.begin_read()?;
-      try_init_ord(&wtx, &rtx)?;
-      try_init_brc20(&wtx, &rtx)?;
-      try_init_brc20s(&wtx, &rtx)?;
+      for table in ProtocolInfo::state_tables(&ProtocolConfig::new_with_options(options)?) {
+        table.create(&wtx)?;
+      }
+      undo::try_init_tables(&wtx)?;
+      webhook::try_init_tables(&wtx)?;
+      // reorgs are unwound from the undo logs, so the savepoints earlier versions kept for the sat
+      // index would only keep the pages they reference from being freed.
+      for savepoint in wtx.list_persistent_savepoints()?.collect::<Vec<u64>>() {
+        wtx.delete_persistent_savepoint(savepoint)?;
+      }
       wtx.commit()?;
       log::info!("Options:\n{:#?}", options);
     }
@@ -301,16 +301,199 @@ impl Index {
       first_inscription_height: options.first_inscription_height(),
       genesis_block_coinbase_transaction,
       height_limit: options.height_limit,
+      mempool: mempool::Mempool::default(),
+      notifier: Notifier::new(),
       options: options.clone(),
       path,
+      protocol_config: ProtocolConfig::new_with_options(options)?,
+      brc20_ranking: ranking::Ranking::default(),
       unrecoverably_reorged: AtomicBool::new(false),
     })
   }
 
+  fn path(options: &Options) -> Result<PathBuf> {
+    Ok(if let Some(path) = &options.index {
+      path.clone()
+    } else {
+      options.data_dir()?.join("index.redb")
+    })
+  }
+
+  fn check_schema_version(database: &Database, path: &Path) -> Result {
+    let schema_version = database
+      .begin_read()?
+      .open_table(STATISTIC_TO_COUNT)?
+      .get(&Statistic::Schema.key())?
+      .map(|x| x.value())
+      .unwrap_or(0);
+
+    match schema_version.cmp(&SCHEMA_VERSION) {
+      cmp::Ordering::Less if schema_version == migrate::BASELINE_SCHEMA_VERSION =>
+        bail!(
+          "index at `{}` needs to be migrated with `ord index migrate` before it can be used: index schema {schema_version}, ord schema {SCHEMA_VERSION}",
+          path.display()
+        ),
+      cmp::Ordering::Less =>
+        bail!(
+          "index at `{}` appears to have been built with an older, incompatible version of ord, consider deleting and rebuilding the index: index schema {schema_version}, ord schema {SCHEMA_VERSION}",
+          path.display()
+        ),
+      cmp::Ordering::Greater =>
+        bail!(
+          "index at `{}` appears to have been built with a newer, incompatible version of ord, consider updating ord: index schema {schema_version}, ord schema {SCHEMA_VERSION}",
+          path.display()
+        ),
+      cmp::Ordering::Equal => Ok(()),
+    }
+  }
+
+  /// Migrates the index at the configured path from the baseline schema, fetching the blocks it
+  /// backfills from Bitcoin Core.
+  pub(crate) fn migrate(options: &Options) -> Result {
+    let path = Self::path(options)?;
+
+    let database = Database::builder()
+      .open(&path)
+      .with_context(|| format!("failed to open index at `{}`", path.display()))?;
+
+    migrate::run(
+      &database,
+      &options.bitcoin_rpc_client()?,
+      options.first_inscription_height(),
+    )
+  }
+
+  /// Re-executes the stored inscription operations of the index at the configured path and compares
+  /// the result with its BRC20 and BRC20S state, without connecting to Bitcoin Core.
+  pub(crate) fn verify_offline(options: &Options) -> Result<verify::Verification> {
+    let path = Self::path(options)?;
+
+    let database = Database::builder()
+      .open(&path)
+      .with_context(|| format!("failed to open index at `{}`", path.display()))?;
+
+    Self::check_schema_version(&database, &path)?;
+
+    verify::run(&database, options, path.parent().unwrap())
+  }
+
+  /// Executes `transactions` on top of the current state without committing anything and returns
+  /// their inscription operations and BRC20 and BRC20S receipts.
+  pub(crate) fn simulate(
+    &self,
+    transactions: &[Transaction],
+    outputs: HashMap<OutPoint, TxOut>,
+  ) -> Result<simulate::Simulation> {
+    simulate::run(self, transactions, outputs)
+  }
+
+  /// Simulates inscribing `inscription` on the `funding` output and sending it to `script_pubkey`
+  /// in the next block, or on a placeholder output of `script_pubkey` when no funding output is
+  /// given. The reveal transaction is the last simulated transaction. Like every simulation, it
+  /// only reads the index, so validations are served while a block is being indexed.
+  pub(crate) fn simulate_inscribe(
+    &self,
+    inscription: &Inscription,
+    funding: Option<OutPoint>,
+    script_pubkey: &Script,
+  ) -> Result<simulate::Simulation> {
+    let funding = match funding {
+      Some(outpoint) => (outpoint, simulate::output(self, outpoint)?),
+      None => simulate::placeholder_funding(script_pubkey),
+    };
+    let (transactions, outputs) =
+      simulate::inscribe_transactions(inscription, funding, script_pubkey);
+    simulate::run(self, &transactions, outputs)
+  }
+
+  /// Polls the mempool and updates the overlay of pending BRC20 and BRC20S state when it changed.
+  pub(crate) fn update_mempool(&self) -> Result {
+    mempool::update(self)
+  }
+
+  /// Returns the state pending transactions would leave behind, once the mempool was polled.
+  pub(crate) fn pending(&self) -> Option<Arc<mempool::Overlay>> {
+    self.mempool.overlay()
+  }
+
+  /// Writes the tables of the index at the configured path to a snapshot archive at `file`, as
+  /// they were at `height` when given.
+  pub(crate) fn export_snapshot(
+    options: &Options,
+    height: Option<u64>,
+    file: &Path,
+  ) -> Result<snapshot::Summary> {
+    let path = Self::path(options)?;
+
+    // the blocks above `height` are unwound in a copy, leaving the index untouched.
+    let scratch = match height {
+      Some(_) => {
+        let scratch = tempfile::NamedTempFile::new_in(path.parent().unwrap())?;
+        fs::copy(&path, scratch.path())
+          .with_context(|| format!("failed to copy index at `{}`", path.display()))?;
+        Some(scratch)
+      }
+      None => None,
+    };
+
+    let database = Database::builder()
+      .open(
+        scratch
+          .as_ref()
+          .map_or(path.as_path(), |scratch| scratch.path()),
+      )
+      .with_context(|| format!("failed to open index at `{}`", path.display()))?;
+
+    Self::check_schema_version(&database, &path)?;
+
+    if let Some(height) = height {
+      snapshot::unwind(&database, height)?;
+    }
+
+    snapshot::export(
+      &database,
+      options.chain(),
+      &ProtocolInfo::state_tables(&ProtocolConfig::new_with_options(options)?),
+      file,
+    )
+  }
+
+  /// Restores the snapshot archive at `file` into a new index at the configured path, which then
+  /// keeps indexing from the snapshot height.
+  pub(crate) fn import_snapshot(options: &Options, file: &Path) -> Result<snapshot::Summary> {
+    let path = Self::path(options)?;
+
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+
+    snapshot::import(
+      file,
+      options.chain(),
+      &ProtocolInfo::state_tables(&ProtocolConfig::new_with_options(options)?),
+      &path,
+    )
+  }
+
+  #[cfg(test)]
+  fn verify(&self) -> Result<verify::Verification> {
+    verify::run(&self.database, &self.options, self.path.parent().unwrap())
+  }
+
+  /// Subscribes to the notifications of blocks, reorgs and receipts committed by the updater.
+  pub(crate) fn subscribe(&self) -> tokio::sync::broadcast::Receiver<Arc<Notification>> {
+    self.notifier.subscribe()
+  }
+
   pub(crate) fn get_chain_network(&self) -> Network {
     self.options.chain().network()
   }
 
+  /// Returns the protocols indexed with the configured options, in resolution order.
+  pub(crate) fn protocols(&self) -> Vec<ProtocolInfo> {
+    ProtocolInfo::all(&self.protocol_config)
+  }
+
   #[cfg(test)]
   fn set_durability(&mut self, durability: redb::Durability) {
     self.durability = durability;
@@ -463,9 +646,18 @@ impl Index {
             Some(&ReorgError::Recoverable { height, depth }) => {
               Reorg::handle_reorg(self, height, depth)?;
 
+              // blocks from the new block count up to `height` were disconnected.
+              let block_count = self.block_count()?;
+              metrics::inc_reorgs(Some(height - block_count));
+              self.notifier.notify([Notification::Reorg {
+                height: block_count,
+                depth: height - block_count,
+              }]);
+
               updater = Updater::new(self)?;
             }
             Some(&ReorgError::Unrecoverable) => {
+              metrics::inc_reorgs(None);
               self
                 .unrecoverably_reorged
                 .store(true, atomic::Ordering::Relaxed);
@@ -590,35 +782,53 @@ impl Index {
     self.begin_read()?.block_height()
   }
 
+  /// Returns the indexed height and, if `query_btc` is set, the height of the bitcoin node's chain
+  /// as of the last index update.
   pub(crate) fn height_btc(&self, query_btc: bool) -> Result<(Option<Height>, Option<Height>)> {
-    let ord_height = self.begin_read()?.block_height()?;
-    if let Some(height) = ord_height {
-      if query_btc {
-        let btc_height = match self.client.get_blockchain_info() {
-          Ok(info) => Height(info.headers),
-          Err(e) => {
-            return Err(anyhow!(
-              "failed to get blockchain info from bitcoin node: {}",
-              e.to_string()
-            ));
-          }
-        };
-        return Ok((Some(height), Some(btc_height)));
-      }
-      Ok((Some(height), None))
-    } else {
-      Ok((None, None))
+    let rtx = self.begin_read()?;
+    let Some(height) = rtx.block_height()? else {
+      return Ok((None, None));
+    };
+
+    if !query_btc {
+      return Ok((Some(height), None));
     }
+
+    let btc_height = rtx
+      .0
+      .open_table(STATISTIC_TO_COUNT)?
+      .get(&Statistic::ChainHeight.key())?
+      .map(|height| Height(height.value()));
+
+    Ok((Some(height), btc_height))
   }
 
   pub(crate) fn block_count(&self) -> Result<u64> {
     self.begin_read()?.block_count()
   }
 
+  /// Returns the height at which the index was migrated from an older schema, if it was. Balance
+  /// history is only recorded from that height on.
+  pub(crate) fn migration_height(&self) -> Result<Option<u64>> {
+    Ok(
+      self
+        .begin_read()?
+        .0
+        .open_table(STATISTIC_TO_COUNT)?
+        .get(&Statistic::MigrationHeight.key())?
+        .map(|height| height.value()),
+    )
+  }
+
   pub(crate) fn block_height(&self) -> Result<Option<Height>> {
     self.begin_read()?.block_height()
   }
 
+  /// Returns the state hash of the block at `height`, or of the latest block.
+  pub(crate) fn state_hash(&self, height: Option<u64>) -> Result<Option<(Height, sha256::Hash)>> {
+    self.begin_read()?.state_hash(height)
+  }
+
   pub(crate) fn block_hash(&self, height: Option<u64>) -> Result<Option<BlockHash>> {
     self.begin_read()?.block_hash(height)
   }
@@ -701,8 +911,33 @@ impl Index {
     self.client.get_block(&hash).into_option()
   }
 
-  pub(crate) fn get_block_info_by_hash(&self, hash: BlockHash) -> Result<Option<GetBlockResult>> {
-    self.client.get_block_info(&hash).into_option()
+  /// Returns the transactions with inscription operations of block `hash`, in block order, or
+  /// `None` if the block is not part of the indexed chain.
+  pub(crate) fn get_block_transactions_by_hash(
+    &self,
+    hash: BlockHash,
+  ) -> Result<Option<Vec<Txid>>> {
+    let rtx = self.begin_read()?;
+    let Some(height) = rtx
+      .0
+      .open_table(BLOCK_HASH_TO_HEIGHT)?
+      .get(&hash.store())?
+      .map(|height| height.value())
+    else {
+      return Ok(None);
+    };
+
+    // check for a block replaced by a reorg.
+    if rtx.block_hash(Some(height))? != Some(hash) {
+      return Ok(None);
+    }
+
+    Ok(Some(
+      ord::DataStore::new(&rtx.0)
+        .get_block_transactions(height)?
+        .map(|(_, txids)| txids)
+        .unwrap_or_default(),
+    ))
   }
 
   pub(crate) fn get_children_by_inscription_id(
@@ -781,7 +1016,7 @@ impl Index {
     inscription_id: InscriptionId,
   ) -> Result<Option<Vec<CollectionKind>>> {
     Ok(
-      ord::OrdDbReader::new(&self.database.begin_read()?)
+      ord::DataStore::new(&self.database.begin_read()?)
         .get_collections_of_inscription(inscription_id)?,
     )
   }
@@ -792,7 +1027,7 @@ impl Index {
   ) -> Result<Option<InscriptionId>> {
     let district = District { number };
     Ok(
-      ord::OrdDbReader::new(&self.database.begin_read()?)
+      ord::DataStore::new(&self.database.begin_read()?)
         .get_collection_inscription_id(&district.to_collection_key())?,
     )
   }
@@ -822,11 +1057,7 @@ impl Index {
     &self,
     outpoint: OutPoint,
   ) -> Result<Vec<(SatPoint, InscriptionId)>> {
-    let rtx = &self.database.begin_read()?;
-    let sat_to_id = rtx.open_multimap_table(SATPOINT_TO_INSCRIPTION_ID)?;
-    let re_id_to_seq_num = rtx.open_table(REINSCRIPTION_ID_TO_SEQUENCE_NUMBER)?;
-
-    Self::inscriptions_on_output_ordered(&re_id_to_seq_num, &sat_to_id, outpoint)
+    Self::inscriptions_on_output_ordered(&self.database.begin_read()?, outpoint)
   }
 
   pub(crate) fn get_inscriptions_on_output(
@@ -842,31 +1073,11 @@ impl Index {
     )
   }
 
-  pub(crate) fn get_inscriptions_with_satpoint_on_output(
-    &self,
-    outpoint: OutPoint,
-  ) -> Result<Vec<(SatPoint, InscriptionId)>> {
-    Self::inscriptions_on_output_ordered(
-      &self
-        .database
-        .begin_read()?
-        .open_table(REINSCRIPTION_ID_TO_SEQUENCE_NUMBER)?,
-      &self
-        .database
-        .begin_read()?
-        .open_multimap_table(SATPOINT_TO_INSCRIPTION_ID)?,
-      outpoint,
-    )
-  }
-
   pub(crate) fn get_transaction_output_by_outpoint(
     &self,
     outpoint: OutPoint,
   ) -> Result<Option<TxOut>> {
-    Self::transaction_output_by_outpoint(
-      &self.database.begin_read()?.open_table(OUTPOINT_TO_ENTRY)?,
-      outpoint,
-    )
+    Self::transaction_output_by_outpoint(&self.database.begin_read()?, outpoint)
   }
 
   pub(crate) fn get_transaction(&self, txid: Txid) -> Result<Option<Transaction>> {
@@ -1042,9 +1253,8 @@ impl Index {
 
     let mut result = BTreeMap::new();
 
-    let table = rtx.open_multimap_table(SATPOINT_TO_INSCRIPTION_ID)?;
     for utxo in utxos.keys() {
-      result.extend(Self::inscriptions_on_output_unordered(&table, *utxo)?);
+      result.extend(Self::inscriptions_on_output_unordered(&rtx, *utxo)?);
     }
 
     Ok(result)
@@ -1323,10 +1533,10 @@ impl Index {
     }
   }
 
-  fn inscriptions_on_output_unordered<'a: 'tx, 'tx>(
-    satpoint_to_id: &'a impl ReadableMultimapTable<&'static SatPointValue, &'static InscriptionIdValue>,
+  fn inscriptions_on_output_unordered(
+    tables: &impl TableReader,
     outpoint: OutPoint,
-  ) -> Result<impl Iterator<Item = (SatPoint, InscriptionId)> + 'tx> {
+  ) -> Result<Vec<(SatPoint, InscriptionId)>> {
     let start = SatPoint {
       outpoint,
       offset: 0,
@@ -1341,29 +1551,30 @@ impl Index {
 
     let mut inscriptions = Vec::new();
 
-    for range in satpoint_to_id.range::<&[u8; 44]>(&start..=&end)? {
-      let (satpoint, ids) = range?;
-      for id_result in ids {
-        let id = id_result?;
+    for (satpoint, ids) in tables
+      .multimap_table(SATPOINT_TO_INSCRIPTION_ID)
+      .range::<&[u8; 44]>(&start..=&end)?
+    {
+      for id in ids {
         inscriptions.push((Entry::load(*satpoint.value()), Entry::load(*id.value())));
       }
     }
 
-    Ok(inscriptions.into_iter())
+    Ok(inscriptions)
   }
 
-  fn inscriptions_on_output_ordered<'a: 'tx, 'tx>(
-    re_id_to_seq_num: &'a impl ReadableTable<&'static InscriptionIdValue, u64>,
-    satpoint_to_id: &'a impl ReadableMultimapTable<&'static SatPointValue, &'static InscriptionIdValue>,
+  fn inscriptions_on_output_ordered(
+    tables: &impl TableReader,
     outpoint: OutPoint,
   ) -> Result<Vec<(SatPoint, InscriptionId)>> {
-    let mut result = Self::inscriptions_on_output_unordered(satpoint_to_id, outpoint)?
-      .collect::<Vec<(SatPoint, InscriptionId)>>();
+    let mut result = Self::inscriptions_on_output_unordered(tables, outpoint)?;
 
     if result.len() <= 1 {
       return Ok(result);
     }
 
+    let re_id_to_seq_num = tables.table(REINSCRIPTION_ID_TO_SEQUENCE_NUMBER);
+
     result.sort_by_key(|(_satpoint, inscription_id)| {
       match re_id_to_seq_num.get(&inscription_id.store()) {
         Ok(Some(num)) => num.value() + 1, // remove at next index refactor
@@ -1376,11 +1587,12 @@ impl Index {
   }
 
   pub(crate) fn transaction_output_by_outpoint(
-    outpoint_to_entry: &impl ReadableTable<&'static OutPointValue, &'static [u8]>,
+    tables: &impl TableReader,
     outpoint: OutPoint,
   ) -> Result<Option<TxOut>> {
     Ok(
-      outpoint_to_entry
+      tables
+        .table(OUTPOINT_TO_ENTRY)
         .get(&outpoint.store())?
         .map(|x| Decodable::consensus_decode(&mut io::Cursor::new(x.value())).unwrap()),
     )
@@ -1388,50 +1600,117 @@ impl Index {
 
   pub(crate) fn brc20_get_tick_info(&self, name: &brc20::Tick) -> Result<Option<brc20::TokenInfo>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20_db = brc20_db::DataStoreReader::new(&rtx);
+    let brc20_db = brc20_db::DataStore::new(&rtx);
     let info = brc20_db.get_token_info(name)?;
     Ok(info)
   }
 
   pub(crate) fn brc20_get_all_tick_info(&self) -> Result<Vec<brc20::TokenInfo>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20_db = brc20_db::DataStoreReader::new(&rtx);
+    let brc20_db = brc20_db::DataStore::new(&rtx);
     let info = brc20_db.get_tokens_info()?;
     Ok(info)
   }
 
+  pub(crate) fn brc20_get_tick_stats(
+    &self,
+    tick: &brc20::Tick,
+  ) -> Result<Option<ranking::TickStats>> {
+    let rtx = self.database.begin_read().unwrap();
+    let brc20_db = brc20_db::DataStore::new(&rtx);
+    let Some(info) = brc20_db.get_token_info(tick)? else {
+      return Ok(None);
+    };
+    let stats = brc20_db
+      .get_token_stats(tick)?
+      .unwrap_or_else(|| brc20::TokenStats::new(&info.tick, info.deployed_number));
+    let holders = brc20_db.get_token_holders_count(tick)?;
+    Ok(Some((info, stats, holders)))
+  }
+
+  /// Returns the stats of all the ticks ranked by `sort`, from the ranking cached for the latest
+  /// block.
+  pub(crate) fn brc20_get_ranked_tick_stats(
+    &self,
+    sort: ranking::StatsSort,
+  ) -> Result<Arc<Vec<ranking::TickStats>>> {
+    let rtx = self.begin_read()?;
+    self.brc20_ranking.get(rtx.block_hash(None)?, sort, || {
+      let brc20_db = brc20_db::DataStore::new(&rtx.0);
+      // both tables are keyed by the lowercase tick, so they iterate in the same order.
+      let mut stats = brc20_db.get_tokens_stats()?.into_iter().peekable();
+      brc20_db
+        .get_tokens_info()?
+        .into_iter()
+        .map(|info| {
+          let tick_stats = match stats.peek() {
+            Some(next) if next.tick.to_lowercase() == info.tick.to_lowercase() => {
+              stats.next().unwrap()
+            }
+            _ => brc20::TokenStats::new(&info.tick, info.deployed_number),
+          };
+          let holders = brc20_db.get_token_holders_count(&info.tick)?;
+          Ok((info, tick_stats, holders))
+        })
+        .collect()
+    })
+  }
+
+  pub(crate) fn brc20_get_tick_holders(
+    &self,
+    tick: &brc20::Tick,
+    start: usize,
+    limit: Option<usize>,
+  ) -> Result<(Vec<(ScriptKey, brc20::Balance)>, usize)> {
+    let rtx = self.database.begin_read().unwrap();
+    let brc20_db = brc20_db::DataStore::new(&rtx);
+    Ok((
+      brc20_db.get_token_holders(tick, start, limit)?,
+      brc20_db.get_token_holders_count(tick)?.try_into().unwrap(),
+    ))
+  }
+
+  pub(crate) fn brc20_get_tick_holders_count(&self, tick: &brc20::Tick) -> Result<u64> {
+    let rtx = self.database.begin_read().unwrap();
+    let brc20_db = brc20_db::DataStore::new(&rtx);
+    Ok(brc20_db.get_token_holders_count(tick)?)
+  }
+
   pub(crate) fn brc20_get_balance_by_address(
     &self,
     tick: &brc20::Tick,
     address: &bitcoin::Address,
+    height: Option<u64>,
   ) -> Result<Option<brc20::Balance>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20_db = brc20_db::DataStoreReader::new(&rtx);
-    let bal = brc20_db.get_balance(&ScriptKey::from_address(address.clone()), tick)?;
+    let brc20_db = brc20_db::DataStore::new(&rtx);
+    let script_key = ScriptKey::from_address(address.clone());
+    let bal = match height {
+      Some(height) => brc20_db.get_balance_at_height(&script_key, tick, height)?,
+      None => brc20_db.get_balance(&script_key, tick)?,
+    };
     Ok(bal)
   }
 
   pub(crate) fn brc20_get_all_balance_by_address(
     &self,
     address: &bitcoin::Address,
+    height: Option<u64>,
   ) -> Result<Vec<brc20::Balance>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20_db = brc20_db::DataStoreReader::new(&rtx);
-    Ok(brc20_db.get_balances(&ScriptKey::from_address(address.clone()))?)
+    let brc20_db = brc20_db::DataStore::new(&rtx);
+    let script_key = ScriptKey::from_address(address.clone());
+    Ok(match height {
+      Some(height) => brc20_db.get_balances_at_height(&script_key, height)?,
+      None => brc20_db.get_balances(&script_key)?,
+    })
   }
 
-  pub(crate) fn get_transaction_info(
-    &self,
-    txid: &bitcoin::Txid,
-  ) -> Result<Option<bitcoincore_rpc::json::GetRawTransactionResult>> {
-    if *txid == self.genesis_block_coinbase_txid {
-      Ok(None)
-    } else {
-      self
-        .client
-        .get_raw_transaction_info(txid, None)
-        .into_option()
-    }
+  /// Returns the height of the block that confirmed `txid`, if the transaction has inscription
+  /// operations.
+  pub(crate) fn get_transaction_height(&self, txid: &Txid) -> Result<Option<u64>> {
+    let rtx = self.database.begin_read()?;
+    Ok(ord::DataStore::new(&rtx).get_transaction_height(txid)?)
   }
 
   pub(crate) fn brc20_get_tx_events_by_txid(
@@ -1439,20 +1718,16 @@ impl Index {
     txid: &bitcoin::Txid,
   ) -> Result<Option<Vec<brc20::Receipt>>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20_db = brc20_db::DataStoreReader::new(&rtx);
+    let brc20_db = brc20_db::DataStore::new(&rtx);
     let res = brc20_db.get_transaction_receipts(txid)?;
 
-    if res.is_empty() {
-      let tx = self.client.get_raw_transaction_info(txid, None)?;
-      if let Some(tx_blockhash) = tx.blockhash {
-        let tx_bh = self.client.get_block_header_info(&tx_blockhash)?;
-        let parsed_height = self.height()?;
-        if parsed_height.is_none() || tx_bh.height as u64 > parsed_height.unwrap().0 {
-          return Ok(None);
-        }
-      } else {
-        return Err(anyhow!("can't get tx block hash: {txid}"));
-      }
+    // transactions without inscription operations are not indexed.
+    if res.is_empty()
+      && ord::DataStore::new(&rtx)
+        .get_transaction_height(txid)?
+        .is_none()
+    {
+      return Ok(None);
     }
 
     Ok(Some(res))
@@ -1463,7 +1738,7 @@ impl Index {
     txs: &Vec<Txid>,
   ) -> Result<Vec<(bitcoin::Txid, Vec<brc20::Receipt>)>> {
     let rtx = self.database.begin_read()?;
-    let brc20_db = brc20_db::DataStoreReader::new(&rtx);
+    let brc20_db = brc20_db::DataStore::new(&rtx);
     let mut result = Vec::new();
     for txid in txs {
       let tx_events = brc20_db.get_transaction_receipts(txid)?;
@@ -1475,24 +1750,68 @@ impl Index {
     Ok(result)
   }
 
+  /// Returns a page of the BRC20 receipts of the address that match `filter`, newest first,
+  /// and the cursor of the next page if there may be one.
+  pub(crate) fn brc20_get_address_events(
+    &self,
+    address: &bitcoin::Address,
+    filter: impl Fn(&brc20::Receipt) -> bool,
+    cursor: Option<u64>,
+    limit: usize,
+  ) -> Result<(Vec<(Txid, brc20::Receipt)>, Option<u64>)> {
+    let rtx = self.database.begin_read().unwrap();
+    let brc20_db = brc20_db::DataStore::new(&rtx);
+    let events = brc20_db.get_address_receipts(
+      &ScriptKey::from_address(address.clone()),
+      cursor,
+      limit,
+      &filter,
+    )?;
+    let next = if events.len() == limit {
+      events.last().map(|(sequence, ..)| *sequence)
+    } else {
+      None
+    };
+    Ok((
+      events
+        .into_iter()
+        .map(|(_, txid, receipt)| (txid, receipt))
+        .collect(),
+      next,
+    ))
+  }
+
   pub(crate) fn brc20_get_tick_transferable_by_address(
     &self,
     tick: &brc20::Tick,
     address: &bitcoin::Address,
   ) -> Result<Vec<brc20::TransferableLog>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20_db = brc20_db::DataStoreReader::new(&rtx);
+    let brc20_db = brc20_db::DataStore::new(&rtx);
     let res = brc20_db.get_transferable_by_tick(&ScriptKey::from_address(address.clone()), tick)?;
 
     Ok(res)
   }
 
+  pub(crate) fn brc20_get_transferable_by_id(
+    &self,
+    address: &bitcoin::Address,
+    inscription_id: &InscriptionId,
+  ) -> Result<Option<brc20::TransferableLog>> {
+    let rtx = self.database.begin_read()?;
+    let brc20_db = brc20_db::DataStore::new(&rtx);
+    let res =
+      brc20_db.get_transferable_by_id(&ScriptKey::from_address(address.clone()), inscription_id)?;
+
+    Ok(res)
+  }
+
   pub(crate) fn brc20_get_all_transferable_by_address(
     &self,
     address: &bitcoin::Address,
   ) -> Result<Vec<brc20::TransferableLog>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20_db = brc20_db::DataStoreReader::new(&rtx);
+    let brc20_db = brc20_db::DataStore::new(&rtx);
     let res = brc20_db.get_transferable(&ScriptKey::from_address(address.clone()))?;
 
     Ok(res)
@@ -1504,7 +1823,7 @@ impl Index {
     limit: Option<usize>,
   ) -> Result<(Vec<brc20s::TickInfo>, usize)> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20s_db = brc20s_db::DataStoreReader::new(&rtx);
+    let brc20s_db = brc20s_db::DataStore::new(&rtx);
     let all_tick = brc20s_db.get_all_tick_info(start, limit)?;
     Ok(all_tick)
   }
@@ -1514,14 +1833,14 @@ impl Index {
     tick_id: &brc20s::TickId,
   ) -> Result<Option<brc20s::TickInfo>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20s_db = brc20s_db::DataStoreReader::new(&rtx);
+    let brc20s_db = brc20s_db::DataStore::new(&rtx);
     let info = brc20s_db.get_tick_info(tick_id)?;
     Ok(info)
   }
 
   pub(crate) fn brc20s_pool_info(&self, pid: &brc20s::Pid) -> Result<Option<brc20s::PoolInfo>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20s_db = brc20s_db::DataStoreReader::new(&rtx);
+    let brc20s_db = brc20s_db::DataStore::new(&rtx);
     let info = brc20s_db.get_pid_to_poolinfo(pid)?;
     Ok(info)
   }
@@ -1531,7 +1850,7 @@ impl Index {
     pledged_tick: &PledgedTick,
   ) -> Result<Option<brc20s::StakeInfo>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20s_db = brc20s_db::DataStoreReader::new(&rtx);
+    let brc20s_db = brc20s_db::DataStore::new(&rtx);
 
     let info =
       brc20s_db.get_user_stakeinfo(&ScriptKey::from_address(address.clone()), pledged_tick)?;
@@ -1544,7 +1863,7 @@ impl Index {
     limit: Option<usize>,
   ) -> Result<(Vec<brc20s::PoolInfo>, usize)> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20s_db = brc20s_db::DataStoreReader::new(&rtx);
+    let brc20s_db = brc20s_db::DataStore::new(&rtx);
     let all_pool = brc20s_db.get_all_poolinfo(start, limit)?;
     Ok(all_pool)
   }
@@ -1554,7 +1873,7 @@ impl Index {
     tick_id: &brc20s::TickId,
   ) -> Result<Vec<brc20s::PoolInfo>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20s_db = brc20s_db::DataStoreReader::new(&rtx);
+    let brc20s_db = brc20s_db::DataStore::new(&rtx);
     let all_pool = brc20s_db.get_all_pools_by_tid(tick_id)?;
     Ok(all_pool)
   }
@@ -1565,7 +1884,7 @@ impl Index {
     address: &bitcoin::Address,
   ) -> Result<Option<brc20s::UserInfo>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20s_db = brc20s_db::DataStoreReader::new(&rtx);
+    let brc20s_db = brc20s_db::DataStore::new(&rtx);
     let info = brc20s_db.get_pid_to_use_info(&ScriptKey::from_address(address.clone()), pid)?;
     Ok(info)
   }
@@ -1576,8 +1895,8 @@ impl Index {
     address: &bitcoin::Address,
   ) -> Result<(Option<String>, Option<String>)> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20s_db = brc20s_db::DataStoreReader::new(&rtx);
-    let brc20_db = brc20_db::DataStoreReader::new(&rtx);
+    let brc20s_db = brc20s_db::DataStore::new(&rtx);
+    let brc20_db = brc20_db::DataStore::new(&rtx);
     let user_info = brc20s_db
       .get_pid_to_use_info(&ScriptKey::from_address(address.clone()), pid)?
       .ok_or(anyhow!("user info not found from state!"))?;
@@ -1609,20 +1928,30 @@ impl Index {
     &self,
     tick_id: &brc20s::TickId,
     address: &bitcoin::Address,
+    height: Option<u64>,
   ) -> Result<Option<brc20s::Balance>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20s_db = brc20s_db::DataStoreReader::new(&rtx);
-    let info = brc20s_db.get_balance(&ScriptKey::from_address(address.clone()), tick_id)?;
+    let brc20s_db = brc20s_db::DataStore::new(&rtx);
+    let script_key = ScriptKey::from_address(address.clone());
+    let info = match height {
+      Some(height) => brc20s_db.get_balance_at_height(&script_key, tick_id, height)?,
+      None => brc20s_db.get_balance(&script_key, tick_id)?,
+    };
     Ok(info)
   }
 
   pub(crate) fn brc20s_all_balance(
     &self,
     address: &bitcoin::Address,
+    height: Option<u64>,
   ) -> Result<Vec<(brc20s::TickId, brc20s::Balance)>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20s_db = brc20s_db::DataStoreReader::new(&rtx);
-    let all_balance = brc20s_db.get_balances(&ScriptKey::from_address(address.clone()))?;
+    let brc20s_db = brc20s_db::DataStore::new(&rtx);
+    let script_key = ScriptKey::from_address(address.clone());
+    let all_balance = match height {
+      Some(height) => brc20s_db.get_balances_at_height(&script_key, height)?,
+      None => brc20s_db.get_balances(&script_key)?,
+    };
     Ok(all_balance)
   }
 
@@ -1632,7 +1961,7 @@ impl Index {
     address: &bitcoin::Address,
   ) -> Result<Vec<brc20s::TransferableAsset>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20s_db = brc20s_db::DataStoreReader::new(&rtx);
+    let brc20s_db = brc20s_db::DataStore::new(&rtx);
 
     let result =
       brc20s_db.get_transferable_by_tickid(&ScriptKey::from_address(address.clone()), tick_id)?;
@@ -1644,27 +1973,23 @@ impl Index {
     address: &bitcoin::Address,
   ) -> Result<Vec<brc20s::TransferableAsset>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20s_db = brc20s_db::DataStoreReader::new(&rtx);
+    let brc20s_db = brc20s_db::DataStore::new(&rtx);
     let info = brc20s_db.get_transferable(&ScriptKey::from_address(address.clone()))?;
     Ok(info)
   }
 
   pub(crate) fn brc20s_txid_receipts(&self, txid: &Txid) -> Result<Option<Vec<brc20s::Receipt>>> {
     let rtx = self.database.begin_read().unwrap();
-    let brc20s_db = brc20s_db::DataStoreReader::new(&rtx);
+    let brc20s_db = brc20s_db::DataStore::new(&rtx);
     let res = brc20s_db.get_txid_to_receipts(txid)?;
 
-    if res.is_empty() {
-      let tx = self.client.get_raw_transaction_info(txid, None)?;
-      if let Some(tx_blockhash) = tx.blockhash {
-        let tx_bh = self.client.get_block_header_info(&tx_blockhash)?;
-        let parsed_height = self.height()?;
-        if parsed_height.is_none() || tx_bh.height as u64 > parsed_height.unwrap().0 {
-          return Ok(None);
-        }
-      } else {
-        return Err(anyhow!("can't get tx block hash: {txid}"));
-      }
+    // transactions without inscription operations are not indexed.
+    if res.is_empty()
+      && ord::DataStore::new(&rtx)
+        .get_transaction_height(txid)?
+        .is_none()
+    {
+      return Ok(None);
     }
 
     Ok(Some(res))
@@ -1675,7 +2000,7 @@ impl Index {
     txs: &Vec<Txid>,
   ) -> Result<Vec<(bitcoin::Txid, Vec<brc20s::Receipt>)>> {
     let rtx = self.database.begin_read()?;
-    let brc20s_db = brc20s_db::DataStoreReader::new(&rtx);
+    let brc20s_db = brc20s_db::DataStore::new(&rtx);
     let mut result = Vec::new();
     for txid in txs {
       let tx_events = brc20s_db.get_txid_to_receipts(txid)?;
@@ -1687,25 +2012,109 @@ impl Index {
     Ok(result)
   }
 
+  /// Returns a page of the BRC20S receipts of the address that match `filter`, newest first,
+  /// and the cursor of the next page if there may be one.
+  pub(crate) fn brc20s_address_receipts(
+    &self,
+    address: &bitcoin::Address,
+    filter: impl Fn(&brc20s::Receipt) -> bool,
+    cursor: Option<u64>,
+    limit: usize,
+  ) -> Result<(Vec<(Txid, brc20s::Receipt)>, Option<u64>)> {
+    let rtx = self.database.begin_read().unwrap();
+    let brc20s_db = brc20s_db::DataStore::new(&rtx);
+    let receipts = brc20s_db.get_address_receipts(
+      &ScriptKey::from_address(address.clone()),
+      cursor,
+      limit,
+      &filter,
+    )?;
+    let next = if receipts.len() == limit {
+      receipts.last().map(|(sequence, ..)| *sequence)
+    } else {
+      None
+    };
+    Ok((
+      receipts
+        .into_iter()
+        .map(|(_, txid, receipt)| (txid, receipt))
+        .collect(),
+      next,
+    ))
+  }
+
+  pub(crate) fn webhook_subscribe(&self, subscription: &webhook::Subscription) -> Result<u64> {
+    let wtx = self.begin_write()?;
+    let id = webhook::add_subscription(&wtx, subscription)?;
+    wtx.commit()?;
+    Ok(id)
+  }
+
+  pub(crate) fn webhook_unsubscribe(&self, id: u64) -> Result<bool> {
+    let wtx = self.begin_write()?;
+    let removed = webhook::remove_subscription(&wtx, id)?;
+    wtx.commit()?;
+    Ok(removed)
+  }
+
+  pub(crate) fn webhook_subscriptions(&self) -> Result<Vec<(u64, webhook::Subscription)>> {
+    webhook::subscriptions(&self.database.begin_read()?)
+  }
+
+  pub(crate) fn webhook_subscription(&self, id: u64) -> Result<Option<webhook::Subscription>> {
+    webhook::subscription(&self.database.begin_read()?, id)
+  }
+
+  pub(crate) fn webhook_deliveries(
+    &self,
+    subscription: u64,
+    start: usize,
+    limit: Option<usize>,
+  ) -> Result<(Vec<(u64, webhook::Delivery)>, usize)> {
+    let deliveries = webhook::deliveries(&self.database.begin_read()?, subscription)?;
+    let total = deliveries.len();
+    Ok((
+      deliveries
+        .into_iter()
+        .skip(start)
+        .take(limit.unwrap_or(usize::MAX))
+        .collect(),
+      total,
+    ))
+  }
+
+  pub(crate) fn webhook_due_deliveries(&self, now: u64) -> Result<Vec<(u64, webhook::Delivery)>> {
+    webhook::due_deliveries(&self.database.begin_read()?, now)
+  }
+
+  pub(crate) fn webhook_record_attempt(
+    &self,
+    id: u64,
+    now: u64,
+    payload: Option<String>,
+    error: Option<String>,
+  ) -> Result {
+    let wtx = self.begin_write()?;
+    webhook::record_attempt(&wtx, id, now, payload, error)?;
+    wtx.commit()?;
+    Ok(())
+  }
+
   pub(crate) fn ord_txid_inscriptions(
     &self,
     txid: &Txid,
   ) -> Result<Option<Vec<ord::InscriptionOp>>> {
     let rtx = self.database.begin_read().unwrap();
-    let ord_db = ord::OrdDbReader::new(&rtx);
+    let ord_db = ord::DataStore::new(&rtx);
     let res = ord_db.get_transaction_operations(txid)?;
 
-    if res.is_empty() {
-      let tx = self.client.get_raw_transaction_info(txid, None)?;
-      if let Some(tx_blockhash) = tx.blockhash {
-        let tx_bh = self.client.get_block_header_info(&tx_blockhash)?;
-        let parsed_height = self.height()?;
-        if parsed_height.is_none() || tx_bh.height as u64 > parsed_height.unwrap().0 {
-          return Ok(None);
-        }
-      } else {
-        return Err(anyhow!("can't get tx block hash: {txid}"));
-      }
+    // transactions without inscription operations are not indexed.
+    if res.is_empty()
+      && ord::DataStore::new(&rtx)
+        .get_transaction_height(txid)?
+        .is_none()
+    {
+      return Ok(None);
     }
 
     Ok(Some(res))
@@ -1715,7 +2124,7 @@ impl Index {
     txs: &Vec<Txid>,
   ) -> Result<Vec<(bitcoin::Txid, Vec<ord::InscriptionOp>)>> {
     let rtx = self.database.begin_read()?;
-    let ord_db = ord::OrdDbReader::new(&rtx);
+    let ord_db = ord::DataStore::new(&rtx);
     let mut result = Vec::new();
     for txid in txs {
       let inscriptions = ord_db.get_transaction_operations(txid)?;
@@ -2868,7 +3277,7 @@ mod tests {
   }
 
   #[test]
-  fn new_schema_gives_correct_error() {
+  fn baseline_schema_requires_migration() {
     let tempdir = {
       let context = Context::builder().build();
 
@@ -2877,7 +3286,7 @@ mod tests {
       wtx
         .open_table(STATISTIC_TO_COUNT)
         .unwrap()
-        .insert(&Statistic::Schema.key(), &u64::MAX)
+        .insert(&Statistic::Schema.key(), &migrate::BASELINE_SCHEMA_VERSION)
         .unwrap();
 
       wtx.commit().unwrap();
@@ -2891,23 +3300,50 @@ mod tests {
 
     assert_eq!(
       Context::builder().tempdir(tempdir).try_build().err().unwrap().to_string(),
-      format!("index at `{}{delimiter}regtest{delimiter}index.redb` appears to have been built with a newer, incompatible version of ord, consider updating ord: index schema {}, ord schema {SCHEMA_VERSION}", path.display(), u64::MAX));
+      format!("index at `{}{delimiter}regtest{delimiter}index.redb` needs to be migrated with `ord index migrate` before it can be used: index schema {}, ord schema {SCHEMA_VERSION}", path.display(), migrate::BASELINE_SCHEMA_VERSION));
   }
 
   #[test]
-  fn inscriptions_on_output() {
-    for context in Context::configurations() {
-      context.mine_blocks(1);
+  fn new_schema_gives_correct_error() {
+    let tempdir = {
+      let context = Context::builder().build();
 
-      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
-        inputs: &[(1, 0, 0, inscription("text/plain", "hello").to_witness())],
-        ..Default::default()
-      });
+      let wtx = context.index.database.begin_write().unwrap();
 
-      let inscription_id = InscriptionId { txid, index: 0 };
+      wtx
+        .open_table(STATISTIC_TO_COUNT)
+        .unwrap()
+        .insert(&Statistic::Schema.key(), &u64::MAX)
+        .unwrap();
 
-      assert_eq!(
-        context
+      wtx.commit().unwrap();
+
+      context.tempdir
+    };
+
+    let path = tempdir.path().to_owned();
+
+    let delimiter = if cfg!(windows) { '\\' } else { '/' };
+
+    assert_eq!(
+      Context::builder().tempdir(tempdir).try_build().err().unwrap().to_string(),
+      format!("index at `{}{delimiter}regtest{delimiter}index.redb` appears to have been built with a newer, incompatible version of ord, consider updating ord: index schema {}, ord schema {SCHEMA_VERSION}", path.display(), u64::MAX));
+  }
+
+  #[test]
+  fn inscriptions_on_output() {
+    for context in Context::configurations() {
+      context.mine_blocks(1);
+
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(1, 0, 0, inscription("text/plain", "hello").to_witness())],
+        ..Default::default()
+      });
+
+      let inscription_id = InscriptionId { txid, index: 0 };
+
+      assert_eq!(
+        context
           .index
           .get_inscriptions_on_output(OutPoint { txid, vout: 0 })
           .unwrap(),
@@ -4171,6 +4607,899 @@ mod tests {
     }
   }
 
+  #[test]
+  fn recover_from_15_block_deep_reorg_with_undo_logs() {
+    for mut context in Context::configurations() {
+      context.index.set_durability(redb::Durability::Immediate);
+
+      context.mine_blocks(1);
+
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(
+          1,
+          0,
+          0,
+          inscription("text/plain;charset=utf-8", "hello").to_witness(),
+        )],
+        ..Default::default()
+      });
+      let first_id = InscriptionId { txid, index: 0 };
+      let first_location = SatPoint {
+        outpoint: OutPoint { txid, vout: 0 },
+        offset: 0,
+      };
+
+      context.mine_blocks(1);
+
+      let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(
+          2,
+          0,
+          0,
+          inscription("text/plain;charset=utf-8", "hello").to_witness(),
+        )],
+        ..Default::default()
+      });
+      let second_id = InscriptionId { txid, index: 0 };
+      let second_outpoint = OutPoint { txid, vout: 0 };
+
+      context.mine_blocks(15);
+
+      if context.index.has_sat_index().unwrap() {
+        assert_eq!(
+          context.index.list(second_outpoint).unwrap().unwrap(),
+          List::Unspent(vec![(100 * COIN_VALUE, 150 * COIN_VALUE)])
+        );
+      }
+
+      for _ in 0..15 {
+        context.rpc_server.invalidate_tip();
+      }
+
+      context.mine_blocks(16);
+
+      context.index.assert_non_existence_of_inscription(second_id);
+
+      context
+        .index
+        .assert_inscription_location(first_id, first_location, Some(50 * COIN_VALUE));
+
+      if context.index.has_sat_index().unwrap() {
+        assert_eq!(
+          context
+            .index
+            .list(first_location.outpoint)
+            .unwrap()
+            .unwrap(),
+          List::Unspent(vec![(50 * COIN_VALUE, 100 * COIN_VALUE)])
+        );
+        assert!(!matches!(
+          context.index.list(second_outpoint).unwrap(),
+          Some(List::Unspent(_))
+        ));
+      }
+    }
+  }
+
+  #[test]
+  fn committed_blocks_inscriptions_and_reorgs_are_notified() {
+    let context = Context::builder().build();
+    let mut receiver = context.index.subscribe();
+
+    let blocks = context.mine_blocks(1);
+    assert!(matches!(
+      &*receiver.try_recv().unwrap(),
+      Notification::BlockConnected { height: 1, blockhash } if *blockhash == blocks[0].block_hash()
+    ));
+    assert!(receiver.try_recv().is_err());
+
+    let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(
+        1,
+        0,
+        0,
+        inscription("text/plain;charset=utf-8", "hello").to_witness(),
+      )],
+      ..Default::default()
+    });
+    context.mine_blocks(1);
+
+    assert!(matches!(
+      &*receiver.try_recv().unwrap(),
+      Notification::Inscription { height: 2, op } if op.txid == txid
+    ));
+    assert!(matches!(
+      &*receiver.try_recv().unwrap(),
+      Notification::BlockConnected { height: 2, .. }
+    ));
+
+    context.rpc_server.invalidate_tip();
+    context.mine_blocks(2);
+
+    assert!(matches!(
+      &*receiver.try_recv().unwrap(),
+      Notification::Reorg {
+        height: 2,
+        depth: 1
+      }
+    ));
+    assert!(matches!(
+      &*receiver.try_recv().unwrap(),
+      Notification::BlockConnected { height: 2, .. }
+    ));
+    assert!(matches!(
+      &*receiver.try_recv().unwrap(),
+      Notification::BlockConnected { height: 3, .. }
+    ));
+    assert!(receiver.try_recv().is_err());
+  }
+
+  #[test]
+  fn verify_re_executes_stored_operations() {
+    let context = Context::builder()
+      .args(["--enable-index-brc20", "--enable-save-ord-receipts"])
+      .build();
+
+    context.mine_blocks(1);
+
+    for (height, content) in [
+      (
+        1,
+        r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"1000","lim":"100"}"#,
+      ),
+      (2, r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"100"}"#),
+      (
+        3,
+        r#"{"p":"brc-20","op":"transfer","tick":"ordi","amt":"40"}"#,
+      ),
+    ] {
+      context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(
+          height,
+          0,
+          0,
+          inscription("text/plain;charset=utf-8", content).to_witness(),
+        )],
+        ..Default::default()
+      });
+      context.mine_blocks(1);
+    }
+
+    context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(4, 1, 0, Default::default())],
+      ..Default::default()
+    });
+    context.mine_blocks(1);
+
+    let verification = context.index.verify().unwrap();
+    assert_eq!(verification.heights, 4);
+    assert_eq!(verification.transactions, 4);
+    assert_eq!(verification.mismatches, Vec::new());
+
+    let tick = brc20::Tick::from_str("ordi").unwrap();
+    let wtx = context.index.database.begin_write().unwrap();
+    let key = wtx
+      .open_table(brc20_db::BRC20_BALANCES)
+      .unwrap()
+      .iter()
+      .unwrap()
+      .next()
+      .unwrap()
+      .unwrap()
+      .0
+      .value()
+      .to_vec();
+    wtx
+      .open_table(brc20_db::BRC20_BALANCES)
+      .unwrap()
+      .remove(key.as_slice())
+      .unwrap();
+    wtx.commit().unwrap();
+
+    let mismatches = context.index.verify().unwrap().mismatches;
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].table, "BRC20_BALANCES");
+    assert_eq!(mismatches[0].key, hex::encode(&key));
+    assert_eq!(mismatches[0].tick, Some(tick.to_string()));
+    assert!(mismatches[0].address.is_some());
+    assert_eq!(mismatches[0].indexed, None);
+    assert!(mismatches[0].verified.is_some());
+  }
+
+  #[test]
+  fn transfers_of_inscriptions_inscribed_in_the_same_block_are_resolved_after_the_inscription() {
+    let context = Context::builder().arg("--enable-index-brc20").build();
+
+    context.mine_blocks(1);
+
+    for (height, content) in [
+      (
+        1,
+        r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"1000","lim":"100"}"#,
+      ),
+      (2, r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"100"}"#),
+    ] {
+      context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(
+          height,
+          0,
+          0,
+          inscription("text/plain;charset=utf-8", content).to_witness(),
+        )],
+        ..Default::default()
+      });
+      context.mine_blocks(1);
+    }
+
+    let inscribe_transfer = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(
+        3,
+        0,
+        0,
+        inscription(
+          "text/plain;charset=utf-8",
+          r#"{"p":"brc-20","op":"transfer","tick":"ordi","amt":"40"}"#,
+        )
+        .to_witness(),
+      )],
+      ..Default::default()
+    });
+
+    let transfer = Client::new(&context.rpc_server.url(), bitcoincore_rpc::Auth::None)
+      .unwrap()
+      .send_raw_transaction(&Transaction {
+        version: 2,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: vec![TxIn {
+          previous_output: OutPoint::new(inscribe_transfer, 0),
+          script_sig: ScriptBuf::new(),
+          sequence: Sequence::MAX,
+          witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+          value: 50 * COIN_VALUE,
+          script_pubkey: ScriptBuf::new(),
+        }],
+      })
+      .unwrap();
+
+    context.mine_blocks(1);
+
+    let receipts = context
+      .index
+      .brc20_get_tx_events_by_txid(&transfer)
+      .unwrap()
+      .unwrap();
+    assert_eq!(receipts.len(), 1);
+    assert_eq!(receipts[0].op, brc20::OperationType::Transfer);
+    assert!(receipts[0].result.is_ok());
+  }
+
+  #[test]
+  fn deploys_without_decimals_default_to_the_configured_max_decimal_width() {
+    let config = TempDir::new().unwrap();
+    let path = config.path().join("ord.yaml");
+    fs::write(
+      &path,
+      "
+hidden:
+protocols:
+  regtest:
+    brc20:
+      versions:
+      - height: 0
+        rules:
+          max_decimal_width: 8
+          max_supply: 18446744073709551615
+",
+    )
+    .unwrap();
+
+    let context = Context::builder()
+      .args(["--enable-index-brc20", "--config", path.to_str().unwrap()])
+      .build();
+
+    context.mine_blocks(1);
+    let txid = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(
+        1,
+        0,
+        0,
+        inscription(
+          "text/plain;charset=utf-8",
+          r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"21000000","lim":"1000"}"#,
+        )
+        .to_witness(),
+      )],
+      ..Default::default()
+    });
+    context.mine_blocks(1);
+
+    assert!(
+      context.index.brc20_get_txs_events(&vec![txid]).unwrap()[0].1[0]
+        .result
+        .is_ok()
+    );
+    assert_eq!(
+      context
+        .index
+        .brc20_get_tick_info(&brc20::Tick::from_str("ordi").unwrap())
+        .unwrap()
+        .unwrap()
+        .decimal,
+      8
+    );
+  }
+
+  #[test]
+  fn self_minted_ticks_are_indexed_from_their_activation_height() {
+    let config = TempDir::new().unwrap();
+    let path = config.path().join("ord.yaml");
+    fs::write(
+      &path,
+      "
+hidden:
+protocols:
+  regtest:
+    brc20:
+      versions:
+      - height: 0
+        rules:
+          max_decimal_width: 18
+          max_supply: 18446744073709551615
+      - height: 4
+        rules:
+          max_decimal_width: 18
+          max_supply: 18446744073709551615
+          allow_self_mint: true
+",
+    )
+    .unwrap();
+
+    let context = Context::builder()
+      .args(["--enable-index-brc20", "--config", path.to_str().unwrap()])
+      .build();
+
+    let inscribe = |context: &Context, input: (usize, usize, usize), inscription: Inscription| {
+      context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(input.0, input.1, input.2, inscription.to_witness())],
+        ..Default::default()
+      })
+    };
+
+    let result = |context: &Context, txid: Txid| {
+      context.index.brc20_get_txs_events(&vec![txid]).unwrap()[0].1[0]
+        .result
+        .clone()
+    };
+
+    context.mine_blocks(1);
+
+    let early = inscribe(
+      &context,
+      (1, 0, 0),
+      inscription(
+        "text/plain;charset=utf-8",
+        r#"{"p":"brc-20","op":"deploy","tick":"smoll","max":"0","lim":"1000","self_mint":"true"}"#,
+      ),
+    );
+    context.mine_blocks(2);
+
+    assert_eq!(
+      result(&context, early),
+      Err(brc20::BRC20Error::InvalidTickLen("smoll".into()))
+    );
+
+    let without_self_mint = inscribe(
+      &context,
+      (2, 0, 0),
+      inscription(
+        "text/plain;charset=utf-8",
+        r#"{"p":"brc-20","op":"deploy","tick":"smoll","max":"0","lim":"1000"}"#,
+      ),
+    );
+    context.mine_blocks(1);
+
+    assert_eq!(
+      result(&context, without_self_mint),
+      Err(brc20::BRC20Error::SelfMintRequired("smoll".into()))
+    );
+
+    let deploy = inscribe(
+      &context,
+      (3, 0, 0),
+      inscription(
+        "text/plain;charset=utf-8",
+        r#"{"p":"brc-20","op":"deploy","tick":"smoll","max":"0","lim":"1000","self_mint":"true"}"#,
+      ),
+    );
+    context.mine_blocks(1);
+
+    assert!(result(&context, deploy).is_ok());
+
+    let token_info = context
+      .index
+      .brc20_get_tick_info(&brc20::Tick::from_str("smoll").unwrap())
+      .unwrap()
+      .unwrap();
+    assert_eq!(token_info.supply, u128::from(u64::MAX) * 10u128.pow(18));
+
+    let orphan = inscribe(
+      &context,
+      (5, 0, 0),
+      inscription(
+        "text/plain;charset=utf-8",
+        r#"{"p":"brc-20","op":"mint","tick":"smoll","amt":"1000"}"#,
+      ),
+    );
+    context.mine_blocks(1);
+
+    assert_eq!(
+      result(&context, orphan),
+      Err(brc20::BRC20Error::SelfMintPermissionDenied("smoll".into()))
+    );
+
+    // the parent is spent by a second input, so that the mint is not a reinscription.
+    let child = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[
+        (
+          6,
+          0,
+          0,
+          Inscription {
+            content_type: Some("text/plain;charset=utf-8".into()),
+            body: Some(r#"{"p":"brc-20","op":"mint","tick":"smoll","amt":"1000"}"#.into()),
+            parent: Some(
+              InscriptionId {
+                txid: deploy,
+                index: 0,
+              }
+              .parent_value(),
+            ),
+            unrecognized_even_field: false,
+          }
+          .to_witness(),
+        ),
+        (5, 1, 0, Default::default()),
+      ],
+      ..Default::default()
+    });
+    context.mine_blocks(1);
+
+    assert!(result(&context, child).is_ok());
+  }
+
+  #[test]
+  fn verify_requires_saved_operations() {
+    let context = Context::builder().arg("--enable-index-brc20").build();
+
+    assert_eq!(
+      context.index.verify().unwrap_err().to_string(),
+      "index verify requires an index built with --enable-save-ord-receipts"
+    );
+  }
+
+  #[test]
+  fn snapshot_restores_index_that_keeps_indexing() {
+    let context = Context::builder().arg("--enable-index-brc20").build();
+
+    context.mine_blocks(1);
+
+    for (height, content) in [
+      (
+        1,
+        r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"1000","lim":"100"}"#,
+      ),
+      (2, r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"100"}"#),
+    ] {
+      context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(
+          height,
+          0,
+          0,
+          inscription("text/plain;charset=utf-8", content).to_witness(),
+        )],
+        ..Default::default()
+      });
+      context.mine_blocks(1);
+    }
+
+    let file = context.tempdir.path().join("snapshot");
+    let exported = snapshot::export(
+      &context.index.database,
+      Chain::Regtest,
+      &ProtocolInfo::state_tables(&context.index.protocol_config),
+      &file,
+    )
+    .unwrap();
+    assert_eq!(exported.header.height, 3);
+    assert_eq!(
+      Some(exported.header.blockhash),
+      context.index.block_hash(None).unwrap()
+    );
+
+    let mut options = context.options.clone();
+    options.index = Some(context.tempdir.path().join("restored").join("index.redb"));
+
+    let imported = Index::import_snapshot(&options, &file).unwrap();
+    assert_eq!(imported, exported);
+
+    assert_eq!(
+      Index::import_snapshot(&options, &file)
+        .unwrap_err()
+        .to_string(),
+      format!(
+        "refusing to import snapshot over existing index at `{}`",
+        options.index.as_ref().unwrap().display()
+      )
+    );
+
+    let restored = Index::open(&options).unwrap();
+    assert_eq!(restored.block_count().unwrap(), 4);
+    assert_eq!(
+      restored.state_hash(None).unwrap(),
+      context.index.state_hash(None).unwrap()
+    );
+
+    context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(
+        3,
+        0,
+        0,
+        inscription(
+          "text/plain;charset=utf-8",
+          r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"100"}"#,
+        )
+        .to_witness(),
+      )],
+      ..Default::default()
+    });
+    context.mine_blocks(1);
+    restored.update().unwrap();
+
+    assert_eq!(restored.block_count().unwrap(), 5);
+    assert_eq!(
+      restored.state_hash(None).unwrap(),
+      context.index.state_hash(None).unwrap()
+    );
+  }
+
+  #[test]
+  fn state_hashes_do_not_depend_on_when_cached_state_is_written_back() {
+    let context = Context::builder().arg("--enable-index-brc20").build();
+
+    context.mine_blocks(1);
+
+    for (height, content) in [
+      (
+        1,
+        r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"1000","lim":"100"}"#,
+      ),
+      (2, r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"100"}"#),
+      (3, r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"50"}"#),
+    ] {
+      context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(
+          height,
+          0,
+          0,
+          inscription("text/plain;charset=utf-8", content).to_witness(),
+        )],
+        ..Default::default()
+      });
+      context.mine_blocks(1);
+    }
+
+    context.mine_blocks(5);
+
+    // indexed in a single batch, with only the tip journaled, so the cached state of all other
+    // blocks is written back when the batch is committed.
+    let mut options = context.options.clone();
+    options.index = Some(context.tempdir.path().join("batched").join("index.redb"));
+    options.max_reorg_depth = Some(1);
+    let batched = Index::open(&options).unwrap();
+    batched.update().unwrap();
+
+    for height in 0..=9 {
+      assert_eq!(
+        batched.state_hash(Some(height)).unwrap(),
+        context.index.state_hash(Some(height)).unwrap()
+      );
+    }
+  }
+
+  #[test]
+  fn simulations_do_not_wait_for_the_writer() {
+    let context = Context::builder().arg("--enable-index-brc20").build();
+    context.mine_blocks(1);
+
+    let inscription = inscription(
+      "text/plain;charset=utf-8",
+      r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"1000","lim":"100"}"#,
+    );
+    let script_pubkey = ScriptBuf::new_v0_p2wpkh(&bitcoin::WPubkeyHash::all_zeros());
+
+    let wtx = context.index.begin_write().unwrap();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    thread::scope(|scope| {
+      scope.spawn(|| {
+        sender
+          .send(
+            context
+              .index
+              .simulate_inscribe(&inscription, None, &script_pubkey),
+          )
+          .unwrap();
+      });
+
+      let simulation = receiver.recv_timeout(Duration::from_secs(10));
+      // let a simulation that waits for the writer finish, so the failure is reported.
+      drop(wtx);
+
+      let simulation = simulation
+        .expect("simulation waited for the writer")
+        .unwrap();
+      assert_eq!(simulation.height, 2);
+      assert!(simulation.transactions.last().unwrap().brc20_receipts[0]
+        .result
+        .is_ok());
+    });
+
+    assert_eq!(context.index.brc20_get_all_tick_info().unwrap().len(), 0);
+  }
+
+  #[test]
+  fn snapshot_exports_earlier_height_from_undo_log() {
+    let mut context = Context::builder().arg("--enable-index-brc20").build();
+    context.index.set_durability(redb::Durability::Immediate);
+
+    context.mine_blocks(1);
+
+    for (height, content) in [
+      (
+        1,
+        r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"1000","lim":"100"}"#,
+      ),
+      (2, r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"100"}"#),
+    ] {
+      context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(
+          height,
+          0,
+          0,
+          inscription("text/plain;charset=utf-8", content).to_witness(),
+        )],
+        ..Default::default()
+      });
+      context.mine_blocks(1);
+    }
+
+    let file = context.tempdir.path().join("snapshot");
+    let exported = Index::export_snapshot(&context.options, Some(2), &file).unwrap();
+    assert_eq!(exported.header.height, 2);
+    assert_eq!(
+      Some(exported.header.blockhash),
+      context.index.block_hash(Some(2)).unwrap()
+    );
+    assert_eq!(context.index.block_count().unwrap(), 4);
+
+    assert_eq!(
+      Index::export_snapshot(&context.options, Some(4), &file)
+        .unwrap_err()
+        .to_string(),
+      "cannot snapshot height 4 above the tip 3"
+    );
+
+    let mut options = context.options.clone();
+    options.index = Some(context.tempdir.path().join("restored").join("index.redb"));
+    Index::import_snapshot(&options, &file).unwrap();
+
+    let restored = Index::open(&options).unwrap();
+    assert_eq!(restored.block_count().unwrap(), 3);
+    assert_eq!(
+      restored.state_hash(None).unwrap(),
+      context.index.state_hash(Some(2)).unwrap()
+    );
+
+    restored.update().unwrap();
+    assert_eq!(restored.block_count().unwrap(), 4);
+    assert_eq!(
+      restored.state_hash(None).unwrap(),
+      context.index.state_hash(None).unwrap()
+    );
+  }
+
+  #[test]
+  fn snapshot_import_rejects_corrupted_archive() {
+    let context = Context::builder().arg("--enable-index-brc20").build();
+
+    let file = context.tempdir.path().join("snapshot");
+    snapshot::export(
+      &context.index.database,
+      Chain::Regtest,
+      &ProtocolInfo::state_tables(&context.index.protocol_config),
+      &file,
+    )
+    .unwrap();
+
+    let mut archive = fs::read(&file).unwrap();
+    archive[20] ^= 1;
+    fs::write(&file, archive).unwrap();
+
+    let mut options = context.options.clone();
+    options.index = Some(context.tempdir.path().join("restored.redb"));
+
+    assert_eq!(
+      Index::import_snapshot(&options, &file)
+        .unwrap_err()
+        .to_string(),
+      format!(
+        "snapshot `{}` is corrupted: checksum mismatch",
+        file.display()
+      )
+    );
+    assert!(!options.index.unwrap().exists());
+  }
+
+  #[test]
+  fn mempool_overlay_follows_pending_transactions() {
+    let context = Context::builder().arg("--enable-index-brc20").build();
+
+    context.mine_blocks(1);
+
+    for (height, content) in [
+      (
+        1,
+        r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"1000","lim":"100"}"#,
+      ),
+      (2, r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"100"}"#),
+      (
+        3,
+        r#"{"p":"brc-20","op":"transfer","tick":"ordi","amt":"40"}"#,
+      ),
+    ] {
+      context.rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(
+          height,
+          0,
+          0,
+          inscription("text/plain;charset=utf-8", content).to_witness(),
+        )],
+        // leaves an output without inscriptions to spend.
+        outputs: 2,
+        ..Default::default()
+      });
+      context.mine_blocks(1);
+    }
+
+    assert!(context.index.pending().is_none());
+
+    let tick = brc20::Tick::from_str("ordi").unwrap();
+    let script_key = ScriptKey::from_script(&ScriptBuf::new(), Network::Regtest);
+    let transfer_inscription = InscriptionId {
+      txid: context.rpc_server.tx(4, 1).txid(),
+      index: 0,
+    };
+
+    let mint = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(
+        4,
+        0,
+        0,
+        inscription(
+          "text/plain;charset=utf-8",
+          r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"50"}"#,
+        )
+        .to_witness(),
+      )],
+      ..Default::default()
+    });
+    let spend = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(4, 1, 0, Default::default())],
+      ..Default::default()
+    });
+
+    context.index.update_mempool().unwrap();
+
+    let overlay = context.index.pending().unwrap();
+    assert_eq!(overlay.height, 5);
+    let pending = overlay.brc20(script_key.clone(), &tick).unwrap();
+    let balance = pending.balance.as_ref().unwrap();
+    assert_eq!(balance.overall_balance, 150_000_000_000_000_000_000);
+    assert_eq!(balance.transferable_balance, 0);
+    assert!(pending.transferable.is_empty());
+    assert_eq!(pending.txids.len(), 2);
+    assert!(pending.txids.contains(&mint));
+    assert!(pending.txids.contains(&spend));
+    assert_eq!(
+      overlay.spend(&transfer_inscription),
+      Some(&mempool::PendingSpend {
+        txid: spend,
+        replaced: Vec::new(),
+      })
+    );
+
+    let second_mint = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(
+        2,
+        1,
+        1,
+        inscription(
+          "text/plain;charset=utf-8",
+          r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"25"}"#,
+        )
+        .to_witness(),
+      )],
+      ..Default::default()
+    });
+
+    context.index.update_mempool().unwrap();
+
+    let overlay = context.index.pending().unwrap();
+    let pending = overlay.brc20(script_key.clone(), &tick).unwrap();
+    assert_eq!(
+      pending.balance.as_ref().unwrap().overall_balance,
+      175_000_000_000_000_000_000
+    );
+    assert_eq!(pending.txids.len(), 3);
+    assert!(pending.txids.contains(&second_mint));
+    assert!(overlay.transaction(&mint).is_some());
+    assert_eq!(overlay.spend(&transfer_inscription).unwrap().txid, spend);
+
+    context.rpc_server.evict_tx(spend);
+    let replacement = context.rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(4, 1, 0, Default::default())],
+      fee: 1,
+      ..Default::default()
+    });
+
+    context.index.update_mempool().unwrap();
+
+    let overlay = context.index.pending().unwrap();
+    assert_eq!(
+      overlay.spend(&transfer_inscription),
+      Some(&mempool::PendingSpend {
+        txid: replacement,
+        replaced: vec![spend],
+      })
+    );
+    let txids = &overlay.brc20(script_key.clone(), &tick).unwrap().txids;
+    assert_eq!(txids.len(), 3);
+    assert!(txids.contains(&mint));
+    assert!(txids.contains(&second_mint));
+    assert!(txids.contains(&replacement));
+
+    context.mine_blocks(1);
+    context.index.update_mempool().unwrap();
+
+    let overlay = context.index.pending().unwrap();
+    assert_eq!(overlay.height, 6);
+    assert!(overlay.brc20(script_key, &tick).is_none());
+    assert!(overlay.spend(&transfer_inscription).is_none());
+  }
+
+  #[test]
+  fn reorg_deeper_than_max_reorg_depth_is_unrecoverable() {
+    let context = Context::builder().args(["--max-reorg-depth", "5"]).build();
+
+    context.mine_blocks(10);
+
+    for _ in 0..7 {
+      context.rpc_server.invalidate_tip();
+    }
+
+    context.rpc_server.mine_blocks(8);
+
+    assert_eq!(
+      context
+        .index
+        .update()
+        .unwrap_err()
+        .downcast_ref::<ReorgError>(),
+      Some(&ReorgError::Unrecoverable)
+    );
+  }
+
   #[test]
   fn inscription_without_parent_tag_has_no_parent_entry() {
     for context in Context::configurations() {
@@ -4474,4 +5803,100 @@ mod tests {
         .is_none());
     }
   }
+
+  #[test]
+  fn blocking_fetcher_returns_transactions_in_order() {
+    let context = Context::builder().build();
+    let txids = context
+      .mine_blocks(3)
+      .iter()
+      .rev()
+      .map(|block| block.txdata[0].txid())
+      .collect::<Vec<Txid>>();
+
+    let fetcher = fetcher::BlockingFetcher::spawn(&context.options).unwrap();
+    assert_eq!(
+      fetcher
+        .get_transactions(txids.clone())
+        .unwrap()
+        .iter()
+        .map(Transaction::txid)
+        .collect::<Vec<Txid>>(),
+      txids
+    );
+    assert_eq!(fetcher.get_transactions(Vec::new()).unwrap(), Vec::new());
+  }
+
+  #[test]
+  fn blocks_below_the_tip_are_read_from_blk_files() {
+    let bitcoin_data_dir = TempDir::new().unwrap();
+    let context = Context::builder()
+      .arg("--read-blk-files")
+      .arg("--bitcoin-data-dir")
+      .arg(bitcoin_data_dir.path())
+      .build();
+
+    let mut blocks = vec![bitcoin::blockdata::constants::genesis_block(
+      Network::Regtest,
+    )];
+    blocks.extend(context.rpc_server.mine_blocks(10));
+
+    write_blk_file(bitcoin_data_dir.path(), &blocks, None);
+
+    context.index.update().unwrap();
+
+    assert_eq!(context.index.block_count().unwrap(), 11);
+    for (height, block) in blocks.iter().enumerate() {
+      assert_eq!(
+        context
+          .index
+          .block_hash(Some(u64::try_from(height).unwrap()))
+          .unwrap(),
+        Some(block.block_hash())
+      );
+    }
+  }
+
+  #[test]
+  fn blocks_that_cannot_be_read_from_blk_files_are_fetched_over_rpc() {
+    let bitcoin_data_dir = TempDir::new().unwrap();
+    let context = Context::builder()
+      .arg("--read-blk-files")
+      .arg("--bitcoin-data-dir")
+      .arg(bitcoin_data_dir.path())
+      .build();
+
+    let mut blocks = vec![bitcoin::blockdata::constants::genesis_block(
+      Network::Regtest,
+    )];
+    blocks.extend(context.rpc_server.mine_blocks(10));
+
+    write_blk_file(bitcoin_data_dir.path(), &blocks, Some(2));
+
+    context.index.update().unwrap();
+
+    assert_eq!(context.index.block_count().unwrap(), 11);
+    assert_eq!(
+      context.index.block_hash(Some(2)).unwrap(),
+      Some(blocks[2].block_hash())
+    );
+  }
+
+  // writes `blocks` in reverse order to the blk file of regtest, with the transactions of the block
+  // at `corrupt_height` overwritten.
+  fn write_blk_file(bitcoin_data_dir: &Path, blocks: &[Block], corrupt_height: Option<usize>) {
+    let mut blk_file = Vec::new();
+    for (height, block) in blocks.iter().enumerate().rev() {
+      let mut block = consensus::serialize(block);
+      if corrupt_height == Some(height) {
+        block[80..].fill(0xff);
+      }
+      blk_file.extend_from_slice(&Network::Regtest.magic().to_bytes());
+      blk_file.extend_from_slice(&u32::try_from(block.len()).unwrap().to_le_bytes());
+      blk_file.extend_from_slice(&block);
+    }
+    let blocks_dir = bitcoin_data_dir.join("regtest").join("blocks");
+    fs::create_dir_all(&blocks_dir).unwrap();
+    fs::write(blocks_dir.join("blk00000.dat"), blk_file).unwrap();
+  }
 }
@@ -0,0 +1,283 @@
+use {
+  super::*,
+  std::io::{BufReader, Read, Seek, SeekFrom},
+};
+
+/// The number of blocks below the chain tip that are fetched over RPC rather than read from the
+/// blk files, since the last blk file is still being written and recent blocks can be reorged.
+pub(crate) const RPC_TIP_DISTANCE: u64 = 6;
+
+const XOR_KEY_LEN: usize = 8;
+
+// the magic and the size of a block, followed by its header.
+const RECORD_PREFIX_LEN: usize = 8;
+const HEADER_LEN: usize = 80;
+
+// where a block is stored in the blk files.
+#[derive(Debug, Clone, Copy)]
+struct Location {
+  file: u32,
+  offset: u64,
+  len: u32,
+}
+
+/// Reads the blocks of the best chain from the `blk*.dat` files of Bitcoin Core.
+///
+/// Bitcoin Core appends blocks to its blk files as it receives them, which is not in height order,
+/// so the headers of all the blocks are scanned first, and the chain is ordered by following the
+/// previous block hashes back from a tip. Files written with `-blocksxor` are de-obfuscated with
+/// the key in `xor.dat`.
+pub(crate) struct BlkReader {
+  blocks_dir: PathBuf,
+  xor_key: [u8; XOR_KEY_LEN],
+  chain: Vec<Location>,
+}
+
+impl BlkReader {
+  /// Scans the blk files in `blocks_dir` and orders the blocks of the chain ending in `tip`.
+  pub(crate) fn open(blocks_dir: &Path, network: Network, tip: BlockHash) -> Result<Self> {
+    let xor_key = match fs::read(blocks_dir.join("xor.dat")) {
+      Ok(key) => key
+        .try_into()
+        .map_err(|_| anyhow!("invalid xor key in {}", blocks_dir.display()))?,
+      Err(err) if err.kind() == io::ErrorKind::NotFound => [0; XOR_KEY_LEN],
+      Err(err) => return Err(err.into()),
+    };
+
+    let mut reader = Self {
+      blocks_dir: blocks_dir.into(),
+      xor_key,
+      chain: Vec::new(),
+    };
+
+    let magic = network.magic().to_bytes();
+    let mut headers = HashMap::new();
+    for file in 0.. {
+      let path = reader.path(file);
+      if !path.exists() {
+        break;
+      }
+      reader
+        .scan(file, magic, &mut headers)
+        .with_context(|| format!("failed to scan {}", path.display()))?;
+    }
+
+    let mut hash = tip;
+    loop {
+      let (prev_blockhash, location) = headers
+        .get(&hash)
+        .ok_or_else(|| anyhow!("block {hash} not found in {}", blocks_dir.display()))?;
+      reader.chain.push(*location);
+      if *prev_blockhash == BlockHash::all_zeros() {
+        break;
+      }
+      hash = *prev_blockhash;
+    }
+    reader.chain.reverse();
+
+    Ok(reader)
+  }
+
+  /// The number of blocks of the chain, which are those below this height.
+  pub(crate) fn block_count(&self) -> u64 {
+    u64::try_from(self.chain.len()).unwrap()
+  }
+
+  pub(crate) fn get_block(&self, height: u64) -> Result<Option<Block>> {
+    self
+      .location(height)
+      .map(|location| Ok(consensus::deserialize(&self.read(location)?)?))
+      .transpose()
+  }
+
+  pub(crate) fn get_header(&self, height: u64) -> Result<Option<Header>> {
+    self
+      .location(height)
+      .map(|location| {
+        let mut header = [0; HEADER_LEN];
+        self.read_at(location.file, location.offset, &mut header)?;
+        Ok(consensus::deserialize(&header)?)
+      })
+      .transpose()
+  }
+
+  fn location(&self, height: u64) -> Option<Location> {
+    self.chain.get(usize::try_from(height).ok()?).copied()
+  }
+
+  fn path(&self, file: u32) -> PathBuf {
+    self.blocks_dir.join(format!("blk{file:05}.dat"))
+  }
+
+  // records the previous block hash and the location of every block in the file, up to the
+  // zeroed space Bitcoin Core preallocates at the end of files.
+  fn scan(
+    &self,
+    file: u32,
+    magic: [u8; 4],
+    headers: &mut HashMap<BlockHash, (BlockHash, Location)>,
+  ) -> Result {
+    let mut reader = BufReader::new(File::open(self.path(file))?);
+    let len = reader.get_ref().metadata()?.len();
+
+    let mut offset = 0;
+    let mut record = [0; RECORD_PREFIX_LEN + HEADER_LEN];
+    while offset + u64::try_from(record.len()).unwrap() <= len {
+      reader.seek(SeekFrom::Start(offset))?;
+      reader.read_exact(&mut record)?;
+      self.xor(&mut record, offset);
+
+      if record[..4] != magic {
+        break;
+      }
+
+      let size = u32::from_le_bytes(record[4..RECORD_PREFIX_LEN].try_into().unwrap());
+      let header = consensus::deserialize::<Header>(&record[RECORD_PREFIX_LEN..])?;
+
+      headers.insert(
+        header.block_hash(),
+        (
+          header.prev_blockhash,
+          Location {
+            file,
+            offset: offset + u64::try_from(RECORD_PREFIX_LEN).unwrap(),
+            len: size,
+          },
+        ),
+      );
+
+      offset += u64::try_from(RECORD_PREFIX_LEN).unwrap() + u64::from(size);
+    }
+
+    Ok(())
+  }
+
+  fn read(&self, location: Location) -> Result<Vec<u8>> {
+    let mut block = vec![0; usize::try_from(location.len)?];
+    self.read_at(location.file, location.offset, &mut block)?;
+    Ok(block)
+  }
+
+  fn read_at(&self, file: u32, offset: u64, buffer: &mut [u8]) -> Result {
+    let mut file = File::open(self.path(file))?;
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(buffer)?;
+    self.xor(buffer, offset);
+    Ok(())
+  }
+
+  // the xor key is applied from the start of each file.
+  fn xor(&self, data: &mut [u8], offset: u64) {
+    let start = usize::try_from(offset % u64::try_from(XOR_KEY_LEN).unwrap()).unwrap();
+    for (i, byte) in data.iter_mut().enumerate() {
+      *byte ^= self.xor_key[(start + i) % XOR_KEY_LEN];
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, tempfile::TempDir};
+
+  fn block(prev_blockhash: BlockHash, nonce: u32) -> Block {
+    Block {
+      header: Header {
+        version: bitcoin::block::Version::ONE,
+        prev_blockhash,
+        merkle_root: Hash::all_zeros(),
+        time: 0,
+        bits: bitcoin::CompactTarget::from_consensus(0x207fffff),
+        nonce,
+      },
+      txdata: vec![Transaction {
+        version: 1,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: vec![TxIn::default()],
+        output: vec![TxOut {
+          value: u64::from(nonce),
+          script_pubkey: ScriptBuf::new(),
+        }],
+      }],
+    }
+  }
+
+  // writes `blocks` as Bitcoin Core does, followed by preallocated space.
+  fn write_blk_file(dir: &Path, file: u32, blocks: &[&Block], xor_key: [u8; XOR_KEY_LEN]) {
+    let mut data = Vec::new();
+    for block in blocks {
+      let block = consensus::serialize(*block);
+      data.extend_from_slice(&Network::Regtest.magic().to_bytes());
+      data.extend_from_slice(&u32::try_from(block.len()).unwrap().to_le_bytes());
+      data.extend_from_slice(&block);
+    }
+    data.resize(data.len() + 1000, 0);
+
+    for (i, byte) in data.iter_mut().enumerate() {
+      *byte ^= xor_key[i % XOR_KEY_LEN];
+    }
+
+    fs::write(dir.join(format!("blk{file:05}.dat")), data).unwrap();
+  }
+
+  fn chain(len: u32) -> Vec<Block> {
+    let mut blocks = vec![block(BlockHash::all_zeros(), 0)];
+    for nonce in 1..len {
+      blocks.push(block(blocks.last().unwrap().block_hash(), nonce));
+    }
+    blocks
+  }
+
+  #[test]
+  fn blocks_are_ordered_by_height_along_the_chain_of_the_tip() {
+    let dir = TempDir::new().unwrap();
+    let blocks = chain(5);
+    let fork = block(blocks[2].block_hash(), 100);
+
+    write_blk_file(dir.path(), 0, &[&blocks[0], &blocks[2], &blocks[1]], [0; 8]);
+    write_blk_file(dir.path(), 1, &[&fork, &blocks[4], &blocks[3]], [0; 8]);
+
+    let reader = BlkReader::open(dir.path(), Network::Regtest, blocks[4].block_hash()).unwrap();
+
+    assert_eq!(reader.block_count(), 5);
+    for (height, block) in blocks.iter().enumerate() {
+      let height = u64::try_from(height).unwrap();
+      assert_eq!(reader.get_block(height).unwrap().as_ref(), Some(block));
+      assert_eq!(reader.get_header(height).unwrap(), Some(block.header));
+    }
+    assert_eq!(reader.get_block(5).unwrap(), None);
+
+    let reader = BlkReader::open(dir.path(), Network::Regtest, fork.block_hash()).unwrap();
+    assert_eq!(reader.block_count(), 4);
+    assert_eq!(reader.get_block(3).unwrap(), Some(fork));
+  }
+
+  #[test]
+  fn obfuscated_blocks_are_read_with_the_xor_key() {
+    let dir = TempDir::new().unwrap();
+    let blocks = chain(3);
+    let xor_key = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    fs::write(dir.path().join("xor.dat"), xor_key).unwrap();
+    write_blk_file(
+      dir.path(),
+      0,
+      &blocks.iter().collect::<Vec<&Block>>(),
+      xor_key,
+    );
+
+    let reader = BlkReader::open(dir.path(), Network::Regtest, blocks[2].block_hash()).unwrap();
+
+    assert_eq!(reader.block_count(), 3);
+    assert_eq!(reader.get_block(1).unwrap().as_ref(), Some(&blocks[1]));
+  }
+
+  #[test]
+  fn missing_tip_is_an_error() {
+    let dir = TempDir::new().unwrap();
+    let blocks = chain(3);
+
+    write_blk_file(dir.path(), 0, &[&blocks[0], &blocks[1]], [0; 8]);
+
+    assert!(BlkReader::open(dir.path(), Network::Regtest, blocks[2].block_hash()).is_err());
+  }
+}
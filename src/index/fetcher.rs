@@ -3,9 +3,11 @@ use {
   anyhow::{anyhow, Result},
   base64::Engine,
   bitcoin::{Transaction, Txid},
+  futures::{StreamExt, TryStreamExt},
   hyper::{client::HttpConnector, Body, Client, Method, Request, Uri},
   serde::Deserialize,
   serde_json::{json, Value},
+  std::sync::mpsc,
 };
 
 #[allow(unused)]
@@ -155,3 +157,61 @@ impl Fetcher {
     Ok(results)
   }
 }
+
+type FetchRequest = (Vec<Txid>, mpsc::Sender<Result<Vec<Transaction>>>);
+
+/// Fetches transactions with batched requests, for callers that block rather than await. The
+/// requests are made from a thread with its own runtime, like the input fetcher of the updater.
+pub(crate) struct BlockingFetcher {
+  sender: mpsc::Sender<FetchRequest>,
+}
+
+impl BlockingFetcher {
+  // The number of transactions in a batched request.
+  const BATCH_SIZE: usize = 256;
+  // The updater already fetches blocks and inputs, so this stays well below the default
+  // rpcworkqueue of 16.
+  const PARALLEL_REQUESTS: usize = 4;
+
+  pub(crate) fn spawn(options: &Options) -> Result<Self> {
+    let fetcher = Fetcher::new(options)?;
+    let (sender, receiver) = mpsc::channel::<FetchRequest>();
+
+    std::thread::spawn(move || {
+      let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+      while let Ok((txids, reply)) = receiver.recv() {
+        let result = rt.block_on(
+          futures::stream::iter(txids.chunks(Self::BATCH_SIZE))
+            .map(|chunk| fetcher.get_transactions(chunk.to_vec()))
+            .buffered(Self::PARALLEL_REQUESTS)
+            .try_concat(),
+        );
+        if reply.send(result).is_err() {
+          log::debug!("Transaction request dropped");
+        }
+      }
+      log::debug!("Transaction request channel closed");
+    });
+
+    Ok(Self { sender })
+  }
+
+  /// Returns the transactions of `txids`, in order.
+  pub(crate) fn get_transactions(&self, txids: Vec<Txid>) -> Result<Vec<Transaction>> {
+    if txids.is_empty() {
+      return Ok(Vec::new());
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    self
+      .sender
+      .send((txids, sender))
+      .map_err(|_| anyhow!("transaction fetcher stopped"))?;
+    receiver
+      .recv()
+      .map_err(|_| anyhow!("transaction fetcher stopped"))?
+  }
+}
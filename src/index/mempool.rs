@@ -0,0 +1,369 @@
+use {
+  super::*,
+  crate::okx::datastore::{overlay::TableOverlay, StateReadWrite},
+  fetcher::BlockingFetcher,
+  simulate::{Continuation, SimulatedTransaction},
+  std::sync::{Mutex, RwLock},
+};
+
+/// A confirmed inscription moved by a pending transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingSpend {
+  pub txid: Txid,
+  /// The earlier pending transactions that moved the inscription and were replaced, most recent
+  /// last.
+  pub replaced: Vec<Txid>,
+}
+
+/// The balance and transferable inscriptions of an address and ticker once the pending
+/// transactions are confirmed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pending<B, T> {
+  pub balance: Option<B>,
+  pub transferable: Vec<T>,
+  /// The pending transactions with receipts of the address and ticker.
+  pub txids: Vec<Txid>,
+}
+
+/// The BRC20 and BRC20S state the pending transactions would leave behind, for the entries their
+/// receipts touch.
+#[derive(Debug, Default, Clone)]
+pub struct Overlay {
+  /// The height of the block the pending transactions were simulated in.
+  pub height: u64,
+  brc20: HashMap<(ScriptKey, String), Pending<brc20::Balance, brc20::TransferableLog>>,
+  brc20s: HashMap<(ScriptKey, String), Pending<brc20s::Balance, brc20s::TransferableAsset>>,
+  spends: HashMap<InscriptionId, PendingSpend>,
+  transactions: HashMap<Txid, SimulatedTransaction>,
+}
+
+impl Overlay {
+  pub(crate) fn brc20(
+    &self,
+    script_key: ScriptKey,
+    tick: &brc20::Tick,
+  ) -> Option<&Pending<brc20::Balance, brc20::TransferableLog>> {
+    self
+      .brc20
+      .get(&(script_key, tick.to_lowercase().as_str().to_string()))
+  }
+
+  pub(crate) fn brc20s(
+    &self,
+    script_key: ScriptKey,
+    tick_id: &brc20s::TickId,
+  ) -> Option<&Pending<brc20s::Balance, brc20s::TransferableAsset>> {
+    self.brc20s.get(&(script_key, tick_id.hex()))
+  }
+
+  pub(crate) fn spend(&self, inscription_id: &InscriptionId) -> Option<&PendingSpend> {
+    self.spends.get(inscription_id)
+  }
+
+  /// Returns the inscription operations and receipts of a pending transaction.
+  pub(crate) fn transaction(&self, txid: &Txid) -> Option<&SimulatedTransaction> {
+    self.transactions.get(txid)
+  }
+
+  /// Records the receipts of `simulated` and reads the entries they touch back from `state`,
+  /// which the transactions were simulated in.
+  fn add(
+    mut self,
+    state: &StateReadWrite<TableOverlay<ReadTransaction>>,
+    simulated: &[SimulatedTransaction],
+  ) -> Result<Self> {
+    let mut brc20_read = HashSet::new();
+    let mut brc20s_read = HashSet::new();
+
+    for transaction in simulated {
+      for receipt in &transaction.brc20_receipts {
+        let Ok(event) = &receipt.result else {
+          continue;
+        };
+        let tick = event.tick();
+        for script_key in [&receipt.from, &receipt.to] {
+          let key = (script_key.clone(), tick.to_lowercase().as_str().to_string());
+          if brc20_read.insert(key.clone()) {
+            let balance = brc20::store(state)
+              .get_balance(script_key, tick)
+              .map_err(|e| anyhow!("failed to get pending brc20 balance! error: {e}"))?;
+            let transferable = brc20::store(state)
+              .get_transferable_by_tick(script_key, tick)
+              .map_err(|e| anyhow!("failed to get pending brc20 transferable! error: {e}"))?;
+            let pending = self.brc20.entry(key.clone()).or_insert_with(|| Pending {
+              balance: None,
+              transferable: Vec::new(),
+              txids: Vec::new(),
+            });
+            pending.balance = balance;
+            pending.transferable = transferable;
+          }
+          let txids = &mut self.brc20.get_mut(&key).unwrap().txids;
+          if !txids.contains(&transaction.txid) {
+            txids.push(transaction.txid);
+          }
+        }
+      }
+
+      for receipt in &transaction.brc20s_receipts {
+        let Ok(events) = &receipt.result else {
+          continue;
+        };
+        for tick_id in events.iter().map(|event| event.tick_id()) {
+          for script_key in [&receipt.from, &receipt.to] {
+            let key = (script_key.clone(), tick_id.hex());
+            if brc20s_read.insert(key.clone()) {
+              let balance = brc20s::store(state)
+                .get_balance(script_key, &tick_id)
+                .map_err(|e| anyhow!("failed to get pending brc20s balance! error: {e}"))?;
+              let transferable = brc20s::store(state)
+                .get_transferable_by_tickid(script_key, &tick_id)
+                .map_err(|e| anyhow!("failed to get pending brc20s transferable! error: {e}"))?;
+              let pending = self.brc20s.entry(key.clone()).or_insert_with(|| Pending {
+                balance: None,
+                transferable: Vec::new(),
+                txids: Vec::new(),
+              });
+              pending.balance = balance;
+              pending.transferable = transferable;
+            }
+            let txids = &mut self.brc20s.get_mut(&key).unwrap().txids;
+            if !txids.contains(&transaction.txid) {
+              txids.push(transaction.txid);
+            }
+          }
+        }
+      }
+    }
+
+    Ok(self)
+  }
+}
+
+/// Follows the mempool of Bitcoin Core.
+#[derive(Default)]
+struct Watcher {
+  /// Fetches the transactions that joined the mempool, spawned on the first poll.
+  fetcher: Option<BlockingFetcher>,
+  /// The state the pending transactions of the overlay left behind.
+  continuation: Option<Continuation>,
+  transactions: HashMap<Txid, Transaction>,
+  /// The values of the outputs spent by the pending transactions, kept across polls.
+  outputs: HashMap<OutPoint, TxOut>,
+  spends: HashMap<InscriptionId, PendingSpend>,
+}
+
+#[derive(Default)]
+pub(crate) struct Mempool {
+  watcher: Mutex<Watcher>,
+  overlay: RwLock<Option<Arc<Overlay>>>,
+}
+
+impl Mempool {
+  pub(crate) fn overlay(&self) -> Option<Arc<Overlay>> {
+    self.overlay.read().unwrap().clone()
+  }
+}
+
+/// Returns the pending transactions that can move or create inscriptions, parents first, and the
+/// confirmed inscriptions they spend.
+fn inscription_transactions(
+  index: &Index,
+  transactions: &HashMap<Txid, Transaction>,
+) -> Result<(Vec<Transaction>, Vec<(InscriptionId, Txid)>)> {
+  fn visit(
+    txid: Txid,
+    transactions: &HashMap<Txid, Transaction>,
+    visited: &mut HashSet<Txid>,
+    ordered: &mut Vec<Txid>,
+  ) {
+    if !visited.insert(txid) {
+      return;
+    }
+    for input in &transactions[&txid].input {
+      if transactions.contains_key(&input.previous_output.txid) {
+        visit(input.previous_output.txid, transactions, visited, ordered);
+      }
+    }
+    ordered.push(txid);
+  }
+
+  let mut txids = transactions.keys().copied().collect::<Vec<Txid>>();
+  txids.sort();
+
+  let mut visited = HashSet::new();
+  let mut ordered = Vec::new();
+  for txid in txids {
+    visit(txid, transactions, &mut visited, &mut ordered);
+  }
+
+  let rtx = index.database.begin_read()?;
+
+  let mut relevant = HashSet::new();
+  let mut inscription_transactions = Vec::new();
+  let mut spends = Vec::new();
+  for txid in ordered {
+    let tx = &transactions[&txid];
+
+    let mut inscribed = !Inscription::from_transaction(tx).is_empty();
+    for input in &tx.input {
+      inscribed |= relevant.contains(&input.previous_output.txid);
+      for (_, inscription_id) in
+        Index::inscriptions_on_output_unordered(&rtx, input.previous_output)?
+      {
+        spends.push((inscription_id, txid));
+        inscribed = true;
+      }
+    }
+
+    if inscribed {
+      relevant.insert(txid);
+      inscription_transactions.push(tx.clone());
+    }
+  }
+
+  Ok((inscription_transactions, spends))
+}
+
+/// Returns the transactions of `txids` that are still pending.
+fn fetch_transactions(
+  index: &Index,
+  fetcher: &BlockingFetcher,
+  txids: Vec<Txid>,
+) -> Result<Vec<Transaction>> {
+  match fetcher.get_transactions(txids.clone()) {
+    Ok(transactions) => Ok(transactions),
+    // a transaction can leave the mempool between the two calls, which fails its whole batch.
+    Err(err) => {
+      log::debug!(
+        "Failed to fetch pending transactions in batches, fetching them one by one: {err}"
+      );
+      let mut transactions = Vec::new();
+      for txid in txids {
+        transactions.extend(index.get_transaction(txid)?);
+      }
+      Ok(transactions)
+    }
+  }
+}
+
+/// Polls the mempool of Bitcoin Core and, when it or the chain changed, updates the overlay by
+/// simulating the pending transactions that carry inscriptions.
+///
+/// Transactions only join the mempool after their parents, so while none left it and the chain
+/// stayed at the same tip, only the new transactions are simulated, on top of the earlier ones.
+/// Otherwise the overlay is rebuilt.
+pub(crate) fn update(index: &Index) -> Result {
+  let mut watcher = index.mempool.watcher.lock().unwrap();
+
+  let txids = index.client.get_raw_mempool()?;
+  let pending = txids.iter().copied().collect::<HashSet<Txid>>();
+
+  let before = watcher.transactions.len();
+  watcher
+    .transactions
+    .retain(|txid, _| pending.contains(txid));
+  let removed = watcher.transactions.len() != before;
+
+  let joined = txids
+    .into_iter()
+    .filter(|txid| !watcher.transactions.contains_key(txid))
+    .collect::<Vec<Txid>>();
+
+  if watcher.fetcher.is_none() {
+    watcher.fetcher = Some(BlockingFetcher::spawn(&index.options)?);
+  }
+  let joined = fetch_transactions(index, watcher.fetcher.as_ref().unwrap(), joined)?;
+  let changed = removed || !joined.is_empty();
+  watcher
+    .transactions
+    .extend(joined.into_iter().map(|tx| (tx.txid(), tx)));
+
+  let tip = index.block_hash(None)?;
+  let current = watcher
+    .continuation
+    .as_ref()
+    .is_some_and(|continuation| continuation.builds_on(tip));
+  if !changed && current {
+    return Ok(());
+  }
+
+  let spent = watcher
+    .transactions
+    .values()
+    .flat_map(|tx| tx.input.iter().map(|input| input.previous_output))
+    .collect::<HashSet<OutPoint>>();
+  watcher
+    .outputs
+    .retain(|outpoint, _| spent.contains(outpoint));
+
+  let (transactions, spent_inscriptions) = inscription_transactions(index, &watcher.transactions)?;
+
+  let (overlay, continuation, transactions) =
+    match (index.mempool.overlay(), watcher.continuation.take()) {
+      (Some(overlay), Some(continuation)) if current && !removed => {
+        let transactions = transactions
+          .into_iter()
+          .filter(|tx| !overlay.transactions.contains_key(&tx.txid()))
+          .collect();
+        (Overlay::clone(&overlay), Some(continuation), transactions)
+      }
+      _ => (Overlay::default(), None, transactions),
+    };
+
+  let (simulation, mut overlay, continuation) = simulate::continue_with(
+    index,
+    continuation,
+    &transactions,
+    watcher.outputs.clone(),
+    |state, simulated| overlay.add(state, simulated),
+  )?;
+
+  watcher.outputs.extend(
+    simulation
+      .outputs
+      .into_iter()
+      .filter(|(outpoint, _)| spent.contains(outpoint)),
+  );
+
+  let mut spends = HashMap::new();
+  for (inscription_id, txid) in spent_inscriptions {
+    let replaced = match watcher.spends.remove(&inscription_id) {
+      Some(mut spend) if spend.txid != txid => {
+        log::info!(
+          "pending spend {} of inscription {inscription_id} was replaced by {txid}",
+          spend.txid,
+        );
+        spend.replaced.push(spend.txid);
+        spend.replaced
+      }
+      Some(spend) => spend.replaced,
+      None => Vec::new(),
+    };
+
+    spends.insert(inscription_id, PendingSpend { txid, replaced });
+  }
+
+  overlay.height = simulation.height;
+  overlay.spends = spends.clone();
+  overlay.transactions.extend(
+    simulation
+      .transactions
+      .into_iter()
+      .map(|transaction| (transaction.txid, transaction)),
+  );
+
+  watcher.spends = spends;
+  watcher.continuation = Some(continuation);
+
+  log::debug!(
+    "Simulated {} of {} pending transactions at height {}",
+    transactions.len(),
+    watcher.transactions.len(),
+    overlay.height
+  );
+
+  *index.mempool.overlay.write().unwrap() = Some(Arc::new(overlay));
+
+  Ok(())
+}
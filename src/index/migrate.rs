@@ -0,0 +1,743 @@
+use {
+  super::*,
+  crate::okx::datastore::{
+    brc20::{self, DataStoreReadWrite as _, TokenStats, TransferableLog, LOWER_TICK_BYTE_COUNT},
+    brc20s::{self, DataStoreReadWrite as _},
+    keys::{inscription_id_key, save_script_key, script_key_hash},
+    ord::{self, DataStoreReadWrite as _},
+  },
+  crate::okx::protocol::brc20s::params::TICK_ID_BYTE_COUNT,
+  bitcoin::{address::NetworkUnchecked, Address, ScriptHash},
+};
+
+/// The schema of the indexes that keyed the BRC20 and BRC20S tables by `_` separated strings.
+pub(super) const BASELINE_SCHEMA_VERSION: u64 = 6;
+
+/// The number of blocks backfilled in each write transaction.
+const BLOCKS_PER_COMMIT: u64 = 1000;
+
+type Entries = Vec<(Vec<u8>, Vec<u8>)>;
+
+type Convert = fn(&Migration, &[&str], &[u8]) -> Result<Entries>;
+
+struct Migration<'a, 'db> {
+  wtx: &'a WriteTransaction<'db>,
+  /// The height of the latest indexed block, at which the balance history starts.
+  height: u64,
+}
+
+/// The tables keyed by strings, the number of parts of their keys, and the conversion of an
+/// entry to binary keys.
+const TABLES: [(&str, usize, Convert); 14] = [
+  ("BRC20_BALANCES", 2, brc20_balance),
+  ("BRC20_TOKEN", 1, hex_key),
+  ("BRC20_EVENTS", 1, txid),
+  ("BRC20_TRANSFERABLELOG", 2, transferable_logs),
+  ("TXID_TO_INSCRIPTION_RECEIPTS", 1, txid),
+  ("BRC20S_TICKINFO", 1, hex_key),
+  ("BRC20S_PID_TO_POOLINFO", 1, hex_key),
+  ("BRC20S_USER_STAKEINFO", 2, script_pledged),
+  ("BRC20S_PID_TO_USERINFO", 2, script_hex),
+  ("BRC20S_STAKE_TICKID_TO_PID", 2, pledged_id),
+  ("BRC20S_TICKID_STAKE_TO_PID", 2, id_pledged),
+  ("BRC20S_BALANCE", 2, brc20s_balance),
+  ("BRC20S_TRANSFERABLE_ASSETS", 3, script_id_inscription),
+  ("BRC20S_TXID_TO_RECEIPTS", 1, txid),
+];
+
+/// Migrates an index of the baseline schema to the current one. The index keeps the baseline
+/// schema until the migration is done, so `Index::open` refuses it until then, and an interrupted
+/// migration resumes where it stopped.
+///
+/// The BRC20 and BRC20S tables are first rewritten to binary keys in a single write transaction,
+/// along with the indexes that can be rebuilt from stored data: the heights of block hashes, the
+/// token holders and the balance history. The indexes the baseline schema didn't keep are then
+/// backfilled from the blocks fetched from `client`, committing every `BLOCKS_PER_COMMIT` blocks:
+/// the heights of transactions, the receipts of addresses and the token stats. Transactions that
+/// only moved inscriptions without a receipt are only indexed by height if their operations were
+/// saved with `--enable-save-ord-receipts`.
+///
+/// The balance history, the state hashes and the undo logs start at the height of the migration,
+/// so balances can't be queried and blocks can't be unwound below it. The savepoints hold tables
+/// of the baseline schema and are deleted.
+pub(crate) fn run(database: &Database, client: &Client, first_inscription_height: u64) -> Result {
+  backfill(
+    database,
+    client,
+    first_inscription_height,
+    BLOCKS_PER_COMMIT,
+  )
+}
+
+fn statistic(wtx: &WriteTransaction, statistic: Statistic) -> Result<Option<u64>> {
+  Ok(
+    wtx
+      .open_table(STATISTIC_TO_COUNT)?
+      .get(&statistic.key())?
+      .map(|x| x.value()),
+  )
+}
+
+fn backfill(
+  database: &Database,
+  client: &Client,
+  first_inscription_height: u64,
+  blocks_per_commit: u64,
+) -> Result {
+  let wtx = database.begin_write()?;
+
+  match statistic(&wtx, Statistic::Schema)? {
+    Some(SCHEMA_VERSION) => {
+      log::info!("Index is already at schema {SCHEMA_VERSION}");
+      return Ok(());
+    }
+    Some(BASELINE_SCHEMA_VERSION) => {}
+    schema_version => bail!(
+      "index schema {} cannot be migrated, only schema {BASELINE_SCHEMA_VERSION} can",
+      schema_version.unwrap_or_default()
+    ),
+  }
+
+  if statistic(&wtx, Statistic::MigrationHeight)?.is_none() {
+    log::info!("Migrating index from schema {BASELINE_SCHEMA_VERSION} to {SCHEMA_VERSION}");
+    rekey(&wtx, first_inscription_height)?;
+    wtx.commit()?;
+  } else {
+    wtx.abort()?;
+  }
+
+  loop {
+    let wtx = database.begin_write()?;
+
+    let height = statistic(&wtx, Statistic::MigrationHeight)?.unwrap_or_default();
+    let start = statistic(&wtx, Statistic::MigrationProgress)?.unwrap_or_default();
+    let end = start
+      .saturating_add(blocks_per_commit)
+      .min(height + 1)
+      .max(start);
+
+    let blocks = wtx
+      .open_table(HEIGHT_TO_BLOCK_HASH)?
+      .range(start..end)?
+      .map(|result| result.map(|(height, hash)| (height.value(), BlockHash::load(*hash.value()))))
+      .collect::<Result<Vec<(u64, BlockHash)>, redb::StorageError>>()?;
+
+    index_blocks(&wtx, client, &blocks)?;
+
+    let done = end > height;
+    {
+      let mut statistics = wtx.open_table(STATISTIC_TO_COUNT)?;
+      if done {
+        statistics.remove(&Statistic::MigrationProgress.key())?;
+        statistics.insert(&Statistic::Schema.key(), &SCHEMA_VERSION)?;
+      } else {
+        statistics.insert(&Statistic::MigrationProgress.key(), &end)?;
+      }
+    }
+
+    wtx.commit()?;
+
+    if done {
+      log::info!("Migrated index to schema {SCHEMA_VERSION}");
+      return Ok(());
+    }
+
+    log::info!("Migrated blocks below {end} of {}", height + 1);
+  }
+}
+
+// rewrites the tables to binary keys, rebuilds the indexes that don't need the blocks, and records
+// the height of the migration and the first block to backfill.
+fn rekey(wtx: &WriteTransaction, first_inscription_height: u64) -> Result {
+  for savepoint in wtx.list_persistent_savepoints()?.collect::<Vec<u64>>() {
+    wtx.delete_persistent_savepoint(savepoint)?;
+  }
+
+  let blocks = wtx
+    .open_table(HEIGHT_TO_BLOCK_HASH)?
+    .iter()?
+    .map(|result| result.map(|(height, hash)| (height.value(), BlockHash::load(*hash.value()))))
+    .collect::<Result<Vec<(u64, BlockHash)>, redb::StorageError>>()?;
+
+  let height = blocks.last().map(|(height, _)| *height).unwrap_or_default();
+
+  let migration = Migration { wtx, height };
+  for (name, parts, convert) in TABLES {
+    let entries = migrate_table(&migration, name, parts, convert)?;
+    log::info!("Migrated {entries} entries of {name}");
+  }
+
+  {
+    let mut block_hash_to_height = wtx.open_table(BLOCK_HASH_TO_HEIGHT)?;
+    for (height, hash) in &blocks {
+      block_hash_to_height.insert(&hash.store(), height)?;
+    }
+  }
+
+  let mut statistics = wtx.open_table(STATISTIC_TO_COUNT)?;
+  statistics.insert(&Statistic::MigrationHeight.key(), &height)?;
+  statistics.insert(
+    &Statistic::MigrationProgress.key(),
+    &first_inscription_height,
+  )?;
+
+  Ok(())
+}
+
+// indexes the transactions with inscription operations of the blocks by height and their receipts
+// by address, in block order, and counts the operations of the BRC20 tokens.
+fn index_blocks(wtx: &WriteTransaction, client: &Client, blocks: &[(u64, BlockHash)]) -> Result {
+  let ord_store = ord::DataStore::new(wtx);
+  let brc20_store = brc20::redb::DataStore::new(wtx);
+  let brc20s_store = brc20s::redb::DataStore::new(wtx);
+
+  let mut stats = BTreeMap::new();
+
+  for (height, hash) in blocks {
+    let block = client.get_block(hash)?;
+
+    let mut txids = Vec::new();
+    // the coinbase transaction has no inscription operations.
+    for tx in block.txdata.iter().skip(1) {
+      let txid = tx.txid();
+      let brc20_receipts = brc20_store.get_transaction_receipts(&txid)?;
+      let brc20s_receipts = brc20s_store.get_transaction_receipts(&txid)?;
+
+      if brc20_receipts.is_empty()
+        && brc20s_receipts.is_empty()
+        && Inscription::from_transaction(tx).is_empty()
+        && ord_store.get_transaction_operations(&txid)?.is_empty()
+      {
+        continue;
+      }
+
+      // earlier commits counted the operations of earlier blocks.
+      for receipt in &brc20_receipts {
+        if let Ok(event) = &receipt.result {
+          let tick = event.tick();
+          if let std::collections::btree_map::Entry::Vacant(entry) =
+            stats.entry(tick.to_lowercase().to_bytes())
+          {
+            if let Some(token) = brc20_store.get_token_stats(tick)? {
+              entry.insert(token);
+            }
+          }
+        }
+      }
+
+      ord_store.save_transaction_height(&txid, *height)?;
+      brc20_store.index_address_receipts(&txid, &brc20_receipts, 0)?;
+      brc20s_store.index_address_receipts(&txid, &brc20s_receipts, 0)?;
+      count_operations(&mut stats, &brc20_receipts, *height);
+      txids.push(txid);
+    }
+
+    if !txids.is_empty() {
+      ord_store.save_block_transactions(*height, block.header.time, &txids)?;
+    }
+  }
+
+  for stats in stats.values() {
+    brc20_store.update_token_stats(&stats.tick, stats)?;
+  }
+
+  Ok(())
+}
+
+// updates the token stats with the successful receipts of a transaction, as executing them did.
+fn count_operations(
+  stats: &mut BTreeMap<[u8; LOWER_TICK_BYTE_COUNT], TokenStats>,
+  receipts: &[brc20::Receipt],
+  height: u64,
+) {
+  for event in receipts
+    .iter()
+    .filter_map(|receipt| receipt.result.as_ref().ok())
+  {
+    let tick = event.tick();
+    let token = stats
+      .entry(tick.to_lowercase().to_bytes())
+      .or_insert_with(|| TokenStats::new(tick, height));
+    match event {
+      brc20::Event::Deploy(_) => *token = TokenStats::new(tick, height),
+      brc20::Event::Mint(_) => token.mint_count += 1,
+      brc20::Event::InscribeTransfer(event) => {
+        token.inscribe_transfer_count += 1;
+        token.transferable_supply = token.transferable_supply.saturating_add(event.amount);
+      }
+      brc20::Event::Transfer(event) => {
+        token.transfer_count += 1;
+        token.transferable_supply = token.transferable_supply.saturating_sub(event.amount);
+      }
+    }
+    token.latest_activity_number = height;
+  }
+}
+
+// converts the entries of a table into a temporary table, which then replaces it.
+fn migrate_table(migration: &Migration, name: &str, parts: usize, convert: Convert) -> Result<u64> {
+  let old = TableDefinition::<&str, &[u8]>::new(name);
+  let temporary_name = format!("{name}_MIGRATION");
+  let temporary = TableDefinition::<&[u8], &[u8]>::new(&temporary_name);
+
+  let wtx = migration.wtx;
+  let mut entries = 0;
+  {
+    let table = wtx.open_table(old)?;
+    let mut converted = wtx.open_table(temporary)?;
+    for result in table.iter()? {
+      let (key, value) = result?;
+      let key = key.value();
+      let split = key.split('_').collect::<Vec<&str>>();
+      if split.len() != parts {
+        bail!("unexpected key `{key}` in {name}");
+      }
+      for (key, value) in convert(migration, &split, value.value())
+        .with_context(|| format!("key `{key}` in {name}"))?
+      {
+        converted.insert(key.as_slice(), value.as_slice())?;
+      }
+      entries += 1;
+    }
+  }
+
+  wtx.delete_table(old)?;
+
+  {
+    let mut table = wtx.open_table(TableDefinition::<&[u8], &[u8]>::new(name))?;
+    for result in wtx.open_table(temporary)?.iter()? {
+      let (key, value) = result?;
+      table.insert(key.value(), value.value())?;
+    }
+  }
+
+  wtx.delete_table(temporary)?;
+
+  Ok(entries)
+}
+
+fn script_key(script: &str) -> Result<ScriptKey> {
+  Ok(match script.parse::<Address<NetworkUnchecked>>() {
+    Ok(address) => ScriptKey::Address(address),
+    Err(_) => ScriptKey::ScriptHash(script.parse::<ScriptHash>()?),
+  })
+}
+
+fn script_hash(script: &str) -> Result<Vec<u8>> {
+  Ok(script_key_hash(&script_key(script)?).to_vec())
+}
+
+// pledged ticks were keyed by the hex of `btc`, of the lowercase BRC20 tick or of the tick id.
+// Unknown pledged ticks are never staked, so their key is not told apart from a tick id.
+fn pledged(pledged: &str) -> Result<Vec<u8>> {
+  let bytes = hex::decode(pledged)?;
+  let mut key = vec![0; 1 + LOWER_TICK_BYTE_COUNT];
+  match bytes.len() {
+    _ if bytes == b"btc" => key[0] = 1,
+    LOWER_TICK_BYTE_COUNT => {
+      key[0] = 2;
+      key[1..].copy_from_slice(&bytes);
+    }
+    TICK_ID_BYTE_COUNT => {
+      key[0] = 3;
+      key[1..=TICK_ID_BYTE_COUNT].copy_from_slice(&bytes);
+    }
+    _ => bail!("unexpected pledged tick `{pledged}`"),
+  }
+  Ok(key)
+}
+
+// the keys of balances record the script key of their hash.
+fn script_hex(migration: &Migration, key: &[&str], value: &[u8]) -> Result<Entries> {
+  save_script_key(migration.wtx, &script_key(key[0])?)?;
+  Ok(vec![(
+    [script_hash(key[0])?, hex::decode(key[1])?].concat(),
+    value.to_vec(),
+  )])
+}
+
+// balances also seed the holder index and the balance history.
+fn brc20_balance(migration: &Migration, key: &[&str], value: &[u8]) -> Result<Entries> {
+  let script = script_key(key[0])?;
+  let balance = bincode::deserialize::<brc20::Balance>(value)?;
+  let store = brc20::redb::DataStore::new(migration.wtx);
+  if balance.overall_balance > 0 {
+    store.insert_token_holder(&script, &balance)?;
+  }
+  store.insert_balance_history(&script, migration.height, &balance)?;
+  script_hex(migration, key, value)
+}
+
+fn brc20s_balance(migration: &Migration, key: &[&str], value: &[u8]) -> Result<Entries> {
+  let balance = bincode::deserialize::<brc20s::Balance>(value)?;
+  brc20s::redb::DataStore::new(migration.wtx).set_balance_history(
+    &script_key(key[0])?,
+    &balance.tick_id,
+    migration.height,
+    &balance,
+  )?;
+  script_hex(migration, key, value)
+}
+
+fn hex_key(_: &Migration, key: &[&str], value: &[u8]) -> Result<Entries> {
+  Ok(vec![(hex::decode(key[0])?, value.to_vec())])
+}
+
+fn txid(_: &Migration, key: &[&str], value: &[u8]) -> Result<Entries> {
+  Ok(vec![(
+    keys::txid_key(&key[0].parse()?).to_vec(),
+    value.to_vec(),
+  )])
+}
+
+// the transferable logs of an address and tick are split into an entry per inscription.
+fn transferable_logs(_: &Migration, key: &[&str], value: &[u8]) -> Result<Entries> {
+  let prefix = [script_hash(key[0])?, hex::decode(key[1])?].concat();
+  Ok(
+    bincode::deserialize::<Vec<TransferableLog>>(value)?
+      .into_iter()
+      .map(|log| {
+        (
+          [prefix.as_slice(), &inscription_id_key(&log.inscription_id)].concat(),
+          bincode::serialize(&log).unwrap(),
+        )
+      })
+      .collect(),
+  )
+}
+
+fn script_pledged(_: &Migration, key: &[&str], value: &[u8]) -> Result<Entries> {
+  Ok(vec![(
+    [script_hash(key[0])?, pledged(key[1])?].concat(),
+    value.to_vec(),
+  )])
+}
+
+fn pledged_id(_: &Migration, key: &[&str], value: &[u8]) -> Result<Entries> {
+  Ok(vec![(
+    [pledged(key[0])?, hex::decode(key[1])?].concat(),
+    value.to_vec(),
+  )])
+}
+
+fn id_pledged(_: &Migration, key: &[&str], value: &[u8]) -> Result<Entries> {
+  Ok(vec![(
+    [hex::decode(key[0])?, pledged(key[1])?].concat(),
+    value.to_vec(),
+  )])
+}
+
+fn script_id_inscription(_: &Migration, key: &[&str], value: &[u8]) -> Result<Entries> {
+  Ok(vec![(
+    [
+      script_hash(key[0])?,
+      hex::decode(key[1])?,
+      inscription_id_key(&key[2].parse()?).to_vec(),
+    ]
+    .concat(),
+    value.to_vec(),
+  )])
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::okx::datastore::brc20::{
+      redb::DataStore, Balance, DataStoreReadOnly, Event, InscripbeTransferEvent, MintEvent,
+      OperationType, Receipt, Tick,
+    },
+    crate::okx::datastore::ord::DataStoreReadOnly as _,
+    bitcoincore_rpc::Auth,
+    tempfile::NamedTempFile,
+    test_bitcoincore_rpc::TransactionTemplate,
+  };
+
+  #[test]
+  fn baseline_indexes_are_rekeyed_and_backfilled() {
+    rekey_and_backfill(BLOCKS_PER_COMMIT);
+  }
+
+  #[test]
+  fn backfill_commits_in_chunks() {
+    rekey_and_backfill(1);
+  }
+
+  fn rekey_and_backfill(blocks_per_commit: u64) {
+    let rpc_server = test_bitcoincore_rpc::builder().build();
+    rpc_server.mine_blocks(1);
+    let txid = rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(1, 0, 0, Default::default())],
+      fee: 0,
+      ..Default::default()
+    });
+    let block = rpc_server.mine_blocks(1)[0].clone();
+    let mint_txid = rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(2, 0, 0, Default::default())],
+      fee: 0,
+      ..Default::default()
+    });
+    let mint_block = rpc_server.mine_blocks(1)[0].clone();
+    let client = Client::new(&rpc_server.url(), Auth::None).unwrap();
+
+    let dbfile = NamedTempFile::new().unwrap();
+    let database = Database::create(dbfile.path()).unwrap();
+
+    let script = ScriptKey::from_address(
+      "bc1qhvd6suvqzjcu9pxjhrwhtrlj85ny3n2mqql5w4"
+        .parse::<Address<NetworkUnchecked>>()
+        .unwrap()
+        .assume_checked(),
+    );
+    let tick = Tick::from_str("ordi").unwrap();
+    let tick_hex = hex::encode(tick.to_lowercase().to_bytes());
+    let balance = Balance {
+      tick: tick.clone(),
+      overall_balance: 10,
+      transferable_balance: 3,
+    };
+    let logs = (0..2)
+      .map(|n| TransferableLog {
+        inscription_id: inscription_id(n),
+        inscription_number: n.into(),
+        amount: 1,
+        tick: tick.clone(),
+        owner: script.clone(),
+      })
+      .collect::<Vec<TransferableLog>>();
+    let receipt = Receipt {
+      inscription_id: inscription_id(1),
+      inscription_number: 1,
+      old_satpoint: SatPoint::from_str(&format!("{txid}:0:0")).unwrap(),
+      new_satpoint: SatPoint::from_str(&format!("{txid}:0:0")).unwrap(),
+      op: OperationType::InscribeTransfer,
+      from: script.clone(),
+      to: script.clone(),
+      result: Ok(Event::InscribeTransfer(InscripbeTransferEvent {
+        tick: tick.clone(),
+        amount: 1,
+      })),
+    };
+
+    let minter = ScriptKey::from_address(
+      "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        .parse::<Address<NetworkUnchecked>>()
+        .unwrap()
+        .assume_checked(),
+    );
+    let mint = Receipt {
+      inscription_id: inscription_id(2),
+      inscription_number: 2,
+      old_satpoint: SatPoint::from_str(&format!("{mint_txid}:0:0")).unwrap(),
+      new_satpoint: SatPoint::from_str(&format!("{mint_txid}:0:0")).unwrap(),
+      op: OperationType::Mint,
+      from: minter.clone(),
+      to: minter,
+      result: Ok(Event::Mint(MintEvent {
+        tick: tick.clone(),
+        amount: 1,
+        msg: None,
+      })),
+    };
+
+    let wtx = database.begin_write().unwrap();
+    {
+      let mut height_to_block_hash = wtx.open_table(HEIGHT_TO_BLOCK_HASH).unwrap();
+      for height in 0..4 {
+        height_to_block_hash
+          .insert(height, &client.get_block_hash(height).unwrap().store())
+          .unwrap();
+      }
+
+      let string_keyed = TableDefinition::<&str, &[u8]>::new;
+      wtx
+        .open_table(string_keyed("BRC20_BALANCES"))
+        .unwrap()
+        .insert(
+          format!("{script}_{tick_hex}").as_str(),
+          bincode::serialize(&balance).unwrap().as_slice(),
+        )
+        .unwrap();
+      wtx
+        .open_table(string_keyed("BRC20_TRANSFERABLELOG"))
+        .unwrap()
+        .insert(
+          format!("{script}_{tick_hex}").as_str(),
+          bincode::serialize(&logs).unwrap().as_slice(),
+        )
+        .unwrap();
+      wtx
+        .open_table(string_keyed("BRC20_EVENTS"))
+        .unwrap()
+        .insert(
+          txid.to_string().as_str(),
+          bincode::serialize(&vec![receipt.clone()])
+            .unwrap()
+            .as_slice(),
+        )
+        .unwrap();
+      wtx
+        .open_table(string_keyed("BRC20_EVENTS"))
+        .unwrap()
+        .insert(
+          mint_txid.to_string().as_str(),
+          bincode::serialize(&vec![mint]).unwrap().as_slice(),
+        )
+        .unwrap();
+      wtx
+        .open_table(STATISTIC_TO_COUNT)
+        .unwrap()
+        .insert(&Statistic::Schema.key(), &BASELINE_SCHEMA_VERSION)
+        .unwrap();
+    }
+    wtx.commit().unwrap();
+
+    let wtx = database.begin_write().unwrap();
+    wtx.persistent_savepoint().unwrap();
+    wtx.commit().unwrap();
+
+    backfill(&database, &client, 0, blocks_per_commit).unwrap();
+
+    let wtx = database.begin_write().unwrap();
+    assert_eq!(wtx.list_persistent_savepoints().unwrap().count(), 0);
+    wtx.abort().unwrap();
+
+    let rtx = database.begin_read().unwrap();
+    let statistic = |statistic: Statistic| {
+      rtx
+        .open_table(STATISTIC_TO_COUNT)
+        .unwrap()
+        .get(&statistic.key())
+        .unwrap()
+        .map(|value| value.value())
+    };
+    assert_eq!(statistic(Statistic::Schema), Some(SCHEMA_VERSION));
+    assert_eq!(statistic(Statistic::MigrationHeight), Some(3));
+    assert_eq!(statistic(Statistic::MigrationProgress), None);
+    assert_eq!(
+      bincode::deserialize::<ScriptKey>(
+        rtx
+          .open_table(keys::SCRIPT_KEYS)
+          .unwrap()
+          .get(&script_key_hash(&script))
+          .unwrap()
+          .unwrap()
+          .value()
+      )
+      .unwrap(),
+      script
+    );
+    assert_eq!(
+      rtx
+        .open_table(BLOCK_HASH_TO_HEIGHT)
+        .unwrap()
+        .get(&block.block_hash().store())
+        .unwrap()
+        .unwrap()
+        .value(),
+      2
+    );
+
+    let reader = DataStore::new(&rtx);
+    assert_eq!(
+      reader.get_balance(&script, &tick).unwrap(),
+      Some(balance.clone())
+    );
+    assert_eq!(reader.get_balances(&script).unwrap(), vec![balance.clone()]);
+    assert_eq!(
+      reader.get_balance_at_height(&script, &tick, 3).unwrap(),
+      Some(balance.clone())
+    );
+    assert_eq!(
+      reader.get_token_holders(&tick, 0, None).unwrap(),
+      vec![(script.clone(), balance)]
+    );
+    assert_eq!(reader.get_token_holders_count(&tick).unwrap(), 1);
+    assert_eq!(
+      reader.get_transferable_by_tick(&script, &tick).unwrap(),
+      logs
+    );
+    assert_eq!(
+      reader
+        .get_transferable_by_id(&script, &inscription_id(1))
+        .unwrap(),
+      Some(logs[1].clone())
+    );
+    assert_eq!(
+      reader.get_transaction_receipts(&txid).unwrap(),
+      vec![receipt.clone()]
+    );
+    assert_eq!(
+      reader
+        .get_address_receipts(&script, None, 10, &|_| true)
+        .unwrap(),
+      vec![(0, txid, receipt)]
+    );
+    assert_eq!(
+      reader.get_token_stats(&tick).unwrap(),
+      Some(TokenStats {
+        mint_count: 1,
+        inscribe_transfer_count: 1,
+        transferable_supply: 1,
+        ..TokenStats::new(&tick, 3)
+      })
+    );
+
+    let ord_reader = ord::DataStore::new(&rtx);
+    assert_eq!(ord_reader.get_transaction_height(&txid).unwrap(), Some(2));
+    assert_eq!(
+      ord_reader.get_block_transactions(2).unwrap(),
+      Some((block.header.time, vec![txid]))
+    );
+    assert_eq!(
+      ord_reader.get_block_transactions(3).unwrap(),
+      Some((mint_block.header.time, vec![mint_txid]))
+    );
+    assert_eq!(ord_reader.get_block_transactions(1).unwrap(), None);
+  }
+
+  #[test]
+  fn migrated_indexes_are_left_alone() {
+    let rpc_server = test_bitcoincore_rpc::builder().build();
+    let client = Client::new(&rpc_server.url(), Auth::None).unwrap();
+
+    let dbfile = NamedTempFile::new().unwrap();
+    let database = Database::create(dbfile.path()).unwrap();
+    let wtx = database.begin_write().unwrap();
+    wtx
+      .open_table(STATISTIC_TO_COUNT)
+      .unwrap()
+      .insert(&Statistic::Schema.key(), &SCHEMA_VERSION)
+      .unwrap();
+    wtx.commit().unwrap();
+
+    run(&database, &client, 0).unwrap();
+
+    assert!(database
+      .begin_read()
+      .unwrap()
+      .open_table(keys::SCRIPT_KEYS)
+      .is_err());
+  }
+
+  #[test]
+  fn other_schemas_cannot_be_migrated() {
+    let rpc_server = test_bitcoincore_rpc::builder().build();
+    let client = Client::new(&rpc_server.url(), Auth::None).unwrap();
+
+    let dbfile = NamedTempFile::new().unwrap();
+    let database = Database::create(dbfile.path()).unwrap();
+    let wtx = database.begin_write().unwrap();
+    wtx
+      .open_table(STATISTIC_TO_COUNT)
+      .unwrap()
+      .insert(&Statistic::Schema.key(), &5)
+      .unwrap();
+    wtx.commit().unwrap();
+
+    assert_eq!(
+      run(&database, &client, 0).unwrap_err().to_string(),
+      "index schema 5 cannot be migrated, only schema 6 can"
+    );
+  }
+}
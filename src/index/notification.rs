@@ -0,0 +1,202 @@
+use {
+  super::*,
+  crate::okx::datastore::{
+    brc20::{self, LowerTick, Tick},
+    brc20s::{self, Pid, TickId},
+    ord::InscriptionOp,
+    ScriptKey,
+  },
+  tokio::sync::broadcast,
+};
+
+/// Notifications buffered by a slow subscriber before it starts lagging.
+const NOTIFICATION_CAPACITY: usize = 4096;
+
+/// A change to the index, published to subscribers once it has been committed.
+#[derive(Debug, Clone)]
+pub(crate) enum Notification {
+  BlockConnected {
+    height: u64,
+    blockhash: BlockHash,
+  },
+  Reorg {
+    height: u64,
+    depth: u64,
+  },
+  Inscription {
+    height: u64,
+    op: InscriptionOp,
+  },
+  BRC20Receipt {
+    height: u64,
+    txid: Txid,
+    receipt: brc20::Receipt,
+  },
+  BRC20SReceipt {
+    height: u64,
+    txid: Txid,
+    receipt: brc20s::Receipt,
+  },
+}
+
+#[derive(Debug)]
+pub(crate) enum FilterError {
+  Tick,
+  TickId,
+  Pid,
+  Address(bitcoin::address::Error),
+}
+
+/// Selects the inscriptions and receipts a subscriber is interested in.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Filter {
+  tick: Option<LowerTick>,
+  tick_id: Option<TickId>,
+  pid: Option<Pid>,
+  script_key: Option<ScriptKey>,
+}
+
+impl Filter {
+  pub(crate) fn new(
+    tick: Option<&str>,
+    tick_id: Option<&str>,
+    pid: Option<&str>,
+    address: Option<&str>,
+    network: Network,
+  ) -> Result<Self, FilterError> {
+    Ok(Self {
+      tick: tick
+        .map(|tick| {
+          Tick::from_str(tick)
+            .map(|tick| tick.to_lowercase())
+            .map_err(|_| FilterError::Tick)
+        })
+        .transpose()?,
+      tick_id: tick_id
+        .map(|tick_id| TickId::from_str(tick_id).map_err(|_| FilterError::TickId))
+        .transpose()?,
+      pid: pid
+        .map(|pid| Pid::from_str(pid).map_err(|_| FilterError::Pid))
+        .transpose()?,
+      script_key: address
+        .map(|address| {
+          Address::from_str(address)
+            .and_then(|address| address.require_network(network))
+            .map(ScriptKey::from_address)
+            .map_err(FilterError::Address)
+        })
+        .transpose()?,
+    })
+  }
+
+  pub(crate) fn script_key(&self) -> Option<&ScriptKey> {
+    self.script_key.as_ref()
+  }
+
+  /// Whether a ticker or pool filter is set, which inscriptions never pass.
+  pub(crate) fn is_protocol_scoped(&self) -> bool {
+    self.tick.is_some() || self.tick_id.is_some() || self.pid.is_some()
+  }
+
+  /// Whether a protocol or address filter is set.
+  pub(crate) fn is_scoped(&self) -> bool {
+    self.is_protocol_scoped() || self.script_key.is_some()
+  }
+
+  fn matches_address(&self, from: &ScriptKey, to: &ScriptKey) -> bool {
+    self
+      .script_key
+      .as_ref()
+      .map_or(true, |script_key| script_key == from || script_key == to)
+  }
+
+  pub(crate) fn matches_brc20(&self, receipt: &brc20::Receipt) -> bool {
+    self.tick_id.is_none()
+      && self.pid.is_none()
+      && self.matches_address(&receipt.from, &receipt.to)
+      && self.tick.as_ref().map_or(true, |tick| {
+        receipt
+          .result
+          .as_ref()
+          .is_ok_and(|event| event.tick().to_lowercase() == *tick)
+      })
+  }
+
+  pub(crate) fn matches_brc20s(&self, receipt: &brc20s::Receipt) -> bool {
+    let events = receipt.result.as_deref().unwrap_or_default();
+    self.tick.is_none()
+      && self.matches_address(&receipt.from, &receipt.to)
+      && self.tick_id.as_ref().map_or(true, |tick_id| {
+        events.iter().any(|event| event.tick_id() == *tick_id)
+      })
+      && self.pid.as_ref().map_or(true, |pid| {
+        events.iter().any(|event| event.pid() == Some(pid))
+      })
+  }
+}
+
+pub(crate) struct Notifier {
+  sender: broadcast::Sender<Arc<Notification>>,
+}
+
+impl Notifier {
+  pub(crate) fn new() -> Self {
+    Self {
+      sender: broadcast::channel(NOTIFICATION_CAPACITY).0,
+    }
+  }
+
+  pub(crate) fn subscribe(&self) -> broadcast::Receiver<Arc<Notification>> {
+    self.sender.subscribe()
+  }
+
+  /// Whether anyone is listening, so the updater can skip collecting notifications.
+  pub(crate) fn has_subscribers(&self) -> bool {
+    self.sender.receiver_count() > 0
+  }
+
+  pub(crate) fn notify(&self, notifications: impl IntoIterator<Item = Notification>) {
+    for notification in notifications {
+      // sending only fails when there are no subscribers left.
+      if self.sender.send(Arc::new(notification)).is_err() {
+        break;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn notify_reaches_every_subscriber() {
+    let notifier = Notifier::new();
+    assert!(!notifier.has_subscribers());
+
+    notifier.notify([Notification::Reorg {
+      height: 1,
+      depth: 1,
+    }]);
+
+    let mut first = notifier.subscribe();
+    let mut second = notifier.subscribe();
+    assert!(notifier.has_subscribers());
+
+    notifier.notify([Notification::Reorg {
+      height: 2,
+      depth: 3,
+    }]);
+
+    for receiver in [&mut first, &mut second] {
+      assert!(matches!(
+        *receiver.try_recv().unwrap(),
+        Notification::Reorg {
+          height: 2,
+          depth: 3
+        }
+      ));
+      assert!(receiver.try_recv().is_err());
+    }
+  }
+}
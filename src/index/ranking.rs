@@ -0,0 +1,159 @@
+use {super::*, crate::okx::datastore::brc20};
+
+/// The info and stats of a BRC20 tick, with its number of holders.
+pub(crate) type TickStats = (brc20::TokenInfo, brc20::TokenStats, u64);
+
+/// The orders the BRC20 ticks are ranked in, all descending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum StatsSort {
+  /// The minted share of the supply.
+  Progress,
+  Holders,
+  /// The number of successful operations.
+  Activity,
+}
+
+impl StatsSort {
+  fn sort(self, ticks: &mut [TickStats]) {
+    // the sorts are stable, so ticks that rank the same keep the order of the lowercase tick.
+    match self {
+      Self::Progress => ticks.sort_by(|(a, ..), (b, ..)| {
+        (b.minted as f64 / b.supply as f64).total_cmp(&(a.minted as f64 / a.supply as f64))
+      }),
+      Self::Holders => ticks.sort_by_key(|(.., holders)| std::cmp::Reverse(*holders)),
+      Self::Activity => ticks.sort_by_key(|(_, stats, _)| {
+        std::cmp::Reverse(stats.mint_count + stats.inscribe_transfer_count + stats.transfer_count)
+      }),
+    }
+  }
+}
+
+/// Rankings of the BRC20 ticks. A ranking is built by the first request after a block has been
+/// indexed, and shared by the requests that follow until the next one.
+#[derive(Default)]
+pub(crate) struct Ranking {
+  cache: Mutex<Option<Cache>>,
+}
+
+struct Cache {
+  tip: Option<BlockHash>,
+  rankings: HashMap<StatsSort, Arc<Vec<TickStats>>>,
+}
+
+impl Ranking {
+  /// Returns the ticks ranked by `sort` as of the block `tip`, loading them with `load` if no
+  /// ranking of that block has been built yet.
+  pub(crate) fn get(
+    &self,
+    tip: Option<BlockHash>,
+    sort: StatsSort,
+    load: impl FnOnce() -> Result<Vec<TickStats>>,
+  ) -> Result<Arc<Vec<TickStats>>> {
+    let mut cache = self.cache.lock().unwrap();
+
+    let cache = match cache.as_mut() {
+      Some(cache) if cache.tip == tip => cache,
+      _ => cache.insert(Cache {
+        tip,
+        rankings: HashMap::new(),
+      }),
+    };
+
+    if let Some(ranking) = cache.rankings.get(&sort) {
+      return Ok(ranking.clone());
+    }
+
+    let mut ticks = match cache.rankings.values().next() {
+      Some(ranking) => ranking.as_ref().clone(),
+      None => load()?,
+    };
+    sort.sort(&mut ticks);
+
+    let ranking = Arc::new(ticks);
+    cache.rankings.insert(sort, ranking.clone());
+    Ok(ranking)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn tick_stats(tick: &str, minted: u128, holders: u64, activity: u64) -> TickStats {
+    let tick = brc20::Tick::from_str(tick).unwrap();
+    let mut stats = brc20::TokenStats::new(&tick, 0);
+    stats.mint_count = activity;
+    (
+      brc20::TokenInfo {
+        tick,
+        inscription_id: InscriptionId::from_str(
+          "1111111111111111111111111111111111111111111111111111111111111111i1",
+        )
+        .unwrap(),
+        inscription_number: 0,
+        supply: 100,
+        minted,
+        limit_per_mint: 100,
+        decimal: 0,
+        deploy_by: ScriptKey::from_script(&ScriptBuf::new(), Network::Bitcoin),
+        deployed_number: 0,
+        deployed_timestamp: 0,
+        latest_mint_number: 0,
+      },
+      stats,
+      holders,
+    )
+  }
+
+  fn ticks(ranking: &[TickStats]) -> Vec<String> {
+    ranking
+      .iter()
+      .map(|(info, ..)| info.tick.to_string())
+      .collect()
+  }
+
+  #[test]
+  fn rankings_are_cached_until_the_tip_changes() {
+    let ranking = Ranking::default();
+    let tip = Some(BlockHash::all_zeros());
+    let load = || {
+      Ok(vec![
+        tick_stats("aaaa", 10, 3, 1),
+        tick_stats("bbbb", 50, 1, 2),
+        tick_stats("cccc", 30, 2, 3),
+      ])
+    };
+
+    assert_eq!(
+      ticks(&ranking.get(tip, StatsSort::Progress, load).unwrap()),
+      ["bbbb", "cccc", "aaaa"]
+    );
+    assert_eq!(
+      ticks(
+        &ranking
+          .get(tip, StatsSort::Holders, || panic!("the ticks are cached"))
+          .unwrap()
+      ),
+      ["aaaa", "cccc", "bbbb"]
+    );
+    assert_eq!(
+      ticks(
+        &ranking
+          .get(tip, StatsSort::Activity, || panic!("the ticks are cached"))
+          .unwrap()
+      ),
+      ["cccc", "bbbb", "aaaa"]
+    );
+
+    assert_eq!(
+      ticks(
+        &ranking
+          .get(None, StatsSort::Progress, || Ok(vec![tick_stats(
+            "dddd", 0, 0, 0
+          )]))
+          .unwrap()
+      ),
+      ["dddd"]
+    );
+  }
+}
@@ -1,4 +1,8 @@
-use {super::*, updater::BlockData};
+use {
+  super::*,
+  undo::{first_undoable_height, UndoLog},
+  updater::BlockData,
+};
 
 #[derive(Debug, PartialEq)]
 pub(crate) enum ReorgError {
@@ -19,10 +23,6 @@ impl fmt::Display for ReorgError {
 
 impl std::error::Error for ReorgError {}
 
-const MAX_SAVEPOINTS: usize = 2;
-const SAVEPOINT_INTERVAL: u64 = 10;
-const CHAIN_TIP_DISTANCE: u64 = 21;
-
 pub(crate) struct Reorg {}
 
 impl Reorg {
@@ -32,8 +32,7 @@ impl Reorg {
     match index.block_hash(height.checked_sub(1))? {
       Some(index_prev_blockhash) if index_prev_blockhash == bitcoind_prev_blockhash => Ok(()),
       Some(index_prev_blockhash) if index_prev_blockhash != bitcoind_prev_blockhash => {
-        let max_recoverable_reorg_depth =
-          (MAX_SAVEPOINTS as u64 - 1) * SAVEPOINT_INTERVAL + height % SAVEPOINT_INTERVAL;
+        let max_recoverable_reorg_depth = index.options.max_reorg_depth() + 1;
 
         for depth in 1..max_recoverable_reorg_depth {
           let index_block_hash = index.block_hash(height.checked_sub(depth))?;
@@ -43,6 +42,9 @@ impl Reorg {
             .into_option()?;
 
           if index_block_hash == bitcoind_block_hash {
+            if !Self::is_undoable(index, height - depth + 1)? {
+              break;
+            }
             return Err(anyhow!(ReorgError::Recoverable { height, depth }));
           }
         }
@@ -53,19 +55,31 @@ impl Reorg {
     }
   }
 
+  fn is_undoable(index: &Index, height: u64) -> Result<bool> {
+    Ok(
+      first_undoable_height(&index.database.begin_read()?)?
+        .map(|first| first <= height)
+        .unwrap_or(false),
+    )
+  }
+
   pub(crate) fn handle_reorg(index: &Index, height: u64, depth: u64) -> Result {
     log::info!("rolling back database after reorg of depth {depth} at height {height}");
 
-    if let redb::Durability::None = index.durability {
-      panic!("set index durability to `Durability::Immediate` to test reorg handling");
-    }
-
-    let mut wtx = index.begin_write()?;
+    let wtx = index.begin_write()?;
 
-    let oldest_savepoint =
-      wtx.get_persistent_savepoint(wtx.list_persistent_savepoints()?.min().unwrap())?;
+    // unwind every block after the common ancestor at `height - depth`, sat ranges included.
+    UndoLog::new(&wtx).rollback(height - depth + 1)?;
 
-    wtx.restore_savepoint(&oldest_savepoint)?;
+    // the webhook deliveries aren't rolled back, the deliveries of the disconnected blocks are
+    // cancelled or reverted instead.
+    let block_count = wtx
+      .open_table(HEIGHT_TO_BLOCK_HASH)?
+      .iter()?
+      .next_back()
+      .transpose()?
+      .map_or(0, |(height, _)| height.value() + 1);
+    webhook::revert_from(&wtx, block_count, webhook::now())?;
 
     Index::increment_statistic(&wtx, Statistic::Commits, 1)?;
     wtx.commit()?;
@@ -78,39 +92,14 @@ impl Reorg {
     Ok(())
   }
 
-  pub(crate) fn update_savepoints(index: &Index, height: u64) -> Result {
-    if let redb::Durability::None = index.durability {
-      return Ok(());
-    }
-
-    if (height < SAVEPOINT_INTERVAL || height % SAVEPOINT_INTERVAL == 0)
-      && index
-        .client
-        .get_blockchain_info()?
-        .headers
-        .saturating_sub(height)
-        <= CHAIN_TIP_DISTANCE
-    {
-      let wtx = index.begin_write()?;
-
-      let savepoints = wtx.list_persistent_savepoints()?.collect::<Vec<u64>>();
-
-      if savepoints.len() >= MAX_SAVEPOINTS {
-        wtx.delete_persistent_savepoint(savepoints.into_iter().min().unwrap())?;
-      }
-
-      Index::increment_statistic(&wtx, Statistic::Commits, 1)?;
-      wtx.commit()?;
-
-      let wtx = index.begin_write()?;
-
-      log::debug!("creating savepoint at height {}", height);
-      wtx.persistent_savepoint()?;
-
-      Index::increment_statistic(&wtx, Statistic::Commits, 1)?;
-      wtx.commit()?;
+  /// Returns how many blocks of undo logs to keep when indexing `height`, or `None` if the block
+  /// is too far from the chain tip to be reorged.
+  pub(crate) fn undo_depth(index: &Index, height: u64, chain_height: u64) -> Option<u64> {
+    let depth = index.options.max_reorg_depth();
+    if chain_height.saturating_sub(height) > depth {
+      None
+    } else {
+      Some(depth)
     }
-
-    Ok(())
   }
 }
@@ -49,6 +49,19 @@ impl Rtx<'_> {
     }
   }
 
+  pub(crate) fn state_hash(&self, height: Option<u64>) -> Result<Option<(Height, sha256::Hash)>> {
+    let table = self.0.open_table(HEIGHT_TO_STATE_HASH)?;
+    let entry = match height {
+      Some(height) => table.get(height)?.map(|hash| (height, *hash.value())),
+      None => table
+        .range(0..)?
+        .next_back()
+        .transpose()?
+        .map(|(height, hash)| (height.value(), *hash.value())),
+    };
+    Ok(entry.map(|(height, hash)| (Height(height), sha256::Hash::from_byte_array(hash))))
+  }
+
   pub(crate) fn latest_block(&self) -> Result<Option<(Height, BlockHash)>> {
     Ok(
       self
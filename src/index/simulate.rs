@@ -0,0 +1,291 @@
+use {
+  super::*,
+  crate::okx::{
+    datastore::{
+      brc20 as brc20_store, brc20s as brc20s_store,
+      ord::{self as ord_store, Action, DataStoreReadWrite, InscriptionOp},
+      overlay::{TableOverlay, Writes},
+      StateCache, StateReadWrite,
+    },
+    protocol::{brc20s::commit_input_satpoint, BlockContext, ProtocolManager},
+  },
+  bitcoin::{absolute::LockTime, Witness},
+};
+
+/// The effects a transaction would have if it were mined in the next block.
+#[derive(Debug, Clone)]
+pub struct SimulatedTransaction {
+  pub txid: Txid,
+  pub operations: Vec<InscriptionOp>,
+  pub brc20_receipts: Vec<brc20::Receipt>,
+  pub brc20s_receipts: Vec<brc20s::Receipt>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Simulation {
+  /// The height of the block the transactions were simulated in.
+  pub height: u64,
+  pub transactions: Vec<SimulatedTransaction>,
+  /// The spent and created outputs of the transactions.
+  pub outputs: HashMap<OutPoint, TxOut>,
+}
+
+/// The state simulations left behind over a tip of the index, for later simulations to build on
+/// while the index stays at that tip.
+pub(crate) struct Continuation {
+  tip: Option<BlockHash>,
+  writes: Writes,
+}
+
+impl Continuation {
+  /// Whether simulations can build on this one with the index at `tip`.
+  pub(crate) fn builds_on(&self, tip: Option<BlockHash>) -> bool {
+    self.tip == tip
+  }
+}
+
+/// Returns the output of a placeholder transaction worth 10,000 sats to `script_pubkey`, for
+/// simulations of inscriptions whose funding output is not known. The output does not exist, so
+/// anything that depends on the sats or the history of the funding output is not simulated.
+pub(crate) fn placeholder_funding(script_pubkey: &Script) -> (OutPoint, TxOut) {
+  (
+    OutPoint::new(Txid::all_zeros(), 0),
+    TxOut {
+      value: 10_000,
+      script_pubkey: script_pubkey.into(),
+    },
+  )
+}
+
+/// Returns unsigned commit and reveal transactions inscribing `inscription` on the first sat of
+/// the `funding` output and sending all its sats to `script_pubkey`, along with the output the
+/// commit transaction spends.
+pub(crate) fn inscribe_transactions(
+  inscription: &Inscription,
+  (funding, funded): (OutPoint, TxOut),
+  script_pubkey: &Script,
+) -> (Vec<Transaction>, HashMap<OutPoint, TxOut>) {
+  let output = TxOut {
+    value: funded.value,
+    script_pubkey: script_pubkey.into(),
+  };
+
+  let commit = Transaction {
+    version: 2,
+    lock_time: LockTime::ZERO,
+    input: vec![TxIn {
+      previous_output: funding,
+      script_sig: ScriptBuf::new(),
+      sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+      witness: Witness::new(),
+    }],
+    output: vec![output.clone()],
+  };
+
+  let reveal = Transaction {
+    version: 2,
+    lock_time: LockTime::ZERO,
+    input: vec![TxIn {
+      previous_output: OutPoint::new(commit.txid(), 0),
+      script_sig: ScriptBuf::new(),
+      sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+      witness: inscription.to_witness(),
+    }],
+    output: vec![output],
+  };
+
+  (vec![commit, reveal], [(funding, funded)].into())
+}
+
+/// Returns the output `outpoint` refers to, from the index or Bitcoin Core.
+pub(crate) fn output(index: &Index, outpoint: OutPoint) -> Result<TxOut> {
+  match index.get_outpoint_entry(outpoint)? {
+    Some(tx_out) => Ok(tx_out),
+    None => index
+      .get_transaction(outpoint.txid)?
+      .and_then(|tx| {
+        tx.output
+          .get(usize::try_from(outpoint.vout).unwrap())
+          .cloned()
+      })
+      .ok_or_else(|| anyhow!("input {outpoint} not found")),
+  }
+}
+
+/// Executes `transactions`, in order, on top of the current state in an overlay over a read
+/// transaction, so nothing is ever written to the index. The values of inputs that are neither in
+/// `outputs` nor created by an earlier transaction are read from the index or Bitcoin Core.
+pub(crate) fn run(
+  index: &Index,
+  transactions: &[Transaction],
+  outputs: HashMap<OutPoint, TxOut>,
+) -> Result<Simulation> {
+  Ok(run_with(index, transactions, outputs, |_, _| Ok(()))?.0)
+}
+
+/// Like `run`, also calling `read` with the resulting state before it is thrown away.
+pub(crate) fn run_with<T>(
+  index: &Index,
+  transactions: &[Transaction],
+  outputs: HashMap<OutPoint, TxOut>,
+  read: impl FnOnce(
+    &StateReadWrite<TableOverlay<ReadTransaction>>,
+    &[SimulatedTransaction],
+  ) -> Result<T>,
+) -> Result<(Simulation, T)> {
+  let (simulation, read, _) = continue_with(index, None, transactions, outputs, read)?;
+  Ok((simulation, read))
+}
+
+/// Like `run_with`, executing `transactions` after those of `continuation`, if any, and keeping the
+/// resulting state for later simulations to build on. Fails if the index moved on from the tip
+/// `continuation` was simulated over.
+pub(crate) fn continue_with<T>(
+  index: &Index,
+  continuation: Option<Continuation>,
+  transactions: &[Transaction],
+  mut outputs: HashMap<OutPoint, TxOut>,
+  read: impl FnOnce(
+    &StateReadWrite<TableOverlay<ReadTransaction>>,
+    &[SimulatedTransaction],
+  ) -> Result<T>,
+) -> Result<(Simulation, T, Continuation)> {
+  let transactions = transactions
+    .iter()
+    .map(|tx| (tx.clone(), tx.txid()))
+    .collect::<Vec<(Transaction, Txid)>>();
+
+  let created = transactions
+    .iter()
+    .map(|(_, txid)| *txid)
+    .collect::<HashSet<Txid>>();
+
+  for (tx, _) in &transactions {
+    for input in &tx.input {
+      let outpoint = input.previous_output;
+      if outpoint.is_null() {
+        bail!("cannot simulate coinbase transaction {}", tx.txid());
+      }
+
+      if created.contains(&outpoint.txid) || outputs.contains_key(&outpoint) {
+        continue;
+      }
+
+      outputs.insert(outpoint, output(index, outpoint)?);
+    }
+  }
+
+  let mut tx_out_cache = outputs.clone();
+
+  let rtx = index.begin_read()?;
+  let height = rtx.block_count()?;
+  let tip = rtx.block_hash(None)?;
+
+  let writes = match continuation {
+    Some(continuation) if !continuation.builds_on(tip) => {
+      bail!("cannot continue simulations over a tip the index moved on from")
+    }
+    Some(continuation) => continuation.writes,
+    None => Writes::default(),
+  };
+  let overlay = TableOverlay::with_writes(&rtx.0, writes);
+
+  let context = BlockContext {
+    network: index.get_chain_network(),
+    blockheight: height,
+    blocktime: u32::try_from(Utc::now().timestamp()).unwrap_or(u32::MAX),
+  };
+
+  let mut operations = updater::index_transactions_inscriptions(
+    &overlay,
+    height,
+    context.blocktime,
+    &transactions,
+    &mut tx_out_cache,
+  )?;
+
+  for (tx, txid) in &transactions {
+    for (vout, tx_out) in tx.output.iter().enumerate() {
+      outputs.insert(
+        OutPoint {
+          txid: *txid,
+          vout: vout.try_into().unwrap(),
+        },
+        tx_out.clone(),
+      );
+    }
+  }
+
+  // nothing is kept across simulations, the cache only holds the writes of this one.
+  let cache = StateCache::new(0);
+  let state = StateReadWrite::new(&overlay, &cache);
+  let protocol_man = ProtocolManager::new(Some(&index.client), &state, &index.protocol_config);
+
+  // BRC20S messages are attributed to the owner of the commit input, which the resolver looks up
+  // through bitcoind unless it is already recorded. Record it for commit transactions that are
+  // only part of this simulation.
+  let commits = transactions
+    .iter()
+    .map(|(tx, txid)| (*txid, tx))
+    .collect::<HashMap<Txid, &Transaction>>();
+
+  for op in operations.values().flatten() {
+    let (Action::New { .. }, Some(commit_transaction)) =
+      (&op.action, commits.get(&op.old_satpoint.outpoint.txid))
+    else {
+      continue;
+    };
+
+    let satpoint = commit_input_satpoint(commit_transaction, op.old_satpoint, |outpoint| {
+      outputs
+        .get(&outpoint)
+        .map(|tx_out| tx_out.value)
+        .ok_or_else(|| anyhow!("input {outpoint} not found"))
+    })?;
+
+    if let Some(tx_out) = outputs.get(&satpoint.outpoint) {
+      ord_store::store(&state).set_outpoint_to_txout(satpoint.outpoint, tx_out)?;
+    }
+    ord_store::store(&state).set_commit_input_satpoint(op.old_satpoint, satpoint)?;
+  }
+
+  let mut simulated = Vec::new();
+  for (tx, txid) in &transactions {
+    let operations = operations.remove(txid).unwrap_or_default();
+
+    for msg in protocol_man.resolve_message(context, tx, &operations)? {
+      protocol_man.execute_message(context, &msg)?;
+    }
+
+    simulated.push(SimulatedTransaction {
+      txid: *txid,
+      brc20_receipts: brc20_store::store(&state)
+        .get_transaction_receipts(txid)
+        .map_err(|e| anyhow!("failed to get brc20 receipts of {txid}! error: {e}"))?,
+      brc20s_receipts: brc20s_store::store(&state)
+        .get_transaction_receipts(txid)
+        .map_err(|e| anyhow!("failed to get brc20s receipts of {txid}! error: {e}"))?,
+      operations,
+    });
+  }
+
+  drop(protocol_man);
+
+  let read = read(&state, &simulated)?;
+
+  // the protocol writes still cached are kept along with the others.
+  state.flush()?;
+
+  Ok((
+    Simulation {
+      height,
+      transactions: simulated,
+      outputs,
+    },
+    read,
+    Continuation {
+      tip,
+      writes: overlay.into_writes(),
+    },
+  ))
+}
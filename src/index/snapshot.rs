@@ -0,0 +1,456 @@
+use {
+  super::*,
+  bitcoin::hashes::HashEngine,
+  okx::datastore::table::TableSpec,
+  redb::{
+    MultimapTableHandle, ReadTransaction, ReadableMultimapTable, RedbKey, RedbValue, TableError,
+    TableHandle,
+  },
+  std::io::BufReader,
+  undo::{TableVisitor, UndoLog},
+};
+
+const MAGIC: &[u8; 8] = b"ORDSNAP\0";
+
+/// Bumped whenever the layout of the archive changes.
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Describes the index a snapshot was taken from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+  pub version: u32,
+  pub chain: String,
+  pub schema_version: u64,
+  pub height: u64,
+  pub blockhash: BlockHash,
+}
+
+/// The result of exporting or importing a snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+  #[serde(flatten)]
+  pub header: Header,
+  pub tables: u64,
+  pub entries: u64,
+  pub checksum: sha256::Hash,
+}
+
+// Every table needed to keep indexing from the snapshot height, including the undo log so that the
+// restored node can still unwind recent blocks, followed by the state tables of the protocols.
+// Webhooks are local to a node and are left out.
+macro_rules! snapshot_tables {
+  (
+    tables { $($table:expr,)* }
+    multimap_tables { $($multimap_table:expr,)* }
+  ) => {
+    fn export_tables(
+      rtx: &ReadTransaction,
+      state_tables: &[TableSpec],
+      writer: &mut Writer,
+    ) -> Result<(u64, u64)> {
+      let mut tables = 0;
+      let mut entries = 0;
+      $(
+        if let Some(count) = export_table(rtx, $table, writer)? {
+          tables += 1;
+          entries += count;
+        }
+      )*
+      $(
+        if let Some(count) = export_multimap_table(rtx, $multimap_table, writer)? {
+          tables += 1;
+          entries += count;
+        }
+      )*
+      for table in state_tables {
+        if let Some(count) = table.layout.visit(table.name, Export { rtx, writer: &mut *writer })? {
+          tables += 1;
+          entries += count;
+        }
+      }
+      Ok((tables, entries))
+    }
+
+    fn import_table(
+      wtx: &WriteTransaction,
+      state_tables: &[TableSpec],
+      name: &str,
+      reader: &mut impl Read,
+    ) -> Result<u64> {
+      $(
+        if name == $table.name() {
+          return import_entries(wtx, $table, reader);
+        }
+      )*
+      $(
+        if name == $multimap_table.name() {
+          return import_multimap_entries(wtx, $multimap_table, reader);
+        }
+      )*
+      if let Some(table) = state_tables.iter().find(|table| table.name == name) {
+        return table.layout.visit(table.name, Import { wtx, reader });
+      }
+      bail!("snapshot contains unknown table `{name}`")
+    }
+  };
+}
+
+snapshot_tables! {
+  tables {
+    BLOCK_HASH_TO_HEIGHT,
+    HEIGHT_TO_BLOCK_HASH,
+    HEIGHT_TO_LAST_INSCRIPTION_NUMBER,
+    HEIGHT_TO_STATE_HASH,
+    INSCRIPTION_ID_TO_INSCRIPTION_ENTRY,
+    INSCRIPTION_ID_TO_SATPOINT,
+    INSCRIPTION_NUMBER_TO_INSCRIPTION_ID,
+    OUTPOINT_TO_ENTRY,
+    OUTPOINT_TO_SAT_RANGES,
+    REINSCRIPTION_ID_TO_SEQUENCE_NUMBER,
+    SAT_TO_SATPOINT,
+    STATISTIC_TO_COUNT,
+    WRITE_TRANSACTION_STARTING_BLOCK_COUNT_TO_TIMESTAMP,
+    undo::HEIGHT_TO_UNDO_SEQUENCE,
+    undo::UNDO_SEQUENCE_TO_CHANGE,
+  }
+  multimap_tables {
+    INSCRIPTION_ID_TO_CHILDREN,
+    SATPOINT_TO_INSCRIPTION_ID,
+    SAT_TO_INSCRIPTION_ID,
+  }
+}
+
+/// Writes to a file while hashing everything written.
+struct Writer {
+  file: BufWriter<File>,
+  engine: sha256::HashEngine,
+}
+
+impl Write for Writer {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let written = self.file.write(buf)?;
+    self.engine.input(&buf[..written]);
+    Ok(written)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.file.flush()
+  }
+}
+
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+  writer.write_all(&u32::try_from(bytes.len()).unwrap().to_be_bytes())?;
+  writer.write_all(bytes)
+}
+
+fn read_bytes(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+  let mut len = [0; 4];
+  reader.read_exact(&mut len)?;
+  let mut bytes = vec![0; usize::try_from(u32::from_be_bytes(len)).unwrap()];
+  reader.read_exact(&mut bytes)?;
+  Ok(bytes)
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+  let mut value = [0; 8];
+  reader.read_exact(&mut value)?;
+  Ok(u64::from_be_bytes(value))
+}
+
+fn export_table<K: RedbKey + 'static, V: RedbValue + 'static>(
+  rtx: &ReadTransaction,
+  definition: TableDefinition<K, V>,
+  writer: &mut Writer,
+) -> Result<Option<u64>> {
+  let table = match rtx.open_table(definition) {
+    Ok(table) => table,
+    Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+    Err(err) => return Err(err.into()),
+  };
+
+  let count = table.len()?;
+  write_bytes(writer, definition.name().as_bytes())?;
+  writer.write_all(&count.to_be_bytes())?;
+
+  for result in table.iter()? {
+    let (key, value) = result?;
+    write_bytes(writer, K::as_bytes(&key.value()).as_ref())?;
+    write_bytes(writer, V::as_bytes(&value.value()).as_ref())?;
+  }
+
+  Ok(Some(count))
+}
+
+fn export_multimap_table<K: RedbKey + 'static, V: RedbKey + 'static>(
+  rtx: &ReadTransaction,
+  definition: MultimapTableDefinition<K, V>,
+  writer: &mut Writer,
+) -> Result<Option<u64>> {
+  let table = match rtx.open_multimap_table(definition) {
+    Ok(table) => table,
+    Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+    Err(err) => return Err(err.into()),
+  };
+
+  let count = table.len()?;
+  write_bytes(writer, definition.name().as_bytes())?;
+  writer.write_all(&count.to_be_bytes())?;
+
+  for result in table.iter()? {
+    let (key, values) = result?;
+    for value in values {
+      write_bytes(writer, K::as_bytes(&key.value()).as_ref())?;
+      write_bytes(writer, V::as_bytes(&value?.value()).as_ref())?;
+    }
+  }
+
+  Ok(Some(count))
+}
+
+struct Export<'a, 'db> {
+  rtx: &'a ReadTransaction<'db>,
+  writer: &'a mut Writer,
+}
+
+impl TableVisitor for Export<'_, '_> {
+  type Output = Result<Option<u64>>;
+
+  fn visit<K: RedbKey + 'static, V: RedbValue + 'static>(
+    self,
+    definition: TableDefinition<K, V>,
+  ) -> Self::Output {
+    export_table(self.rtx, definition, self.writer)
+  }
+}
+
+struct Import<'a, 'db, R> {
+  wtx: &'a WriteTransaction<'db>,
+  reader: &'a mut R,
+}
+
+impl<R: Read> TableVisitor for Import<'_, '_, R> {
+  type Output = Result<u64>;
+
+  fn visit<K: RedbKey + 'static, V: RedbValue + 'static>(
+    self,
+    definition: TableDefinition<K, V>,
+  ) -> Self::Output {
+    import_entries(self.wtx, definition, self.reader)
+  }
+}
+
+fn import_entries<K: RedbKey + 'static, V: RedbValue + 'static>(
+  wtx: &WriteTransaction,
+  definition: TableDefinition<K, V>,
+  reader: &mut impl Read,
+) -> Result<u64> {
+  let mut table = wtx.open_table(definition)?;
+  let count = read_u64(reader)?;
+  for _ in 0..count {
+    let key = read_bytes(reader)?;
+    let value = read_bytes(reader)?;
+    table.insert(K::from_bytes(&key), V::from_bytes(&value))?;
+  }
+  Ok(count)
+}
+
+fn import_multimap_entries<K: RedbKey + 'static, V: RedbKey + 'static>(
+  wtx: &WriteTransaction,
+  definition: MultimapTableDefinition<K, V>,
+  reader: &mut impl Read,
+) -> Result<u64> {
+  let mut table = wtx.open_multimap_table(definition)?;
+  let count = read_u64(reader)?;
+  for _ in 0..count {
+    let key = read_bytes(reader)?;
+    let value = read_bytes(reader)?;
+    table.insert(K::from_bytes(&key), V::from_bytes(&value))?;
+  }
+  Ok(count)
+}
+
+/// Unwinds every block above `height` with the undo log, so that `database` can be exported as it
+/// was at `height`.
+pub(crate) fn unwind(database: &Database, height: u64) -> Result {
+  let first_undoable = undo::first_undoable_height(&database.begin_read()?)?;
+
+  let wtx = database.begin_write()?;
+
+  let tip = wtx
+    .open_table(HEIGHT_TO_BLOCK_HASH)?
+    .range(0..)?
+    .next_back()
+    .transpose()?
+    .map(|(height, _)| height.value());
+
+  match tip {
+    Some(tip) if height > tip => bail!("cannot snapshot height {height} above the tip {tip}"),
+    Some(tip) if height == tip => return Ok(()),
+    None => bail!("cannot snapshot an empty index"),
+    Some(_) => {}
+  }
+
+  match first_undoable {
+    Some(first) if first <= height + 1 => UndoLog::new(&wtx).rollback(height + 1)?,
+    Some(first) => bail!(
+      "cannot snapshot height {height}, the undo log only reaches back to height {}",
+      first - 1
+    ),
+    None => bail!("cannot snapshot height {height}, the index has no undo log"),
+  }
+
+  wtx.commit()?;
+
+  Ok(())
+}
+
+/// Writes the index tables at the tip of `database` to `file`.
+///
+/// The archive is the magic bytes, the snapshot version, the length prefixed bincode header and
+/// the tables, each being its length prefixed name, its entry count and its length prefixed raw
+/// keys and values. An empty name ends the tables, and the SHA256 of everything before it closes
+/// the archive.
+pub(crate) fn export(
+  database: &Database,
+  chain: Chain,
+  state_tables: &[TableSpec],
+  file: &Path,
+) -> Result<Summary> {
+  let rtx = database.begin_read()?;
+
+  let Some((height, blockhash)) = rtx
+    .open_table(HEIGHT_TO_BLOCK_HASH)?
+    .range(0..)?
+    .next_back()
+    .transpose()?
+    .map(|(height, hash)| (height.value(), BlockHash::load(*hash.value())))
+  else {
+    bail!("cannot snapshot an empty index");
+  };
+
+  let header = Header {
+    version: SNAPSHOT_VERSION,
+    chain: chain.to_string(),
+    schema_version: SCHEMA_VERSION,
+    height,
+    blockhash,
+  };
+
+  let mut writer = Writer {
+    file: BufWriter::new(File::create(file)?),
+    engine: sha256::Hash::engine(),
+  };
+
+  writer.write_all(MAGIC)?;
+  writer.write_all(&SNAPSHOT_VERSION.to_be_bytes())?;
+  write_bytes(&mut writer, &bincode::serialize(&header)?)?;
+
+  let (tables, entries) = export_tables(&rtx, state_tables, &mut writer)?;
+  write_bytes(&mut writer, &[])?;
+
+  let checksum = sha256::Hash::from_engine(writer.engine.clone());
+  writer.file.write_all(checksum.as_byte_array())?;
+  writer.file.flush()?;
+
+  Ok(Summary {
+    header,
+    tables,
+    entries,
+    checksum,
+  })
+}
+
+/// Verifies the checksum of `file` and restores its tables into a new database at `path`.
+pub(crate) fn import(
+  file: &Path,
+  chain: Chain,
+  state_tables: &[TableSpec],
+  path: &Path,
+) -> Result<Summary> {
+  let len = fs::metadata(file)?.len();
+  let Some(body_len) = len.checked_sub(32) else {
+    bail!("snapshot `{}` is truncated", file.display());
+  };
+
+  let mut reader = BufReader::new(File::open(file)?);
+
+  let mut engine = sha256::Hash::engine();
+  io::copy(&mut (&mut reader).take(body_len), &mut engine)?;
+  let checksum = sha256::Hash::from_engine(engine);
+
+  let mut expected = [0; 32];
+  reader.read_exact(&mut expected)?;
+  if checksum.to_byte_array() != expected {
+    bail!(
+      "snapshot `{}` is corrupted: checksum mismatch",
+      file.display()
+    );
+  }
+
+  let mut reader = BufReader::new(File::open(file)?).take(body_len);
+
+  let mut magic = [0; 8];
+  reader.read_exact(&mut magic)?;
+  if &magic != MAGIC {
+    bail!("`{}` is not a snapshot", file.display());
+  }
+
+  let mut version = [0; 4];
+  reader.read_exact(&mut version)?;
+  let version = u32::from_be_bytes(version);
+  if version != SNAPSHOT_VERSION {
+    bail!("snapshot version {version} is not supported, expected version {SNAPSHOT_VERSION}");
+  }
+
+  let header: Header = bincode::deserialize(&read_bytes(&mut reader)?)?;
+
+  if header.chain != chain.to_string() {
+    bail!("snapshot is for {} but ord is on {chain}", header.chain);
+  }
+
+  if header.schema_version != SCHEMA_VERSION {
+    bail!(
+      "snapshot was taken with index schema {}, ord schema {SCHEMA_VERSION}",
+      header.schema_version
+    );
+  }
+
+  if path.exists() {
+    bail!(
+      "refusing to import snapshot over existing index at `{}`",
+      path.display()
+    );
+  }
+
+  let mut restore = || -> Result<(u64, u64)> {
+    let database = Database::create(path)?;
+    let wtx = database.begin_write()?;
+
+    let mut tables = 0;
+    let mut entries = 0;
+    loop {
+      let name = String::from_utf8(read_bytes(&mut reader)?)?;
+      if name.is_empty() {
+        break;
+      }
+      entries += import_table(&wtx, state_tables, &name, &mut reader)?;
+      tables += 1;
+    }
+
+    wtx.commit()?;
+
+    Ok((tables, entries))
+  };
+
+  let (tables, entries) = restore().map_err(|err| {
+    fs::remove_file(path).ok();
+    err
+  })?;
+
+  Ok(Summary {
+    header,
+    tables,
+    entries,
+    checksum,
+  })
+}
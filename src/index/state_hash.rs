@@ -0,0 +1,393 @@
+//! The state hash commits to the BRC20 and BRC20S consensus state after every block, so that
+//! indexers can compare their state block by block whatever their storage layout.
+//!
+//! Every write to a consensus entry is recorded as a change, a key and a value in the encoding
+//! below, and only the last change to each key in a block is hashed. Fields are separated by `:`,
+//! addresses are their string form, or the hex script hash of non-standard scripts, BRC20 ticks
+//! are lowercase, BRC20S ticks are their hex tick id, inscription ids are `<txid>i<index>`, and
+//! amounts are base-10 integers in base units. A removed entry has the value `-`.
+//!
+//! - `brc20.token:<tick>` `<deploy inscription>:<supply>:<minted>:<limit per mint>:<decimals>:<deployer>:<deploy height>:<last mint height>`
+//! - `brc20.balance:<address>:<tick>` `<overall>:<transferable>`
+//! - `brc20.transferable:<address>:<tick>:<inscription>` `<amount>`
+//! - `brc20s.tick:<tick id>` `<name>:<deploy inscription>:<allocated>:<decimals>:<circulation>:<supply>:<deployer>:<deploy height>:<last mint height>:<pids>`
+//! - `brc20s.pool:<pid>` `<type>:<deploy inscription>:<stake>:<erate>:<minted>:<staked>:<dmax>:<acc reward per share>:<last update height>:<only>:<deploy height>`
+//! - `brc20s.stake:<address>:<stake>` `<max share>:<total only>:<pool stakes>`
+//! - `brc20s.user:<address>:<pid>` `<staked>:<minted>:<pending reward>:<reward debt>:<last update height>`
+//! - `brc20s.balance:<address>:<tick id>` `<overall>:<transferable>`
+//! - `brc20s.transferable:<address>:<tick id>:<inscription>` `<amount>`
+//!
+//! A stake is `btc`, a lowercase BRC20 tick or a BRC20S tick id, `<pids>` is the comma separated
+//! pool ids of the tick, `<pool stakes>` is the comma separated `<pid>/<only>/<amount>` of the
+//! pools staked in, and booleans are `0` or `1`.
+//!
+//! The block digest is the SHA256 of the lines `<key> <value>\n` of the changes, sorted bytewise
+//! by key. The state hash is the SHA256 of the previous state hash, all zeros before the first
+//! block, followed by the block digest.
+
+use {
+  super::*,
+  crate::okx::datastore::{
+    brc20::{self, Tick},
+    brc20s::{self, PledgedTick},
+    table::TableWriter,
+    ScriptKey,
+  },
+  bitcoin::hashes::HashEngine,
+};
+
+/// The changes to the consensus state made by the block being indexed, as encoded key and value.
+/// It is drained when the block's state hash is committed, so it is empty between blocks.
+pub(crate) const STATE_CHANGES: TableDefinition<&[u8], &[u8]> =
+  TableDefinition::new("STATE_CHANGES");
+
+/// A change to a consensus state entry, encoded as documented in this module.
+pub(crate) struct Change {
+  key: String,
+  value: Option<String>,
+}
+
+fn tick(tick: &Tick) -> String {
+  tick.to_lowercase().to_string()
+}
+
+fn stake(stake: &PledgedTick) -> String {
+  match stake {
+    PledgedTick::BRC20Tick(brc20_tick) => tick(brc20_tick),
+    stake => stake.to_string(),
+  }
+}
+
+fn flag(value: bool) -> u8 {
+  u8::from(value)
+}
+
+impl Change {
+  fn insert(key: String, value: String) -> Self {
+    Self {
+      key,
+      value: Some(value),
+    }
+  }
+
+  pub(crate) fn brc20_token(info: &brc20::TokenInfo) -> Self {
+    Self::insert(
+      format!("brc20.token:{}", tick(&info.tick)),
+      format!(
+        "{}:{}:{}:{}:{}:{}:{}:{}",
+        info.inscription_id,
+        info.supply,
+        info.minted,
+        info.limit_per_mint,
+        info.decimal,
+        info.deploy_by,
+        info.deployed_number,
+        info.latest_mint_number,
+      ),
+    )
+  }
+
+  pub(crate) fn brc20_balance(script_key: &ScriptKey, balance: &brc20::Balance) -> Self {
+    Self::insert(
+      format!("brc20.balance:{script_key}:{}", tick(&balance.tick)),
+      format!(
+        "{}:{}",
+        balance.overall_balance, balance.transferable_balance
+      ),
+    )
+  }
+
+  fn brc20_transferable_key(
+    script_key: &ScriptKey,
+    brc20_tick: &Tick,
+    inscription_id: &InscriptionId,
+  ) -> String {
+    format!(
+      "brc20.transferable:{script_key}:{}:{inscription_id}",
+      tick(brc20_tick)
+    )
+  }
+
+  pub(crate) fn brc20_transferable(
+    script_key: &ScriptKey,
+    brc20_tick: &Tick,
+    log: &brc20::TransferableLog,
+  ) -> Self {
+    Self::insert(
+      Self::brc20_transferable_key(script_key, brc20_tick, &log.inscription_id),
+      log.amount.to_string(),
+    )
+  }
+
+  pub(crate) fn brc20_transferable_removed(
+    script_key: &ScriptKey,
+    brc20_tick: &Tick,
+    inscription_id: &InscriptionId,
+  ) -> Self {
+    Self {
+      key: Self::brc20_transferable_key(script_key, brc20_tick, inscription_id),
+      value: None,
+    }
+  }
+
+  pub(crate) fn brc20s_tick(info: &brc20s::TickInfo) -> Self {
+    Self::insert(
+      format!("brc20s.tick:{}", info.tick_id.hex()),
+      format!(
+        "{}:{}:{}:{}:{}:{}:{}:{}:{}:{}",
+        info.name.as_str(),
+        info.inscription_id,
+        info.allocated,
+        info.decimal,
+        info.circulation,
+        info.supply,
+        info.deployer,
+        info.deploy_block,
+        info.latest_mint_block,
+        info
+          .pids
+          .iter()
+          .map(|pid| pid.as_str())
+          .collect::<Vec<&str>>()
+          .join(","),
+      ),
+    )
+  }
+
+  pub(crate) fn brc20s_pool(info: &brc20s::PoolInfo) -> Self {
+    Self::insert(
+      format!("brc20s.pool:{}", info.pid.as_str()),
+      format!(
+        "{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}",
+        info.ptype.to_string(),
+        info.inscription_id,
+        stake(&info.stake),
+        info.erate,
+        info.minted,
+        info.staked,
+        info.dmax,
+        info.acc_reward_per_share,
+        info.last_update_block,
+        flag(info.only),
+        info.deploy_block,
+      ),
+    )
+  }
+
+  pub(crate) fn brc20s_stake(script_key: &ScriptKey, info: &brc20s::StakeInfo) -> Self {
+    Self::insert(
+      format!("brc20s.stake:{script_key}:{}", stake(&info.stake)),
+      format!(
+        "{}:{}:{}",
+        info.max_share,
+        info.total_only,
+        info
+          .pool_stakes
+          .iter()
+          .map(|(pid, only, amount)| format!("{}/{}/{amount}", pid.as_str(), flag(*only)))
+          .collect::<Vec<String>>()
+          .join(","),
+      ),
+    )
+  }
+
+  pub(crate) fn brc20s_user(script_key: &ScriptKey, info: &brc20s::UserInfo) -> Self {
+    Self::insert(
+      format!("brc20s.user:{script_key}:{}", info.pid.as_str()),
+      format!(
+        "{}:{}:{}:{}:{}",
+        info.staked, info.minted, info.pending_reward, info.reward_debt, info.latest_updated_block,
+      ),
+    )
+  }
+
+  pub(crate) fn brc20s_balance(script_key: &ScriptKey, balance: &brc20s::Balance) -> Self {
+    Self::insert(
+      format!("brc20s.balance:{script_key}:{}", balance.tick_id.hex()),
+      format!(
+        "{}:{}",
+        balance.overall_balance, balance.transferable_balance
+      ),
+    )
+  }
+
+  fn brc20s_transferable_key(
+    script_key: &ScriptKey,
+    tick_id: &brc20s::TickId,
+    inscription_id: &InscriptionId,
+  ) -> String {
+    format!(
+      "brc20s.transferable:{script_key}:{}:{inscription_id}",
+      tick_id.hex()
+    )
+  }
+
+  pub(crate) fn brc20s_transferable(
+    script_key: &ScriptKey,
+    asset: &brc20s::TransferableAsset,
+  ) -> Self {
+    Self::insert(
+      Self::brc20s_transferable_key(script_key, &asset.tick_id, &asset.inscription_id),
+      asset.amount.to_string(),
+    )
+  }
+
+  pub(crate) fn brc20s_transferable_removed(
+    script_key: &ScriptKey,
+    tick_id: &brc20s::TickId,
+    inscription_id: &InscriptionId,
+  ) -> Self {
+    Self {
+      key: Self::brc20s_transferable_key(script_key, tick_id, inscription_id),
+      value: None,
+    }
+  }
+}
+
+/// Records a change to the consensus state made by the current block, replacing any earlier
+/// change to the same entry.
+pub(crate) fn record_change(tables: &impl TableWriter, change: Change) -> Result<(), redb::Error> {
+  tables.table(STATE_CHANGES).insert(
+    change.key.as_bytes(),
+    change.value.as_deref().unwrap_or("-").as_bytes(),
+  )
+}
+
+/// Hashes the changes recorded for the block at `height`, chains the digest to the state hash of
+/// the previous block and stores the result.
+pub(crate) fn commit_block(wtx: &WriteTransaction, height: u64) -> Result<sha256::Hash> {
+  let mut engine = sha256::Hash::engine();
+
+  // redb iterates byte slice keys in bytewise order.
+  for result in wtx.open_table(STATE_CHANGES)?.drain::<&[u8]>(..)? {
+    let (key, value) = result?;
+    engine.input(key.value());
+    engine.input(b" ");
+    engine.input(value.value());
+    engine.input(b"\n");
+  }
+
+  let block_digest = sha256::Hash::from_engine(engine);
+
+  let previous = match height.checked_sub(1) {
+    Some(previous) => wtx
+      .open_table(HEIGHT_TO_STATE_HASH)?
+      .get(previous)?
+      .map(|hash| *hash.value())
+      .unwrap_or_default(),
+    None => [0; 32],
+  };
+
+  let mut engine = sha256::Hash::engine();
+  engine.input(&previous);
+  engine.input(block_digest.as_byte_array());
+  let state_hash = sha256::Hash::from_engine(engine);
+
+  undo::UndoLog::new(wtx).insert(HEIGHT_TO_STATE_HASH, height, state_hash.as_byte_array())?;
+
+  Ok(state_hash)
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, std::str::FromStr, tempfile::NamedTempFile};
+
+  fn change(key: &str, value: &str) -> Change {
+    Change {
+      key: key.into(),
+      value: Some(value.into()),
+    }
+  }
+
+  fn state_hashes(blocks: &[&[(&str, &str)]]) -> Vec<sha256::Hash> {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+
+    let hashes = blocks
+      .iter()
+      .enumerate()
+      .map(|(height, block)| {
+        for (key, value) in block.iter() {
+          record_change(&wtx, change(key, value)).unwrap();
+        }
+        commit_block(&wtx, u64::try_from(height).unwrap()).unwrap()
+      })
+      .collect();
+
+    assert!(wtx.open_table(STATE_CHANGES).unwrap().is_empty().unwrap());
+
+    hashes
+  }
+
+  #[test]
+  fn state_hash_depends_on_final_values_only() {
+    assert_eq!(
+      state_hashes(&[&[("a", "1"), ("b", "2")]]),
+      state_hashes(&[&[("b", "3"), ("a", "1"), ("b", "2")]]),
+    );
+    assert_ne!(
+      state_hashes(&[&[("a", "1"), ("b", "2")]]),
+      state_hashes(&[&[("a", "1"), ("b", "3")]]),
+    );
+  }
+
+  #[test]
+  fn state_hash_is_chained() {
+    let first = state_hashes(&[&[("a", "1")], &[]]);
+    let second = state_hashes(&[&[("a", "2")], &[]]);
+    assert_ne!(first[0], first[1]);
+    assert_ne!(first[1], second[1]);
+  }
+
+  #[test]
+  fn block_digest_hashes_the_documented_lines() {
+    let hashes = state_hashes(&[&[("b", "2"), ("a", "-")]]);
+
+    let mut engine = sha256::Hash::engine();
+    engine.input(&[0; 32]);
+    engine.input(sha256::Hash::hash(b"a -\nb 2\n").as_byte_array());
+    assert_eq!(hashes[0], sha256::Hash::from_engine(engine));
+  }
+
+  #[test]
+  fn changes_are_encoded_as_documented() {
+    let script_key = ScriptKey::from_script(
+      &Address::from_str("bc1qhvd6suvqzjcu9pxjhrwhtrlj85ny3n2mqql5w4")
+        .unwrap()
+        .assume_checked()
+        .script_pubkey(),
+      Network::Bitcoin,
+    );
+    let inscription_id =
+      InscriptionId::from_str("1111111111111111111111111111111111111111111111111111111111111111i1")
+        .unwrap();
+
+    let balance = Change::brc20_balance(
+      &script_key,
+      &brc20::Balance {
+        tick: Tick::from_str("OrDi").unwrap(),
+        overall_balance: 10,
+        transferable_balance: 3,
+      },
+    );
+    assert_eq!(
+      balance.key,
+      "brc20.balance:bc1qhvd6suvqzjcu9pxjhrwhtrlj85ny3n2mqql5w4:ordi"
+    );
+    assert_eq!(balance.value.unwrap(), "10:3");
+
+    let removed = Change::brc20_transferable_removed(
+      &script_key,
+      &Tick::from_str("OrDi").unwrap(),
+      &inscription_id,
+    );
+    assert_eq!(
+      removed.key,
+      format!(
+        "brc20.transferable:bc1qhvd6suvqzjcu9pxjhrwhtrlj85ny3n2mqql5w4:ordi:{inscription_id}"
+      )
+    );
+    assert_eq!(removed.value, None);
+  }
+}
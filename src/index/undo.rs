@@ -0,0 +1,475 @@
+use {
+  super::*,
+  okx::datastore::keys::ScriptKeyHash,
+  redb::{AccessGuard, MultimapTableHandle, RedbKey, RedbValue, TableHandle},
+  std::borrow::Borrow,
+};
+
+pub(crate) const HEIGHT_TO_UNDO_SEQUENCE: TableDefinition<u64, u64> =
+  TableDefinition::new("HEIGHT_TO_UNDO_SEQUENCE");
+pub(crate) const UNDO_SEQUENCE_TO_CHANGE: TableDefinition<u64, &[u8]> =
+  TableDefinition::new("UNDO_SEQUENCE_TO_CHANGE");
+
+/// The inverse of a single write, replayed to unwind a block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Revert {
+  /// Put back the previous value of a table entry, or remove the entry if there was none.
+  Restore(Option<Vec<u8>>),
+  /// Add a value back to a multimap entry.
+  Insert(Vec<u8>),
+  /// Drop a value from a multimap entry.
+  Remove(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Change {
+  layout: Layout,
+  table: String,
+  key: Vec<u8>,
+  revert: Revert,
+}
+
+pub(crate) trait JournaledTable {
+  const LAYOUT: Layout;
+}
+
+pub(crate) trait JournaledMultimapTable {
+  const LAYOUT: Layout;
+}
+
+/// Something done to a table whose key and value types are only known from its layout.
+pub(crate) trait TableVisitor {
+  type Output;
+
+  fn visit<K: RedbKey + 'static, V: RedbValue + 'static>(
+    self,
+    definition: TableDefinition<K, V>,
+  ) -> Self::Output;
+}
+
+macro_rules! layouts {
+  (
+    tables { $($layout:ident: $key:ty => $value:ty,)* }
+    multimap_tables { $($multimap_layout:ident: $multimap_key:ty => $multimap_value:ty,)* }
+  ) => {
+    /// The key and value types of a journaled table, needed to reopen it by name on rollback.
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    pub(crate) enum Layout {
+      $($layout,)*
+      $($multimap_layout,)*
+    }
+
+    $(
+      impl JournaledTable for ($key, $value) {
+        const LAYOUT: Layout = Layout::$layout;
+      }
+    )*
+
+    $(
+      impl JournaledMultimapTable for ($multimap_key, $multimap_value) {
+        const LAYOUT: Layout = Layout::$multimap_layout;
+      }
+    )*
+
+    impl Layout {
+      /// Calls `visitor` with the definition of the table `name` of this layout.
+      pub(crate) fn visit<T: TableVisitor>(self, name: &str, visitor: T) -> T::Output {
+        match self {
+          $(Layout::$layout => visitor.visit(TableDefinition::<$key, $value>::new(name)),)*
+          $(Layout::$multimap_layout => panic!("{name} is a multimap table"),)*
+        }
+      }
+    }
+
+    fn revert(wtx: &WriteTransaction, change: &Change) -> Result<(), redb::Error> {
+      match change.layout {
+        $(Layout::$layout => revert_table::<$key, $value>(wtx, change),)*
+        $(Layout::$multimap_layout => revert_multimap_table::<$multimap_key, $multimap_value>(wtx, change),)*
+      }
+    }
+  };
+}
+
+layouts! {
+  tables {
+    BytesToBytes: &'static [u8] => &'static [u8],
+    StrToBytes: &'static str => &'static [u8],
+    StrToInscriptionId: &'static str => &'static InscriptionIdValue,
+    StrToU64: &'static str => u64,
+    BlockHashToU64: &'static BlockHashValue => u64,
+    InscriptionIdToBytes: &'static InscriptionIdValue => &'static [u8],
+    InscriptionIdToEntry: &'static InscriptionIdValue => InscriptionEntryValue,
+    InscriptionIdToSatPoint: &'static InscriptionIdValue => &'static SatPointValue,
+    InscriptionIdToU64: &'static InscriptionIdValue => u64,
+    I64ToInscriptionId: i64 => &'static InscriptionIdValue,
+    U64ToBlockHash: u64 => &'static BlockHashValue,
+    U64ToInscriptionNumbers: u64 => (i64, i64),
+    U64ToSatPoint: u64 => &'static SatPointValue,
+    U64ToU64: u64 => u64,
+    U64ToBytes: u64 => &'static [u8],
+    ScriptKeyHashToBytes: &'static ScriptKeyHash => &'static [u8],
+  }
+  multimap_tables {
+    InscriptionIdToInscriptionIds: &'static InscriptionIdValue => &'static InscriptionIdValue,
+    SatPointToInscriptionIds: &'static SatPointValue => &'static InscriptionIdValue,
+    U64ToInscriptionIds: u64 => &'static InscriptionIdValue,
+  }
+}
+
+fn revert_table<K: RedbKey + 'static, V: RedbValue + 'static>(
+  wtx: &WriteTransaction,
+  change: &Change,
+) -> Result<(), redb::Error> {
+  let mut table = wtx.open_table(TableDefinition::<K, V>::new(&change.table))?;
+  match &change.revert {
+    Revert::Restore(Some(value)) => {
+      table.insert(K::from_bytes(&change.key), V::from_bytes(value))?;
+    }
+    Revert::Restore(None) => {
+      table.remove(K::from_bytes(&change.key))?;
+    }
+    Revert::Insert(_) | Revert::Remove(_) => unreachable!("multimap change in a table"),
+  }
+  Ok(())
+}
+
+fn revert_multimap_table<K: RedbKey + 'static, V: RedbKey + 'static>(
+  wtx: &WriteTransaction,
+  change: &Change,
+) -> Result<(), redb::Error> {
+  let mut table = wtx.open_multimap_table(MultimapTableDefinition::<K, V>::new(&change.table))?;
+  match &change.revert {
+    Revert::Insert(value) => {
+      table.insert(K::from_bytes(&change.key), V::from_bytes(value))?;
+    }
+    Revert::Remove(value) => {
+      table.remove(K::from_bytes(&change.key), V::from_bytes(value))?;
+    }
+    Revert::Restore(_) => unreachable!("table change in a multimap table"),
+  }
+  Ok(())
+}
+
+pub(crate) fn try_init_tables(wtx: &WriteTransaction) -> Result<(), redb::Error> {
+  wtx.open_table(HEIGHT_TO_UNDO_SEQUENCE)?;
+  wtx.open_table(UNDO_SEQUENCE_TO_CHANGE)?;
+  Ok(())
+}
+
+/// Returns the lowest height that can still be unwound, if any block is journaled.
+pub(crate) fn first_undoable_height(rtx: &redb::ReadTransaction) -> Result<Option<u64>> {
+  Ok(
+    rtx
+      .open_table(HEIGHT_TO_UNDO_SEQUENCE)?
+      .iter()?
+      .next()
+      .transpose()?
+      .map(|(height, _)| height.value()),
+  )
+}
+
+/// UndoLog journals every write of a block so that the block can be unwound on reorg.
+///
+/// Journaling is only active after `begin_block` has been called with a depth, otherwise
+/// writes go straight to their tables.
+pub(crate) struct UndoLog<'db, 'a> {
+  wtx: &'a WriteTransaction<'db>,
+}
+
+impl<'db, 'a> UndoLog<'db, 'a> {
+  pub(crate) fn new(wtx: &'a WriteTransaction<'db>) -> Self {
+    Self { wtx }
+  }
+
+  /// Starts journaling the block at `height`, keeping the undo logs of the last `depth` blocks.
+  /// Passing `None` stops journaling and drops all undo logs.
+  pub(crate) fn begin_block(&self, height: u64, depth: Option<u64>) -> Result<(), redb::Error> {
+    let mut markers = self.wtx.open_table(HEIGHT_TO_UNDO_SEQUENCE)?;
+    let mut changes = self.wtx.open_table(UNDO_SEQUENCE_TO_CHANGE)?;
+
+    let Some(depth) = depth else {
+      markers.drain(0..)?;
+      changes.drain(0..)?;
+      return Ok(());
+    };
+
+    let oldest = (height + 1).saturating_sub(depth);
+    markers.drain(..oldest)?;
+    if let Some(sequence) = markers.get(oldest)?.map(|sequence| sequence.value()) {
+      changes.drain(..sequence)?;
+    }
+
+    let sequence = Self::next_sequence(&markers, &changes)?;
+    markers.insert(height, sequence)?;
+    Ok(())
+  }
+
+  /// Unwinds every journaled block from `height` up to the tip.
+  pub(crate) fn rollback(&self, height: u64) -> Result {
+    let mut markers = self.wtx.open_table(HEIGHT_TO_UNDO_SEQUENCE)?;
+    let Some(sequence) = markers.get(height)?.map(|sequence| sequence.value()) else {
+      bail!("no undo log for block {height}");
+    };
+
+    let mut changes = self.wtx.open_table(UNDO_SEQUENCE_TO_CHANGE)?;
+    let journal = changes
+      .drain(sequence..)?
+      .map(|result| {
+        result.map(|(_, change)| bincode::deserialize::<Change>(change.value()).unwrap())
+      })
+      .collect::<Result<Vec<Change>, _>>()?;
+    markers.drain(height..)?;
+    drop(changes);
+    drop(markers);
+
+    log::debug!(
+      "reverting {} changes of blocks from height {height}",
+      journal.len()
+    );
+
+    for change in journal.iter().rev() {
+      revert(self.wtx, change)?;
+    }
+
+    Ok(())
+  }
+
+  /// Inserts into a table that is not open elsewhere and journals the previous value.
+  pub(crate) fn insert<'k, 'v, K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: impl Borrow<K::SelfType<'k>>,
+    value: impl Borrow<V::SelfType<'v>>,
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledTable,
+  {
+    let mut table = self.wtx.open_table(definition)?;
+    let old = table.insert(key.borrow(), value)?;
+    self.record(definition, key, old)
+  }
+
+  /// Removes from a table that is not open elsewhere and journals the previous value.
+  pub(crate) fn remove<'k, K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: impl Borrow<K::SelfType<'k>>,
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledTable,
+  {
+    let mut table = self.wtx.open_table(definition)?;
+    let old = table.remove(key.borrow())?;
+    self.record(definition, key, old)
+  }
+
+  /// Adds a value to a multimap table that is not open elsewhere and journals it.
+  pub(crate) fn insert_multimap<'k, 'v, K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: impl Borrow<K::SelfType<'k>>,
+    value: impl Borrow<V::SelfType<'v>>,
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledMultimapTable,
+  {
+    let mut table = self.wtx.open_multimap_table(definition)?;
+    let existed = table.insert(key.borrow(), value.borrow())?;
+    self.record_multimap_insert(definition, key, value, existed)
+  }
+
+  /// Removes every value of a key from a multimap table that is not open elsewhere and journals
+  /// them.
+  pub(crate) fn remove_all<'k, K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: impl Borrow<K::SelfType<'k>>,
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledMultimapTable,
+  {
+    let mut table = self.wtx.open_multimap_table(definition)?;
+    for removed in table.remove_all(key.borrow())? {
+      self.record_multimap_remove(definition, key.borrow(), removed?.value())?;
+    }
+    Ok(())
+  }
+
+  /// Journals a write made on an open table, given the value it replaced.
+  pub(crate) fn record<'k, K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: impl Borrow<K::SelfType<'k>>,
+    old: Option<AccessGuard<V>>,
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledTable,
+  {
+    let old = old.map(|old| V::as_bytes(&old.value()).as_ref().to_vec());
+    self.push(Change {
+      layout: <(K, V)>::LAYOUT,
+      table: definition.name().to_string(),
+      key: K::as_bytes(key.borrow()).as_ref().to_vec(),
+      revert: Revert::Restore(old),
+    })
+  }
+
+  /// Journals a value added to an open multimap table. `existed` is the result of the insert.
+  pub(crate) fn record_multimap_insert<'k, 'v, K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: impl Borrow<K::SelfType<'k>>,
+    value: impl Borrow<V::SelfType<'v>>,
+    existed: bool,
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledMultimapTable,
+  {
+    if existed {
+      return Ok(());
+    }
+    self.push(Change {
+      layout: <(K, V)>::LAYOUT,
+      table: definition.name().to_string(),
+      key: K::as_bytes(key.borrow()).as_ref().to_vec(),
+      revert: Revert::Remove(V::as_bytes(value.borrow()).as_ref().to_vec()),
+    })
+  }
+
+  /// Journals a value dropped from an open multimap table.
+  pub(crate) fn record_multimap_remove<'k, 'v, K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: impl Borrow<K::SelfType<'k>>,
+    value: impl Borrow<V::SelfType<'v>>,
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledMultimapTable,
+  {
+    self.push(Change {
+      layout: <(K, V)>::LAYOUT,
+      table: definition.name().to_string(),
+      key: K::as_bytes(key.borrow()).as_ref().to_vec(),
+      revert: Revert::Insert(V::as_bytes(value.borrow()).as_ref().to_vec()),
+    })
+  }
+
+  fn push(&self, change: Change) -> Result<(), redb::Error> {
+    let markers = self.wtx.open_table(HEIGHT_TO_UNDO_SEQUENCE)?;
+    if markers.is_empty()? {
+      return Ok(());
+    }
+    let mut changes = self.wtx.open_table(UNDO_SEQUENCE_TO_CHANGE)?;
+    let sequence = Self::next_sequence(&markers, &changes)?;
+    changes.insert(sequence, bincode::serialize(&change).unwrap().as_slice())?;
+    Ok(())
+  }
+
+  fn next_sequence(
+    markers: &Table<u64, u64>,
+    changes: &Table<u64, &[u8]>,
+  ) -> Result<u64, redb::Error> {
+    if let Some((sequence, _)) = changes.iter()?.next_back().transpose()? {
+      return Ok(sequence.value() + 1);
+    }
+    Ok(
+      markers
+        .iter()?
+        .next_back()
+        .transpose()?
+        .map(|(_, sequence)| sequence.value())
+        .unwrap_or(0),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, redb::Database, tempfile::NamedTempFile};
+
+  const BALANCES: TableDefinition<&str, &[u8]> = TableDefinition::new("BALANCES");
+  const SATPOINTS: MultimapTableDefinition<u64, &InscriptionIdValue> =
+    MultimapTableDefinition::new("SATPOINTS");
+
+  fn balances(wtx: &WriteTransaction) -> Vec<(String, Vec<u8>)> {
+    wtx
+      .open_table(BALANCES)
+      .unwrap()
+      .iter()
+      .unwrap()
+      .map(|result| {
+        let (key, value) = result.unwrap();
+        (key.value().to_string(), value.value().to_vec())
+      })
+      .collect()
+  }
+
+  #[test]
+  fn rollback_unwinds_blocks_in_reverse() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    let undo = UndoLog::new(&wtx);
+
+    undo.begin_block(1, Some(10)).unwrap();
+    undo.insert(BALANCES, "a", [1].as_slice()).unwrap();
+    undo.insert(BALANCES, "b", [1].as_slice()).unwrap();
+
+    undo.begin_block(2, Some(10)).unwrap();
+    undo.insert(BALANCES, "a", [2].as_slice()).unwrap();
+    undo.remove(BALANCES, "b").unwrap();
+    {
+      let mut satpoints = wtx.open_multimap_table(SATPOINTS).unwrap();
+      let existed = satpoints.insert(7, &[0; 36]).unwrap();
+      undo
+        .record_multimap_insert(SATPOINTS, &7, &[0; 36], existed)
+        .unwrap();
+    }
+
+    undo.begin_block(3, Some(10)).unwrap();
+    undo.insert(BALANCES, "c", [3].as_slice()).unwrap();
+
+    undo.rollback(2).unwrap();
+
+    assert_eq!(
+      balances(&wtx),
+      vec![("a".to_string(), vec![1]), ("b".to_string(), vec![1])]
+    );
+    assert!(wtx
+      .open_multimap_table(SATPOINTS)
+      .unwrap()
+      .get(7)
+      .unwrap()
+      .next()
+      .is_none());
+
+    undo.rollback(1).unwrap();
+    assert!(balances(&wtx).is_empty());
+    assert!(undo.rollback(1).is_err());
+  }
+
+  #[test]
+  fn begin_block_prunes_undo_logs_beyond_depth() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    let undo = UndoLog::new(&wtx);
+
+    for height in 0..5u8 {
+      undo.begin_block(height.into(), Some(2)).unwrap();
+      undo.insert(BALANCES, "a", [height].as_slice()).unwrap();
+    }
+
+    assert!(undo.rollback(2).is_err());
+    undo.rollback(3).unwrap();
+    assert_eq!(balances(&wtx), vec![("a".to_string(), vec![2])]);
+
+    undo.begin_block(3, None).unwrap();
+    undo.insert(BALANCES, "a", [3].as_slice()).unwrap();
+    assert!(wtx
+      .open_table(UNDO_SEQUENCE_TO_CHANGE)
+      .unwrap()
+      .is_empty()
+      .unwrap());
+  }
+}
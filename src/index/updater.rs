@@ -1,9 +1,19 @@
 use {
   self::inscription_updater::InscriptionUpdater,
-  super::{fetcher::Fetcher, *},
+  super::{
+    blk::{self, BlkReader},
+    fetcher::{BlockingFetcher, Fetcher},
+    state_hash,
+    undo::UndoLog,
+    *,
+  },
   crate::okx::{
-    datastore::StateReadWrite,
-    protocol::{BlockContext, ProtocolConfig, ProtocolManager},
+    datastore::{
+      ord::operation::InscriptionOp,
+      table::{TableReader, TableWriter},
+      StateCache, StateReadWrite,
+    },
+    protocol::{BlockContext, ProtocolManager},
   },
   futures::future::try_join_all,
   std::sync::mpsc,
@@ -35,10 +45,13 @@ impl From<Block> for BlockData {
 
 pub(crate) struct Updater<'index> {
   range_cache: HashMap<OutPointValue, Vec<u8>>,
+  chain_height: u64,
   height: u64,
   index: &'index Index,
   index_sats: bool,
+  notifications: Vec<Notification>,
   sat_ranges_since_flush: u64,
+  state_cache: StateCache,
   outputs_cached: u64,
   outputs_inserted_since_flush: u64,
   outputs_traversed: u64,
@@ -48,10 +61,13 @@ impl<'index> Updater<'_> {
   pub(crate) fn new(index: &'index Index) -> Result<Updater<'index>> {
     Ok(Updater {
       range_cache: HashMap::new(),
+      chain_height: 0,
       height: index.block_count()?,
       index,
       index_sats: index.has_sat_index()?,
+      notifications: Vec::new(),
       sat_ranges_since_flush: 0,
+      state_cache: StateCache::new(index.options.state_cache_size()),
       outputs_cached: 0,
       outputs_inserted_since_flush: 0,
       outputs_traversed: 0,
@@ -61,6 +77,8 @@ impl<'index> Updater<'_> {
   pub(crate) fn update_index(&mut self) -> Result {
     let mut wtx = self.index.begin_write()?;
     let starting_height = self.index.client.get_block_count()? + 1;
+    self.chain_height = starting_height - 1;
+    metrics::set_heights(self.height.saturating_sub(1), self.chain_height);
 
     wtx
       .open_table(WRITE_TRANSACTION_STARTING_BLOCK_COUNT_TO_TIMESTAMP)?
@@ -91,12 +109,15 @@ impl<'index> Updater<'_> {
 
     let (mut outpoint_sender, mut tx_out_receiver) = Self::spawn_fetcher(self.index)?;
 
+    let transaction_fetcher = BlockingFetcher::spawn(&self.index.options)?;
+
     let mut uncommitted = 0;
     while let Ok(block) = rx.recv() {
       self.index_block(
         self.index,
         &mut outpoint_sender,
         &mut tx_out_receiver,
+        &transaction_fetcher,
         &mut wtx,
         block,
       )?;
@@ -171,6 +192,12 @@ impl<'index> Updater<'_> {
 
     let first_inscription_height = index.first_inscription_height;
 
+    let blk_reader = if index.options.read_blk_files {
+      Self::open_blk_reader(index, &client, height)
+    } else {
+      None
+    };
+
     thread::spawn(move || loop {
       if let Some(height_limit) = height_limit {
         if height >= height_limit {
@@ -178,7 +205,35 @@ impl<'index> Updater<'_> {
         }
       }
 
-      match Self::get_block_with_retries(&client, height, index_sats, first_inscription_height) {
+      let block = match &blk_reader {
+        Some(reader) if height < reader.block_count() => {
+          let block = if index_sats || height >= first_inscription_height {
+            reader.get_block(height)
+          } else {
+            reader.get_header(height).map(|header| {
+              header.map(|header| Block {
+                header,
+                txdata: Vec::new(),
+              })
+            })
+          };
+
+          // a block that can't be read from the blk files is fetched over RPC instead, so that a
+          // corrupt record doesn't stall every later update on the same block.
+          match block {
+            Ok(Some(block)) => Ok(Some(block)),
+            Ok(None) => Err(anyhow!("block {height} not found in blk files")),
+            Err(err) => Err(err),
+          }
+          .or_else(|err| {
+            log::warn!("Failed to read block {height} from blk files, fetching it over RPC: {err}");
+            Self::get_block_with_retries(&client, height, index_sats, first_inscription_height)
+          })
+        }
+        _ => Self::get_block_with_retries(&client, height, index_sats, first_inscription_height),
+      };
+
+      match block {
         Ok(Some(block)) => {
           if let Err(err) = tx.send(block.into()) {
             log::info!("Block receiver disconnected: {err}");
@@ -197,6 +252,41 @@ impl<'index> Updater<'_> {
     Ok(rx)
   }
 
+  // reads the blocks from the blk files of Bitcoin Core up to a few blocks below the chain tip,
+  // unless the index is already there or the files can't be read.
+  fn open_blk_reader(index: &Index, client: &Client, height: u64) -> Option<BlkReader> {
+    let blocks_dir = index.options.blocks_dir().ok()?;
+
+    let tip = client
+      .get_block_count()
+      .ok()?
+      .checked_sub(blk::RPC_TIP_DISTANCE)
+      .filter(|tip| *tip >= height)?;
+
+    let start = Instant::now();
+    match client
+      .get_block_hash(tip)
+      .map_err(Error::from)
+      .and_then(|tip| BlkReader::open(&blocks_dir, index.get_chain_network(), tip))
+    {
+      Ok(reader) => {
+        log::info!(
+          "Reading blocks {height} to {tip} from {} in {} ms",
+          blocks_dir.display(),
+          start.elapsed().as_millis()
+        );
+        Some(reader)
+      }
+      Err(err) => {
+        log::warn!(
+          "Failed to read blocks from {}, fetching them over RPC: {err}",
+          blocks_dir.display()
+        );
+        None
+      }
+    }
+  }
+
   fn get_block_with_retries(
     client: &Client,
     height: u64,
@@ -317,11 +407,30 @@ impl<'index> Updater<'_> {
     index: &Index,
     outpoint_sender: &mut Sender<OutPoint>,
     tx_out_receiver: &mut Receiver<TxOut>,
+    transaction_fetcher: &BlockingFetcher,
     wtx: &mut WriteTransaction,
     block: BlockData,
   ) -> Result<()> {
     Reorg::detect_reorg(&block, self.height, self.index)?;
 
+    let undo_depth = Reorg::undo_depth(self.index, self.height, self.chain_height);
+
+    // the sat ranges and the protocol state of journaled blocks are written to the tables with
+    // each block, so that they are journaled. Those of earlier blocks still in the caches belong
+    // to no journaled block.
+    if undo_depth.is_some() {
+      if self.index_sats {
+        let mut outpoint_to_sat_ranges = wtx.open_table(OUTPOINT_TO_SAT_RANGES)?;
+        for (outpoint, sat_range) in self.range_cache.drain() {
+          outpoint_to_sat_ranges.insert(&outpoint, sat_range.as_slice())?;
+        }
+      }
+      StateReadWrite::new(wtx, &self.state_cache).flush()?;
+    }
+
+    let undo = UndoLog::new(wtx);
+    undo.begin_block(self.height, undo_depth)?;
+
     let start = Instant::now();
     let mut sat_ranges_written = 0;
     let mut outputs_in_block = 0;
@@ -332,7 +441,7 @@ impl<'index> Updater<'_> {
       return Err(anyhow!("Previous block did not consume all input values"));
     };
 
-    let mut outpoint_to_entry = wtx.open_table(OUTPOINT_TO_ENTRY)?;
+    let outpoint_to_entry = wtx.open_table(OUTPOINT_TO_ENTRY)?;
 
     let index_inscriptions = self.height >= index.first_inscription_height;
 
@@ -370,6 +479,9 @@ impl<'index> Updater<'_> {
       }
     }
 
+    // the inscription updater reaches the table through the transaction.
+    drop(outpoint_to_entry);
+
     let time = timestamp(block.header.time);
 
     log::info!(
@@ -381,18 +493,10 @@ impl<'index> Updater<'_> {
       total_outputs_count,
     );
 
+    let mut block_hash_to_height = wtx.open_table(BLOCK_HASH_TO_HEIGHT)?;
     let mut height_to_block_hash = wtx.open_table(HEIGHT_TO_BLOCK_HASH)?;
     let mut height_to_last_inscription_number =
       wtx.open_table(HEIGHT_TO_LAST_INSCRIPTION_NUMBER)?;
-    let mut inscription_id_to_inscription_entry =
-      wtx.open_table(INSCRIPTION_ID_TO_INSCRIPTION_ENTRY)?;
-    let mut inscription_id_to_satpoint = wtx.open_table(INSCRIPTION_ID_TO_SATPOINT)?;
-    let mut inscription_number_to_inscription_id =
-      wtx.open_table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID)?;
-    let mut reinscription_id_to_seq_num = wtx.open_table(REINSCRIPTION_ID_TO_SEQUENCE_NUMBER)?;
-    let mut sat_to_inscription_id = wtx.open_multimap_table(SAT_TO_INSCRIPTION_ID)?;
-    let mut inscription_id_to_children = wtx.open_multimap_table(INSCRIPTION_ID_TO_CHILDREN)?;
-    let mut satpoint_to_inscription_id = wtx.open_multimap_table(SATPOINT_TO_INSCRIPTION_ID)?;
     let mut statistic_to_count = wtx.open_table(STATISTIC_TO_COUNT)?;
 
     let mut lost_sats = statistic_to_count
@@ -408,16 +512,9 @@ impl<'index> Updater<'_> {
     let mut tx_out_cache = HashMap::new();
     let mut inscription_updater = InscriptionUpdater::new(
       self.height,
-      &mut inscription_id_to_children,
-      &mut inscription_id_to_satpoint,
+      &*wtx,
       tx_out_receiver,
-      &mut inscription_id_to_inscription_entry,
       lost_sats,
-      &mut inscription_number_to_inscription_id,
-      &mut outpoint_to_entry,
-      &mut reinscription_id_to_seq_num,
-      &mut sat_to_inscription_id,
-      &mut satpoint_to_inscription_id,
       block.header.time,
       unbound_inscriptions,
       &mut tx_out_cache,
@@ -449,11 +546,18 @@ impl<'index> Updater<'_> {
               self.outputs_cached += 1;
               sat_ranges
             }
-            None => outpoint_to_sat_ranges
-              .remove(&key)?
-              .ok_or_else(|| anyhow!("Could not find outpoint {} in index", input.previous_output))?
-              .value()
-              .to_vec(),
+            None => {
+              let old = outpoint_to_sat_ranges.remove(&key)?;
+              let sat_ranges = old
+                .as_ref()
+                .ok_or_else(|| {
+                  anyhow!("Could not find outpoint {} in index", input.previous_output)
+                })?
+                .value()
+                .to_vec();
+              undo.record(OUTPOINT_TO_SAT_RANGES, &key, old)?;
+              sat_ranges
+            }
           };
 
           for chunk in sat_ranges.chunks_exact(11) {
@@ -465,6 +569,7 @@ impl<'index> Updater<'_> {
           tx,
           *txid,
           &mut sat_to_satpoint,
+          &undo,
           &mut input_sat_ranges,
           &mut sat_ranges_written,
           &mut outputs_in_block,
@@ -475,11 +580,12 @@ impl<'index> Updater<'_> {
         coinbase_inputs.extend(input_sat_ranges);
       }
 
-      if let Some((tx, txid)) = block.txdata.get(0) {
+      if let Some((tx, txid)) = block.txdata.first() {
         self.index_transaction_sats(
           tx,
           *txid,
           &mut sat_to_satpoint,
+          &undo,
           &mut coinbase_inputs,
           &mut sat_ranges_written,
           &mut outputs_in_block,
@@ -489,14 +595,16 @@ impl<'index> Updater<'_> {
       }
 
       if !coinbase_inputs.is_empty() {
-        let mut lost_sat_ranges = outpoint_to_sat_ranges
-          .remove(&OutPoint::null().store())?
+        let old = outpoint_to_sat_ranges.remove(&OutPoint::null().store())?;
+        let mut lost_sat_ranges = old
+          .as_ref()
           .map(|ranges| ranges.value().to_vec())
           .unwrap_or_default();
+        undo.record(OUTPOINT_TO_SAT_RANGES, &OutPoint::null().store(), old)?;
 
         for (start, end) in coinbase_inputs {
           if !Sat(start).is_common() {
-            sat_to_satpoint.insert(
+            let old = sat_to_satpoint.insert(
               &start,
               &SatPoint {
                 outpoint: OutPoint::null(),
@@ -504,6 +612,7 @@ impl<'index> Updater<'_> {
               }
               .store(),
             )?;
+            undo.record(SAT_TO_SATPOINT, &start, old)?;
           }
 
           lost_sat_ranges.extend_from_slice(&(start, end).store());
@@ -521,6 +630,7 @@ impl<'index> Updater<'_> {
 
     self.index_block_inscription_numbers(
       &mut height_to_last_inscription_number,
+      &undo,
       &inscription_updater,
       index_inscriptions,
     )?;
@@ -528,37 +638,95 @@ impl<'index> Updater<'_> {
     let lost_sats = inscription_updater.lost_sats;
     let unbound_inscriptions = inscription_updater.unbound_inscriptions;
     let operations = inscription_updater.operations.clone();
+    drop(inscription_updater);
 
     // write tx_out to outpoint_to_entry table.
+    let outpoint_to_entry = wtx.table(OUTPOINT_TO_ENTRY);
     for (outpoint, tx_out) in tx_out_cache {
       let mut entry = Vec::new();
       tx_out.consensus_encode(&mut entry)?;
       outpoint_to_entry.insert(&outpoint.store(), entry.as_slice())?;
     }
 
-    std::mem::drop(inscription_id_to_inscription_entry);
-    std::mem::drop(outpoint_to_entry);
+    metrics::observe_block_phase("inscriptions", start.elapsed());
 
     // Create a protocol manager to index the block of brc20, brc20s data.
-    let config = ProtocolConfig::new_with_options(&index.options);
-    ProtocolManager::new(&index.client, &StateReadWrite::new(wtx), &config).index_block(
-      BlockContext {
-        network: index.get_chain_network(),
-        blockheight: self.height,
-        blocktime: block.header.time,
-      },
-      &block,
-      operations,
-    )?;
+    let notify = index.notifier.has_subscribers();
+    let webhooks = webhook::has_subscriptions(wtx)?;
+    let mut notifications = Vec::new();
+    let state = StateReadWrite::new(wtx, &self.state_cache);
+    ProtocolManager::new(Some(&index.client), &state, &index.protocol_config)
+      .with_fetcher(transaction_fetcher)
+      .index_block(
+        BlockContext {
+          network: index.get_chain_network(),
+          blockheight: self.height,
+          blocktime: block.header.time,
+        },
+        &block,
+        operations,
+        (notify || webhooks).then_some(&mut notifications),
+      )?;
+    state.record_changes()?;
+
+    if undo_depth.is_some() {
+      state.flush()?;
+    }
+
+    if self.index_sats && undo_depth.is_some() {
+      let mut outpoint_to_sat_ranges = wtx.open_table(OUTPOINT_TO_SAT_RANGES)?;
+      for (outpoint, sat_range) in self.range_cache.drain() {
+        let old = outpoint_to_sat_ranges.insert(&outpoint, sat_range.as_slice())?;
+        undo.record(OUTPOINT_TO_SAT_RANGES, &outpoint, old)?;
+      }
+    }
+
+    if index.options.enable_index_brc20 || index.options.enable_index_brc20s {
+      state_hash::commit_block(wtx, self.height)?;
+    }
+
+    if webhooks {
+      webhook::enqueue_block(
+        wtx,
+        index.get_chain_network(),
+        self.height,
+        &notifications,
+        webhook::now(),
+      )?;
+    }
 
-    statistic_to_count.insert(&Statistic::LostSats.key(), &lost_sats)?;
+    if notify {
+      self.notifications.extend(notifications);
+      self.notifications.push(Notification::BlockConnected {
+        height: self.height,
+        blockhash: block.header.block_hash(),
+      });
+    }
 
-    statistic_to_count.insert(&Statistic::UnboundInscriptions.key(), &unbound_inscriptions)?;
+    let old = statistic_to_count.insert(&Statistic::LostSats.key(), &lost_sats)?;
+    undo.record(STATISTIC_TO_COUNT, &Statistic::LostSats.key(), old)?;
 
-    height_to_block_hash.insert(&self.height, &block.header.block_hash().store())?;
+    let old =
+      statistic_to_count.insert(&Statistic::UnboundInscriptions.key(), &unbound_inscriptions)?;
+    undo.record(
+      STATISTIC_TO_COUNT,
+      &Statistic::UnboundInscriptions.key(),
+      old,
+    )?;
+
+    let old = height_to_block_hash.insert(&self.height, &block.header.block_hash().store())?;
+    undo.record(HEIGHT_TO_BLOCK_HASH, &self.height, old)?;
+
+    let old = block_hash_to_height.insert(&block.header.block_hash().store(), &self.height)?;
+    undo.record(
+      BLOCK_HASH_TO_HEIGHT,
+      &block.header.block_hash().store(),
+      old,
+    )?;
 
     self.height += 1;
     self.outputs_traversed += outputs_in_block;
+    metrics::set_heights(self.height - 1, self.chain_height);
 
     log::info!(
       "Wrote {sat_ranges_written} sat ranges from {outputs_in_block} outputs in {} ms",
@@ -573,10 +741,11 @@ impl<'index> Updater<'_> {
     tx: &Transaction,
     txid: Txid,
     sat_to_satpoint: &mut Table<u64, &SatPointValue>,
+    undo: &UndoLog,
     input_sat_ranges: &mut VecDeque<(u64, u64)>,
     sat_ranges_written: &mut u64,
     outputs_traversed: &mut u64,
-    inscription_updater: &mut InscriptionUpdater,
+    inscription_updater: &mut InscriptionUpdater<WriteTransaction>,
     index_inscriptions: bool,
   ) -> Result {
     if index_inscriptions {
@@ -597,7 +766,7 @@ impl<'index> Updater<'_> {
           .ok_or_else(|| anyhow!("insufficient inputs for transaction outputs"))?;
 
         if !Sat(range.0).is_common() {
-          sat_to_satpoint.insert(
+          let old = sat_to_satpoint.insert(
             &range.0,
             &SatPoint {
               outpoint,
@@ -605,6 +774,7 @@ impl<'index> Updater<'_> {
             }
             .store(),
           )?;
+          undo.record(SAT_TO_SATPOINT, &range.0, old)?;
         }
 
         let count = range.1 - range.0;
@@ -637,25 +807,28 @@ impl<'index> Updater<'_> {
   fn index_block_inscription_numbers(
     &mut self,
     height_to_inscription_number: &mut Table<u64, (i64, i64)>,
-    inscription_updater: &InscriptionUpdater,
+    undo: &UndoLog,
+    inscription_updater: &InscriptionUpdater<WriteTransaction>,
     index_inscription: bool,
   ) -> Result {
     if !index_inscription {
       return Ok(());
     }
 
-    height_to_inscription_number.insert(
+    let old = height_to_inscription_number.insert(
       &self.height,
       (
         inscription_updater.next_number,
         inscription_updater.next_cursed_number,
       ),
     )?;
+    undo.record(HEIGHT_TO_LAST_INSCRIPTION_NUMBER, &self.height, old)?;
 
     Ok(())
   }
 
   fn commit(&mut self, wtx: WriteTransaction) -> Result {
+    let start = Instant::now();
     log::info!(
       "Committing at block height {}, {} outputs traversed, {} in map, {} cached",
       self.height,
@@ -681,16 +854,83 @@ impl<'index> Updater<'_> {
       self.outputs_inserted_since_flush = 0;
     }
 
+    StateReadWrite::new(&wtx, &self.state_cache).flush()?;
+
     Index::increment_statistic(&wtx, Statistic::OutputsTraversed, self.outputs_traversed)?;
     self.outputs_traversed = 0;
     Index::increment_statistic(&wtx, Statistic::SatRanges, self.sat_ranges_since_flush)?;
     self.sat_ranges_since_flush = 0;
     Index::increment_statistic(&wtx, Statistic::Commits, 1)?;
+    wtx
+      .open_table(STATISTIC_TO_COUNT)?
+      .insert(&Statistic::ChainHeight.key(), &self.chain_height)?;
 
     wtx.commit()?;
+    // blocks are committed in batches, so this is the time of a batch rather than of a block.
+    metrics::observe_block_phase("commit", start.elapsed());
 
-    Reorg::update_savepoints(self.index, self.height)?;
+    // only publish what has been committed, so subscribers never see state that can't be read.
+    self.index.notifier.notify(self.notifications.drain(..));
 
     Ok(())
   }
 }
+
+/// Indexes the inscriptions of `transactions` into `tables` as if they were the next block at
+/// `height`, and returns their inscription operations. The value of every input must be in
+/// `tx_out_cache`, in the index or be an output of an earlier transaction. Inscriptions sent to fees
+/// are left floating, since there is no coinbase to land in.
+pub(super) fn index_transactions_inscriptions<T: TableWriter>(
+  tables: &T,
+  height: u64,
+  timestamp: u32,
+  transactions: &[(Transaction, Txid)],
+  tx_out_cache: &mut HashMap<OutPoint, TxOut>,
+) -> Result<HashMap<Txid, Vec<InscriptionOp>>> {
+  let statistic_to_count = tables.table(STATISTIC_TO_COUNT);
+
+  let lost_sats = statistic_to_count
+    .get(Statistic::LostSats.key())?
+    .map(|lost_sats| lost_sats.value())
+    .unwrap_or(0);
+
+  let unbound_inscriptions = statistic_to_count
+    .get(Statistic::UnboundInscriptions.key())?
+    .map(|unbound_inscriptions| unbound_inscriptions.value())
+    .unwrap_or(0);
+
+  // nothing is ever fetched, a missing input fails instead of blocking.
+  let (_, mut tx_out_receiver) = tokio::sync::mpsc::channel::<TxOut>(1);
+
+  let mut inscription_updater = InscriptionUpdater::new(
+    height,
+    tables,
+    &mut tx_out_receiver,
+    lost_sats,
+    timestamp,
+    unbound_inscriptions,
+    tx_out_cache,
+  )?;
+
+  for (tx, txid) in transactions {
+    inscription_updater.index_transaction_inscriptions(tx, *txid, None)?;
+  }
+
+  let operations = inscription_updater.operations.clone();
+  let lost_sats = inscription_updater.lost_sats;
+  let unbound_inscriptions = inscription_updater.unbound_inscriptions;
+  drop(inscription_updater);
+
+  // later transactions are indexed on top of these.
+  statistic_to_count.insert(Statistic::LostSats.key(), lost_sats)?;
+  statistic_to_count.insert(Statistic::UnboundInscriptions.key(), unbound_inscriptions)?;
+
+  let outpoint_to_entry = tables.table(OUTPOINT_TO_ENTRY);
+  for (outpoint, tx_out) in tx_out_cache.iter() {
+    let mut entry = Vec::new();
+    tx_out.consensus_encode(&mut entry)?;
+    outpoint_to_entry.insert(&outpoint.store(), entry.as_slice())?;
+  }
+
+  Ok(operations)
+}
@@ -1,6 +1,9 @@
 use {
   super::*,
-  crate::okx::datastore::ord::operation::{Action, InscriptionOp},
+  crate::okx::datastore::{
+    ord::operation::{Action, InscriptionOp},
+    table::TableWriter,
+  },
   inscription::Curse,
 };
 
@@ -25,57 +28,33 @@ enum Origin {
   Old,
 }
 
-pub(super) struct InscriptionUpdater<'a, 'db, 'tx> {
+pub(super) struct InscriptionUpdater<'a, T> {
   flotsam: Vec<Flotsam>,
   pub(super) operations: HashMap<Txid, Vec<InscriptionOp>>,
   height: u64,
-  id_to_children:
-    &'a mut MultimapTable<'db, 'tx, &'static InscriptionIdValue, &'static InscriptionIdValue>,
-  id_to_satpoint: &'a mut Table<'db, 'tx, &'static InscriptionIdValue, &'static SatPointValue>,
+  tables: &'a T,
   tx_out_receiver: &'a mut Receiver<TxOut>,
-  id_to_entry: &'a mut Table<'db, 'tx, &'static InscriptionIdValue, InscriptionEntryValue>,
   pub(super) lost_sats: u64,
   pub(super) next_cursed_number: i64,
   pub(super) next_number: i64,
-  number_to_id: &'a mut Table<'db, 'tx, i64, &'static InscriptionIdValue>,
-  outpoint_to_entry: &'a mut Table<'db, 'tx, &'static OutPointValue, &'static [u8]>,
   reward: u64,
-  reinscription_id_to_seq_num: &'a mut Table<'db, 'tx, &'static InscriptionIdValue, u64>,
-  sat_to_inscription_id: &'a mut MultimapTable<'db, 'tx, u64, &'static InscriptionIdValue>,
-  satpoint_to_id:
-    &'a mut MultimapTable<'db, 'tx, &'static SatPointValue, &'static InscriptionIdValue>,
   timestamp: u32,
   pub(super) unbound_inscriptions: u64,
   tx_out_cache: &'a mut HashMap<OutPoint, TxOut>,
 }
 
-impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
+impl<'a, T: TableWriter> InscriptionUpdater<'a, T> {
   pub(super) fn new(
     height: u64,
-    id_to_children: &'a mut MultimapTable<
-      'db,
-      'tx,
-      &'static InscriptionIdValue,
-      &'static InscriptionIdValue,
-    >,
-    id_to_satpoint: &'a mut Table<'db, 'tx, &'static InscriptionIdValue, &'static SatPointValue>,
+    tables: &'a T,
     tx_out_receiver: &'a mut Receiver<TxOut>,
-    id_to_entry: &'a mut Table<'db, 'tx, &'static InscriptionIdValue, InscriptionEntryValue>,
     lost_sats: u64,
-    number_to_id: &'a mut Table<'db, 'tx, i64, &'static InscriptionIdValue>,
-    outpoint_to_entry: &'a mut Table<'db, 'tx, &'static OutPointValue, &'static [u8]>,
-    reinscription_id_to_seq_num: &'a mut Table<'db, 'tx, &'static InscriptionIdValue, u64>,
-    sat_to_inscription_id: &'a mut MultimapTable<'db, 'tx, u64, &'static InscriptionIdValue>,
-    satpoint_to_id: &'a mut MultimapTable<
-      'db,
-      'tx,
-      &'static SatPointValue,
-      &'static InscriptionIdValue,
-    >,
     timestamp: u32,
     unbound_inscriptions: u64,
     tx_out_cache: &'a mut HashMap<OutPoint, TxOut>,
   ) -> Result<Self> {
+    let number_to_id = tables.table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID);
+
     let next_cursed_number = number_to_id
       .iter()?
       .next()
@@ -94,19 +73,12 @@ impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
       flotsam: Vec::new(),
       operations: HashMap::new(),
       height,
-      id_to_children,
-      id_to_satpoint,
+      tables,
       tx_out_receiver,
-      id_to_entry,
       lost_sats,
       next_cursed_number,
       next_number,
-      number_to_id,
-      outpoint_to_entry,
       reward: Height(height).subsidy(),
-      reinscription_id_to_seq_num,
-      sat_to_inscription_id,
-      satpoint_to_id,
       timestamp,
       unbound_inscriptions,
       tx_out_cache,
@@ -133,11 +105,9 @@ impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
       }
 
       // find existing inscriptions on input (transfers of inscriptions)
-      for (old_satpoint, inscription_id) in Index::inscriptions_on_output_ordered(
-        self.reinscription_id_to_seq_num,
-        self.satpoint_to_id,
-        tx_in.previous_output,
-      )? {
+      for (old_satpoint, inscription_id) in
+        Index::inscriptions_on_output_ordered(self.tables, tx_in.previous_output)?
+      {
         let offset = total_input_value + old_satpoint.offset;
         floating_inscriptions.push(Flotsam {
           txid,
@@ -160,7 +130,7 @@ impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
       {
         tx_out.value
       } else if let Some(tx_out) =
-        Index::transaction_output_by_outpoint(self.outpoint_to_entry, tx_in.previous_output)?
+        Index::transaction_output_by_outpoint(self.tables, tx_in.previous_output)?
       {
         tx_out.value
       } else {
@@ -196,15 +166,14 @@ impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
         } else if inscription.tx_in_offset != 0 {
           Some(Curse::NotAtOffsetZero)
         } else if inscribed_offsets.contains_key(&offset) {
-          let seq_num = self.reinscription_id_to_seq_num.len()?;
+          let reinscription_id_to_seq_num = self.tables.table(REINSCRIPTION_ID_TO_SEQUENCE_NUMBER);
+          let seq_num = reinscription_id_to_seq_num.len()?;
 
           let sat = Self::calculate_sat(input_sat_ranges, offset);
           log::info!("processing reinscription {inscription_id} on sat {:?}: sequence number {seq_num}, inscribed offsets {:?}", sat, inscribed_offsets);
 
           // if reinscription track its ordering
-          self
-            .reinscription_id_to_seq_num
-            .insert(&inscription_id.store(), seq_num)?;
+          reinscription_id_to_seq_num.insert(&inscription_id.store(), seq_num)?;
 
           Some(Curse::Reinscription)
         } else {
@@ -224,7 +193,11 @@ impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
           let initial_inscription_is_cursed = inscribed_offsets
             .get(&offset)
             .and_then(|(inscription_id, _count)| {
-              match self.id_to_entry.get(&inscription_id.store()) {
+              match self
+                .tables
+                .table(INSCRIPTION_ID_TO_INSCRIPTION_ENTRY)
+                .get(&inscription_id.store())
+              {
                 Ok(option) => option.map(|entry| {
                   let loaded_entry = InscriptionEntry::load(entry.value());
                   loaded_entry.number < 0
@@ -432,7 +405,8 @@ impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
     let unbound = match flotsam.origin {
       Origin::Old => {
         self
-          .satpoint_to_id
+          .tables
+          .multimap_table(SATPOINT_TO_INSCRIPTION_ID)
           .remove_all(&flotsam.old_satpoint.store())?;
         false
       }
@@ -455,7 +429,10 @@ impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
           next_number
         };
 
-        self.number_to_id.insert(number, &inscription_id)?;
+        self
+          .tables
+          .table(INSCRIPTION_NUMBER_TO_INSCRIPTION_ID)
+          .insert(number, &inscription_id)?;
 
         let sat = if unbound {
           None
@@ -464,25 +441,32 @@ impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
         };
 
         if let Some(Sat(n)) = sat {
-          self.sat_to_inscription_id.insert(&n, &inscription_id)?;
+          self
+            .tables
+            .multimap_table(SAT_TO_INSCRIPTION_ID)
+            .insert(&n, &inscription_id)?;
         }
 
-        self.id_to_entry.insert(
-          &inscription_id,
-          &InscriptionEntry {
-            fee,
-            height: self.height,
-            number,
-            parent,
-            sat,
-            timestamp: self.timestamp,
-          }
-          .store(),
-        )?;
+        self
+          .tables
+          .table(INSCRIPTION_ID_TO_INSCRIPTION_ENTRY)
+          .insert(
+            &inscription_id,
+            &InscriptionEntry {
+              fee,
+              height: self.height,
+              number,
+              parent,
+              sat,
+              timestamp: self.timestamp,
+            }
+            .store(),
+          )?;
 
         if let Some(parent) = parent {
           self
-            .id_to_children
+            .tables
+            .multimap_table(INSCRIPTION_ID_TO_CHILDREN)
             .insert(&parent.store(), &inscription_id)?;
         }
 
@@ -507,7 +491,8 @@ impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
       .push(InscriptionOp {
         txid: flotsam.txid,
         inscription_number: self
-          .id_to_entry
+          .tables
+          .table(INSCRIPTION_ID_TO_INSCRIPTION_ENTRY)
           .get(&flotsam.inscription_id.store())?
           .map(|entry| InscriptionEntry::load(entry.value()).number),
         inscription_id: flotsam.inscription_id,
@@ -529,8 +514,14 @@ impl<'a, 'db, 'tx> InscriptionUpdater<'a, 'db, 'tx> {
         new_satpoint: Some(Entry::load(satpoint)),
       });
 
-    self.satpoint_to_id.insert(&satpoint, &inscription_id)?;
-    self.id_to_satpoint.insert(&inscription_id, &satpoint)?;
+    self
+      .tables
+      .multimap_table(SATPOINT_TO_INSCRIPTION_ID)
+      .insert(&satpoint, &inscription_id)?;
+    self
+      .tables
+      .table(INSCRIPTION_ID_TO_SATPOINT)
+      .insert(&inscription_id, &satpoint)?;
 
     Ok(())
   }
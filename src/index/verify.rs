@@ -0,0 +1,613 @@
+use {
+  super::*,
+  crate::okx::{
+    datastore::{
+      keys,
+      ord::{Action, InscriptionOp},
+      table::{TableReader, TableSpec, TableWriter},
+      ScriptKey, StateCache, StateReadWrite,
+    },
+    protocol::brc20s::params::PID_BYTE_COUNT,
+    protocol::{BlockContext, ProtocolConfig, ProtocolManager},
+  },
+  redb::{MultimapTableHandle, ReadTransaction, RedbKey, RedbValue, TableHandle},
+  serde::de::DeserializeOwned,
+  std::ops::Bound,
+  tempfile::NamedTempFile,
+  undo::{JournaledMultimapTable, JournaledTable, TableVisitor},
+};
+
+/// The scratch database is committed every this many blocks to bound the size of a write transaction.
+const COMMIT_INTERVAL: u64 = 1000;
+
+/// The result of re-executing the stored inscription operations.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Verification {
+  pub heights: u64,
+  pub transactions: u64,
+  pub mismatches: Vec<Mismatch>,
+}
+
+/// An entry that differs between the live index and the re-executed state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mismatch {
+  pub table: String,
+  pub key: String,
+  pub address: Option<String>,
+  /// The tick, tick id or pid of the entry.
+  pub tick: Option<String>,
+  /// The entry in the live index, if any.
+  pub indexed: Option<String>,
+  /// The entry produced by re-executing the operations, if any.
+  pub verified: Option<String>,
+}
+
+type Scope = (Option<String>, Option<String>);
+
+/// How the entries of a consensus table are shown in a mismatch.
+#[derive(Clone, Copy)]
+struct Format {
+  decode: fn(&[u8]) -> String,
+  scope: fn(&[u8], Option<&[u8]>, Option<&[u8]>, &ScriptKeys) -> Scope,
+}
+
+/// Resolves the script key hashes keys start with, from either database.
+struct ScriptKeys<'a, 'db> {
+  indexed: &'a ReadTransaction<'db>,
+  verified: &'a ReadTransaction<'db>,
+}
+
+impl<'a, 'db> ScriptKeys<'a, 'db> {
+  fn address(&self, key: &[u8]) -> Option<String> {
+    let hash: &keys::ScriptKeyHash = key.get(..keys::SCRIPT_KEY_HASH_LEN)?.try_into().unwrap();
+    [self.indexed, self.verified].iter().find_map(|rtx| {
+      rtx
+        .open_table(keys::SCRIPT_KEYS)
+        .ok()?
+        .get(hash)
+        .ok()?
+        .map(|script| {
+          bincode::deserialize::<ScriptKey>(script.value())
+            .unwrap()
+            .to_string()
+        })
+    })
+  }
+}
+
+const FORMATS: [(TableDefinition<'static, &[u8], &[u8]>, Format); 8] = [
+  (
+    brc20_db::BRC20_BALANCES,
+    Format {
+      decode: decode::<brc20::Balance>,
+      scope: script_tick_scope,
+    },
+  ),
+  (
+    brc20_db::BRC20_TOKEN,
+    Format {
+      decode: decode::<brc20::TokenInfo>,
+      scope: tick_scope,
+    },
+  ),
+  (
+    brc20_db::BRC20_EVENTS,
+    Format {
+      decode: decode::<Vec<brc20::Receipt>>,
+      scope: brc20_receipt_scope,
+    },
+  ),
+  (
+    brc20s_db::BRC20S_BALANCES,
+    Format {
+      decode: decode::<brc20s::Balance>,
+      scope: script_id_scope,
+    },
+  ),
+  (
+    brc20s_db::BRC20S_TICKINFO,
+    Format {
+      decode: decode::<brc20s::TickInfo>,
+      scope: id_scope,
+    },
+  ),
+  (
+    brc20s_db::BRC20S_PID_TO_POOLINFO,
+    Format {
+      decode: decode::<brc20s::PoolInfo>,
+      scope: id_scope,
+    },
+  ),
+  (
+    brc20s_db::BRC20S_PID_TO_USERINFO,
+    Format {
+      decode: decode::<brc20s::UserInfo>,
+      scope: script_id_scope,
+    },
+  ),
+  (
+    brc20s_db::BRC20S_TXID_TO_RECEIPTS,
+    Format {
+      decode: decode::<Vec<brc20s::Receipt>>,
+      scope: brc20s_receipt_scope,
+    },
+  ),
+];
+
+// the entries of tables without a known format are shown in hex.
+fn format(table: &str) -> Format {
+  FORMATS
+    .iter()
+    .find(|(definition, _)| definition.name() == table)
+    .map_or(
+      Format {
+        decode: decode_hex,
+        scope: no_scope,
+      },
+      |(_, format)| *format,
+    )
+}
+
+// amounts are u128, which JSON values can't hold, so entries are reported in their debug format.
+fn decode<T: DeserializeOwned + fmt::Debug>(value: &[u8]) -> String {
+  format!("{:?}", bincode::deserialize::<T>(value).unwrap())
+}
+
+fn decode_hex(value: &[u8]) -> String {
+  hex::encode(value)
+}
+
+// BRC20 ticks are keyed by their zero padded lowercase name.
+fn decode_tick(tick: &[u8]) -> String {
+  std::str::from_utf8(tick)
+    .map(|tick| tick.trim_end_matches('\0').to_string())
+    .unwrap_or_else(|_| hex::encode(tick))
+}
+
+// BRC20S tick ids are shown in hex, pids are keyed by their name.
+fn decode_id(id: &[u8]) -> String {
+  match std::str::from_utf8(id) {
+    Ok(pid) if id.len() == PID_BYTE_COUNT => pid.to_string(),
+    _ => hex::encode(id),
+  }
+}
+
+fn script_tick_scope(
+  key: &[u8],
+  _: Option<&[u8]>,
+  _: Option<&[u8]>,
+  scripts: &ScriptKeys,
+) -> Scope {
+  (
+    scripts.address(key),
+    key.get(keys::SCRIPT_KEY_HASH_LEN..).map(decode_tick),
+  )
+}
+
+fn tick_scope(key: &[u8], _: Option<&[u8]>, _: Option<&[u8]>, _: &ScriptKeys) -> Scope {
+  (None, Some(decode_tick(key)))
+}
+
+fn script_id_scope(key: &[u8], _: Option<&[u8]>, _: Option<&[u8]>, scripts: &ScriptKeys) -> Scope {
+  (
+    scripts.address(key),
+    key.get(keys::SCRIPT_KEY_HASH_LEN..).map(decode_id),
+  )
+}
+
+fn no_scope(_: &[u8], _: Option<&[u8]>, _: Option<&[u8]>, _: &ScriptKeys) -> Scope {
+  (None, None)
+}
+
+fn id_scope(key: &[u8], _: Option<&[u8]>, _: Option<&[u8]>, _: &ScriptKeys) -> Scope {
+  (None, Some(decode_id(key)))
+}
+
+fn brc20_receipt_scope(
+  _: &[u8],
+  indexed: Option<&[u8]>,
+  verified: Option<&[u8]>,
+  _: &ScriptKeys,
+) -> Scope {
+  first_difference::<brc20::Receipt>(indexed, verified)
+    .map(|receipt| {
+      (
+        Some(receipt.from.to_string()),
+        receipt.result.ok().map(|event| event.tick().to_string()),
+      )
+    })
+    .unwrap_or_default()
+}
+
+fn brc20s_receipt_scope(
+  _: &[u8],
+  indexed: Option<&[u8]>,
+  verified: Option<&[u8]>,
+  _: &ScriptKeys,
+) -> Scope {
+  first_difference::<brc20s::Receipt>(indexed, verified)
+    .map(|receipt| {
+      (
+        Some(receipt.from.to_string()),
+        receipt
+          .result
+          .ok()
+          .and_then(|events| events.first().map(|event| event.tick_id().hex())),
+      )
+    })
+    .unwrap_or_default()
+}
+
+// returns the first receipt of a transaction that differs, preferring the indexed one.
+fn first_difference<T: DeserializeOwned + PartialEq>(
+  indexed: Option<&[u8]>,
+  verified: Option<&[u8]>,
+) -> Option<T> {
+  let mut indexed: Vec<T> = indexed
+    .map(|value| bincode::deserialize(value).unwrap())
+    .unwrap_or_default();
+  let mut verified: Vec<T> = verified
+    .map(|value| bincode::deserialize(value).unwrap())
+    .unwrap_or_default();
+
+  let position = (0..indexed.len().max(verified.len()))
+    .find(|&position| indexed.get(position) != verified.get(position))?;
+
+  if position < indexed.len() {
+    Some(indexed.swap_remove(position))
+  } else {
+    Some(verified.swap_remove(position))
+  }
+}
+
+/// Reads the tables of the protocols from the scratch database and every other table from the live
+/// index, which already holds everything the resolvers would write to them.
+struct ScratchTables<'a, 'db, 'sdb> {
+  live: &'a ReadTransaction<'db>,
+  scratch: &'a WriteTransaction<'sdb>,
+  protocol_tables: &'a HashSet<&'a str>,
+}
+
+impl<'a, 'db, 'sdb> ScratchTables<'a, 'db, 'sdb> {
+  fn is_scratch(&self, name: &str) -> bool {
+    self.protocol_tables.contains(name)
+  }
+}
+
+impl<'a, 'db, 'sdb> TableReader for ScratchTables<'a, 'db, 'sdb> {
+  fn get_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<Option<Vec<u8>>, redb::Error> {
+    if self.is_scratch(definition.name()) {
+      self.scratch.get_bytes(definition, key)
+    } else {
+      self.live.get_bytes(definition, key)
+    }
+  }
+
+  fn range_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    start: Bound<&[u8]>,
+    end: Bound<&[u8]>,
+    reverse: bool,
+    limit: usize,
+  ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, redb::Error> {
+    if self.is_scratch(definition.name()) {
+      self
+        .scratch
+        .range_bytes(definition, start, end, reverse, limit)
+    } else {
+      self
+        .live
+        .range_bytes(definition, start, end, reverse, limit)
+    }
+  }
+
+  fn len<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+  ) -> Result<u64, redb::Error> {
+    if self.is_scratch(definition.name()) {
+      self.scratch.len(definition)
+    } else {
+      self.live.len(definition)
+    }
+  }
+
+  fn get_all_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<Vec<Vec<u8>>, redb::Error> {
+    if self.is_scratch(definition.name()) {
+      self.scratch.get_all_bytes(definition, key)
+    } else {
+      self.live.get_all_bytes(definition, key)
+    }
+  }
+
+  fn range_all_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    start: Bound<&[u8]>,
+    end: Bound<&[u8]>,
+  ) -> Result<Vec<(Vec<u8>, Vec<Vec<u8>>)>, redb::Error> {
+    if self.is_scratch(definition.name()) {
+      self.scratch.range_all_bytes(definition, start, end)
+    } else {
+      self.live.range_all_bytes(definition, start, end)
+    }
+  }
+}
+
+impl<'a, 'db, 'sdb> TableWriter for ScratchTables<'a, 'db, 'sdb> {
+  fn insert_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: &[u8],
+    value: &[u8],
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledTable,
+  {
+    if self.is_scratch(definition.name()) {
+      self.scratch.insert_bytes(definition, key, value)?;
+    }
+    Ok(())
+  }
+
+  fn remove_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledTable,
+  {
+    if self.is_scratch(definition.name()) {
+      self.scratch.remove_bytes(definition, key)?;
+    }
+    Ok(())
+  }
+
+  fn insert_multimap_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: &[u8],
+    value: &[u8],
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledMultimapTable,
+  {
+    if self.is_scratch(definition.name()) {
+      self.scratch.insert_multimap_bytes(definition, key, value)?;
+    }
+    Ok(())
+  }
+
+  fn remove_all_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledMultimapTable,
+  {
+    if self.is_scratch(definition.name()) {
+      self.scratch.remove_all_bytes(definition, key)?;
+    }
+    Ok(())
+  }
+}
+
+// The inscriptions revealed by a transaction, in envelope order, rebuilt from its operations.
+fn new_inscriptions(txid: Txid, operations: &[InscriptionOp]) -> Vec<Inscription> {
+  let mut inscriptions = Vec::new();
+  for operation in operations {
+    if let Action::New { inscription, .. } = &operation.action {
+      if operation.inscription_id.txid != txid {
+        continue;
+      }
+      let index = usize::try_from(operation.inscription_id.index).unwrap();
+      if inscriptions.len() <= index {
+        inscriptions.resize(index + 1, Inscription::default());
+      }
+      inscriptions[index] = inscription.clone();
+    }
+  }
+  inscriptions
+}
+
+/// Re-executes the stored inscription operations into a scratch database created in `dir`, and
+/// compares the resulting BRC20 and BRC20S state with the live index.
+pub(crate) fn run(database: &Database, options: &Options, dir: &Path) -> Result<Verification> {
+  if !options.enable_save_ord_receipts {
+    bail!("index verify requires an index built with --enable-save-ord-receipts");
+  }
+
+  let config = ProtocolConfig::new_with_options(options)?;
+  let network = options.chain().network();
+  let rtx = database.begin_read()?;
+
+  let end = rtx
+    .open_table(HEIGHT_TO_BLOCK_HASH)?
+    .range(0..)?
+    .next_back()
+    .and_then(|result| result.ok())
+    .map(|(height, _hash)| height.value() + 1)
+    .unwrap_or(0);
+
+  // the protocols' tables are re-executed into the scratch database, of which the consensus
+  // tables are compared with the live index.
+  let protocols = ProtocolInfo::all(&config);
+  let protocol_tables = protocols
+    .iter()
+    .flat_map(|protocol| protocol.tables)
+    .copied()
+    .collect::<Vec<TableSpec>>();
+  let consensus_tables = protocols
+    .iter()
+    .flat_map(|protocol| protocol.consensus_tables)
+    .map(|name| {
+      protocol_tables
+        .iter()
+        .find(|table| table.name == *name)
+        .copied()
+        .ok_or_else(|| anyhow!("consensus table {name} is not a table of its protocol"))
+    })
+    .collect::<Result<Vec<TableSpec>>>()?;
+
+  let scratch_file = NamedTempFile::new_in(dir)?;
+  let scratch = Database::create(scratch_file.path())?;
+
+  {
+    let wtx = scratch.begin_write()?;
+    for table in &protocol_tables {
+      table.create(&wtx)?;
+    }
+    wtx.commit()?;
+  }
+
+  let scratch_table_names = protocol_tables
+    .iter()
+    .map(|table| table.name)
+    .collect::<HashSet<&str>>();
+  let cache = StateCache::new(options.state_cache_size());
+  let live = ord::DataStore::new(&rtx);
+
+  let mut heights = 0;
+  let mut transactions = 0;
+  let mut start = options.first_inscription_height();
+
+  while start < end {
+    let wtx = scratch.begin_write()?;
+    {
+      let tables = ScratchTables {
+        live: &rtx,
+        scratch: &wtx,
+        protocol_tables: &scratch_table_names,
+      };
+      let state = StateReadWrite::new(&tables, &cache);
+      let protocol_man = ProtocolManager::new(None, &state, &config);
+
+      for height in start..end.min(start + COMMIT_INTERVAL) {
+        let Some((blocktime, txids)) = live.get_block_transactions(height)? else {
+          continue;
+        };
+        let context = BlockContext {
+          network,
+          blockheight: height,
+          blocktime,
+        };
+
+        for txid in txids {
+          let operations = live.get_transaction_operations(&txid)?;
+          let new_inscriptions = new_inscriptions(txid, &operations);
+          for msg in protocol_man.resolve_operations(
+            context,
+            &new_inscriptions,
+            &operations,
+            &HashMap::new(),
+          )? {
+            protocol_man.execute_message(context, &msg)?;
+          }
+          transactions += 1;
+        }
+        // the scratch index keeps no state hashes, this only starts the changes of the next block.
+        state.record_changes()?;
+        heights += 1;
+      }
+
+      drop(protocol_man);
+      state.flush()?;
+    }
+    wtx.commit()?;
+    start += COMMIT_INTERVAL;
+  }
+
+  let scratch_rtx = scratch.begin_read()?;
+  let mut mismatches = Vec::new();
+  let scripts = ScriptKeys {
+    indexed: &rtx,
+    verified: &scratch_rtx,
+  };
+  for table in &consensus_tables {
+    table.layout.visit(
+      table.name,
+      Diff {
+        scripts: &scripts,
+        mismatches: &mut mismatches,
+      },
+    )?;
+  }
+
+  Ok(Verification {
+    heights,
+    transactions,
+    mismatches,
+  })
+}
+
+/// Compares a consensus table between the live index and the re-executed state.
+struct Diff<'a, 'b, 'db> {
+  scripts: &'a ScriptKeys<'b, 'db>,
+  mismatches: &'a mut Vec<Mismatch>,
+}
+
+impl<'a, 'b, 'db> TableVisitor for Diff<'a, 'b, 'db> {
+  type Output = Result;
+
+  fn visit<K: RedbKey + 'static, V: RedbValue + 'static>(
+    self,
+    definition: TableDefinition<K, V>,
+  ) -> Result {
+    let format = format(definition.name());
+    let indexed = self.scripts.indexed.open_table(definition)?;
+    let verified = self.scripts.verified.open_table(definition)?;
+
+    let mut mismatch = |key: &[u8], indexed: Option<&[u8]>, verified: Option<&[u8]>| {
+      let (address, tick) = (format.scope)(key, indexed, verified, self.scripts);
+      self.mismatches.push(Mismatch {
+        table: definition.name().to_string(),
+        key: hex::encode(key),
+        address,
+        tick,
+        indexed: indexed.map(format.decode),
+        verified: verified.map(format.decode),
+      });
+    };
+
+    for result in indexed.iter()? {
+      let (key, value) = result?;
+      let (key, value) = (key.value(), value.value());
+      let value = V::as_bytes(&value);
+      let verified = verified
+        .get(&key)?
+        .map(|verified| V::as_bytes(&verified.value()).as_ref().to_vec());
+      if verified.as_deref() != Some(value.as_ref()) {
+        mismatch(
+          K::as_bytes(&key).as_ref(),
+          Some(value.as_ref()),
+          verified.as_deref(),
+        );
+      }
+    }
+
+    for result in verified.iter()? {
+      let (key, value) = result?;
+      let key = key.value();
+      if indexed.get(&key)?.is_none() {
+        mismatch(
+          K::as_bytes(&key).as_ref(),
+          None,
+          Some(V::as_bytes(&value.value()).as_ref()),
+        );
+      }
+    }
+
+    Ok(())
+  }
+}
@@ -0,0 +1,577 @@
+use {
+  super::*,
+  crate::okx::datastore::{brc20, brc20s},
+  notification::{Filter, FilterError},
+  std::time::{SystemTime, UNIX_EPOCH},
+};
+
+const WEBHOOK_ID_TO_SUBSCRIPTION: TableDefinition<u64, &[u8]> =
+  TableDefinition::new("WEBHOOK_ID_TO_SUBSCRIPTION");
+const WEBHOOK_DELIVERY_ID_TO_DELIVERY: TableDefinition<u64, &[u8]> =
+  TableDefinition::new("WEBHOOK_DELIVERY_ID_TO_DELIVERY");
+const WEBHOOK_PENDING_DELIVERY_ID_TO_DUE_TIME: TableDefinition<u64, u64> =
+  TableDefinition::new("WEBHOOK_PENDING_DELIVERY_ID_TO_DUE_TIME");
+
+/// Deliveries are given up on after this many failed attempts.
+pub(crate) const MAX_DELIVERY_ATTEMPTS: u32 = 10;
+const BASE_RETRY_DELAY_MILLIS: u64 = 1000;
+const MAX_RETRY_DELAY_MILLIS: u64 = 60 * 60 * 1000;
+
+/// A URL that receipts matching all of the set filters are posted to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Subscription {
+  pub(crate) url: String,
+  pub(crate) address: Option<String>,
+  pub(crate) tick: Option<String>,
+  pub(crate) tick_id: Option<String>,
+  pub(crate) pid: Option<String>,
+}
+
+impl Subscription {
+  pub(crate) fn filter(&self, network: Network) -> Result<Filter, FilterError> {
+    Filter::new(
+      self.tick.as_deref(),
+      self.tick_id.as_deref(),
+      self.pid.as_deref(),
+      self.address.as_deref(),
+      network,
+    )
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) enum DeliveryStatus {
+  Pending,
+  Delivered,
+  Failed,
+  /// The block of the payload was reorged out before it was delivered.
+  Cancelled,
+}
+
+/// What is posted for a delivery. Receipts are queued as they are indexed and rendered into
+/// their JSON document when they are first attempted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) enum Payload {
+  BRC20Receipt {
+    txid: Txid,
+    receipt: brc20::Receipt,
+  },
+  BRC20SReceipt {
+    txid: Txid,
+    receipt: brc20s::Receipt,
+  },
+  Rendered(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Delivery {
+  pub(crate) subscription: u64,
+  pub(crate) height: u64,
+  /// The delivery this one compensates for, if it is a revert notification.
+  pub(crate) reverts: Option<u64>,
+  /// Whether the block of the payload has been reorged out.
+  pub(crate) reverted: bool,
+  pub(crate) payload: Payload,
+  pub(crate) status: DeliveryStatus,
+  pub(crate) attempts: u32,
+  /// Unix time in milliseconds of the last attempt.
+  pub(crate) last_attempt: Option<u64>,
+  pub(crate) last_error: Option<String>,
+}
+
+impl Delivery {
+  pub(crate) fn new(subscription: u64, height: u64, payload: Payload) -> Self {
+    Self {
+      subscription,
+      height,
+      reverts: None,
+      reverted: false,
+      payload,
+      status: DeliveryStatus::Pending,
+      attempts: 0,
+      last_attempt: None,
+      last_error: None,
+    }
+  }
+}
+
+/// Unix time in milliseconds.
+pub(crate) fn now() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |duration| {
+      u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+    })
+}
+
+pub(crate) fn try_init_tables(wtx: &WriteTransaction) -> Result {
+  wtx.open_table(WEBHOOK_ID_TO_SUBSCRIPTION)?;
+  wtx.open_table(WEBHOOK_DELIVERY_ID_TO_DELIVERY)?;
+  wtx.open_table(WEBHOOK_PENDING_DELIVERY_ID_TO_DUE_TIME)?;
+  Ok(())
+}
+
+fn next_id(table: &impl ReadableTable<u64, &'static [u8]>) -> Result<u64> {
+  Ok(
+    table
+      .iter()?
+      .next_back()
+      .transpose()?
+      .map_or(0, |(id, _)| id.value() + 1),
+  )
+}
+
+pub(crate) fn add_subscription(wtx: &WriteTransaction, subscription: &Subscription) -> Result<u64> {
+  let mut table = wtx.open_table(WEBHOOK_ID_TO_SUBSCRIPTION)?;
+  let id = next_id(&table)?;
+  table.insert(id, bincode::serialize(subscription).unwrap().as_slice())?;
+  Ok(id)
+}
+
+/// Removes the subscription and cancels its pending deliveries.
+pub(crate) fn remove_subscription(wtx: &WriteTransaction, id: u64) -> Result<bool> {
+  if wtx
+    .open_table(WEBHOOK_ID_TO_SUBSCRIPTION)?
+    .remove(id)?
+    .is_none()
+  {
+    return Ok(false);
+  }
+
+  let mut deliveries = wtx.open_table(WEBHOOK_DELIVERY_ID_TO_DELIVERY)?;
+  let mut pending = wtx.open_table(WEBHOOK_PENDING_DELIVERY_ID_TO_DUE_TIME)?;
+  let pending_ids = pending
+    .iter()?
+    .map(|result| result.map(|(id, _)| id.value()))
+    .collect::<Result<Vec<u64>, _>>()?;
+  for delivery_id in pending_ids {
+    let mut delivery = get_delivery(&deliveries, delivery_id)?;
+    if delivery.subscription == id {
+      delivery.status = DeliveryStatus::Cancelled;
+      deliveries.insert(
+        delivery_id,
+        bincode::serialize(&delivery).unwrap().as_slice(),
+      )?;
+      pending.remove(delivery_id)?;
+    }
+  }
+
+  Ok(true)
+}
+
+pub(crate) fn subscriptions(rtx: &redb::ReadTransaction) -> Result<Vec<(u64, Subscription)>> {
+  read_subscriptions(&rtx.open_table(WEBHOOK_ID_TO_SUBSCRIPTION)?)
+}
+
+fn read_subscriptions(
+  table: &impl ReadableTable<u64, &'static [u8]>,
+) -> Result<Vec<(u64, Subscription)>> {
+  table
+    .iter()?
+    .map(|result| {
+      let (id, data) = result?;
+      Ok((id.value(), bincode::deserialize(data.value()).unwrap()))
+    })
+    .collect()
+}
+
+pub(crate) fn subscription(rtx: &redb::ReadTransaction, id: u64) -> Result<Option<Subscription>> {
+  Ok(
+    rtx
+      .open_table(WEBHOOK_ID_TO_SUBSCRIPTION)?
+      .get(id)?
+      .map(|data| bincode::deserialize(data.value()).unwrap()),
+  )
+}
+
+fn get_delivery(table: &impl ReadableTable<u64, &'static [u8]>, id: u64) -> Result<Delivery> {
+  Ok(bincode::deserialize(
+    table
+      .get(id)?
+      .ok_or_else(|| anyhow!("webhook delivery {id} not found"))?
+      .value(),
+  )?)
+}
+
+/// Returns the deliveries of the subscription, newest first.
+pub(crate) fn deliveries(
+  rtx: &redb::ReadTransaction,
+  subscription: u64,
+) -> Result<Vec<(u64, Delivery)>> {
+  let mut deliveries = Vec::new();
+  for result in rtx
+    .open_table(WEBHOOK_DELIVERY_ID_TO_DELIVERY)?
+    .iter()?
+    .rev()
+  {
+    let (id, data) = result?;
+    let delivery: Delivery = bincode::deserialize(data.value()).unwrap();
+    if delivery.subscription == subscription {
+      deliveries.push((id.value(), delivery));
+    }
+  }
+  Ok(deliveries)
+}
+
+pub(crate) fn has_subscriptions(wtx: &WriteTransaction) -> Result<bool> {
+  Ok(!wtx.open_table(WEBHOOK_ID_TO_SUBSCRIPTION)?.is_empty()?)
+}
+
+#[cfg(test)]
+fn enqueue(wtx: &WriteTransaction, delivery: &Delivery, now: u64) -> Result<u64> {
+  let mut deliveries = wtx.open_table(WEBHOOK_DELIVERY_ID_TO_DELIVERY)?;
+  let id = next_id(&deliveries)?;
+  deliveries.insert(id, bincode::serialize(delivery).unwrap().as_slice())?;
+  wtx
+    .open_table(WEBHOOK_PENDING_DELIVERY_ID_TO_DUE_TIME)?
+    .insert(id, now)?;
+  Ok(id)
+}
+
+/// Queues a delivery of every receipt of the block at `height` to the subscriptions it matches,
+/// in the transaction indexing the block, so no receipt is missed or queued twice.
+pub(crate) fn enqueue_block(
+  wtx: &WriteTransaction,
+  network: Network,
+  height: u64,
+  notifications: &[Notification],
+  now: u64,
+) -> Result {
+  let mut filters = Vec::new();
+  for (id, subscription) in read_subscriptions(&wtx.open_table(WEBHOOK_ID_TO_SUBSCRIPTION)?)? {
+    match subscription.filter(network) {
+      Ok(filter) => filters.push((id, filter)),
+      Err(err) => log::warn!("webhook {id}: invalid filter: {err:?}"),
+    }
+  }
+
+  if filters.is_empty() {
+    return Ok(());
+  }
+
+  let mut deliveries = wtx.open_table(WEBHOOK_DELIVERY_ID_TO_DELIVERY)?;
+  let mut pending = wtx.open_table(WEBHOOK_PENDING_DELIVERY_ID_TO_DUE_TIME)?;
+  let mut next = next_id(&deliveries)?;
+  for notification in notifications {
+    for (subscription, filter) in &filters {
+      let payload = match notification {
+        Notification::BRC20Receipt { txid, receipt, .. } if filter.matches_brc20(receipt) => {
+          Payload::BRC20Receipt {
+            txid: *txid,
+            receipt: receipt.clone(),
+          }
+        }
+        Notification::BRC20SReceipt { txid, receipt, .. } if filter.matches_brc20s(receipt) => {
+          Payload::BRC20SReceipt {
+            txid: *txid,
+            receipt: receipt.clone(),
+          }
+        }
+        _ => continue,
+      };
+      let delivery = Delivery::new(*subscription, height, payload);
+      deliveries.insert(next, bincode::serialize(&delivery).unwrap().as_slice())?;
+      pending.insert(next, now)?;
+      next += 1;
+    }
+  }
+
+  Ok(())
+}
+
+/// Returns the pending deliveries due at `now`, oldest first.
+pub(crate) fn due_deliveries(
+  rtx: &redb::ReadTransaction,
+  now: u64,
+) -> Result<Vec<(u64, Delivery)>> {
+  let deliveries = rtx.open_table(WEBHOOK_DELIVERY_ID_TO_DELIVERY)?;
+  let mut due = Vec::new();
+  for result in rtx
+    .open_table(WEBHOOK_PENDING_DELIVERY_ID_TO_DUE_TIME)?
+    .iter()?
+  {
+    let (id, due_time) = result?;
+    if due_time.value() <= now {
+      due.push((id.value(), get_delivery(&deliveries, id.value())?));
+    }
+  }
+  Ok(due)
+}
+
+/// Records an attempt to deliver, scheduling a retry with exponential backoff if it failed.
+///
+/// The rendered `payload` replaces the queued receipt, so retries and reverts post the same document.
+pub(crate) fn record_attempt(
+  wtx: &WriteTransaction,
+  id: u64,
+  now: u64,
+  payload: Option<String>,
+  error: Option<String>,
+) -> Result {
+  let mut deliveries = wtx.open_table(WEBHOOK_DELIVERY_ID_TO_DELIVERY)?;
+  let mut pending = wtx.open_table(WEBHOOK_PENDING_DELIVERY_ID_TO_DUE_TIME)?;
+  let mut delivery = get_delivery(&deliveries, id)?;
+
+  // the delivery may have been cancelled while it was being attempted.
+  if delivery.status != DeliveryStatus::Pending {
+    return Ok(());
+  }
+
+  if let Some(payload) = payload {
+    delivery.payload = Payload::Rendered(payload);
+  }
+  delivery.attempts += 1;
+  delivery.last_attempt = Some(now);
+  delivery.status = match &error {
+    None => DeliveryStatus::Delivered,
+    Some(_) if delivery.attempts >= MAX_DELIVERY_ATTEMPTS => DeliveryStatus::Failed,
+    Some(_) => DeliveryStatus::Pending,
+  };
+  delivery.last_error = error;
+
+  if delivery.status == DeliveryStatus::Pending {
+    let delay = BASE_RETRY_DELAY_MILLIS
+      .saturating_mul(1 << (delivery.attempts - 1).min(32))
+      .min(MAX_RETRY_DELAY_MILLIS);
+    pending.insert(id, now + delay)?;
+  } else {
+    pending.remove(id)?;
+  }
+
+  deliveries.insert(id, bincode::serialize(&delivery).unwrap().as_slice())?;
+  Ok(())
+}
+
+/// Compensates for the deliveries of blocks at and above `height`, which were reorged out.
+///
+/// Pending deliveries are cancelled, and a revert notification carrying the original payload is
+/// queued for every delivery that was already attempted.
+pub(crate) fn revert_from(wtx: &WriteTransaction, height: u64, now: u64) -> Result<Vec<u64>> {
+  let mut deliveries = wtx.open_table(WEBHOOK_DELIVERY_ID_TO_DELIVERY)?;
+  let mut pending = wtx.open_table(WEBHOOK_PENDING_DELIVERY_ID_TO_DUE_TIME)?;
+
+  // deliveries are queued block by block, and every live delivery above a reorged block has
+  // been reverted, so the live deliveries are in ascending height order.
+  let mut reverted = Vec::new();
+  for result in deliveries.iter()?.rev() {
+    let (id, data) = result?;
+    let delivery: Delivery = bincode::deserialize(data.value()).unwrap();
+    if delivery.reverts.is_some() || delivery.reverted {
+      continue;
+    }
+    if delivery.height < height {
+      break;
+    }
+    reverted.push((id.value(), delivery));
+  }
+
+  let mut reverts = Vec::new();
+  for (id, mut delivery) in reverted.into_iter().rev() {
+    delivery.reverted = true;
+    if delivery.status == DeliveryStatus::Pending && delivery.attempts == 0 {
+      delivery.status = DeliveryStatus::Cancelled;
+      pending.remove(id)?;
+    } else {
+      let revert = Delivery {
+        reverts: Some(id),
+        ..Delivery::new(
+          delivery.subscription,
+          delivery.height,
+          Payload::Rendered(
+            serde_json::json!({
+              "subscription": delivery.subscription,
+              "type": "revert",
+              "height": delivery.height,
+              "delivery": id,
+              "data": match &delivery.payload {
+                Payload::Rendered(payload) => serde_json::from_str(payload).unwrap_or_default(),
+                // the payload couldn't be rendered when it was attempted.
+                _ => serde_json::Value::Null,
+              },
+            })
+            .to_string(),
+          ),
+        )
+      };
+      let revert_id = next_id(&deliveries)?;
+      deliveries.insert(revert_id, bincode::serialize(&revert).unwrap().as_slice())?;
+      pending.insert(revert_id, now)?;
+      reverts.push(revert_id);
+    }
+    deliveries.insert(id, bincode::serialize(&delivery).unwrap().as_slice())?;
+  }
+
+  Ok(reverts)
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, tempfile::NamedTempFile};
+
+  fn subscription() -> Subscription {
+    Subscription {
+      url: "http://127.0.0.1:8080/hook".into(),
+      address: None,
+      tick: Some("ordi".into()),
+      tick_id: None,
+      pid: None,
+    }
+  }
+
+  #[test]
+  fn failed_deliveries_are_retried_with_backoff() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    try_init_tables(&wtx).unwrap();
+
+    let subscription = add_subscription(&wtx, &subscription()).unwrap();
+    let id = enqueue(
+      &wtx,
+      &Delivery::new(subscription, 1, Payload::Rendered("{}".into())),
+      0,
+    )
+    .unwrap();
+
+    record_attempt(&wtx, id, 0, None, Some("connection refused".into())).unwrap();
+    record_attempt(&wtx, id, 1000, None, Some("connection refused".into())).unwrap();
+    wtx.commit().unwrap();
+
+    let rtx = db.begin_read().unwrap();
+    assert!(due_deliveries(&rtx, 2999).unwrap().is_empty());
+    let (due_id, delivery) = due_deliveries(&rtx, 3000).unwrap().remove(0);
+    assert_eq!(due_id, id);
+    assert_eq!(delivery.attempts, 2);
+    assert_eq!(delivery.status, DeliveryStatus::Pending);
+    assert_eq!(delivery.last_error.as_deref(), Some("connection refused"));
+
+    let wtx = db.begin_write().unwrap();
+    for attempt in 2..MAX_DELIVERY_ATTEMPTS {
+      record_attempt(
+        &wtx,
+        id,
+        u64::from(attempt) * 1000,
+        None,
+        Some("timeout".into()),
+      )
+      .unwrap();
+    }
+    wtx.commit().unwrap();
+
+    let rtx = db.begin_read().unwrap();
+    assert!(due_deliveries(&rtx, u64::MAX).unwrap().is_empty());
+    assert_eq!(
+      deliveries(&rtx, subscription).unwrap()[0].1.status,
+      DeliveryStatus::Failed
+    );
+  }
+
+  #[test]
+  fn reorged_deliveries_are_cancelled_or_reverted() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    try_init_tables(&wtx).unwrap();
+
+    let subscription = add_subscription(&wtx, &subscription()).unwrap();
+    let kept = enqueue(
+      &wtx,
+      &Delivery::new(subscription, 1, Payload::Rendered("a".into())),
+      0,
+    )
+    .unwrap();
+    let delivered = enqueue(
+      &wtx,
+      &Delivery::new(subscription, 2, Payload::Rendered("b".into())),
+      0,
+    )
+    .unwrap();
+    let pending = enqueue(
+      &wtx,
+      &Delivery::new(subscription, 3, Payload::Rendered("c".into())),
+      0,
+    )
+    .unwrap();
+    record_attempt(&wtx, kept, 0, None, None).unwrap();
+    record_attempt(&wtx, delivered, 0, Some(r#"{"event":"b"}"#.into()), None).unwrap();
+
+    let reverts = revert_from(&wtx, 2, 0).unwrap();
+    assert_eq!(reverts.len(), 1);
+
+    // a second reorg at the same height doesn't revert anything twice.
+    assert!(revert_from(&wtx, 2, 0).unwrap().is_empty());
+    wtx.commit().unwrap();
+
+    let rtx = db.begin_read().unwrap();
+    let deliveries = deliveries(&rtx, subscription).unwrap();
+    assert_eq!(deliveries.len(), 4);
+
+    let (revert_id, revert) = &deliveries[0];
+    assert_eq!(*revert_id, reverts[0]);
+    assert_eq!(revert.reverts, Some(delivered));
+    assert_eq!(
+      revert.payload,
+      Payload::Rendered(
+        serde_json::json!({
+          "subscription": subscription,
+          "type": "revert",
+          "height": 2,
+          "delivery": delivered,
+          "data": {"event": "b"},
+        })
+        .to_string()
+      )
+    );
+
+    assert_eq!(deliveries[1].0, pending);
+    assert_eq!(deliveries[1].1.status, DeliveryStatus::Cancelled);
+    assert_eq!(deliveries[2].0, delivered);
+    assert!(deliveries[2].1.reverted);
+    assert_eq!(deliveries[3].0, kept);
+    assert!(!deliveries[3].1.reverted);
+
+    assert_eq!(
+      due_deliveries(&rtx, 0)
+        .unwrap()
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect::<Vec<u64>>(),
+      reverts
+    );
+  }
+
+  #[test]
+  fn removing_a_subscription_cancels_its_pending_deliveries() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    try_init_tables(&wtx).unwrap();
+
+    let first = add_subscription(&wtx, &subscription()).unwrap();
+    let second = add_subscription(&wtx, &subscription()).unwrap();
+    enqueue(
+      &wtx,
+      &Delivery::new(first, 1, Payload::Rendered("a".into())),
+      0,
+    )
+    .unwrap();
+    let kept = enqueue(
+      &wtx,
+      &Delivery::new(second, 1, Payload::Rendered("b".into())),
+      0,
+    )
+    .unwrap();
+
+    assert!(remove_subscription(&wtx, first).unwrap());
+    assert!(!remove_subscription(&wtx, first).unwrap());
+    wtx.commit().unwrap();
+
+    let rtx = db.begin_read().unwrap();
+    assert_eq!(subscriptions(&rtx).unwrap(), vec![(second, subscription())]);
+    assert_eq!(
+      deliveries(&rtx, first).unwrap()[0].1.status,
+      DeliveryStatus::Cancelled
+    );
+    assert_eq!(due_deliveries(&rtx, 0).unwrap()[0].0, kept);
+  }
+}
@@ -194,7 +194,6 @@ impl Inscription {
     Some(InscriptionId { txid, index })
   }
 
-  #[cfg(test)]
   pub(crate) fn to_witness(&self) -> Witness {
     let builder = script::Builder::new();
 
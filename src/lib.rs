@@ -19,6 +19,7 @@ use {
     degree::Degree,
     deserialize_from_str::DeserializeFromStr,
     epoch::Epoch,
+    fee_rate::FeeRate,
     height::Height,
     index::{Index, List},
     inscription_id::InscriptionId,
@@ -100,12 +101,14 @@ mod decimal;
 mod degree;
 mod deserialize_from_str;
 mod epoch;
+mod fee_rate;
 mod height;
 mod index;
 mod inscription;
 pub mod inscription_id;
 mod logger;
 mod media;
+mod metrics;
 mod object;
 mod okx;
 mod options;
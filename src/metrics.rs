@@ -0,0 +1,322 @@
+//! Indexer, protocol and server metrics, rendered in the Prometheus text format by `/metrics`.
+//!
+//! The metrics are process wide, so that the updater and the server record into the same registry
+//! whether or not they run in the same subcommand.
+
+use super::*;
+
+// upper bounds, in seconds, of the latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 12] = [
+  0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 10.0,
+];
+
+const INDEX_HEIGHT: &str = "ord_index_height";
+const CHAIN_TIP_HEIGHT: &str = "ord_chain_tip_height";
+const BLOCK_PHASE_SECONDS: &str = "ord_index_block_phase_seconds";
+const PROTOCOL_MESSAGES: &str = "ord_protocol_messages_total";
+const PROTOCOL_RECEIPTS: &str = "ord_protocol_receipts_total";
+const REORGS: &str = "ord_reorgs_total";
+const HTTP_REQUEST_SECONDS: &str = "ord_http_request_duration_seconds";
+
+lazy_static! {
+  static ref REGISTRY: Mutex<BTreeMap<(&'static str, Labels), Value>> = Mutex::new(BTreeMap::new());
+}
+
+type Labels = Vec<(&'static str, String)>;
+
+enum Value {
+  Counter(u64),
+  Gauge(u64),
+  Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+  },
+}
+
+impl Value {
+  fn kind(&self) -> &'static str {
+    match self {
+      Self::Counter(_) => "counter",
+      Self::Gauge(_) => "gauge",
+      Self::Histogram { .. } => "histogram",
+    }
+  }
+}
+
+fn help(name: &str) -> &'static str {
+  match name {
+    INDEX_HEIGHT => "Height of the last indexed block.",
+    CHAIN_TIP_HEIGHT => "Height of the chain tip reported by Bitcoin Core.",
+    BLOCK_PHASE_SECONDS => "Time spent indexing a block, by phase.",
+    PROTOCOL_MESSAGES => "Messages resolved from inscription operations, by protocol.",
+    PROTOCOL_RECEIPTS => "Receipts of executed messages, by protocol and result.",
+    REORGS => "Reorgs detected by the updater, by depth.",
+    HTTP_REQUEST_SECONDS => "Time spent serving HTTP requests, by route.",
+    _ => "",
+  }
+}
+
+fn update(name: &'static str, labels: Labels, default: fn() -> Value, f: impl FnOnce(&mut Value)) {
+  f(REGISTRY
+    .lock()
+    .unwrap()
+    .entry((name, labels))
+    .or_insert_with(default))
+}
+
+fn set_gauge(name: &'static str, value: u64) {
+  update(
+    name,
+    Vec::new(),
+    || Value::Gauge(0),
+    |gauge| {
+      if let Value::Gauge(current) = gauge {
+        *current = value;
+      }
+    },
+  );
+}
+
+fn inc_counter(name: &'static str, labels: Labels, n: u64) {
+  update(
+    name,
+    labels,
+    || Value::Counter(0),
+    |counter| {
+      if let Value::Counter(current) = counter {
+        *current += n;
+      }
+    },
+  );
+}
+
+fn observe(name: &'static str, labels: Labels, duration: Duration) {
+  let seconds = duration.as_secs_f64();
+  update(
+    name,
+    labels,
+    || Value::Histogram {
+      buckets: [0; LATENCY_BUCKETS.len()],
+      count: 0,
+      sum: 0.0,
+    },
+    |histogram| {
+      if let Value::Histogram {
+        buckets,
+        count,
+        sum,
+      } = histogram
+      {
+        for (bucket, bound) in buckets.iter_mut().zip(LATENCY_BUCKETS) {
+          if seconds <= bound {
+            *bucket += 1;
+          }
+        }
+        *count += 1;
+        *sum += seconds;
+      }
+    },
+  );
+}
+
+/// Records the height of the last indexed block and of the chain tip.
+pub(crate) fn set_heights(index_height: u64, chain_tip: u64) {
+  set_gauge(INDEX_HEIGHT, index_height);
+  set_gauge(CHAIN_TIP_HEIGHT, chain_tip);
+}
+
+/// Records the time a phase of indexing a block took.
+pub(crate) fn observe_block_phase(phase: &'static str, duration: Duration) {
+  observe(BLOCK_PHASE_SECONDS, vec![("phase", phase.into())], duration);
+}
+
+/// Counts `n` messages resolved by `protocol`.
+pub(crate) fn inc_messages(protocol: &'static str, n: u64) {
+  inc_counter(PROTOCOL_MESSAGES, vec![("protocol", protocol.into())], n);
+}
+
+/// Counts a receipt of `protocol`, whose result is `ok` or the name of its error.
+pub(crate) fn inc_receipts(protocol: &'static str, result: String) {
+  inc_counter(
+    PROTOCOL_RECEIPTS,
+    vec![("protocol", protocol.into()), ("result", result)],
+    1,
+  );
+}
+
+/// Counts a reorg of `depth` blocks, or one too deep to recover from.
+pub(crate) fn inc_reorgs(depth: Option<u64>) {
+  inc_counter(
+    REORGS,
+    vec![(
+      "depth",
+      depth.map_or_else(|| "unrecoverable".into(), |depth| depth.to_string()),
+    )],
+    1,
+  );
+}
+
+/// Records the time serving a request to `route` took.
+pub(crate) fn observe_request(route: String, duration: Duration) {
+  observe(HTTP_REQUEST_SECONDS, vec![("route", route)], duration);
+}
+
+/// Returns `ok` for a successful result, or the name of the error variant, which is how the
+/// receipt errors are serialized.
+pub(crate) fn result_label<T, E: Serialize>(result: &Result<T, E>) -> String {
+  let Err(err) = result else {
+    return "ok".into();
+  };
+
+  match serde_json::to_value(err) {
+    Ok(serde_json::Value::String(variant)) => variant,
+    Ok(serde_json::Value::Object(object)) => object
+      .keys()
+      .next()
+      .cloned()
+      .unwrap_or_else(|| "error".into()),
+    _ => "error".into(),
+  }
+}
+
+fn escape(value: &str) -> String {
+  value
+    .replace('\\', r"\\")
+    .replace('"', r#"\""#)
+    .replace('\n', r"\n")
+}
+
+fn format_labels(labels: &Labels, extra: Option<(&str, String)>) -> String {
+  let labels = labels
+    .iter()
+    .map(|(name, value)| (*name, value.clone()))
+    .chain(extra)
+    .map(|(name, value)| format!("{name}=\"{}\"", escape(&value)))
+    .collect::<Vec<String>>();
+
+  if labels.is_empty() {
+    String::new()
+  } else {
+    format!("{{{}}}", labels.join(","))
+  }
+}
+
+/// Renders all the metrics recorded so far in the Prometheus text format.
+pub(crate) fn render() -> String {
+  let registry = REGISTRY.lock().unwrap();
+
+  let mut output = String::new();
+  let mut last = None;
+  for ((name, labels), value) in registry.iter() {
+    if last != Some(*name) {
+      output.push_str(&format!("# HELP {name} {}\n", help(name)));
+      output.push_str(&format!("# TYPE {name} {}\n", value.kind()));
+      last = Some(*name);
+    }
+
+    match value {
+      Value::Counter(value) | Value::Gauge(value) => {
+        output.push_str(&format!("{name}{} {value}\n", format_labels(labels, None)));
+      }
+      Value::Histogram {
+        buckets,
+        count,
+        sum,
+      } => {
+        for (bucket, bound) in buckets.iter().zip(LATENCY_BUCKETS) {
+          output.push_str(&format!(
+            "{name}_bucket{} {bucket}\n",
+            format_labels(labels, Some(("le", bound.to_string())))
+          ));
+        }
+        output.push_str(&format!(
+          "{name}_bucket{} {count}\n",
+          format_labels(labels, Some(("le", "+Inf".into())))
+        ));
+        output.push_str(&format!(
+          "{name}_sum{} {sum}\n",
+          format_labels(labels, None)
+        ));
+        output.push_str(&format!(
+          "{name}_count{} {count}\n",
+          format_labels(labels, None)
+        ));
+      }
+    }
+  }
+
+  output
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::okx::{datastore::brc20::BRC20Error, protocol::brc20s::BRC20SError},
+  };
+
+  // the registry is shared by all tests, so each test records under labels of its own.
+
+  #[test]
+  fn counters_are_rendered_with_their_labels() {
+    inc_messages("counters_test", 2);
+    inc_messages("counters_test", 3);
+
+    let output = render();
+    assert!(output.contains(
+      "# HELP ord_protocol_messages_total Messages resolved from inscription operations, by protocol.\n# TYPE ord_protocol_messages_total counter\n"
+    ));
+    assert!(output.contains("ord_protocol_messages_total{protocol=\"counters_test\"} 5\n"));
+  }
+
+  #[test]
+  fn histograms_have_cumulative_buckets() {
+    observe_request("/histogram_test".into(), Duration::from_millis(20));
+    observe_request("/histogram_test".into(), Duration::from_secs(20));
+
+    let output = render();
+    for (le, count) in [("0.01", 0), ("0.025", 1), ("10", 1), ("+Inf", 2)] {
+      assert!(
+        output.contains(&format!(
+          "ord_http_request_duration_seconds_bucket{{route=\"/histogram_test\",le=\"{le}\"}} {count}\n"
+        )),
+        "{output}"
+      );
+    }
+    assert!(
+      output.contains("ord_http_request_duration_seconds_count{route=\"/histogram_test\"} 2\n")
+    );
+  }
+
+  #[test]
+  fn label_values_are_escaped() {
+    inc_reorgs(None);
+    observe_request("/\"escape\\test\"".into(), Duration::ZERO);
+
+    let output = render();
+    assert!(output.contains("ord_reorgs_total{depth=\"unrecoverable\"}"));
+    assert!(output.contains(r#"{route="/\"escape\\test\"",le="+Inf"} 1"#));
+  }
+
+  #[test]
+  fn results_are_labeled_with_their_error_variant() {
+    assert_eq!(result_label::<(), BRC20Error>(&Ok(())), "ok");
+    assert_eq!(
+      result_label::<(), _>(&Err(BRC20Error::TickNotFound("ordi".into()))),
+      "TickNotFound"
+    );
+    assert_eq!(
+      result_label::<(), _>(&Err(BRC20SError::DivedZero)),
+      "DivedZero"
+    );
+    assert_eq!(
+      result_label::<(), _>(&Err(BRC20SError::Overflow {
+        op: "add".into(),
+        org: "1".into(),
+        other: "2".into(),
+      })),
+      "Overflow"
+    );
+  }
+}
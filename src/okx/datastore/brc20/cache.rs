@@ -0,0 +1,424 @@
+use {
+  super::{
+    redb::{script_tick_key, tick_key},
+    Balance, DataStoreReadOnly, DataStoreReadWrite, Receipt, Tick, TokenInfo, TokenStats,
+    TransferInfo, TransferableLog,
+  },
+  crate::{
+    index::state_hash::Change,
+    okx::datastore::{cache::WriteBackCache, ScriptKey},
+    InscriptionId,
+  },
+  bitcoin::Txid,
+};
+
+/// Cache holds the decoded balances, token infos and token stats of BRC20.
+pub(crate) struct Cache {
+  balances: WriteBackCache<ScriptKey, Balance>,
+  tokens: WriteBackCache<Tick, TokenInfo>,
+  stats: WriteBackCache<Tick, TokenStats>,
+  limit: usize,
+}
+
+impl Cache {
+  pub(crate) fn new(limit: usize) -> Self {
+    Self {
+      balances: WriteBackCache::default(),
+      tokens: WriteBackCache::default(),
+      stats: WriteBackCache::default(),
+      limit,
+    }
+  }
+
+  fn size(&self) -> usize {
+    self.balances.size() + self.tokens.size() + self.stats.size()
+  }
+
+  fn clear(&self) {
+    self.balances.clear();
+    self.tokens.clear();
+    self.stats.clear();
+  }
+
+  /// Takes the changes to the consensus state written since they were last taken.
+  pub(crate) fn take_changes(&self) -> Vec<Change> {
+    // token stats are not consensus state.
+    self.stats.take_changed();
+
+    self
+      .balances
+      .take_changed()
+      .into_iter()
+      .map(|(script_key, balance)| Change::brc20_balance(&script_key, &balance))
+      .chain(
+        self
+          .tokens
+          .take_changed()
+          .into_iter()
+          .map(|(_, info)| Change::brc20_token(&info)),
+      )
+      .collect()
+  }
+}
+
+/// CachedDataStore serves the BRC20 state of `store` through a write-back `Cache`.
+///
+/// Reads spanning several entries of the cached tables flush the dirty entries first, so that
+/// they see every write.
+pub struct CachedDataStore<'a, W> {
+  store: W,
+  cache: &'a Cache,
+}
+
+impl<'a, W: DataStoreReadWrite> CachedDataStore<'a, W> {
+  pub(crate) fn new(store: W, cache: &'a Cache) -> Self {
+    Self { store, cache }
+  }
+
+  /// Writes the dirty entries back to the store.
+  pub(crate) fn flush(&self) -> Result<(), W::Error> {
+    for (script_key, balance) in self.cache.balances.take_dirty() {
+      self.store.update_token_balance(&script_key, balance)?;
+    }
+    for (tick, info) in self.cache.tokens.take_dirty() {
+      self.store.insert_token_info(&tick, &info)?;
+    }
+    for (tick, stats) in self.cache.stats.take_dirty() {
+      self.store.update_token_stats(&tick, &stats)?;
+    }
+    Ok(())
+  }
+
+  // keeps the cache within its limit, by writing everything back and starting over.
+  fn evict(&self) -> Result<(), W::Error> {
+    if self.cache.size() > self.cache.limit {
+      self.flush()?;
+      self.cache.clear();
+    }
+    Ok(())
+  }
+}
+
+impl<'a, W: DataStoreReadWrite> DataStoreReadOnly for CachedDataStore<'a, W> {
+  type Error = W::Error;
+
+  fn get_balances(&self, script_key: &ScriptKey) -> Result<Vec<Balance>, Self::Error> {
+    self.flush()?;
+    self.store.get_balances(script_key)
+  }
+
+  fn get_balance(
+    &self,
+    script_key: &ScriptKey,
+    tick: &Tick,
+  ) -> Result<Option<Balance>, Self::Error> {
+    let balance = self
+      .cache
+      .balances
+      .get_or_load(script_tick_key(script_key, tick), || {
+        self.store.get_balance(script_key, tick)
+      })?;
+    self.evict()?;
+    Ok(balance)
+  }
+
+  fn get_balances_at_height(
+    &self,
+    script_key: &ScriptKey,
+    height: u64,
+  ) -> Result<Vec<Balance>, Self::Error> {
+    self.store.get_balances_at_height(script_key, height)
+  }
+
+  fn get_balance_at_height(
+    &self,
+    script_key: &ScriptKey,
+    tick: &Tick,
+    height: u64,
+  ) -> Result<Option<Balance>, Self::Error> {
+    self.store.get_balance_at_height(script_key, tick, height)
+  }
+
+  fn get_token_info(&self, tick: &Tick) -> Result<Option<TokenInfo>, Self::Error> {
+    let info = self
+      .cache
+      .tokens
+      .get_or_load(tick_key(tick).to_vec(), || self.store.get_token_info(tick))?;
+    self.evict()?;
+    Ok(info)
+  }
+
+  fn get_tokens_info(&self) -> Result<Vec<TokenInfo>, Self::Error> {
+    self.flush()?;
+    self.store.get_tokens_info()
+  }
+
+  fn get_token_stats(&self, tick: &Tick) -> Result<Option<TokenStats>, Self::Error> {
+    let stats = self
+      .cache
+      .stats
+      .get_or_load(tick_key(tick).to_vec(), || self.store.get_token_stats(tick))?;
+    self.evict()?;
+    Ok(stats)
+  }
+
+  fn get_tokens_stats(&self) -> Result<Vec<TokenStats>, Self::Error> {
+    self.flush()?;
+    self.store.get_tokens_stats()
+  }
+
+  fn get_token_holders(
+    &self,
+    tick: &Tick,
+    start: usize,
+    limit: Option<usize>,
+  ) -> Result<Vec<(ScriptKey, Balance)>, Self::Error> {
+    self.flush()?;
+    self.store.get_token_holders(tick, start, limit)
+  }
+
+  fn get_token_holders_count(&self, tick: &Tick) -> Result<u64, Self::Error> {
+    self.flush()?;
+    self.store.get_token_holders_count(tick)
+  }
+
+  fn get_transaction_receipts(&self, txid: &Txid) -> Result<Vec<Receipt>, Self::Error> {
+    self.store.get_transaction_receipts(txid)
+  }
+
+  fn get_address_receipts(
+    &self,
+    script_key: &ScriptKey,
+    before: Option<u64>,
+    limit: usize,
+    filter: &dyn Fn(&Receipt) -> bool,
+  ) -> Result<Vec<(u64, Txid, Receipt)>, Self::Error> {
+    self
+      .store
+      .get_address_receipts(script_key, before, limit, filter)
+  }
+
+  fn get_transferable(&self, script: &ScriptKey) -> Result<Vec<TransferableLog>, Self::Error> {
+    self.store.get_transferable(script)
+  }
+
+  fn get_transferable_by_tick(
+    &self,
+    script: &ScriptKey,
+    tick: &Tick,
+  ) -> Result<Vec<TransferableLog>, Self::Error> {
+    self.store.get_transferable_by_tick(script, tick)
+  }
+
+  fn get_transferable_by_id(
+    &self,
+    script: &ScriptKey,
+    inscription_id: &InscriptionId,
+  ) -> Result<Option<TransferableLog>, Self::Error> {
+    self.store.get_transferable_by_id(script, inscription_id)
+  }
+
+  fn get_inscribe_transfer_inscription(
+    &self,
+    inscription_id: InscriptionId,
+  ) -> Result<Option<TransferInfo>, Self::Error> {
+    self.store.get_inscribe_transfer_inscription(inscription_id)
+  }
+}
+
+impl<'a, W: DataStoreReadWrite> DataStoreReadWrite for CachedDataStore<'a, W> {
+  fn update_token_balance(
+    &self,
+    script_key: &ScriptKey,
+    new_balance: Balance,
+  ) -> Result<(), Self::Error> {
+    self.cache.balances.put(
+      script_tick_key(script_key, &new_balance.tick),
+      script_key.clone(),
+      new_balance,
+    );
+    self.evict()
+  }
+
+  fn insert_balance_history(
+    &self,
+    script_key: &ScriptKey,
+    height: u64,
+    balance: &Balance,
+  ) -> Result<(), Self::Error> {
+    self
+      .store
+      .insert_balance_history(script_key, height, balance)
+  }
+
+  fn insert_token_info(&self, tick: &Tick, new_info: &TokenInfo) -> Result<(), Self::Error> {
+    self
+      .cache
+      .tokens
+      .put(tick_key(tick).to_vec(), tick.clone(), new_info.clone());
+    self.evict()
+  }
+
+  fn update_mint_token_info(
+    &self,
+    tick: &Tick,
+    minted_amt: u128,
+    minted_block_number: u64,
+  ) -> Result<(), Self::Error> {
+    let mut info = self
+      .get_token_info(tick)?
+      .unwrap_or_else(|| panic!("token {} not exist", tick.as_str()));
+
+    info.minted = minted_amt;
+    info.latest_mint_number = minted_block_number;
+
+    self.insert_token_info(tick, &info)
+  }
+
+  fn update_token_stats(&self, tick: &Tick, stats: &TokenStats) -> Result<(), Self::Error> {
+    self
+      .cache
+      .stats
+      .put(tick_key(tick).to_vec(), tick.clone(), stats.clone());
+    self.evict()
+  }
+
+  fn save_transaction_receipts(
+    &self,
+    txid: &Txid,
+    receipts: &[Receipt],
+  ) -> Result<(), Self::Error> {
+    self.store.save_transaction_receipts(txid, receipts)
+  }
+
+  fn add_transaction_receipt(&self, txid: &Txid, receipt: &Receipt) -> Result<(), Self::Error> {
+    self.store.add_transaction_receipt(txid, receipt)
+  }
+
+  fn insert_transferable(
+    &self,
+    script: &ScriptKey,
+    tick: &Tick,
+    inscription: TransferableLog,
+  ) -> Result<(), Self::Error> {
+    self.store.insert_transferable(script, tick, inscription)
+  }
+
+  fn remove_transferable(
+    &self,
+    script: &ScriptKey,
+    tick: &Tick,
+    inscription_id: InscriptionId,
+  ) -> Result<(), Self::Error> {
+    self.store.remove_transferable(script, tick, inscription_id)
+  }
+
+  fn insert_inscribe_transfer_inscription(
+    &self,
+    inscription_id: InscriptionId,
+    transfer_info: TransferInfo,
+  ) -> Result<(), Self::Error> {
+    self
+      .store
+      .insert_inscribe_transfer_inscription(inscription_id, transfer_info)
+  }
+
+  fn remove_inscribe_transfer_inscription(
+    &self,
+    inscription_id: InscriptionId,
+  ) -> Result<(), Self::Error> {
+    self
+      .store
+      .remove_inscribe_transfer_inscription(inscription_id)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*, crate::okx::datastore::brc20::redb::DataStore, bitcoin::Address, redb::Database,
+    std::str::FromStr, tempfile::NamedTempFile,
+  };
+
+  #[test]
+  fn writes_reach_the_store_on_flush() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    let brc20db = DataStore::new(&wtx);
+    let cache = Cache::new(usize::MAX);
+    let cached = CachedDataStore::new(DataStore::new(&wtx), &cache);
+
+    let script = ScriptKey::from_address(
+      Address::from_str("bc1qhvd6suvqzjcu9pxjhrwhtrlj85ny3n2mqql5w4")
+        .unwrap()
+        .assume_checked(),
+    );
+    let balance = Balance {
+      tick: Tick::from_str("abcd").unwrap(),
+      overall_balance: 10,
+      transferable_balance: 0,
+    };
+
+    cached
+      .update_token_balance(&script, balance.clone())
+      .unwrap();
+    let upper = Tick::from_str("ABCD").unwrap();
+    assert_eq!(
+      cached.get_balance(&script, &upper).unwrap(),
+      Some(balance.clone())
+    );
+    assert_eq!(brc20db.get_balance(&script, &upper).unwrap(), None);
+
+    cached.flush().unwrap();
+    assert_eq!(
+      brc20db.get_balance(&script, &upper).unwrap(),
+      Some(balance.clone())
+    );
+  }
+
+  #[test]
+  fn list_reads_and_eviction_write_back_dirty_entries() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    let brc20db = DataStore::new(&wtx);
+    let script = ScriptKey::from_address(
+      Address::from_str("bc1qhvd6suvqzjcu9pxjhrwhtrlj85ny3n2mqql5w4")
+        .unwrap()
+        .assume_checked(),
+    );
+    let balance = Balance {
+      tick: Tick::from_str("abcd").unwrap(),
+      overall_balance: 10,
+      transferable_balance: 0,
+    };
+
+    let cache = Cache::new(usize::MAX);
+    let cached = CachedDataStore::new(DataStore::new(&wtx), &cache);
+    cached
+      .update_token_balance(&script, balance.clone())
+      .unwrap();
+    assert_eq!(cached.get_balances(&script).unwrap(), vec![balance.clone()]);
+
+    let balance = Balance {
+      overall_balance: 20,
+      ..balance
+    };
+    let cache = Cache::new(0);
+    let cached = CachedDataStore::new(DataStore::new(&wtx), &cache);
+    cached
+      .update_token_balance(&script, balance.clone())
+      .unwrap();
+    assert_eq!(
+      brc20db.get_balance(&script, &balance.tick).unwrap(),
+      Some(balance.clone())
+    );
+
+    // the changes of the block stay cached until they are taken.
+    assert!(cache.size() > 0);
+    assert_eq!(cache.take_changes().len(), 1);
+    cached.get_balance(&script, &balance.tick).unwrap();
+    assert_eq!(cache.size(), 0);
+  }
+}
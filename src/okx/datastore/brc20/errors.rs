@@ -63,4 +63,10 @@ pub enum BRC20Error {
 
   #[error("invalid integer {0}")]
   InvalidInteger(String),
+
+  #[error("tick: {0} must be deployed with self_mint")]
+  SelfMintRequired(String),
+
+  #[error("tick: {0} can only be minted by a child of its deploy inscription")]
+  SelfMintPermissionDenied(String),
 }
@@ -1,17 +1,19 @@
 pub(super) mod balance;
+pub(crate) mod cache;
 pub(super) mod errors;
 pub(super) mod events;
 pub mod redb;
 pub(super) mod tick;
 pub(super) mod token_info;
+pub(super) mod token_stats;
 pub(super) mod transfer;
 pub(super) mod transferable_log;
 
 pub use self::{
   balance::Balance, errors::BRC20Error, events::Receipt, events::*, tick::*, token_info::TokenInfo,
-  transfer::TransferInfo, transferable_log::TransferableLog,
+  token_stats::TokenStats, transfer::TransferInfo, transferable_log::TransferableLog,
 };
-use super::ScriptKey;
+use super::{ScriptKey, StateRWriter};
 use crate::{InscriptionId, Result};
 use bitcoin::Txid;
 use std::fmt::{Debug, Display};
@@ -26,10 +28,43 @@ pub trait DataStoreReadOnly {
     tick: &Tick,
   ) -> Result<Option<Balance>, Self::Error>;
 
+  fn get_balances_at_height(
+    &self,
+    script_key: &ScriptKey,
+    height: u64,
+  ) -> Result<Vec<Balance>, Self::Error>;
+  fn get_balance_at_height(
+    &self,
+    script_key: &ScriptKey,
+    tick: &Tick,
+    height: u64,
+  ) -> Result<Option<Balance>, Self::Error>;
+
   fn get_token_info(&self, tick: &Tick) -> Result<Option<TokenInfo>, Self::Error>;
   fn get_tokens_info(&self) -> Result<Vec<TokenInfo>, Self::Error>;
 
+  fn get_token_stats(&self, tick: &Tick) -> Result<Option<TokenStats>, Self::Error>;
+  fn get_tokens_stats(&self) -> Result<Vec<TokenStats>, Self::Error>;
+
+  fn get_token_holders(
+    &self,
+    tick: &Tick,
+    start: usize,
+    limit: Option<usize>,
+  ) -> Result<Vec<(ScriptKey, Balance)>, Self::Error>;
+  fn get_token_holders_count(&self, tick: &Tick) -> Result<u64, Self::Error>;
+
   fn get_transaction_receipts(&self, txid: &Txid) -> Result<Vec<Receipt>, Self::Error>;
+  /// Returns up to `limit` receipts of the address that match `filter`, newest first, each with
+  /// its sequence among the receipts of the address. Only receipts older than the sequence `before`
+  /// are returned, if it is set.
+  fn get_address_receipts(
+    &self,
+    script_key: &ScriptKey,
+    before: Option<u64>,
+    limit: usize,
+    filter: &dyn Fn(&Receipt) -> bool,
+  ) -> Result<Vec<(u64, Txid, Receipt)>, Self::Error>;
 
   fn get_transferable(&self, script: &ScriptKey) -> Result<Vec<TransferableLog>, Self::Error>;
   fn get_transferable_by_tick(
@@ -56,6 +91,13 @@ pub trait DataStoreReadWrite: DataStoreReadOnly {
     new_balance: Balance,
   ) -> Result<(), Self::Error>;
 
+  fn insert_balance_history(
+    &self,
+    script_key: &ScriptKey,
+    height: u64,
+    balance: &Balance,
+  ) -> Result<(), Self::Error>;
+
   fn insert_token_info(&self, tick: &Tick, new_info: &TokenInfo) -> Result<(), Self::Error>;
 
   fn update_mint_token_info(
@@ -65,6 +107,8 @@ pub trait DataStoreReadWrite: DataStoreReadOnly {
     minted_block_number: u64,
   ) -> Result<(), Self::Error>;
 
+  fn update_token_stats(&self, tick: &Tick, stats: &TokenStats) -> Result<(), Self::Error>;
+
   fn save_transaction_receipts(&self, txid: &Txid, receipts: &[Receipt])
     -> Result<(), Self::Error>;
 
@@ -95,3 +139,11 @@ pub trait DataStoreReadWrite: DataStoreReadOnly {
     inscription_id: InscriptionId,
   ) -> Result<(), Self::Error>;
 }
+
+/// The BRC20 store of a `StateRWriter`, served through its write-back cache.
+pub(crate) type Store<'a, T> = cache::CachedDataStore<'a, redb::DataStore<'a, T>>;
+
+/// Opens the BRC20 store of `state`.
+pub(crate) fn store<S: StateRWriter>(state: &S) -> Store<'_, S::Tables> {
+  cache::CachedDataStore::new(redb::DataStore::new(state.tables()), &state.cache().brc20)
+}
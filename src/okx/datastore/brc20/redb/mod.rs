@@ -1,30 +1,154 @@
 mod read_only;
 mod read_write;
 
-use super::{LowerTick, ScriptKey, Tick};
-use crate::{InscriptionId, Result};
+use super::{ScriptKey, Tick, LOWER_TICK_BYTE_COUNT};
+use crate::{
+  okx::datastore::keys::{
+    inscription_id_key, max_key, min_key, script_key_hash, txid_key, INSCRIPTION_ID_KEY_LEN,
+    SCRIPT_KEY_HASH_LEN,
+  },
+  InscriptionId, Result,
+};
 
+use crate::okx::datastore::table::define_state_tables;
 use bitcoin::Txid;
-use redb::TableDefinition;
 
-pub use self::{read_only::try_init_tables, read_only::DataStoreReader, read_write::DataStore};
+define_state_tables! {
+  TABLES {
+    BRC20_BALANCES: &[u8] => &[u8] = "BRC20_BALANCES",
+    BRC20_TOKEN: &[u8] => &[u8] = "BRC20_TOKEN",
+    BRC20_EVENTS: &[u8] => &[u8] = "BRC20_EVENTS",
+    BRC20_TRANSFERABLELOG: &[u8] => &[u8] = "BRC20_TRANSFERABLELOG",
+    BRC20_INSCRIBE_TRANSFER: &[u8; 36] => &[u8] = "BRC20_INSCRIBE_TRANSFER",
+    BRC20_TOKEN_HOLDERS: &[u8] => &[u8] = "BRC20_TOKEN_HOLDERS",
+    BRC20_TOKEN_HOLDER_COUNTS: &[u8] => &[u8] = "BRC20_TOKEN_HOLDER_COUNTS",
+    BRC20_BALANCE_HISTORY: &[u8] => &[u8] = "BRC20_BALANCE_HISTORY",
+    BRC20_TOKEN_STATS: &[u8] => &[u8] = "BRC20_TOKEN_STATS",
+    BRC20_ADDRESS_EVENTS: &[u8] => &[u8] = "BRC20_ADDRESS_EVENTS",
+  }
+}
+
+/// DataStore keeps the BRC20 state in the tables of `T`.
+pub struct DataStore<'a, T> {
+  tables: &'a T,
+}
+
+impl<'a, T> DataStore<'a, T> {
+  pub fn new(tables: &'a T) -> Self {
+    Self { tables }
+  }
+}
+
+// The tables are keyed by fixed width binary composites, so that the entries of an address or a
+// tick are a range of keys: the hash of a script key, the zero padded lowercase tick, an inscription
+// id and a big endian height or sequence.
+
+const SCRIPT_TICK_KEY_LEN: usize = SCRIPT_KEY_HASH_LEN + LOWER_TICK_BYTE_COUNT;
+
+pub(super) fn tick_key(tick: &Tick) -> [u8; LOWER_TICK_BYTE_COUNT] {
+  tick.to_lowercase().to_bytes()
+}
+
+pub(super) fn script_tick_key(script: &ScriptKey, tick: &Tick) -> Vec<u8> {
+  [script_key_hash(script).as_slice(), &tick_key(tick)].concat()
+}
+
+fn min_script_tick_key(script: &ScriptKey) -> Vec<u8> {
+  min_key(&script_key_hash(script), LOWER_TICK_BYTE_COUNT)
+}
+
+fn max_script_tick_key(script: &ScriptKey) -> Vec<u8> {
+  max_key(&script_key_hash(script), LOWER_TICK_BYTE_COUNT)
+}
+
+fn script_tick_height_key(script: &ScriptKey, tick: &Tick, height: u64) -> Vec<u8> {
+  [
+    script_tick_key(script, tick).as_slice(),
+    &height.to_be_bytes(),
+  ]
+  .concat()
+}
+
+fn min_script_tick_height_key(script: &ScriptKey) -> Vec<u8> {
+  min_key(&script_key_hash(script), LOWER_TICK_BYTE_COUNT + 8)
+}
+
+fn max_script_tick_height_key(script: &ScriptKey) -> Vec<u8> {
+  max_key(&script_key_hash(script), LOWER_TICK_BYTE_COUNT + 8)
+}
+
+// split a balance history key into its script tick key and height.
+fn split_script_tick_height_key(key: &[u8]) -> (&[u8], u64) {
+  let (script_tick, height) = key.split_at(SCRIPT_TICK_KEY_LEN);
+  (script_tick, u64::from_be_bytes(height.try_into().unwrap()))
+}
+
+fn script_tick_inscription_key(
+  script: &ScriptKey,
+  tick: &Tick,
+  inscription_id: &InscriptionId,
+) -> Vec<u8> {
+  [
+    script_tick_key(script, tick).as_slice(),
+    &inscription_id_key(inscription_id),
+  ]
+  .concat()
+}
+
+fn min_script_inscription_key(script: &ScriptKey) -> Vec<u8> {
+  min_key(
+    &script_key_hash(script),
+    LOWER_TICK_BYTE_COUNT + INSCRIPTION_ID_KEY_LEN,
+  )
+}
+
+fn max_script_inscription_key(script: &ScriptKey) -> Vec<u8> {
+  max_key(
+    &script_key_hash(script),
+    LOWER_TICK_BYTE_COUNT + INSCRIPTION_ID_KEY_LEN,
+  )
+}
+
+fn min_script_tick_inscription_key(script: &ScriptKey, tick: &Tick) -> Vec<u8> {
+  min_key(&script_tick_key(script, tick), INSCRIPTION_ID_KEY_LEN)
+}
+
+fn max_script_tick_inscription_key(script: &ScriptKey, tick: &Tick) -> Vec<u8> {
+  max_key(&script_tick_key(script, tick), INSCRIPTION_ID_KEY_LEN)
+}
+
+fn script_sequence_key(script: &ScriptKey, sequence: u64) -> Vec<u8> {
+  [script_key_hash(script).as_slice(), &sequence.to_be_bytes()].concat()
+}
 
-const BRC20_BALANCES: TableDefinition<&str, &[u8]> = TableDefinition::new("BRC20_BALANCES");
-const BRC20_TOKEN: TableDefinition<&str, &[u8]> = TableDefinition::new("BRC20_TOKEN");
-const BRC20_EVENTS: TableDefinition<&str, &[u8]> = TableDefinition::new("BRC20_EVENTS");
-const BRC20_TRANSFERABLELOG: TableDefinition<&str, &[u8]> =
-  TableDefinition::new("BRC20_TRANSFERABLELOG");
-const BRC20_INSCRIBE_TRANSFER: TableDefinition<&[u8; 36], &[u8]> =
-  TableDefinition::new("BRC20_INSCRIBE_TRANSFER");
+fn min_script_sequence_key(script: &ScriptKey) -> Vec<u8> {
+  min_key(&script_key_hash(script), 8)
+}
+
+fn max_script_sequence_key(script: &ScriptKey) -> Vec<u8> {
+  max_key(&script_key_hash(script), 8)
+}
+
+// split the sequence out of an address event key.
+fn split_script_sequence_key(key: &[u8]) -> u64 {
+  u64::from_be_bytes(key[SCRIPT_KEY_HASH_LEN..].try_into().unwrap())
+}
 
-fn script_tick_key(script: &ScriptKey, tick: &Tick) -> String {
-  format!("{}_{}", script, tick.to_lowercase().hex())
+// the holders of a tick are keyed by their inverted overall balance, so that they iterate from the
+// largest balance down and a page of holders is a range of keys.
+fn tick_holder_key(tick: &Tick, overall_balance: u128, script: &ScriptKey) -> Vec<u8> {
+  [
+    tick_key(tick).as_slice(),
+    &(u128::MAX - overall_balance).to_be_bytes(),
+    &script_key_hash(script),
+  ]
+  .concat()
 }
 
-fn min_script_tick_key(script: &ScriptKey) -> String {
-  format!("{}_{}", script, LowerTick::min_hex())
+fn min_tick_holder_key(tick: &Tick) -> Vec<u8> {
+  min_key(&tick_key(tick), 16 + SCRIPT_KEY_HASH_LEN)
 }
 
-fn max_script_tick_key(script: &ScriptKey) -> String {
-  format!("{}_{}", script, LowerTick::max_hex())
+fn max_tick_holder_key(tick: &Tick) -> Vec<u8> {
+  max_key(&tick_key(tick), 16 + SCRIPT_KEY_HASH_LEN)
 }
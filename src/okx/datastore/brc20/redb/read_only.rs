@@ -1,107 +1,22 @@
 use super::*;
-use crate::okx::datastore::brc20::{
-  Balance, DataStoreReadOnly, Receipt, Tick, TokenInfo, TransferInfo, TransferableLog,
+use crate::okx::datastore::{
+  brc20::{
+    Balance, DataStoreReadOnly, Receipt, Tick, TokenInfo, TokenStats, TransferInfo, TransferableLog,
+  },
+  table::TableReader,
 };
-use redb::{
-  AccessGuard, Range, ReadOnlyTable, ReadTransaction, ReadableTable, RedbKey, RedbValue,
-  StorageError, Table, TableDefinition, WriteTransaction,
-};
-use std::borrow::Borrow;
-use std::ops::RangeBounds;
-
-pub fn try_init_tables<'db, 'a>(
-  wtx: &'a WriteTransaction<'db>,
-  rtx: &'a ReadTransaction<'db>,
-) -> Result<bool, redb::Error> {
-  if rtx.open_table(BRC20_BALANCES).is_err() {
-    wtx.open_table(BRC20_BALANCES)?;
-    wtx.open_table(BRC20_TOKEN)?;
-    wtx.open_table(BRC20_EVENTS)?;
-    wtx.open_table(BRC20_TRANSFERABLELOG)?;
-    wtx.open_table(BRC20_INSCRIBE_TRANSFER)?;
-  }
-
-  Ok(true)
-}
-
-pub struct DataStoreReader<'db, 'a> {
-  wrapper: ReaderWrapper<'db, 'a>,
-}
-
-pub(super) fn new_with_wtx<'db, 'a>(wtx: &'a WriteTransaction<'db>) -> DataStoreReader<'db, 'a> {
-  DataStoreReader {
-    wrapper: ReaderWrapper::Wtx(wtx),
-  }
-}
-
-impl<'db, 'a> DataStoreReader<'db, 'a> {
-  pub fn new(rtx: &'a ReadTransaction<'db>) -> Self {
-    Self {
-      wrapper: ReaderWrapper::Rtx(rtx),
-    }
-  }
-}
-
-enum ReaderWrapper<'db, 'a> {
-  Rtx(&'a ReadTransaction<'db>),
-  Wtx(&'a WriteTransaction<'db>),
-}
-
-impl<'db, 'a> ReaderWrapper<'db, 'a> {
-  fn open_table<K: RedbKey + 'static, V: RedbValue + 'static>(
-    &self,
-    definition: TableDefinition<'_, K, V>,
-  ) -> Result<TableWrapper<'db, '_, K, V>, redb::Error> {
-    match self {
-      Self::Rtx(rtx) => Ok(TableWrapper::RtxTable(rtx.open_table(definition)?)),
-      Self::Wtx(wtx) => Ok(TableWrapper::WtxTable(wtx.open_table(definition)?)),
-    }
-  }
-}
-
-enum TableWrapper<'db, 'txn, K: RedbKey + 'static, V: RedbValue + 'static> {
-  RtxTable(ReadOnlyTable<'txn, K, V>),
-  WtxTable(Table<'db, 'txn, K, V>),
-}
-
-impl<'db, 'txn, K: RedbKey + 'static, V: RedbValue + 'static> TableWrapper<'db, 'txn, K, V> {
-  fn get<'a>(
-    &self,
-    key: impl Borrow<K::SelfType<'a>>,
-  ) -> Result<Option<AccessGuard<'_, V>>, StorageError>
-  where
-    K: 'a,
-  {
-    match self {
-      Self::RtxTable(rtx_table) => rtx_table.get(key),
-      Self::WtxTable(wtx_table) => wtx_table.get(key),
-    }
-  }
 
-  fn range<'a: 'b, 'b, KR>(
-    &'a self,
-    range: impl RangeBounds<KR> + 'b,
-  ) -> Result<Range<'a, K, V>, StorageError>
-  where
-    K: 'a,
-    KR: Borrow<K::SelfType<'b>> + 'b,
-  {
-    match self {
-      Self::RtxTable(rtx_table) => rtx_table.range(range),
-      Self::WtxTable(wtx_table) => wtx_table.range(range),
-    }
-  }
-}
-
-impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
+impl<'a, T: TableReader> DataStoreReadOnly for DataStore<'a, T> {
   type Error = redb::Error;
 
   fn get_balances(&self, script_key: &ScriptKey) -> Result<Vec<Balance>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20_BALANCES)?
-        .range(min_script_tick_key(script_key).as_str()..max_script_tick_key(script_key).as_str())?
+        .tables
+        .table(BRC20_BALANCES)
+        .range(
+          min_script_tick_key(script_key).as_slice()..=max_script_tick_key(script_key).as_slice(),
+        )?
         .flat_map(|result| {
           result.map(|(_, data)| bincode::deserialize::<Balance>(data.value()).unwrap())
         })
@@ -116,19 +31,68 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   ) -> Result<Option<Balance>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20_BALANCES)?
-        .get(script_tick_key(script_key, tick).as_str())?
+        .tables
+        .table(BRC20_BALANCES)
+        .get(script_tick_key(script_key, tick).as_slice())?
         .map(|v| bincode::deserialize::<Balance>(v.value()).unwrap()),
     )
   }
 
+  fn get_balances_at_height(
+    &self,
+    script_key: &ScriptKey,
+    height: u64,
+  ) -> Result<Vec<Balance>, Self::Error> {
+    let table = self.tables.table(BRC20_BALANCE_HISTORY);
+
+    // history keys are ordered by tick and then by height, so the last entry
+    // not above the height is the balance of the tick at that height.
+    let mut balances: Vec<(Vec<u8>, Balance)> = Vec::new();
+    for result in table.range(
+      min_script_tick_height_key(script_key).as_slice()
+        ..=max_script_tick_height_key(script_key).as_slice(),
+    )? {
+      let (key, data) = result?;
+      let (script_tick, updated_at) = split_script_tick_height_key(key.value());
+      if updated_at > height {
+        continue;
+      }
+      let balance = bincode::deserialize::<Balance>(data.value()).unwrap();
+      match balances.last_mut() {
+        Some((last, last_balance)) if last == script_tick => *last_balance = balance,
+        _ => balances.push((script_tick.to_vec(), balance)),
+      }
+    }
+
+    Ok(balances.into_iter().map(|(_, balance)| balance).collect())
+  }
+
+  fn get_balance_at_height(
+    &self,
+    script_key: &ScriptKey,
+    tick: &Tick,
+    height: u64,
+  ) -> Result<Option<Balance>, Self::Error> {
+    Ok(
+      self
+        .tables
+        .table(BRC20_BALANCE_HISTORY)
+        .range(
+          script_tick_height_key(script_key, tick, 0).as_slice()
+            ..=script_tick_height_key(script_key, tick, height).as_slice(),
+        )?
+        .next_back()
+        .transpose()?
+        .map(|(_, v)| bincode::deserialize::<Balance>(v.value()).unwrap()),
+    )
+  }
+
   fn get_token_info(&self, tick: &Tick) -> Result<Option<TokenInfo>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20_TOKEN)?
-        .get(tick.to_lowercase().hex().as_str())?
+        .tables
+        .table(BRC20_TOKEN)
+        .get(tick_key(tick).as_slice())?
         .map(|v| bincode::deserialize::<TokenInfo>(v.value()).unwrap()),
     )
   }
@@ -136,9 +100,9 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   fn get_tokens_info(&self) -> Result<Vec<TokenInfo>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20_TOKEN)?
-        .range::<&str>(..)?
+        .tables
+        .table(BRC20_TOKEN)
+        .iter()?
         .flat_map(|result| {
           result.map(|(_, data)| bincode::deserialize::<TokenInfo>(data.value()).unwrap())
         })
@@ -146,28 +110,150 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
     )
   }
 
+  fn get_token_stats(&self, tick: &Tick) -> Result<Option<TokenStats>, Self::Error> {
+    Ok(
+      self
+        .tables
+        .table(BRC20_TOKEN_STATS)
+        .get(tick_key(tick).as_slice())?
+        .map(|v| bincode::deserialize::<TokenStats>(v.value()).unwrap()),
+    )
+  }
+
+  fn get_tokens_stats(&self) -> Result<Vec<TokenStats>, Self::Error> {
+    Ok(
+      self
+        .tables
+        .table(BRC20_TOKEN_STATS)
+        .iter()?
+        .flat_map(|result| {
+          result.map(|(_, data)| bincode::deserialize::<TokenStats>(data.value()).unwrap())
+        })
+        .collect(),
+    )
+  }
+
+  fn get_token_holders(
+    &self,
+    tick: &Tick,
+    start: usize,
+    limit: Option<usize>,
+  ) -> Result<Vec<(ScriptKey, Balance)>, Self::Error> {
+    let holders_table = self.tables.table(BRC20_TOKEN_HOLDERS);
+    let balances_table = self.tables.table(BRC20_BALANCES);
+
+    let mut holders = Vec::new();
+    for result in holders_table
+      .range(min_tick_holder_key(tick).as_slice()..=max_tick_holder_key(tick).as_slice())?
+      .skip(start)
+      .take(limit.unwrap_or(usize::MAX))
+    {
+      let (_, data) = result?;
+      let script_key = bincode::deserialize::<ScriptKey>(data.value()).unwrap();
+      let balance = balances_table
+        .get(script_tick_key(&script_key, tick).as_slice())?
+        .map(|v| bincode::deserialize::<Balance>(v.value()).unwrap())
+        .ok_or_else(|| {
+          redb::Error::Corrupted(format!("holder {script_key} of tick {tick} has no balance"))
+        })?;
+      holders.push((script_key, balance));
+    }
+
+    Ok(holders)
+  }
+
+  fn get_token_holders_count(&self, tick: &Tick) -> Result<u64, Self::Error> {
+    Ok(
+      self
+        .tables
+        .table(BRC20_TOKEN_HOLDER_COUNTS)
+        .get(tick_key(tick).as_slice())?
+        .map_or(0, |count| {
+          u64::from_be_bytes(count.value().try_into().unwrap())
+        }),
+    )
+  }
+
   fn get_transaction_receipts(&self, txid: &Txid) -> Result<Vec<Receipt>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20_EVENTS)?
-        .get(txid.to_string().as_str())?
+        .tables
+        .table(BRC20_EVENTS)
+        .get(txid_key(txid).as_slice())?
         .map_or(Vec::new(), |v| {
           bincode::deserialize::<Vec<Receipt>>(v.value()).unwrap()
         }),
     )
   }
 
+  fn get_address_receipts(
+    &self,
+    script_key: &ScriptKey,
+    before: Option<u64>,
+    limit: usize,
+    filter: &dyn Fn(&Receipt) -> bool,
+  ) -> Result<Vec<(u64, Txid, Receipt)>, Self::Error> {
+    let address_table = self.tables.table(BRC20_ADDRESS_EVENTS);
+    let receipts_table = self.tables.table(BRC20_EVENTS);
+
+    // the address entries reference a receipt by its transaction and position, the receipts of
+    // a transaction are loaded once for its consecutive entries.
+    let mut transaction: Option<(Txid, Vec<Receipt>)> = None;
+    let mut receipts = Vec::new();
+    for result in address_table
+      .range(
+        min_script_sequence_key(script_key).as_slice()
+          ..script_sequence_key(script_key, before.unwrap_or(u64::MAX)).as_slice(),
+      )?
+      .rev()
+    {
+      if receipts.len() >= limit {
+        break;
+      }
+
+      let (key, data) = result?;
+      let (txid, index) = bincode::deserialize::<(Txid, u32)>(data.value()).unwrap();
+      if transaction.as_ref().map(|(cached, _)| *cached) != Some(txid) {
+        let tx_receipts = receipts_table
+          .get(txid_key(&txid).as_slice())?
+          .map_or(Vec::new(), |v| {
+            bincode::deserialize::<Vec<Receipt>>(v.value()).unwrap()
+          });
+        transaction = Some((txid, tx_receipts));
+      }
+
+      let receipt = transaction
+        .as_ref()
+        .and_then(|(_, tx_receipts)| tx_receipts.get(usize::try_from(index).unwrap()))
+        .ok_or_else(|| {
+          redb::Error::Corrupted(format!(
+            "receipt {index} of transaction {txid} of address {script_key} not found"
+          ))
+        })?;
+      if filter(receipt) {
+        receipts.push((
+          split_script_sequence_key(key.value()),
+          txid,
+          receipt.clone(),
+        ));
+      }
+    }
+
+    Ok(receipts)
+  }
+
   fn get_transferable(&self, script: &ScriptKey) -> Result<Vec<TransferableLog>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20_TRANSFERABLELOG)?
-        .range(min_script_tick_key(script).as_str()..max_script_tick_key(script).as_str())?
+        .tables
+        .table(BRC20_TRANSFERABLELOG)
+        .range(
+          min_script_inscription_key(script).as_slice()
+            ..=max_script_inscription_key(script).as_slice(),
+        )?
         .flat_map(|result| {
-          result.map(|(_, v)| bincode::deserialize::<Vec<TransferableLog>>(v.value()).unwrap())
+          result.map(|(_, v)| bincode::deserialize::<TransferableLog>(v.value()).unwrap())
         })
-        .flatten()
         .collect(),
     )
   }
@@ -179,12 +265,16 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   ) -> Result<Vec<TransferableLog>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20_TRANSFERABLELOG)?
-        .get(script_tick_key(script, tick).as_str())?
-        .map_or(Vec::new(), |v| {
-          bincode::deserialize::<Vec<TransferableLog>>(v.value()).unwrap()
-        }),
+        .tables
+        .table(BRC20_TRANSFERABLELOG)
+        .range(
+          min_script_tick_inscription_key(script, tick).as_slice()
+            ..=max_script_tick_inscription_key(script, tick).as_slice(),
+        )?
+        .flat_map(|result| {
+          result.map(|(_, v)| bincode::deserialize::<TransferableLog>(v.value()).unwrap())
+        })
+        .collect(),
     )
   }
 
@@ -206,15 +296,11 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
     &self,
     inscription_id: InscriptionId,
   ) -> Result<Option<TransferInfo>, Self::Error> {
-    let mut value = [0; 36];
-    let (txid, index) = value.split_at_mut(32);
-    txid.copy_from_slice(inscription_id.txid.as_ref());
-    index.copy_from_slice(&inscription_id.index.to_be_bytes());
     Ok(
       self
-        .wrapper
-        .open_table(BRC20_INSCRIBE_TRANSFER)?
-        .get(&value)?
+        .tables
+        .table(BRC20_INSCRIBE_TRANSFER)
+        .get(&inscription_id_key(&inscription_id))?
         .map(|v| bincode::deserialize::<TransferInfo>(v.value()).unwrap()),
     )
   }
@@ -1,96 +1,171 @@
 use crate::{
-  okx::datastore::brc20::{
-    Balance, DataStoreReadOnly, DataStoreReadWrite, Receipt, Tick, TokenInfo, TransferInfo,
-    TransferableLog,
+  index::state_hash,
+  okx::datastore::{
+    brc20::{
+      Balance, DataStoreReadOnly, DataStoreReadWrite, Receipt, Tick, TokenInfo, TokenStats,
+      TransferInfo, TransferableLog,
+    },
+    keys::save_script_key,
+    table::TableWriter,
   },
-  InscriptionId,
 };
 
 use super::*;
 use bitcoin::Txid;
-use redb::WriteTransaction;
+use redb::TableDefinition;
 
-pub struct DataStore<'db, 'a> {
-  wtx: &'a WriteTransaction<'db>,
-}
-
-impl<'db, 'a> DataStore<'db, 'a> {
-  pub fn new(wtx: &'a WriteTransaction<'db>) -> Self {
-    Self { wtx }
-  }
-}
-
-impl<'db, 'a> DataStoreReadOnly for DataStore<'db, 'a> {
-  type Error = redb::Error;
-
-  fn get_balances(&self, script_key: &ScriptKey) -> Result<Vec<Balance>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_balances(script_key)
-  }
-
-  fn get_balance(
+impl<'a, T: TableWriter> DataStore<'a, T> {
+  // writes a consensus state entry and records it for the block state hash.
+  // Balances and token infos are recorded by the cache instead, as it writes them back after the
+  // block that changed them.
+  fn insert_state(
     &self,
-    script_key: &ScriptKey,
-    tick: &Tick,
-  ) -> Result<Option<Balance>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_balance(script_key, tick)
-  }
-
-  fn get_token_info(&self, tick: &Tick) -> Result<Option<TokenInfo>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_token_info(tick)
+    table: TableDefinition<'static, &'static [u8], &'static [u8]>,
+    key: &[u8],
+    value: &[u8],
+    change: state_hash::Change,
+  ) -> Result<(), redb::Error> {
+    self.tables.table(table).insert(key, value)?;
+    state_hash::record_change(self.tables, change)
   }
 
-  fn get_tokens_info(&self) -> Result<Vec<TokenInfo>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_tokens_info()
-  }
-
-  fn get_transaction_receipts(&self, txid: &Txid) -> Result<Vec<Receipt>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_transaction_receipts(txid)
-  }
-
-  fn get_transferable(&self, script: &ScriptKey) -> Result<Vec<TransferableLog>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_transferable(script)
+  // removes a consensus state entry and records it for the block state hash.
+  fn remove_state(
+    &self,
+    table: TableDefinition<'static, &'static [u8], &'static [u8]>,
+    key: &[u8],
+    change: state_hash::Change,
+  ) -> Result<(), redb::Error> {
+    self.tables.table(table).remove(key)?;
+    state_hash::record_change(self.tables, change)
   }
 
-  fn get_transferable_by_tick(
+  fn insert_address_receipt(
     &self,
     script: &ScriptKey,
-    tick: &Tick,
-  ) -> Result<Vec<TransferableLog>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_transferable_by_tick(script, tick)
+    txid: &Txid,
+    index: usize,
+  ) -> Result<(), redb::Error> {
+    let sequence = self
+      .tables
+      .table(BRC20_ADDRESS_EVENTS)
+      .range(
+        min_script_sequence_key(script).as_slice()..=max_script_sequence_key(script).as_slice(),
+      )?
+      .next_back()
+      .transpose()?
+      .map_or(0, |(key, _)| split_script_sequence_key(key.value()) + 1);
+
+    self.tables.table(BRC20_ADDRESS_EVENTS).insert(
+      script_sequence_key(script, sequence).as_slice(),
+      bincode::serialize(&(txid, u32::try_from(index).unwrap()))
+        .unwrap()
+        .as_slice(),
+    )?;
+    Ok(())
   }
 
-  fn get_transferable_by_id(
+  /// Indexes the receipts of a transaction from the `start`th on under the addresses involved.
+  pub(crate) fn index_address_receipts(
     &self,
-    script: &ScriptKey,
-    inscription_id: &InscriptionId,
-  ) -> Result<Option<TransferableLog>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_transferable_by_id(script, inscription_id)
+    txid: &Txid,
+    receipts: &[Receipt],
+    start: usize,
+  ) -> Result<(), redb::Error> {
+    for (index, receipt) in receipts.iter().enumerate().skip(start) {
+      self.insert_address_receipt(&receipt.from, txid, index)?;
+      if receipt.to != receipt.from {
+        self.insert_address_receipt(&receipt.to, txid, index)?;
+      }
+    }
+    Ok(())
   }
 
-  fn get_inscribe_transfer_inscription(
+  /// Adds an address that didn't hold the tick of `balance` to its holders, keyed by its overall
+  /// balance.
+  pub(crate) fn insert_token_holder(
     &self,
-    inscription_id: InscriptionId,
-  ) -> Result<Option<TransferInfo>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_inscribe_transfer_inscription(inscription_id)
+    script_key: &ScriptKey,
+    balance: &Balance,
+  ) -> Result<(), redb::Error> {
+    self.tables.table(BRC20_TOKEN_HOLDERS).insert(
+      tick_holder_key(&balance.tick, balance.overall_balance, script_key).as_slice(),
+      bincode::serialize(script_key).unwrap().as_slice(),
+    )?;
+    self.count_token_holder(&balance.tick, true)
+  }
+
+  // keeps the number of holders of a tick, as an address starts or stops holding it.
+  fn count_token_holder(&self, tick: &Tick, holds: bool) -> Result<(), redb::Error> {
+    let table = self.tables.table(BRC20_TOKEN_HOLDER_COUNTS);
+    let count = table.get(tick_key(tick).as_slice())?.map_or(0, |count| {
+      u64::from_be_bytes(count.value().try_into().unwrap())
+    });
+    let count = if holds {
+      count + 1
+    } else {
+      count.saturating_sub(1)
+    };
+    table.insert(tick_key(tick).as_slice(), count.to_be_bytes().as_slice())?;
+    Ok(())
   }
 }
 
-impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
+impl<'a, T: TableWriter> DataStoreReadWrite for DataStore<'a, T> {
   fn update_token_balance(
     &self,
     script_key: &ScriptKey,
     new_balance: Balance,
   ) -> Result<(), Self::Error> {
-    self.wtx.open_table(BRC20_BALANCES)?.insert(
-      script_tick_key(script_key, &new_balance.tick).as_str(),
+    // keep the tick holder index in sync, an address holds a tick while its overall balance is
+    // non-zero, and is keyed by that balance.
+    let old_overall_balance = self
+      .get_balance(script_key, &new_balance.tick)?
+      .map_or(0, |balance| balance.overall_balance);
+    if old_overall_balance != new_balance.overall_balance {
+      if old_overall_balance > 0 {
+        self
+          .tables
+          .table(BRC20_TOKEN_HOLDERS)
+          .remove(tick_holder_key(&new_balance.tick, old_overall_balance, script_key).as_slice())?;
+      }
+      match (old_overall_balance > 0, new_balance.overall_balance > 0) {
+        (false, true) => self.insert_token_holder(script_key, &new_balance)?,
+        (true, true) => {
+          self.tables.table(BRC20_TOKEN_HOLDERS).insert(
+            tick_holder_key(&new_balance.tick, new_balance.overall_balance, script_key).as_slice(),
+            bincode::serialize(script_key).unwrap().as_slice(),
+          )?;
+        }
+        (true, false) => self.count_token_holder(&new_balance.tick, false)?,
+        (false, false) => {}
+      }
+    }
+    save_script_key(self.tables, script_key)?;
+
+    self.tables.table(BRC20_BALANCES).insert(
+      script_tick_key(script_key, &new_balance.tick).as_slice(),
       bincode::serialize(&new_balance).unwrap().as_slice(),
     )?;
     Ok(())
   }
 
+  fn insert_balance_history(
+    &self,
+    script_key: &ScriptKey,
+    height: u64,
+    balance: &Balance,
+  ) -> Result<(), Self::Error> {
+    self.tables.table(BRC20_BALANCE_HISTORY).insert(
+      script_tick_height_key(script_key, &balance.tick, height).as_slice(),
+      bincode::serialize(balance).unwrap().as_slice(),
+    )?;
+    Ok(())
+  }
+
   fn insert_token_info(&self, tick: &Tick, new_info: &TokenInfo) -> Result<(), Self::Error> {
-    self.wtx.open_table(BRC20_TOKEN)?.insert(
-      tick.to_lowercase().hex().as_str(),
+    self.tables.table(BRC20_TOKEN).insert(
+      tick_key(tick).as_slice(),
       bincode::serialize(new_info).unwrap().as_slice(),
     )?;
     Ok(())
@@ -109,20 +184,32 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
     info.minted = minted_amt;
     info.latest_mint_number = minted_block_number;
 
-    self.wtx.open_table(BRC20_TOKEN)?.insert(
-      tick.to_lowercase().hex().as_str(),
+    self.tables.table(BRC20_TOKEN).insert(
+      tick_key(tick).as_slice(),
       bincode::serialize(&info).unwrap().as_slice(),
     )?;
     Ok(())
   }
 
+  fn update_token_stats(&self, tick: &Tick, stats: &TokenStats) -> Result<(), Self::Error> {
+    self.tables.table(BRC20_TOKEN_STATS).insert(
+      tick_key(tick).as_slice(),
+      bincode::serialize(stats).unwrap().as_slice(),
+    )?;
+    Ok(())
+  }
+
   fn save_transaction_receipts(
     &self,
     txid: &Txid,
     receipts: &[Receipt],
   ) -> Result<(), Self::Error> {
-    self.wtx.open_table(BRC20_EVENTS)?.insert(
-      txid.to_string().as_str(),
+    // only receipts appended since the last save are new to the address index.
+    let saved = self.get_transaction_receipts(txid)?.len();
+    self.index_address_receipts(txid, receipts, saved)?;
+
+    self.tables.table(BRC20_EVENTS).insert(
+      txid_key(txid).as_slice(),
       bincode::serialize(receipts).unwrap().as_slice(),
     )?;
     Ok(())
@@ -140,19 +227,21 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
     tick: &Tick,
     inscription: TransferableLog,
   ) -> Result<(), Self::Error> {
-    let mut logs = self.get_transferable_by_tick(script, tick)?;
-    if logs
-      .iter()
-      .any(|log| log.inscription_id == inscription.inscription_id)
+    let key = script_tick_inscription_key(script, tick, &inscription.inscription_id);
+    if self
+      .tables
+      .table(BRC20_TRANSFERABLELOG)
+      .get(key.as_slice())?
+      .is_some()
     {
       return Ok(());
     }
 
-    logs.push(inscription);
-
-    self.wtx.open_table(BRC20_TRANSFERABLELOG)?.insert(
-      script_tick_key(script, tick).as_str(),
-      bincode::serialize(&logs).unwrap().as_slice(),
+    self.insert_state(
+      BRC20_TRANSFERABLELOG,
+      key.as_slice(),
+      bincode::serialize(&inscription).unwrap().as_slice(),
+      state_hash::Change::brc20_transferable(script, tick, &inscription),
     )?;
     Ok(())
   }
@@ -163,15 +252,17 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
     tick: &Tick,
     inscription_id: InscriptionId,
   ) -> Result<(), Self::Error> {
-    let mut logs = self.get_transferable_by_tick(script, tick)?;
-    let old_len = logs.len();
-
-    logs.retain(|log| log.inscription_id != inscription_id);
-
-    if logs.len() != old_len {
-      self.wtx.open_table(BRC20_TRANSFERABLELOG)?.insert(
-        script_tick_key(script, tick).as_str(),
-        bincode::serialize(&logs).unwrap().as_slice(),
+    let key = script_tick_inscription_key(script, tick, &inscription_id);
+    if self
+      .tables
+      .table(BRC20_TRANSFERABLELOG)
+      .get(key.as_slice())?
+      .is_some()
+    {
+      self.remove_state(
+        BRC20_TRANSFERABLELOG,
+        key.as_slice(),
+        state_hash::Change::brc20_transferable_removed(script, tick, &inscription_id),
       )?;
     }
     Ok(())
@@ -182,13 +273,8 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
     inscription_id: InscriptionId,
     transfer_info: TransferInfo,
   ) -> Result<(), Self::Error> {
-    let mut value = [0; 36];
-    let (txid, index) = value.split_at_mut(32);
-    txid.copy_from_slice(inscription_id.txid.as_ref());
-    index.copy_from_slice(&inscription_id.index.to_be_bytes());
-
-    self.wtx.open_table(BRC20_INSCRIBE_TRANSFER)?.insert(
-      &value,
+    self.tables.table(BRC20_INSCRIBE_TRANSFER).insert(
+      &inscription_id_key(&inscription_id),
       bincode::serialize(&transfer_info).unwrap().as_slice(),
     )?;
     Ok(())
@@ -198,15 +284,10 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
     &self,
     inscription_id: InscriptionId,
   ) -> Result<(), Self::Error> {
-    let mut value = [0; 36];
-    let (txid, index) = value.split_at_mut(32);
-    txid.copy_from_slice(inscription_id.txid.as_ref());
-    index.copy_from_slice(&inscription_id.index.to_be_bytes());
-
     self
-      .wtx
-      .open_table(BRC20_INSCRIBE_TRANSFER)?
-      .remove(&value)?;
+      .tables
+      .table(BRC20_INSCRIBE_TRANSFER)
+      .remove(&inscription_id_key(&inscription_id))?;
     Ok(())
   }
 }
@@ -215,7 +296,7 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
 mod tests {
   use crate::okx::datastore::brc20::{
     BRC20Error, Balance, DataStoreReadOnly, DataStoreReadWrite, Event, MintEvent, OperationType,
-    Receipt, Tick, TokenInfo, TransferEvent, TransferableLog,
+    Receipt, Tick, TokenInfo, TokenStats, TransferEvent, TransferableLog,
   };
 
   use super::*;
@@ -337,6 +418,174 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_get_token_holders() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    let brc20db = DataStore::new(&wtx);
+
+    let script1 = ScriptKey::from_address(
+      Address::from_str("bc1qhvd6suvqzjcu9pxjhrwhtrlj85ny3n2mqql5w4")
+        .unwrap()
+        .assume_checked(),
+    );
+    let script2 = ScriptKey::from_address(
+      Address::from_str("33iFwdLuRpW1uK1RTRqsoi8rR4NpDzk66k")
+        .unwrap()
+        .assume_checked(),
+    );
+    let script3 = ScriptKey::from_address(
+      Address::from_str("132F25rTsvBdp9JzLLBHP5mvGY66i1xdiM")
+        .unwrap()
+        .assume_checked(),
+    );
+    let tick = Tick::from_str("abcd").unwrap();
+    let other_tick = Tick::from_str("abce").unwrap();
+
+    let balance1 = Balance {
+      tick: tick.clone(),
+      overall_balance: 10,
+      transferable_balance: 0,
+    };
+    let balance2 = Balance {
+      tick: Tick::from_str("ABCD").unwrap(),
+      overall_balance: 30,
+      transferable_balance: 10,
+    };
+    let balance3 = Balance {
+      tick: tick.clone(),
+      overall_balance: 20,
+      transferable_balance: 0,
+    };
+    brc20db
+      .update_token_balance(&script1, balance1.clone())
+      .unwrap();
+    brc20db
+      .update_token_balance(&script2, balance2.clone())
+      .unwrap();
+    brc20db
+      .update_token_balance(&script3, balance3.clone())
+      .unwrap();
+    brc20db
+      .update_token_balance(
+        &script1,
+        Balance {
+          tick: other_tick.clone(),
+          overall_balance: 100,
+          transferable_balance: 0,
+        },
+      )
+      .unwrap();
+
+    assert_eq!(brc20db.get_token_holders_count(&tick).unwrap(), 3);
+    assert_eq!(brc20db.get_token_holders_count(&other_tick).unwrap(), 1);
+    assert_eq!(
+      brc20db.get_token_holders(&tick, 0, None).unwrap(),
+      vec![
+        (script2.clone(), balance2.clone()),
+        (script3.clone(), balance3.clone()),
+        (script1.clone(), balance1),
+      ]
+    );
+    assert_eq!(
+      brc20db.get_token_holders(&tick, 1, Some(1)).unwrap(),
+      vec![(script3.clone(), balance3.clone())]
+    );
+
+    // a changed balance moves the holder within the ranking.
+    let balance1 = Balance {
+      tick: tick.clone(),
+      overall_balance: 25,
+      transferable_balance: 0,
+    };
+    brc20db
+      .update_token_balance(&script1, balance1.clone())
+      .unwrap();
+    assert_eq!(brc20db.get_token_holders_count(&tick).unwrap(), 3);
+    assert_eq!(
+      brc20db.get_token_holders(&tick, 0, Some(2)).unwrap(),
+      vec![
+        (script2.clone(), balance2.clone()),
+        (script1.clone(), balance1)
+      ]
+    );
+
+    // an empty balance is no longer counted as a holder.
+    brc20db
+      .update_token_balance(&script1, Balance::new(&tick))
+      .unwrap();
+    assert_eq!(brc20db.get_token_holders_count(&tick).unwrap(), 2);
+    assert_eq!(
+      brc20db.get_token_holders(&tick, 0, None).unwrap(),
+      vec![(script2, balance2), (script3, balance3)]
+    );
+  }
+
+  #[test]
+  fn test_balance_history() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    let brc20db = DataStore::new(&wtx);
+
+    let script = ScriptKey::from_address(
+      Address::from_str("bc1qhvd6suvqzjcu9pxjhrwhtrlj85ny3n2mqql5w4")
+        .unwrap()
+        .assume_checked(),
+    );
+    let tick1 = Tick::from_str("abcd").unwrap();
+    let tick2 = Tick::from_str("1234").unwrap();
+    let balance_at = |tick: &Tick, overall_balance: u128| Balance {
+      tick: tick.clone(),
+      overall_balance,
+      transferable_balance: 0,
+    };
+
+    brc20db
+      .insert_balance_history(&script, 100, &balance_at(&tick1, 10))
+      .unwrap();
+    brc20db
+      .insert_balance_history(&script, 200, &balance_at(&tick1, 20))
+      .unwrap();
+    brc20db
+      .insert_balance_history(&script, 150, &balance_at(&tick2, 5))
+      .unwrap();
+
+    assert_eq!(
+      brc20db.get_balance_at_height(&script, &tick1, 99).unwrap(),
+      None
+    );
+    assert_eq!(
+      brc20db.get_balance_at_height(&script, &tick1, 100).unwrap(),
+      Some(balance_at(&tick1, 10))
+    );
+    assert_eq!(
+      brc20db
+        .get_balance_at_height(&script, &Tick::from_str("ABCD").unwrap(), 199)
+        .unwrap(),
+      Some(balance_at(&tick1, 10))
+    );
+    assert_eq!(
+      brc20db
+        .get_balance_at_height(&script, &tick1, 1000)
+        .unwrap(),
+      Some(balance_at(&tick1, 20))
+    );
+
+    assert_eq!(brc20db.get_balances_at_height(&script, 99).unwrap(), vec![]);
+    assert_eq!(
+      brc20db.get_balances_at_height(&script, 149).unwrap(),
+      vec![balance_at(&tick1, 10)]
+    );
+    let mut balances = brc20db.get_balances_at_height(&script, 200).unwrap();
+    balances.sort_by(|a, b| a.tick.cmp(&b.tick));
+    assert_eq!(
+      balances,
+      vec![balance_at(&tick2, 5), balance_at(&tick1, 20)]
+    );
+  }
+
   #[test]
   fn test_get_set_token_info() {
     let dbfile = NamedTempFile::new().unwrap();
@@ -531,6 +780,39 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_token_stats() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    let brc20db = DataStore::new(&wtx);
+
+    let tick1 = Tick::from_str("aBcd").unwrap();
+    let tick2 = Tick::from_str("1234").unwrap();
+
+    let mut stats1 = TokenStats::new(&tick1, 10);
+    stats1.mint_count = 3;
+    stats1.transferable_supply = 7;
+    let stats2 = TokenStats::new(&tick2, 11);
+
+    brc20db.update_token_stats(&tick1, &stats1).unwrap();
+    brc20db.update_token_stats(&tick2, &stats2).unwrap();
+
+    assert_eq!(
+      brc20db
+        .get_token_stats(&Tick::from_str("ABCD").unwrap())
+        .unwrap(),
+      Some(stats1.clone())
+    );
+    assert_eq!(brc20db.get_tokens_stats().unwrap(), vec![stats2, stats1]);
+    assert_eq!(
+      brc20db
+        .get_token_stats(&Tick::from_str("none").unwrap())
+        .unwrap(),
+      None
+    );
+  }
+
   #[test]
   fn test_save_get_transaction_receipts() {
     let dbfile = NamedTempFile::new().unwrap();
@@ -637,6 +919,98 @@ mod tests {
     assert_eq!(brc20db.get_transaction_receipts(&txid).unwrap(), receipts);
   }
 
+  #[test]
+  fn test_address_receipts() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    let brc20db = DataStore::new(&wtx);
+
+    let txid =
+      Txid::from_str("b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735").unwrap();
+    let sender = ScriptKey::from_address(
+      Address::from_str("bc1qhvd6suvqzjcu9pxjhrwhtrlj85ny3n2mqql5w4")
+        .unwrap()
+        .assume_checked(),
+    );
+    let receiver = ScriptKey::from_address(
+      Address::from_str("bc1pjdmfs5lvqfl6qmzpc0e4ewfdgfmdyz2t79scrsaz8ep98374wwnsywz7t4")
+        .unwrap()
+        .assume_checked(),
+    );
+
+    let mint = Receipt {
+      inscription_id: InscriptionId::from_str(
+        "1111111111111111111111111111111111111111111111111111111111111111i1",
+      )
+      .unwrap(),
+      inscription_number: 1,
+      op: OperationType::Mint,
+      from: sender.clone(),
+      to: sender.clone(),
+      old_satpoint: SatPoint::from_str(
+        "1111111111111111111111111111111111111111111111111111111111111111:1:1",
+      )
+      .unwrap(),
+      new_satpoint: SatPoint::from_str(
+        "2111111111111111111111111111111111111111111111111111111111111111:1:1",
+      )
+      .unwrap(),
+      result: Ok(Event::Mint(MintEvent {
+        tick: Tick::from_str("abcd").unwrap(),
+        amount: 30,
+        msg: None,
+      })),
+    };
+    let transfer = Receipt {
+      op: OperationType::Transfer,
+      to: receiver.clone(),
+      result: Ok(Event::Transfer(TransferEvent {
+        tick: Tick::from_str("abcd").unwrap(),
+        amount: 10,
+        msg: None,
+      })),
+      ..mint.clone()
+    };
+
+    brc20db.add_transaction_receipt(&txid, &mint).unwrap();
+    brc20db.add_transaction_receipt(&txid, &transfer).unwrap();
+
+    assert_eq!(
+      brc20db
+        .get_address_receipts(&sender, None, usize::MAX, &|_| true)
+        .unwrap(),
+      vec![(1, txid, transfer.clone()), (0, txid, mint.clone())]
+    );
+    assert_eq!(
+      brc20db
+        .get_address_receipts(&receiver, None, usize::MAX, &|_| true)
+        .unwrap(),
+      vec![(0, txid, transfer.clone())]
+    );
+
+    // a page continues below the sequence of the last receipt of the previous one.
+    assert_eq!(
+      brc20db
+        .get_address_receipts(&sender, None, 1, &|_| true)
+        .unwrap(),
+      vec![(1, txid, transfer)]
+    );
+    assert_eq!(
+      brc20db
+        .get_address_receipts(&sender, Some(1), 1, &|_| true)
+        .unwrap(),
+      vec![(0, txid, mint.clone())]
+    );
+    assert_eq!(
+      brc20db
+        .get_address_receipts(&sender, None, usize::MAX, &|receipt| receipt.op
+          == OperationType::Mint)
+        .unwrap(),
+      vec![(0, txid, mint)]
+    );
+  }
+
   #[test]
   fn test_get_transferable_by_tick() {
     let dbfile = NamedTempFile::new().unwrap();
@@ -691,7 +1065,7 @@ mod tests {
       brc20db
         .get_transferable_by_tick(&script, &upper_tick)
         .unwrap(),
-      vec![transferable_log1, transferable_log2]
+      vec![transferable_log2, transferable_log1]
     );
 
     // check not exist key
@@ -3,9 +3,12 @@ use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::{fmt::Formatter, str::FromStr};
 
 pub const TICK_BYTE_COUNT: usize = 4;
+pub const SELF_MINT_TICK_BYTE_COUNT: usize = 5;
+/// Lowercasing a character takes at most four times its bytes.
+pub const LOWER_TICK_BYTE_COUNT: usize = TICK_BYTE_COUNT * 4;
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Tick([u8; TICK_BYTE_COUNT]);
+pub struct Tick(Box<[u8]>);
 
 impl FromStr for Tick {
   type Err = BRC20Error;
@@ -13,11 +16,14 @@ impl FromStr for Tick {
   fn from_str(s: &str) -> Result<Self, Self::Err> {
     let bytes = s.as_bytes();
 
-    if bytes.len() != TICK_BYTE_COUNT {
-      return Err(BRC20Error::InvalidTickLen(s.to_string()));
+    match bytes.len() {
+      TICK_BYTE_COUNT => {}
+      // the key of a 5 byte tick ending with NUL would be the key of the 4 byte tick before it.
+      SELF_MINT_TICK_BYTE_COUNT if bytes[SELF_MINT_TICK_BYTE_COUNT - 1] != 0 => {}
+      _ => return Err(BRC20Error::InvalidTickLen(s.to_string())),
     }
 
-    Ok(Self(bytes.try_into().unwrap()))
+    Ok(Self(bytes.into()))
   }
 }
 
@@ -25,12 +31,17 @@ impl Tick {
   pub fn as_str(&self) -> &str {
     // NOTE: Tick comes from &str by from_str,
     // so it could be calling unwrap when convert to str
-    std::str::from_utf8(self.0.as_slice()).unwrap()
+    std::str::from_utf8(&self.0).unwrap()
   }
 
   pub fn to_lowercase(&self) -> LowerTick {
     LowerTick::new(&self.as_str().to_lowercase())
   }
+
+  /// Whether only the deploy inscription can mint the tick, which is the case for 5 byte ticks.
+  pub fn self_mint(&self) -> bool {
+    self.0.len() == SELF_MINT_TICK_BYTE_COUNT
+  }
 }
 
 impl Serialize for Tick {
@@ -70,18 +81,11 @@ impl LowerTick {
     std::str::from_utf8(&self.0).unwrap()
   }
 
-  pub fn hex(&self) -> String {
-    let mut data = [0u8; TICK_BYTE_COUNT * 4];
+  /// Returns the tick zero padded to the longest lowercase tick.
+  pub fn to_bytes(&self) -> [u8; LOWER_TICK_BYTE_COUNT] {
+    let mut data = [0u8; LOWER_TICK_BYTE_COUNT];
     data[..self.0.len()].copy_from_slice(&self.0);
-    hex::encode(data)
-  }
-
-  pub fn min_hex() -> String {
-    hex::encode([0u8; TICK_BYTE_COUNT * 4])
-  }
-
-  pub fn max_hex() -> String {
-    hex::encode([0xffu8; TICK_BYTE_COUNT * 4])
+    data
   }
 }
 
@@ -106,21 +110,39 @@ mod tests {
     assert!(Tick::from_str("aBc1").is_ok());
     assert!("aBc1".parse::<Tick>().is_ok());
     assert!("ατ".parse::<Tick>().is_ok());
-    assert!("∑ii".parse::<Tick>().is_err());
+    assert!("∑iii".parse::<Tick>().is_err());
     assert!("∑i".parse::<Tick>().is_ok());
     assert!("⊢i".parse::<Tick>().is_ok());
-    assert!("⊢ii".parse::<Tick>().is_err());
+    assert!("⊢iii".parse::<Tick>().is_err());
     assert!("≯a".parse::<Tick>().is_ok());
-    assert!("a≯a".parse::<Tick>().is_err());
+    assert!("a≯aa".parse::<Tick>().is_err());
+    assert!("abc".parse::<Tick>().is_err());
+    assert!("abcdef".parse::<Tick>().is_err());
+  }
+
+  #[test]
+  fn test_self_mint_tick() {
+    assert!(!"abcd".parse::<Tick>().unwrap().self_mint());
+    assert!("abcde".parse::<Tick>().unwrap().self_mint());
+    assert!("∑ii".parse::<Tick>().unwrap().self_mint());
+    assert!("a≯a".parse::<Tick>().unwrap().self_mint());
+    assert_eq!(
+      "abcd\0".parse::<Tick>().unwrap_err(),
+      BRC20Error::InvalidTickLen("abcd\0".to_string())
+    );
+    assert_eq!(
+      hex::encode("ABCDE".parse::<Tick>().unwrap().to_lowercase().to_bytes()),
+      "61626364650000000000000000000000"
+    );
   }
   #[test]
   fn test_tick_hex() {
     assert_eq!(
-      Tick::from_str("XAİ").unwrap().to_lowercase().hex(),
+      hex::encode(Tick::from_str("XAİ").unwrap().to_lowercase().to_bytes()),
       "786169cc870000000000000000000000"
     );
     assert_eq!(
-      Tick::from_str("aBc1").unwrap().to_lowercase().hex(),
+      hex::encode(Tick::from_str("aBc1").unwrap().to_lowercase().to_bytes()),
       "61626331000000000000000000000000"
     );
   }
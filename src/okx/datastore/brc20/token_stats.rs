@@ -0,0 +1,28 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+
+/// Activity counters of a ticker, updated as its operations are executed.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TokenStats {
+  pub tick: Tick,
+  pub mint_count: u64,
+  pub inscribe_transfer_count: u64,
+  pub transfer_count: u64,
+  /// The amount locked in inscribed transfers that haven't been sent yet.
+  pub transferable_supply: u128,
+  /// The height of the block of the latest operation.
+  pub latest_activity_number: u64,
+}
+
+impl TokenStats {
+  pub fn new(tick: &Tick, height: u64) -> Self {
+    Self {
+      tick: tick.clone(),
+      mint_count: 0,
+      inscribe_transfer_count: 0,
+      transfer_count: 0,
+      transferable_supply: 0,
+      latest_activity_number: height,
+    }
+  }
+}
@@ -0,0 +1,464 @@
+use {
+  super::{
+    redb::{pid_key, script_pid_key, script_pledged_key, script_tickid_key, tick_id_key},
+    Balance, DataStoreReadOnly, DataStoreReadWrite, InscriptionOperation, Pid, PledgedTick,
+    PoolInfo, Receipt, StakeInfo, TickId, TickInfo, TransferInfo, TransferableAsset, UserInfo,
+  },
+  crate::{
+    index::state_hash::Change,
+    okx::datastore::{cache::WriteBackCache, ScriptKey},
+    InscriptionId,
+  },
+  bitcoin::Txid,
+};
+
+/// Cache holds the decoded tick infos, pool infos, user infos, stake infos and balances of BRC20S.
+pub(crate) struct Cache {
+  ticks: WriteBackCache<TickId, TickInfo>,
+  pools: WriteBackCache<Pid, PoolInfo>,
+  users: WriteBackCache<(ScriptKey, Pid), UserInfo>,
+  stakes: WriteBackCache<(ScriptKey, PledgedTick), StakeInfo>,
+  balances: WriteBackCache<(ScriptKey, TickId), Balance>,
+  limit: usize,
+}
+
+impl Cache {
+  pub(crate) fn new(limit: usize) -> Self {
+    Self {
+      ticks: WriteBackCache::default(),
+      pools: WriteBackCache::default(),
+      users: WriteBackCache::default(),
+      stakes: WriteBackCache::default(),
+      balances: WriteBackCache::default(),
+      limit,
+    }
+  }
+
+  fn size(&self) -> usize {
+    self.ticks.size()
+      + self.pools.size()
+      + self.users.size()
+      + self.stakes.size()
+      + self.balances.size()
+  }
+
+  fn clear(&self) {
+    self.ticks.clear();
+    self.pools.clear();
+    self.users.clear();
+    self.stakes.clear();
+    self.balances.clear();
+  }
+
+  /// Takes the changes to the consensus state written since they were last taken.
+  pub(crate) fn take_changes(&self) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (_, tick_info) in self.ticks.take_changed() {
+      changes.push(Change::brc20s_tick(&tick_info));
+    }
+    for (_, pool_info) in self.pools.take_changed() {
+      changes.push(Change::brc20s_pool(&pool_info));
+    }
+    for ((script_key, _), user_info) in self.users.take_changed() {
+      changes.push(Change::brc20s_user(&script_key, &user_info));
+    }
+    for ((script_key, _), stake_info) in self.stakes.take_changed() {
+      changes.push(Change::brc20s_stake(&script_key, &stake_info));
+    }
+    for ((script_key, _), balance) in self.balances.take_changed() {
+      changes.push(Change::brc20s_balance(&script_key, &balance));
+    }
+    changes
+  }
+}
+
+/// CachedDataStore serves the BRC20S state of `store` through a write-back `Cache`.
+///
+/// Reads spanning several entries of the cached tables flush the dirty entries first, so that
+/// they see every write.
+pub struct CachedDataStore<'a, W> {
+  store: W,
+  cache: &'a Cache,
+}
+
+impl<'a, W: DataStoreReadWrite> CachedDataStore<'a, W> {
+  pub(crate) fn new(store: W, cache: &'a Cache) -> Self {
+    Self { store, cache }
+  }
+
+  /// Writes the dirty entries back to the store.
+  pub(crate) fn flush(&self) -> Result<(), W::Error> {
+    for (tick_id, tick_info) in self.cache.ticks.take_dirty() {
+      self.store.set_tick_info(&tick_id, &tick_info)?;
+    }
+    for (pid, pool_info) in self.cache.pools.take_dirty() {
+      self.store.set_pid_to_poolinfo(&pid, &pool_info)?;
+    }
+    for ((script_key, pid), user_info) in self.cache.users.take_dirty() {
+      self
+        .store
+        .set_pid_to_use_info(&script_key, &pid, &user_info)?;
+    }
+    for ((script_key, pledged_tick), stake_info) in self.cache.stakes.take_dirty() {
+      self
+        .store
+        .set_user_stakeinfo(&script_key, &pledged_tick, &stake_info)?;
+    }
+    for ((script_key, tick_id), balance) in self.cache.balances.take_dirty() {
+      self
+        .store
+        .set_token_balance(&script_key, &tick_id, balance)?;
+    }
+    Ok(())
+  }
+
+  // keeps the cache within its limit, by writing everything back and starting over.
+  fn evict(&self) -> Result<(), W::Error> {
+    if self.cache.size() > self.cache.limit {
+      self.flush()?;
+      self.cache.clear();
+    }
+    Ok(())
+  }
+}
+
+impl<'a, W: DataStoreReadWrite> DataStoreReadOnly for CachedDataStore<'a, W> {
+  type Error = W::Error;
+
+  fn get_txid_to_inscription_receipts(
+    &self,
+    txid: &Txid,
+  ) -> Result<Vec<InscriptionOperation>, Self::Error> {
+    self.store.get_txid_to_inscription_receipts(txid)
+  }
+
+  fn get_tick_info(&self, tick_id: &TickId) -> Result<Option<TickInfo>, Self::Error> {
+    let tick_info = self
+      .cache
+      .ticks
+      .get_or_load(tick_id_key(tick_id).to_vec(), || {
+        self.store.get_tick_info(tick_id)
+      })?;
+    self.evict()?;
+    Ok(tick_info)
+  }
+
+  fn get_all_tick_info(
+    &self,
+    start: usize,
+    limit: Option<usize>,
+  ) -> Result<(Vec<TickInfo>, usize), Self::Error> {
+    self.flush()?;
+    self.store.get_all_tick_info(start, limit)
+  }
+
+  fn get_pid_to_poolinfo(&self, pid: &Pid) -> Result<Option<PoolInfo>, Self::Error> {
+    let pool_info = self.cache.pools.get_or_load(pid_key(pid).to_vec(), || {
+      self.store.get_pid_to_poolinfo(pid)
+    })?;
+    self.evict()?;
+    Ok(pool_info)
+  }
+
+  fn get_all_pools_by_tid(&self, tick_id: &TickId) -> Result<Vec<PoolInfo>, Self::Error> {
+    self.flush()?;
+    self.store.get_all_pools_by_tid(tick_id)
+  }
+
+  fn get_all_poolinfo(
+    &self,
+    start: usize,
+    limit: Option<usize>,
+  ) -> Result<(Vec<PoolInfo>, usize), Self::Error> {
+    self.flush()?;
+    self.store.get_all_poolinfo(start, limit)
+  }
+
+  fn get_user_stakeinfo(
+    &self,
+    script_key: &ScriptKey,
+    pledged_tick: &PledgedTick,
+  ) -> Result<Option<StakeInfo>, Self::Error> {
+    let stake_info = self
+      .cache
+      .stakes
+      .get_or_load(script_pledged_key(script_key, pledged_tick), || {
+        self.store.get_user_stakeinfo(script_key, pledged_tick)
+      })?;
+    self.evict()?;
+    Ok(stake_info)
+  }
+
+  fn get_pid_to_use_info(
+    &self,
+    script_key: &ScriptKey,
+    pid: &Pid,
+  ) -> Result<Option<UserInfo>, Self::Error> {
+    let user_info = self
+      .cache
+      .users
+      .get_or_load(script_pid_key(script_key, pid), || {
+        self.store.get_pid_to_use_info(script_key, pid)
+      })?;
+    self.evict()?;
+    Ok(user_info)
+  }
+
+  fn get_tickid_stake_to_pid(
+    &self,
+    tick_id: &TickId,
+    pledged: &PledgedTick,
+  ) -> Result<Option<Pid>, Self::Error> {
+    self.store.get_tickid_stake_to_pid(tick_id, pledged)
+  }
+
+  fn get_tickid_to_all_pid(&self, tick_id: &TickId) -> Result<Vec<Pid>, Self::Error> {
+    self.store.get_tickid_to_all_pid(tick_id)
+  }
+
+  fn get_stake_to_all_pid(&self, pledged: &PledgedTick) -> Result<Vec<Pid>, Self::Error> {
+    self.store.get_stake_to_all_pid(pledged)
+  }
+
+  fn get_balance(
+    &self,
+    script_key: &ScriptKey,
+    tick_id: &TickId,
+  ) -> Result<Option<Balance>, Self::Error> {
+    let balance = self
+      .cache
+      .balances
+      .get_or_load(script_tickid_key(script_key, tick_id), || {
+        self.store.get_balance(script_key, tick_id)
+      })?;
+    self.evict()?;
+    Ok(balance)
+  }
+
+  fn get_balances(&self, script_key: &ScriptKey) -> Result<Vec<(TickId, Balance)>, Self::Error> {
+    self.flush()?;
+    self.store.get_balances(script_key)
+  }
+
+  fn get_balance_at_height(
+    &self,
+    script_key: &ScriptKey,
+    tick_id: &TickId,
+    height: u64,
+  ) -> Result<Option<Balance>, Self::Error> {
+    self
+      .store
+      .get_balance_at_height(script_key, tick_id, height)
+  }
+
+  fn get_balances_at_height(
+    &self,
+    script_key: &ScriptKey,
+    height: u64,
+  ) -> Result<Vec<(TickId, Balance)>, Self::Error> {
+    self.store.get_balances_at_height(script_key, height)
+  }
+
+  fn get_transferable_asset(
+    &self,
+    script_key: &ScriptKey,
+    tick_id: &TickId,
+    inscription_id: &InscriptionId,
+  ) -> Result<Option<TransferableAsset>, Self::Error> {
+    self
+      .store
+      .get_transferable_asset(script_key, tick_id, inscription_id)
+  }
+
+  fn get_transferable(&self, script: &ScriptKey) -> Result<Vec<TransferableAsset>, Self::Error> {
+    self.store.get_transferable(script)
+  }
+
+  fn get_transferable_by_tickid(
+    &self,
+    script: &ScriptKey,
+    tick_id: &TickId,
+  ) -> Result<Vec<TransferableAsset>, Self::Error> {
+    self.store.get_transferable_by_tickid(script, tick_id)
+  }
+
+  fn get_transferable_by_id(
+    &self,
+    script: &ScriptKey,
+    inscription_id: &InscriptionId,
+  ) -> Result<Option<TransferableAsset>, Self::Error> {
+    self.store.get_transferable_by_id(script, inscription_id)
+  }
+
+  fn get_transaction_receipts(&self, txid: &Txid) -> Result<Vec<Receipt>, Self::Error> {
+    self.store.get_transaction_receipts(txid)
+  }
+
+  fn get_txid_to_receipts(&self, txid: &Txid) -> Result<Vec<Receipt>, Self::Error> {
+    self.store.get_txid_to_receipts(txid)
+  }
+
+  fn get_address_receipts(
+    &self,
+    script_key: &ScriptKey,
+    before: Option<u64>,
+    limit: usize,
+    filter: &dyn Fn(&Receipt) -> bool,
+  ) -> Result<Vec<(u64, Txid, Receipt)>, Self::Error> {
+    self
+      .store
+      .get_address_receipts(script_key, before, limit, filter)
+  }
+
+  fn get_inscribe_transfer_inscription(
+    &self,
+    inscription_id: InscriptionId,
+  ) -> Result<Option<TransferInfo>, Self::Error> {
+    self.store.get_inscribe_transfer_inscription(inscription_id)
+  }
+}
+
+impl<'a, W: DataStoreReadWrite> DataStoreReadWrite for CachedDataStore<'a, W> {
+  fn set_txid_to_inscription_receipts(
+    &self,
+    tx_id: &Txid,
+    inscription_operations: &[InscriptionOperation],
+  ) -> Result<(), Self::Error> {
+    self
+      .store
+      .set_txid_to_inscription_receipts(tx_id, inscription_operations)
+  }
+
+  fn set_tick_info(&self, tick_id: &TickId, tick_info: &TickInfo) -> Result<(), Self::Error> {
+    self
+      .cache
+      .ticks
+      .put(tick_id_key(tick_id).to_vec(), *tick_id, tick_info.clone());
+    self.evict()
+  }
+
+  fn set_pid_to_poolinfo(&self, pid: &Pid, pool_info: &PoolInfo) -> Result<(), Self::Error> {
+    self
+      .cache
+      .pools
+      .put(pid_key(pid).to_vec(), pid.clone(), pool_info.clone());
+    self.evict()
+  }
+
+  fn set_user_stakeinfo(
+    &self,
+    script_key: &ScriptKey,
+    pledged_tick: &PledgedTick,
+    stake_info: &StakeInfo,
+  ) -> Result<(), Self::Error> {
+    self.cache.stakes.put(
+      script_pledged_key(script_key, pledged_tick),
+      (script_key.clone(), pledged_tick.clone()),
+      stake_info.clone(),
+    );
+    self.evict()
+  }
+
+  fn set_pid_to_use_info(
+    &self,
+    script_key: &ScriptKey,
+    pid: &Pid,
+    user_info: &UserInfo,
+  ) -> Result<(), Self::Error> {
+    self.cache.users.put(
+      script_pid_key(script_key, pid),
+      (script_key.clone(), pid.clone()),
+      user_info.clone(),
+    );
+    self.evict()
+  }
+
+  fn set_tickid_stake_to_pid(
+    &self,
+    tick_id: &TickId,
+    pledged: &PledgedTick,
+    pid: &Pid,
+  ) -> Result<(), Self::Error> {
+    self.store.set_tickid_stake_to_pid(tick_id, pledged, pid)
+  }
+
+  fn set_token_balance(
+    &self,
+    script_key: &ScriptKey,
+    tick_id: &TickId,
+    balance: Balance,
+  ) -> Result<(), Self::Error> {
+    self.cache.balances.put(
+      script_tickid_key(script_key, tick_id),
+      (script_key.clone(), *tick_id),
+      balance,
+    );
+    self.evict()
+  }
+
+  fn set_balance_history(
+    &self,
+    script_key: &ScriptKey,
+    tick_id: &TickId,
+    height: u64,
+    balance: &Balance,
+  ) -> Result<(), Self::Error> {
+    self
+      .store
+      .set_balance_history(script_key, tick_id, height, balance)
+  }
+
+  fn set_transferable_assets(
+    &self,
+    script_key: &ScriptKey,
+    tick_id: &TickId,
+    inscription_id: &InscriptionId,
+    transferable_asset: &TransferableAsset,
+  ) -> Result<(), Self::Error> {
+    self
+      .store
+      .set_transferable_assets(script_key, tick_id, inscription_id, transferable_asset)
+  }
+
+  fn add_transaction_receipt(&self, txid: &Txid, receipt: &Receipt) -> Result<(), Self::Error> {
+    self.store.add_transaction_receipt(txid, receipt)
+  }
+
+  fn save_transaction_receipts(
+    &self,
+    tx_id: &Txid,
+    receipt: &[Receipt],
+  ) -> Result<(), Self::Error> {
+    self.store.save_transaction_receipts(tx_id, receipt)
+  }
+
+  fn remove_transferable(
+    &self,
+    script: &ScriptKey,
+    tick_id: &TickId,
+    inscription_id: &InscriptionId,
+  ) -> Result<(), Self::Error> {
+    self
+      .store
+      .remove_transferable(script, tick_id, inscription_id)
+  }
+
+  fn insert_inscribe_transfer_inscription(
+    &self,
+    inscription_id: InscriptionId,
+    transfer_info: TransferInfo,
+  ) -> Result<(), Self::Error> {
+    self
+      .store
+      .insert_inscribe_transfer_inscription(inscription_id, transfer_info)
+  }
+
+  fn remove_inscribe_transfer_inscription(
+    &self,
+    inscription_id: InscriptionId,
+  ) -> Result<(), Self::Error> {
+    self
+      .store
+      .remove_inscribe_transfer_inscription(inscription_id)
+  }
+}
@@ -39,6 +39,30 @@ pub enum Event {
   Transfer(TransferEvent),
 }
 
+impl Event {
+  /// The pool of the event, if it is a pool event.
+  pub fn pid(&self) -> Option<&Pid> {
+    match self {
+      Self::DeployPool(event) => Some(&event.pid),
+      Self::Deposit(event) => Some(&event.pid),
+      Self::Withdraw(event) => Some(&event.pid),
+      Self::PassiveWithdraw(event) => Some(&event.pid),
+      Self::Mint(event) => Some(&event.pid),
+      Self::DeployTick(_) | Self::InscribeTransfer(_) | Self::Transfer(_) => None,
+    }
+  }
+
+  /// The tick id of the event, pool events belong to the tick id their pid starts with.
+  pub fn tick_id(&self) -> TickId {
+    match self {
+      Self::DeployTick(event) => event.tick_id,
+      Self::InscribeTransfer(event) => event.tick_id,
+      Self::Transfer(event) => event.tick_id,
+      _ => self.pid().unwrap().clone().into(),
+    }
+  }
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct DeployTickEvent {
   pub tick_id: TickId,
@@ -1,4 +1,5 @@
 mod balance;
+pub(crate) mod cache;
 mod event;
 pub mod pool_info;
 pub mod redb;
@@ -11,7 +12,7 @@ mod user_info;
 pub use self::{
   balance::*, event::*, pool_info::*, stake_info::*, temp::*, tick::*, transfer::*, user_info::*,
 };
-use crate::okx::datastore::ScriptKey;
+use crate::okx::datastore::{ScriptKey, StateRWriter};
 use crate::InscriptionId;
 use bitcoin::Txid;
 use std::fmt::{Debug, Display};
@@ -81,6 +82,20 @@ pub trait DataStoreReadOnly {
 
   fn get_balances(&self, script_key: &ScriptKey) -> Result<Vec<(TickId, Balance)>, Self::Error>;
 
+  // BRC20S_BALANCE_HISTORY
+  fn get_balance_at_height(
+    &self,
+    script_key: &ScriptKey,
+    tick_id: &TickId,
+    height: u64,
+  ) -> Result<Option<Balance>, Self::Error>;
+
+  fn get_balances_at_height(
+    &self,
+    script_key: &ScriptKey,
+    height: u64,
+  ) -> Result<Vec<(TickId, Balance)>, Self::Error>;
+
   // BRC20S_TRANSFERABLE_ASSETS
   fn get_transferable_asset(
     &self,
@@ -107,6 +122,18 @@ pub trait DataStoreReadOnly {
   fn get_transaction_receipts(&self, txid: &Txid) -> Result<Vec<Receipt>, Self::Error>;
   fn get_txid_to_receipts(&self, txid: &Txid) -> Result<Vec<Receipt>, Self::Error>;
 
+  // BRC20S_ADDRESS_RECEIPTS
+  /// Returns up to `limit` receipts of the address that match `filter`, newest first, each with
+  /// its sequence among the receipts of the address. Only receipts older than the sequence `before`
+  /// are returned, if it is set.
+  fn get_address_receipts(
+    &self,
+    script_key: &ScriptKey,
+    before: Option<u64>,
+    limit: usize,
+    filter: &dyn Fn(&Receipt) -> bool,
+  ) -> Result<Vec<(u64, Txid, Receipt)>, Self::Error>;
+
   fn get_inscribe_transfer_inscription(
     &self,
     inscription_id: InscriptionId,
@@ -159,6 +186,15 @@ pub trait DataStoreReadWrite: DataStoreReadOnly {
     balance: Balance,
   ) -> Result<(), Self::Error>;
 
+  // BRC20S_BALANCE_HISTORY
+  fn set_balance_history(
+    &self,
+    script_key: &ScriptKey,
+    tick_id: &TickId,
+    height: u64,
+    balance: &Balance,
+  ) -> Result<(), Self::Error>;
+
   // BRC20S_TRANSFERABLE_ASSETS
   fn set_transferable_assets(
     &self,
@@ -191,3 +227,11 @@ pub trait DataStoreReadWrite: DataStoreReadOnly {
     inscription_id: InscriptionId,
   ) -> Result<(), Self::Error>;
 }
+
+/// The BRC20S store of a `StateRWriter`, served through its write-back cache.
+pub(crate) type Store<'a, T> = cache::CachedDataStore<'a, redb::DataStore<'a, T>>;
+
+/// Opens the BRC20S store of `state`.
+pub(crate) fn store<S: StateRWriter>(state: &S) -> Store<'_, S::Tables> {
+  cache::CachedDataStore::new(redb::DataStore::new(state.tables()), &state.cache().brc20s)
+}
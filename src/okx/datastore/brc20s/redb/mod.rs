@@ -1,126 +1,209 @@
 mod read_only;
 mod read_write;
 
-pub use self::{read_only::try_init_tables, read_only::DataStoreReader, read_write::DataStore};
-
+use crate::okx::datastore::brc20::LOWER_TICK_BYTE_COUNT;
 use crate::okx::datastore::brc20s::{Pid, PledgedTick, TickId};
+use crate::okx::datastore::keys::{
+  inscription_id_key, max_key, min_key, script_key_hash, txid_key, INSCRIPTION_ID_KEY_LEN,
+  SCRIPT_KEY_HASH_LEN,
+};
+use crate::okx::datastore::table::define_state_tables;
 use crate::okx::datastore::ScriptKey;
+use crate::okx::protocol::brc20s::params::{PID_BYTE_COUNT, TICK_ID_BYTE_COUNT, TICK_ID_STR_COUNT};
 use crate::InscriptionId;
 use bitcoin::Txid;
-use redb::TableDefinition;
-
-const TXID_TO_INSCRIPTION_RECEIPTS: TableDefinition<&str, &[u8]> =
-  TableDefinition::new("TXID_TO_INSCRIPTION_RECEIPTS");
-const BRC20S_TICKINFO: TableDefinition<&str, &[u8]> = TableDefinition::new("BRC20S_TICKINFO");
-const BRC20S_PID_TO_POOLINFO: TableDefinition<&str, &[u8]> =
-  TableDefinition::new("BRC20S_PID_TO_POOLINFO");
-const BRC20S_USER_STAKEINFO: TableDefinition<&str, &[u8]> =
-  TableDefinition::new("BRC20S_USER_STAKEINFO");
-const BRC20S_PID_TO_USERINFO: TableDefinition<&str, &[u8]> =
-  TableDefinition::new("BRC20S_PID_TO_USERINFO");
-const BRC20S_STAKE_TICKID_TO_PID: TableDefinition<&str, &[u8]> =
-  TableDefinition::new("BRC20S_STAKE_TICKID_TO_PID");
-const BRC20S_TICKID_STAKE_TO_PID: TableDefinition<&str, &[u8]> =
-  TableDefinition::new("BRC20S_TICKID_STAKE_TO_PID");
-const BRC20S_BALANCES: TableDefinition<&str, &[u8]> = TableDefinition::new("BRC20S_BALANCE");
-const BRC20S_TRANSFERABLE_ASSETS: TableDefinition<&str, &[u8]> =
-  TableDefinition::new("BRC20S_TRANSFERABLE_ASSETS");
-const BRC20S_TXID_TO_RECEIPTS: TableDefinition<&str, &[u8]> =
-  TableDefinition::new("BRC20S_TXID_TO_RECEIPTS");
-const BRC20S_INSCRIBE_TRANSFER: TableDefinition<&[u8; 36], &[u8]> =
-  TableDefinition::new("BRC20S_INSCRIBE_TRANSFER");
-
-fn script_tickid_key(script: &ScriptKey, tick_id: &TickId) -> String {
-  format!("{}_{}", script, tick_id.hex())
+
+define_state_tables! {
+  TABLES {
+    TXID_TO_INSCRIPTION_RECEIPTS: &[u8] => &[u8] = "TXID_TO_INSCRIPTION_RECEIPTS",
+    BRC20S_TICKINFO: &[u8] => &[u8] = "BRC20S_TICKINFO",
+    BRC20S_PID_TO_POOLINFO: &[u8] => &[u8] = "BRC20S_PID_TO_POOLINFO",
+    BRC20S_USER_STAKEINFO: &[u8] => &[u8] = "BRC20S_USER_STAKEINFO",
+    BRC20S_PID_TO_USERINFO: &[u8] => &[u8] = "BRC20S_PID_TO_USERINFO",
+    BRC20S_STAKE_TICKID_TO_PID: &[u8] => &[u8] = "BRC20S_STAKE_TICKID_TO_PID",
+    BRC20S_TICKID_STAKE_TO_PID: &[u8] => &[u8] = "BRC20S_TICKID_STAKE_TO_PID",
+    BRC20S_BALANCES: &[u8] => &[u8] = "BRC20S_BALANCE",
+    BRC20S_BALANCE_HISTORY: &[u8] => &[u8] = "BRC20S_BALANCE_HISTORY",
+    BRC20S_TRANSFERABLE_ASSETS: &[u8] => &[u8] = "BRC20S_TRANSFERABLE_ASSETS",
+    BRC20S_TXID_TO_RECEIPTS: &[u8] => &[u8] = "BRC20S_TXID_TO_RECEIPTS",
+    BRC20S_INSCRIBE_TRANSFER: &[u8; 36] => &[u8] = "BRC20S_INSCRIBE_TRANSFER",
+    BRC20S_ADDRESS_RECEIPTS: &[u8] => &[u8] = "BRC20S_ADDRESS_RECEIPTS",
+  }
+}
+
+/// DataStore keeps the BRC20S state in the tables of `T`.
+pub struct DataStore<'a, T> {
+  tables: &'a T,
+}
+
+impl<'a, T> DataStore<'a, T> {
+  pub fn new(tables: &'a T) -> Self {
+    Self { tables }
+  }
+}
+
+// The tables are keyed by fixed width binary composites, so that the entries of an address, a tick
+// id or a pledged tick are a range of keys: the hash of a script key, the tick id, the pid, the
+// pledged tick, an inscription id and a big endian height or sequence.
+
+const SCRIPT_TICKID_KEY_LEN: usize = SCRIPT_KEY_HASH_LEN + TICK_ID_BYTE_COUNT;
+const PLEDGED_KEY_LEN: usize = 1 + LOWER_TICK_BYTE_COUNT;
+
+pub(super) fn tick_id_key(tick_id: &TickId) -> [u8; TICK_ID_BYTE_COUNT] {
+  *tick_id.as_bytes()
+}
+
+pub(super) fn pid_key(pid: &Pid) -> [u8; PID_BYTE_COUNT] {
+  *pid.as_bytes()
+}
+
+// a tag byte followed by the zero padded lowercase BRC20 tick or BRC20S tick id.
+fn pledged_key(pledged_tick: &PledgedTick) -> [u8; PLEDGED_KEY_LEN] {
+  let mut key = [0; PLEDGED_KEY_LEN];
+  match pledged_tick {
+    PledgedTick::Unknown => {}
+    PledgedTick::Native => key[0] = 1,
+    PledgedTick::BRC20Tick(tick) => {
+      key[0] = 2;
+      key[1..].copy_from_slice(&tick.to_lowercase().to_bytes());
+    }
+    PledgedTick::BRC20STick(tick_id) => {
+      key[0] = 3;
+      key[1..=TICK_ID_BYTE_COUNT].copy_from_slice(tick_id.as_bytes());
+    }
+  }
+  key
+}
+
+pub(super) fn script_tickid_key(script: &ScriptKey, tick_id: &TickId) -> Vec<u8> {
+  [script_key_hash(script).as_slice(), &tick_id_key(tick_id)].concat()
+}
+
+fn min_script_tick_id_key(script: &ScriptKey) -> Vec<u8> {
+  min_key(&script_key_hash(script), TICK_ID_BYTE_COUNT)
+}
+
+fn max_script_tick_id_key(script: &ScriptKey) -> Vec<u8> {
+  max_key(&script_key_hash(script), TICK_ID_BYTE_COUNT)
+}
+
+fn script_tickid_height_key(script: &ScriptKey, tick_id: &TickId, height: u64) -> Vec<u8> {
+  [
+    script_tickid_key(script, tick_id).as_slice(),
+    &height.to_be_bytes(),
+  ]
+  .concat()
+}
+
+fn min_script_tickid_height_key(script: &ScriptKey) -> Vec<u8> {
+  min_key(&script_key_hash(script), TICK_ID_BYTE_COUNT + 8)
+}
+
+fn max_script_tickid_height_key(script: &ScriptKey) -> Vec<u8> {
+  max_key(&script_key_hash(script), TICK_ID_BYTE_COUNT + 8)
+}
+
+// split a balance history key into its script tick id key and height.
+fn split_script_tickid_height_key(key: &[u8]) -> (&[u8], u64) {
+  let (script_tickid, height) = key.split_at(SCRIPT_TICKID_KEY_LEN);
+  (
+    script_tickid,
+    u64::from_be_bytes(height.try_into().unwrap()),
+  )
+}
+
+fn script_sequence_key(script: &ScriptKey, sequence: u64) -> Vec<u8> {
+  [script_key_hash(script).as_slice(), &sequence.to_be_bytes()].concat()
+}
+
+fn min_script_sequence_key(script: &ScriptKey) -> Vec<u8> {
+  min_key(&script_key_hash(script), 8)
+}
+
+fn max_script_sequence_key(script: &ScriptKey) -> Vec<u8> {
+  max_key(&script_key_hash(script), 8)
+}
+
+// split the sequence out of an address receipt key.
+fn split_script_sequence_key(key: &[u8]) -> u64 {
+  u64::from_be_bytes(key[SCRIPT_KEY_HASH_LEN..].try_into().unwrap())
 }
 
 fn script_tickid_inscriptionid_key(
   script: &ScriptKey,
   tick_id: &TickId,
   inscriptionid: &InscriptionId,
-) -> String {
-  format!("{}_{}_{}", script, tick_id.hex(), inscriptionid)
+) -> Vec<u8> {
+  [
+    script_tickid_key(script, tick_id).as_slice(),
+    &inscription_id_key(inscriptionid),
+  ]
+  .concat()
 }
 
-fn script_pid_key(script: &ScriptKey, pid: &Pid) -> String {
-  format!("{}_{}", script, pid.hex(),)
+fn min_script_inscriptionid_key(script: &ScriptKey) -> Vec<u8> {
+  min_key(
+    &script_key_hash(script),
+    TICK_ID_BYTE_COUNT + INSCRIPTION_ID_KEY_LEN,
+  )
 }
 
-fn script_pledged_key(script: &ScriptKey, pledged_tick: &PledgedTick) -> String {
-  let pledged_key = match pledged_tick {
-    PledgedTick::Native => hex::encode("btc"),
-    PledgedTick::BRC20Tick(tick) => tick.to_lowercase().hex(),
-    PledgedTick::BRC20STick(tick_id) => tick_id.hex(),
-    PledgedTick::Unknown => hex::encode("!@#$%"),
-  };
-
-  format!("{}_{}", script, pledged_key)
+fn max_script_inscriptionid_key(script: &ScriptKey) -> Vec<u8> {
+  max_key(
+    &script_key_hash(script),
+    TICK_ID_BYTE_COUNT + INSCRIPTION_ID_KEY_LEN,
+  )
 }
 
-fn stake_tickid_key(pledged_tick: &PledgedTick, tick_id: &TickId) -> String {
-  let pledged_key = match pledged_tick {
-    PledgedTick::Native => hex::encode("btc"),
-    PledgedTick::BRC20Tick(tick) => tick.to_lowercase().hex(),
-    PledgedTick::BRC20STick(tick_id) => tick_id.hex(),
-    PledgedTick::Unknown => hex::encode("!@#$%"),
-  };
-
-  format!("{}_{}", pledged_key, tick_id.hex())
+fn min_script_tickid_inscriptionid_key(script: &ScriptKey, tick_id: &TickId) -> Vec<u8> {
+  min_key(&script_tickid_key(script, tick_id), INSCRIPTION_ID_KEY_LEN)
 }
 
-fn tickid_stake_key(pledged_tick: &PledgedTick, tick_id: &TickId) -> String {
-  let pledged_key = match pledged_tick {
-    PledgedTick::Native => hex::encode("btc"),
-    PledgedTick::BRC20Tick(tick) => tick.to_lowercase().hex(),
-    PledgedTick::BRC20STick(tick_id) => tick_id.hex(),
-    PledgedTick::Unknown => hex::encode("!@#$%"),
-  };
-
-  format!("{}_{}", tick_id.hex(), pledged_key)
+fn max_script_tickid_inscriptionid_key(script: &ScriptKey, tick_id: &TickId) -> Vec<u8> {
+  max_key(&script_tickid_key(script, tick_id), INSCRIPTION_ID_KEY_LEN)
 }
 
-fn min_script_tick_id_key(script: &ScriptKey) -> String {
-  format!("{}_{}", script, TickId::min_hex())
+pub(super) fn script_pid_key(script: &ScriptKey, pid: &Pid) -> Vec<u8> {
+  [script_key_hash(script).as_slice(), &pid_key(pid)].concat()
 }
 
-fn max_script_tick_id_key(script: &ScriptKey) -> String {
-  format!("{}_{}", script, TickId::max_hex())
+pub(super) fn script_pledged_key(script: &ScriptKey, pledged_tick: &PledgedTick) -> Vec<u8> {
+  [
+    script_key_hash(script).as_slice(),
+    &pledged_key(pledged_tick),
+  ]
+  .concat()
 }
 
-fn min_tickid_stake_key(tick_id: &TickId) -> String {
-  format!("{}_{}", tick_id.hex(), PledgedTick::min_hex())
+fn stake_tickid_key(pledged_tick: &PledgedTick, tick_id: &TickId) -> Vec<u8> {
+  [pledged_key(pledged_tick).as_slice(), &tick_id_key(tick_id)].concat()
 }
 
-fn max_tickid_stake_key(tick_id: &TickId) -> String {
-  format!("{}_{}", tick_id.hex(), PledgedTick::max_hex())
+fn tickid_stake_key(pledged_tick: &PledgedTick, tick_id: &TickId) -> Vec<u8> {
+  [tick_id_key(tick_id).as_slice(), &pledged_key(pledged_tick)].concat()
 }
 
-fn min_stake_tickid_key(pledged: &PledgedTick) -> String {
-  let pledged_key = match pledged {
-    PledgedTick::Native => hex::encode("btc"),
-    PledgedTick::BRC20Tick(tick) => tick.to_lowercase().hex(),
-    PledgedTick::BRC20STick(tick_id) => tick_id.hex(),
-    PledgedTick::Unknown => hex::encode("!@#$%"),
-  };
+fn min_tickid_stake_key(tick_id: &TickId) -> Vec<u8> {
+  min_key(&tick_id_key(tick_id), PLEDGED_KEY_LEN)
+}
 
-  format!("{}_{}", pledged_key, TickId::min_hex())
+fn max_tickid_stake_key(tick_id: &TickId) -> Vec<u8> {
+  max_key(&tick_id_key(tick_id), PLEDGED_KEY_LEN)
 }
 
-fn max_stake_tickid_key(pledged: &PledgedTick) -> String {
-  let pledged_key = match pledged {
-    PledgedTick::Native => hex::encode("btc"),
-    PledgedTick::BRC20Tick(tick) => tick.to_lowercase().hex(),
-    PledgedTick::BRC20STick(tick_id) => tick_id.hex(),
-    PledgedTick::Unknown => hex::encode("!@#$%"),
-  };
+fn min_stake_tickid_key(pledged: &PledgedTick) -> Vec<u8> {
+  min_key(&pledged_key(pledged), TICK_ID_BYTE_COUNT)
+}
 
-  format!("{}_{}", pledged_key, TickId::max_hex())
+fn max_stake_tickid_key(pledged: &PledgedTick) -> Vec<u8> {
+  max_key(&pledged_key(pledged), TICK_ID_BYTE_COUNT)
 }
 
-fn min_tid_to_pid_key(tick_id: &TickId) -> String {
-  format!("{}000000", hex::encode(tick_id.hex()).as_str())
+// pids start with the hex of their tick id.
+fn min_tid_to_pid_key(tick_id: &TickId) -> Vec<u8> {
+  min_key(tick_id.hex().as_bytes(), PID_BYTE_COUNT - TICK_ID_STR_COUNT)
 }
 
-fn max_tid_to_pid_key(tick_id: &TickId) -> String {
-  format!("{}ffffff", hex::encode(tick_id.hex()).as_str())
+fn max_tid_to_pid_key(tick_id: &TickId) -> Vec<u8> {
+  max_key(tick_id.hex().as_bytes(), PID_BYTE_COUNT - TICK_ID_STR_COUNT)
 }
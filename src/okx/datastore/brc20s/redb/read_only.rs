@@ -1,113 +1,13 @@
 use super::*;
-use crate::okx::datastore::brc20s::{
-  Balance, DataStoreReadOnly, InscriptionOperation, Pid, PledgedTick, PoolInfo, Receipt, StakeInfo,
-  TickId, TickInfo, TransferInfo, TransferableAsset, UserInfo,
+use crate::okx::datastore::{
+  brc20s::{
+    Balance, DataStoreReadOnly, InscriptionOperation, Pid, PledgedTick, PoolInfo, Receipt,
+    StakeInfo, TickId, TickInfo, TransferInfo, TransferableAsset, UserInfo,
+  },
+  table::TableReader,
 };
-use redb::{
-  AccessGuard, Range, ReadOnlyTable, ReadTransaction, ReadableTable, RedbKey, RedbValue,
-  StorageError, Table, TableDefinition, WriteTransaction,
-};
-use std::{borrow::Borrow, ops::RangeBounds};
-
-pub fn try_init_tables<'db, 'a>(
-  wtx: &'a WriteTransaction<'db>,
-  rtx: &'a ReadTransaction<'db>,
-) -> Result<bool, redb::Error> {
-  if rtx.open_table(BRC20S_TICKINFO).is_err() {
-    wtx.open_table(BRC20S_TICKINFO)?;
-    wtx.open_table(BRC20S_PID_TO_POOLINFO)?;
-    wtx.open_table(BRC20S_USER_STAKEINFO)?;
-    wtx.open_table(BRC20S_PID_TO_USERINFO)?;
-    wtx.open_table(BRC20S_STAKE_TICKID_TO_PID)?;
-    wtx.open_table(BRC20S_TICKID_STAKE_TO_PID)?;
-    wtx.open_table(BRC20S_BALANCES)?;
-    wtx.open_table(BRC20S_TRANSFERABLE_ASSETS)?;
-    wtx.open_table(BRC20S_TXID_TO_RECEIPTS)?;
-    wtx.open_table(BRC20S_INSCRIBE_TRANSFER)?;
-  }
-
-  Ok(true)
-}
-
-pub struct DataStoreReader<'db, 'a> {
-  wrapper: ReaderWrapper<'db, 'a>,
-}
-
-pub(in crate::okx) fn new_with_wtx<'db, 'a>(
-  wtx: &'a WriteTransaction<'db>,
-) -> DataStoreReader<'db, 'a> {
-  DataStoreReader {
-    wrapper: ReaderWrapper::Wtx(wtx),
-  }
-}
-
-impl<'db, 'a> DataStoreReader<'db, 'a> {
-  pub fn new(rtx: &'a ReadTransaction<'db>) -> Self {
-    Self {
-      wrapper: ReaderWrapper::Rtx(rtx),
-    }
-  }
-}
 
-enum ReaderWrapper<'db, 'a> {
-  Rtx(&'a ReadTransaction<'db>),
-  Wtx(&'a WriteTransaction<'db>),
-}
-
-impl<'db, 'a> ReaderWrapper<'db, 'a> {
-  fn open_table<K: RedbKey + 'static, V: RedbValue + 'static>(
-    &self,
-    definition: TableDefinition<'_, K, V>,
-  ) -> Result<TableWrapper<'db, '_, K, V>, redb::Error> {
-    match self {
-      Self::Rtx(rtx) => Ok(TableWrapper::RtxTable(rtx.open_table(definition)?)),
-      Self::Wtx(wtx) => Ok(TableWrapper::WtxTable(wtx.open_table(definition)?)),
-    }
-  }
-}
-
-enum TableWrapper<'db, 'txn, K: RedbKey + 'static, V: RedbValue + 'static> {
-  RtxTable(ReadOnlyTable<'txn, K, V>),
-  WtxTable(Table<'db, 'txn, K, V>),
-}
-
-impl<'db, 'txn, K: RedbKey + 'static, V: RedbValue + 'static> TableWrapper<'db, 'txn, K, V> {
-  fn get<'a>(
-    &self,
-    key: impl Borrow<K::SelfType<'a>>,
-  ) -> Result<Option<AccessGuard<'_, V>>, StorageError>
-  where
-    K: 'a,
-  {
-    match self {
-      Self::RtxTable(rtx_table) => rtx_table.get(key),
-      Self::WtxTable(wtx_table) => wtx_table.get(key),
-    }
-  }
-
-  fn range<'a: 'b, 'b, KR>(
-    &'a self,
-    range: impl RangeBounds<KR> + 'b,
-  ) -> Result<Range<'a, K, V>, StorageError>
-  where
-    K: 'a,
-    KR: Borrow<K::SelfType<'b>> + 'b,
-  {
-    match self {
-      Self::RtxTable(rtx_table) => rtx_table.range(range),
-      Self::WtxTable(wtx_table) => wtx_table.range(range),
-    }
-  }
-
-  fn len(&self) -> Result<u64, StorageError> {
-    match self {
-      Self::RtxTable(rtx_table) => rtx_table.len(),
-      Self::WtxTable(wtx_table) => wtx_table.len(),
-    }
-  }
-}
-
-impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
+impl<'a, T: TableReader> DataStoreReadOnly for DataStore<'a, T> {
   type Error = redb::Error;
 
   // TXID_TO_INSCRIPTION_RECEIPTS
@@ -117,9 +17,9 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   ) -> Result<Vec<InscriptionOperation>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(TXID_TO_INSCRIPTION_RECEIPTS)?
-        .get(txid.to_string().as_str())?
+        .tables
+        .table(TXID_TO_INSCRIPTION_RECEIPTS)
+        .get(txid_key(txid).as_slice())?
         .map_or(Vec::new(), |v| {
           bincode::deserialize::<Vec<InscriptionOperation>>(v.value()).unwrap()
         }),
@@ -130,9 +30,9 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   fn get_tick_info(&self, tick_id: &TickId) -> Result<Option<TickInfo>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20S_TICKINFO)?
-        .get(tick_id.hex().as_str())?
+        .tables
+        .table(BRC20S_TICKINFO)
+        .get(tick_id_key(tick_id).as_slice())?
         .map(|v| bincode::deserialize::<TickInfo>(v.value()).unwrap()),
     )
   }
@@ -142,10 +42,10 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
     start: usize,
     limit: Option<usize>,
   ) -> Result<(Vec<TickInfo>, usize), Self::Error> {
-    let table = self.wrapper.open_table(BRC20S_TICKINFO)?;
+    let table = self.tables.table(BRC20S_TICKINFO);
     let total = table.len()?;
     let tickinfos = table
-      .range(TickId::min_hex().as_str()..TickId::max_hex().as_str())?
+      .iter()?
       .skip(start)
       .take(limit.unwrap_or(usize::MAX))
       .flat_map(|result| {
@@ -162,18 +62,18 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   fn get_pid_to_poolinfo(&self, pid: &Pid) -> Result<Option<PoolInfo>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20S_PID_TO_POOLINFO)?
-        .get(pid.hex().as_str())?
+        .tables
+        .table(BRC20S_PID_TO_POOLINFO)
+        .get(pid_key(pid).as_slice())?
         .map(|v| bincode::deserialize::<PoolInfo>(v.value()).unwrap()),
     )
   }
 
   fn get_all_pools_by_tid(&self, tick_id: &TickId) -> Result<Vec<PoolInfo>, Self::Error> {
-    let table = self.wrapper.open_table(BRC20S_PID_TO_POOLINFO)?;
+    let table = self.tables.table(BRC20S_PID_TO_POOLINFO);
     let mut total = 0;
     let pools = table
-      .range(min_tid_to_pid_key(tick_id).as_str()..max_tid_to_pid_key(tick_id).as_str())?
+      .range(min_tid_to_pid_key(tick_id).as_slice()..=max_tid_to_pid_key(tick_id).as_slice())?
       .flat_map(|result| {
         result.map(|(_, data)| {
           let pool = bincode::deserialize::<PoolInfo>(data.value()).unwrap();
@@ -190,10 +90,10 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
     start: usize,
     limit: Option<usize>,
   ) -> Result<(Vec<PoolInfo>, usize), Self::Error> {
-    let table = self.wrapper.open_table(BRC20S_PID_TO_POOLINFO)?;
+    let table = self.tables.table(BRC20S_PID_TO_POOLINFO);
     let total = table.len()?;
     let pools = table
-      .range(Pid::min_hex().as_str()..Pid::max_hex().as_str())?
+      .iter()?
       .skip(start)
       .take(limit.unwrap_or(usize::MAX))
       .flat_map(|result| {
@@ -214,9 +114,9 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   ) -> Result<Option<StakeInfo>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20S_USER_STAKEINFO)?
-        .get(script_pledged_key(script_key, pledged_tick).as_str())?
+        .tables
+        .table(BRC20S_USER_STAKEINFO)
+        .get(script_pledged_key(script_key, pledged_tick).as_slice())?
         .map(|v| bincode::deserialize::<StakeInfo>(v.value()).unwrap()),
     )
   }
@@ -229,9 +129,9 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   ) -> Result<Option<UserInfo>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20S_PID_TO_USERINFO)?
-        .get(script_pid_key(script_key, pid).as_str())?
+        .tables
+        .table(BRC20S_PID_TO_USERINFO)
+        .get(script_pid_key(script_key, pid).as_slice())?
         .map(|v| bincode::deserialize::<UserInfo>(v.value()).unwrap()),
     )
   }
@@ -244,9 +144,9 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   ) -> Result<Option<Pid>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20S_STAKE_TICKID_TO_PID)?
-        .get(stake_tickid_key(pledged, tick_id).as_str())?
+        .tables
+        .table(BRC20S_STAKE_TICKID_TO_PID)
+        .get(stake_tickid_key(pledged, tick_id).as_slice())?
         .map(|v| bincode::deserialize::<Pid>(v.value()).unwrap()),
     )
   }
@@ -257,9 +157,9 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
     let max = max_tickid_stake_key(tick_id);
     Ok(
       self
-        .wrapper
-        .open_table(BRC20S_TICKID_STAKE_TO_PID)?
-        .range(min.as_str()..max.as_str())?
+        .tables
+        .table(BRC20S_TICKID_STAKE_TO_PID)
+        .range(min.as_slice()..=max.as_slice())?
         .flat_map(|result| {
           result.map(|(_, data)| {
             let pid = bincode::deserialize::<Pid>(data.value()).unwrap();
@@ -274,9 +174,9 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   fn get_stake_to_all_pid(&self, pledged: &PledgedTick) -> Result<Vec<Pid>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20S_STAKE_TICKID_TO_PID)?
-        .range(min_stake_tickid_key(pledged).as_str()..max_stake_tickid_key(pledged).as_str())?
+        .tables
+        .table(BRC20S_STAKE_TICKID_TO_PID)
+        .range(min_stake_tickid_key(pledged).as_slice()..=max_stake_tickid_key(pledged).as_slice())?
         .flat_map(|result| {
           result.map(|(_, data)| {
             let pid = bincode::deserialize::<Pid>(data.value()).unwrap();
@@ -295,9 +195,9 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   ) -> Result<Option<Balance>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20S_BALANCES)?
-        .get(script_tickid_key(script_key, tick_id).as_str())?
+        .tables
+        .table(BRC20S_BALANCES)
+        .get(script_tickid_key(script_key, tick_id).as_slice())?
         .map(|v| {
           let bal = bincode::deserialize::<Balance>(v.value()).unwrap();
           assert_eq!(&bal.tick_id, tick_id);
@@ -309,10 +209,11 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   fn get_balances(&self, script_key: &ScriptKey) -> Result<Vec<(TickId, Balance)>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20S_BALANCES)?
+        .tables
+        .table(BRC20S_BALANCES)
         .range(
-          min_script_tick_id_key(script_key).as_str()..max_script_tick_id_key(script_key).as_str(),
+          min_script_tick_id_key(script_key).as_slice()
+            ..=max_script_tick_id_key(script_key).as_slice(),
         )?
         .flat_map(|result| {
           result.map(|(_, data)| {
@@ -324,6 +225,61 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
     )
   }
 
+  // BRC20S_BALANCE_HISTORY
+  fn get_balance_at_height(
+    &self,
+    script_key: &ScriptKey,
+    tick_id: &TickId,
+    height: u64,
+  ) -> Result<Option<Balance>, Self::Error> {
+    Ok(
+      self
+        .tables
+        .table(BRC20S_BALANCE_HISTORY)
+        .range(
+          script_tickid_height_key(script_key, tick_id, 0).as_slice()
+            ..=script_tickid_height_key(script_key, tick_id, height).as_slice(),
+        )?
+        .next_back()
+        .transpose()?
+        .map(|(_, v)| bincode::deserialize::<Balance>(v.value()).unwrap()),
+    )
+  }
+
+  fn get_balances_at_height(
+    &self,
+    script_key: &ScriptKey,
+    height: u64,
+  ) -> Result<Vec<(TickId, Balance)>, Self::Error> {
+    let table = self.tables.table(BRC20S_BALANCE_HISTORY);
+
+    // history keys are ordered by tick id and then by height, so the last entry
+    // not above the height is the balance of the tick id at that height.
+    let mut balances: Vec<(Vec<u8>, Balance)> = Vec::new();
+    for result in table.range(
+      min_script_tickid_height_key(script_key).as_slice()
+        ..=max_script_tickid_height_key(script_key).as_slice(),
+    )? {
+      let (key, data) = result?;
+      let (script_tickid, updated_at) = split_script_tickid_height_key(key.value());
+      if updated_at > height {
+        continue;
+      }
+      let balance = bincode::deserialize::<Balance>(data.value()).unwrap();
+      match balances.last_mut() {
+        Some((last, last_balance)) if last == script_tickid => *last_balance = balance,
+        _ => balances.push((script_tickid.to_vec(), balance)),
+      }
+    }
+
+    Ok(
+      balances
+        .into_iter()
+        .map(|(_, balance)| (balance.tick_id, balance))
+        .collect(),
+    )
+  }
+
   // BRC20S_TRANSFERABLE_ASSETS
   fn get_transferable_asset(
     &self,
@@ -333,9 +289,9 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   ) -> Result<Option<TransferableAsset>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20S_TRANSFERABLE_ASSETS)?
-        .get(script_tickid_inscriptionid_key(script_key, tick_id, inscription_id).as_str())?
+        .tables
+        .table(BRC20S_TRANSFERABLE_ASSETS)
+        .get(script_tickid_inscriptionid_key(script_key, tick_id, inscription_id).as_slice())?
         .map(|v| bincode::deserialize::<TransferableAsset>(v.value()).unwrap()),
     )
   }
@@ -343,9 +299,12 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   fn get_transferable(&self, script: &ScriptKey) -> Result<Vec<TransferableAsset>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20S_TRANSFERABLE_ASSETS)?
-        .range(min_script_tick_id_key(script).as_str()..max_script_tick_id_key(script).as_str())?
+        .tables
+        .table(BRC20S_TRANSFERABLE_ASSETS)
+        .range(
+          min_script_inscriptionid_key(script).as_slice()
+            ..=max_script_inscriptionid_key(script).as_slice(),
+        )?
         .flat_map(|result| {
           result.map(|(_, v)| bincode::deserialize::<TransferableAsset>(v.value()).unwrap())
         })
@@ -360,10 +319,15 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   ) -> Result<Vec<TransferableAsset>, Self::Error> {
     Ok(
       self
-        .get_transferable(script)?
-        .iter()
-        .filter(|log| log.tick_id == *tick_id)
-        .cloned()
+        .tables
+        .table(BRC20S_TRANSFERABLE_ASSETS)
+        .range(
+          min_script_tickid_inscriptionid_key(script, tick_id).as_slice()
+            ..=max_script_tickid_inscriptionid_key(script, tick_id).as_slice(),
+        )?
+        .flat_map(|result| {
+          result.map(|(_, v)| bincode::deserialize::<TransferableAsset>(v.value()).unwrap())
+        })
         .collect(),
     )
   }
@@ -386,9 +350,9 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   fn get_txid_to_receipts(&self, txid: &Txid) -> Result<Vec<Receipt>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20S_TXID_TO_RECEIPTS)?
-        .get(txid.to_string().as_str())?
+        .tables
+        .table(BRC20S_TXID_TO_RECEIPTS)
+        .get(txid_key(txid).as_slice())?
         .map_or(Vec::new(), |v| {
           bincode::deserialize::<Vec<Receipt>>(v.value()).unwrap()
         }),
@@ -398,28 +362,81 @@ impl<'db, 'a> DataStoreReadOnly for DataStoreReader<'db, 'a> {
   fn get_transaction_receipts(&self, tx_id: &Txid) -> Result<Vec<Receipt>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(BRC20S_TXID_TO_RECEIPTS)?
-        .get(tx_id.to_string().as_str())?
+        .tables
+        .table(BRC20S_TXID_TO_RECEIPTS)
+        .get(txid_key(tx_id).as_slice())?
         .map_or(Vec::new(), |v| {
           bincode::deserialize::<Vec<Receipt>>(v.value()).unwrap()
         }),
     )
   }
 
+  // BRC20S_ADDRESS_RECEIPTS
+  fn get_address_receipts(
+    &self,
+    script_key: &ScriptKey,
+    before: Option<u64>,
+    limit: usize,
+    filter: &dyn Fn(&Receipt) -> bool,
+  ) -> Result<Vec<(u64, Txid, Receipt)>, Self::Error> {
+    let address_table = self.tables.table(BRC20S_ADDRESS_RECEIPTS);
+    let receipts_table = self.tables.table(BRC20S_TXID_TO_RECEIPTS);
+
+    // the address entries reference a receipt by its transaction and position, the receipts of
+    // a transaction are loaded once for its consecutive entries.
+    let mut transaction: Option<(Txid, Vec<Receipt>)> = None;
+    let mut receipts = Vec::new();
+    for result in address_table
+      .range(
+        min_script_sequence_key(script_key).as_slice()
+          ..script_sequence_key(script_key, before.unwrap_or(u64::MAX)).as_slice(),
+      )?
+      .rev()
+    {
+      if receipts.len() >= limit {
+        break;
+      }
+
+      let (key, data) = result?;
+      let (txid, index) = bincode::deserialize::<(Txid, u32)>(data.value()).unwrap();
+      if transaction.as_ref().map(|(cached, _)| *cached) != Some(txid) {
+        let tx_receipts = receipts_table
+          .get(txid_key(&txid).as_slice())?
+          .map_or(Vec::new(), |v| {
+            bincode::deserialize::<Vec<Receipt>>(v.value()).unwrap()
+          });
+        transaction = Some((txid, tx_receipts));
+      }
+
+      let receipt = transaction
+        .as_ref()
+        .and_then(|(_, tx_receipts)| tx_receipts.get(usize::try_from(index).unwrap()))
+        .ok_or_else(|| {
+          redb::Error::Corrupted(format!(
+            "receipt {index} of transaction {txid} of address {script_key} not found"
+          ))
+        })?;
+      if filter(receipt) {
+        receipts.push((
+          split_script_sequence_key(key.value()),
+          txid,
+          receipt.clone(),
+        ));
+      }
+    }
+
+    Ok(receipts)
+  }
+
   fn get_inscribe_transfer_inscription(
     &self,
     inscription_id: InscriptionId,
   ) -> Result<Option<TransferInfo>, Self::Error> {
-    let mut value = [0; 36];
-    let (txid, index) = value.split_at_mut(32);
-    txid.copy_from_slice(inscription_id.txid.as_ref());
-    index.copy_from_slice(&inscription_id.index.to_be_bytes());
     Ok(
       self
-        .wrapper
-        .open_table(BRC20S_INSCRIBE_TRANSFER)?
-        .get(&value)?
+        .tables
+        .table(BRC20S_INSCRIBE_TRANSFER)
+        .get(&inscription_id_key(&inscription_id))?
         .map(|v| bincode::deserialize::<TransferInfo>(v.value()).unwrap()),
     )
   }
@@ -1,171 +1,95 @@
 use super::*;
 use crate::{
-  okx::datastore::brc20s::{
-    Balance, DataStoreReadOnly, DataStoreReadWrite, InscriptionOperation, Pid, PoolInfo, Receipt,
-    StakeInfo, TickId, TickInfo, TransferInfo, TransferableAsset, UserInfo,
+  index::state_hash,
+  okx::datastore::{
+    brc20s::{
+      Balance, DataStoreReadOnly, DataStoreReadWrite, InscriptionOperation, Pid, PoolInfo, Receipt,
+      StakeInfo, TickId, TickInfo, TransferInfo, TransferableAsset, UserInfo,
+    },
+    keys::save_script_key,
+    table::TableWriter,
   },
-  InscriptionId,
 };
 use bitcoin::Txid;
-use redb::WriteTransaction;
+use redb::TableDefinition;
 
-pub struct DataStore<'db, 'a> {
-  wtx: &'a WriteTransaction<'db>,
-}
-
-impl<'db, 'a> DataStore<'db, 'a> {
-  pub fn new(wtx: &'a WriteTransaction<'db>) -> Self {
-    Self { wtx }
-  }
-}
-
-impl<'db, 'a> DataStoreReadOnly for DataStore<'db, 'a> {
-  type Error = redb::Error;
-
-  // TXID_TO_INSCRIPTION_RECEIPTS
-  fn get_txid_to_inscription_receipts(
-    &self,
-    txid: &Txid,
-  ) -> Result<Vec<InscriptionOperation>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_txid_to_inscription_receipts(txid)
-  }
-
-  // BRC20S_TICKINFO
-  fn get_tick_info(&self, tick_id: &TickId) -> Result<Option<TickInfo>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_tick_info(tick_id)
-  }
-
-  fn get_all_tick_info(
-    &self,
-    start: usize,
-    limit: Option<usize>,
-  ) -> Result<(Vec<TickInfo>, usize), Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_all_tick_info(start, limit)
-  }
-
-  // BRC20S_PID_TO_POOLINFO
-  fn get_pid_to_poolinfo(&self, pid: &Pid) -> Result<Option<PoolInfo>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_pid_to_poolinfo(pid)
-  }
-
-  fn get_all_pools_by_tid(&self, tick_id: &TickId) -> Result<Vec<PoolInfo>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_all_pools_by_tid(tick_id)
-  }
-
-  fn get_all_poolinfo(
-    &self,
-    start: usize,
-    limit: Option<usize>,
-  ) -> Result<(Vec<PoolInfo>, usize), Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_all_poolinfo(start, limit)
-  }
-
-  // BRC20S_USER_STAKEINFO
-  fn get_user_stakeinfo(
-    &self,
-    script_key: &ScriptKey,
-    pledged_tick: &PledgedTick,
-  ) -> Result<Option<StakeInfo>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_user_stakeinfo(script_key, pledged_tick)
-  }
-
-  // BRC20S_PID_TO_USERINFO
-  fn get_pid_to_use_info(
-    &self,
-    script_key: &ScriptKey,
-    pid: &Pid,
-  ) -> Result<Option<UserInfo>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_pid_to_use_info(script_key, pid)
-  }
-
-  // BRC20S_STAKE_TICKID_TO_PID
-  fn get_tickid_stake_to_pid(
-    &self,
-    tick_id: &TickId,
-    pledged: &PledgedTick,
-  ) -> Result<Option<Pid>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_tickid_stake_to_pid(tick_id, pledged)
-  }
-
-  // get_tickid_to_all_pid
-  fn get_tickid_to_all_pid(&self, tick_id: &TickId) -> Result<Vec<Pid>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_tickid_to_all_pid(tick_id)
-  }
-
-  // get_stake_to_all_pid
-  fn get_stake_to_all_pid(&self, pledged: &PledgedTick) -> Result<Vec<Pid>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_stake_to_all_pid(pledged)
-  }
-
-  // BRC20S_BALANCE
-  fn get_balance(
+impl<'a, T: TableWriter> DataStore<'a, T> {
+  // writes a consensus state entry and records it for the block state hash.
+  // The cached ticks, pools, users, stakes and balances are recorded by the cache instead, since
+  // their writes can reach the tables in a later block.
+  fn insert_state(
     &self,
-    script_key: &ScriptKey,
-    tick_id: &TickId,
-  ) -> Result<Option<Balance>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_balance(script_key, tick_id)
-  }
-
-  fn get_balances(&self, script_key: &ScriptKey) -> Result<Vec<(TickId, Balance)>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_balances(script_key)
+    table: TableDefinition<'static, &'static [u8], &'static [u8]>,
+    key: &[u8],
+    value: &[u8],
+    change: state_hash::Change,
+  ) -> Result<(), redb::Error> {
+    self.tables.table(table).insert(key, value)?;
+    state_hash::record_change(self.tables, change)
   }
 
-  // BRC20S_TRANSFERABLE_ASSETS
-  fn get_transferable_asset(
+  // removes a consensus state entry and records it for the block state hash.
+  fn remove_state(
     &self,
-    script_key: &ScriptKey,
-    tick_id: &TickId,
-    inscription_id: &InscriptionId,
-  ) -> Result<Option<TransferableAsset>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_transferable_asset(script_key, tick_id, inscription_id)
-  }
-
-  fn get_transferable(&self, script: &ScriptKey) -> Result<Vec<TransferableAsset>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_transferable(script)
+    table: TableDefinition<'static, &'static [u8], &'static [u8]>,
+    key: &[u8],
+    change: state_hash::Change,
+  ) -> Result<(), redb::Error> {
+    self.tables.table(table).remove(key)?;
+    state_hash::record_change(self.tables, change)
   }
 
-  fn get_transferable_by_tickid(
+  fn insert_address_receipt(
     &self,
     script: &ScriptKey,
-    tick_id: &TickId,
-  ) -> Result<Vec<TransferableAsset>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_transferable_by_tickid(script, tick_id)
-  }
-
-  fn get_transferable_by_id(
-    &self,
-    script: &ScriptKey,
-    inscription_id: &InscriptionId,
-  ) -> Result<Option<TransferableAsset>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_transferable_by_id(script, inscription_id)
-  }
-
-  // BRC20S_TXID_TO_RECEIPTS
-  fn get_txid_to_receipts(&self, tx_id: &Txid) -> Result<Vec<Receipt>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_txid_to_receipts(tx_id)
-  }
-
-  fn get_transaction_receipts(&self, tx_id: &Txid) -> Result<Vec<Receipt>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_transaction_receipts(tx_id)
+    tx_id: &Txid,
+    index: usize,
+  ) -> Result<(), redb::Error> {
+    let sequence = self
+      .tables
+      .table(BRC20S_ADDRESS_RECEIPTS)
+      .range(
+        min_script_sequence_key(script).as_slice()..=max_script_sequence_key(script).as_slice(),
+      )?
+      .next_back()
+      .transpose()?
+      .map_or(0, |(key, _)| split_script_sequence_key(key.value()) + 1);
+
+    self.tables.table(BRC20S_ADDRESS_RECEIPTS).insert(
+      script_sequence_key(script, sequence).as_slice(),
+      bincode::serialize(&(tx_id, u32::try_from(index).unwrap()))
+        .unwrap()
+        .as_slice(),
+    )?;
+    Ok(())
   }
 
-  fn get_inscribe_transfer_inscription(
+  /// Indexes the receipts of a transaction from the `start`th on under the addresses involved.
+  pub(crate) fn index_address_receipts(
     &self,
-    inscription_id: InscriptionId,
-  ) -> Result<Option<TransferInfo>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_inscribe_transfer_inscription(inscription_id)
+    tx_id: &Txid,
+    receipts: &[Receipt],
+    start: usize,
+  ) -> Result<(), redb::Error> {
+    for (index, receipt) in receipts.iter().enumerate().skip(start) {
+      self.insert_address_receipt(&receipt.from, tx_id, index)?;
+      if receipt.to != receipt.from {
+        self.insert_address_receipt(&receipt.to, tx_id, index)?;
+      }
+    }
+    Ok(())
   }
 }
 
-impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
+impl<'a, T: TableWriter> DataStoreReadWrite for DataStore<'a, T> {
   // TXID_TO_INSCRIPTION_RECEIPTS
   fn set_txid_to_inscription_receipts(
     &self,
     tx_id: &Txid,
     inscription_operations: &[InscriptionOperation],
   ) -> Result<(), Self::Error> {
-    self.wtx.open_table(TXID_TO_INSCRIPTION_RECEIPTS)?.insert(
-      tx_id.to_string().as_str(),
+    self.tables.table(TXID_TO_INSCRIPTION_RECEIPTS).insert(
+      txid_key(tx_id).as_slice(),
       bincode::serialize(inscription_operations)
         .unwrap()
         .as_slice(),
@@ -175,8 +99,8 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
 
   // BRC20S_TICKINFO
   fn set_tick_info(&self, tick_id: &TickId, tick_info: &TickInfo) -> Result<(), Self::Error> {
-    self.wtx.open_table(BRC20S_TICKINFO)?.insert(
-      tick_id.hex().as_str(),
+    self.tables.table(BRC20S_TICKINFO).insert(
+      tick_id_key(tick_id).as_slice(),
       bincode::serialize(tick_info).unwrap().as_slice(),
     )?;
     Ok(())
@@ -184,8 +108,8 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
 
   // BRC20S_PID_TO_POOLINFO
   fn set_pid_to_poolinfo(&self, pid: &Pid, pool_info: &PoolInfo) -> Result<(), Self::Error> {
-    self.wtx.open_table(BRC20S_PID_TO_POOLINFO)?.insert(
-      pid.hex().as_str(),
+    self.tables.table(BRC20S_PID_TO_POOLINFO).insert(
+      pid_key(pid).as_slice(),
       bincode::serialize(pool_info).unwrap().as_slice(),
     )?;
     Ok(())
@@ -198,8 +122,8 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
     pledged_tick: &PledgedTick,
     stake_info: &StakeInfo,
   ) -> Result<(), Self::Error> {
-    self.wtx.open_table(BRC20S_USER_STAKEINFO)?.insert(
-      script_pledged_key(script_key, pledged_tick).as_str(),
+    self.tables.table(BRC20S_USER_STAKEINFO).insert(
+      script_pledged_key(script_key, pledged_tick).as_slice(),
       bincode::serialize(stake_info).unwrap().as_slice(),
     )?;
     Ok(())
@@ -212,8 +136,9 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
     pid: &Pid,
     user_info: &UserInfo,
   ) -> Result<(), Self::Error> {
-    self.wtx.open_table(BRC20S_PID_TO_USERINFO)?.insert(
-      script_pid_key(script_key, pid).as_str(),
+    save_script_key(self.tables, script_key)?;
+    self.tables.table(BRC20S_PID_TO_USERINFO).insert(
+      script_pid_key(script_key, pid).as_slice(),
       bincode::serialize(user_info).unwrap().as_slice(),
     )?;
     Ok(())
@@ -226,13 +151,13 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
     pledged: &PledgedTick,
     pid: &Pid,
   ) -> Result<(), Self::Error> {
-    self.wtx.open_table(BRC20S_STAKE_TICKID_TO_PID)?.insert(
-      stake_tickid_key(pledged, tick_id).as_str(),
+    self.tables.table(BRC20S_STAKE_TICKID_TO_PID).insert(
+      stake_tickid_key(pledged, tick_id).as_slice(),
       bincode::serialize(pid).unwrap().as_slice(),
     )?;
 
-    self.wtx.open_table(BRC20S_TICKID_STAKE_TO_PID)?.insert(
-      tickid_stake_key(pledged, tick_id).as_str(),
+    self.tables.table(BRC20S_TICKID_STAKE_TO_PID).insert(
+      tickid_stake_key(pledged, tick_id).as_slice(),
       bincode::serialize(pid).unwrap().as_slice(),
     )?;
     Ok(())
@@ -245,13 +170,29 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
     tick_id: &TickId,
     balance: Balance,
   ) -> Result<(), Self::Error> {
-    self.wtx.open_table(BRC20S_BALANCES)?.insert(
-      script_tickid_key(script_key, tick_id).as_str(),
+    save_script_key(self.tables, script_key)?;
+    self.tables.table(BRC20S_BALANCES).insert(
+      script_tickid_key(script_key, tick_id).as_slice(),
       bincode::serialize(&balance).unwrap().as_slice(),
     )?;
     Ok(())
   }
 
+  // BRC20S_BALANCE_HISTORY
+  fn set_balance_history(
+    &self,
+    script_key: &ScriptKey,
+    tick_id: &TickId,
+    height: u64,
+    balance: &Balance,
+  ) -> Result<(), Self::Error> {
+    self.tables.table(BRC20S_BALANCE_HISTORY).insert(
+      script_tickid_height_key(script_key, tick_id, height).as_slice(),
+      bincode::serialize(balance).unwrap().as_slice(),
+    )?;
+    Ok(())
+  }
+
   // BRC20S_TRANSFERABLE_ASSETS
   fn set_transferable_assets(
     &self,
@@ -260,9 +201,11 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
     inscription_id: &InscriptionId,
     transferable_asset: &TransferableAsset,
   ) -> Result<(), Self::Error> {
-    self.wtx.open_table(BRC20S_TRANSFERABLE_ASSETS)?.insert(
-      script_tickid_inscriptionid_key(script_key, tick_id, inscription_id).as_str(),
+    self.insert_state(
+      BRC20S_TRANSFERABLE_ASSETS,
+      script_tickid_inscriptionid_key(script_key, tick_id, inscription_id).as_slice(),
       bincode::serialize(transferable_asset).unwrap().as_slice(),
+      state_hash::Change::brc20s_transferable(script_key, transferable_asset),
     )?;
     Ok(())
   }
@@ -279,8 +222,12 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
     tx_id: &Txid,
     receipts: &[Receipt],
   ) -> Result<(), Self::Error> {
-    self.wtx.open_table(BRC20S_TXID_TO_RECEIPTS)?.insert(
-      tx_id.to_string().as_str(),
+    // only receipts appended since the last save are new to the address index.
+    let saved = self.get_transaction_receipts(tx_id)?.len();
+    self.index_address_receipts(tx_id, receipts, saved)?;
+
+    self.tables.table(BRC20S_TXID_TO_RECEIPTS).insert(
+      txid_key(tx_id).as_slice(),
       bincode::serialize(receipts).unwrap().as_slice(),
     )?;
     Ok(())
@@ -292,10 +239,11 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
     tick_id: &TickId,
     inscription_id: &InscriptionId,
   ) -> Result<(), Self::Error> {
-    self
-      .wtx
-      .open_table(BRC20S_TRANSFERABLE_ASSETS)?
-      .remove(script_tickid_inscriptionid_key(script_key, tick_id, inscription_id).as_str())?;
+    self.remove_state(
+      BRC20S_TRANSFERABLE_ASSETS,
+      script_tickid_inscriptionid_key(script_key, tick_id, inscription_id).as_slice(),
+      state_hash::Change::brc20s_transferable_removed(script_key, tick_id, inscription_id),
+    )?;
     Ok(())
   }
 
@@ -304,13 +252,8 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
     inscription_id: InscriptionId,
     transfer_info: TransferInfo,
   ) -> Result<(), Self::Error> {
-    let mut value = [0; 36];
-    let (txid, index) = value.split_at_mut(32);
-    txid.copy_from_slice(inscription_id.txid.as_ref());
-    index.copy_from_slice(&inscription_id.index.to_be_bytes());
-
-    self.wtx.open_table(BRC20S_INSCRIBE_TRANSFER)?.insert(
-      &value,
+    self.tables.table(BRC20S_INSCRIBE_TRANSFER).insert(
+      &inscription_id_key(&inscription_id),
       bincode::serialize(&transfer_info).unwrap().as_slice(),
     )?;
     Ok(())
@@ -320,15 +263,10 @@ impl<'db, 'a> DataStoreReadWrite for DataStore<'db, 'a> {
     &self,
     inscription_id: InscriptionId,
   ) -> Result<(), Self::Error> {
-    let mut value = [0; 36];
-    let (txid, index) = value.split_at_mut(32);
-    txid.copy_from_slice(inscription_id.txid.as_ref());
-    index.copy_from_slice(&inscription_id.index.to_be_bytes());
-
     self
-      .wtx
-      .open_table(BRC20S_INSCRIBE_TRANSFER)?
-      .remove(&value)?;
+      .tables
+      .table(BRC20S_INSCRIBE_TRANSFER)
+      .remove(&inscription_id_key(&inscription_id))?;
     Ok(())
   }
 }
@@ -417,6 +355,85 @@ mod tests {
     assert_eq!(all_balances, expect);
   }
 
+  #[test]
+  fn test_balance_history() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    let brc20s_db = DataStore::new(&wtx);
+
+    let script = ScriptKey::from_address(
+      Address::from_str("bc1qhvd6suvqzjcu9pxjhrwhtrlj85ny3n2mqql5w4")
+        .unwrap()
+        .assume_checked(),
+    );
+    let tick1 = TickId::from_str("f7c515d6b1").unwrap();
+    let tick2 = TickId::from_str("f7c515d6b2").unwrap();
+    let balance_at = |tick_id: TickId, overall_balance: u128| Balance {
+      tick_id,
+      overall_balance,
+      transferable_balance: 0,
+    };
+
+    brc20s_db
+      .set_balance_history(&script, &tick1, 100, &balance_at(tick1, 10))
+      .unwrap();
+    brc20s_db
+      .set_balance_history(&script, &tick1, 200, &balance_at(tick1, 20))
+      .unwrap();
+    brc20s_db
+      .set_balance_history(&script, &tick2, 150, &balance_at(tick2, 5))
+      .unwrap();
+
+    assert_eq!(
+      brc20s_db
+        .get_balance_at_height(&script, &tick1, 99)
+        .unwrap(),
+      None
+    );
+    assert_eq!(
+      brc20s_db
+        .get_balance_at_height(&script, &tick1, 100)
+        .unwrap(),
+      Some(balance_at(tick1, 10))
+    );
+    assert_eq!(
+      brc20s_db
+        .get_balance_at_height(&script, &tick1, 199)
+        .unwrap(),
+      Some(balance_at(tick1, 10))
+    );
+    assert_eq!(
+      brc20s_db
+        .get_balance_at_height(&script, &tick1, 1000)
+        .unwrap(),
+      Some(balance_at(tick1, 20))
+    );
+
+    assert_eq!(
+      brc20s_db.get_balances_at_height(&script, 99).unwrap(),
+      vec![]
+    );
+    assert_eq!(
+      brc20s_db.get_balances_at_height(&script, 149).unwrap(),
+      vec![(tick1, balance_at(tick1, 10))]
+    );
+    assert_eq!(
+      brc20s_db.get_balances_at_height(&script, 150).unwrap(),
+      vec![
+        (tick1, balance_at(tick1, 10)),
+        (tick2, balance_at(tick2, 5))
+      ]
+    );
+    assert_eq!(
+      brc20s_db.get_balances_at_height(&script, 200).unwrap(),
+      vec![
+        (tick1, balance_at(tick1, 20)),
+        (tick2, balance_at(tick2, 5))
+      ]
+    );
+  }
+
   #[test]
   fn test_txid_to_inscription_receipts() {
     let dbfile = NamedTempFile::new().unwrap();
@@ -969,6 +986,76 @@ mod tests {
     assert_eq!(brc20s_db.get_txid_to_receipts(&txid).unwrap(), op_vec);
   }
 
+  #[test]
+  fn test_address_receipts() {
+    let sender = ScriptKey::Address(
+      Address::from_str("bc1pgllnmtxs0g058qz7c6qgaqq4qknwrqj9z7rqn9e2dzhmcfmhlu4sfadf5e").unwrap(),
+    );
+    let receiver =
+      ScriptKey::Address(Address::from_str("bc1qhvd6suvqzjcu9pxjhrwhtrlj85ny3n2mqql5w4").unwrap());
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    let brc20s_db = DataStore::new(&wtx);
+
+    let txid =
+      Txid::from_str("b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735").unwrap();
+
+    let inscribe_transfer = Receipt {
+      inscription_id: InscriptionId::from_str(
+        "2111111111111111111111111111111111111111111111111111111111111111i1",
+      )
+      .unwrap(),
+      inscription_number: 0,
+      old_satpoint: SatPoint {
+        outpoint: Default::default(),
+        offset: 0,
+      },
+      new_satpoint: SatPoint {
+        outpoint: Default::default(),
+        offset: 0,
+      },
+      op: OperationType::InscribeTransfer,
+      from: sender.clone(),
+      to: sender.clone(),
+      result: Err(BRC20SError::InvalidTickLen("abcde".to_string())),
+    };
+    let transfer = Receipt {
+      op: OperationType::Transfer,
+      to: receiver.clone(),
+      ..inscribe_transfer.clone()
+    };
+
+    brc20s_db
+      .save_transaction_receipts(&txid, &[inscribe_transfer.clone()])
+      .unwrap();
+    brc20s_db
+      .save_transaction_receipts(&txid, &[inscribe_transfer.clone(), transfer.clone()])
+      .unwrap();
+
+    assert_eq!(
+      brc20s_db
+        .get_address_receipts(&sender, None, usize::MAX, &|_| true)
+        .unwrap(),
+      vec![
+        (1, txid, transfer.clone()),
+        (0, txid, inscribe_transfer.clone())
+      ]
+    );
+    assert_eq!(
+      brc20s_db
+        .get_address_receipts(&sender, Some(1), usize::MAX, &|_| true)
+        .unwrap(),
+      vec![(0, txid, inscribe_transfer)]
+    );
+    assert_eq!(
+      brc20s_db
+        .get_address_receipts(&receiver, None, usize::MAX, &|_| true)
+        .unwrap(),
+      vec![(0, txid, transfer)]
+    );
+  }
+
   #[test]
   fn test_stake_tickid_to_pid() {
     let dbfile = NamedTempFile::new().unwrap();
@@ -4,7 +4,7 @@ use crate::okx::protocol::brc20s::params::ZERO_NUM;
 use crate::okx::protocol::brc20s::{BRC20SError, Num};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
 pub struct StakeInfo {
   pub stake: PledgedTick,
   pub pool_stakes: Vec<(Pid, bool, u128)>,
@@ -6,7 +6,6 @@ use crate::okx::protocol::brc20s::params::{
 };
 use crate::okx::protocol::brc20s::BRC20SError;
 use crate::InscriptionId;
-use std::mem;
 
 use crate::okx::datastore::brc20s::Pid;
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
@@ -42,12 +41,8 @@ impl TickId {
     hex::encode(self.0)
   }
 
-  pub fn min_hex() -> String {
-    Self([0u8; TICK_ID_BYTE_COUNT]).hex()
-  }
-
-  pub fn max_hex() -> String {
-    Self([0xffu8; TICK_ID_BYTE_COUNT]).hex()
+  pub fn as_bytes(&self) -> &[u8; TICK_ID_BYTE_COUNT] {
+    &self.0
   }
 }
 
@@ -85,13 +80,13 @@ impl FromStr for Tick {
     }
 
     if length == NATIVE_TOKEN.len() && s.to_lowercase() == NATIVE_TOKEN {
-      return Ok(Self(bytes.try_into().unwrap()));
+      return Ok(Self(bytes.into()));
     }
 
     if !(TICK_BYTE_MIN_COUNT..=TICK_BYTE_MAX_COUNT).contains(&length) {
       return Err(BRC20SError::InvalidTickLen(s.to_string()));
     }
-    Ok(Self(bytes.try_into().unwrap()))
+    Ok(Self(bytes.into()))
   }
 }
 
@@ -156,16 +151,6 @@ pub enum PledgedTick {
 }
 
 impl PledgedTick {
-  pub fn max_hex() -> String {
-    const MAX_SIZE: usize = mem::size_of::<PledgedTick>();
-    hex::encode([0xffu8; MAX_SIZE])
-  }
-
-  pub fn min_hex() -> String {
-    const MAX_SIZE: usize = mem::size_of::<PledgedTick>();
-    hex::encode([0u8; MAX_SIZE])
-  }
-
   pub fn to_type(&self) -> String {
     match self {
       PledgedTick::Unknown => "Unknown".to_string(),
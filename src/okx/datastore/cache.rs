@@ -0,0 +1,235 @@
+use {
+  super::{brc20, brc20s},
+  serde::Serialize,
+  std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    mem,
+  },
+};
+
+struct Entry<K, V> {
+  value: Option<V>,
+  // the arguments needed to write the value back, set once it has been written.
+  args: Option<K>,
+  // whether the value is not yet written back.
+  dirty: bool,
+  // whether the value was written by the current block.
+  changed: bool,
+  size: usize,
+}
+
+/// WriteBackCache keeps decoded state entries in memory, under the keys of the underlying store.
+///
+/// Loaded entries, including missing ones, are served from memory until the cache is cleared.
+/// Written entries are dirty until they are taken to be written back to the store, and changed
+/// until they are taken as the changes of the block that wrote them, so that writing back can
+/// be deferred across blocks.
+pub(crate) struct WriteBackCache<K, V> {
+  entries: RefCell<HashMap<Vec<u8>, Entry<K, V>>>,
+  size: Cell<usize>,
+}
+
+impl<K, V> Default for WriteBackCache<K, V> {
+  fn default() -> Self {
+    Self {
+      entries: RefCell::new(HashMap::new()),
+      size: Cell::new(0),
+    }
+  }
+}
+
+impl<K: Clone, V: Clone + Serialize> WriteBackCache<K, V> {
+  /// Returns the cached value of `key`, loading it from the store on a miss.
+  pub(crate) fn get_or_load<E>(
+    &self,
+    key: Vec<u8>,
+    load: impl FnOnce() -> Result<Option<V>, E>,
+  ) -> Result<Option<V>, E> {
+    if let Some(entry) = self.entries.borrow().get(&key) {
+      return Ok(entry.value.clone());
+    }
+
+    let value = load()?;
+    self.insert(
+      key,
+      Entry {
+        value: value.clone(),
+        args: None,
+        dirty: false,
+        changed: false,
+        size: 0,
+      },
+    );
+    Ok(value)
+  }
+
+  /// Caches `value` as the dirty and changed value of `key`, to be written back with `args`.
+  pub(crate) fn put(&self, key: Vec<u8>, args: K, value: V) {
+    self.insert(
+      key,
+      Entry {
+        value: Some(value),
+        args: Some(args),
+        dirty: true,
+        changed: true,
+        size: 0,
+      },
+    );
+  }
+
+  /// Takes the dirty entries in key order, which stay cached as clean entries.
+  pub(crate) fn take_dirty(&self) -> Vec<(K, V)> {
+    Self::take(&mut self.entries.borrow_mut(), |entry| &mut entry.dirty)
+  }
+
+  /// Takes the entries changed since they were last taken in key order, which stay cached.
+  pub(crate) fn take_changed(&self) -> Vec<(K, V)> {
+    Self::take(&mut self.entries.borrow_mut(), |entry| &mut entry.changed)
+  }
+
+  /// Drops all entries but the changed ones, which must not be dirty.
+  pub(crate) fn clear(&self) {
+    let mut entries = self.entries.borrow_mut();
+    debug_assert!(entries.values().all(|entry| !entry.dirty));
+    entries.retain(|_, entry| entry.changed);
+    self
+      .size
+      .set(entries.values().map(|entry| entry.size).sum());
+  }
+
+  /// Returns the estimated memory used by the cached entries, in bytes.
+  pub(crate) fn size(&self) -> usize {
+    self.size.get()
+  }
+
+  // clears the `flag` of the entries it is set on, and returns them in key order.
+  fn take(
+    entries: &mut HashMap<Vec<u8>, Entry<K, V>>,
+    flag: impl Fn(&mut Entry<K, V>) -> &mut bool,
+  ) -> Vec<(K, V)> {
+    let mut taken = entries
+      .iter_mut()
+      .filter_map(|(key, entry)| {
+        if !mem::take(flag(entry)) {
+          return None;
+        }
+        Some((key.clone(), entry.args.clone()?, entry.value.clone()?))
+      })
+      .collect::<Vec<_>>();
+
+    taken.sort_by(|a, b| a.0.cmp(&b.0));
+    taken
+      .into_iter()
+      .map(|(_, args, value)| (args, value))
+      .collect()
+  }
+
+  fn insert(&self, key: Vec<u8>, mut entry: Entry<K, V>) {
+    entry.size = mem::size_of::<Entry<K, V>>()
+      + key.len()
+      + entry.value.as_ref().map_or(0, |value| {
+        usize::try_from(bincode::serialized_size(value).unwrap()).unwrap()
+      });
+    let size = entry.size;
+
+    let old = self.entries.borrow_mut().insert(key, entry);
+    self
+      .size
+      .set(self.size.get() + size - old.map_or(0, |old| old.size));
+  }
+}
+
+/// StateCache holds the decoded BRC20 and BRC20S state cached across the blocks of an index update.
+pub struct StateCache {
+  pub(super) brc20: brc20::cache::Cache,
+  pub(super) brc20s: brc20s::cache::Cache,
+}
+
+impl StateCache {
+  /// Creates a cache keeping up to `limit` bytes of the state of each protocol.
+  pub(crate) fn new(limit: usize) -> Self {
+    Self {
+      brc20: brc20::cache::Cache::new(limit),
+      brc20s: brc20s::cache::Cache::new(limit),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn loads_once_and_takes_dirty_entries_in_key_order() {
+    let cache = WriteBackCache::<&str, u64>::default();
+    let mut loads = 0;
+
+    for _ in 0..2 {
+      assert_eq!(
+        cache
+          .get_or_load::<()>(b"b".to_vec(), || {
+            loads += 1;
+            Ok(None)
+          })
+          .unwrap(),
+        None
+      );
+    }
+    assert_eq!(loads, 1);
+
+    cache.put(b"b".to_vec(), "b", 2);
+    cache.put(b"a".to_vec(), "a", 1);
+    assert_eq!(
+      cache.get_or_load::<()>(b"b".to_vec(), || unreachable!()),
+      Ok(Some(2))
+    );
+
+    assert_eq!(cache.take_dirty(), vec![("a", 1), ("b", 2)]);
+    assert_eq!(cache.take_dirty(), Vec::new());
+    assert_eq!(
+      cache.get_or_load::<()>(b"a".to_vec(), || unreachable!()),
+      Ok(Some(1))
+    );
+  }
+
+  #[test]
+  fn size_tracks_cached_entries() {
+    let cache = WriteBackCache::<(), u64>::default();
+    assert_eq!(cache.size(), 0);
+
+    cache.put(b"a".to_vec(), (), 1);
+    let size = cache.size();
+    assert!(size > 0);
+
+    cache.put(b"a".to_vec(), (), 2);
+    assert_eq!(cache.size(), size);
+
+    cache.take_dirty();
+    cache.take_changed();
+    cache.clear();
+    assert_eq!(cache.size(), 0);
+  }
+
+  #[test]
+  fn changed_entries_are_taken_separately_and_survive_clearing() {
+    let cache = WriteBackCache::<&str, u64>::default();
+
+    cache.put(b"a".to_vec(), "a", 1);
+    cache.put(b"b".to_vec(), "b", 2);
+    assert_eq!(cache.take_dirty(), vec![("a", 1), ("b", 2)]);
+
+    cache.put(b"b".to_vec(), "b", 3);
+    assert_eq!(cache.take_dirty(), vec![("b", 3)]);
+    cache.clear();
+    assert_eq!(
+      cache.get_or_load::<()>(b"a".to_vec(), || unreachable!()),
+      Ok(Some(1))
+    );
+
+    assert_eq!(cache.take_changed(), vec![("a", 1), ("b", 3)]);
+    assert_eq!(cache.take_changed(), Vec::new());
+    cache.clear();
+    assert_eq!(cache.size(), 0);
+  }
+}
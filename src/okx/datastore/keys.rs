@@ -0,0 +1,92 @@
+use {
+  super::{
+    table::{define_state_tables, TableWriter},
+    ScriptKey,
+  },
+  crate::InscriptionId,
+  bitcoin::{
+    hashes::{sha256, Hash},
+    Txid,
+  },
+};
+
+// The script keys of the hashes the BRC20 and BRC20S balance keys start with, bincode encoded.
+define_state_tables! {
+  TABLES {
+    SCRIPT_KEYS: &ScriptKeyHash => &[u8] = "SCRIPT_KEYS",
+  }
+}
+
+pub(crate) const SCRIPT_KEY_HASH_LEN: usize = 32;
+pub(crate) const INSCRIPTION_ID_KEY_LEN: usize = 36;
+
+/// The fixed width stand-in of a script key in the keys of the BRC20 and BRC20S tables.
+pub(crate) type ScriptKeyHash = [u8; SCRIPT_KEY_HASH_LEN];
+
+/// Returns the SHA256 of the address or script hash of `script`.
+pub(crate) fn script_key_hash(script: &ScriptKey) -> ScriptKeyHash {
+  sha256::Hash::hash(script.to_string().as_bytes()).to_byte_array()
+}
+
+pub(crate) fn txid_key(txid: &Txid) -> [u8; 32] {
+  txid.to_byte_array()
+}
+
+pub(crate) fn inscription_id_key(inscription_id: &InscriptionId) -> [u8; INSCRIPTION_ID_KEY_LEN] {
+  let mut value = [0; INSCRIPTION_ID_KEY_LEN];
+  let (txid, index) = value.split_at_mut(32);
+  txid.copy_from_slice(inscription_id.txid.as_ref());
+  index.copy_from_slice(&inscription_id.index.to_be_bytes());
+  value
+}
+
+/// Records the script key of its hash, unless it is already known.
+pub(crate) fn save_script_key(
+  tables: &impl TableWriter,
+  script: &ScriptKey,
+) -> Result<(), redb::Error> {
+  let table = tables.table(SCRIPT_KEYS);
+  let hash = script_key_hash(script);
+  if table.get(&hash)?.is_none() {
+    table.insert(&hash, bincode::serialize(script).unwrap().as_slice())?;
+  }
+  Ok(())
+}
+
+/// Returns the smallest key starting with `prefix` and followed by `width` more bytes.
+pub(crate) fn min_key(prefix: &[u8], width: usize) -> Vec<u8> {
+  let mut key = prefix.to_vec();
+  key.resize(prefix.len() + width, 0);
+  key
+}
+
+/// Returns the largest key starting with `prefix` and followed by `width` more bytes.
+pub(crate) fn max_key(prefix: &[u8], width: usize) -> Vec<u8> {
+  let mut key = prefix.to_vec();
+  key.resize(prefix.len() + width, u8::MAX);
+  key
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, bitcoin::Address, std::str::FromStr};
+
+  #[test]
+  fn script_key_hash_is_the_hash_of_the_address() {
+    let address = "bc1qhvd6suvqzjcu9pxjhrwhtrlj85ny3n2mqql5w4";
+    assert_eq!(
+      script_key_hash(&ScriptKey::from_address(
+        Address::from_str(address).unwrap().assume_checked()
+      )),
+      sha256::Hash::hash(address.as_bytes()).to_byte_array()
+    );
+  }
+
+  #[test]
+  fn min_and_max_keys_bound_the_keys_with_a_prefix() {
+    assert_eq!(min_key(&[1, 2], 2), vec![1, 2, 0, 0]);
+    assert_eq!(max_key(&[1, 2], 2), vec![1, 2, 0xff, 0xff]);
+    assert!(min_key(&[1, 2], 2).as_slice() <= [1, 2, 0, 7].as_slice());
+    assert!(max_key(&[1, 2], 2).as_slice() < [1, 3].as_slice());
+  }
+}
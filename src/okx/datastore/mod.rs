@@ -1,47 +1,26 @@
 pub(crate) mod balance;
 pub mod brc20;
 pub mod brc20s;
+pub(crate) mod cache;
+pub(crate) mod keys;
 pub mod ord;
+pub(crate) mod overlay;
 mod redb;
 mod script_key;
+pub(crate) mod table;
 
-pub use self::{
-  redb::{StateReadOnly, StateReadWrite},
-  script_key::ScriptKey,
-};
+pub use self::{cache::StateCache, redb::StateReadWrite, script_key::ScriptKey};
 
-/// StateReader is a collection of multiple readonly storages.
+/// StateRWriter gives the protocols access to the tables they keep their state in.
 ///
-/// There are multiple categories in the storage, and they can be obtained separately.
-pub trait StateReader {
-  type OrdReader: ord::DataStoreReadOnly;
-  type BRC20Reader: brc20::DataStoreReadOnly;
-  type BRC20SReader: brc20s::DataStoreReadOnly;
-
-  // Returns a reference to the readonly Ord store.
-  fn ord(&self) -> &Self::OrdReader;
-
-  // Returns a reference to the readonly BRC20 store.
-  fn brc20(&self) -> &Self::BRC20Reader;
-
-  // Returns a reference to the readonly BRC20S store.
-  fn brc20s(&self) -> &Self::BRC20SReader;
-}
-
-/// StateRWriter is a collection of multiple read-write storages.
-///
-/// There are multiple categories in the storage, and they can be obtained separately.
+/// Every protocol opens its own store over `tables`. The BRC20 and BRC20S stores are served through
+/// the write-back caches of `cache`, which only reach the tables once the state is flushed.
 pub trait StateRWriter {
-  type OrdRWriter: ord::DataStoreReadWrite;
-  type BRC20RWriter: brc20::DataStoreReadWrite;
-  type BRC20SRWriter: brc20s::DataStoreReadWrite;
-
-  // Returns a reference to the read-write ord store.
-  fn ord(&self) -> &Self::OrdRWriter;
+  type Tables: table::TableWriter;
 
-  // Returns a reference to the read-write BRC20 store.
-  fn brc20(&self) -> &Self::BRC20RWriter;
+  // Returns the tables of the state.
+  fn tables(&self) -> &Self::Tables;
 
-  // Returns a reference to the read-write BRC20S store.
-  fn brc20s(&self) -> &Self::BRC20SRWriter;
+  // Returns the caches the protocol stores are served through.
+  fn cache(&self) -> &StateCache;
 }
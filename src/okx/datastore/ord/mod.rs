@@ -1,10 +1,11 @@
 pub use self::{
   operation::{Action, InscriptionOp},
-  redb::{OrdDbReadWriter, OrdDbReader},
+  redb::DataStore,
 };
 
 use {
-  crate::{InscriptionId, Result},
+  super::StateRWriter,
+  crate::{InscriptionId, Result, SatPoint},
   bitcoin::{OutPoint, TxOut, Txid},
   collections::CollectionKind,
   std::fmt::{Debug, Display},
@@ -25,6 +26,15 @@ pub trait DataStoreReadOnly {
 
   fn get_transaction_operations(&self, txid: &Txid) -> Result<Vec<InscriptionOp>, Self::Error>;
 
+  /// Returns the height of the block that confirmed `txid`, if it has inscription operations.
+  fn get_transaction_height(&self, txid: &Txid) -> Result<Option<u64>, Self::Error>;
+
+  /// Returns the block time and the transactions with inscription operations of the block, in block order.
+  fn get_block_transactions(&self, height: u64) -> Result<Option<(u32, Vec<Txid>)>, Self::Error>;
+
+  /// Returns the input satpoint of the commit transaction that funded the inscription at `satpoint`.
+  fn get_commit_input_satpoint(&self, satpoint: SatPoint) -> Result<Option<SatPoint>, Self::Error>;
+
   fn get_collections_of_inscription(
     &self,
     inscription_id: InscriptionId,
@@ -45,6 +55,21 @@ pub trait DataStoreReadWrite: DataStoreReadOnly {
     operations: &[InscriptionOp],
   ) -> Result<(), Self::Error>;
 
+  fn save_transaction_height(&self, txid: &Txid, height: u64) -> Result<(), Self::Error>;
+
+  fn save_block_transactions(
+    &self,
+    height: u64,
+    blocktime: u32,
+    txids: &[Txid],
+  ) -> Result<(), Self::Error>;
+
+  fn set_commit_input_satpoint(
+    &self,
+    satpoint: SatPoint,
+    commit_input_satpoint: SatPoint,
+  ) -> Result<(), Self::Error>;
+
   fn set_inscription_by_collection_key(
     &self,
     key: &str,
@@ -57,3 +82,8 @@ pub trait DataStoreReadWrite: DataStoreReadOnly {
     kind: &[CollectionKind],
   ) -> Result<(), Self::Error>;
 }
+
+/// Opens the ord store of `state`.
+pub(crate) fn store<S: StateRWriter>(state: &S) -> DataStore<'_, S::Tables> {
+  DataStore::new(state.tables())
+}
@@ -1,15 +1,31 @@
 pub mod read_only;
 pub mod read_write;
 
-pub use self::{
-  read_only::OrdDbReader,
-  read_write::{try_init_tables, OrdDbReadWriter},
-};
-use {super::CollectionKind, redb::TableDefinition};
-
-const ORD_TX_TO_OPERATIONS: TableDefinition<&str, &[u8]> =
-  TableDefinition::new("ORD_TX_TO_OPERATIONS");
-const COLLECTIONS_KEY_TO_INSCRIPTION_ID: TableDefinition<&str, &[u8; 36]> =
-  TableDefinition::new("COLLECTIONS_KEY_TO_INSCRIPTION_ID");
-const COLLECTIONS_INSCRIPTION_ID_TO_KINDS: TableDefinition<&[u8; 36], &[u8]> =
-  TableDefinition::new("COLLECTIONS_INSCRIPTION_ID_TO_KINDS");
+use {super::CollectionKind, crate::okx::datastore::table::define_state_tables};
+
+define_state_tables! {
+  TABLES {
+    ORD_TX_TO_OPERATIONS: &str => &[u8] = "ORD_TX_TO_OPERATIONS",
+    ORD_TX_TO_HEIGHT: &str => u64 = "ORD_TX_TO_HEIGHT",
+    ORD_HEIGHT_TO_BLOCK_TRANSACTIONS: u64 => &[u8] = "ORD_HEIGHT_TO_BLOCK_TRANSACTIONS",
+    ORD_SATPOINT_TO_COMMIT_INPUT_SATPOINT: &str => &[u8] = "ORD_SATPOINT_TO_COMMIT_INPUT_SATPOINT",
+  }
+}
+
+define_state_tables! {
+  COLLECTION_TABLES {
+    COLLECTIONS_KEY_TO_INSCRIPTION_ID: &str => &[u8; 36] = "COLLECTIONS_KEY_TO_INSCRIPTION_ID",
+    COLLECTIONS_INSCRIPTION_ID_TO_KINDS: &[u8; 36] => &[u8] = "COLLECTIONS_INSCRIPTION_ID_TO_KINDS",
+  }
+}
+
+/// DataStore keeps the ord state in the tables of `T`, next to the index tables it reads.
+pub struct DataStore<'a, T> {
+  tables: &'a T,
+}
+
+impl<'a, T> DataStore<'a, T> {
+  pub fn new(tables: &'a T) -> Self {
+    Self { tables }
+  }
+}
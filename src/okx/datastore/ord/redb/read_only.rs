@@ -2,77 +2,20 @@ use {
   super::*,
   crate::{
     index::{INSCRIPTION_ID_TO_INSCRIPTION_ENTRY, OUTPOINT_TO_ENTRY},
-    okx::datastore::ord::{DataStoreReadOnly, InscriptionOp},
-    Hash, InscriptionId, Result,
+    okx::datastore::{
+      ord::{DataStoreReadOnly, InscriptionOp},
+      table::TableReader,
+    },
+    Hash, InscriptionId, Result, SatPoint,
   },
   bitcoin::{
     consensus::{Decodable, Encodable},
     OutPoint, TxOut, Txid,
   },
-  redb::{
-    AccessGuard, ReadOnlyTable, ReadTransaction, ReadableTable, RedbKey, RedbValue, StorageError,
-    Table, TableDefinition, WriteTransaction,
-  },
-  std::{borrow::Borrow, io},
+  std::io,
 };
 
-pub struct OrdDbReader<'db, 'a> {
-  wrapper: ReaderWrapper<'db, 'a>,
-}
-
-pub(crate) fn new_with_wtx<'db, 'a>(wtx: &'a WriteTransaction<'db>) -> OrdDbReader<'db, 'a> {
-  OrdDbReader {
-    wrapper: ReaderWrapper::Wtx(wtx),
-  }
-}
-
-impl<'db, 'a> OrdDbReader<'db, 'a> {
-  #[allow(dead_code)]
-  pub fn new(rtx: &'a ReadTransaction<'db>) -> Self {
-    Self {
-      wrapper: ReaderWrapper::Rtx(rtx),
-    }
-  }
-}
-#[allow(dead_code)]
-enum ReaderWrapper<'db, 'a> {
-  Rtx(&'a ReadTransaction<'db>),
-  Wtx(&'a WriteTransaction<'db>),
-}
-
-impl<'db, 'a> ReaderWrapper<'db, 'a> {
-  fn open_table<K: RedbKey + 'static, V: RedbValue + 'static>(
-    &self,
-    definition: TableDefinition<'_, K, V>,
-  ) -> Result<TableWrapper<'db, '_, K, V>, redb::Error> {
-    match self {
-      Self::Rtx(rtx) => Ok(TableWrapper::RtxTable(rtx.open_table(definition)?)),
-      Self::Wtx(wtx) => Ok(TableWrapper::WtxTable(wtx.open_table(definition)?)),
-    }
-  }
-}
-
-enum TableWrapper<'db, 'txn, K: RedbKey + 'static, V: RedbValue + 'static> {
-  RtxTable(ReadOnlyTable<'txn, K, V>),
-  WtxTable(Table<'db, 'txn, K, V>),
-}
-
-impl<'db, 'txn, K: RedbKey + 'static, V: RedbValue + 'static> TableWrapper<'db, 'txn, K, V> {
-  fn get<'a>(
-    &self,
-    key: impl Borrow<K::SelfType<'a>>,
-  ) -> Result<Option<AccessGuard<'_, V>>, StorageError>
-  where
-    K: 'a,
-  {
-    match self {
-      Self::RtxTable(rtx_table) => rtx_table.get(key),
-      Self::WtxTable(wtx_table) => wtx_table.get(key),
-    }
-  }
-}
-
-impl<'db, 'a> DataStoreReadOnly for OrdDbReader<'db, 'a> {
+impl<'a, T: TableReader> DataStoreReadOnly for DataStore<'a, T> {
   type Error = redb::Error;
   fn get_collections_of_inscription(
     &self,
@@ -85,8 +28,8 @@ impl<'db, 'a> DataStoreReadOnly for OrdDbReader<'db, 'a> {
 
     Ok(
       self
-        .wrapper
-        .open_table(COLLECTIONS_INSCRIPTION_ID_TO_KINDS)?
+        .tables
+        .table(COLLECTIONS_INSCRIPTION_ID_TO_KINDS)
         .get(&key)?
         .map(|v| bincode::deserialize::<Vec<CollectionKind>>(v.value()).unwrap()),
     )
@@ -95,8 +38,8 @@ impl<'db, 'a> DataStoreReadOnly for OrdDbReader<'db, 'a> {
   fn get_collection_inscription_id(&self, key: &str) -> Result<Option<InscriptionId>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(COLLECTIONS_KEY_TO_INSCRIPTION_ID)?
+        .tables
+        .table(COLLECTIONS_KEY_TO_INSCRIPTION_ID)
         .get(key)?
         .map(|v| {
           let (txid, index) = v.value().split_at(32);
@@ -117,8 +60,8 @@ impl<'db, 'a> DataStoreReadOnly for OrdDbReader<'db, 'a> {
     index.copy_from_slice(&inscription_id.index.to_be_bytes());
     Ok(
       self
-        .wrapper
-        .open_table(INSCRIPTION_ID_TO_INSCRIPTION_ENTRY)?
+        .tables
+        .table(INSCRIPTION_ID_TO_INSCRIPTION_ENTRY)
         .get(&key)?
         .map(|value| value.value().2),
     )
@@ -131,8 +74,8 @@ impl<'db, 'a> DataStoreReadOnly for OrdDbReader<'db, 'a> {
       .unwrap();
     Ok(
       self
-        .wrapper
-        .open_table(OUTPOINT_TO_ENTRY)?
+        .tables
+        .table(OUTPOINT_TO_ENTRY)
         .get(&value)?
         .map(|x| Decodable::consensus_decode(&mut io::Cursor::new(x.value())).unwrap()),
     )
@@ -141,12 +84,42 @@ impl<'db, 'a> DataStoreReadOnly for OrdDbReader<'db, 'a> {
   fn get_transaction_operations(&self, txid: &Txid) -> Result<Vec<InscriptionOp>, Self::Error> {
     Ok(
       self
-        .wrapper
-        .open_table(ORD_TX_TO_OPERATIONS)?
+        .tables
+        .table(ORD_TX_TO_OPERATIONS)
         .get(txid.to_string().as_str())?
         .map_or(Vec::new(), |v| {
           bincode::deserialize::<Vec<InscriptionOp>>(v.value()).unwrap()
         }),
     )
   }
+
+  fn get_transaction_height(&self, txid: &Txid) -> Result<Option<u64>, Self::Error> {
+    Ok(
+      self
+        .tables
+        .table(ORD_TX_TO_HEIGHT)
+        .get(txid.to_string().as_str())?
+        .map(|v| v.value()),
+    )
+  }
+
+  fn get_block_transactions(&self, height: u64) -> Result<Option<(u32, Vec<Txid>)>, Self::Error> {
+    Ok(
+      self
+        .tables
+        .table(ORD_HEIGHT_TO_BLOCK_TRANSACTIONS)
+        .get(height)?
+        .map(|v| bincode::deserialize::<(u32, Vec<Txid>)>(v.value()).unwrap()),
+    )
+  }
+
+  fn get_commit_input_satpoint(&self, satpoint: SatPoint) -> Result<Option<SatPoint>, Self::Error> {
+    Ok(
+      self
+        .tables
+        .table(ORD_SATPOINT_TO_COMMIT_INPUT_SATPOINT)
+        .get(satpoint.to_string().as_str())?
+        .map(|v| Decodable::consensus_decode(&mut io::Cursor::new(v.value())).unwrap()),
+    )
+  }
 }
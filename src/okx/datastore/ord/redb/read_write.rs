@@ -2,70 +2,16 @@ use {
   super::*,
   crate::{
     index::OUTPOINT_TO_ENTRY,
-    okx::datastore::ord::{DataStoreReadOnly, DataStoreReadWrite, InscriptionOp},
-    InscriptionId, Result,
+    okx::datastore::{
+      ord::{DataStoreReadWrite, InscriptionOp},
+      table::TableWriter,
+    },
+    InscriptionId, Result, SatPoint,
   },
   bitcoin::{consensus::Encodable, OutPoint, TxOut, Txid},
-  redb::{ReadTransaction, WriteTransaction},
 };
 
-pub fn try_init_tables<'db, 'a>(
-  wtx: &'a WriteTransaction<'db>,
-  rtx: &'a ReadTransaction<'db>,
-) -> Result<bool, redb::Error> {
-  if rtx.open_table(ORD_TX_TO_OPERATIONS).is_err() {
-    wtx.open_table(ORD_TX_TO_OPERATIONS)?;
-    wtx.open_table(COLLECTIONS_KEY_TO_INSCRIPTION_ID)?;
-    wtx.open_table(COLLECTIONS_INSCRIPTION_ID_TO_KINDS)?;
-  }
-  Ok(true)
-}
-
-pub struct OrdDbReadWriter<'db, 'a> {
-  wtx: &'a WriteTransaction<'db>,
-}
-
-impl<'db, 'a> OrdDbReadWriter<'db, 'a>
-where
-  'db: 'a,
-{
-  pub fn new(wtx: &'a WriteTransaction<'db>) -> Self {
-    Self { wtx }
-  }
-}
-
-impl<'db, 'a> DataStoreReadOnly for OrdDbReadWriter<'db, 'a> {
-  type Error = redb::Error;
-  fn get_number_by_inscription_id(
-    &self,
-    inscription_id: InscriptionId,
-  ) -> Result<Option<i64>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_number_by_inscription_id(inscription_id)
-  }
-
-  fn get_outpoint_to_txout(&self, outpoint: OutPoint) -> Result<Option<TxOut>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_outpoint_to_txout(outpoint)
-  }
-
-  fn get_transaction_operations(
-    &self,
-    txid: &bitcoin::Txid,
-  ) -> Result<Vec<InscriptionOp>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_transaction_operations(txid)
-  }
-  // collections
-  fn get_collection_inscription_id(&self, key: &str) -> Result<Option<InscriptionId>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_collection_inscription_id(key)
-  }
-  fn get_collections_of_inscription(
-    &self,
-    inscription_id: InscriptionId,
-  ) -> Result<Option<Vec<CollectionKind>>, Self::Error> {
-    read_only::new_with_wtx(self.wtx).get_collections_of_inscription(inscription_id)
-  }
-}
-
-impl<'db, 'a> DataStoreReadWrite for OrdDbReadWriter<'db, 'a> {
+impl<'a, T: TableWriter> DataStoreReadWrite for DataStore<'a, T> {
   // OUTPOINT_TO_SCRIPT
 
   fn set_outpoint_to_txout(&self, outpoint: OutPoint, tx_out: &TxOut) -> Result<(), Self::Error> {
@@ -77,8 +23,8 @@ impl<'db, 'a> DataStoreReadWrite for OrdDbReadWriter<'db, 'a> {
     let mut entry = Vec::new();
     tx_out.consensus_encode(&mut entry)?;
     self
-      .wtx
-      .open_table(OUTPOINT_TO_ENTRY)?
+      .tables
+      .table(OUTPOINT_TO_ENTRY)
       .insert(&value, entry.as_slice())?;
     Ok(())
   }
@@ -88,12 +34,48 @@ impl<'db, 'a> DataStoreReadWrite for OrdDbReadWriter<'db, 'a> {
     txid: &Txid,
     operations: &[InscriptionOp],
   ) -> Result<(), Self::Error> {
-    self.wtx.open_table(ORD_TX_TO_OPERATIONS)?.insert(
+    self.tables.table(ORD_TX_TO_OPERATIONS).insert(
       txid.to_string().as_str(),
       bincode::serialize(operations).unwrap().as_slice(),
     )?;
     Ok(())
   }
+
+  fn save_transaction_height(&self, txid: &Txid, height: u64) -> Result<(), Self::Error> {
+    self
+      .tables
+      .table(ORD_TX_TO_HEIGHT)
+      .insert(txid.to_string().as_str(), height)?;
+    Ok(())
+  }
+
+  fn save_block_transactions(
+    &self,
+    height: u64,
+    blocktime: u32,
+    txids: &[Txid],
+  ) -> Result<(), Self::Error> {
+    self.tables.table(ORD_HEIGHT_TO_BLOCK_TRANSACTIONS).insert(
+      height,
+      bincode::serialize(&(blocktime, txids)).unwrap().as_slice(),
+    )?;
+    Ok(())
+  }
+
+  fn set_commit_input_satpoint(
+    &self,
+    satpoint: SatPoint,
+    commit_input_satpoint: SatPoint,
+  ) -> Result<(), Self::Error> {
+    let mut value = Vec::new();
+    commit_input_satpoint.consensus_encode(&mut value)?;
+    self
+      .tables
+      .table(ORD_SATPOINT_TO_COMMIT_INPUT_SATPOINT)
+      .insert(satpoint.to_string().as_str(), value.as_slice())?;
+    Ok(())
+  }
+
   fn set_inscription_by_collection_key(
     &self,
     key: &str,
@@ -104,8 +86,8 @@ impl<'db, 'a> DataStoreReadWrite for OrdDbReadWriter<'db, 'a> {
     txid.copy_from_slice(inscription_id.txid.as_ref());
     index.copy_from_slice(&inscription_id.index.to_be_bytes());
     self
-      .wtx
-      .open_table(COLLECTIONS_KEY_TO_INSCRIPTION_ID)?
+      .tables
+      .table(COLLECTIONS_KEY_TO_INSCRIPTION_ID)
       .insert(key, &value)?;
     Ok(())
   }
@@ -120,8 +102,8 @@ impl<'db, 'a> DataStoreReadWrite for OrdDbReadWriter<'db, 'a> {
     txid.copy_from_slice(inscription_id.txid.as_ref());
     index.copy_from_slice(&inscription_id.index.to_be_bytes());
     self
-      .wtx
-      .open_table(COLLECTIONS_INSCRIPTION_ID_TO_KINDS)?
+      .tables
+      .table(COLLECTIONS_INSCRIPTION_ID_TO_KINDS)
       .insert(&key, bincode::serialize(&kind).unwrap().as_slice())?;
     Ok(())
   }
@@ -130,7 +112,8 @@ impl<'db, 'a> DataStoreReadWrite for OrdDbReadWriter<'db, 'a> {
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::{inscription, okx::datastore::ord::Action, unbound_outpoint, SatPoint};
+  use crate::okx::datastore::ord::DataStoreReadOnly;
+  use crate::{inscription, okx::datastore::ord::Action, unbound_outpoint};
   use redb::Database;
   use std::str::FromStr;
   use tempfile::NamedTempFile;
@@ -140,7 +123,7 @@ mod tests {
     let dbfile = NamedTempFile::new().unwrap();
     let db = Database::create(dbfile.path()).unwrap();
     let wtx = db.begin_write().unwrap();
-    let ord_db = OrdDbReadWriter::new(&wtx);
+    let ord_db = DataStore::new(&wtx);
 
     let outpoint1 = unbound_outpoint();
     let tx_out = TxOut {
@@ -164,7 +147,7 @@ mod tests {
     let dbfile = NamedTempFile::new().unwrap();
     let db = Database::create(dbfile.path()).unwrap();
     let wtx = db.begin_write().unwrap();
-    let ord_db = OrdDbReadWriter::new(&wtx);
+    let ord_db = DataStore::new(&wtx);
     let txid =
       Txid::from_str("b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735").unwrap();
     let operation = InscriptionOp {
@@ -195,4 +178,68 @@ mod tests {
       vec![operation]
     );
   }
+
+  #[test]
+  fn test_transaction_height() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    let ord_db = DataStore::new(&wtx);
+    let txid =
+      Txid::from_str("b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735").unwrap();
+
+    assert_eq!(ord_db.get_transaction_height(&txid).unwrap(), None);
+
+    ord_db.save_transaction_height(&txid, 100).unwrap();
+
+    assert_eq!(ord_db.get_transaction_height(&txid).unwrap(), Some(100));
+  }
+
+  #[test]
+  fn test_block_transactions() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    let ord_db = DataStore::new(&wtx);
+    let txids = vec![
+      Txid::from_str("b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735").unwrap(),
+      Txid::from_str("1111111111111111111111111111111111111111111111111111111111111111").unwrap(),
+    ];
+
+    assert_eq!(ord_db.get_block_transactions(100).unwrap(), None);
+
+    ord_db
+      .save_block_transactions(100, 1_700_000_000, &txids)
+      .unwrap();
+
+    assert_eq!(
+      ord_db.get_block_transactions(100).unwrap(),
+      Some((1_700_000_000, txids))
+    );
+  }
+
+  #[test]
+  fn test_commit_input_satpoint() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    let ord_db = DataStore::new(&wtx);
+    let satpoint =
+      SatPoint::from_str("b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735:0:0")
+        .unwrap();
+    let commit_input_satpoint =
+      SatPoint::from_str("1111111111111111111111111111111111111111111111111111111111111111:1:1")
+        .unwrap();
+
+    assert_eq!(ord_db.get_commit_input_satpoint(satpoint).unwrap(), None);
+
+    ord_db
+      .set_commit_input_satpoint(satpoint, commit_input_satpoint)
+      .unwrap();
+
+    assert_eq!(
+      ord_db.get_commit_input_satpoint(satpoint).unwrap(),
+      Some(commit_input_satpoint)
+    );
+  }
 }
@@ -0,0 +1,376 @@
+use {
+  super::table::{TableReader, TableWriter},
+  crate::index::undo::{JournaledMultimapTable, JournaledTable},
+  redb::{
+    MultimapTableDefinition, MultimapTableHandle, RedbKey, RedbValue, TableDefinition, TableHandle,
+  },
+  std::{cell::RefCell, cmp::Ordering, collections::HashMap, ops::Bound},
+};
+
+/// A value written to the overlay, `None` once removed.
+struct Written {
+  value: Option<Vec<u8>>,
+  // whether the key is in the base, which the overlay len is counted from.
+  in_base: bool,
+}
+
+/// The writes kept by a `TableOverlay`, in the key order of their tables.
+#[derive(Default)]
+pub(crate) struct Writes {
+  tables: HashMap<String, Vec<(Vec<u8>, Written)>>,
+  // every value of the multimap keys written to, in value order.
+  multimap_tables: HashMap<String, Vec<(Vec<u8>, Vec<Vec<u8>>)>>,
+}
+
+/// TableOverlay keeps the writes to the tables of a `TableReader` in memory, and reads them back on
+/// top of it.
+///
+/// The base is never written to, the writes are dropped along with the overlay unless they are
+/// taken back out of it. Entries are kept in the key order of their tables, so that ranges merge
+/// with those of the base.
+pub(crate) struct TableOverlay<'a, T> {
+  base: &'a T,
+  tables: RefCell<HashMap<String, Vec<(Vec<u8>, Written)>>>,
+  multimap_tables: RefCell<HashMap<String, Vec<(Vec<u8>, Vec<Vec<u8>>)>>>,
+}
+
+impl<'a, T: TableReader> TableOverlay<'a, T> {
+  /// Returns an overlay reading `writes` back on top of `base`, which must hold the same entries
+  /// as the base they were written over.
+  pub(crate) fn with_writes(base: &'a T, writes: Writes) -> Self {
+    Self {
+      base,
+      tables: RefCell::new(writes.tables),
+      multimap_tables: RefCell::new(writes.multimap_tables),
+    }
+  }
+
+  pub(crate) fn into_writes(self) -> Writes {
+    Writes {
+      tables: self.tables.into_inner(),
+      multimap_tables: self.multimap_tables.into_inner(),
+    }
+  }
+
+  fn write<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: &[u8],
+    value: Option<Vec<u8>>,
+  ) -> Result<(), redb::Error> {
+    let mut tables = self.tables.borrow_mut();
+    let entries = tables.entry(definition.name().to_string()).or_default();
+    match position::<K, _>(entries, key) {
+      Ok(index) => entries[index].1.value = value,
+      Err(index) => {
+        let in_base = self.base.get_bytes(definition, key)?.is_some();
+        entries.insert(index, (key.to_vec(), Written { value, in_base }));
+      }
+    }
+    Ok(())
+  }
+}
+
+fn position<K: RedbKey, E>(entries: &[(Vec<u8>, E)], key: &[u8]) -> Result<usize, usize> {
+  entries.binary_search_by(|(probe, _)| K::compare(probe, key))
+}
+
+fn contains<K: RedbKey>(start: Bound<&[u8]>, end: Bound<&[u8]>, key: &[u8]) -> bool {
+  let after_start = match start {
+    Bound::Included(start) => K::compare(key, start) != Ordering::Less,
+    Bound::Excluded(start) => K::compare(key, start) == Ordering::Greater,
+    Bound::Unbounded => true,
+  };
+  let before_end = match end {
+    Bound::Included(end) => K::compare(key, end) != Ordering::Greater,
+    Bound::Excluded(end) => K::compare(key, end) == Ordering::Less,
+    Bound::Unbounded => true,
+  };
+  after_start && before_end
+}
+
+impl<'a, T: TableReader> TableReader for TableOverlay<'a, T> {
+  fn get_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<Option<Vec<u8>>, redb::Error> {
+    if let Some(entries) = self.tables.borrow().get(definition.name()) {
+      if let Ok(index) = position::<K, _>(entries, key) {
+        return Ok(entries[index].1.value.clone());
+      }
+    }
+    self.base.get_bytes(definition, key)
+  }
+
+  fn range_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    start: Bound<&[u8]>,
+    end: Bound<&[u8]>,
+    reverse: bool,
+    limit: usize,
+  ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, redb::Error> {
+    let tables = self.tables.borrow();
+    let Some(written) = tables.get(definition.name()) else {
+      return self
+        .base
+        .range_bytes(definition, start, end, reverse, limit);
+    };
+
+    let written = written
+      .iter()
+      .filter(|(key, _)| contains::<K>(start, end, key))
+      .collect::<Vec<_>>();
+
+    // every written entry hides at most one entry of the base, so this many are enough to fill
+    // `limit` entries.
+    let mut entries = self
+      .base
+      .range_bytes(
+        definition,
+        start,
+        end,
+        reverse,
+        limit.saturating_add(written.len()),
+      )?
+      .into_iter()
+      .filter(|(key, _)| {
+        written
+          .binary_search_by(|(probe, _)| K::compare(probe, key))
+          .is_err()
+      })
+      .chain(written.iter().filter_map(|(key, written)| {
+        written
+          .value
+          .as_ref()
+          .map(|value| (key.clone(), value.clone()))
+      }))
+      .collect::<Vec<(Vec<u8>, Vec<u8>)>>();
+
+    entries.sort_by(|(a, _), (b, _)| {
+      if reverse {
+        K::compare(b, a)
+      } else {
+        K::compare(a, b)
+      }
+    });
+    entries.truncate(limit);
+
+    Ok(entries)
+  }
+
+  fn len<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+  ) -> Result<u64, redb::Error> {
+    let mut len = self.base.len(definition)?;
+    if let Some(entries) = self.tables.borrow().get(definition.name()) {
+      for (_, written) in entries {
+        match (written.value.is_some(), written.in_base) {
+          (true, false) => len += 1,
+          (false, true) => len -= 1,
+          _ => {}
+        }
+      }
+    }
+    Ok(len)
+  }
+
+  fn get_all_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<Vec<Vec<u8>>, redb::Error> {
+    if let Some(entries) = self.multimap_tables.borrow().get(definition.name()) {
+      if let Ok(index) = position::<K, _>(entries, key) {
+        return Ok(entries[index].1.clone());
+      }
+    }
+    self.base.get_all_bytes(definition, key)
+  }
+
+  fn range_all_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    start: Bound<&[u8]>,
+    end: Bound<&[u8]>,
+  ) -> Result<Vec<(Vec<u8>, Vec<Vec<u8>>)>, redb::Error> {
+    let mut entries = self.base.range_all_bytes(definition, start, end)?;
+
+    if let Some(written) = self.multimap_tables.borrow().get(definition.name()) {
+      entries.retain(|(key, _)| position::<K, _>(written, key).is_err());
+      entries.extend(
+        written
+          .iter()
+          .filter(|(key, values)| !values.is_empty() && contains::<K>(start, end, key))
+          .cloned(),
+      );
+      entries.sort_by(|(a, _), (b, _)| K::compare(a, b));
+    }
+
+    Ok(entries)
+  }
+}
+
+impl<'a, T: TableReader> TableWriter for TableOverlay<'a, T> {
+  fn insert_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: &[u8],
+    value: &[u8],
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledTable,
+  {
+    self.write(definition, key, Some(value.to_vec()))
+  }
+
+  fn remove_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledTable,
+  {
+    self.write(definition, key, None)
+  }
+
+  fn insert_multimap_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: &[u8],
+    value: &[u8],
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledMultimapTable,
+  {
+    let mut tables = self.multimap_tables.borrow_mut();
+    let entries = tables.entry(definition.name().to_string()).or_default();
+    let index = match position::<K, _>(entries, key) {
+      Ok(index) => index,
+      Err(index) => {
+        let values = self.base.get_all_bytes(definition, key)?;
+        entries.insert(index, (key.to_vec(), values));
+        index
+      }
+    };
+
+    let values = &mut entries[index].1;
+    if let Err(index) = values.binary_search_by(|probe| V::compare(probe, value)) {
+      values.insert(index, value.to_vec());
+    }
+    Ok(())
+  }
+
+  fn remove_all_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledMultimapTable,
+  {
+    let mut tables = self.multimap_tables.borrow_mut();
+    let entries = tables.entry(definition.name().to_string()).or_default();
+    match position::<K, _>(entries, key) {
+      Ok(index) => entries[index].1.clear(),
+      Err(index) => entries.insert(index, (key.to_vec(), Vec::new())),
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {
+    super::*,
+    crate::okx::datastore::table::StateValue,
+    redb::{Database, ReadableTable},
+    tempfile::NamedTempFile,
+  };
+
+  const NUMBERS: TableDefinition<u64, u64> = TableDefinition::new("NUMBERS");
+  const SETS: MultimapTableDefinition<u64, &[u8; 36]> = MultimapTableDefinition::new("SETS");
+
+  fn id(n: u64) -> [u8; 36] {
+    [u8::try_from(n).unwrap(); 36]
+  }
+
+  #[test]
+  fn writes_are_read_back_on_top_of_the_base() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    {
+      let wtx = db.begin_write().unwrap();
+      for n in 0..10 {
+        wtx.table(NUMBERS).insert(n * 2, n).unwrap();
+        wtx.multimap_table(SETS).insert(n, &id(n)).unwrap();
+      }
+      wtx.commit().unwrap();
+    }
+
+    let rtx = db.begin_read().unwrap();
+    let overlay = TableOverlay::with_writes(&rtx, Writes::default());
+    let numbers = overlay.table(NUMBERS);
+    numbers.insert(3, 100).unwrap();
+    numbers.insert(4, 200).unwrap();
+    numbers.remove(0).unwrap();
+    numbers.remove(5).unwrap();
+
+    assert_eq!(numbers.get(3).unwrap().unwrap().value(), 100);
+    assert_eq!(numbers.get(4).unwrap().unwrap().value(), 200);
+    assert!(numbers.get(0).unwrap().is_none());
+    assert_eq!(numbers.len().unwrap(), 10);
+
+    fn keys(
+      range: impl Iterator<Item = Result<(StateValue<u64>, StateValue<u64>), redb::Error>>,
+    ) -> Vec<u64> {
+      range.map(|result| result.unwrap().0.value()).collect()
+    }
+    assert_eq!(
+      keys(numbers.iter().unwrap()),
+      vec![2, 3, 4, 6, 8, 10, 12, 14, 16, 18]
+    );
+    assert_eq!(keys(numbers.range(..5).unwrap().rev()), vec![4, 3, 2]);
+
+    let sets = overlay.multimap_table(SETS);
+    sets.insert(1, &id(7)).unwrap();
+    sets.remove_all(2).unwrap();
+    sets.insert(20, &id(1)).unwrap();
+
+    let values = |key: u64| {
+      sets
+        .range(key..=key)
+        .unwrap()
+        .into_iter()
+        .flat_map(|(_, values)| values)
+        .map(|value| *value.value())
+        .collect::<Vec<[u8; 36]>>()
+    };
+    assert_eq!(values(1), vec![id(1), id(7)]);
+    assert!(values(2).is_empty());
+    assert_eq!(
+      sets
+        .range(1..=20)
+        .unwrap()
+        .iter()
+        .map(|(key, _)| key.value())
+        .collect::<Vec<u64>>(),
+      vec![1, 3, 4, 5, 6, 7, 8, 9, 20]
+    );
+
+    assert_eq!(rtx.open_table(NUMBERS).unwrap().len().unwrap(), 10);
+    assert!(rtx.open_table(NUMBERS).unwrap().get(3).unwrap().is_none());
+
+    let overlay = TableOverlay::with_writes(&rtx, overlay.into_writes());
+    assert_eq!(overlay.table(NUMBERS).get(3).unwrap().unwrap().value(), 100);
+    assert_eq!(overlay.table(NUMBERS).len().unwrap(), 10);
+    assert!(overlay
+      .multimap_table(SETS)
+      .range(2..=2)
+      .unwrap()
+      .is_empty());
+  }
+}
@@ -1,80 +1,54 @@
 use {
-  super::{
-    brc20::redb::{DataStore as BRC20StateRW, DataStoreReader as BRC20StateReader},
-    brc20s::redb::{DataStore as BRC20SStateRW, DataStoreReader as BRC20SStateReader},
-    ord::redb::{OrdDbReadWriter as OrdStateRW, OrdDbReader as OrdStateReader},
-    StateRWriter, StateReader,
-  },
-  redb::{ReadTransaction, WriteTransaction},
+  super::{brc20, brc20s, table::TableWriter, StateCache, StateRWriter},
+  crate::index::state_hash,
+  anyhow::anyhow,
 };
 
-/// StateReadOnly, based on `redb`, is an implementation of the StateRWriter trait.
-pub struct StateReadOnly<'db, 'a> {
-  ord: OrdStateReader<'db, 'a>,
-  brc20: BRC20StateReader<'db, 'a>,
-  brc20s: BRC20SStateReader<'db, 'a>,
-}
-
-impl<'db, 'a> StateReadOnly<'db, 'a> {
-  #[allow(dead_code)]
-  pub fn new(rtx: &'a ReadTransaction<'db>) -> Self {
-    Self {
-      ord: OrdStateReader::new(rtx),
-      brc20: BRC20StateReader::new(rtx),
-      brc20s: BRC20SStateReader::new(rtx),
-    }
-  }
-}
-
-impl<'db, 'a> StateReader for StateReadOnly<'db, 'a> {
-  type OrdReader = OrdStateReader<'db, 'a>;
-  type BRC20Reader = BRC20StateReader<'db, 'a>;
-  type BRC20SReader = BRC20SStateReader<'db, 'a>;
-
-  fn ord(&self) -> &Self::OrdReader {
-    &self.ord
-  }
-
-  fn brc20(&self) -> &Self::BRC20Reader {
-    &self.brc20
-  }
-
-  fn brc20s(&self) -> &Self::BRC20SReader {
-    &self.brc20s
-  }
-}
-
 /// StateReadWrite, based on `redb`, is an implementation of the StateRWriter trait.
-pub struct StateReadWrite<'db, 'a> {
-  ord: OrdStateRW<'db, 'a>,
-  brc20: BRC20StateRW<'db, 'a>,
-  brc20s: BRC20SStateRW<'db, 'a>,
+pub struct StateReadWrite<'a, T> {
+  tables: &'a T,
+  cache: &'a StateCache,
 }
 
-impl<'db, 'a> StateReadWrite<'db, 'a> {
-  pub fn new(wtx: &'a WriteTransaction<'db>) -> Self {
-    Self {
-      ord: OrdStateRW::new(wtx),
-      brc20: BRC20StateRW::new(wtx),
-      brc20s: BRC20SStateRW::new(wtx),
+impl<'a, T: TableWriter> StateReadWrite<'a, T> {
+  pub fn new(tables: &'a T, cache: &'a StateCache) -> Self {
+    Self { tables, cache }
+  }
+
+  /// Writes the dirty entries of the cache back to the tables.
+  pub(crate) fn flush(&self) -> crate::Result {
+    brc20::store(self)
+      .flush()
+      .map_err(|e| anyhow!("failed to flush cached BRC20 state! error: {e}"))?;
+    brc20s::store(self)
+      .flush()
+      .map_err(|e| anyhow!("failed to flush cached BRC20S state! error: {e}"))?;
+    Ok(())
+  }
+
+  /// Records the changes the block made to the cached state for its state hash.
+  pub(crate) fn record_changes(&self) -> crate::Result {
+    for change in self
+      .cache
+      .brc20
+      .take_changes()
+      .into_iter()
+      .chain(self.cache.brc20s.take_changes())
+    {
+      state_hash::record_change(self.tables, change)?;
     }
+    Ok(())
   }
 }
 
-impl<'db, 'a> StateRWriter for StateReadWrite<'db, 'a> {
-  type OrdRWriter = OrdStateRW<'db, 'a>;
-  type BRC20RWriter = BRC20StateRW<'db, 'a>;
-  type BRC20SRWriter = BRC20SStateRW<'db, 'a>;
-
-  fn ord(&self) -> &Self::OrdRWriter {
-    &self.ord
-  }
+impl<'a, T: TableWriter> StateRWriter for StateReadWrite<'a, T> {
+  type Tables = T;
 
-  fn brc20(&self) -> &Self::BRC20RWriter {
-    &self.brc20
+  fn tables(&self) -> &Self::Tables {
+    self.tables
   }
 
-  fn brc20s(&self) -> &Self::BRC20SRWriter {
-    &self.brc20s
+  fn cache(&self) -> &StateCache {
+    self.cache
   }
 }
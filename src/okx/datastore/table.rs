@@ -0,0 +1,736 @@
+use {
+  crate::index::undo::{JournaledMultimapTable, JournaledTable, Layout, TableVisitor, UndoLog},
+  redb::{
+    MultimapTableDefinition, ReadTransaction, ReadableMultimapTable, ReadableTable, RedbKey,
+    RedbValue, TableDefinition, WriteTransaction,
+  },
+  std::{
+    borrow::Borrow,
+    collections::VecDeque,
+    marker::PhantomData,
+    ops::{Bound, RangeBounds},
+  },
+};
+
+/// The first batch of entries a `Range` fetches, doubled with every batch up to `MAX_BATCH`.
+const MIN_BATCH: usize = 16;
+const MAX_BATCH: usize = 1024;
+
+/// Defines the tables of a store, along with `$list`, their names and layouts.
+macro_rules! define_state_tables {
+  ($list:ident { $($name:ident: $key:ty => $value:ty = $table:literal,)* }) => {
+    $(
+      pub(crate) const $name: redb::TableDefinition<$key, $value> =
+        redb::TableDefinition::new($table);
+    )*
+
+    pub(crate) const $list: &[$crate::okx::datastore::table::TableSpec] = &[
+      $($crate::okx::datastore::table::TableSpec::new::<$key, $value>($table),)*
+    ];
+  };
+}
+
+pub(crate) use define_state_tables;
+
+/// A table the protocols keep their state in, by name and layout, so that it can be opened without
+/// knowing its key and value types.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct TableSpec {
+  pub(crate) name: &'static str,
+  pub(crate) layout: Layout,
+}
+
+impl TableSpec {
+  pub(crate) const fn new<K: RedbKey + 'static, V: RedbValue + 'static>(name: &'static str) -> Self
+  where
+    (K, V): JournaledTable,
+  {
+    Self {
+      name,
+      layout: <(K, V)>::LAYOUT,
+    }
+  }
+
+  /// Creates the table in `wtx`, unless it exists already.
+  pub(crate) fn create(&self, wtx: &WriteTransaction) -> Result<(), redb::Error> {
+    struct Create<'a, 'db>(&'a WriteTransaction<'db>);
+
+    impl TableVisitor for Create<'_, '_> {
+      type Output = Result<(), redb::Error>;
+
+      fn visit<K: RedbKey + 'static, V: RedbValue + 'static>(
+        self,
+        definition: TableDefinition<K, V>,
+      ) -> Self::Output {
+        self.0.open_table(definition)?;
+        Ok(())
+      }
+    }
+
+    self.layout.visit(self.name, Create(wtx))
+  }
+}
+
+/// TableReader reads the tables the protocols keep their state in.
+///
+/// Keys and values cross the trait as the bytes of their table types, so that the tables can be
+/// served by a redb transaction or by anything layered over one. Use `table` for typed access.
+pub(crate) trait TableReader {
+  /// Returns the value of `key` in `definition`.
+  fn get_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<Option<Vec<u8>>, redb::Error>;
+
+  /// Returns up to `limit` entries of `definition` between `start` and `end`, in key order, or in
+  /// reverse key order from `end` when `reverse` is set.
+  fn range_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    start: Bound<&[u8]>,
+    end: Bound<&[u8]>,
+    reverse: bool,
+    limit: usize,
+  ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, redb::Error>;
+
+  /// Returns the number of entries of `definition`.
+  fn len<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+  ) -> Result<u64, redb::Error>;
+
+  /// Returns the values of `key` in the multimap `definition`, in value order.
+  fn get_all_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<Vec<Vec<u8>>, redb::Error>;
+
+  /// Returns the keys of the multimap `definition` between `start` and `end` along with their
+  /// values, in key order.
+  fn range_all_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    start: Bound<&[u8]>,
+    end: Bound<&[u8]>,
+  ) -> Result<Vec<(Vec<u8>, Vec<Vec<u8>>)>, redb::Error>;
+
+  /// Opens `definition` with typed keys and values.
+  fn table<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<'static, K, V>,
+  ) -> StateTable<'_, Self, K, V> {
+    StateTable {
+      tables: self,
+      definition,
+    }
+  }
+
+  /// Opens the multimap `definition` with typed keys and values.
+  fn multimap_table<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<'static, K, V>,
+  ) -> StateMultimapTable<'_, Self, K, V> {
+    StateMultimapTable {
+      tables: self,
+      definition,
+    }
+  }
+}
+
+/// TableWriter writes the tables the protocols keep their state in, journaling every write so that
+/// it is unwound with its block on reorg.
+pub(crate) trait TableWriter: TableReader {
+  /// Sets the value of `key` in `definition`.
+  fn insert_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: &[u8],
+    value: &[u8],
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledTable;
+
+  /// Removes `key` from `definition`.
+  fn remove_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledTable;
+
+  /// Adds `value` to the values of `key` in the multimap `definition`.
+  fn insert_multimap_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: &[u8],
+    value: &[u8],
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledMultimapTable;
+
+  /// Removes every value of `key` from the multimap `definition`.
+  fn remove_all_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledMultimapTable;
+}
+
+fn get_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+  table: &impl ReadableTable<K, V>,
+  key: &[u8],
+) -> Result<Option<Vec<u8>>, redb::Error> {
+  Ok(
+    table
+      .get(K::from_bytes(key))?
+      .map(|value| V::as_bytes(&value.value()).as_ref().to_vec()),
+  )
+}
+
+fn range_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+  table: &impl ReadableTable<K, V>,
+  start: Bound<&[u8]>,
+  end: Bound<&[u8]>,
+  reverse: bool,
+  limit: usize,
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>, redb::Error> {
+  let range = table.range::<K::SelfType<'_>>((typed::<K>(start), typed::<K>(end)))?;
+  if reverse {
+    collect_bytes(range.rev(), limit)
+  } else {
+    collect_bytes(range, limit)
+  }
+}
+
+fn typed<K: RedbKey>(bound: Bound<&[u8]>) -> Bound<K::SelfType<'_>> {
+  match bound {
+    Bound::Included(key) => Bound::Included(K::from_bytes(key)),
+    Bound::Excluded(key) => Bound::Excluded(K::from_bytes(key)),
+    Bound::Unbounded => Bound::Unbounded,
+  }
+}
+
+fn collect_bytes<'a, K: RedbKey + 'static, V: RedbValue + 'static>(
+  entries: impl Iterator<
+    Item = Result<(redb::AccessGuard<'a, K>, redb::AccessGuard<'a, V>), redb::StorageError>,
+  >,
+  limit: usize,
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>, redb::Error> {
+  entries
+    .take(limit)
+    .map(|result| {
+      let (key, value) = result?;
+      let (key, value) = (key.value(), value.value());
+      let entry = (
+        K::as_bytes(&key).as_ref().to_vec(),
+        V::as_bytes(&value).as_ref().to_vec(),
+      );
+      Ok(entry)
+    })
+    .collect()
+}
+
+fn get_all_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+  table: &impl ReadableMultimapTable<K, V>,
+  key: &[u8],
+) -> Result<Vec<Vec<u8>>, redb::Error> {
+  let mut values = Vec::new();
+  for value in table.get(K::from_bytes(key))? {
+    let value = value?;
+    let value = value.value();
+    values.push(V::as_bytes(&value).as_ref().to_vec());
+  }
+  Ok(values)
+}
+
+fn range_all_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+  table: &impl ReadableMultimapTable<K, V>,
+  start: Bound<&[u8]>,
+  end: Bound<&[u8]>,
+) -> Result<Vec<(Vec<u8>, Vec<Vec<u8>>)>, redb::Error> {
+  let mut entries = Vec::new();
+  for result in table.range::<K::SelfType<'_>>((typed::<K>(start), typed::<K>(end)))? {
+    let (key, values) = result?;
+    let key = key.value();
+    let mut bytes = Vec::new();
+    for value in values {
+      let value = value?;
+      let value = value.value();
+      bytes.push(V::as_bytes(&value).as_ref().to_vec());
+    }
+    entries.push((K::as_bytes(&key).as_ref().to_vec(), bytes));
+  }
+  Ok(entries)
+}
+
+impl<'db> TableReader for ReadTransaction<'db> {
+  fn get_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<Option<Vec<u8>>, redb::Error> {
+    get_bytes(&self.open_table(definition)?, key)
+  }
+
+  fn range_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    start: Bound<&[u8]>,
+    end: Bound<&[u8]>,
+    reverse: bool,
+    limit: usize,
+  ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, redb::Error> {
+    range_bytes(&self.open_table(definition)?, start, end, reverse, limit)
+  }
+
+  fn len<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+  ) -> Result<u64, redb::Error> {
+    Ok(self.open_table(definition)?.len()?)
+  }
+
+  fn get_all_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<Vec<Vec<u8>>, redb::Error> {
+    get_all_bytes(&self.open_multimap_table(definition)?, key)
+  }
+
+  fn range_all_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    start: Bound<&[u8]>,
+    end: Bound<&[u8]>,
+  ) -> Result<Vec<(Vec<u8>, Vec<Vec<u8>>)>, redb::Error> {
+    range_all_bytes(&self.open_multimap_table(definition)?, start, end)
+  }
+}
+
+impl<'db> TableReader for WriteTransaction<'db> {
+  fn get_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<Option<Vec<u8>>, redb::Error> {
+    get_bytes(&self.open_table(definition)?, key)
+  }
+
+  fn range_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    start: Bound<&[u8]>,
+    end: Bound<&[u8]>,
+    reverse: bool,
+    limit: usize,
+  ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, redb::Error> {
+    range_bytes(&self.open_table(definition)?, start, end, reverse, limit)
+  }
+
+  fn len<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+  ) -> Result<u64, redb::Error> {
+    Ok(self.open_table(definition)?.len()?)
+  }
+
+  fn get_all_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<Vec<Vec<u8>>, redb::Error> {
+    get_all_bytes(&self.open_multimap_table(definition)?, key)
+  }
+
+  fn range_all_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    start: Bound<&[u8]>,
+    end: Bound<&[u8]>,
+  ) -> Result<Vec<(Vec<u8>, Vec<Vec<u8>>)>, redb::Error> {
+    range_all_bytes(&self.open_multimap_table(definition)?, start, end)
+  }
+}
+
+impl<'db> TableWriter for WriteTransaction<'db> {
+  fn insert_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: &[u8],
+    value: &[u8],
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledTable,
+  {
+    UndoLog::new(self).insert(definition, K::from_bytes(key), V::from_bytes(value))
+  }
+
+  fn remove_bytes<K: RedbKey + 'static, V: RedbValue + 'static>(
+    &self,
+    definition: TableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledTable,
+  {
+    UndoLog::new(self).remove(definition, K::from_bytes(key))
+  }
+
+  fn insert_multimap_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: &[u8],
+    value: &[u8],
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledMultimapTable,
+  {
+    UndoLog::new(self).insert_multimap(definition, K::from_bytes(key), V::from_bytes(value))
+  }
+
+  fn remove_all_bytes<K: RedbKey + 'static, V: RedbKey + 'static>(
+    &self,
+    definition: MultimapTableDefinition<K, V>,
+    key: &[u8],
+  ) -> Result<(), redb::Error>
+  where
+    (K, V): JournaledMultimapTable,
+  {
+    UndoLog::new(self).remove_all(definition, K::from_bytes(key))
+  }
+}
+
+/// A key or value read through a `StateTable`.
+pub(crate) struct StateValue<T> {
+  bytes: Vec<u8>,
+  value: PhantomData<T>,
+}
+
+impl<T: RedbValue> StateValue<T> {
+  fn new(bytes: Vec<u8>) -> Self {
+    Self {
+      bytes,
+      value: PhantomData,
+    }
+  }
+
+  pub(crate) fn value(&self) -> T::SelfType<'_> {
+    T::from_bytes(&self.bytes)
+  }
+}
+
+/// StateTable is a table of a `TableReader`, with typed keys and values.
+pub(crate) struct StateTable<'a, T: ?Sized, K: RedbKey + 'static, V: RedbValue + 'static> {
+  tables: &'a T,
+  definition: TableDefinition<'static, K, V>,
+}
+
+impl<'a, T: TableReader + ?Sized, K: RedbKey + 'static, V: RedbValue + 'static>
+  StateTable<'a, T, K, V>
+{
+  pub(crate) fn get<'k>(
+    &self,
+    key: impl Borrow<K::SelfType<'k>>,
+  ) -> Result<Option<StateValue<V>>, redb::Error> {
+    Ok(
+      self
+        .tables
+        .get_bytes(self.definition, K::as_bytes(key.borrow()).as_ref())?
+        .map(StateValue::new),
+    )
+  }
+
+  /// Returns the entries within `range`, fetched in batches as they are iterated.
+  pub(crate) fn range<'k, KR: Borrow<K::SelfType<'k>>>(
+    &self,
+    range: impl RangeBounds<KR>,
+  ) -> Result<Range<'a, T, K, V>, redb::Error> {
+    Ok(Range {
+      tables: self.tables,
+      definition: self.definition,
+      start: owned::<K, KR>(range.start_bound()),
+      end: owned::<K, KR>(range.end_bound()),
+      front: VecDeque::new(),
+      back: VecDeque::new(),
+      batch: MIN_BATCH,
+      exhausted: false,
+    })
+  }
+
+  pub(crate) fn len(&self) -> Result<u64, redb::Error> {
+    self.tables.len(self.definition)
+  }
+
+  pub(crate) fn iter(&self) -> Result<Range<'a, T, K, V>, redb::Error> {
+    self.range::<K::SelfType<'_>>(..)
+  }
+}
+
+impl<'a, T: TableWriter + ?Sized, K: RedbKey + 'static, V: RedbValue + 'static>
+  StateTable<'a, T, K, V>
+where
+  (K, V): JournaledTable,
+{
+  pub(crate) fn insert<'k, 'v>(
+    &self,
+    key: impl Borrow<K::SelfType<'k>>,
+    value: impl Borrow<V::SelfType<'v>>,
+  ) -> Result<(), redb::Error> {
+    self.tables.insert_bytes(
+      self.definition,
+      K::as_bytes(key.borrow()).as_ref(),
+      V::as_bytes(value.borrow()).as_ref(),
+    )
+  }
+
+  pub(crate) fn remove<'k>(&self, key: impl Borrow<K::SelfType<'k>>) -> Result<(), redb::Error> {
+    self
+      .tables
+      .remove_bytes(self.definition, K::as_bytes(key.borrow()).as_ref())
+  }
+}
+
+/// StateMultimapTable is a multimap table of a `TableReader`, with typed keys and values.
+pub(crate) struct StateMultimapTable<'a, T: ?Sized, K: RedbKey + 'static, V: RedbKey + 'static> {
+  tables: &'a T,
+  definition: MultimapTableDefinition<'static, K, V>,
+}
+
+impl<'a, T: TableReader + ?Sized, K: RedbKey + 'static, V: RedbKey + 'static>
+  StateMultimapTable<'a, T, K, V>
+{
+  /// Returns the keys within `range` along with their values.
+  pub(crate) fn range<'k, KR: Borrow<K::SelfType<'k>>>(
+    &self,
+    range: impl RangeBounds<KR>,
+  ) -> Result<Vec<(StateValue<K>, Vec<StateValue<V>>)>, redb::Error> {
+    Ok(
+      self
+        .tables
+        .range_all_bytes(
+          self.definition,
+          borrowed(&owned::<K, KR>(range.start_bound())),
+          borrowed(&owned::<K, KR>(range.end_bound())),
+        )?
+        .into_iter()
+        .map(|(key, values)| {
+          (
+            StateValue::new(key),
+            values.into_iter().map(StateValue::new).collect(),
+          )
+        })
+        .collect(),
+    )
+  }
+}
+
+impl<'a, T: TableWriter + ?Sized, K: RedbKey + 'static, V: RedbKey + 'static>
+  StateMultimapTable<'a, T, K, V>
+where
+  (K, V): JournaledMultimapTable,
+{
+  pub(crate) fn insert<'k, 'v>(
+    &self,
+    key: impl Borrow<K::SelfType<'k>>,
+    value: impl Borrow<V::SelfType<'v>>,
+  ) -> Result<(), redb::Error> {
+    self.tables.insert_multimap_bytes(
+      self.definition,
+      K::as_bytes(key.borrow()).as_ref(),
+      V::as_bytes(value.borrow()).as_ref(),
+    )
+  }
+
+  pub(crate) fn remove_all<'k>(
+    &self,
+    key: impl Borrow<K::SelfType<'k>>,
+  ) -> Result<(), redb::Error> {
+    self
+      .tables
+      .remove_all_bytes(self.definition, K::as_bytes(key.borrow()).as_ref())
+  }
+}
+
+fn owned<'k, K: RedbKey + 'k, KR: Borrow<K::SelfType<'k>>>(bound: Bound<&KR>) -> Bound<Vec<u8>> {
+  match bound {
+    Bound::Included(key) => Bound::Included(K::as_bytes(key.borrow()).as_ref().to_vec()),
+    Bound::Excluded(key) => Bound::Excluded(K::as_bytes(key.borrow()).as_ref().to_vec()),
+    Bound::Unbounded => Bound::Unbounded,
+  }
+}
+
+fn borrowed(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+  match bound {
+    Bound::Included(key) => Bound::Included(key),
+    Bound::Excluded(key) => Bound::Excluded(key),
+    Bound::Unbounded => Bound::Unbounded,
+  }
+}
+
+/// Range iterates the entries of a `StateTable` from either end, fetching them in growing batches.
+pub(crate) struct Range<'a, T: ?Sized, K: RedbKey + 'static, V: RedbValue + 'static> {
+  tables: &'a T,
+  definition: TableDefinition<'static, K, V>,
+  // the bounds of the entries not fetched yet.
+  start: Bound<Vec<u8>>,
+  end: Bound<Vec<u8>>,
+  front: VecDeque<(Vec<u8>, Vec<u8>)>,
+  back: VecDeque<(Vec<u8>, Vec<u8>)>,
+  batch: usize,
+  exhausted: bool,
+}
+
+type Entry<K, V> = Result<(StateValue<K>, StateValue<V>), redb::Error>;
+
+impl<'a, T: TableReader + ?Sized, K: RedbKey + 'static, V: RedbValue + 'static> Range<'a, T, K, V> {
+  fn fetch(&mut self, reverse: bool) -> Result<(), redb::Error> {
+    let entries = self.tables.range_bytes(
+      self.definition,
+      borrowed(&self.start),
+      borrowed(&self.end),
+      reverse,
+      self.batch,
+    )?;
+
+    self.exhausted = entries.len() < self.batch;
+    self.batch = (self.batch * 2).min(MAX_BATCH);
+
+    if let Some((key, _)) = entries.last() {
+      if reverse {
+        self.end = Bound::Excluded(key.clone());
+      } else {
+        self.start = Bound::Excluded(key.clone());
+      }
+    }
+
+    if reverse {
+      for entry in entries {
+        self.back.push_front(entry);
+      }
+    } else {
+      self.front.extend(entries);
+    }
+
+    Ok(())
+  }
+
+  fn next_from(&mut self, reverse: bool) -> Option<Entry<K, V>> {
+    let buffered = if reverse { &self.back } else { &self.front };
+    if buffered.is_empty() && !self.exhausted {
+      if let Err(err) = self.fetch(reverse) {
+        self.exhausted = true;
+        self.front.clear();
+        self.back.clear();
+        return Some(Err(err));
+      }
+    }
+
+    let entry = if reverse {
+      self.back.pop_back().or_else(|| self.front.pop_back())
+    } else {
+      self.front.pop_front().or_else(|| self.back.pop_front())
+    };
+
+    entry.map(|(key, value)| Ok((StateValue::new(key), StateValue::new(value))))
+  }
+}
+
+impl<'a, T: TableReader + ?Sized, K: RedbKey + 'static, V: RedbValue + 'static> Iterator
+  for Range<'a, T, K, V>
+{
+  type Item = Entry<K, V>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.next_from(false)
+  }
+}
+
+impl<'a, T: TableReader + ?Sized, K: RedbKey + 'static, V: RedbValue + 'static> DoubleEndedIterator
+  for Range<'a, T, K, V>
+{
+  fn next_back(&mut self) -> Option<Self::Item> {
+    self.next_from(true)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, redb::Database, tempfile::NamedTempFile};
+
+  const NUMBERS: TableDefinition<u64, u64> = TableDefinition::new("NUMBERS");
+
+  #[test]
+  fn range_iterates_from_both_ends_across_batches() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+
+    for n in 0..100 {
+      wtx.table(NUMBERS).insert(n, n * 2).unwrap();
+    }
+
+    let values = |range: Range<WriteTransaction, u64, u64>| {
+      range
+        .map(|result| result.unwrap().1.value())
+        .collect::<Vec<u64>>()
+    };
+
+    let table = wtx.table(NUMBERS);
+    assert_eq!(values(table.iter().unwrap()).len(), 100);
+    assert_eq!(values(table.range(10..13).unwrap()), vec![20, 22, 24]);
+    assert_eq!(
+      values(table.range(..=50).unwrap()).last().copied(),
+      Some(100)
+    );
+    assert_eq!(
+      table
+        .range(90..)
+        .unwrap()
+        .rev()
+        .map(|result| result.unwrap().0.value())
+        .collect::<Vec<u64>>(),
+      (90..100).rev().collect::<Vec<u64>>()
+    );
+
+    let mut range = table.iter().unwrap();
+    let mut front = Vec::new();
+    let mut back = Vec::new();
+    loop {
+      match (range.next(), range.next_back()) {
+        (Some(a), Some(b)) => {
+          front.push(a.unwrap().0.value());
+          back.push(b.unwrap().0.value());
+        }
+        (Some(a), None) => front.push(a.unwrap().0.value()),
+        (None, _) => break,
+      }
+    }
+    back.reverse();
+    front.extend(back);
+    assert_eq!(front, (0..100).collect::<Vec<u64>>());
+  }
+
+  #[test]
+  fn writes_are_journaled() {
+    let dbfile = NamedTempFile::new().unwrap();
+    let db = Database::create(dbfile.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    let undo = UndoLog::new(&wtx);
+
+    undo.begin_block(1, Some(10)).unwrap();
+    wtx.table(NUMBERS).insert(1, 1).unwrap();
+    undo.begin_block(2, Some(10)).unwrap();
+    wtx.table(NUMBERS).insert(1, 2).unwrap();
+    wtx.table(NUMBERS).insert(2, 2).unwrap();
+    undo.rollback(2).unwrap();
+
+    assert_eq!(wtx.table(NUMBERS).get(1).unwrap().unwrap().value(), 1);
+    assert!(wtx.table(NUMBERS).get(2).unwrap().is_none());
+  }
+}
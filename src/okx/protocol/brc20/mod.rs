@@ -13,13 +13,19 @@ mod msg_resolver;
 mod num;
 mod operation;
 mod params;
+mod protocol;
+mod version;
 
 use self::error::Error;
 pub(crate) use self::{
   error::JSONError,
   msg_executor::{execute, ExecutionMessage},
   num::Num,
-  operation::{deserialize_brc20_operation, Deploy, Mint, Operation, Transfer},
+  operation::{
+    deserialize_brc20_operation, serialize_brc20_operation, Deploy, Mint, Operation, Transfer,
+  },
+  protocol::BRC20Protocol,
+  version::{default_versions, Config},
 };
 
 #[derive(Debug, Clone, PartialEq)]
@@ -31,4 +37,6 @@ pub struct Message {
   pub new_satpoint: Option<SatPoint>,
   pub op: Operation,
   pub sat_in_outputs: bool,
+  // The parent of a new inscription, when the transaction spends it.
+  pub parent: Option<InscriptionId>,
 }
@@ -1,7 +1,4 @@
-use super::{
-  params::{BIGDECIMAL_TEN, MAXIMUM_SUPPLY, MAX_DECIMAL_WIDTH},
-  *,
-};
+use super::{params::BIGDECIMAL_TEN, *};
 
 use crate::okx::datastore::brc20 as brc20_store;
 use crate::okx::datastore::ord as ord_store;
@@ -10,7 +7,7 @@ use crate::{
   okx::{
     datastore::brc20::{
       BRC20Error, Balance, DeployEvent, Event, InscripbeTransferEvent, MintEvent, Receipt, Tick,
-      TokenInfo, TransferEvent, TransferInfo, TransferableLog,
+      TokenInfo, TokenStats, TransferEvent, TransferInfo, TransferableLog,
     },
     protocol::{
       brc20::{Message, Mint, Operation},
@@ -34,6 +31,7 @@ pub struct ExecutionMessage {
   pub(self) from: ScriptKey,
   pub(self) to: Option<ScriptKey>,
   pub(self) op: Operation,
+  pub(self) parent: Option<InscriptionId>,
 }
 
 impl ExecutionMessage {
@@ -61,12 +59,14 @@ impl ExecutionMessage {
         None
       },
       op: msg.op.clone(),
+      parent: msg.parent,
     })
   }
 }
 
 pub fn execute<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataStoreReadWrite>(
   context: BlockContext,
+  config: &Config,
   ord_store: &'a O,
   brc20_store: &'a N,
   msg: &ExecutionMessage,
@@ -74,12 +74,19 @@ pub fn execute<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataStoreRea
   log::debug!("BRC20 execute message: {:?}", msg);
   let event = match &msg.op {
     Operation::Deploy(deploy) => {
-      process_deploy(context, ord_store, brc20_store, msg, deploy.clone())
+      process_deploy(context, config, ord_store, brc20_store, msg, deploy.clone())
     }
-    Operation::Mint(mint) => process_mint(context, ord_store, brc20_store, msg, mint.clone()),
-    Operation::InscribeTransfer(transfer) => {
-      process_inscribe_transfer(context, ord_store, brc20_store, msg, transfer.clone())
+    Operation::Mint(mint) => {
+      process_mint(context, config, ord_store, brc20_store, msg, mint.clone())
     }
+    Operation::InscribeTransfer(transfer) => process_inscribe_transfer(
+      context,
+      config,
+      ord_store,
+      brc20_store,
+      msg,
+      transfer.clone(),
+    ),
     Operation::Transfer(_) => process_transfer(context, ord_store, brc20_store, msg),
   };
 
@@ -109,6 +116,7 @@ pub fn execute<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataStoreRea
 
 fn process_deploy<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataStoreReadWrite>(
   context: BlockContext,
+  config: &Config,
   _ord_store: &'a O,
   brc20_store: &'a N,
   msg: &ExecutionMessage,
@@ -117,7 +125,7 @@ fn process_deploy<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataStore
   // ignore inscribe inscription to coinbase.
   let to_script_key = msg.to.clone().ok_or(BRC20Error::InscribeToCoinbase)?;
 
-  let tick = deploy.tick.parse::<Tick>()?;
+  let tick = parse_tick(&deploy.tick, config)?;
 
   if let Some(stored_tick_info) = brc20_store
     .get_token_info(&tick)
@@ -128,19 +136,32 @@ fn process_deploy<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataStore
     )));
   }
 
-  let dec = Num::from_str(&deploy.decimals.map_or(MAX_DECIMAL_WIDTH.to_string(), |v| v))?
-    .checked_to_u8()?;
-  if dec > MAX_DECIMAL_WIDTH {
+  if tick.self_mint() && deploy.self_mint.as_deref() != Some("true") {
+    return Err(Error::BRC20Error(BRC20Error::SelfMintRequired(
+      tick.to_string(),
+    )));
+  }
+
+  let dec = Num::from_str(
+    &deploy
+      .decimals
+      .map_or(config.max_decimal_width.to_string(), |v| v),
+  )?
+  .checked_to_u8()?;
+  if dec > config.max_decimal_width {
     return Err(Error::BRC20Error(BRC20Error::DecimalsTooLarge(dec)));
   }
   let base = BIGDECIMAL_TEN.checked_powu(u64::from(dec))?;
 
-  let supply = Num::from_str(&deploy.max_supply)?;
+  let max_supply = Num::from(config.max_supply);
+  let mut supply = Num::from_str(&deploy.max_supply)?;
 
-  if supply.sign() == Sign::NoSign
-    || supply > MAXIMUM_SUPPLY.to_owned()
-    || supply.scale() > i64::from(dec)
-  {
+  // a self minted tick deployed with a zero supply has the maximum supply.
+  if tick.self_mint() && supply.sign() == Sign::NoSign {
+    supply = max_supply.clone();
+  }
+
+  if supply.sign() == Sign::NoSign || supply > max_supply || supply.scale() > i64::from(dec) {
     return Err(Error::BRC20Error(BRC20Error::InvalidSupply(
       supply.to_string(),
     )));
@@ -148,10 +169,7 @@ fn process_deploy<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataStore
 
   let limit = Num::from_str(&deploy.mint_limit.map_or(deploy.max_supply, |v| v))?;
 
-  if limit.sign() == Sign::NoSign
-    || limit > MAXIMUM_SUPPLY.to_owned()
-    || limit.scale() > i64::from(dec)
-  {
+  if limit.sign() == Sign::NoSign || limit > max_supply || limit.scale() > i64::from(dec) {
     return Err(Error::BRC20Error(BRC20Error::MintLimitOutOfRange(
       tick.to_lowercase().to_string(),
       limit.to_string(),
@@ -177,6 +195,9 @@ fn process_deploy<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataStore
   brc20_store
     .insert_token_info(&tick, &new_info)
     .map_err(|e| Error::LedgerError(e))?;
+  brc20_store
+    .update_token_stats(&tick, &TokenStats::new(&tick, context.blockheight))
+    .map_err(|e| Error::LedgerError(e))?;
 
   Ok(Event::Deploy(DeployEvent {
     supply,
@@ -188,6 +209,7 @@ fn process_deploy<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataStore
 
 fn process_mint<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataStoreReadWrite>(
   context: BlockContext,
+  config: &Config,
   _ord_store: &'a O,
   brc20_store: &'a N,
   msg: &ExecutionMessage,
@@ -196,13 +218,19 @@ fn process_mint<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataStoreRe
   // ignore inscribe inscription to coinbase.
   let to_script_key = msg.to.clone().ok_or(BRC20Error::InscribeToCoinbase)?;
 
-  let tick = mint.tick.parse::<Tick>()?;
+  let tick = parse_tick(&mint.tick, config)?;
 
   let token_info = brc20_store
     .get_token_info(&tick)
     .map_err(|e| Error::LedgerError(e))?
     .ok_or(BRC20Error::TickNotFound(tick.to_string()))?;
 
+  if tick.self_mint() && msg.parent != Some(token_info.inscription_id) {
+    return Err(Error::BRC20Error(BRC20Error::SelfMintPermissionDenied(
+      tick.to_string(),
+    )));
+  }
+
   let base = BIGDECIMAL_TEN.checked_powu(u64::from(token_info.decimal))?;
 
   let mut amt = Num::from_str(&mint.amount)?;
@@ -256,9 +284,7 @@ fn process_mint<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataStoreRe
     .checked_to_u128()?;
 
   // store to database.
-  brc20_store
-    .update_token_balance(&to_script_key, balance)
-    .map_err(|e| Error::LedgerError(e))?;
+  update_balance(context, brc20_store, &to_script_key, balance)?;
 
   // update token minted.
   let minted = minted.checked_add(&amt)?.checked_to_u128()?;
@@ -266,6 +292,10 @@ fn process_mint<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataStoreRe
     .update_mint_token_info(&tick, minted, context.blockheight)
     .map_err(|e| Error::LedgerError(e))?;
 
+  let mut stats = get_stats(context, brc20_store, &tick)?;
+  stats.mint_count += 1;
+  update_stats(context, brc20_store, &tick, stats)?;
+
   Ok(Event::Mint(MintEvent {
     tick: token_info.tick,
     amount: amt.checked_to_u128()?,
@@ -278,7 +308,8 @@ fn process_inscribe_transfer<
   O: ord_store::DataStoreReadOnly,
   N: brc20_store::DataStoreReadWrite,
 >(
-  _context: BlockContext,
+  context: BlockContext,
+  config: &Config,
   _ord_store: &'a O,
   brc20_store: &'a N,
   msg: &ExecutionMessage,
@@ -287,7 +318,7 @@ fn process_inscribe_transfer<
   // ignore inscribe inscription to coinbase.
   let to_script_key = msg.to.clone().ok_or(BRC20Error::InscribeToCoinbase)?;
 
-  let tick = transfer.tick.parse::<Tick>()?;
+  let tick = parse_tick(&transfer.tick, config)?;
 
   let token_info = brc20_store
     .get_token_info(&tick)
@@ -329,9 +360,7 @@ fn process_inscribe_transfer<
   balance.transferable_balance = transferable.checked_add(&amt)?.checked_to_u128()?;
 
   let amt = amt.checked_to_u128()?;
-  brc20_store
-    .update_token_balance(&to_script_key, balance)
-    .map_err(|e| Error::LedgerError(e))?;
+  update_balance(context, brc20_store, &to_script_key, balance)?;
 
   let inscription = TransferableLog {
     inscription_id: msg.inscription_id,
@@ -354,6 +383,12 @@ fn process_inscribe_transfer<
     )
     .map_err(|e| Error::LedgerError(e))?;
 
+  // the stats aren't consensus state, so they saturate instead of failing the operation.
+  let mut stats = get_stats(context, brc20_store, &tick)?;
+  stats.inscribe_transfer_count += 1;
+  stats.transferable_supply = stats.transferable_supply.saturating_add(amt);
+  update_stats(context, brc20_store, &tick, stats)?;
+
   Ok(Event::InscribeTransfer(InscripbeTransferEvent {
     tick: inscription.tick,
     amount: amt,
@@ -361,7 +396,7 @@ fn process_inscribe_transfer<
 }
 
 fn process_transfer<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataStoreReadWrite>(
-  _context: BlockContext,
+  context: BlockContext,
   _ord_store: &'a O,
   brc20_store: &'a N,
   msg: &ExecutionMessage,
@@ -401,9 +436,7 @@ fn process_transfer<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataSto
   from_balance.overall_balance = from_overall;
   from_balance.transferable_balance = from_transferable;
 
-  brc20_store
-    .update_token_balance(&msg.from, from_balance)
-    .map_err(|e| Error::LedgerError(e))?;
+  update_balance(context, brc20_store, &msg.from, from_balance)?;
 
   // redirect receiver to sender if transfer to conibase.
   let mut out_msg = None;
@@ -425,9 +458,7 @@ fn process_transfer<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataSto
   let to_overall = Into::<Num>::into(to_balance.overall_balance);
   to_balance.overall_balance = to_overall.checked_add(&amt)?.checked_to_u128()?;
 
-  brc20_store
-    .update_token_balance(&to_script_key, to_balance)
-    .map_err(|e| Error::LedgerError(e))?;
+  update_balance(context, brc20_store, &to_script_key, to_balance)?;
 
   brc20_store
     .remove_transferable(&msg.from, &tick, msg.inscription_id)
@@ -437,9 +468,67 @@ fn process_transfer<'a, O: ord_store::DataStoreReadOnly, N: brc20_store::DataSto
     .remove_inscribe_transfer_inscription(msg.inscription_id)
     .map_err(|e| Error::LedgerError(e))?;
 
+  let amount = transferable.amount;
+
+  // the stats aren't consensus state, so they saturate instead of failing the operation.
+  let mut stats = get_stats(context, brc20_store, &tick)?;
+  stats.transfer_count += 1;
+  stats.transferable_supply = stats.transferable_supply.saturating_sub(amount);
+  update_stats(context, brc20_store, &tick, stats)?;
+
   Ok(Event::Transfer(TransferEvent {
     msg: out_msg,
     tick: token_info.tick,
-    amount: amt.checked_to_u128()?,
+    amount,
   }))
 }
+
+/// Parses `tick`, which may only be a self minted 5 byte tick once those are allowed.
+fn parse_tick(tick: &str, config: &Config) -> Result<Tick, BRC20Error> {
+  let parsed = tick.parse::<Tick>()?;
+  if parsed.self_mint() && !config.allow_self_mint {
+    return Err(BRC20Error::InvalidTickLen(tick.to_string()));
+  }
+  Ok(parsed)
+}
+
+// get the stats of the ticker, which are created when it is deployed.
+fn get_stats<N: brc20_store::DataStoreReadWrite>(
+  context: BlockContext,
+  brc20_store: &N,
+  tick: &Tick,
+) -> Result<TokenStats, Error<N>> {
+  Ok(
+    brc20_store
+      .get_token_stats(tick)
+      .map_err(|e| Error::LedgerError(e))?
+      .unwrap_or_else(|| TokenStats::new(tick, context.blockheight)),
+  )
+}
+
+fn update_stats<N: brc20_store::DataStoreReadWrite>(
+  context: BlockContext,
+  brc20_store: &N,
+  tick: &Tick,
+  mut stats: TokenStats,
+) -> Result<(), Error<N>> {
+  stats.latest_activity_number = context.blockheight;
+  brc20_store
+    .update_token_stats(tick, &stats)
+    .map_err(|e| Error::LedgerError(e))
+}
+
+// store the new balance and record it in the balance history of the current block.
+fn update_balance<N: brc20_store::DataStoreReadWrite>(
+  context: BlockContext,
+  brc20_store: &N,
+  script_key: &ScriptKey,
+  balance: Balance,
+) -> Result<(), Error<N>> {
+  brc20_store
+    .insert_balance_history(script_key, context.blockheight, &balance)
+    .map_err(|e| Error::LedgerError(e))?;
+  brc20_store
+    .update_token_balance(script_key, balance)
+    .map_err(|e| Error::LedgerError(e))
+}
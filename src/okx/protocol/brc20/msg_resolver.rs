@@ -11,11 +11,15 @@ use crate::{
   Result,
 };
 use anyhow::anyhow;
+use std::collections::HashSet;
 
 impl Message {
+  /// Resolves the message of `op`. `tx_inscriptions` are the inscriptions the transaction of `op`
+  /// moves or creates, which are the only valid parents of its new inscriptions.
   pub(crate) fn resolve<N: DataStoreReadOnly>(
     brc20_store: &N,
     new_inscriptions: &[Inscription],
+    tx_inscriptions: &HashSet<InscriptionId>,
     op: &InscriptionOp,
   ) -> Result<Option<Message>> {
     log::debug!("BRC20 resolving the message from {:?}", op);
@@ -24,6 +28,7 @@ impl Message {
       .map(|satpoint| satpoint.outpoint.txid == op.txid)
       .unwrap_or(false);
 
+    let mut parent = None;
     let brc20_operation = match op.action {
       // New inscription is not `cursed` or `unbound`.
       Action::New {
@@ -31,12 +36,13 @@ impl Message {
         unbound: false,
         inscription: _,
       } if sat_in_outputs => {
-        match deserialize_brc20_operation(
-          new_inscriptions
-            .get(usize::try_from(op.inscription_id.index).unwrap())
-            .unwrap(),
-          &op.action,
-        ) {
+        let inscription = new_inscriptions
+          .get(usize::try_from(op.inscription_id.index).unwrap())
+          .unwrap();
+        parent = inscription
+          .parent()
+          .filter(|parent| tx_inscriptions.contains(parent));
+        match deserialize_brc20_operation(inscription, &op.action) {
           Ok(brc20_operation) => brc20_operation,
           _ => return Ok(None),
         }
@@ -68,6 +74,7 @@ impl Message {
       new_satpoint: op.new_satpoint,
       op: brc20_operation,
       sat_in_outputs,
+      parent,
     }))
   }
 }
@@ -95,7 +102,7 @@ mod tests {
       action: Action::New {
         cursed: false,
         unbound: false,
-        inscription: inscriptions.get(0).unwrap().clone(),
+        inscription: inscriptions.first().unwrap().clone(),
       },
       inscription_number: Some(1),
       inscription_id: InscriptionId { txid, index: 0 },
@@ -154,7 +161,10 @@ mod tests {
     let (inscriptions, op) = create_inscribe_operation(
       r#"{ "p": "brc-20s","op": "deploy", "tick": "ordi", "max": "1000", "lim": "10" }"#,
     );
-    assert_matches!(Message::resolve(&brc20_store, &inscriptions, &op), Ok(None));
+    assert_matches!(
+      Message::resolve(&brc20_store, &inscriptions, &HashSet::new(), &op),
+      Ok(None)
+    );
   }
 
   #[test]
@@ -171,34 +181,37 @@ mod tests {
       action: Action::New {
         cursed: true,
         unbound: false,
-        inscription: inscriptions.get(0).unwrap().clone(),
+        inscription: inscriptions.first().unwrap().clone(),
       },
       ..op
     };
-    assert_matches!(Message::resolve(&brc20_store, &inscriptions, &op), Ok(None));
+    assert_matches!(
+      Message::resolve(&brc20_store, &inscriptions, &HashSet::new(), &op),
+      Ok(None)
+    );
 
     let op2 = InscriptionOp {
       action: Action::New {
         cursed: false,
         unbound: true,
-        inscription: inscriptions.get(0).unwrap().clone(),
+        inscription: inscriptions.first().unwrap().clone(),
       },
       ..op
     };
     assert_matches!(
-      Message::resolve(&brc20_store, &inscriptions, &op2),
+      Message::resolve(&brc20_store, &inscriptions, &HashSet::new(), &op2),
       Ok(None)
     );
     let op3 = InscriptionOp {
       action: Action::New {
         cursed: true,
         unbound: true,
-        inscription: inscriptions.get(0).unwrap().clone(),
+        inscription: inscriptions.first().unwrap().clone(),
       },
       ..op
     };
     assert_matches!(
-      Message::resolve(&brc20_store, &inscriptions, &op3),
+      Message::resolve(&brc20_store, &inscriptions, &HashSet::new(), &op3),
       Ok(None)
     );
   }
@@ -223,11 +236,13 @@ mod tests {
         max_supply: "1000".to_string(),
         mint_limit: Some("10".to_string()),
         decimals: None,
+        self_mint: None,
       }),
       sat_in_outputs: true,
+      parent: None,
     };
     assert_matches!(
-      Message::resolve(&brc20_store, &inscriptions, &op),
+      Message::resolve(&brc20_store, &inscriptions, &HashSet::new(), &op),
       Ok(Some(_result_msg))
     );
   }
@@ -241,7 +256,10 @@ mod tests {
 
     // inscribe transfer not found
     let op = create_transfer_operation();
-    assert_matches!(Message::resolve(&brc20_store, &[], &op), Ok(None));
+    assert_matches!(
+      Message::resolve(&brc20_store, &[], &HashSet::new(), &op),
+      Ok(None)
+    );
 
     // non-first transfer operations.
     let op1 = InscriptionOp {
@@ -255,7 +273,10 @@ mod tests {
       },
       ..op
     };
-    assert_matches!(Message::resolve(&brc20_store, &[], &op1), Ok(None));
+    assert_matches!(
+      Message::resolve(&brc20_store, &[], &HashSet::new(), &op1),
+      Ok(None)
+    );
   }
 
   #[test]
@@ -287,8 +308,12 @@ mod tests {
         amount: "100".to_string(),
       }),
       sat_in_outputs: true,
+      parent: None,
     };
 
-    assert_matches!(Message::resolve(&brc20_store, &[], &op), Ok(Some(_msg)));
+    assert_matches!(
+      Message::resolve(&brc20_store, &[], &HashSet::new(), &op),
+      Ok(Some(_msg))
+    );
   }
 }
@@ -1,4 +1,7 @@
-use serde::{Deserialize, Serialize};
+use {
+  serde::{Deserialize, Deserializer, Serialize},
+  serde_json::Value,
+};
 
 #[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 pub struct Deploy {
@@ -6,10 +9,27 @@ pub struct Deploy {
   pub tick: String,
   #[serde(rename = "max")]
   pub max_supply: String,
-  #[serde(rename = "lim")]
+  #[serde(rename = "lim", skip_serializing_if = "Option::is_none")]
   pub mint_limit: Option<String>,
-  #[serde(rename = "dec")]
+  #[serde(rename = "dec", skip_serializing_if = "Option::is_none")]
   pub decimals: Option<String>,
+  #[serde(
+    rename = "self_mint",
+    default,
+    deserialize_with = "deserialize_self_mint",
+    skip_serializing_if = "Option::is_none"
+  )]
+  pub self_mint: Option<String>,
+}
+
+// deploys were valid with any `self_mint` before self minted ticks, so only a string counts.
+fn deserialize_self_mint<'de, D: Deserializer<'de>>(
+  deserializer: D,
+) -> Result<Option<String>, D::Error> {
+  Ok(match Value::deserialize(deserializer)? {
+    Value::String(self_mint) => Some(self_mint),
+    _ => None,
+  })
 }
 
 #[cfg(test)]
@@ -24,6 +44,7 @@ mod tests {
       max_supply: "12000".to_string(),
       mint_limit: Some("12".to_string()),
       decimals: Some("11".to_string()),
+      self_mint: None,
     };
 
     assert_eq!(
@@ -50,6 +71,7 @@ mod tests {
         max_supply: "12000".to_string(),
         mint_limit: Some("12".to_string()),
         decimals: Some("11".to_string()),
+        self_mint: None,
       })
     );
   }
@@ -74,6 +96,7 @@ mod tests {
         max_supply: "100".to_string(),
         mint_limit: None,
         decimals: Some("10".to_string()),
+        self_mint: None,
       })
     );
 
@@ -86,6 +109,7 @@ mod tests {
         max_supply: "100".to_string(),
         mint_limit: Some("10".to_string()),
         decimals: None,
+        self_mint: None,
       })
     );
 
@@ -97,6 +121,7 @@ mod tests {
         max_supply: "100".to_string(),
         mint_limit: None,
         decimals: None,
+        self_mint: None,
       })
     );
   }
@@ -111,6 +136,39 @@ mod tests {
         max_supply: "300".to_string(),
         mint_limit: Some("20".to_string()),
         decimals: Some("17".to_string()),
+        self_mint: None,
+      })
+    );
+  }
+
+  #[test]
+  fn test_self_mint() {
+    assert_eq!(
+      deserialize_brc20(
+        r#"{"p":"brc-20","op":"deploy","tick":"smoll","max":"0","self_mint":"true"}"#
+      )
+      .unwrap(),
+      RawOperation::Deploy(Deploy {
+        tick: "smoll".to_string(),
+        max_supply: "0".to_string(),
+        mint_limit: None,
+        decimals: None,
+        self_mint: Some("true".to_string()),
+      })
+    );
+
+    // a deploy was valid with any `self_mint` before self minted ticks.
+    assert_eq!(
+      deserialize_brc20(
+        r#"{"p":"brc-20","op":"deploy","tick":"smol","max":"100","self_mint":true}"#
+      )
+      .unwrap(),
+      RawOperation::Deploy(Deploy {
+        tick: "smol".to_string(),
+        max_supply: "100".to_string(),
+        mint_limit: None,
+        decimals: None,
+        self_mint: None,
       })
     );
   }
@@ -80,6 +80,22 @@ pub(crate) fn deserialize_brc20_operation(
   }
 }
 
+/// Returns the inscription content of a deploy, mint or inscribe transfer operation.
+pub(crate) fn serialize_brc20_operation(operation: &Operation) -> String {
+  let raw_operation = match operation.clone() {
+    Operation::Deploy(deploy) => RawOperation::Deploy(deploy),
+    Operation::Mint(mint) => RawOperation::Mint(mint),
+    Operation::InscribeTransfer(transfer) | Operation::Transfer(transfer) => {
+      RawOperation::Transfer(transfer)
+    }
+  };
+
+  let fields = serde_json::to_string(&raw_operation).unwrap();
+
+  // the tagged fields start with the operation, put the protocol in front of it.
+  format!(r#"{{"p":"{PROTOCOL_LITERAL}",{}"#, &fields[1..])
+}
+
 fn deserialize_brc20(s: &str) -> Result<RawOperation, JSONError> {
   let value: Value = serde_json::from_str(s).map_err(|_| JSONError::InvalidJson)?;
   if value.get("p") != Some(&json!(PROTOCOL_LITERAL)) {
@@ -94,6 +110,46 @@ mod tests {
   use super::*;
   use crate::okx::datastore::ord::Action;
 
+  #[test]
+  fn test_serialize_brc20_operation() {
+    let operation = Operation::Deploy(Deploy {
+      tick: "ordi".to_string(),
+      max_supply: "21000000".to_string(),
+      mint_limit: Some("1000".to_string()),
+      decimals: None,
+      self_mint: None,
+    });
+
+    let content = serialize_brc20_operation(&operation);
+    assert_eq!(
+      content,
+      r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"21000000","lim":"1000"}"#
+    );
+    assert_eq!(
+      deserialize_brc20_operation(
+        &Inscription::new(
+          Some("text/plain;charset=utf-8".as_bytes().to_vec()),
+          Some(content.into_bytes()),
+        ),
+        &Action::New {
+          cursed: false,
+          unbound: false,
+          inscription: Inscription::default(),
+        },
+      )
+      .unwrap(),
+      operation
+    );
+
+    assert_eq!(
+      serialize_brc20_operation(&Operation::InscribeTransfer(Transfer {
+        tick: "ordi".to_string(),
+        amount: "100".to_string(),
+      })),
+      r#"{"p":"brc-20","op":"transfer","tick":"ordi","amt":"100"}"#
+    );
+  }
+
   #[test]
   fn test_deploy_deserialize() {
     let max_supply = "21000000".to_string();
@@ -115,7 +171,8 @@ mod tests {
         tick: "ordi".to_string(),
         max_supply,
         mint_limit: Some(mint_limit),
-        decimals: None
+        decimals: None,
+        self_mint: None,
       })
     );
   }
@@ -220,6 +277,7 @@ mod tests {
         max_supply: "12000".to_string(),
         mint_limit: Some("12".to_string()),
         decimals: Some("11".to_string()),
+        self_mint: None,
       }),
     );
     let inscription = crate::inscription(
@@ -0,0 +1,121 @@
+use {
+  super::*,
+  crate::{
+    index::Notification,
+    metrics,
+    okx::{
+      datastore::{
+        brc20::{self as brc20_store, DataStoreReadOnly},
+        ord::{self as ord_store, InscriptionOp},
+        table::TableSpec,
+        StateRWriter,
+      },
+      protocol::{version::Versions, BlockContext, Protocol, ProtocolConfig, ResolveContext},
+    },
+    subcommand::server,
+    Result,
+  },
+  anyhow::anyhow,
+  axum::Router,
+};
+
+/// BRC20 tokens, indexed from `first_brc20_height`.
+pub(crate) struct BRC20Protocol {
+  activation_height: Option<u64>,
+  versions: Versions<Config>,
+}
+
+impl BRC20Protocol {
+  pub(crate) fn new(config: &ProtocolConfig) -> Self {
+    Self {
+      activation_height: config.first_brc20_height,
+      versions: config.brc20_versions.clone(),
+    }
+  }
+}
+
+impl Protocol for BRC20Protocol {
+  type Message = Message;
+  type Receipt = brc20_store::Receipt;
+
+  fn name(&self) -> &'static str {
+    "brc20"
+  }
+
+  fn activation_height(&self) -> Option<u64> {
+    self.activation_height
+  }
+
+  fn tables(&self) -> &'static [TableSpec] {
+    brc20_store::redb::TABLES
+  }
+
+  fn consensus_tables(&self) -> &'static [&'static str] {
+    &["BRC20_BALANCES", "BRC20_TOKEN", "BRC20_EVENTS"]
+  }
+
+  fn router(&self) -> Option<Router> {
+    Some(server::brc20::router())
+  }
+
+  fn resolve<RW: StateRWriter>(
+    &self,
+    context: &mut ResolveContext<RW>,
+    operation: &InscriptionOp,
+  ) -> Result<Option<Message>> {
+    let msg = Message::resolve(
+      &brc20_store::store(context.state),
+      context.new_inscriptions,
+      &context.inscriptions,
+      operation,
+    )?;
+    if let Some(msg) = &msg {
+      log::debug!(
+        "BRC20 resolved the message from {:?}, msg {:?}",
+        operation,
+        msg
+      );
+    }
+    Ok(msg)
+  }
+
+  fn execute<RW: StateRWriter>(
+    &self,
+    context: BlockContext,
+    state: &RW,
+    msg: &Message,
+  ) -> Result<Option<brc20_store::Receipt>> {
+    execute(
+      context,
+      &self.versions.at(context.blockheight),
+      &ord_store::store(state),
+      &brc20_store::store(state),
+      &ExecutionMessage::from_message(&ord_store::store(state), msg, context.network)?,
+    )
+  }
+
+  fn receipt_result(&self, receipt: &brc20_store::Receipt) -> String {
+    metrics::result_label(&receipt.result)
+  }
+
+  fn notifications<RW: StateRWriter>(
+    &self,
+    context: BlockContext,
+    state: &RW,
+    txid: &Txid,
+    notifications: &mut Vec<Notification>,
+  ) -> Result {
+    notifications.extend(
+      brc20_store::store(state)
+        .get_transaction_receipts(txid)
+        .map_err(|e| anyhow!("failed to get brc20 receipts of {txid}! error: {e}"))?
+        .into_iter()
+        .map(|receipt| Notification::BRC20Receipt {
+          height: context.blockheight,
+          txid: *txid,
+          receipt,
+        }),
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,73 @@
+use {
+  super::params::MAX_DECIMAL_WIDTH,
+  crate::{
+    okx::protocol::version::{Rules, Version},
+    Chain, Result,
+  },
+  anyhow::ensure,
+  serde::Deserialize,
+};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+  pub max_decimal_width: u8,
+  pub max_supply: u64,
+  /// Whether 5 byte ticks, which only their deploy inscription can mint, are indexed.
+  #[serde(default)]
+  pub allow_self_mint: bool,
+}
+
+impl Rules for Config {
+  // amounts are scaled by `10^max_decimal_width` into a u128, which only fits up to 18 decimals
+  // for a supply of `u64::MAX`.
+  fn check(&self) -> Result {
+    ensure!(
+      self.max_decimal_width <= MAX_DECIMAL_WIDTH,
+      "max_decimal_width {} exceeds {MAX_DECIMAL_WIDTH}",
+      self.max_decimal_width
+    );
+    Ok(())
+  }
+}
+
+// start at the first BRC20 block
+pub const fn genesis() -> Config {
+  Config {
+    max_decimal_width: MAX_DECIMAL_WIDTH,
+    max_supply: u64::MAX,
+    allow_self_mint: false,
+  }
+}
+
+// start at block 837090
+pub const fn self_mint() -> Config {
+  Config {
+    allow_self_mint: true,
+    ..genesis()
+  }
+}
+
+/// The built-in rule versions of `chain`, used unless the config file sets others.
+pub(crate) fn default_versions(chain: Chain) -> Vec<Version<Config>> {
+  match chain {
+    Chain::Mainnet => vec![
+      Version {
+        height: 0,
+        rules: genesis(),
+      },
+      Version {
+        height: 837090,
+        rules: self_mint(),
+      },
+    ],
+    Chain::Regtest => vec![Version {
+      height: 0,
+      rules: self_mint(),
+    }],
+    Chain::Testnet | Chain::Signet => vec![Version {
+      height: 0,
+      rules: genesis(),
+    }],
+  }
+}
@@ -9,19 +9,26 @@ pub mod msg_resolver;
 pub mod num;
 pub mod operation;
 pub mod params;
+mod protocol;
 mod util;
 #[cfg(test)]
 #[macro_use]
 mod test;
 mod version;
 
-pub(crate) use self::operation::deserialize_brc20s_operation;
 pub use self::{
   error::{BRC20SError, Error},
   msg_executor::{execute, ExecutionMessage},
   num::Num,
-  operation::{Deploy, Mint, Operation, PassiveUnStake, RawOperation, Stake, Transfer, UnStake},
-  version::get_config_by_network,
+  operation::{Deploy, Mint, Operation, PassiveUnStake, Stake, Transfer, UnStake},
+  version::Config,
+};
+pub(crate) use self::{
+  msg_executor::stakeable_balance,
+  msg_resolver::commit_input_satpoint,
+  operation::{deserialize_brc20s_operation, serialize_brc20s_operation},
+  protocol::BRC20SProtocol,
+  version::default_versions,
 };
 #[derive(Debug, Clone)]
 pub struct Message {
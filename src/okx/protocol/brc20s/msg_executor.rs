@@ -489,21 +489,8 @@ fn process_stake<'a, M: brc20::DataStoreReadWrite, N: brc20s::DataStoreReadWrite
     )));
   }
 
-  let staked_total =
-    Num::from(user_stakeinfo.total_only).checked_add(&Num::from(user_stakeinfo.max_share))?;
-  if stake_balance.lt(&staked_total) {
-    return Err(Error::BRC20SError(BRC20SError::InternalError(
-      "got serious error stake_balance < user staked total".to_string(),
-    )));
-  }
   let has_staked = Num::from(userinfo.staked);
-  let can_stake_balance = if pool.only {
-    stake_balance.checked_sub(&staked_total)?
-  } else {
-    stake_balance
-      .checked_sub(&Num::from(user_stakeinfo.total_only))?
-      .checked_sub(&has_staked)?
-  };
+  let can_stake_balance = stakeable_balance(&pool, &stake_balance, &user_stakeinfo, &has_staked)?;
   if can_stake_balance.lt(&amount) {
     return Err(Error::BRC20SError(BRC20SError::InsufficientBalance(
       amount.truncate_to_str().unwrap(),
@@ -568,6 +555,31 @@ fn process_stake<'a, M: brc20::DataStoreReadWrite, N: brc20s::DataStoreReadWrite
   }))
 }
 
+/// Returns how much of `stake_balance` can still be deposited into `pool` by a user who staked
+/// `has_staked` in it. A balance deposited into an `only` pool can't back any other deposit,
+/// while the pools that are not `only` share the rest of the balance.
+pub(crate) fn stakeable_balance(
+  pool: &PoolInfo,
+  stake_balance: &Num,
+  user_stakeinfo: &StakeInfo,
+  has_staked: &Num,
+) -> Result<Num, BRC20SError> {
+  let staked_total =
+    Num::from(user_stakeinfo.total_only).checked_add(&Num::from(user_stakeinfo.max_share))?;
+  if stake_balance.lt(&staked_total) {
+    return Err(BRC20SError::InternalError(
+      "got serious error stake_balance < user staked total".to_string(),
+    ));
+  }
+  if pool.only {
+    stake_balance.checked_sub(&staked_total)
+  } else {
+    stake_balance
+      .checked_sub(&Num::from(user_stakeinfo.total_only))?
+      .checked_sub(has_staked)
+  }
+}
+
 fn process_unstake<'a, M: brc20::DataStoreReadWrite, N: brc20s::DataStoreReadWrite>(
   context: BlockContext,
   _config: version::Config,
@@ -857,9 +869,13 @@ fn process_mint<'a, M: brc20::DataStoreReadWrite, N: brc20s::DataStoreReadWrite>
     .map_err(|e| Error::LedgerError(e))?;
 
   // update user balance
-  brc20s_store
-    .set_token_balance(&to_script_key, &tick_id, user_balance)
-    .map_err(|e| Error::LedgerError(e))?;
+  update_balance(
+    context,
+    brc20s_store,
+    &to_script_key,
+    &tick_id,
+    user_balance,
+  )?;
 
   Ok(Event::Mint(MintEvent {
     pid: pool_id,
@@ -868,7 +884,7 @@ fn process_mint<'a, M: brc20::DataStoreReadWrite, N: brc20s::DataStoreReadWrite>
 }
 
 fn process_inscribe_transfer<'a, M: brc20::DataStoreReadWrite, N: brc20s::DataStoreReadWrite>(
-  _context: BlockContext,
+  context: BlockContext,
   _config: version::Config,
   _brc20_store: &'a M,
   brc20s_store: &'a N,
@@ -931,9 +947,7 @@ fn process_inscribe_transfer<'a, M: brc20::DataStoreReadWrite, N: brc20s::DataSt
   };
 
   //update balance
-  brc20s_store
-    .set_token_balance(&to_script_key, &tick_id, balance)
-    .map_err(|e| Error::LedgerError(e))?;
+  update_balance(context, brc20s_store, &to_script_key, &tick_id, balance)?;
 
   brc20s_store
     .set_transferable_assets(
@@ -962,7 +976,7 @@ fn process_inscribe_transfer<'a, M: brc20::DataStoreReadWrite, N: brc20s::DataSt
 }
 
 fn process_transfer<'a, M: brc20::DataStoreReadWrite, N: brc20s::DataStoreReadWrite>(
-  _context: BlockContext,
+  context: BlockContext,
   _config: version::Config,
   _brc20_store: &'a M,
   brc20s_store: &'a N,
@@ -1012,9 +1026,13 @@ fn process_transfer<'a, M: brc20::DataStoreReadWrite, N: brc20s::DataStoreReadWr
   from_balance.overall_balance = from_overall;
   from_balance.transferable_balance = from_transferable;
 
-  brc20s_store
-    .set_token_balance(&from_script_key, &transferable.tick_id, from_balance)
-    .map_err(|e| Error::LedgerError(e))?;
+  update_balance(
+    context,
+    brc20s_store,
+    &from_script_key,
+    &transferable.tick_id,
+    from_balance,
+  )?;
   // redirect receiver to sender if transfer to conibase.
   // let to_script_key = if let None = to_script_key.clone() {
   //   from_script_key.clone()
@@ -1031,9 +1049,13 @@ fn process_transfer<'a, M: brc20::DataStoreReadWrite, N: brc20s::DataStoreReadWr
   let to_overall = Into::<Num>::into(to_balance.overall_balance);
   to_balance.overall_balance = to_overall.checked_add(&amt)?.checked_to_u128()?;
 
-  brc20s_store
-    .set_token_balance(&to_script_key, &transferable.tick_id, to_balance)
-    .map_err(|e| Error::LedgerError(e))?;
+  update_balance(
+    context,
+    brc20s_store,
+    &to_script_key,
+    &transferable.tick_id,
+    to_balance,
+  )?;
 
   brc20s_store
     .remove_transferable(&from_script_key, &transferable.tick_id, &msg.inscription_id)
@@ -1050,6 +1072,22 @@ fn process_transfer<'a, M: brc20::DataStoreReadWrite, N: brc20s::DataStoreReadWr
   }))
 }
 
+// store the new balance and record it in the balance history of the current block.
+fn update_balance<N: brc20s::DataStoreReadWrite>(
+  context: BlockContext,
+  brc20s_store: &N,
+  script_key: &ScriptKey,
+  tick_id: &TickId,
+  balance: Balance,
+) -> Result<(), Error<N>> {
+  brc20s_store
+    .set_balance_history(script_key, tick_id, context.blockheight, &balance)
+    .map_err(|e| Error::LedgerError(e))?;
+  brc20s_store
+    .set_token_balance(script_key, tick_id, balance)
+    .map_err(|e| Error::LedgerError(e))
+}
+
 #[allow(unused)]
 #[cfg(test)]
 mod tests {
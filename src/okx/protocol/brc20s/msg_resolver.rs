@@ -12,25 +12,57 @@ use {
     Index, Result,
   },
   anyhow::anyhow,
-  bitcoin::{OutPoint, TxOut},
+  bitcoin::{OutPoint, Transaction, TxOut, Txid},
   bitcoincore_rpc::Client,
   std::collections::HashMap,
 };
 
 impl Message {
+  /// Returns the commit transaction of the new inscription of `op`, unless `op` is not a BRC20S
+  /// operation or its commit input satpoint is already stored.
+  pub(crate) fn commit_transaction<O: ord::DataStoreReadOnly>(
+    ord_store: &O,
+    new_inscriptions: &[Inscription],
+    op: &InscriptionOp,
+  ) -> Result<Option<Txid>> {
+    match op.action {
+      Action::New {
+        cursed: false,
+        unbound: false,
+        inscription: _,
+      } if sat_in_outputs(op)
+        && deserialize_brc20s_operation(
+          new_inscriptions
+            .get(usize::try_from(op.inscription_id.index).unwrap())
+            .unwrap(),
+          &op.action,
+        )
+        .is_ok() => {}
+      _ => return Ok(None),
+    }
+
+    if ord_store
+      .get_commit_input_satpoint(op.old_satpoint)
+      .map_err(|e| anyhow!("failed to get commit input satpoint from state! error: {e}"))?
+      .is_some()
+    {
+      return Ok(None);
+    }
+
+    Ok(Some(op.old_satpoint.outpoint.txid))
+  }
+
   pub(crate) fn resolve<'a, O: ord::DataStoreReadOnly, M: brc20s::DataStoreReadOnly>(
-    client: &Client,
+    client: Option<&Client>,
     ord_store: &'a O,
     brc20s_store: &'a M,
     new_inscriptions: &[Inscription],
     op: &InscriptionOp,
+    transactions: &HashMap<Txid, Transaction>,
     outpoint_to_txout_cache: &mut HashMap<OutPoint, TxOut>,
   ) -> Result<Option<Self>> {
     log::debug!("BRC20S resolving the message from {:?}", op);
-    let sat_in_outputs = op
-      .new_satpoint
-      .map(|satpoint| satpoint.outpoint.txid == op.txid)
-      .unwrap_or(false);
+    let sat_in_outputs = sat_in_outputs(op);
     let brc20s_operation = match op.action {
       // New inscription is not `cursed` or `unbound`.
       Action::New {
@@ -79,6 +111,7 @@ impl Message {
           client,
           ord_store,
           op.old_satpoint,
+          transactions,
           outpoint_to_txout_cache,
         )?),
         Action::Transfer => None,
@@ -89,18 +122,78 @@ impl Message {
   }
 }
 
+fn sat_in_outputs(op: &InscriptionOp) -> bool {
+  op.new_satpoint
+    .map(|satpoint| satpoint.outpoint.txid == op.txid)
+    .unwrap_or(false)
+}
+
+// returns a fetched transaction, or asks Bitcoin Core for it.
+fn get_transaction(
+  client: Option<&Client>,
+  transactions: &HashMap<Txid, Transaction>,
+  txid: Txid,
+) -> Result<Option<Transaction>> {
+  if let Some(transaction) = transactions.get(&txid) {
+    return Ok(Some(transaction.clone()));
+  }
+
+  let client = client.ok_or(anyhow!(
+    "failed to get BRC20S message commit input satpoint! error: {txid} not found"
+  ))?;
+
+  Index::get_transaction_retries(client, txid)
+}
+
 fn get_commit_input_satpoint<O: ord::DataStoreReadOnly>(
-  client: &Client,
+  client: Option<&Client>,
   ord_store: &O,
   satpoint: SatPoint,
+  transactions: &HashMap<Txid, Transaction>,
   outpoint_to_txout_cache: &mut HashMap<OutPoint, TxOut>,
 ) -> Result<SatPoint> {
+  // the commit input satpoint is recorded the first time the message is resolved.
+  if let Some(commit_input_satpoint) = ord_store
+    .get_commit_input_satpoint(satpoint)
+    .map_err(|e| anyhow!("failed to get commit input satpoint from state! error: {e}"))?
+  {
+    return Ok(commit_input_satpoint);
+  }
+
   let commit_transaction =
-    &Index::get_transaction_retries(client, satpoint.outpoint.txid)?.ok_or(anyhow!(
+    &get_transaction(client, transactions, satpoint.outpoint.txid)?.ok_or(anyhow!(
       "failed to BRC20S message commit transaction! error: {} not found",
       satpoint.outpoint.txid
     ))?;
 
+  commit_input_satpoint(commit_transaction, satpoint, |outpoint| {
+    if let Some(tx_out) = ord_store
+      .get_outpoint_to_txout(outpoint)
+      .map_err(|e| anyhow!("failed to get tx out from state! error: {e}"))?
+    {
+      Ok(tx_out.value)
+    } else if let Some(tx_out) = get_transaction(client, transactions, outpoint.txid)?.map(|tx| {
+      tx.output
+        .get(usize::try_from(outpoint.vout).unwrap())
+        .unwrap()
+        .clone()
+    }) {
+      let value = tx_out.value;
+      outpoint_to_txout_cache.insert(outpoint, tx_out);
+      Ok(value)
+    } else {
+      Err(anyhow!("failed to get tx out! error: {outpoint} not found"))
+    }
+  })
+}
+
+/// Maps `satpoint`, on an output of `commit_transaction`, to the satpoint of the same sat on the
+/// input it spends. `input_value` returns the value of a spent output.
+pub(crate) fn commit_input_satpoint(
+  commit_transaction: &Transaction,
+  satpoint: SatPoint,
+  mut input_value: impl FnMut(OutPoint) -> Result<u64>,
+) -> Result<SatPoint> {
   // get satoshi offset
   let mut offset = 0;
   for (vout, output) in commit_transaction.output.iter().enumerate() {
@@ -112,35 +205,15 @@ fn get_commit_input_satpoint<O: ord::DataStoreReadOnly>(
     break;
   }
 
-  let mut input_value = 0;
+  let mut total_value = 0;
   for input in &commit_transaction.input {
-    let value = if let Some(tx_out) = ord_store
-      .get_outpoint_to_txout(input.previous_output)
-      .map_err(|e| anyhow!("failed to get tx out from state! error: {e}"))?
-    {
-      tx_out.value
-    } else if let Some(tx_out) = Index::get_transaction_retries(client, input.previous_output.txid)?
-      .map(|tx| {
-        tx.output
-          .get(usize::try_from(input.previous_output.vout).unwrap())
-          .unwrap()
-          .clone()
-      })
-    {
-      outpoint_to_txout_cache.insert(input.previous_output, tx_out.clone());
-      tx_out.value
-    } else {
-      return Err(anyhow!(
-        "failed to get tx out! error: {} not found",
-        input.previous_output
-      ));
-    };
+    let value = input_value(input.previous_output)?;
 
-    input_value += value;
-    if input_value >= offset {
+    total_value += value;
+    if total_value >= offset {
       return Ok(SatPoint {
         outpoint: input.previous_output,
-        offset: value - input_value + offset,
+        offset: value - total_value + offset,
       });
     }
   }
@@ -151,7 +224,7 @@ mod tests {
   use super::*;
   use crate::okx::datastore::{
     brc20s::{redb::DataStore, DataStoreReadWrite, Tick, TickId, TransferInfo},
-    ord::OrdDbReadWriter,
+    ord::redb::DataStore as OrdDataStore,
   };
   use bitcoin::OutPoint;
   use bitcoincore_rpc::{Auth, Client};
@@ -174,7 +247,7 @@ mod tests {
       action: Action::New {
         cursed: false,
         unbound: false,
-        inscription: inscriptions.get(0).unwrap().clone(),
+        inscription: inscriptions.first().unwrap().clone(),
       },
       inscription_number: Some(1),
       inscription_id: InscriptionId { txid, index: 0 },
@@ -229,7 +302,7 @@ mod tests {
     let db_file = NamedTempFile::new().unwrap();
     let db = Database::create(db_file.path()).unwrap();
     let wtx = db.begin_write().unwrap();
-    let ord_store = OrdDbReadWriter::new(&wtx);
+    let ord_store = OrdDataStore::new(&wtx);
     let brc20s_store = DataStore::new(&wtx);
 
     let mut outpoint_to_txout_cache = HashMap::new();
@@ -239,11 +312,12 @@ mod tests {
     );
     assert_matches!(
       Message::resolve(
-        &client,
+        Some(&client),
         &ord_store,
         &brc20s_store,
         &inscriptions,
         &op,
+        &HashMap::new(),
         &mut outpoint_to_txout_cache,
       ),
       Ok(None)
@@ -256,7 +330,7 @@ mod tests {
     let db_file = NamedTempFile::new().unwrap();
     let db = Database::create(db_file.path()).unwrap();
     let wtx = db.begin_write().unwrap();
-    let ord_store = OrdDbReadWriter::new(&wtx);
+    let ord_store = OrdDataStore::new(&wtx);
     let brc20s_store = DataStore::new(&wtx);
 
     let mut outpoint_to_txout_cache = HashMap::new();
@@ -268,17 +342,18 @@ mod tests {
       action: Action::New {
         cursed: true,
         unbound: false,
-        inscription: inscriptions.get(0).unwrap().clone(),
+        inscription: inscriptions.first().unwrap().clone(),
       },
       ..op
     };
     assert_matches!(
       Message::resolve(
-        &client,
+        Some(&client),
         &ord_store,
         &brc20s_store,
         &inscriptions,
         &op,
+        &HashMap::new(),
         &mut outpoint_to_txout_cache,
       ),
       Ok(None)
@@ -288,17 +363,18 @@ mod tests {
       action: Action::New {
         cursed: false,
         unbound: true,
-        inscription: inscriptions.get(0).unwrap().clone(),
+        inscription: inscriptions.first().unwrap().clone(),
       },
       ..op.clone()
     };
     assert_matches!(
       Message::resolve(
-        &client,
+        Some(&client),
         &ord_store,
         &brc20s_store,
         &inscriptions,
         &op2,
+        &HashMap::new(),
         &mut outpoint_to_txout_cache,
       ),
       Ok(None)
@@ -307,17 +383,18 @@ mod tests {
       action: Action::New {
         cursed: true,
         unbound: true,
-        inscription: inscriptions.get(0).unwrap().clone(),
+        inscription: inscriptions.first().unwrap().clone(),
       },
       ..op.clone()
     };
     assert_matches!(
       Message::resolve(
-        &client,
+        Some(&client),
         &ord_store,
         &brc20s_store,
         &inscriptions,
         &op3,
+        &HashMap::new(),
         &mut outpoint_to_txout_cache,
       ),
       Ok(None)
@@ -330,7 +407,7 @@ mod tests {
     let db_file = NamedTempFile::new().unwrap();
     let db = Database::create(db_file.path()).unwrap();
     let wtx = db.begin_write().unwrap();
-    let ord_store = OrdDbReadWriter::new(&wtx);
+    let ord_store = OrdDataStore::new(&wtx);
     let brc20s_store = DataStore::new(&wtx);
 
     let mut outpoint_to_txout_cache = HashMap::new();
@@ -338,11 +415,12 @@ mod tests {
     let op = create_transfer_operation();
     assert_matches!(
       Message::resolve(
-        &client,
+        Some(&client),
         &ord_store,
         &brc20s_store,
         &[],
         &op,
+        &HashMap::new(),
         &mut outpoint_to_txout_cache,
       ),
       Ok(None)
@@ -362,11 +440,12 @@ mod tests {
     };
     assert_matches!(
       Message::resolve(
-        &client,
+        Some(&client),
         &ord_store,
         &brc20s_store,
         &[],
         &op1,
+        &HashMap::new(),
         &mut outpoint_to_txout_cache,
       ),
       Ok(None)
@@ -379,7 +458,7 @@ mod tests {
     let db_file = NamedTempFile::new().unwrap();
     let db = Database::create(db_file.path()).unwrap();
     let wtx = db.begin_write().unwrap();
-    let ord_store = OrdDbReadWriter::new(&wtx);
+    let ord_store = OrdDataStore::new(&wtx);
     let brc20s_store = DataStore::new(&wtx);
 
     let mut outpoint_to_txout_cache = HashMap::new();
@@ -412,14 +491,89 @@ mod tests {
     };
     assert_matches!(
       Message::resolve(
-        &client,
+        Some(&client),
         &ord_store,
         &brc20s_store,
         &[],
         &op,
+        &HashMap::new(),
         &mut outpoint_to_txout_cache,
       ),
       Ok(Some(_msg))
     );
   }
+
+  #[test]
+  fn fetched_commit_transactions_resolve_without_bitcoind() {
+    let db_file = NamedTempFile::new().unwrap();
+    let db = Database::create(db_file.path()).unwrap();
+    let wtx = db.begin_write().unwrap();
+    let ord_store = OrdDataStore::new(&wtx);
+    let brc20s_store = DataStore::new(&wtx);
+
+    let input = Transaction {
+      version: 1,
+      lock_time: bitcoin::absolute::LockTime::ZERO,
+      input: Vec::new(),
+      output: vec![TxOut {
+        value: 1000,
+        script_pubkey: Default::default(),
+      }],
+    };
+    let commit = Transaction {
+      input: vec![bitcoin::TxIn {
+        previous_output: OutPoint {
+          txid: input.txid(),
+          vout: 0,
+        },
+        ..Default::default()
+      }],
+      ..input.clone()
+    };
+
+    let (inscriptions, op) = create_inscribe_operation(
+      r#"{"p":"brc20-s","op":"deploy","t":"pool","pid":"a3668daeaa#1f","stake":"btc","earn":"ordi","erate":"10","dmax":"12000000","dec":"18","total":"21000000","only":"1"}"#,
+    );
+    let op = InscriptionOp {
+      old_satpoint: SatPoint {
+        outpoint: OutPoint {
+          txid: commit.txid(),
+          vout: 0,
+        },
+        offset: 0,
+      },
+      ..op
+    };
+
+    assert_eq!(
+      Message::commit_transaction(&ord_store, &inscriptions, &op).unwrap(),
+      Some(commit.txid())
+    );
+
+    let transactions = [commit, input.clone()]
+      .into_iter()
+      .map(|tx| (tx.txid(), tx))
+      .collect();
+    let mut outpoint_to_txout_cache = HashMap::new();
+    let msg = Message::resolve(
+      None,
+      &ord_store,
+      &brc20s_store,
+      &inscriptions,
+      &op,
+      &transactions,
+      &mut outpoint_to_txout_cache,
+    )
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(
+      msg.commit_input_satpoint.map(|satpoint| satpoint.outpoint),
+      Some(OutPoint {
+        txid: input.txid(),
+        vout: 0
+      })
+    );
+    assert_eq!(outpoint_to_txout_cache.len(), 1);
+  }
 }
@@ -111,6 +111,25 @@ pub(crate) fn deserialize_brc20s_operation(
   }
 }
 
+/// Returns the inscription content of an operation that is inscribed.
+pub(crate) fn serialize_brc20s_operation(operation: &Operation) -> String {
+  let raw_operation = match operation.clone() {
+    Operation::Deploy(deploy) => RawOperation::Deploy(deploy),
+    Operation::Mint(mint) => RawOperation::Mint(mint),
+    Operation::Stake(stake) => RawOperation::Stake(stake),
+    Operation::UnStake(unstake) => RawOperation::UnStake(unstake),
+    Operation::PassiveUnStake(passive_unstake) => RawOperation::PassiveUnStake(passive_unstake),
+    Operation::InscribeTransfer(transfer) | Operation::Transfer(transfer) => {
+      RawOperation::Transfer(transfer)
+    }
+  };
+
+  let fields = serde_json::to_string(&raw_operation).unwrap();
+
+  // the tagged fields start with the operation, put the protocol in front of it.
+  format!(r#"{{"p":"{PROTOCOL_LITERAL}",{}"#, &fields[1..])
+}
+
 pub fn deserialize_brc20s(s: &str) -> Result<RawOperation, JSONError> {
   let value: Value = serde_json::from_str(s).map_err(|_| JSONError::InvalidJson)?;
   if value.get("p") != Some(&json!(PROTOCOL_LITERAL)) {
@@ -150,6 +169,24 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_serialize_brc20s_operation() {
+    let stake = Stake {
+      pool_id: "a3668daeaa#1f".to_string(),
+      amount: "10".to_string(),
+    };
+
+    let content = serialize_brc20s_operation(&Operation::Stake(stake.clone()));
+    assert_eq!(
+      content,
+      r##"{"p":"brc20-s","op":"deposit","pid":"a3668daeaa#1f","amt":"10"}"##
+    );
+    assert_eq!(
+      deserialize_brc20s(&content).unwrap(),
+      RawOperation::Stake(stake)
+    );
+  }
+
   #[test]
   fn test_stake_deserialize() {
     let json_str = r#"{
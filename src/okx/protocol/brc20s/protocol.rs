@@ -0,0 +1,243 @@
+use {
+  super::*,
+  crate::{
+    index::Notification,
+    metrics,
+    okx::{
+      datastore::{
+        balance::convert_pledged_tick_without_decimal,
+        brc20 as brc20_store,
+        brc20s::{self as brc20s_store, DataStoreReadOnly, PledgedTick},
+        ord::{self as ord_store, DataStoreReadWrite, InscriptionOp},
+        table::TableSpec,
+        StateRWriter,
+      },
+      protocol::{
+        brc20 as brc20_proto, version::Versions, BlockContext, Protocol, ProtocolConfig,
+        ResolveContext,
+      },
+    },
+    subcommand::server,
+    Inscription, Result,
+  },
+  anyhow::anyhow,
+  axum::Router,
+  std::any::Any,
+};
+
+/// BRC20S staking pools, indexed from `first_brc20s_height`.
+///
+/// Transfers of BRC20 and BRC20S tokens that are staked in a pool passively withdraw them.
+pub(crate) struct BRC20SProtocol {
+  activation_height: Option<u64>,
+  versions: Versions<Config>,
+}
+
+impl BRC20SProtocol {
+  pub(crate) fn new(config: &ProtocolConfig) -> Self {
+    Self {
+      activation_height: config.first_brc20s_height,
+      versions: config.brc20s_versions.clone(),
+    }
+  }
+
+  fn execute_message<RW: StateRWriter>(
+    &self,
+    context: BlockContext,
+    state: &RW,
+    msg: &Message,
+  ) -> Result<Option<brc20s_store::Receipt>> {
+    execute(
+      context,
+      self.versions.at(context.blockheight),
+      &brc20_store::store(state),
+      &brc20s_store::store(state),
+      &ExecutionMessage::from_message(&ord_store::store(state), msg, context.network)?,
+    )
+  }
+
+  /// Returns the operation withdrawing `amount` of the transferred `tick` from the pools the
+  /// sender staked it in.
+  fn passive_unstake<RW: StateRWriter>(
+    state: &RW,
+    tick: PledgedTick,
+    amount: u128,
+  ) -> Option<Operation> {
+    match convert_pledged_tick_without_decimal(
+      &tick,
+      amount,
+      &brc20s_store::store(state),
+      &brc20_store::store(state),
+    ) {
+      Ok(amt) => Some(Operation::PassiveUnStake(PassiveUnStake {
+        stake: tick.to_string(),
+        amount: amt.to_string(),
+      })),
+      Err(e) => {
+        log::error!("brc20s receipt failed: {e}");
+        None
+      }
+    }
+  }
+}
+
+impl Protocol for BRC20SProtocol {
+  type Message = Message;
+  type Receipt = brc20s_store::Receipt;
+
+  fn name(&self) -> &'static str {
+    "brc20s"
+  }
+
+  fn activation_height(&self) -> Option<u64> {
+    self.activation_height
+  }
+
+  fn tables(&self) -> &'static [TableSpec] {
+    brc20s_store::redb::TABLES
+  }
+
+  fn consensus_tables(&self) -> &'static [&'static str] {
+    &[
+      "BRC20S_BALANCE",
+      "BRC20S_TICKINFO",
+      "BRC20S_PID_TO_POOLINFO",
+      "BRC20S_PID_TO_USERINFO",
+      "BRC20S_TXID_TO_RECEIPTS",
+    ]
+  }
+
+  fn router(&self) -> Option<Router> {
+    Some(server::brc20s::router())
+  }
+
+  fn commit_transaction<RW: StateRWriter>(
+    &self,
+    state: &RW,
+    new_inscriptions: &[Inscription],
+    operation: &InscriptionOp,
+  ) -> Result<Option<Txid>> {
+    Message::commit_transaction(&ord_store::store(state), new_inscriptions, operation)
+  }
+
+  fn resolve<RW: StateRWriter>(
+    &self,
+    context: &mut ResolveContext<RW>,
+    operation: &InscriptionOp,
+  ) -> Result<Option<Message>> {
+    let Some(msg) = Message::resolve(
+      context.client,
+      &ord_store::store(context.state),
+      &brc20s_store::store(context.state),
+      context.new_inscriptions,
+      operation,
+      context.transactions,
+      &mut context.outpoint_to_txout_cache,
+    )?
+    else {
+      return Ok(None);
+    };
+
+    log::debug!(
+      "BRC20S resolved the message from {:?}, msg {:?}",
+      operation,
+      msg
+    );
+
+    if let Some(commit_input_satpoint) = msg.commit_input_satpoint {
+      ord_store::store(context.state)
+        .set_commit_input_satpoint(msg.old_satpoint, commit_input_satpoint)
+        .map_err(|e| anyhow!("failed to set commit input satpoint to state! error: {e}"))?;
+    }
+
+    Ok(Some(msg))
+  }
+
+  fn execute<RW: StateRWriter>(
+    &self,
+    context: BlockContext,
+    state: &RW,
+    msg: &Message,
+  ) -> Result<Option<brc20s_store::Receipt>> {
+    self.execute_message(context, state, msg)
+  }
+
+  fn receipt_result(&self, receipt: &brc20s_store::Receipt) -> String {
+    metrics::result_label(&receipt.result)
+  }
+
+  fn on_receipt<RW: StateRWriter>(
+    &self,
+    context: BlockContext,
+    state: &RW,
+    msg: &dyn Any,
+    receipt: &dyn Any,
+  ) -> Result {
+    if let (Some(msg), Some(receipt)) = (
+      msg.downcast_ref::<brc20_proto::Message>(),
+      receipt.downcast_ref::<brc20_store::Receipt>(),
+    ) {
+      if let Ok(brc20_store::Event::Transfer(transfer)) = &receipt.result {
+        if let Some(op) = Self::passive_unstake(
+          state,
+          PledgedTick::BRC20Tick(transfer.tick.clone()),
+          transfer.amount,
+        ) {
+          let passive_msg = Message {
+            txid: msg.txid,
+            inscription_id: msg.inscription_id,
+            old_satpoint: msg.old_satpoint,
+            new_satpoint: msg.new_satpoint,
+            commit_input_satpoint: None,
+            op,
+            sat_in_outputs: msg.sat_in_outputs,
+          };
+          self.execute_message(context, state, &passive_msg)?;
+        }
+      }
+    } else if let (Some(msg), Some(receipt)) = (
+      msg.downcast_ref::<Message>(),
+      receipt.downcast_ref::<brc20s_store::Receipt>(),
+    ) {
+      if let Ok(events) = &receipt.result {
+        let mut events = events.iter();
+        while let Some(brc20s_store::Event::Transfer(transfer)) = events.next() {
+          if let Some(op) = Self::passive_unstake(
+            state,
+            PledgedTick::BRC20STick(transfer.tick_id),
+            transfer.amt,
+          ) {
+            let passive_msg = Message {
+              commit_input_satpoint: None,
+              op,
+              ..msg.clone()
+            };
+            self.execute_message(context, state, &passive_msg)?;
+          }
+        }
+      }
+    }
+    Ok(())
+  }
+
+  fn notifications<RW: StateRWriter>(
+    &self,
+    context: BlockContext,
+    state: &RW,
+    txid: &Txid,
+    notifications: &mut Vec<Notification>,
+  ) -> Result {
+    notifications.extend(
+      brc20s_store::store(state)
+        .get_transaction_receipts(txid)
+        .map_err(|e| anyhow!("failed to get brc20s receipts of {txid}! error: {e}"))?
+        .into_iter()
+        .map(|receipt| Notification::BRC20SReceipt {
+          height: context.blockheight,
+          txid: *txid,
+          receipt,
+        }),
+    );
+    Ok(())
+  }
+}
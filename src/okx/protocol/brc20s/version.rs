@@ -1,7 +1,16 @@
-use crate::okx::datastore::brc20s::PledgedTick;
-use bitcoin::Network;
+use {
+  crate::{
+    okx::{
+      datastore::brc20s::PledgedTick,
+      protocol::version::{Rules, Version},
+    },
+    Chain,
+  },
+  serde::Deserialize,
+};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
   pub allow_share_pool: bool,
   pub allow_btc_staking: bool,
@@ -10,6 +19,8 @@ pub struct Config {
   pub max_staked_pool_num: u64,
 }
 
+impl Rules for Config {}
+
 // start at block 798108
 pub const fn zebra() -> Config {
   Config {
@@ -31,23 +42,45 @@ pub const fn koala() -> Config {
   }
 }
 
-pub fn get_config_by_network(network: Network, blockheight: u64) -> Config {
-  match network {
-    Network::Bitcoin => match blockheight {
-      n if n >= 800310 => koala(),
-      _ => zebra(),
-    },
-    Network::Testnet => match blockheight {
-      n if n >= 2468142 => koala(),
-      _ => zebra(),
+/// The built-in rule versions of `chain`, used unless the config file sets others.
+pub(crate) fn default_versions(chain: Chain) -> Vec<Version<Config>> {
+  let koala_height = match chain {
+    Chain::Mainnet => 800310,
+    Chain::Testnet => 2468142,
+    Chain::Signet => 153382,
+    Chain::Regtest => {
+      return vec![Version {
+        height: 0,
+        rules: koala(),
+      }]
+    }
+  };
+
+  vec![
+    Version {
+      height: 0,
+      rules: zebra(),
     },
-    Network::Signet => match blockheight {
-      n if n >= 153382 => koala(),
-      _ => zebra(),
+    Version {
+      height: koala_height,
+      rules: koala(),
     },
-    Network::Regtest => koala(),
+  ]
+}
+
+#[cfg(test)]
+pub fn get_config_by_network(network: bitcoin::Network, blockheight: u64) -> Config {
+  let chain = match network {
+    bitcoin::Network::Bitcoin => Chain::Mainnet,
+    bitcoin::Network::Testnet => Chain::Testnet,
+    bitcoin::Network::Signet => Chain::Signet,
+    bitcoin::Network::Regtest => Chain::Regtest,
     _ => panic!("not support network"),
-  }
+  };
+
+  crate::okx::protocol::version::Versions::new(default_versions(chain))
+    .unwrap()
+    .at(blockheight)
 }
 
 pub fn tick_can_staked(token: &PledgedTick, config: &Config) -> bool {
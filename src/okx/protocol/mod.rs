@@ -1,21 +1,24 @@
 pub(crate) mod brc20;
 pub(crate) mod brc20s;
-pub(crate) mod execute_manager;
-pub(crate) mod message;
 pub(crate) mod ord;
 pub(crate) mod protocol_manager;
-pub(crate) mod resolve_manager;
+pub(crate) mod registry;
 mod utils;
+pub(crate) mod version;
 
 pub use self::protocol_manager::ProtocolManager;
+pub(crate) use self::{
+  registry::{Message, Protocol, ProtocolInfo, Registry, ResolveContext},
+  version::ChainSchedule,
+};
 
 use {
   self::{
-    execute_manager::CallManager,
-    message::{Message, Receipt},
-    resolve_manager::MsgResolveManager,
+    registry::{register_protocols, DynProtocol},
+    version::Versions,
   },
-  crate::Options,
+  crate::{Options, Result},
+  anyhow::anyhow,
   bitcoin::Network,
 };
 
@@ -32,29 +35,42 @@ pub struct ProtocolConfig {
   first_brc20s_height: Option<u64>,
   enable_ord_receipts: bool,
   enable_index_bitmap: bool,
+  brc20_versions: Versions<brc20::Config>,
+  brc20s_versions: Versions<brc20s::Config>,
 }
 
 impl ProtocolConfig {
-  pub(crate) fn new_with_options(options: &Options) -> Self {
+  pub(crate) fn new_with_options(options: &Options) -> Result<Self> {
+    let chain = options.chain();
+    let schedule = options.load_config()?.protocols(chain);
+
     let mut config = Self {
       first_inscription_height: options.first_inscription_height(),
       first_brc20_height: if options.enable_index_brc20 {
-        Some(options.first_brc20_height())
+        Some(options.first_brc20_height(schedule.brc20.first_height))
       } else {
         None
       },
       first_brc20s_height: if options.enable_index_brc20s {
-        Some(options.first_brc20s_height())
+        Some(options.first_brc20s_height(schedule.brc20s.first_height))
       } else {
         None
       },
       enable_ord_receipts: options.enable_save_ord_receipts,
       enable_index_bitmap: options.enable_index_bitmap,
+      brc20_versions: schedule
+        .brc20
+        .versions_or(|| brc20::default_versions(chain))
+        .map_err(|e| anyhow!("invalid brc20 rule versions for {chain}: {e}"))?,
+      brc20s_versions: schedule
+        .brc20s
+        .versions_or(|| brc20s::default_versions(chain))
+        .map_err(|e| anyhow!("invalid brc20s rule versions for {chain}: {e}"))?,
     };
 
     if config.first_brc20s_height.is_some() && config.first_brc20_height.is_none() {
-      config.first_brc20_height = Some(options.first_brc20_height());
+      config.first_brc20_height = Some(options.first_brc20_height(schedule.brc20.first_height));
     }
-    config
+    Ok(config)
   }
 }
@@ -3,18 +3,67 @@ use {
   crate::{
     okx::{
       datastore::ord::{
+        self as ord_store,
         bitmap::District,
         collections::CollectionKind,
         operation::{Action, InscriptionOp},
       },
-      protocol::BlockContext,
+      datastore::{table::TableSpec, StateRWriter},
+      protocol::{BlockContext, Protocol, ProtocolConfig},
     },
+    subcommand::server,
     Inscription, InscriptionId, Result,
   },
+  axum::Router,
   bitcoin::Txid,
   std::collections::HashMap,
 };
 
+/// Bitmap districts, the first inscription of `<number>.bitmap` for every block number reached.
+pub(crate) struct BitmapProtocol {
+  activation_height: Option<u64>,
+}
+
+impl BitmapProtocol {
+  pub(crate) fn new(config: &ProtocolConfig) -> Self {
+    Self {
+      activation_height: config
+        .enable_index_bitmap
+        .then_some(config.first_inscription_height),
+    }
+  }
+}
+
+impl Protocol for BitmapProtocol {
+  type Message = ();
+  type Receipt = ();
+
+  fn name(&self) -> &'static str {
+    "bitmap"
+  }
+
+  fn activation_height(&self) -> Option<u64> {
+    self.activation_height
+  }
+
+  fn tables(&self) -> &'static [TableSpec] {
+    ord_store::redb::COLLECTION_TABLES
+  }
+
+  fn router(&self) -> Option<Router> {
+    Some(server::ord::bitmap_router())
+  }
+
+  fn index_block<RW: StateRWriter>(
+    &self,
+    context: BlockContext,
+    state: &RW,
+    operations: &HashMap<Txid, Vec<InscriptionOp>>,
+  ) -> Result<u64> {
+    index_bitmap(&ord_store::store(state), context, operations)
+  }
+}
+
 pub fn index_bitmap<O: DataStoreReadWrite>(
   ord_store: &O,
   context: BlockContext,
@@ -26,10 +75,10 @@ pub fn index_bitmap<O: DataStoreReadWrite>(
   let mut positive_inscriptions = operations
     .values()
     .flatten()
-    .cloned()
     .filter(|op| {
       !op.inscription_number.unwrap().is_negative() && matches!(op.action, Action::New { .. })
     })
+    .cloned()
     .collect::<Vec<_>>();
 
   // sort by inscription number.
@@ -17,3 +17,24 @@ pub fn save_transaction_operations<O: DataStoreReadWrite>(
     .save_transaction_operations(txid, tx_operations)
     .map_err(|e| anyhow!("failed to set transaction ordinals operations to state! error: {e}"))
 }
+
+pub fn save_transaction_height<O: DataStoreReadWrite>(
+  ord_store: &O,
+  txid: &Txid,
+  height: u64,
+) -> Result<()> {
+  ord_store
+    .save_transaction_height(txid, height)
+    .map_err(|e| anyhow!("failed to set transaction height to state! error: {e}"))
+}
+
+pub fn save_block_transactions<O: DataStoreReadWrite>(
+  ord_store: &O,
+  height: u64,
+  blocktime: u32,
+  txids: &[Txid],
+) -> Result<()> {
+  ord_store
+    .save_block_transactions(height, blocktime, txids)
+    .map_err(|e| anyhow!("failed to set block transactions to state! error: {e}"))
+}
@@ -1,90 +1,480 @@
 use {
   super::*,
   crate::{
-    index::BlockData,
+    index::{fetcher::BlockingFetcher, BlockData, Notification},
+    metrics,
     okx::{
-      datastore::{ord::operation::InscriptionOp, StateRWriter},
+      datastore::{
+        ord::{
+          self as ord_store, operation::InscriptionOp, Action, DataStoreReadOnly,
+          DataStoreReadWrite,
+        },
+        StateRWriter,
+      },
       protocol::ord as ord_proto,
     },
-    Instant, Result,
+    Inscription, Instant, Result,
   },
-  bitcoin::Txid,
+  anyhow::anyhow,
+  bitcoin::{Transaction, Txid},
   bitcoincore_rpc::Client,
-  std::collections::HashMap,
+  std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    thread,
+    time::Duration,
+  },
 };
 
+// The number of transactions with inscription operations below which the inscriptions of a block
+// are parsed on the indexing thread, as spawning threads would take longer.
+const PARALLEL_PARSE_THRESHOLD: usize = 64;
+
 pub struct ProtocolManager<'a, RW: StateRWriter> {
+  client: Option<&'a Client>,
+  fetcher: Option<&'a BlockingFetcher>,
   state_store: &'a RW,
   config: &'a ProtocolConfig,
-  call_man: CallManager<'a, RW>,
-  resolve_man: MsgResolveManager<'a, RW>,
+  protocols: Vec<Box<dyn DynProtocol<RW>>>,
+}
+
+impl<'a, RW: StateRWriter> Registry for ProtocolManager<'a, RW> {
+  fn register<P: Protocol + 'static>(&mut self, protocol: P) {
+    self.protocols.push(Box::new(protocol));
+  }
 }
 
 impl<'a, RW: StateRWriter> ProtocolManager<'a, RW> {
   // Need three datastore, and they're all in the same write transaction.
-  pub fn new(client: &'a Client, state_store: &'a RW, config: &'a ProtocolConfig) -> Self {
-    Self {
+  pub fn new(client: Option<&'a Client>, state_store: &'a RW, config: &'a ProtocolConfig) -> Self {
+    let mut manager = Self {
+      client,
+      fetcher: None,
       state_store,
       config,
-      call_man: CallManager::new(state_store),
-      resolve_man: MsgResolveManager::new(client, state_store, config),
-    }
+      protocols: Vec::new(),
+    };
+    register_protocols(config, &mut manager);
+    manager
   }
 
+  /// Fetches the commit transactions of each block with `fetcher` before resolving its messages.
+  pub(crate) fn with_fetcher(mut self, fetcher: &'a BlockingFetcher) -> Self {
+    self.fetcher = Some(fetcher);
+    self
+  }
+
+  fn is_active(protocol: &dyn DynProtocol<RW>, context: BlockContext) -> bool {
+    protocol
+      .activation_height()
+      .map(|height| context.blockheight >= height)
+      .unwrap_or(false)
+  }
+
+  /// Indexes the inscription operations of `block`.
+  ///
+  /// Resolution is split from execution. The new inscriptions of the transactions are parsed
+  /// concurrently, the commit transactions the messages are resolved with are fetched in batches,
+  /// and the messages of every transaction are resolved before any is executed. The messages are
+  /// then executed in block order. The only messages that depend on the execution of the block are
+  /// the transfers of inscriptions inscribed by an earlier transaction of the block, whose
+  /// transferable state is only known once that transaction is executed, so the transactions
+  /// moving them are resolved right before they are executed instead. The time spent in each is
+  /// recorded in the block phase metrics, with the fetch counted as resolution.
   pub(crate) fn index_block(
     &self,
     context: BlockContext,
     block: &BlockData,
     operations: HashMap<Txid, Vec<InscriptionOp>>,
+    mut notifications: Option<&mut Vec<Notification>>,
   ) -> Result {
     let start = Instant::now();
     let mut inscriptions_size = 0;
     let mut messages_size = 0;
-    // skip the coinbase transaction.
-    for (tx, txid) in block.txdata.iter() {
+    let mut block_txids = Vec::new();
+    let mut execute_time = Duration::ZERO;
+
+    let resolve_start = Instant::now();
+    let transactions_operations = block
+      .txdata
+      .iter()
       // skip coinbase transaction.
-      if tx
-        .input
-        .first()
-        .map(|tx_in| tx_in.previous_output.is_null())
-        .unwrap_or_default()
-      {
-        continue;
-      }
+      .filter(|(tx, _)| {
+        !tx
+          .input
+          .first()
+          .map(|tx_in| tx_in.previous_output.is_null())
+          .unwrap_or_default()
+      })
+      .filter_map(|(tx, txid)| {
+        operations
+          .get(txid)
+          .map(|tx_operations| (tx, txid, tx_operations))
+      })
+      .collect::<Vec<_>>();
+
+    let block_operations = transactions_operations
+      .iter()
+      .zip(parse_new_inscriptions(
+        &transactions_operations
+          .iter()
+          .map(|(tx, _, _)| *tx)
+          .collect::<Vec<&Transaction>>(),
+      ))
+      .map(|((tx, txid, tx_operations), new_inscriptions)| {
+        (*tx, *txid, new_inscriptions, *tx_operations)
+      })
+      .collect::<Vec<_>>();
+
+    let transactions = self.fetch_commit_transactions(
+      context,
+      block_operations
+        .iter()
+        .map(|(_, _, new_inscriptions, tx_operations)| {
+          (new_inscriptions.as_slice(), tx_operations.as_slice())
+        }),
+    )?;
+    let fetched = transactions.len();
+
+    let mut earlier = HashSet::new();
+    let mut resolved = Vec::new();
+    for (tx, txid, new_inscriptions, tx_operations) in &block_operations {
+      let dependent = tx_operations.iter().any(|operation| {
+        matches!(operation.action, Action::Transfer)
+          && earlier.contains(&operation.inscription_id.txid)
+      });
+      earlier.insert(**txid);
+
+      resolved.push(if dependent {
+        None
+      } else {
+        Some(self.resolve_operations(
+          context,
+          new_inscriptions,
+          Self::input_operations(tx, tx_operations),
+          &transactions,
+        )?)
+      });
+    }
+    let mut resolve_time = resolve_start.elapsed();
 
+    for ((tx, txid, new_inscriptions, tx_operations), messages) in
+      block_operations.into_iter().zip(resolved)
+    {
       // index inscription operations.
-      if let Some(tx_operations) = operations.get(txid) {
+      if context.blockheight >= self.config.first_inscription_height {
+        // index the height of the transaction, so that API queries don't need bitcoind.
+        ord_proto::save_transaction_height(
+          &ord_store::store(self.state_store),
+          txid,
+          context.blockheight,
+        )?;
+        block_txids.push(*txid);
+
         // save all transaction operations to ord database.
-        if self.config.enable_ord_receipts
-          && context.blockheight >= self.config.first_inscription_height
-        {
-          ord_proto::save_transaction_operations(self.state_store.ord(), txid, tx_operations)?;
+        if self.config.enable_ord_receipts {
+          ord_proto::save_transaction_operations(
+            &ord_store::store(self.state_store),
+            txid,
+            tx_operations,
+          )?;
           inscriptions_size += tx_operations.len();
         }
+      }
+
+      let messages = match messages {
+        Some(messages) => messages,
+        None => {
+          let resolve_start = Instant::now();
+          let messages = self.resolve_operations(
+            context,
+            &new_inscriptions,
+            Self::input_operations(tx, tx_operations),
+            &transactions,
+          )?;
+          resolve_time += resolve_start.elapsed();
+          messages
+        }
+      };
 
-        // Resolve and execute messages.
-        let messages = self
-          .resolve_man
-          .resolve_message(context, tx, tx_operations)?;
-        for msg in messages.iter() {
-          self.call_man.execute_message(context, msg)?;
+      let execute_start = Instant::now();
+      for msg in messages.iter() {
+        let protocol = &self.protocols[msg.protocol()];
+        metrics::inc_messages(protocol.name(), 1);
+        if let Some(receipt) = self.execute(context, msg)? {
+          metrics::inc_receipts(protocol.name(), protocol.receipt_result(receipt.as_ref()));
         }
-        messages_size += messages.len();
       }
+      execute_time += execute_start.elapsed();
+      messages_size += messages.len();
+
+      if let Some(notifications) = notifications.as_deref_mut() {
+        self.collect_notifications(
+          context,
+          txid,
+          tx_operations,
+          !messages.is_empty(),
+          notifications,
+        )?;
+      }
+    }
+    if !block_txids.is_empty() {
+      ord_proto::save_block_transactions(
+        &ord_store::store(self.state_store),
+        context.blockheight,
+        context.blocktime,
+        &block_txids,
+      )?;
     }
-    let mut bitmap_count = 0;
-    if self.config.enable_index_bitmap {
-      bitmap_count = ord_proto::bitmap::index_bitmap(self.state_store.ord(), context, &operations)?;
+    metrics::observe_block_phase("resolve", resolve_time);
+    metrics::observe_block_phase("execute", execute_time);
+
+    let mut indexed = String::new();
+    for protocol in &self.protocols {
+      if Self::is_active(protocol.as_ref(), context) {
+        let count = protocol.index_block(context, self.state_store, &operations)?;
+        if count > 0 {
+          indexed.push_str(&format!(", {} {count}", protocol.name()));
+        }
+      }
     }
 
     log::info!(
-      "Protocol Manager indexed block {} with ord inscriptions {}, messages {}, bitmap {} in {} ms",
+      "Protocol Manager indexed block {} with ord inscriptions {}, messages {}, fetched transactions {}{} in {} ms",
       context.blockheight,
       inscriptions_size,
       messages_size,
-      bitmap_count,
+      fetched,
+      indexed,
       (Instant::now() - start).as_millis(),
     );
     Ok(())
   }
+
+  // fetches the commit transactions the active protocols look up to resolve `operations`, and the
+  // transactions of the outputs they spend that aren't stored, in two rounds of batched requests.
+  fn fetch_commit_transactions<'o>(
+    &self,
+    context: BlockContext,
+    operations: impl Iterator<Item = (&'o [Inscription], &'o [InscriptionOp])>,
+  ) -> Result<HashMap<Txid, Transaction>> {
+    let Some(fetcher) = self.fetcher else {
+      return Ok(HashMap::new());
+    };
+
+    let mut commit_txids = Vec::new();
+    let mut seen = HashSet::new();
+    for (new_inscriptions, tx_operations) in operations {
+      for operation in tx_operations {
+        for protocol in &self.protocols {
+          if !Self::is_active(protocol.as_ref(), context) {
+            continue;
+          }
+          if let Some(txid) =
+            protocol.commit_transaction(self.state_store, new_inscriptions, operation)?
+          {
+            if seen.insert(txid) {
+              commit_txids.push(txid);
+            }
+          }
+        }
+      }
+    }
+
+    let commit_transactions = fetcher.get_transactions(commit_txids)?;
+
+    let mut input_txids = Vec::new();
+    for input in commit_transactions.iter().flat_map(|tx| &tx.input) {
+      let outpoint = input.previous_output;
+      if outpoint.is_null() || seen.contains(&outpoint.txid) {
+        continue;
+      }
+      if ord_store::store(self.state_store)
+        .get_outpoint_to_txout(outpoint)
+        .map_err(|e| anyhow!("failed to get tx out from state! error: {e}"))?
+        .is_none()
+      {
+        seen.insert(outpoint.txid);
+        input_txids.push(outpoint.txid);
+      }
+    }
+
+    Ok(
+      commit_transactions
+        .into_iter()
+        .chain(fetcher.get_transactions(input_txids)?)
+        .map(|tx| (tx.txid(), tx))
+        .collect(),
+    )
+  }
+
+  /// Resolves the messages of the inscription operations of `tx`, which are the operations of
+  /// its block ordered by input.
+  pub fn resolve_message(
+    &self,
+    context: BlockContext,
+    tx: &Transaction,
+    operations: &[InscriptionOp],
+  ) -> Result<Vec<Message>> {
+    let new_inscriptions = Inscription::from_transaction(tx)
+      .into_iter()
+      .map(|v| v.inscription)
+      .collect::<Vec<Inscription>>();
+
+    self.resolve_operations(
+      context,
+      &new_inscriptions,
+      Self::input_operations(tx, operations),
+      &HashMap::new(),
+    )
+  }
+
+  // returns the operations of the inputs of `tx`, in input order.
+  fn input_operations<'o>(
+    tx: &Transaction,
+    operations: &'o [InscriptionOp],
+  ) -> Vec<&'o InscriptionOp> {
+    log::debug!(
+      "Protocol Manager resolving transaction {}, operations size: {}, data: {:?}",
+      tx.txid(),
+      operations.len(),
+      operations
+    );
+    let mut tx_operations = Vec::new();
+    let mut operation_iter = operations.iter().peekable();
+
+    for input in &tx.input {
+      // "operations" is a list of all the operations in the current block, and they are ordered.
+      // We just need to find the operation corresponding to the current transaction here.
+      while let Some(operation) = operation_iter.peek() {
+        if operation.old_satpoint.outpoint != input.previous_output {
+          break;
+        }
+        tx_operations.push(operation_iter.next().unwrap());
+      }
+    }
+    tx_operations
+  }
+
+  /// Resolves the messages of the inscription operations of a single transaction, in input order.
+  /// Each operation is resolved by the first active protocol that recognizes it, and commit
+  /// transactions missing from `transactions` are asked from Bitcoin Core.
+  pub fn resolve_operations<'o>(
+    &self,
+    context: BlockContext,
+    new_inscriptions: &[Inscription],
+    operations: impl IntoIterator<Item = &'o InscriptionOp>,
+    transactions: &HashMap<Txid, Transaction>,
+  ) -> Result<Vec<Message>> {
+    let operations = operations.into_iter().collect::<Vec<_>>();
+
+    let mut resolve_context = ResolveContext {
+      client: self.client,
+      state: self.state_store,
+      new_inscriptions,
+      transactions,
+      inscriptions: operations.iter().map(|op| op.inscription_id).collect(),
+      outpoint_to_txout_cache: HashMap::new(),
+    };
+
+    let mut messages = Vec::new();
+    for operation in operations {
+      for (position, protocol) in self.protocols.iter().enumerate() {
+        if !Self::is_active(protocol.as_ref(), context) {
+          continue;
+        }
+        if let Some(msg) = protocol.resolve(&mut resolve_context, operation)? {
+          messages.push(Message::new(position, msg));
+          break;
+        }
+      }
+    }
+
+    for (outpoint, txout) in resolve_context.outpoint_to_txout_cache {
+      ord_store::store(self.state_store)
+        .set_outpoint_to_txout(outpoint, &txout)
+        .or(Err(anyhow!(
+          "failed to get tx out! error: {} not found",
+          outpoint
+        )))?;
+    }
+    Ok(messages)
+  }
+
+  /// Executes `msg` with the protocol that resolved it, and passes the receipt on to every active
+  /// protocol.
+  pub fn execute_message(&self, context: BlockContext, msg: &Message) -> Result {
+    self.execute(context, msg).map(|_| ())
+  }
+
+  fn execute(&self, context: BlockContext, msg: &Message) -> Result<Option<Box<dyn Any>>> {
+    let Some(receipt) =
+      self.protocols[msg.protocol()].execute(context, self.state_store, msg.inner())?
+    else {
+      return Ok(None);
+    };
+
+    for protocol in &self.protocols {
+      if Self::is_active(protocol.as_ref(), context) {
+        protocol.on_receipt(context, self.state_store, msg.inner(), receipt.as_ref())?;
+      }
+    }
+    Ok(Some(receipt))
+  }
+
+  // collect the inscription operations of the transaction and the receipts its messages produced.
+  fn collect_notifications(
+    &self,
+    context: BlockContext,
+    txid: &Txid,
+    tx_operations: &[InscriptionOp],
+    executed: bool,
+    notifications: &mut Vec<Notification>,
+  ) -> Result {
+    let height = context.blockheight;
+    notifications.extend(tx_operations.iter().map(|op| Notification::Inscription {
+      height,
+      op: op.clone(),
+    }));
+
+    if !executed {
+      return Ok(());
+    }
+
+    for protocol in &self.protocols {
+      if Self::is_active(protocol.as_ref(), context) {
+        protocol.notifications(context, self.state_store, txid, notifications)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+// parses the new inscriptions of `transactions` on as many threads as there are cores, since
+// parsing only reads the transactions.
+fn parse_new_inscriptions(transactions: &[&Transaction]) -> Vec<Vec<Inscription>> {
+  let parse = |tx: &Transaction| {
+    Inscription::from_transaction(tx)
+      .into_iter()
+      .map(|v| v.inscription)
+      .collect::<Vec<Inscription>>()
+  };
+
+  let threads = thread::available_parallelism()
+    .map(usize::from)
+    .unwrap_or(1);
+
+  if transactions.len() < PARALLEL_PARSE_THRESHOLD || threads == 1 {
+    return transactions.iter().map(|tx| parse(tx)).collect();
+  }
+
+  thread::scope(|scope| {
+    transactions
+      .chunks(transactions.len().div_ceil(threads))
+      .map(|chunk| scope.spawn(move || chunk.iter().map(|tx| parse(tx)).collect::<Vec<_>>()))
+      .collect::<Vec<_>>()
+      .into_iter()
+      .flat_map(|handle| handle.join().unwrap())
+      .collect()
+  })
 }
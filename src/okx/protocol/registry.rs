@@ -0,0 +1,352 @@
+use {
+  super::*,
+  crate::{
+    index::Notification,
+    okx::datastore::{
+      keys,
+      ord::{self as ord_store, InscriptionOp},
+      table::TableSpec,
+      StateRWriter,
+    },
+    Inscription, InscriptionId, Result,
+  },
+  anyhow::anyhow,
+  axum::Router,
+  bitcoin::{OutPoint, Transaction, TxOut, Txid},
+  bitcoincore_rpc::Client,
+  std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+  },
+};
+
+/// A metaprotocol indexed from inscription operations.
+///
+/// Every protocol the `ProtocolManager` registers gets to resolve the inscription operations of a
+/// transaction, in registration order, and the first one that resolves a message owns it. Messages
+/// are executed by the protocol that resolved them, and every receipt is passed on to all active
+/// protocols, so that a protocol can react to the receipts of another.
+pub(crate) trait Protocol {
+  /// The message resolved from an inscription operation.
+  type Message: 'static;
+  /// The outcome of executing a message.
+  type Receipt: 'static;
+
+  /// The name of the protocol, used in logs.
+  fn name(&self) -> &'static str;
+
+  /// The first height the protocol is indexed at, or `None` when it is not indexed.
+  fn activation_height(&self) -> Option<u64>;
+
+  /// The tables the protocol stores its state in.
+  fn tables(&self) -> &'static [TableSpec] {
+    &[]
+  }
+
+  /// The names of the tables holding the consensus state of the protocol, which `index verify`
+  /// compares with the state re-executed from the stored operations.
+  fn consensus_tables(&self) -> &'static [&'static str] {
+    &[]
+  }
+
+  /// The routes of the protocol's JSON API, nested under `/api/v1`.
+  fn router(&self) -> Option<Router> {
+    None
+  }
+
+  /// Returns the commit transaction resolving `operation` looks up, so that the `ProtocolManager`
+  /// can fetch it, and the transactions of the outputs it spends, in a batch with those of the
+  /// other operations of the block.
+  fn commit_transaction<RW: StateRWriter>(
+    &self,
+    _state: &RW,
+    _new_inscriptions: &[Inscription],
+    _operation: &InscriptionOp,
+  ) -> Result<Option<Txid>> {
+    Ok(None)
+  }
+
+  /// Returns the message of `operation`, or `None` when the operation is not part of the protocol.
+  fn resolve<RW: StateRWriter>(
+    &self,
+    _context: &mut ResolveContext<RW>,
+    _operation: &InscriptionOp,
+  ) -> Result<Option<Self::Message>> {
+    Ok(None)
+  }
+
+  /// Executes a message the protocol resolved and returns its receipt, if any.
+  fn execute<RW: StateRWriter>(
+    &self,
+    _context: BlockContext,
+    _state: &RW,
+    _msg: &Self::Message,
+  ) -> Result<Option<Self::Receipt>> {
+    Ok(None)
+  }
+
+  /// The result of `receipt` in the receipt metrics, `ok` or the name of its error.
+  fn receipt_result(&self, _receipt: &Self::Receipt) -> String {
+    "ok".into()
+  }
+
+  /// Called with every executed message and its receipt, of any protocol. Use `downcast_ref` to
+  /// pick the messages of a given protocol.
+  fn on_receipt<RW: StateRWriter>(
+    &self,
+    _context: BlockContext,
+    _state: &RW,
+    _msg: &dyn Any,
+    _receipt: &dyn Any,
+  ) -> Result {
+    Ok(())
+  }
+
+  /// Called once all the transactions of a block are executed, returns the number of entries the
+  /// protocol indexed.
+  fn index_block<RW: StateRWriter>(
+    &self,
+    _context: BlockContext,
+    _state: &RW,
+    _operations: &HashMap<Txid, Vec<InscriptionOp>>,
+  ) -> Result<u64> {
+    Ok(0)
+  }
+
+  /// Appends the notifications of the receipts stored for `txid`.
+  fn notifications<RW: StateRWriter>(
+    &self,
+    _context: BlockContext,
+    _state: &RW,
+    _txid: &Txid,
+    _notifications: &mut Vec<Notification>,
+  ) -> Result {
+    Ok(())
+  }
+}
+
+/// Collects the protocols enabled by a `ProtocolConfig`.
+pub(crate) trait Registry {
+  fn register<P: Protocol + 'static>(&mut self, protocol: P);
+}
+
+/// Registers the built-in protocols with `registry`, in resolution order.
+pub(crate) fn register_protocols(config: &ProtocolConfig, registry: &mut impl Registry) {
+  registry.register(brc20::BRC20Protocol::new(config));
+  registry.register(brc20s::BRC20SProtocol::new(config));
+  registry.register(ord::bitmap::BitmapProtocol::new(config));
+}
+
+/// The name, tables and routes of a registered protocol.
+pub(crate) struct ProtocolInfo {
+  pub(crate) name: &'static str,
+  pub(crate) activation_height: Option<u64>,
+  pub(crate) tables: &'static [TableSpec],
+  pub(crate) consensus_tables: &'static [&'static str],
+  pub(crate) router: Option<Router>,
+}
+
+impl Registry for Vec<ProtocolInfo> {
+  fn register<P: Protocol + 'static>(&mut self, protocol: P) {
+    self.push(ProtocolInfo {
+      name: protocol.name(),
+      activation_height: protocol.activation_height(),
+      tables: protocol.tables(),
+      consensus_tables: protocol.consensus_tables(),
+      router: protocol.router(),
+    });
+  }
+}
+
+impl ProtocolInfo {
+  /// Returns the protocols enabled by `config`, in resolution order.
+  pub(crate) fn all(config: &ProtocolConfig) -> Vec<Self> {
+    let mut protocols = Vec::new();
+    register_protocols(config, &mut protocols);
+    protocols
+  }
+
+  /// Returns the tables the `ProtocolManager` keeps for every protocol, followed by the tables of
+  /// the protocols registered with `config`.
+  pub(crate) fn state_tables(config: &ProtocolConfig) -> Vec<TableSpec> {
+    let mut tables = [ord_store::redb::TABLES, keys::TABLES].concat();
+    for protocol in Self::all(config) {
+      for table in protocol.tables {
+        if !tables.contains(table) {
+          tables.push(*table);
+        }
+      }
+    }
+    tables
+  }
+}
+
+/// What the protocols share while resolving the operations of a transaction.
+pub(crate) struct ResolveContext<'a, RW: StateRWriter> {
+  pub(crate) client: Option<&'a Client>,
+  pub(crate) state: &'a RW,
+  pub(crate) new_inscriptions: &'a [Inscription],
+  /// Transactions fetched ahead of resolution, looked up before asking Bitcoin Core.
+  pub(crate) transactions: &'a HashMap<Txid, Transaction>,
+  /// The inscriptions the transaction moves or creates, which are the possible parents of its new
+  /// inscriptions.
+  pub(crate) inscriptions: HashSet<InscriptionId>,
+  /// Outputs fetched from Bitcoin Core, stored in the ord tables once the transaction is resolved.
+  pub(crate) outpoint_to_txout_cache: HashMap<OutPoint, TxOut>,
+}
+
+/// A message resolved by a registered protocol.
+pub struct Message {
+  /// The position of the protocol in the registry.
+  protocol: usize,
+  inner: Box<dyn Any>,
+}
+
+/// A `Protocol` whose messages and receipts are type erased, so that the `ProtocolManager` can
+/// hold protocols of different types.
+pub(super) trait DynProtocol<RW: StateRWriter> {
+  fn name(&self) -> &'static str;
+
+  fn activation_height(&self) -> Option<u64>;
+
+  fn commit_transaction(
+    &self,
+    state: &RW,
+    new_inscriptions: &[Inscription],
+    operation: &InscriptionOp,
+  ) -> Result<Option<Txid>>;
+
+  fn resolve(
+    &self,
+    context: &mut ResolveContext<RW>,
+    operation: &InscriptionOp,
+  ) -> Result<Option<Box<dyn Any>>>;
+
+  fn execute(
+    &self,
+    context: BlockContext,
+    state: &RW,
+    msg: &dyn Any,
+  ) -> Result<Option<Box<dyn Any>>>;
+
+  fn receipt_result(&self, receipt: &dyn Any) -> String;
+
+  fn on_receipt(
+    &self,
+    context: BlockContext,
+    state: &RW,
+    msg: &dyn Any,
+    receipt: &dyn Any,
+  ) -> Result;
+
+  fn index_block(
+    &self,
+    context: BlockContext,
+    state: &RW,
+    operations: &HashMap<Txid, Vec<InscriptionOp>>,
+  ) -> Result<u64>;
+
+  fn notifications(
+    &self,
+    context: BlockContext,
+    state: &RW,
+    txid: &Txid,
+    notifications: &mut Vec<Notification>,
+  ) -> Result;
+}
+
+impl<RW: StateRWriter, P: Protocol> DynProtocol<RW> for P {
+  fn name(&self) -> &'static str {
+    Protocol::name(self)
+  }
+
+  fn activation_height(&self) -> Option<u64> {
+    Protocol::activation_height(self)
+  }
+
+  fn commit_transaction(
+    &self,
+    state: &RW,
+    new_inscriptions: &[Inscription],
+    operation: &InscriptionOp,
+  ) -> Result<Option<Txid>> {
+    Protocol::commit_transaction(self, state, new_inscriptions, operation)
+  }
+
+  fn resolve(
+    &self,
+    context: &mut ResolveContext<RW>,
+    operation: &InscriptionOp,
+  ) -> Result<Option<Box<dyn Any>>> {
+    Ok(Protocol::resolve(self, context, operation)?.map(|msg| Box::new(msg) as Box<dyn Any>))
+  }
+
+  fn execute(
+    &self,
+    context: BlockContext,
+    state: &RW,
+    msg: &dyn Any,
+  ) -> Result<Option<Box<dyn Any>>> {
+    let msg = msg.downcast_ref::<P::Message>().ok_or_else(|| {
+      anyhow!(
+        "{} cannot execute a message of another protocol",
+        Protocol::name(self)
+      )
+    })?;
+
+    Ok(
+      Protocol::execute(self, context, state, msg)?
+        .map(|receipt| Box::new(receipt) as Box<dyn Any>),
+    )
+  }
+
+  fn receipt_result(&self, receipt: &dyn Any) -> String {
+    receipt
+      .downcast_ref::<P::Receipt>()
+      .map(|receipt| Protocol::receipt_result(self, receipt))
+      .unwrap_or_else(|| "unknown".into())
+  }
+
+  fn on_receipt(
+    &self,
+    context: BlockContext,
+    state: &RW,
+    msg: &dyn Any,
+    receipt: &dyn Any,
+  ) -> Result {
+    Protocol::on_receipt(self, context, state, msg, receipt)
+  }
+
+  fn index_block(
+    &self,
+    context: BlockContext,
+    state: &RW,
+    operations: &HashMap<Txid, Vec<InscriptionOp>>,
+  ) -> Result<u64> {
+    Protocol::index_block(self, context, state, operations)
+  }
+
+  fn notifications(
+    &self,
+    context: BlockContext,
+    state: &RW,
+    txid: &Txid,
+    notifications: &mut Vec<Notification>,
+  ) -> Result {
+    Protocol::notifications(self, context, state, txid, notifications)
+  }
+}
+
+impl Message {
+  pub(super) fn new(protocol: usize, inner: Box<dyn Any>) -> Self {
+    Self { protocol, inner }
+  }
+
+  pub(super) fn protocol(&self) -> usize {
+    self.protocol
+  }
+
+  pub(super) fn inner(&self) -> &dyn Any {
+    self.inner.as_ref()
+  }
+}
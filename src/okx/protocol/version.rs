@@ -0,0 +1,224 @@
+use {
+  super::{brc20, brc20s},
+  crate::Result,
+  anyhow::{bail, Context},
+  serde::Deserialize,
+};
+
+/// A rule set of a protocol and the height it applies from.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Version<C> {
+  pub height: u64,
+  pub rules: C,
+}
+
+/// The rule set of a protocol.
+pub trait Rules {
+  /// Checks that the values of a rule set loaded from the config file can be applied.
+  fn check(&self) -> Result {
+    Ok(())
+  }
+}
+
+/// The rule sets of a protocol on a chain, ordered by the height they apply from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Versions<C>(Vec<Version<C>>);
+
+impl<C: Clone + Rules> Versions<C> {
+  pub fn new(versions: Vec<Version<C>>) -> Result<Self> {
+    if versions.is_empty() {
+      bail!("at least one rule version is required");
+    }
+
+    for pair in versions.windows(2) {
+      if pair[0].height >= pair[1].height {
+        bail!(
+          "rule versions must be ordered by strictly increasing height, got {} before {}",
+          pair[0].height,
+          pair[1].height
+        );
+      }
+    }
+
+    for version in &versions {
+      version
+        .rules
+        .check()
+        .with_context(|| format!("invalid rules at height {}", version.height))?;
+    }
+
+    Ok(Self(versions))
+  }
+
+  /// Returns the rules of the last version applying at `height`, or of the first version when
+  /// `height` is below all of them.
+  pub fn at(&self, height: u64) -> C {
+    self
+      .0
+      .iter()
+      .rev()
+      .find(|version| version.height <= height)
+      .unwrap_or(&self.0[0])
+      .rules
+      .clone()
+  }
+}
+
+/// The activation height and rule versions of a protocol on a chain, as read from the `protocols`
+/// section of the config file. Unset values keep the built-in defaults of the chain.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ProtocolSchedule<C> {
+  pub(crate) first_height: Option<u64>,
+  pub(crate) versions: Option<Vec<Version<C>>>,
+}
+
+impl<C> Default for ProtocolSchedule<C> {
+  fn default() -> Self {
+    Self {
+      first_height: None,
+      versions: None,
+    }
+  }
+}
+
+impl<C: Clone + Rules> ProtocolSchedule<C> {
+  pub(crate) fn versions_or(
+    &self,
+    defaults: impl FnOnce() -> Vec<Version<C>>,
+  ) -> Result<Versions<C>> {
+    Versions::new(self.versions.clone().unwrap_or_else(defaults))
+  }
+}
+
+/// The protocol schedules configured for a chain.
+#[derive(Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ChainSchedule {
+  #[serde(default)]
+  pub(crate) brc20: ProtocolSchedule<brc20::Config>,
+  #[serde(default)]
+  pub(crate) brc20s: ProtocolSchedule<brc20s::Config>,
+}
+
+#[cfg(test)]
+mod tests {
+  use {super::*, crate::Chain};
+
+  impl Rules for u64 {}
+
+  fn version(height: u64, config: u64) -> Version<u64> {
+    Version {
+      height,
+      rules: config,
+    }
+  }
+
+  #[test]
+  fn rules_apply_from_their_height() {
+    let versions = Versions::new(vec![version(10, 1), version(20, 2)]).unwrap();
+    assert_eq!(versions.at(0), 1);
+    assert_eq!(versions.at(10), 1);
+    assert_eq!(versions.at(19), 1);
+    assert_eq!(versions.at(20), 2);
+    assert_eq!(versions.at(u64::MAX), 2);
+  }
+
+  #[test]
+  fn versions_must_be_ordered_and_not_empty() {
+    assert_eq!(
+      Versions::<u64>::new(Vec::new()).unwrap_err().to_string(),
+      "at least one rule version is required"
+    );
+    assert_eq!(
+      Versions::new(vec![version(20, 1), version(20, 2)])
+        .unwrap_err()
+        .to_string(),
+      "rule versions must be ordered by strictly increasing height, got 20 before 20"
+    );
+  }
+
+  #[test]
+  fn brc20_decimal_width_cannot_exceed_18() {
+    assert_eq!(
+      format!(
+        "{:#}",
+        Versions::new(vec![Version {
+          height: 5,
+          rules: brc20::Config {
+            max_decimal_width: 19,
+            max_supply: u64::MAX,
+            allow_self_mint: false,
+          },
+        }])
+        .unwrap_err()
+      ),
+      "invalid rules at height 5: max_decimal_width 19 exceeds 18"
+    );
+  }
+
+  #[test]
+  fn schedule_is_read_from_yaml() {
+    let schedule: ChainSchedule = serde_yaml::from_str(
+      "
+brc20s:
+  first_height: 100
+  versions:
+  - height: 100
+    rules:
+      allow_share_pool: false
+      allow_btc_staking: true
+      allow_brc20_staking: true
+      allow_brc20s_staking: false
+      max_staked_pool_num: 3
+",
+    )
+    .unwrap();
+
+    assert_eq!(schedule.brc20, ProtocolSchedule::default());
+    assert_eq!(schedule.brc20s.first_height, Some(100));
+
+    let versions = schedule
+      .brc20s
+      .versions_or(|| brc20s::default_versions(Chain::Signet))
+      .unwrap();
+    assert_eq!(
+      versions.at(200),
+      brc20s::Config {
+        allow_share_pool: false,
+        allow_btc_staking: true,
+        allow_brc20_staking: true,
+        allow_brc20s_staking: false,
+        max_staked_pool_num: 3,
+      }
+    );
+
+    assert_eq!(
+      schedule
+        .brc20
+        .versions_or(|| brc20::default_versions(Chain::Signet))
+        .unwrap(),
+      Versions::new(brc20::default_versions(Chain::Signet)).unwrap()
+    );
+  }
+
+  #[test]
+  fn unknown_rules_are_rejected() {
+    assert!(serde_yaml::from_str::<ChainSchedule>(
+      "
+brc20s:
+  versions:
+  - height: 0
+    rules:
+      allow_share_pool: true
+      allow_btc_staking: false
+      allow_brc20_staking: true
+      allow_brc20s_staking: false
+      max_staked_pool_num: 128
+      allow_everything: true
+",
+    )
+    .is_err());
+  }
+}
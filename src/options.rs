@@ -83,6 +83,21 @@ pub(crate) struct Options {
   pub(crate) enable_save_ord_receipts: bool,
   #[arg(long, help = "Enable Index Bitmap Collection.")]
   pub(crate) enable_index_bitmap: bool,
+  #[arg(
+    long,
+    help = "Keep undo logs to recover from reorgs up to <MAX_REORG_DEPTH> blocks deep. [default: 20]"
+  )]
+  pub(crate) max_reorg_depth: Option<u64>,
+  #[arg(
+    long,
+    help = "Keep up to <STATE_CACHE_SIZE> bytes of decoded BRC20 and BRC20S state each in memory while indexing. [default: 67108864]"
+  )]
+  pub(crate) state_cache_size: Option<usize>,
+  #[arg(
+    long,
+    help = "Read blocks from the blk*.dat files in the Bitcoin Core data dir, instead of over RPC, until close to the chain tip."
+  )]
+  pub(crate) read_blk_files: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -132,27 +147,37 @@ impl Options {
     }
   }
 
-  pub(crate) fn first_brc20_height(&self) -> u64 {
+  pub(crate) fn max_reorg_depth(&self) -> u64 {
+    self.max_reorg_depth.unwrap_or(20)
+  }
+
+  pub(crate) fn state_cache_size(&self) -> usize {
+    self.state_cache_size.unwrap_or(64 << 20)
+  }
+
+  /// The first BRC20 height, from the command line, then from the config file, then the
+  /// default of the chain.
+  pub(crate) fn first_brc20_height(&self, configured: Option<u64>) -> u64 {
+    let first_brc20_height = self.first_brc20_height.or(configured);
     if self.chain() == Chain::Regtest {
-      self.first_brc20_height.unwrap_or(0)
+      first_brc20_height.unwrap_or(0)
     } else if integration_test() {
       0
     } else {
-      self
-        .first_brc20_height
-        .unwrap_or_else(|| self.chain().first_brc20_height())
+      first_brc20_height.unwrap_or_else(|| self.chain().first_brc20_height())
     }
   }
 
-  pub(crate) fn first_brc20s_height(&self) -> u64 {
+  /// The first BRC20S height, from the command line, then from the config file, then the
+  /// default of the chain.
+  pub(crate) fn first_brc20s_height(&self, configured: Option<u64>) -> u64 {
+    let first_brc20s_height = self.first_brc20s_height.or(configured);
     if self.chain() == Chain::Regtest {
-      self.first_brc20s_height.unwrap_or(0)
+      first_brc20s_height.unwrap_or(0)
     } else if integration_test() {
       0
     } else {
-      self
-        .first_brc20s_height
-        .unwrap_or_else(|| self.chain().first_brc20s_height())
+      first_brc20s_height.unwrap_or_else(|| self.chain().first_brc20s_height())
     }
   }
 
@@ -173,21 +198,29 @@ impl Options {
       return Ok(cookie_file.clone());
     }
 
+    Ok(self.bitcoin_chain_dir()?.join(".cookie"))
+  }
+
+  /// The directory Bitcoin Core stores its `blk*.dat` files in.
+  pub(crate) fn blocks_dir(&self) -> Result<PathBuf> {
+    Ok(self.bitcoin_chain_dir()?.join("blocks"))
+  }
+
+  // the data dir of Bitcoin Core for the chain.
+  fn bitcoin_chain_dir(&self) -> Result<PathBuf> {
     let path = if let Some(bitcoin_data_dir) = &self.bitcoin_data_dir {
       bitcoin_data_dir.clone()
     } else if cfg!(target_os = "linux") {
       dirs::home_dir()
-        .ok_or_else(|| anyhow!("failed to get cookie file path: could not get home dir"))?
+        .ok_or_else(|| anyhow!("failed to get Bitcoin Core data dir: could not get home dir"))?
         .join(".bitcoin")
     } else {
       dirs::data_dir()
-        .ok_or_else(|| anyhow!("failed to get cookie file path: could not get data dir"))?
+        .ok_or_else(|| anyhow!("failed to get Bitcoin Core data dir: could not get data dir"))?
         .join("Bitcoin")
     };
 
-    let path = self.chain().join_with_data_dir(&path);
-
-    Ok(path.join(".cookie"))
+    Ok(self.chain().join_with_data_dir(&path))
   }
 
   pub(crate) fn data_dir(&self) -> Result<PathBuf> {
@@ -316,7 +349,6 @@ impl Options {
     Ok(client)
   }
 
-  #[allow(unused)]
   pub(crate) fn bitcoin_rpc_client_for_wallet_command(&self, create: bool) -> Result<Client> {
     let client = self.bitcoin_rpc_client()?;
 
@@ -359,7 +391,10 @@ impl Options {
 
 #[cfg(test)]
 mod tests {
-  use {super::*, bitcoin::Network, std::path::Path, tempfile::TempDir};
+  use {
+    super::*, crate::okx::protocol::ProtocolConfig, bitcoin::Network, std::path::Path,
+    tempfile::TempDir,
+  };
 
   #[test]
   fn rpc_url_overrides_network() {
@@ -492,6 +527,23 @@ mod tests {
     }));
   }
 
+  #[test]
+  fn blocks_dir_is_in_bitcoin_data_dir() {
+    let arguments = Arguments::try_parse_from([
+      "ord",
+      "--bitcoin-data-dir=foo",
+      "--chain=signet",
+      "index",
+      "run",
+    ])
+    .unwrap();
+
+    assert_eq!(
+      arguments.options.blocks_dir().unwrap(),
+      Path::new("foo").join("signet").join("blocks")
+    );
+  }
+
   #[test]
   fn mainnet_data_dir() {
     let data_dir = Arguments::try_parse_from(["ord", "index", "run"])
@@ -842,6 +894,64 @@ mod tests {
     );
   }
 
+  #[test]
+  fn protocol_schedule_is_loaded_from_config() {
+    let tempdir = TempDir::new().unwrap();
+    let path = tempdir.path().join("ord.yaml");
+    fs::write(
+      &path,
+      "hidden:\nprotocols:\n  signet:\n    brc20:\n      first_height: 1000\n    brc20s:\n      versions: []",
+    )
+    .unwrap();
+
+    let options = Arguments::try_parse_from([
+      "ord",
+      "--signet",
+      "--config",
+      path.to_str().unwrap(),
+      "index",
+      "run",
+    ])
+    .unwrap()
+    .options;
+
+    let config = options.load_config().unwrap();
+
+    assert_eq!(
+      options.first_brc20_height(config.protocols(Chain::Signet).brc20.first_height),
+      1000
+    );
+    assert_eq!(
+      options.first_brc20s_height(config.protocols(Chain::Signet).brc20s.first_height),
+      Chain::Signet.first_brc20s_height()
+    );
+
+    assert_eq!(
+      ProtocolConfig::new_with_options(&options)
+        .unwrap_err()
+        .to_string(),
+      "invalid brc20s rule versions for signet: at least one rule version is required"
+    );
+
+    let options = Arguments::try_parse_from([
+      "ord",
+      "--signet",
+      "--config",
+      path.to_str().unwrap(),
+      "--first-brc20-height",
+      "2000",
+      "index",
+      "run",
+    ])
+    .unwrap()
+    .options;
+
+    assert_eq!(
+      options.first_brc20_height(config.protocols(Chain::Signet).brc20.first_height),
+      2000
+    );
+  }
+
   #[test]
   fn setting_db_cache_size() {
     let arguments =
@@ -2,7 +2,7 @@ use super::*;
 
 pub mod decode;
 mod index;
-mod server;
+pub(crate) mod server;
 pub mod wallet;
 
 #[derive(Debug, Parser)]
@@ -11,6 +11,8 @@ pub(crate) enum Subcommand {
   Index(index::IndexSubcommand),
   #[command(about = "Run the explorer server")]
   Server(server::Server),
+  #[command(subcommand, about = "Wallet commands")]
+  Wallet(wallet::Wallet),
 }
 
 impl Subcommand {
@@ -23,6 +25,7 @@ impl Subcommand {
         LISTENERS.lock().unwrap().push(handle.clone());
         server.run(options, index, handle)
       }
+      Self::Wallet(wallet) => wallet.run(options),
     }
   }
 }
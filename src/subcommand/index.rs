@@ -4,15 +4,28 @@ use super::*;
 pub(crate) enum IndexSubcommand {
   #[command(about = "Write inscription numbers and ids to a tab-separated file")]
   Export(Export),
+  #[command(
+    about = "Migrate an index built with an older schema, fetching the blocks it backfills from Bitcoin Core"
+  )]
+  Migrate,
   #[command(about = "Update the index")]
   Run,
+  #[command(subcommand, about = "Export or import a snapshot of the index")]
+  Snapshot(Snapshot),
+  #[command(
+    about = "Re-execute the stored inscription operations and compare the BRC20 and BRC20S state with the index"
+  )]
+  Verify,
 }
 
 impl IndexSubcommand {
   pub(crate) fn run(self, options: Options) -> SubcommandResult {
     match self {
       Self::Export(export) => export.run(options),
+      Self::Migrate => migrate(options),
       Self::Run => index::run(options),
+      Self::Snapshot(snapshot) => snapshot.run(options),
+      Self::Verify => verify(options),
     }
   }
 }
@@ -40,6 +53,34 @@ impl Export {
   }
 }
 
+#[derive(Debug, Parser)]
+pub(crate) enum Snapshot {
+  #[command(about = "Write the index tables at the current height to a snapshot archive")]
+  Export {
+    #[arg(help = "Write snapshot to <FILE>")]
+    file: PathBuf,
+    #[arg(
+      long,
+      help = "Write the index tables as they were at <HEIGHT>, which must be within the undo log"
+    )]
+    height: Option<u64>,
+  },
+  #[command(about = "Restore a new index from a snapshot archive")]
+  Import {
+    #[arg(help = "Read snapshot from <FILE>")]
+    file: PathBuf,
+  },
+}
+
+impl Snapshot {
+  pub(crate) fn run(self, options: Options) -> SubcommandResult {
+    Ok(Box::new(match self {
+      Self::Export { file, height } => Index::export_snapshot(&options, height, &file)?,
+      Self::Import { file } => Index::import_snapshot(&options, &file)?,
+    }))
+  }
+}
+
 pub(crate) fn run(options: Options) -> SubcommandResult {
   let index = Index::open(&options)?;
 
@@ -47,3 +88,13 @@ pub(crate) fn run(options: Options) -> SubcommandResult {
 
   Ok(Box::new(Empty {}))
 }
+
+pub(crate) fn migrate(options: Options) -> SubcommandResult {
+  Index::migrate(&options)?;
+
+  Ok(Box::new(Empty {}))
+}
+
+pub(crate) fn verify(options: Options) -> SubcommandResult {
+  Ok(Box::new(Index::verify_offline(&options)?))
+}
@@ -15,11 +15,12 @@ use {
   },
   axum::{
     body,
-    extract::{Extension, Json, Path, Query},
+    extract::{Extension, Json, MatchedPath, Path, Query},
     headers::UserAgent,
-    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    http::{header, HeaderMap, HeaderValue, Request, StatusCode, Uri},
+    middleware::{self, Next},
     response::{IntoResponse, Redirect, Response},
-    routing::get,
+    routing::{get, post},
     Router, TypedHeader,
   },
   axum_server::Handle,
@@ -41,11 +42,14 @@ use {
 };
 
 mod api;
-mod brc20;
-mod brc20s;
+pub(crate) mod brc20;
+pub(crate) mod brc20s;
 mod info;
-mod ord;
+pub(crate) mod ord;
+mod simulate;
+mod stream;
 mod types;
+mod webhook;
 
 mod accept_json;
 mod error;
@@ -142,12 +146,23 @@ pub(crate) struct Server {
   https: bool,
   #[arg(long, help = "Redirect HTTP traffic to HTTPS.")]
   redirect_http_to_https: bool,
+  #[arg(
+    long,
+    help = "Enable the webhook API, posting BRC20 and BRC20S receipts to registered URLs."
+  )]
+  enable_webhooks: bool,
+  #[arg(
+    long,
+    help = "Follow the mempool of Bitcoin Core and add the pending BRC20 and BRC20S state to balance and transferable responses."
+  )]
+  enable_mempool_watcher: bool,
 }
 
 impl Server {
   pub(crate) fn run(self, options: Options, index: Arc<Index>, handle: Handle) -> SubcommandResult {
     Runtime::new()?.block_on(async {
       let index_clone = index.clone();
+      let enable_mempool_watcher = self.enable_mempool_watcher;
       let index_thread = thread::spawn(move || loop {
         if SHUTTING_DOWN.load(atomic::Ordering::Relaxed) {
           break;
@@ -155,6 +170,11 @@ impl Server {
         if let Err(error) = index_clone.update() {
           log::warn!("Updating index: {error}");
         }
+        if enable_mempool_watcher {
+          if let Err(error) = index_clone.update_mempool() {
+            log::warn!("Updating mempool: {error}");
+          }
+        }
 
         thread::sleep(Duration::from_millis(5000));
       });
@@ -167,10 +187,16 @@ impl Server {
           brc20::brc20_all_balance,
           brc20::brc20_tick_info,
           brc20::brc20_all_tick_info,
+          brc20::brc20_tick_holders,
+          brc20::brc20_tick_stats,
+          brc20::brc20_all_tick_stats,
           brc20::brc20_tx_events,
           brc20::brc20_block_events,
+          brc20::brc20_block_state_hash,
           brc20::brc20_transferable,
           brc20::brc20_all_transferable,
+          brc20::brc20_address_events,
+          brc20::brc20_validate,
 
           brc20s::brc20s_tick_info,
           brc20s::brc20s_all_tick_info,
@@ -181,10 +207,12 @@ impl Server {
           brc20s::brc20s_all_pools_by_tid,
           brc20s::brc20s_txid_receipts,
           brc20s::brc20s_block_receipts,
+          brc20s::brc20s_address_receipts,
           brc20s::brc20s_transferable,
           brc20s::brc20s_all_transferable,
           brc20s::brc20s_userinfo,
           brc20s::brc20s_stake_info,
+          brc20s::brc20s_validate,
 
           ord::ord_inscription_id,
           ord::ord_inscription_number,
@@ -193,13 +221,25 @@ impl Server {
           ord::ord_block_inscriptions,
 
           info::node_info,
+          stream::stream,
+          webhook::webhook_subscribe,
+          webhook::webhooks,
+          webhook::webhook,
+          webhook::webhook_unsubscribe,
+          webhook::webhook_deliveries,
+          simulate::simulate,
         ),
         components(schemas(
 
           // BRC20 schemas
           brc20::TickInfo,
           brc20::AllTickInfo,
+          brc20::Holder,
+          brc20::TickHolders,
+          brc20::TickStats,
+          brc20::AllTickStats,
           brc20::Balance,
+          brc20::PendingBalance,
           brc20::AllBalance,
           brc20::TxEvent,
           brc20::DeployEvent,
@@ -209,17 +249,28 @@ impl Server {
           brc20::ErrorEvent,
           brc20::TxEvents,
           brc20::BlockEvents,
+          brc20::StateHash,
+          brc20::AddressEvent,
+          brc20::AddressEvents,
           brc20::TransferableInscription,
           brc20::TransferableInscriptions,
+          brc20::PendingTransferable,
+          brc20::ValidateRequest,
+          brc20::Validation,
 
           // BRC20 responses schemas
           response::BRC20Tick,
           response::BRC20AllTick,
+          response::BRC20TickStats,
+          response::BRC20AllTickStats,
           response::BRC20Balance,
           response::BRC20AllBalance,
           response::BRC20TxEvents,
           response::BRC20BlockEvents,
+          response::BRC20StateHash,
+          response::BRC20AddressEvents,
           response::BRC20Transferable,
+          response::BRC20Validation,
 
           // BRC20S schemas
           brc20s::Tick,
@@ -229,6 +280,7 @@ impl Server {
           brc20s::TickInfo,
           brc20s::AllTickInfo,
           brc20s::Balance,
+          brc20s::PendingBalance,
           brc20s::AllBalance,
           brc20s::Pool,
           brc20s::AllPoolInfo,
@@ -245,11 +297,16 @@ impl Server {
           brc20s::Receipt,
           brc20s::TxReceipts,
           brc20s::BlockReceipts,
+          brc20s::AddressReceipt,
+          brc20s::AddressReceipts,
           brc20s::TransferableInscription,
           brc20s::Transferable,
+          brc20s::PendingTransferable,
           brc20s::UserInfo,
           brc20s::StakedInfo,
           brc20s::StakedPid,
+          brc20s::ValidateRequest,
+          brc20s::Validation,
 
           // BRC20S responses schemas
           response::BRC20STick,
@@ -260,7 +317,9 @@ impl Server {
           response::BRC20SAllPool,
           response::BRC20STxReceipts,
           response::BRC20SBlockReceipts,
+          response::BRC20SAddressReceipts,
           response::BRC20Transferable,
+          response::BRC20SValidation,
 
           // Ord schemas
           ord::OrdInscription,
@@ -279,10 +338,33 @@ impl Server {
           response::OrdOutPointResult,
 
 
+          // Stream schemas
+          stream::BlockNotification,
+          stream::ReorgNotification,
+          stream::InscriptionNotification,
+          stream::BRC20Notification,
+          stream::BRC20SNotification,
+
+          // Webhook schemas
+          webhook::WebhookRequest,
+          webhook::Webhook,
+          webhook::Webhooks,
+          webhook::Delivery,
+          webhook::Deliveries,
+          simulate::SimulateRequest,
+          simulate::Simulation,
+
+          // Webhook responses schemas
+          response::WebhookInfo,
+          response::WebhookList,
+          response::WebhookDeliveries,
+
           // Node Info schemas
           info::NodeInfo,
+          info::ProtocolStatus,
           info::ChainInfo,
           types::ScriptPubkey,
+          types::PendingSpend,
           response::Node,
           ApiError
         ))
@@ -299,12 +381,13 @@ impl Server {
         chain: options.chain(),
         domain: acme_domains.first().cloned(),
       });
-      let api_v1_router = Router::new()
+      let mut api_v1_router = Router::new()
         .route(
           "/api-docs/openapi.json",
           get(|| async { ApiDoc::openapi().to_pretty_json().unwrap() }),
         )
         .route("/node/info", get(info::node_info))
+        .route("/stream", get(stream::stream))
         .route("/ord/id/:id/inscription", get(ord::ord_inscription_id))
         .route(
           "/ord/number/:number/inscription",
@@ -319,104 +402,34 @@ impl Server {
           "/ord/block/:blockhash/inscriptions",
           get(ord::ord_block_inscriptions),
         )
-        .route(
-          "/ord/debug/bitmap/district/:number",
-          get(ord::ord_debug_bitmap_district),
-        )
-        .route("/brc20/tick/:tick", get(brc20::brc20_tick_info))
-        .route("/brc20/tick", get(brc20::brc20_all_tick_info))
-        .route(
-          "/brc20/tick/:tick/address/:address/balance",
-          get(brc20::brc20_balance),
-        )
-        .route(
-          "/brc20/address/:address/balance",
-          get(brc20::brc20_all_balance),
-        )
-        .route(
-          "/brc20/tick/:tick/address/:address/transferable",
-          get(brc20::brc20_transferable),
-        )
-        .route(
-          "/brc20/address/:address/transferable",
-          get(brc20::brc20_all_transferable),
-        )
-        .route("/brc20/tx/:txid/events", get(brc20::brc20_tx_events))
-        .route("/brc20/tx/:txid", get(brc20::brc20_tx))
-        .route(
-          "/brc20/block/:block_hash/events",
-          get(brc20::brc20_block_events),
-        )
-        .route("/brc20s/tick", get(brc20s::brc20s_all_tick_info))
-        .route("/brc20s/tick/:tick_id", get(brc20s::brc20s_tick_info))
-        .route(
-          "/brc20s/debug/tick/:tick_id",
-          get(brc20s::brc20s_debug_tick_info),
-        )
-        .route("/brc20s/pool", get(brc20s::brc20s_all_pool_info))
-        .route("/brc20s/pool/:pid", get(brc20s::brc20s_pool_info))
-        .route(
-          "/brc20s/pool/tid/:tick_id",
-          get(brc20s::brc20s_all_pools_by_tid),
-        )
-        .route(
-          "/brc20s/debug/pool/:pid",
-          get(brc20s::brc20s_debug_pool_info),
-        )
-        .route(
-          "/brc20s/debug/stake/:address/:tick",
-          get(brc20s::brc20s_debug_stake_info),
-        )
-        .route(
-          "/brc20s/pool/:pid/address/:address/userinfo",
-          get(brc20s::brc20s_userinfo),
-        )
-        .route(
-          "/brc20s/debug/pool/:pid/address/:address/reward",
-          get(brc20s::brc20s_user_pending_reward),
-        )
-        .route(
-          "/brc20s/tick/:tick_id/address/:address/balance",
-          get(brc20s::brc20s_balance),
-        )
-        .route(
-          "/brc20s/debug/pool/:pid/address/:address/userinfo",
-          get(brc20s::brc20s_debug_userinfo),
-        )
-        .route(
-          "/brc20s/debug/tick/:tick_id/address/:address/balance",
-          get(brc20s::brc20s_debug_balance),
-        )
-        .route(
-          "/brc20s/address/:address/balance",
-          get(brc20s::brc20s_all_balance),
-        )
-        .route(
-          "/brc20s/tick/:tick_id/address/:address/transferable",
-          get(brc20s::brc20s_transferable),
-        )
-        .route(
-          "/brc20s/address/:address/transferable",
-          get(brc20s::brc20s_all_transferable),
-        )
-        .route(
-          "/brc20s/tx/:txid/receipts",
-          get(brc20s::brc20s_txid_receipts),
-        )
-        .route(
-          "/brc20s/debug/tx/:txid/receipts",
-          get(brc20s::brc20s_debug_txid_receipts),
-        )
-        .route(
-          "/brc20s/block/:blockhash/receipts",
-          get(brc20s::brc20s_block_receipts),
-        )
-        .route(
-          "/brc20s/stake/:address/:tick",
-          get(brc20s::brc20s_stake_info),
-        );
+        .route("/simulate", post(simulate::simulate));
+
+      for protocol in index.protocols() {
+        if let Some(router) = protocol.router {
+          api_v1_router = api_v1_router.merge(router);
+        }
+      }
+
+      if self.enable_webhooks {
+        api_v1_router = api_v1_router
+          .route(
+            "/webhooks",
+            get(webhook::webhooks).post(webhook::webhook_subscribe),
+          )
+          .route(
+            "/webhooks/:id",
+            get(webhook::webhook).delete(webhook::webhook_unsubscribe),
+          )
+          .route("/webhooks/:id/deliveries", get(webhook::webhook_deliveries));
+        webhook::spawn_dispatcher(index.clone());
+      }
 
-      let api_router = Router::new().nest("/v1", api_v1_router);
+      let api_router = Router::new().nest(
+        "/v1",
+        api_v1_router
+          .route_layer(middleware::from_fn(Self::track_request))
+          .with_state(()),
+      );
 
       let router = Router::new()
         .route("/", get(Self::home))
@@ -457,6 +470,8 @@ impl Server {
         .route("/static/*path", get(Self::static_asset))
         .route("/status", get(Self::status))
         .route("/tx/:txid", get(Self::transaction))
+        .route("/metrics", get(Self::metrics))
+        .route_layer(middleware::from_fn(Self::track_request))
         .nest("/api", api_router)
         .layer(Extension(index))
         .layer(Extension(page_config))
@@ -892,6 +907,22 @@ impl Server {
     }
   }
 
+  async fn metrics() -> impl IntoResponse {
+    (
+      [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+      metrics::render(),
+    )
+  }
+
+  // records the latency of requests by route. Added with `route_layer` to each router, since the
+  // route of a request to a nested router is only known inside of it.
+  async fn track_request<B>(path: MatchedPath, request: Request<B>, next: Next<B>) -> Response {
+    let start = Instant::now();
+    let response = next.run(request).await;
+    metrics::observe_request(path.as_str().into(), start.elapsed());
+    response
+  }
+
   async fn search_by_query(
     Extension(index): Extension<Arc<Index>>,
     Query(search): Query<Search>,
@@ -1916,6 +1947,34 @@ mod tests {
     TestServer::new().assert_response("/status", StatusCode::OK, "OK");
   }
 
+  #[test]
+  fn metrics_endpoint() {
+    let test_server = TestServer::new();
+
+    test_server.get("/blockcount");
+    test_server.get("/api/v1/node/info");
+
+    let response = test_server.get("/metrics");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+      response.headers().get(header::CONTENT_TYPE).unwrap(),
+      "text/plain; version=0.0.4"
+    );
+
+    let metrics = response.text().unwrap();
+    assert_regex_match!(metrics, r"(?s).*\nord_index_height \d+\n.*");
+    assert_regex_match!(metrics, r"(?s).*\nord_chain_tip_height \d+\n.*");
+    for route in ["/blockcount", "/api/v1/node/info"] {
+      assert!(
+        metrics.contains(&format!(
+          "ord_http_request_duration_seconds_count{{route=\"{route}\"}}"
+        )),
+        "{metrics}"
+      );
+    }
+    assert!(!metrics.contains("route=\"/api/*"), "{metrics}");
+  }
+
   #[test]
   fn block_count_endpoint() {
     let test_server = TestServer::new();
@@ -2393,15 +2452,15 @@ mod tests {
   fn detect_unrecoverable_reorg() {
     let test_server = TestServer::new();
 
-    test_server.mine_blocks(21);
+    test_server.mine_blocks(30);
 
     test_server.assert_response("/status", StatusCode::OK, "OK");
 
-    for _ in 0..15 {
+    for _ in 0..25 {
       test_server.bitcoin_rpc_server.invalidate_tip();
     }
 
-    test_server.bitcoin_rpc_server.mine_blocks(21);
+    test_server.bitcoin_rpc_server.mine_blocks(30);
 
     test_server.assert_response_regex("/status", StatusCode::OK, "unrecoverable reorg detected.*");
   }
@@ -3158,6 +3217,676 @@ mod tests {
     );
   }
 
+  #[test]
+  fn stream_pushes_committed_blocks() {
+    let server = TestServer::new();
+
+    assert_eq!(
+      server.get("/api/v1/stream?tick=toolong").status(),
+      StatusCode::BAD_REQUEST
+    );
+
+    let response = reqwest::blocking::get(server.join_url("/api/v1/stream")).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let blocks = server.mine_blocks(1);
+
+    let mut lines = io::BufRead::lines(io::BufReader::new(response));
+    assert_eq!(lines.next().unwrap().unwrap(), "event:block");
+    assert_eq!(
+      lines.next().unwrap().unwrap(),
+      format!(
+        "data:{{\"height\":1,\"blockhash\":\"{}\"}}",
+        blocks[0].block_hash()
+      )
+    );
+  }
+
+  #[test]
+  fn webhooks_post_matching_receipts_and_reverts() {
+    let received = Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let hook_url = format!("http://{}/hook", listener.local_addr().unwrap());
+    {
+      let received = received.clone();
+      let router = Router::new().route(
+        "/hook",
+        axum::routing::post(move |body: String| async move {
+          received.lock().unwrap().push(body);
+        }),
+      );
+      thread::spawn(|| {
+        Runtime::new().unwrap().block_on(async {
+          axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(router.into_make_service())
+            .await
+            .unwrap()
+        })
+      });
+    }
+
+    let wait_for = |n: usize| {
+      for _ in 0..200 {
+        if received.lock().unwrap().len() >= n {
+          break;
+        }
+        thread::sleep(Duration::from_millis(50));
+      }
+      received
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|body| serde_json::from_str::<serde_json::Value>(body).unwrap())
+        .collect::<Vec<serde_json::Value>>()
+    };
+
+    let server = TestServer::new_server(
+      test_bitcoincore_rpc::builder()
+        .network(bitcoin::network::constants::Network::Regtest)
+        .build(),
+      None,
+      &["--chain", "regtest", "--enable-index-brc20"],
+      &["--enable-webhooks"],
+    );
+
+    let register = |body: String| {
+      reqwest::blocking::Client::new()
+        .post(server.join_url("/api/v1/webhooks"))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .unwrap()
+    };
+
+    assert_eq!(
+      register(format!(r#"{{"url":"{hook_url}"}}"#)).status(),
+      StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+      register(r#"{"url":"https://127.0.0.1/hook","tick":"ordi"}"#.into()).status(),
+      StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+      register(format!(r#"{{"url":"{hook_url}","tick":"toolong"}}"#)).status(),
+      StatusCode::BAD_REQUEST
+    );
+
+    let response = register(format!(r#"{{"url":"{hook_url}","tick":"ordi"}}"#));
+    assert_eq!(response.status(), StatusCode::OK);
+    let webhook = response.json::<serde_json::Value>().unwrap()["data"]["id"]
+      .as_u64()
+      .unwrap();
+    register(format!(r#"{{"url":"{hook_url}","tick":"sats"}}"#));
+
+    server.mine_blocks(1);
+
+    let txid = server.bitcoin_rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(
+        1,
+        0,
+        0,
+        inscription(
+          "text/plain;charset=utf-8",
+          r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"21000000","lim":"1000"}"#,
+        )
+        .to_witness(),
+      )],
+      ..Default::default()
+    });
+
+    server.mine_blocks(1);
+
+    let events = wait_for(1);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["subscription"], webhook);
+    assert_eq!(events[0]["type"], "brc20");
+    assert_eq!(events[0]["height"], 2);
+    assert_eq!(events[0]["data"]["txid"], txid.to_string());
+    assert_eq!(events[0]["data"]["event"]["type"], "deploy");
+
+    let deliveries =
+      server.get_json::<serde_json::Value>(format!("/api/v1/webhooks/{webhook}/deliveries"));
+    assert_eq!(deliveries["data"]["total"], 1);
+    assert_eq!(deliveries["data"]["deliveries"][0]["status"], "delivered");
+    assert_eq!(deliveries["data"]["deliveries"][0]["attempts"], 1);
+
+    server.bitcoin_rpc_server.invalidate_tip();
+    server.mine_blocks(2);
+
+    let events = wait_for(2);
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[1]["type"], "revert");
+    assert_eq!(events[1]["height"], 2);
+    assert_eq!(events[1]["delivery"], 0);
+    assert_eq!(events[1]["data"], events[0]);
+
+    assert_eq!(
+      reqwest::blocking::Client::new()
+        .delete(server.join_url(&format!("/api/v1/webhooks/{webhook}")))
+        .send()
+        .unwrap()
+        .status(),
+      StatusCode::OK
+    );
+    assert_eq!(
+      server.get(format!("/api/v1/webhooks/{webhook}")).status(),
+      StatusCode::NOT_FOUND
+    );
+  }
+
+  #[test]
+  fn brc20_block_state_hash() {
+    let server = TestServer::new_server(
+      test_bitcoincore_rpc::builder()
+        .network(bitcoin::network::constants::Network::Regtest)
+        .build(),
+      None,
+      &["--chain", "regtest", "--enable-index-brc20"],
+      &[],
+    );
+
+    server.mine_blocks(1);
+
+    let state_hash = |height: u64| {
+      server.get_json::<serde_json::Value>(format!("/api/v1/brc20/block/{height}/state_hash"))
+        ["data"]["stateHash"]
+        .as_str()
+        .unwrap()
+        .to_string()
+    };
+
+    let before = state_hash(1);
+
+    server.bitcoin_rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(
+        1,
+        0,
+        0,
+        inscription(
+          "text/plain;charset=utf-8",
+          r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"21000000","lim":"1000"}"#,
+        )
+        .to_witness(),
+      )],
+      ..Default::default()
+    });
+    server.mine_blocks(1);
+
+    let deployed = state_hash(2);
+    assert_eq!(state_hash(1), before);
+    assert_ne!(deployed, before);
+
+    let response = server.get_json::<serde_json::Value>("/api/v1/brc20/block/2/state_hash");
+    assert_eq!(response["data"]["height"], 2);
+    assert_eq!(
+      response["data"]["blockhash"],
+      server
+        .index
+        .block_hash(Some(2))
+        .unwrap()
+        .unwrap()
+        .to_string()
+    );
+
+    let info = server.get_json::<serde_json::Value>("/api/v1/node/info");
+    assert_eq!(info["data"]["chainInfo"]["stateHash"], deployed);
+
+    assert_eq!(
+      server.get("/api/v1/brc20/block/3/state_hash").status(),
+      StatusCode::NOT_FOUND
+    );
+
+    // replace the deploy block with an empty one.
+    server.bitcoin_rpc_server.invalidate_tip();
+    server.mine_blocks(2);
+
+    assert_eq!(state_hash(1), before);
+    assert_ne!(state_hash(2), deployed);
+    assert_ne!(state_hash(3), state_hash(2));
+  }
+
+  #[test]
+  fn transaction_and_block_queries_are_served_from_the_index() {
+    let server = TestServer::new_server(
+      test_bitcoincore_rpc::builder()
+        .network(bitcoin::network::constants::Network::Regtest)
+        .build(),
+      None,
+      &[
+        "--chain",
+        "regtest",
+        "--enable-index-brc20",
+        "--enable-save-ord-receipts",
+      ],
+      &[],
+    );
+
+    server.mine_blocks(2);
+
+    let deploy = server.bitcoin_rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(
+        1,
+        0,
+        0,
+        inscription(
+          "text/plain;charset=utf-8",
+          r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"21000000","lim":"1000"}"#,
+        )
+        .to_witness(),
+      )],
+      ..Default::default()
+    });
+    let plain = server.bitcoin_rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(2, 0, 0, Default::default())],
+      ..Default::default()
+    });
+    let block = server.mine_blocks(1)[0].block_hash();
+    server.mine_blocks(1);
+
+    let events = server.get_json::<serde_json::Value>(format!("/api/v1/brc20/tx/{deploy}/events"));
+    assert_eq!(events["data"]["events"][0]["type"], "deploy");
+
+    let tx = server.get_json::<serde_json::Value>(format!("/api/v1/brc20/tx/{deploy}"));
+    assert_eq!(tx["data"]["confirmed"], true);
+    assert_eq!(tx["data"]["blockhash"], block.to_string());
+    assert_eq!(tx["data"]["inscriptions"][0]["action"], "inscribe");
+    assert_eq!(
+      tx["data"]["inscriptions"][0]["operation"],
+      serde_json::json!({"type": "deploy", "tick": "ordi", "max": "21000000", "lim": "1000"})
+    );
+
+    let inscription =
+      server.get_json::<serde_json::Value>(format!("/api/v1/ord/id/{deploy}i0/inscription"));
+    assert_eq!(
+      inscription["data"]["contentType"],
+      "text/plain;charset=utf-8"
+    );
+    assert!(inscription["data"]["owner"].is_object());
+
+    // transactions without inscription operations have no indexed height.
+    assert_eq!(
+      server
+        .get(format!("/api/v1/brc20/tx/{plain}/events"))
+        .status(),
+      StatusCode::NOT_FOUND
+    );
+
+    let block_events =
+      server.get_json::<serde_json::Value>(format!("/api/v1/brc20/block/{block}/events"));
+    assert_eq!(block_events["data"]["block"][0]["txid"], deploy.to_string());
+    assert_eq!(block_events["data"]["block"].as_array().unwrap().len(), 1);
+
+    let empty = server.index.block_hash(Some(4)).unwrap().unwrap();
+    assert_eq!(
+      server.get_json::<serde_json::Value>(format!("/api/v1/brc20/block/{empty}/events"))["data"]
+        ["block"],
+      serde_json::json!([])
+    );
+
+    // replace the deploy block, its hash is no longer part of the indexed chain.
+    server.bitcoin_rpc_server.invalidate_tip();
+    server.bitcoin_rpc_server.invalidate_tip();
+    server.mine_blocks(3);
+    assert_eq!(
+      server
+        .get(format!("/api/v1/brc20/block/{block}/events"))
+        .status(),
+      StatusCode::NOT_FOUND
+    );
+
+    let info = server.get_json::<serde_json::Value>("/api/v1/node/info?btc=true");
+    assert_eq!(info["data"]["chainInfo"]["ordHeight"], 5);
+    assert_eq!(info["data"]["chainInfo"]["chainHeight"], 5);
+  }
+
+  #[test]
+  fn simulate_returns_receipts_without_committing() {
+    let server = TestServer::new_server(
+      test_bitcoincore_rpc::builder()
+        .network(bitcoin::network::constants::Network::Regtest)
+        .build(),
+      None,
+      &["--chain", "regtest", "--enable-index-brc20"],
+      &[],
+    );
+
+    server.mine_blocks(1);
+
+    server.bitcoin_rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(
+        1,
+        0,
+        0,
+        inscription(
+          "text/plain;charset=utf-8",
+          r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"21000000","lim":"1000"}"#,
+        )
+        .to_witness(),
+      )],
+      ..Default::default()
+    });
+    server.mine_blocks(1);
+
+    let simulate = |body: String| {
+      reqwest::blocking::Client::new()
+        .post(server.join_url("/api/v1/simulate"))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .send()
+        .unwrap()
+    };
+
+    assert_eq!(simulate("{}".into()).status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+      simulate(r#"{"tx":"00"}"#.into()).status(),
+      StatusCode::BAD_REQUEST
+    );
+
+    let txid = server.bitcoin_rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(
+        2,
+        0,
+        0,
+        inscription(
+          "text/plain;charset=utf-8",
+          r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"1000"}"#,
+        )
+        .to_witness(),
+      )],
+      ..Default::default()
+    });
+    let tx = server.bitcoin_rpc_server.mempool().pop().unwrap();
+    assert_eq!(tx.txid(), txid);
+
+    let state_hash = server.index.state_hash(None).unwrap();
+
+    let response = simulate(format!(
+      r#"{{"tx":"{}"}}"#,
+      bitcoin::consensus::encode::serialize_hex(&tx)
+    ));
+    assert_eq!(response.status(), StatusCode::OK);
+    let simulation = response.json::<serde_json::Value>().unwrap()["data"].clone();
+
+    assert_eq!(simulation["txid"], txid.to_string());
+    assert_eq!(simulation["height"], 3);
+    assert_eq!(simulation["inscriptions"].as_array().unwrap().len(), 1);
+    assert_eq!(
+      simulation["inscriptions"][0]["inscriptionId"],
+      InscriptionId { txid, index: 0 }.to_string()
+    );
+    assert_eq!(simulation["brc20"].as_array().unwrap().len(), 1);
+    assert_eq!(simulation["brc20"][0]["type"], "mint");
+    assert_eq!(simulation["brc20"][0]["amount"], "1000000000000000000000");
+    assert_eq!(simulation["brc20"][0]["valid"], true);
+
+    assert_eq!(server.index.state_hash(None).unwrap(), state_hash);
+    assert_eq!(
+      server.get_json::<serde_json::Value>("/api/v1/brc20/tick/ordi")["data"]["minted"],
+      "0"
+    );
+
+    server.mine_blocks(1);
+
+    let events = server.get_json::<serde_json::Value>(format!("/api/v1/brc20/tx/{txid}/events"));
+    assert_eq!(events["data"]["events"], simulation["brc20"]);
+  }
+
+  #[test]
+  fn validate_returns_the_error_the_operation_would_record() {
+    let server = TestServer::new_server(
+      test_bitcoincore_rpc::builder()
+        .network(bitcoin::network::constants::Network::Regtest)
+        .build(),
+      None,
+      &[
+        "--chain",
+        "regtest",
+        "--enable-index-brc20",
+        "--enable-index-brc20s",
+      ],
+      &[],
+    );
+
+    let coinbase = server.mine_blocks(1)[0].txdata[0].txid();
+
+    let address = "bcrt1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw";
+
+    let validate_funded = |protocol: &str, address: &str, payload: &str, funding: &str| {
+      reqwest::blocking::Client::new()
+        .post(server.join_url(&format!("/api/v1/{protocol}/validate")))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(
+          serde_json::json!({ "address": address, "payload": payload, "funding": funding })
+            .to_string(),
+        )
+        .send()
+        .unwrap()
+    };
+
+    let validate = |protocol: &str, address: &str, payload: &str| {
+      reqwest::blocking::Client::new()
+        .post(server.join_url(&format!("/api/v1/{protocol}/validate")))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(serde_json::json!({ "address": address, "payload": payload }).to_string())
+        .send()
+        .unwrap()
+    };
+
+    let deploy = r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"21000000","lim":"1000"}"#;
+
+    assert_eq!(
+      validate(
+        "brc20",
+        "bc1qs758ursh4q9z627kt3pp5yysm78ddny6txaqgw",
+        deploy
+      )
+      .status(),
+      StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+      validate("brc20", address, r#"{"p":"brc-20","op":"deploy"}"#).status(),
+      StatusCode::BAD_REQUEST
+    );
+
+    let response = validate("brc20", address, deploy);
+    assert_eq!(response.status(), StatusCode::OK);
+    let validation = response.json::<serde_json::Value>().unwrap()["data"].clone();
+    assert_eq!(validation["height"], 2);
+    assert_eq!(validation["valid"], true);
+    assert_eq!(validation["msg"], "ok");
+    assert_eq!(validation["error"], serde_json::Value::Null);
+
+    let state_hash = server.index.state_hash(None).unwrap();
+
+    server.bitcoin_rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(
+        1,
+        0,
+        0,
+        inscription("text/plain;charset=utf-8", deploy).to_witness(),
+      )],
+      ..Default::default()
+    });
+    server.mine_blocks(1);
+
+    assert_ne!(server.index.state_hash(None).unwrap(), state_hash);
+
+    let validation = validate("brc20", address, deploy)
+      .json::<serde_json::Value>()
+      .unwrap()["data"]
+      .clone();
+    assert_eq!(validation["height"], 3);
+    assert_eq!(validation["valid"], false);
+    assert_eq!(validation["msg"], "tick: ordi has been existed");
+    assert_eq!(
+      validation["error"],
+      serde_json::json!({ "DuplicateTick": "ordi" })
+    );
+
+    let validation = validate(
+      "brc20",
+      address,
+      r#"{"p":"brc-20","op":"transfer","tick":"ordi","amt":"10"}"#,
+    )
+    .json::<serde_json::Value>()
+    .unwrap()["data"]
+      .clone();
+    assert_eq!(validation["valid"], false);
+    assert_eq!(
+      validation["error"],
+      serde_json::json!({ "InsufficientBalance": ["0", "10000000000000000000"] })
+    );
+
+    let validation = validate(
+      "brc20s",
+      address,
+      r#"{"p":"brc20-s","op":"deposit","pid":"0000000000#01","amt":"10"}"#,
+    )
+    .json::<serde_json::Value>()
+    .unwrap()["data"]
+      .clone();
+    assert_eq!(validation["valid"], false);
+    assert_eq!(validation["msg"], "pool 0000000000#01 is not exist");
+
+    // a pool deploy checks that the commit input belongs to the deployer.
+    let pid = format!(
+      "{}#01",
+      &bitcoin::hashes::sha256::Hash::hash(format!("earn2100000018{address}{address}").as_bytes())
+        .to_string()[..10]
+    );
+    let validation = validate(
+      "brc20s",
+      address,
+      &format!(
+        r#"{{"p":"brc20-s","op":"deploy","t":"pool","pid":"{pid}","stake":"ordi","earn":"earn","erate":"10","dmax":"1000","total":"21000000","only":"1"}}"#
+      ),
+    )
+    .json::<serde_json::Value>()
+    .unwrap()["data"]
+      .clone();
+    assert_eq!(validation["msg"], "ok");
+    assert_eq!(validation["valid"], true);
+
+    // with a real funding output, the commit input belongs to whoever owns that output.
+    let validation = validate_funded(
+      "brc20s",
+      address,
+      &format!(
+        r#"{{"p":"brc20-s","op":"deploy","t":"pool","pid":"{pid}","stake":"ordi","earn":"earn","erate":"10","dmax":"1000","total":"21000000","only":"1"}}"#
+      ),
+      &format!("{coinbase}:0"),
+    )
+    .json::<serde_json::Value>()
+    .unwrap()["data"]
+      .clone();
+    assert_eq!(validation["valid"], false);
+
+    assert_eq!(
+      validate_funded("brc20", address, deploy, "foo").status(),
+      StatusCode::BAD_REQUEST
+    );
+
+    assert_eq!(
+      server.get_json::<serde_json::Value>("/api/v1/brc20s/pool")["data"]["total"],
+      0
+    );
+  }
+
+  #[test]
+  fn brc20_tick_stats() {
+    let server = TestServer::new_server(
+      test_bitcoincore_rpc::builder()
+        .network(bitcoin::network::constants::Network::Regtest)
+        .build(),
+      None,
+      &["--chain", "regtest", "--enable-index-brc20"],
+      &[],
+    );
+
+    server.mine_blocks(1);
+
+    let inscribe = |block: usize, content: &str| {
+      server.bitcoin_rpc_server.broadcast_tx(TransactionTemplate {
+        inputs: &[(
+          block,
+          0,
+          0,
+          inscription("text/plain;charset=utf-8", content).to_witness(),
+        )],
+        ..Default::default()
+      });
+      server.mine_blocks(1);
+    };
+
+    inscribe(
+      1,
+      r#"{"p":"brc-20","op":"deploy","tick":"ordi","max":"21000000","lim":"1000"}"#,
+    );
+    inscribe(
+      2,
+      r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"1000"}"#,
+    );
+    inscribe(3, r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"500"}"#);
+    inscribe(
+      4,
+      r#"{"p":"brc-20","op":"transfer","tick":"ordi","amt":"300"}"#,
+    );
+
+    let stats = server.get_json::<serde_json::Value>("/api/v1/brc20/tick/ORDI/stats");
+    assert_eq!(stats["data"]["mintCount"], 2);
+    assert_eq!(stats["data"]["inscribeTransferCount"], 1);
+    assert_eq!(stats["data"]["transferCount"], 0);
+    assert_eq!(stats["data"]["holders"], 1);
+    assert_eq!(stats["data"]["minted"], "1500000000000000000000");
+    assert_eq!(stats["data"]["transferableSupply"], "300000000000000000000");
+    assert_eq!(stats["data"]["progress"], 1500.0 / 21000000.0);
+    assert_eq!(stats["data"]["latestActivityHeight"], 5);
+
+    server.bitcoin_rpc_server.broadcast_tx(TransactionTemplate {
+      inputs: &[(5, 1, 0, Default::default())],
+      ..Default::default()
+    });
+    server.mine_blocks(1);
+
+    let stats = server.get_json::<serde_json::Value>("/api/v1/brc20/tick/ordi/stats");
+    assert_eq!(stats["data"]["transferCount"], 1);
+    assert_eq!(stats["data"]["transferableSupply"], "0");
+    assert_eq!(stats["data"]["latestActivityHeight"], 6);
+
+    inscribe(
+      6,
+      r#"{"p":"brc-20","op":"deploy","tick":"sats","max":"1000","lim":"1000"}"#,
+    );
+    inscribe(
+      7,
+      r#"{"p":"brc-20","op":"mint","tick":"sats","amt":"1000"}"#,
+    );
+
+    let ticks = |sort: &str| {
+      server.get_json::<serde_json::Value>(format!("/api/v1/brc20/stats?sort={sort}"))["data"]
+        ["ticks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tick| tick["tick"].as_str().unwrap().to_string())
+        .collect::<Vec<String>>()
+    };
+
+    assert_eq!(ticks("progress"), ["sats", "ordi"]);
+    assert_eq!(ticks("activity"), ["ordi", "sats"]);
+
+    assert_eq!(
+      server.get("/api/v1/brc20/stats?sort=name").status(),
+      StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+      server.get("/api/v1/brc20/tick/none/stats").status(),
+      StatusCode::NOT_FOUND
+    );
+  }
+
   #[test]
   fn brc20_endpoint() {
     let test_server = TestServer::new();
@@ -3165,12 +3894,14 @@ mod tests {
     for url in [
       "/brc20/tick/🍎",
       "/brc20/tick",
+      "/brc20/tick/ordi/holders",
       "/brc20/tick/ordi/address/bc1pjdmfs5lvqfl6qmzpc0e4ewfdgfmdyz2t79scrsaz8ep98374wwnsywz7t4/balance",
       "/brc20/address/bc1pjdmfs5lvqfl6qmzpc0e4ewfdgfmdyz2t79scrsaz8ep98374wwnsywz7t4/balance",
       "/brc20/tx/b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735",
       "/brc20/tick/ordi/address/bc1pjdmfs5lvqfl6qmzpc0e4ewfdgfmdyz2t79scrsaz8ep98374wwnsywz7t4/transferable",
       "/brc20/block/00000000000000000003a337a676b0101f3f7ef7dcbc01debb69f85c6da04dcf",
-      "/brc20/address/bc1pjdmfs5lvqfl6qmzpc0e4ewfdgfmdyz2t79scrsaz8ep98374wwnsywz7t4/transferable"
+      "/brc20/address/bc1pjdmfs5lvqfl6qmzpc0e4ewfdgfmdyz2t79scrsaz8ep98374wwnsywz7t4/transferable",
+      "/brc20/address/bc1pjdmfs5lvqfl6qmzpc0e4ewfdgfmdyz2t79scrsaz8ep98374wwnsywz7t4/events?tick=ordi&op=mint"
     ] {
 
       println!("{}", url);
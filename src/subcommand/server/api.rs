@@ -8,6 +8,49 @@ pub struct Pagination {
   pub limit: Option<usize>,
 }
 
+/// The number of entries of a page when its limit isn't set.
+pub(crate) const DEFAULT_PAGE_LIMIT: usize = 100;
+
+#[derive(Deserialize, IntoParams)]
+pub struct CursorPagination {
+  /// The cursor returned with the previous page, to continue after its last entry.
+  pub cursor: Option<u64>,
+  /// Limit of the result, defaults to 100.
+  pub limit: Option<usize>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct HeightQuery {
+  /// Query the state at the block height, defaults to the latest indexed block.
+  pub height: Option<u64>,
+}
+
+impl HeightQuery {
+  /// Returns the queried height, which must not be above the indexed height, nor below the height
+  /// a migrated index started recording balance history at.
+  pub(super) fn checked_height(&self, index: &Index) -> Result<Option<u64>, ApiError> {
+    let Some(height) = self.height else {
+      return Ok(None);
+    };
+
+    if index
+      .block_height()?
+      .map_or(true, |indexed| height > indexed.n())
+    {
+      return Err(ApiError::bad_request(format!(
+        "height {height} has not been indexed yet"
+      )));
+    }
+
+    match index.migration_height()? {
+      Some(migrated) if height < migrated => Err(ApiError::bad_request(format!(
+        "height {height} predates the balance history, which starts at height {migrated}"
+      ))),
+      _ => Ok(Some(height)),
+    }
+  }
+}
+
 pub(crate) type ApiResult<T> = Result<axum::Json<ApiResponse<T>>, ApiError>;
 
 pub(super) trait ApiOptionExt<T> {
@@ -1,4 +1,12 @@
-use {super::*, crate::okx::datastore::brc20::Tick, axum::Json, utoipa::ToSchema};
+use {
+  super::*,
+  crate::okx::datastore::{
+    brc20::{self as brc20_store, Tick},
+    ScriptKey,
+  },
+  axum::Json,
+  utoipa::ToSchema,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -15,17 +23,39 @@ pub struct Balance {
   /// Overall balance.
   #[schema(format = "uint64")]
   pub overall_balance: String,
+  /// The balance once the pending transactions are confirmed. Only present when the mempool watcher is enabled.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[schema(value_type = Option<brc20::PendingBalance>)]
+  pub pending: Option<PendingBalance>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[schema(as = brc20::PendingBalance)]
+pub struct PendingBalance {
+  /// Available balance.
+  #[schema(format = "uint64")]
+  pub available_balance: String,
+  /// Transferable balance.
+  #[schema(format = "uint64")]
+  pub transferable_balance: String,
+  /// Overall balance.
+  #[schema(format = "uint64")]
+  pub overall_balance: String,
+  /// The pending transactions changing the balance.
+  pub txids: Vec<String>,
 }
 
 /// Get the ticker balance of the address.
 ///
-/// Retrieve the asset balance of the 'ticker' for the address.
+/// Retrieve the asset balance of the 'ticker' for the address. Without a height, the balance includes a pending section when the mempool watcher is enabled.
 #[utoipa::path(
     get,
     path = "/api/v1/brc20/tick/{ticker}/address/{address}/balance",
     params(
-        ("ticker" = String, Path, description = "Token ticker", min_length = 4, max_length = 4),
-        ("address" = String, Path, description = "Address")
+        ("ticker" = String, Path, description = "Token ticker", min_length = 4, max_length = 5),
+        ("address" = String, Path, description = "Address"),
+        HeightQuery
   ),
     responses(
       (status = 200, description = "Obtain account balance by query ticker.", body = BRC20Balance),
@@ -37,8 +67,14 @@ pub struct Balance {
 pub(crate) async fn brc20_balance(
   Extension(index): Extension<Arc<Index>>,
   Path((tick, address)): Path<(String, String)>,
+  Query(query): Query<HeightQuery>,
 ) -> ApiResult<Balance> {
-  log::debug!("rpc: get brc20_balance: {} {}", tick, address);
+  log::debug!(
+    "rpc: get brc20_balance: {} {} {:?}",
+    tick,
+    address,
+    query.height
+  );
 
   let tick =
     Tick::from_str(&tick).map_err(|_| ApiError::bad_request(BRC20Error::IncorrectTickFormat))?;
@@ -47,19 +83,46 @@ pub(crate) async fn brc20_balance(
     .and_then(|address| address.require_network(index.get_chain_network()))
     .map_err(ApiError::bad_request)?;
 
-  let balance = index
-    .brc20_get_balance_by_address(&tick, &address)?
-    .ok_or_api_not_found(BRC20Error::BalanceNotFound)?;
+  let height = query.checked_height(&index)?;
+
+  let overlay = query.height.is_none().then(|| index.pending()).flatten();
+  let pending = overlay
+    .as_ref()
+    .and_then(|overlay| overlay.brc20(ScriptKey::from_address(address.clone()), &tick));
+
+  let balance = match index.brc20_get_balance_by_address(&tick, &address, height)? {
+    Some(balance) => balance,
+    None => match pending.and_then(|pending| pending.balance.as_ref()) {
+      Some(pending) => brc20_store::Balance::new(&pending.tick),
+      None => return Err(ApiError::not_found(BRC20Error::BalanceNotFound)),
+    },
+  };
 
   let available_balance = balance.overall_balance - balance.transferable_balance;
 
   log::debug!("rpc: get brc20_balance: {} {} {:?}", tick, address, balance);
 
+  let pending = overlay.as_ref().map(|_| {
+    let pending_balance = pending
+      .and_then(|pending| pending.balance.as_ref())
+      .unwrap_or(&balance);
+    PendingBalance {
+      available_balance: (pending_balance.overall_balance - pending_balance.transferable_balance)
+        .to_string(),
+      transferable_balance: pending_balance.transferable_balance.to_string(),
+      overall_balance: pending_balance.overall_balance.to_string(),
+      txids: pending
+        .map(|pending| pending.txids.iter().map(ToString::to_string).collect())
+        .unwrap_or_default(),
+    }
+  });
+
   Ok(Json(ApiResponse::ok(Balance {
     tick: balance.tick.to_string(),
     available_balance: available_balance.to_string(),
     transferable_balance: balance.transferable_balance.to_string(),
     overall_balance: balance.overall_balance.to_string(),
+    pending,
   })))
 }
 
@@ -78,7 +141,8 @@ pub struct AllBalance {
     get,
     path = "/api/v1/brc20/address/{address}/balance",
     params(
-        ("address" = String, Path, description = "Address")
+        ("address" = String, Path, description = "Address"),
+        HeightQuery
   ),
     responses(
       (status = 200, description = "Obtain account balances by query address.", body = BRC20AllBalance),
@@ -90,14 +154,17 @@ pub struct AllBalance {
 pub(crate) async fn brc20_all_balance(
   Extension(index): Extension<Arc<Index>>,
   Path(address): Path<String>,
+  Query(query): Query<HeightQuery>,
 ) -> ApiResult<AllBalance> {
-  log::debug!("rpc: get brc20_all_balance: {}", address);
+  log::debug!("rpc: get brc20_all_balance: {} {:?}", address, query.height);
 
   let address: bitcoin::Address = Address::from_str(&address)
     .and_then(|address| address.require_network(index.get_chain_network()))
     .map_err(ApiError::bad_request)?;
 
-  let all_balance = index.brc20_get_all_balance_by_address(&address)?;
+  let height = query.checked_height(&index)?;
+
+  let all_balance = index.brc20_get_all_balance_by_address(&address, height)?;
 
   log::debug!("rpc: get brc20_all_balance: {} {:?}", address, all_balance);
 
@@ -109,6 +176,7 @@ pub(crate) async fn brc20_all_balance(
         available_balance: (bal.overall_balance - bal.transferable_balance).to_string(),
         transferable_balance: bal.transferable_balance.to_string(),
         overall_balance: bal.overall_balance.to_string(),
+        pending: None,
       })
       .collect(),
   })))
@@ -0,0 +1,80 @@
+use {super::*, crate::okx::datastore::brc20::Tick, axum::Json, utoipa::ToSchema};
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[schema(as = brc20::Holder)]
+pub struct Holder {
+  pub owner: ScriptPubkey,
+  /// Available balance.
+  #[schema(format = "uint64")]
+  pub available_balance: String,
+  /// Transferable balance.
+  #[schema(format = "uint64")]
+  pub transferable_balance: String,
+  /// Overall balance.
+  #[schema(format = "uint64")]
+  pub overall_balance: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[schema(as = brc20::TickHolders)]
+pub struct TickHolders {
+  /// Name of the ticker.
+  pub tick: String,
+  #[schema(value_type = Vec<brc20::Holder>)]
+  pub holders: Vec<Holder>,
+  /// The total number of holders.
+  pub total: usize,
+}
+
+/// Get the holders of the ticker.
+///
+/// Retrieve the addresses holding the ticker, sorted by overall balance in descending order.
+#[utoipa::path(
+    get,
+    path = "/api/v1/brc20/tick/{ticker}/holders",
+    params(
+        ("ticker" = String, Path, description = "Token ticker", min_length = 4, max_length = 5),
+        Pagination
+  ),
+    responses(
+      (status = 200, description = "Obtain the holders of the ticker.", body = BRC20TickHolders),
+      (status = 400, description = "Bad query.", body = ApiError, example = json!(&ApiError::bad_request(BRC20Error::IncorrectTickFormat))),
+      (status = 404, description = "Ticker not found.", body = ApiError, example = json!(&ApiError::not_found(BRC20Error::TickNotFound))),
+      (status = 500, description = "Internal server error.", body = ApiError, example = json!(&ApiError::internal("internal error"))),
+    )
+  )]
+pub(crate) async fn brc20_tick_holders(
+  Extension(index): Extension<Arc<Index>>,
+  Path(tick): Path<String>,
+  Query(page): Query<Pagination>,
+) -> ApiResult<TickHolders> {
+  log::debug!("rpc: get brc20_tick_holders: {}", tick);
+
+  let tick =
+    Tick::from_str(&tick).map_err(|_| ApiError::bad_request(BRC20Error::IncorrectTickFormat))?;
+
+  let tick_info = index
+    .brc20_get_tick_info(&tick)?
+    .ok_or_api_not_found(BRC20Error::TickNotFound)?;
+
+  let (holders, total) =
+    index.brc20_get_tick_holders(&tick, page.start.unwrap_or(0), page.limit)?;
+
+  log::debug!("rpc: get brc20_tick_holders: {} {:?}", tick, holders);
+
+  Ok(Json(ApiResponse::ok(TickHolders {
+    tick: tick_info.tick.to_string(),
+    holders: holders
+      .into_iter()
+      .map(|(script_key, bal)| Holder {
+        owner: script_key.into(),
+        available_balance: (bal.overall_balance - bal.transferable_balance).to_string(),
+        transferable_balance: bal.transferable_balance.to_string(),
+        overall_balance: bal.overall_balance.to_string(),
+      })
+      .collect(),
+    total,
+  })))
+}
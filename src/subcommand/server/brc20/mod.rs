@@ -1,13 +1,20 @@
-use super::{types::ScriptPubkey, *};
+use super::{
+  types::{PendingSpend, ScriptPubkey},
+  *,
+};
 mod balance;
+mod holders;
 mod receipt;
+mod state_hash;
+mod stats;
 mod ticker;
 mod transaction;
 mod transferable;
+mod validate;
 
 #[derive(Debug, thiserror::Error)]
 pub(super) enum BRC20Error {
-  #[error("ticker must be 4 bytes length")]
+  #[error("ticker must be 4 bytes length, or 5 for self mint tickers")]
   IncorrectTickFormat,
   #[error("tick not found")]
   TickNotFound,
@@ -19,6 +26,55 @@ pub(super) enum BRC20Error {
   EventsNotFound,
   #[error("block not found")]
   BlockNotFound,
+  #[error("operation type must be one of deploy, mint, inscribeTransfer or transfer")]
+  IncorrectOperationType,
+  #[error("sort must be one of progress, holders or activity")]
+  IncorrectStatsSort,
 }
 
-pub(super) use {balance::*, receipt::*, ticker::*, transaction::*, transferable::*};
+pub(super) use {
+  balance::*, holders::*, receipt::*, state_hash::*, stats::*, ticker::*, transaction::*,
+  transferable::*, validate::*,
+};
+
+/// The routes of the BRC20 JSON API.
+pub(crate) fn router() -> Router {
+  Router::new()
+    .route("/brc20/tick/:tick", get(brc20_tick_info))
+    .route("/brc20/tick", get(brc20_all_tick_info))
+    .route("/brc20/tick/:tick/holders", get(brc20_tick_holders))
+    .route("/brc20/tick/:tick/stats", get(brc20_tick_stats))
+    .route("/brc20/stats", get(brc20_all_tick_stats))
+    .route(
+      "/brc20/tick/:tick/address/:address/balance",
+      get(brc20_balance),
+    )
+    .route(
+      "/brc20/address/:address/balance",
+      get(brc20_all_balance),
+    )
+    .route(
+      "/brc20/tick/:tick/address/:address/transferable",
+      get(brc20_transferable),
+    )
+    .route(
+      "/brc20/address/:address/transferable",
+      get(brc20_all_transferable),
+    )
+    .route(
+      "/brc20/address/:address/events",
+      get(brc20_address_events),
+    )
+    // the height shares its path segment name with the block events route.
+    .route(
+      "/brc20/block/:block_hash/state_hash",
+      get(brc20_block_state_hash),
+    )
+    .route("/brc20/tx/:txid/events", get(brc20_tx_events))
+    .route("/brc20/tx/:txid", get(brc20_tx))
+    .route(
+      "/brc20/block/:block_hash/events",
+      get(brc20_block_events),
+    )
+    .route("/brc20/validate", post(brc20_validate))
+}
@@ -1,4 +1,9 @@
-use {super::*, crate::okx::datastore::brc20 as brc20_store, axum::Json, utoipa::ToSchema};
+use {
+  super::*,
+  crate::okx::datastore::brc20 as brc20_store,
+  axum::Json,
+  utoipa::{IntoParams, ToSchema},
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[schema(as = brc20::TxEvent)]
@@ -235,17 +240,21 @@ impl From<&brc20_store::Receipt> for TxEvent {
         from: event.from.clone().into(),
         to: event.to.clone().into(),
         msg: err.to_string(),
-        event: match event.op {
-          brc20_store::OperationType::Deploy => "deploy".to_string(),
-          brc20_store::OperationType::Mint => "mint".to_string(),
-          brc20_store::OperationType::InscribeTransfer => "inscribeTransfer".to_string(),
-          brc20_store::OperationType::Transfer => "transfer".to_string(),
-        },
+        event: event_type(&event.op).to_string(),
       }),
     }
   }
 }
 
+fn event_type(op: &brc20_store::OperationType) -> &'static str {
+  match op {
+    brc20_store::OperationType::Deploy => "deploy",
+    brc20_store::OperationType::Mint => "mint",
+    brc20_store::OperationType::InscribeTransfer => "inscribeTransfer",
+    brc20_store::OperationType::Transfer => "transfer",
+  }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[schema(as = brc20::TxEvents)]
 #[serde(rename_all = "camelCase")]
@@ -320,25 +329,13 @@ pub(crate) async fn brc20_block_events(
   log::debug!("rpc: get brc20_block_events: {}", blockhash);
 
   let blockhash = bitcoin::BlockHash::from_str(&blockhash).map_err(ApiError::bad_request)?;
-  // get block from btc client.
-  let blockinfo = index
-    .get_block_info_by_hash(blockhash)
-    .map_err(ApiError::internal)?
-    .ok_or_api_not_found(BRC20Error::BlockNotFound)?;
-
-  // get blockhash from redb.
-  let blockhash = index
-    .block_hash(Some(u64::try_from(blockinfo.height).unwrap()))
+  let txids = index
+    .get_block_transactions_by_hash(blockhash)
     .map_err(ApiError::internal)?
     .ok_or_api_not_found(BRC20Error::BlockNotFound)?;
 
-  // check blockhash.
-  if blockinfo.hash != blockhash {
-    return Err(ApiError::NotFound(BRC20Error::BlockNotFound.to_string()));
-  }
-
   let block_events = index
-    .brc20_get_txs_events(&blockinfo.tx)
+    .brc20_get_txs_events(&txids)
     .map_err(ApiError::internal)?;
 
   log::debug!(
@@ -357,3 +354,107 @@ pub(crate) async fn brc20_block_events(
       .collect(),
   })))
 }
+
+#[derive(Deserialize, IntoParams)]
+pub struct EventFilter {
+  /// Only return events of the ticker.
+  pub tick: Option<String>,
+  /// Only return events of the type, one of `deploy`, `mint`, `inscribeTransfer` or `transfer`.
+  pub op: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = brc20::AddressEvent)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressEvent {
+  /// The transaction ID of the event.
+  pub txid: String,
+  #[schema(value_type = brc20::TxEvent)]
+  pub event: TxEvent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = brc20::AddressEvents)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressEvents {
+  #[schema(value_type = Vec<brc20::AddressEvent>)]
+  pub events: Vec<AddressEvent>,
+  /// The cursor of the next page, absent on the last page.
+  pub next: Option<u64>,
+}
+
+/// Get the events of the address.
+///
+/// Retrieve the BRC20 events sent or received by the address, newest first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/brc20/address/{address}/events",
+    params(
+        ("address" = String, Path, description = "Address"),
+        EventFilter,
+        CursorPagination
+  ),
+    responses(
+      (status = 200, description = "Obtain the events of the address.", body = BRC20AddressEvents),
+      (status = 400, description = "Bad query.", body = ApiError, example = json!(&ApiError::bad_request("bad request"))),
+      (status = 500, description = "Internal server error.", body = ApiError, example = json!(&ApiError::internal("internal error"))),
+    )
+  )]
+pub(crate) async fn brc20_address_events(
+  Extension(index): Extension<Arc<Index>>,
+  Path(address): Path<String>,
+  Query(filter): Query<EventFilter>,
+  Query(page): Query<CursorPagination>,
+) -> ApiResult<AddressEvents> {
+  log::debug!("rpc: get brc20_address_events: {}", address);
+
+  let address: bitcoin::Address = Address::from_str(&address)
+    .and_then(|address| address.require_network(index.get_chain_network()))
+    .map_err(ApiError::bad_request)?;
+
+  let tick = filter
+    .tick
+    .map(|tick| {
+      brc20_store::Tick::from_str(&tick)
+        .map_err(|_| ApiError::bad_request(BRC20Error::IncorrectTickFormat))
+    })
+    .transpose()?
+    .map(|tick| tick.to_lowercase());
+
+  if let Some(op) = &filter.op {
+    if !["deploy", "mint", "inscribeTransfer", "transfer"].contains(&op.as_str()) {
+      return Err(ApiError::bad_request(BRC20Error::IncorrectOperationType));
+    }
+  }
+
+  let (events, next) = index.brc20_get_address_events(
+    &address,
+    |receipt| {
+      filter
+        .op
+        .as_ref()
+        .map_or(true, |op| event_type(&receipt.op) == op)
+        && tick.as_ref().map_or(true, |tick| {
+          receipt
+            .result
+            .as_ref()
+            .is_ok_and(|event| event.tick().to_lowercase() == *tick)
+        })
+    },
+    page.cursor,
+    page.limit.unwrap_or(DEFAULT_PAGE_LIMIT),
+  )?;
+
+  log::debug!("rpc: get brc20_address_events: {} {:?}", address, events);
+
+  Ok(Json(ApiResponse::ok(AddressEvents {
+    events: events
+      .iter()
+      .map(|(txid, receipt)| AddressEvent {
+        txid: txid.to_string(),
+        event: receipt.into(),
+      })
+      .collect(),
+    next,
+  })))
+}
@@ -0,0 +1,52 @@
+use {super::*, axum::Json, utoipa::ToSchema};
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = brc20::StateHash)]
+#[serde(rename_all = "camelCase")]
+/// The chained hash of the BRC20 and BRC20S state changes up to a block.
+pub struct StateHash {
+  /// The height of the block.
+  #[schema(format = "uint64")]
+  pub height: u64,
+  /// The hash of the block.
+  pub blockhash: String,
+  /// The state hash of the block, hex encoded.
+  pub state_hash: String,
+}
+
+/// Get the state hash of a block.
+///
+/// Retrieve the chained hash of the balances, token info and transferable logs changed by the block and every block before it, to compare checkpoints with other indexers.
+#[utoipa::path(
+    get,
+    path = "/api/v1/brc20/block/{height}/state_hash",
+    params(
+        ("height" = u64, Path, description = "block height")
+  ),
+    responses(
+      (status = 200, description = "Obtain the state hash of the block.", body = BRC20StateHash),
+      (status = 400, description = "Bad query.", body = ApiError, example = json!(&ApiError::bad_request("bad request"))),
+      (status = 404, description = "Block not found.", body = ApiError, example = json!(&ApiError::not_found(BRC20Error::BlockNotFound))),
+      (status = 500, description = "Internal server error.", body = ApiError, example = json!(&ApiError::internal("internal error"))),
+    )
+  )]
+pub(crate) async fn brc20_block_state_hash(
+  Extension(index): Extension<Arc<Index>>,
+  Path(height): Path<u64>,
+) -> ApiResult<StateHash> {
+  log::debug!("rpc: get brc20_block_state_hash: {}", height);
+
+  let (_, state_hash) = index
+    .state_hash(Some(height))?
+    .ok_or_api_not_found(BRC20Error::BlockNotFound)?;
+
+  let blockhash = index
+    .block_hash(Some(height))?
+    .ok_or_api_not_found(BRC20Error::BlockNotFound)?;
+
+  Ok(Json(ApiResponse::ok(StateHash {
+    height,
+    blockhash: blockhash.to_string(),
+    state_hash: state_hash.to_string(),
+  })))
+}
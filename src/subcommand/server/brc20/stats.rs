@@ -0,0 +1,156 @@
+use {
+  super::*,
+  crate::{
+    index::ranking,
+    okx::datastore::brc20::{Tick, TokenInfo, TokenStats},
+  },
+  axum::Json,
+  utoipa::{IntoParams, ToSchema},
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = brc20::TickStats)]
+#[serde(rename_all = "camelCase")]
+/// Activity statistics of a BRC20 ticker.
+pub struct TickStats {
+  /// Name of the ticker.
+  pub tick: String,
+  /// The total supply of the ticker.
+  #[schema(format = "uint64")]
+  pub supply: String,
+  /// The amount of the ticker that has been minted.
+  #[schema(format = "uint64")]
+  pub minted: String,
+  /// The minted share of the supply, between 0 and 1.
+  pub progress: f64,
+  /// The number of addresses holding the ticker.
+  #[schema(format = "uint64")]
+  pub holders: u64,
+  /// The number of successful mints.
+  #[schema(format = "uint64")]
+  pub mint_count: u64,
+  /// The number of successful transfer inscriptions.
+  #[schema(format = "uint64")]
+  pub inscribe_transfer_count: u64,
+  /// The number of successful transfers.
+  #[schema(format = "uint64")]
+  pub transfer_count: u64,
+  /// The amount locked in transfer inscriptions that haven't been sent yet.
+  #[schema(format = "uint64")]
+  pub transferable_supply: String,
+  /// The height of the block that the ticker deployed.
+  #[schema(format = "uint64")]
+  pub deploy_height: u64,
+  /// The height of the block of the latest successful operation.
+  #[schema(format = "uint64")]
+  pub latest_activity_height: u64,
+}
+
+impl TickStats {
+  fn new((info, stats, holders): &(TokenInfo, TokenStats, u64)) -> Self {
+    Self {
+      tick: info.tick.to_string(),
+      supply: info.supply.to_string(),
+      minted: info.minted.to_string(),
+      progress: info.minted as f64 / info.supply as f64,
+      holders: *holders,
+      mint_count: stats.mint_count,
+      inscribe_transfer_count: stats.inscribe_transfer_count,
+      transfer_count: stats.transfer_count,
+      transferable_supply: stats.transferable_supply.to_string(),
+      deploy_height: info.deployed_number,
+      latest_activity_height: stats.latest_activity_number,
+    }
+  }
+}
+
+/// Get the ticker stats.
+///
+/// Retrieve the mint, transfer and holder statistics of the ticker.
+#[utoipa::path(
+    get,
+    path = "/api/v1/brc20/tick/{ticker}/stats",
+    params(
+      ("ticker" = String, Path, description = "Token ticker", min_length = 4, max_length = 5)
+  ),
+    responses(
+      (status = 200, description = "Obtain the statistics of the ticker.", body = BRC20TickStats),
+      (status = 400, description = "Bad query.", body = ApiError, example = json!(&ApiError::bad_request(BRC20Error::IncorrectTickFormat))),
+      (status = 404, description = "Ticker not found.", body = ApiError, example = json!(&ApiError::not_found(BRC20Error::TickNotFound))),
+      (status = 500, description = "Internal server error.", body = ApiError, example = json!(&ApiError::internal("internal error"))),
+    )
+  )]
+pub(crate) async fn brc20_tick_stats(
+  Extension(index): Extension<Arc<Index>>,
+  Path(tick): Path<String>,
+) -> ApiResult<TickStats> {
+  log::debug!("rpc: get brc20_tick_stats: {}", tick);
+
+  let tick =
+    Tick::from_str(&tick).map_err(|_| ApiError::bad_request(BRC20Error::IncorrectTickFormat))?;
+
+  let tick_stats = index
+    .brc20_get_tick_stats(&tick)?
+    .ok_or_api_not_found(BRC20Error::TickNotFound)?;
+
+  Ok(Json(ApiResponse::ok(TickStats::new(&tick_stats))))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct StatsSort {
+  /// Sort the tickers by `progress`, `holders` or `activity` (default), in descending order.
+  pub sort: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = brc20::AllTickStats)]
+#[serde(rename_all = "camelCase")]
+pub struct AllTickStats {
+  #[schema(value_type = Vec<brc20::TickStats>)]
+  pub ticks: Vec<TickStats>,
+  /// The total number of tickers.
+  pub total: usize,
+}
+
+/// Get the stats of all tickers.
+///
+/// Retrieve the statistics of all tickers, sorted by mint progress, holders or the number of successful operations.
+#[utoipa::path(
+    get,
+    path = "/api/v1/brc20/stats",
+    params(
+        StatsSort,
+        Pagination
+  ),
+    responses(
+      (status = 200, description = "Obtain the statistics of all tickers.", body = BRC20AllTickStats),
+      (status = 400, description = "Bad query.", body = ApiError, example = json!(&ApiError::bad_request(BRC20Error::IncorrectStatsSort))),
+      (status = 500, description = "Internal server error.", body = ApiError, example = json!(&ApiError::internal("internal error"))),
+    )
+  )]
+pub(crate) async fn brc20_all_tick_stats(
+  Extension(index): Extension<Arc<Index>>,
+  Query(sort): Query<StatsSort>,
+  Query(page): Query<Pagination>,
+) -> ApiResult<AllTickStats> {
+  log::debug!("rpc: get brc20_all_tick_stats");
+
+  let sort = match sort.sort.as_deref().unwrap_or("activity") {
+    "progress" => ranking::StatsSort::Progress,
+    "holders" => ranking::StatsSort::Holders,
+    "activity" => ranking::StatsSort::Activity,
+    _ => return Err(ApiError::bad_request(BRC20Error::IncorrectStatsSort)),
+  };
+
+  let ticks = index.brc20_get_ranked_tick_stats(sort)?;
+
+  Ok(Json(ApiResponse::ok(AllTickStats {
+    ticks: ticks
+      .iter()
+      .skip(page.start.unwrap_or_default())
+      .take(page.limit.unwrap_or(usize::MAX))
+      .map(TickStats::new)
+      .collect(),
+    total: ticks.len(),
+  })))
+}
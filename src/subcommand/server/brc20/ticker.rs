@@ -50,6 +50,11 @@ pub struct TickInfo {
   /// The timestamp of the block that the ticker deployed.
   #[schema(format = "uint32")]
   pub deploy_blocktime: u32,
+  /// The number of addresses holding the ticker.<br>
+  /// Only returned when querying a single ticker.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[schema(format = "uint64")]
+  pub holders: Option<u64>,
 }
 
 impl From<TokenInfo> for TickInfo {
@@ -66,6 +71,7 @@ impl From<TokenInfo> for TickInfo {
       txid: tick_info.inscription_id.txid.to_string(),
       deploy_height: tick_info.deployed_number,
       deploy_blocktime: tick_info.deployed_timestamp,
+      holders: None,
     }
   }
 }
@@ -77,7 +83,7 @@ impl From<TokenInfo> for TickInfo {
     get,
     path = "/api/v1/brc20/tick/{ticker}",
     params(
-      ("ticker" = String, Path, description = "Token ticker", min_length = 4, max_length = 4)
+      ("ticker" = String, Path, description = "Token ticker", min_length = 4, max_length = 5)
   ),
     responses(
       (status = 200, description = "Obtain matching BRC20 ticker by query.", body = BRC20Tick),
@@ -97,9 +103,14 @@ pub(crate) async fn brc20_tick_info(
     .brc20_get_tick_info(&tick)?
     .ok_or_api_not_found(BRC20Error::TickNotFound)?;
 
+  let holders = index.brc20_get_tick_holders_count(&tick)?;
+
   log::debug!("rpc: get brc20_tick_info: {:?} {:?}", tick, tick_info);
 
-  Ok(Json(ApiResponse::ok(tick_info.into())))
+  let mut tick_info = TickInfo::from(tick_info);
+  tick_info.holders = Some(holders);
+
+  Ok(Json(ApiResponse::ok(tick_info)))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -1,7 +1,7 @@
 use {
   super::*,
   crate::okx::{
-    datastore::{brc20::redb as brc20_db, ScriptKey},
+    datastore::{brc20::OperationType, ord::Action},
     protocol::brc20 as brc20_proto,
   },
   axum::Json,
@@ -129,7 +129,8 @@ pub(crate) async fn brc20_tx(
   log::debug!("rpc: get brc20_tx: {}", txid);
   let txid = bitcoin::Txid::from_str(&txid).map_err(|e| ApiError::bad_request(e.to_string()))?;
 
-  let tx_info = get_operations_by_txid(&index, &txid, true)?;
+  let tx_info =
+    get_operations_by_txid(&index, &txid)?.ok_or_api_not_found(BRC20Error::OperationNotFound)?;
 
   if tx_info.inscriptions.is_empty() {
     return Err(ApiError::not_found(BRC20Error::OperationNotFound));
@@ -139,74 +140,80 @@ pub(crate) async fn brc20_tx(
   Ok(Json(ApiResponse::ok(tx_info)))
 }
 
+/// Builds the BRC20 operations of a transaction from its receipts, which are indexed once it is
+/// confirmed and simulated in the mempool overlay while it is pending.
 fn get_operations_by_txid(
   index: &Arc<Index>,
   txid: &bitcoin::Txid,
-  with_unconfirmed: bool,
-) -> Result<TxInscriptionInfo> {
-  let mut brc20_operation_infos = Vec::new();
-
-  let tx_result = index
-    .get_transaction_info(txid)?
-    .ok_or(anyhow!("can't get transaction info: {txid}"))?;
-
-  // get inscription operations
-  let operations = ord::get_ord_operations_by_txid(index, txid, with_unconfirmed)?;
+) -> Result<Option<TxInscriptionInfo>> {
+  let Some(operations) = ord::get_ord_operations_by_txid(index, txid, true)? else {
+    return Ok(None);
+  };
 
-  // get new inscriptions
-  let new_inscriptions = Inscription::from_transaction(&tx_result.transaction()?)
-    .into_iter()
-    .map(|i| i.inscription)
-    .collect::<Vec<Inscription>>();
+  let (blockhash, receipts) = match index.get_transaction_height(txid)? {
+    Some(height) => (
+      index.block_hash(Some(height))?,
+      index.brc20_get_tx_events_by_txid(txid)?.unwrap_or_default(),
+    ),
+    None => (
+      None,
+      index
+        .pending()
+        .and_then(|overlay| {
+          overlay
+            .transaction(txid)
+            .map(|tx| tx.brc20_receipts.clone())
+        })
+        .unwrap_or_default(),
+    ),
+  };
 
-  let rtx = index.begin_read()?.0;
-  let brc20_store = brc20_db::DataStoreReader::new(&rtx);
-  for operation in operations {
-    match brc20_proto::Message::resolve(&brc20_store, &new_inscriptions, &operation)? {
-      None => continue,
-      Some(msg) => brc20_operation_infos.push(InscriptionInfo {
-        action: match msg.op {
-          brc20_proto::Operation::Transfer(_) => ActionType::Transfer,
-          _ => ActionType::Inscribe,
-        },
-        inscription_number: index
-          .get_inscription_entry(msg.inscription_id)?
-          .map(|entry| entry.number),
-        inscription_id: msg.inscription_id.to_string(),
-        from: index
-          .get_outpoint_entry(msg.old_satpoint.outpoint)?
-          .map(|txout| {
-            ScriptKey::from_script(&txout.script_pubkey, index.get_chain_network()).into()
-          })
-          .ok_or(anyhow!("outpoint not found {}", msg.old_satpoint.outpoint))?,
-        to: match msg.new_satpoint {
-          Some(satpoint) => match index.get_outpoint_entry(satpoint.outpoint) {
-            Ok(Some(txout)) => {
-              Some(ScriptKey::from_script(&txout.script_pubkey, index.get_chain_network()).into())
-            }
-            Ok(None) => return Err(anyhow!("outpoint not found {}", satpoint.outpoint)),
-            Err(e) => return Err(e),
-          },
-          None => None,
-        },
-        old_satpoint: msg.old_satpoint.to_string(),
-        new_satpoint: msg.new_satpoint.map(|v| v.to_string()),
-        operation: Some(RawOperation::Brc20Operation(msg.op.into())),
-      }),
+  let mut brc20_operation_infos = Vec::new();
+  for receipt in receipts {
+    // a transfer sends an inscription created by an earlier transaction.
+    let action = match receipt.op {
+      OperationType::Transfer => Some(Action::Transfer),
+      _ => operations
+        .iter()
+        .find(|op| op.inscription_id == receipt.inscription_id)
+        .map(|op| op.action.clone()),
     };
+    let operation = match action {
+      Some(action) => ord::get_inscription(index, receipt.inscription_id)?
+        .and_then(|inscription| {
+          brc20_proto::deserialize_brc20_operation(&inscription, &action).ok()
+        })
+        .map(|op| RawOperation::Brc20Operation(op.into())),
+      None => None,
+    };
+
+    brc20_operation_infos.push(InscriptionInfo {
+      action: match receipt.op {
+        OperationType::Transfer => ActionType::Transfer,
+        _ => ActionType::Inscribe,
+      },
+      inscription_number: Some(receipt.inscription_number),
+      inscription_id: receipt.inscription_id.to_string(),
+      from: receipt.from.into(),
+      to: Some(receipt.to.into()),
+      old_satpoint: receipt.old_satpoint.to_string(),
+      new_satpoint: Some(receipt.new_satpoint.to_string()),
+      operation,
+    });
   }
-  // if the transaction is not confirmed, try to parsing protocol
-  Ok(TxInscriptionInfo {
+
+  Ok(Some(TxInscriptionInfo {
     txid: txid.to_string(),
-    blockhash: tx_result.blockhash.map(|v| v.to_string()),
-    confirmed: tx_result.blockhash.is_some(),
+    blockhash: blockhash.map(|v| v.to_string()),
+    confirmed: blockhash.is_some(),
     inscriptions: brc20_operation_infos,
-  })
+  }))
 }
+
 #[cfg(test)]
 mod tests {
 
-  use super::*;
+  use {super::*, crate::okx::datastore::ScriptKey};
   #[test]
   fn serialize_deploy() {
     let deploy = Deploy {
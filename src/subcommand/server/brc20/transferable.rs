@@ -1,4 +1,9 @@
-use {super::*, crate::okx::datastore::brc20 as brc20_store, axum::Json, utoipa::ToSchema};
+use {
+  super::*,
+  crate::okx::datastore::{brc20 as brc20_store, ScriptKey},
+  axum::Json,
+  utoipa::ToSchema,
+};
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[schema(as = brc20::TransferableInscription)]
@@ -31,12 +36,12 @@ impl From<&brc20_store::TransferableLog> for TransferableInscription {
 
 /// Get the transferable inscriptions of the address.
 ///
-/// Retrieve the transferable inscriptions with the ticker from the given address.
+/// Retrieve the transferable inscriptions with the ticker from the given address, with a pending section when the mempool watcher is enabled.
 #[utoipa::path(
   get,
   path = "/api/v1/brc20/tick/{ticker}/address/{address}/transferable",
   params(
-      ("ticker" = String, Path, description = "Token ticker", min_length = 4, max_length = 4),
+      ("ticker" = String, Path, description = "Token ticker", min_length = 4, max_length = 5),
       ("address" = String, Path, description = "Address")
 ),
   responses(
@@ -66,8 +71,32 @@ pub(crate) async fn brc20_transferable(
     transferable
   );
 
+  let pending = index.pending().map(|overlay| {
+    let pending = overlay.brc20(ScriptKey::from_address(address.clone()), &tick);
+    PendingTransferable {
+      inscriptions: pending
+        .map(|pending| &pending.transferable)
+        .unwrap_or(&transferable)
+        .iter()
+        .map(|trans| trans.into())
+        .collect(),
+      spends: transferable
+        .iter()
+        .filter_map(|trans| {
+          overlay
+            .spend(&trans.inscription_id)
+            .map(|spend| PendingSpend::new(trans.inscription_id, spend))
+        })
+        .collect(),
+      txids: pending
+        .map(|pending| pending.txids.iter().map(ToString::to_string).collect())
+        .unwrap_or_default(),
+    }
+  });
+
   Ok(Json(ApiResponse::ok(TransferableInscriptions {
     inscriptions: transferable.iter().map(|trans| trans.into()).collect(),
+    pending,
   })))
 }
 
@@ -77,6 +106,22 @@ pub(crate) async fn brc20_transferable(
 pub struct TransferableInscriptions {
   #[schema(value_type = Vec<brc20::TransferableInscription>)]
   pub inscriptions: Vec<TransferableInscription>,
+  /// The transferable inscriptions once the pending transactions are confirmed. Only present when the mempool watcher is enabled.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[schema(value_type = Option<brc20::PendingTransferable>)]
+  pub pending: Option<PendingTransferable>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = brc20::PendingTransferable)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingTransferable {
+  #[schema(value_type = Vec<brc20::TransferableInscription>)]
+  pub inscriptions: Vec<TransferableInscription>,
+  /// The pending transactions moving the confirmed transferable inscriptions, with the transactions they replaced.
+  pub spends: Vec<PendingSpend>,
+  /// The pending transactions changing the transferable inscriptions.
+  pub txids: Vec<String>,
 }
 
 /// Get the balance of ticker of the address.
@@ -113,5 +158,6 @@ pub(crate) async fn brc20_all_transferable(
 
   Ok(Json(ApiResponse::ok(TransferableInscriptions {
     inscriptions: transferable.iter().map(|trans| trans.into()).collect(),
+    pending: None,
   })))
 }
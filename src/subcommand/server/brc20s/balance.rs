@@ -1,4 +1,4 @@
-use {super::*, axum::Json, utoipa::ToSchema};
+use {super::*, crate::okx::datastore::ScriptKey, axum::Json, utoipa::ToSchema};
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 #[schema(as = brc20s::Balance)]
@@ -12,6 +12,24 @@ pub(crate) struct Balance {
   /// Overall balance.
   #[schema(format = "uint64")]
   pub overall: String,
+  /// The balance once the pending transactions are confirmed. Only present when the mempool watcher is enabled.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[schema(value_type = Option<brc20s::PendingBalance>)]
+  pub pending: Option<PendingBalance>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[schema(as = brc20s::PendingBalance)]
+pub(crate) struct PendingBalance {
+  /// Transferable balance.
+  #[schema(format = "uint64")]
+  pub transferable: String,
+  /// Overall balance.
+  #[schema(format = "uint64")]
+  pub overall: String,
+  /// The pending transactions changing the balance.
+  pub txids: Vec<String>,
 }
 
 impl Balance {
@@ -31,6 +49,7 @@ impl From<&brc20s::Balance> for Balance {
       tick,
       transferable: balance.transferable_balance.to_string(),
       overall: balance.overall_balance.to_string(),
+      pending: None,
     }
   }
 }
@@ -39,13 +58,14 @@ impl From<&brc20s::Balance> for Balance {
 
 /// Get the ticker balance of the address.
 ///
-/// The balance is the sum of the transferable balance and the available balance.
+/// The balance is the sum of the transferable balance and the available balance. Without a height, the balance includes a pending section when the mempool watcher is enabled.
 #[utoipa::path(
   get,
   path = "/api/v1/brc20s/tick/{tick_id}/address/{address}/balance",
   params(
       ("tick_id" = String, Path, description = "Token ticker ID", min_length = 10, max_length = 10),
-      ("address" = String, Path, description = "Address")
+      ("address" = String, Path, description = "Address"),
+      HeightQuery
 ),
   responses(
     (status = 200, description = "Obtain account balance by query ticker.", body = BRC20SBalance),
@@ -57,11 +77,13 @@ impl From<&brc20s::Balance> for Balance {
 pub(crate) async fn brc20s_balance(
   Extension(index): Extension<Arc<Index>>,
   Path((tick_id, address)): Path<(String, String)>,
+  Query(query): Query<HeightQuery>,
 ) -> ApiResult<Balance> {
   log::debug!(
-    "rpc: get brc20s_balance: tickId:{}, address:{}",
+    "rpc: get brc20s_balance: tickId:{}, address:{}, height:{:?}",
     tick_id,
-    address
+    address,
+    query.height
   );
 
   let tick_id = brc20s::TickId::from_str(&tick_id)
@@ -70,11 +92,38 @@ pub(crate) async fn brc20s_balance(
   let address: bitcoin::Address = Address::from_str(&address)
     .and_then(|address| address.require_network(index.get_chain_network()))
     .map_err(ApiError::bad_request)?;
-  let balance = &index
-    .brc20s_balance(&tick_id, &address)?
-    .ok_or_api_not_found(BRC20SError::BalanceNotFound)?;
+  let height = query.checked_height(&index)?;
+
+  let overlay = query.height.is_none().then(|| index.pending()).flatten();
+  let pending = overlay
+    .as_ref()
+    .and_then(|overlay| overlay.brc20s(ScriptKey::from_address(address.clone()), &tick_id));
+
+  let balance = &match index.brc20s_balance(&tick_id, &address, height)? {
+    Some(balance) => balance,
+    None
+      if pending
+        .and_then(|pending| pending.balance.as_ref())
+        .is_some() =>
+    {
+      brc20s::Balance::new(tick_id)
+    }
+    None => return Err(ApiError::not_found(BRC20SError::BalanceNotFound)),
+  };
 
   let mut balance_result = Balance::from(balance);
+  balance_result.pending = overlay.as_ref().map(|_| {
+    let pending_balance = pending
+      .and_then(|pending| pending.balance.as_ref())
+      .unwrap_or(balance);
+    PendingBalance {
+      transferable: pending_balance.transferable_balance.to_string(),
+      overall: pending_balance.overall_balance.to_string(),
+      txids: pending
+        .map(|pending| pending.txids.iter().map(ToString::to_string).collect())
+        .unwrap_or_default(),
+    }
+  });
 
   let tick_info = &index
     .brc20s_tick_info(&tick_id)?
@@ -105,7 +154,8 @@ pub(crate) struct AllBalance {
   get,
   path = "/api/v1/brc20s/address/{address}/balance",
   params(
-      ("address" = String, Path, description = "Address")
+      ("address" = String, Path, description = "Address"),
+      HeightQuery
 ),
   responses(
     (status = 200, description = "Obtain account balances by query address.", body = BRC20SAllBalance),
@@ -117,14 +167,21 @@ pub(crate) struct AllBalance {
 pub(crate) async fn brc20s_all_balance(
   Extension(index): Extension<Arc<Index>>,
   Path(address): Path<String>,
+  Query(query): Query<HeightQuery>,
 ) -> ApiResult<AllBalance> {
-  log::debug!("rpc: get brc20s_all_balance: {}", address);
+  log::debug!(
+    "rpc: get brc20s_all_balance: {}, height:{:?}",
+    address,
+    query.height
+  );
 
   let address: bitcoin::Address = Address::from_str(&address)
     .and_then(|address| address.require_network(index.get_chain_network()))
     .map_err(ApiError::bad_request)?;
 
-  let all_balance = index.brc20s_all_balance(&address)?;
+  let height = query.checked_height(&index)?;
+
+  let all_balance = index.brc20s_all_balance(&address, height)?;
 
   log::debug!("rpc: get brc20s_all_balance: {} {:?}", address, all_balance);
 
@@ -164,7 +221,7 @@ pub(crate) async fn brc20s_debug_balance(
     .and_then(|address| address.require_network(index.get_chain_network()))
     .map_err(ApiError::bad_request)?;
   let balance = index
-    .brc20s_balance(&tick_id, &address)?
+    .brc20s_balance(&tick_id, &address, None)?
     .ok_or_api_not_found(BRC20SError::BalanceNotFound)?;
 
   log::debug!(
@@ -1,5 +1,8 @@
 use {
-  super::{types::ScriptPubkey, *},
+  super::{
+    types::{PendingSpend, ScriptPubkey},
+    *,
+  },
   crate::okx::datastore::brc20s,
   utoipa::ToSchema,
 };
@@ -10,8 +13,11 @@ mod receipt;
 mod reward;
 mod ticker;
 mod transferable;
+mod validate;
 
-pub(super) use {balance::*, pool::*, receipt::*, reward::*, ticker::*, transferable::*};
+pub(super) use {
+  balance::*, pool::*, receipt::*, reward::*, ticker::*, transferable::*, validate::*,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum BRC20SError {
@@ -86,6 +92,66 @@ pub(crate) struct Earn {
   pub name: String,
 }
 
+/// The routes of the BRC20S JSON API.
+pub(crate) fn router() -> Router {
+  Router::new()
+    .route("/brc20s/tick", get(brc20s_all_tick_info))
+    .route("/brc20s/tick/:tick_id", get(brc20s_tick_info))
+    .route("/brc20s/debug/tick/:tick_id", get(brc20s_debug_tick_info))
+    .route("/brc20s/pool", get(brc20s_all_pool_info))
+    .route("/brc20s/pool/:pid", get(brc20s_pool_info))
+    .route("/brc20s/pool/tid/:tick_id", get(brc20s_all_pools_by_tid))
+    .route("/brc20s/debug/pool/:pid", get(brc20s_debug_pool_info))
+    .route(
+      "/brc20s/debug/stake/:address/:tick",
+      get(brc20s_debug_stake_info),
+    )
+    .route(
+      "/brc20s/pool/:pid/address/:address/userinfo",
+      get(brc20s_userinfo),
+    )
+    .route(
+      "/brc20s/debug/pool/:pid/address/:address/reward",
+      get(brc20s_user_pending_reward),
+    )
+    .route(
+      "/brc20s/tick/:tick_id/address/:address/balance",
+      get(brc20s_balance),
+    )
+    .route(
+      "/brc20s/debug/pool/:pid/address/:address/userinfo",
+      get(brc20s_debug_userinfo),
+    )
+    .route(
+      "/brc20s/debug/tick/:tick_id/address/:address/balance",
+      get(brc20s_debug_balance),
+    )
+    .route("/brc20s/address/:address/balance", get(brc20s_all_balance))
+    .route(
+      "/brc20s/tick/:tick_id/address/:address/transferable",
+      get(brc20s_transferable),
+    )
+    .route(
+      "/brc20s/address/:address/transferable",
+      get(brc20s_all_transferable),
+    )
+    .route("/brc20s/tx/:txid/receipts", get(brc20s_txid_receipts))
+    .route(
+      "/brc20s/debug/tx/:txid/receipts",
+      get(brc20s_debug_txid_receipts),
+    )
+    .route(
+      "/brc20s/block/:blockhash/receipts",
+      get(brc20s_block_receipts),
+    )
+    .route(
+      "/brc20s/address/:address/receipts",
+      get(brc20s_address_receipts),
+    )
+    .route("/brc20s/stake/:address/:tick", get(brc20s_stake_info))
+    .route("/brc20s/validate", post(brc20s_validate))
+}
+
 #[cfg(test)]
 mod tests {
   use crate::okx::datastore::brc20::Tick;
@@ -1,4 +1,4 @@
-use {super::*, crate::okx::datastore::brc20s, axum::Json};
+use {super::*, crate::okx::datastore::brc20s, axum::Json, utoipa::IntoParams};
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[schema(as = brc20s::Receipt)]
 #[serde(rename_all = "camelCase")]
@@ -29,7 +29,7 @@ pub struct Receipt {
 }
 
 impl Receipt {
-  pub(super) fn from(receipt: &brc20s::Receipt, index: Arc<Index>) -> Result<Self> {
+  pub(crate) fn from(receipt: &brc20s::Receipt, index: Arc<Index>) -> Result<Self> {
     let mut result = Self {
       op: receipt.op.clone().into(),
       inscription_number: match receipt.op {
@@ -515,27 +515,13 @@ pub(crate) async fn brc20s_block_receipts(
   log::debug!("rpc: get brc20s_block_receipts: {}", blockhash);
 
   let blockhash = bitcoin::BlockHash::from_str(&blockhash).map_err(ApiError::bad_request)?;
-  // get block from btc client.
-  let blockinfo = index
-    .get_block_info_by_hash(blockhash)
+  let txids = index
+    .get_block_transactions_by_hash(blockhash)
     .map_err(ApiError::internal)?
     .ok_or_api_not_found(BRC20SError::BlockReceiptsNotFound)?;
 
-  // get blockhash from redb.
-  let blockhash = index
-    .block_hash(Some(u64::try_from(blockinfo.height).unwrap()))
-    .map_err(ApiError::internal)?
-    .ok_or_api_not_found(BRC20SError::BlockReceiptsNotFound)?;
-
-  // check of conflicting block.
-  if blockinfo.hash != blockhash {
-    return Err(ApiError::NotFound(
-      BRC20SError::BlockReceiptsNotFound.to_string(),
-    ));
-  }
-
   let block_receipts = index
-    .brc20s_txs_receipts(&blockinfo.tx)
+    .brc20s_txs_receipts(&txids)
     .map_err(ApiError::internal)?;
   log::debug!("rpc: get brc20s_block_receipts: {:?}", block_receipts);
 
@@ -564,3 +550,108 @@ pub(crate) async fn brc20s_block_receipts(
     block: api_block_receipts,
   })))
 }
+
+#[derive(Deserialize, IntoParams)]
+pub struct ReceiptFilter {
+  /// Only return receipts of the ticker id.
+  pub tick_id: Option<String>,
+  /// Only return receipts of the operation type.
+  #[param(value_type = Option<brc20s::OperationType>)]
+  pub op: Option<OperationType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[schema(as = brc20s::AddressReceipt)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressReceipt {
+  /// The transaction ID of the receipt.
+  pub txid: String,
+  #[schema(value_type = brc20s::Receipt)]
+  pub receipt: Receipt,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[schema(as = brc20s::AddressReceipts)]
+#[serde(rename_all = "camelCase")]
+pub struct AddressReceipts {
+  #[schema(value_type = Vec<brc20s::AddressReceipt>)]
+  pub receipts: Vec<AddressReceipt>,
+  /// The cursor of the next page, absent on the last page.
+  pub next: Option<u64>,
+}
+
+// brc20s/address/:address/receipts
+/// Get the receipts of the address.
+///
+/// Get the receipts sent or received by the address, newest first.
+#[utoipa::path(
+  get,
+  path = "/api/v1/brc20s/address/{address}/receipts",
+  params(
+      ("address" = String, Path, description = "Address"),
+      ReceiptFilter,
+      CursorPagination
+),
+  responses(
+    (status = 200, description = "Obtain the receipts of the address.", body = BRC20SAddressReceipts),
+    (status = 400, description = "Bad query.", body = ApiError, example = json!(&ApiError::bad_request("bad request"))),
+    (status = 500, description = "Internal server error.", body = ApiError, example = json!(&ApiError::internal("internal error"))),
+  )
+)]
+pub(crate) async fn brc20s_address_receipts(
+  Extension(index): Extension<Arc<Index>>,
+  Path(address): Path<String>,
+  Query(filter): Query<ReceiptFilter>,
+  Query(page): Query<CursorPagination>,
+) -> ApiResult<AddressReceipts> {
+  log::debug!("rpc: get brc20s_address_receipts: {}", address);
+
+  let address: bitcoin::Address = Address::from_str(&address)
+    .and_then(|address| address.require_network(index.get_chain_network()))
+    .map_err(ApiError::bad_request)?;
+
+  let tick_id = filter
+    .tick_id
+    .map(|tick_id| {
+      brc20s::TickId::from_str(&tick_id)
+        .map_err(|_| ApiError::bad_request(BRC20SError::IncorrectTickIdFormat))
+    })
+    .transpose()?;
+
+  let (all_receipt, next) = index.brc20s_address_receipts(
+    &address,
+    |receipt| {
+      filter
+        .op
+        .as_ref()
+        .map_or(true, |op| OperationType::from(receipt.op.clone()) == *op)
+        && tick_id.as_ref().map_or(true, |tick_id| {
+          receipt
+            .result
+            .as_ref()
+            .is_ok_and(|events| events.iter().any(|event| event.tick_id() == *tick_id))
+        })
+    },
+    page.cursor,
+    page.limit.unwrap_or(DEFAULT_PAGE_LIMIT),
+  )?;
+
+  log::debug!("rpc: get brc20s_address_receipts: {:?}", all_receipt);
+
+  let mut receipts = Vec::new();
+  for (txid, receipt) in all_receipt.iter() {
+    match Receipt::from(receipt, index.clone()) {
+      Ok(receipt) => {
+        receipts.push(AddressReceipt {
+          txid: txid.to_string(),
+          receipt,
+        });
+      }
+      Err(_) => {
+        return Err(ApiError::internal("failed to get address receipts"));
+      }
+    }
+  }
+
+  Ok(Json(ApiResponse::ok(AddressReceipts { receipts, next })))
+}
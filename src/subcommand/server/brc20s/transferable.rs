@@ -1,4 +1,8 @@
-use {super::*, crate::okx::datastore::brc20s, axum::Json};
+use {
+  super::*,
+  crate::okx::datastore::{brc20s, ScriptKey},
+  axum::Json,
+};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[schema(as = brc20s::TransferableInscription)]
@@ -47,7 +51,7 @@ impl From<&brc20s::TransferableAsset> for TransferableInscription {
 
 // brc20s/tick/:tickId/address/:address/transferable
 
-/// Retrieve the transferable inscriptions with the specified ticker from the given address.
+/// Retrieve the transferable inscriptions with the specified ticker from the given address, with a pending section when the mempool watcher is enabled.
 #[utoipa::path(
   get,
   path = "/api/v1/brc20s/tick/{tick_id}/address/{address}/transferable",
@@ -82,7 +86,45 @@ pub(crate) async fn brc20s_transferable(
     all_transfer
   );
 
+  let pending = match index.pending() {
+    Some(overlay) => {
+      let pending = overlay.brc20s(ScriptKey::from_address(address.clone()), &tick_id);
+      Some(PendingTransferable {
+        inscriptions: pending
+          .map(|pending| &pending.transferable)
+          .unwrap_or(all_transfer)
+          .iter()
+          .map(|asset| {
+            let mut inscription = TransferableInscription::from(asset);
+
+            // the ticker and the inscription can both be created by a pending transaction.
+            if let Some(tick_info) = index.brc20s_tick_info(&asset.tick_id)? {
+              inscription.set_tick_name(tick_info.name.as_str().to_string());
+            }
+            if let Some(entry) = index.get_inscription_entry(asset.inscription_id)? {
+              inscription.set_inscription_number(entry.number);
+            }
+            Ok(inscription)
+          })
+          .collect::<Result<Vec<_>>>()?,
+        spends: all_transfer
+          .iter()
+          .filter_map(|asset| {
+            overlay
+              .spend(&asset.inscription_id)
+              .map(|spend| PendingSpend::new(asset.inscription_id, spend))
+          })
+          .collect(),
+        txids: pending
+          .map(|pending| pending.txids.iter().map(ToString::to_string).collect())
+          .unwrap_or_default(),
+      })
+    }
+    None => None,
+  };
+
   Ok(Json(ApiResponse::ok(Transferable {
+    pending,
     inscriptions: all_transfer
       .iter()
       .map(|asset| {
@@ -109,6 +151,22 @@ pub(crate) async fn brc20s_transferable(
 pub(crate) struct Transferable {
   #[schema(value_type = Vec<brc20s::TransferableInscription>)]
   pub inscriptions: Vec<TransferableInscription>,
+  /// The transferable inscriptions once the pending transactions are confirmed. Only present when the mempool watcher is enabled.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[schema(value_type = Option<brc20s::PendingTransferable>)]
+  pub pending: Option<PendingTransferable>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[schema(as = brc20s::PendingTransferable)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PendingTransferable {
+  #[schema(value_type = Vec<brc20s::TransferableInscription>)]
+  pub inscriptions: Vec<TransferableInscription>,
+  /// The pending transactions moving the confirmed transferable inscriptions, with the transactions they replaced.
+  pub spends: Vec<PendingSpend>,
+  /// The pending transactions changing the transferable inscriptions.
+  pub txids: Vec<String>,
 }
 
 // brc20s/address/:address/transferable
@@ -141,6 +199,7 @@ pub(crate) async fn brc20s_all_transferable(
   log::debug!("rpc: get brc20s_all_transferable: {} {:?}", address, all);
 
   Ok(Json(ApiResponse::ok(Transferable {
+    pending: None,
     inscriptions: all
       .iter()
       .map(|asset| {
@@ -0,0 +1,114 @@
+use {
+  super::*,
+  crate::okx::{
+    datastore::ord::Action,
+    protocol::brc20s::{deserialize_brc20s_operation, BRC20SError},
+  },
+  axum::Json,
+  tokio::task,
+  utoipa::ToSchema,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = brc20s::ValidateRequest)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidateRequest {
+  /// The address that would inscribe the payload and receive the inscription.
+  pub address: String,
+  /// The BRC20S JSON payload.
+  pub payload: String,
+  /// The output that would fund the commit transaction, as `txid:vout`. Without it, the commit transaction spends a placeholder output of 10,000 sats of the address, whose sats have no history.
+  #[serde(default)]
+  pub funding: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = brc20s::Validation)]
+#[serde(rename_all = "camelCase")]
+pub struct Validation {
+  /// The height of the block the payload was validated in.
+  #[schema(format = "uint64")]
+  pub height: u64,
+  /// Whether the operation would succeed.
+  pub valid: bool,
+  /// The message the receipt would record.
+  pub msg: String,
+  /// The error the receipt would record.
+  #[schema(value_type = Option<Object>)]
+  pub error: Option<BRC20SError>,
+}
+
+/// Validate a BRC20S payload.
+///
+/// Execute the payload as if the address inscribed it on the funding output, or on one of its own outputs, in the next block, on a read-only view of the index that never waits for the indexer, and return the error the operation would fail with, if any.
+#[utoipa::path(
+  post,
+  path = "/api/v1/brc20s/validate",
+  request_body = brc20s::ValidateRequest,
+  responses(
+    (status = 200, description = "Obtain the result of the operation.", body = BRC20SValidation),
+    (status = 400, description = "Bad request.", body = ApiError, example = json!(&ApiError::bad_request("bad request"))),
+    (status = 500, description = "Internal server error.", body = ApiError, example = json!(&ApiError::internal("internal error"))),
+  )
+)]
+pub(crate) async fn brc20s_validate(
+  Extension(index): Extension<Arc<Index>>,
+  Json(request): Json<ValidateRequest>,
+) -> ApiResult<Validation> {
+  log::debug!("rpc: post brc20s_validate: {}", request.address);
+
+  let address: bitcoin::Address = Address::from_str(&request.address)
+    .and_then(|address| address.require_network(index.get_chain_network()))
+    .map_err(ApiError::bad_request)?;
+
+  let funding = request
+    .funding
+    .as_deref()
+    .map(OutPoint::from_str)
+    .transpose()
+    .map_err(ApiError::bad_request)?;
+
+  let inscription = Inscription {
+    content_type: Some("text/plain;charset=utf-8".as_bytes().to_vec()),
+    body: Some(request.payload.into_bytes()),
+    ..Default::default()
+  };
+
+  deserialize_brc20s_operation(
+    &inscription,
+    &Action::New {
+      cursed: false,
+      unbound: false,
+      inscription: inscription.clone(),
+    },
+  )
+  .map_err(ApiError::bad_request)?;
+
+  let simulation = task::spawn_blocking({
+    let index = index.clone();
+    move || index.simulate_inscribe(&inscription, funding, &address.script_pubkey())
+  })
+  .await
+  .map_err(ApiError::internal)??;
+
+  let receipt = simulation
+    .transactions
+    .last()
+    .and_then(|transaction| transaction.brc20s_receipts.first())
+    .ok_or_else(|| {
+      ApiError::bad_request(format!(
+        "payload would not be indexed as a BRC20S operation at height {}",
+        simulation.height
+      ))
+    })?;
+
+  Ok(Json(ApiResponse::ok(Validation {
+    height: simulation.height,
+    valid: receipt.result.is_ok(),
+    msg: match &receipt.result {
+      Ok(_) => "ok".to_string(),
+      Err(err) => err.to_string(),
+    },
+    error: receipt.result.clone().err(),
+  })))
+}
@@ -17,6 +17,20 @@ pub struct NodeInfo {
   pub build_time: Option<String>,
   /// Chain information of the blockchain.
   pub chain_info: ChainInfo,
+  /// The protocols indexed by the node, in resolution order.
+  pub protocols: Vec<ProtocolStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProtocolStatus {
+  /// The name of the protocol.
+  pub name: String,
+  /// The first height the protocol is indexed at, absent when it is not indexed.
+  #[schema(format = "uint64")]
+  pub activation_height: Option<u64>,
+  /// The tables the protocol stores its state in.
+  pub tables: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -30,11 +44,13 @@ pub struct ChainInfo {
   /// The height of the blockchain.
   #[schema(format = "uint64")]
   pub chain_height: Option<u64>,
+  /// The BRC20 and BRC20S state hash at the height of our indexer.
+  pub state_hash: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, IntoParams)]
 pub struct NodeInfoQuery {
-  /// Optional to query the BTC chain status.
+  /// Optional to query the BTC chain height, as seen by the last index update.
   btc: Option<bool>,
 }
 
@@ -60,6 +76,11 @@ pub(crate) async fn node_info(
 
   let (ord_height, btc_height) = index.height_btc(query.btc.unwrap_or_default())?;
 
+  let state_hash = match ord_height {
+    Some(height) => index.state_hash(Some(height.0))?,
+    None => None,
+  };
+
   let node_info = NodeInfo {
     version: Some(build::PKG_VERSION.into()),
     branch: Some(build::BRANCH.into()),
@@ -69,7 +90,21 @@ pub(crate) async fn node_info(
       network: Some(index.get_chain_network().to_string()),
       ord_height: ord_height.map(|h| h.0),
       chain_height: btc_height.map(|h| h.0),
+      state_hash: state_hash.map(|(_, hash)| hash.to_string()),
     },
+    protocols: index
+      .protocols()
+      .into_iter()
+      .map(|protocol| ProtocolStatus {
+        name: protocol.name.into(),
+        activation_height: protocol.activation_height,
+        tables: protocol
+          .tables
+          .iter()
+          .map(|table| table.name.to_string())
+          .collect(),
+      })
+      .collect(),
   };
 
   Ok(Json(ApiResponse::ok(node_info)))
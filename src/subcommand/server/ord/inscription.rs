@@ -92,33 +92,13 @@ fn ord_get_inscription_by_id(index: Arc<Index>, id: InscriptionId) -> ApiResult<
   let location_outpoint = inscription_data.sat_point.outpoint;
   let mut owner = None;
   if location_outpoint != unbound_outpoint() {
-    owner = if inscription_data.tx.txid() != location_outpoint.txid {
-      let location_raw_tx = index
-        .get_transaction(location_outpoint.txid)?
-        .ok_or_api_not_found(format!(
-          "inscriptionId not found {}",
-          location_outpoint.txid
-        ))?;
-      Some(
-        ScriptKey::from_script(
-          &location_raw_tx
-            .output
-            .get(usize::try_from(location_outpoint.vout).unwrap())
-            .unwrap()
-            .script_pubkey,
-          index.get_chain_network(),
-        )
-        .into(),
-      )
-    } else {
-      Some(
-        ScriptKey::from_script(
-          &inscription_data.tx.output[0].script_pubkey,
-          index.get_chain_network(),
-        )
-        .into(),
-      )
-    };
+    // the outputs an inscription is sent to are kept by the index.
+    let location_output = index
+      .get_outpoint_entry(location_outpoint)?
+      .ok_or_api_not_found(format!("outpoint not found {location_outpoint}"))?;
+    owner = Some(
+      ScriptKey::from_script(&location_output.script_pubkey, index.get_chain_network()).into(),
+    );
   };
 
   Ok(Json(ApiResponse::ok(OrdInscription {
@@ -142,7 +122,6 @@ fn ord_get_inscription_by_id(index: Arc<Index>, id: InscriptionId) -> ApiResult<
 }
 
 struct InscriptionAllData {
-  pub tx: Transaction,
   pub entry: InscriptionEntry,
   pub sat_point: SatPoint,
   pub inscription: Inscription,
@@ -157,15 +136,10 @@ fn get_inscription_all_data_by_id(
     Some(entry) => entry,
     None => return Ok(None),
   };
-  let tx = match index.get_transaction(inscription_id.txid)? {
-    Some(tx) => tx,
+  let inscription = match get_inscription(&index, inscription_id)? {
+    Some(inscription) => inscription,
     None => return Ok(None),
   };
-  let inscription =
-    match Inscription::from_transaction(&tx).get(usize::try_from(inscription_id.index).unwrap()) {
-      Some(transaction_inscription) => transaction_inscription.inscription.clone(),
-      None => return Ok(None),
-    };
 
   let sat_point = match index.get_inscription_satpoint_by_id(inscription_id)? {
     Some(sat_point) => sat_point,
@@ -178,7 +152,6 @@ fn get_inscription_all_data_by_id(
 
   Ok(Some(InscriptionAllData {
     entry,
-    tx,
     inscription,
     collections,
     sat_point,
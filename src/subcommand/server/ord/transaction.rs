@@ -51,10 +51,20 @@ pub struct TxInscription {
 }
 
 impl TxInscription {
-  pub(super) fn new(op: InscriptionOp, index: Arc<Index>) -> Result<Self> {
-    let from = index
-      .get_outpoint_entry(op.old_satpoint.outpoint)?
-      .map(|txout| ScriptKey::from_script(&txout.script_pubkey, index.get_chain_network()))
+  pub(crate) fn new(op: InscriptionOp, index: Arc<Index>) -> Result<Self> {
+    Self::with_outputs(op, index.get_chain_network(), |outpoint| {
+      index.get_outpoint_entry(outpoint)
+    })
+  }
+
+  /// Builds the inscription action, looking up the spent and created outputs with `get_output`.
+  pub(crate) fn with_outputs(
+    op: InscriptionOp,
+    network: Network,
+    get_output: impl Fn(OutPoint) -> Result<Option<TxOut>>,
+  ) -> Result<Self> {
+    let from = get_output(op.old_satpoint.outpoint)?
+      .map(|txout| ScriptKey::from_script(&txout.script_pubkey, network))
       .ok_or(anyhow!(
         "outpoint {} not found from database",
         op.old_satpoint.outpoint
@@ -66,9 +76,8 @@ impl TxInscription {
           None
         } else {
           Some(
-            index
-              .get_outpoint_entry(new_satpoint.outpoint)?
-              .map(|txout| ScriptKey::from_script(&txout.script_pubkey, index.get_chain_network()))
+            get_output(new_satpoint.outpoint)?
+              .map(|txout| ScriptKey::from_script(&txout.script_pubkey, network))
               .ok_or(anyhow!(
                 "outpoint {} not found from database",
                 new_satpoint.outpoint
@@ -178,25 +187,13 @@ pub(crate) async fn ord_block_inscriptions(
   log::debug!("rpc: get ord_block_inscriptions: {}", blockhash);
 
   let blockhash = bitcoin::BlockHash::from_str(&blockhash).map_err(ApiError::bad_request)?;
-  // get block from btc client.
-  let blockinfo = index
-    .get_block_info_by_hash(blockhash)
-    .map_err(ApiError::internal)?
-    .ok_or_api_not_found(OrdError::BlockNotFound)?;
-
-  // get blockhash from redb.
-  let blockhash = index
-    .block_hash(Some(u64::try_from(blockinfo.height).unwrap()))
+  let txids = index
+    .get_block_transactions_by_hash(blockhash)
     .map_err(ApiError::internal)?
     .ok_or_api_not_found(OrdError::BlockNotFound)?;
 
-  // check of conflicting block.
-  if blockinfo.hash != blockhash {
-    return Err(ApiError::NotFound(OrdError::BlockNotFound.to_string()));
-  }
-
   let block_inscriptions = index
-    .ord_get_txs_inscriptions(&blockinfo.tx)
+    .ord_get_txs_inscriptions(&txids)
     .map_err(ApiError::internal)?;
 
   log::debug!("rpc: get ord_block_inscriptions: {:?}", block_inscriptions);
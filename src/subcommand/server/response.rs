@@ -6,11 +6,17 @@ use {
 #[aliases(
   BRC20Tick = ApiResponse<brc20::TickInfo>,
   BRC20AllTick = ApiResponse<brc20::AllTickInfo>,
+  BRC20TickHolders = ApiResponse<brc20::TickHolders>,
+  BRC20TickStats = ApiResponse<brc20::TickStats>,
+  BRC20AllTickStats = ApiResponse<brc20::AllTickStats>,
   BRC20Balance = ApiResponse<brc20::Balance>,
   BRC20AllBalance = ApiResponse<brc20::AllBalance>,
   BRC20TxEvents = ApiResponse<brc20::TxEvents>,
   BRC20BlockEvents = ApiResponse<brc20::BlockEvents>,
+  BRC20StateHash = ApiResponse<brc20::StateHash>,
+  BRC20AddressEvents = ApiResponse<brc20::AddressEvents>,
   BRC20Transferable = ApiResponse<brc20::TransferableInscriptions>,
+  BRC20Validation = ApiResponse<brc20::Validation>,
 
   BRC20STick = ApiResponse<brc20s::TickInfo>,
   BRC20SAllTick = ApiResponse<brc20s::AllTickInfo>,
@@ -20,9 +26,11 @@ use {
   BRC20SAllPool = ApiResponse<brc20s::AllPoolInfo>,
   BRC20STxReceipts = ApiResponse<brc20s::TxReceipts>,
   BRC20SBlockReceipts = ApiResponse<brc20s::BlockReceipts>,
+  BRC20SAddressReceipts = ApiResponse<brc20s::AddressReceipts>,
   BRC20STransferable = ApiResponse<brc20s::Transferable>,
   BRC20SUserInfo = ApiResponse<brc20s::UserInfo>,
   BRC20SStakedInfo = ApiResponse<brc20s::StakedInfo>,
+  BRC20SValidation = ApiResponse<brc20s::Validation>,
 
   OrdOrdInscription = ApiResponse<ord::OrdInscription>,
   OrdOutPointData = ApiResponse<ord::OutPointData>,
@@ -30,6 +38,12 @@ use {
   OrdTxInscriptions = ApiResponse<ord::TxInscriptions>,
   OrdBlockInscriptions = ApiResponse<ord::BlockInscriptions>,
 
+  WebhookInfo = ApiResponse<webhook::Webhook>,
+  WebhookList = ApiResponse<webhook::Webhooks>,
+  WebhookDeliveries = ApiResponse<webhook::Deliveries>,
+
+  SimulationResult = ApiResponse<simulate::Simulation>,
+
   Node = ApiResponse<NodeInfo>
 )]
 pub(crate) struct ApiResponse<T: Serialize> {
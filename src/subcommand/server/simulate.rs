@@ -0,0 +1,148 @@
+use {
+  super::{ord::TxInscription, *},
+  base64::Engine,
+  bitcoin::{consensus, psbt::PartiallySignedTransaction, Witness},
+  std::collections::HashMap,
+  tokio::task,
+  utoipa::ToSchema,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = simulate::SimulateRequest)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulateRequest {
+  /// The hex encoded raw transaction.
+  pub tx: Option<String>,
+  /// The base64 encoded PSBT. Unfinalized taproot inputs are simulated with their tapscript.
+  pub psbt: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = simulate::Simulation)]
+#[serde(rename_all = "camelCase")]
+pub struct Simulation {
+  pub txid: String,
+  /// The height of the block the transaction was simulated in.
+  #[schema(format = "uint64")]
+  pub height: u64,
+  #[schema(value_type = Vec<ord::TxInscription>)]
+  pub inscriptions: Vec<TxInscription>,
+  #[schema(value_type = Vec<brc20::TxEvent>)]
+  pub brc20: Vec<brc20::TxEvent>,
+  #[schema(value_type = Vec<brc20s::Receipt>)]
+  pub brc20s: Vec<brc20s::Receipt>,
+}
+
+/// Returns the transaction of the PSBT and the spent outputs it carries.
+fn psbt_transaction(psbt: &str) -> Result<(Transaction, HashMap<OutPoint, TxOut>)> {
+  let psbt = PartiallySignedTransaction::deserialize(
+    &base64::engine::general_purpose::STANDARD.decode(psbt)?,
+  )?;
+
+  let mut tx = psbt.unsigned_tx.clone();
+  let mut outputs = HashMap::new();
+
+  for (tx_in, input) in tx.input.iter_mut().zip(&psbt.inputs) {
+    let outpoint = tx_in.previous_output;
+
+    if let Some(tx_out) = input.witness_utxo.clone().or_else(|| {
+      input.non_witness_utxo.as_ref().and_then(|prev_tx| {
+        prev_tx
+          .output
+          .get(usize::try_from(outpoint.vout).unwrap())
+          .cloned()
+      })
+    }) {
+      outputs.insert(outpoint, tx_out);
+    }
+
+    if let Some(script_sig) = &input.final_script_sig {
+      tx_in.script_sig = script_sig.clone();
+    }
+
+    if let Some(witness) = &input.final_script_witness {
+      tx_in.witness = witness.clone();
+    } else if let Some((control_block, (script, _))) = input.tap_scripts.iter().next() {
+      // a placeholder signature is enough to find the envelope in the tapscript.
+      tx_in.witness = Witness::from_slice(&[
+        [0; 64].as_slice(),
+        script.as_bytes(),
+        &control_block.serialize(),
+      ]);
+    }
+  }
+
+  Ok((tx, outputs))
+}
+
+/// Simulate a transaction.
+///
+/// Execute the raw transaction or PSBT as if it were mined in the next block, without committing anything, and return the inscription actions and the BRC20 and BRC20S receipts it would produce.
+#[utoipa::path(
+    post,
+    path = "/api/v1/simulate",
+    request_body = simulate::SimulateRequest,
+    responses(
+      (status = 200, description = "Obtain the simulated effects of the transaction.", body = SimulationResult),
+      (status = 400, description = "Bad request.", body = ApiError, example = json!(&ApiError::bad_request("bad request"))),
+      (status = 500, description = "Internal server error.", body = ApiError, example = json!(&ApiError::internal("internal error"))),
+    )
+  )]
+pub(crate) async fn simulate(
+  Extension(index): Extension<Arc<Index>>,
+  Json(request): Json<SimulateRequest>,
+) -> ApiResult<Simulation> {
+  log::debug!("rpc: post simulate");
+
+  let (tx, outputs) = match (request.tx, request.psbt) {
+    (Some(tx), None) => (
+      hex::decode(tx)
+        .map_err(anyhow::Error::from)
+        .and_then(|tx| Ok(consensus::deserialize::<Transaction>(&tx)?))
+        .map_err(ApiError::bad_request)?,
+      HashMap::new(),
+    ),
+    (None, Some(psbt)) => psbt_transaction(&psbt).map_err(ApiError::bad_request)?,
+    _ => {
+      return Err(ApiError::bad_request(
+        "exactly one of tx or psbt must be set",
+      ))
+    }
+  };
+
+  if tx.is_coin_base() {
+    return Err(ApiError::bad_request(
+      "coinbase transactions cannot be simulated",
+    ));
+  }
+
+  let simulation = task::spawn_blocking({
+    let index = index.clone();
+    move || index.simulate(&[tx], outputs)
+  })
+  .await
+  .map_err(ApiError::internal)??;
+
+  let network = index.get_chain_network();
+  let transaction = simulation.transactions.into_iter().next().unwrap();
+
+  Ok(Json(ApiResponse::ok(Simulation {
+    txid: transaction.txid.to_string(),
+    height: simulation.height,
+    inscriptions: transaction
+      .operations
+      .into_iter()
+      .map(|op| {
+        TxInscription::with_outputs(op, network, |outpoint| {
+          Ok(simulation.outputs.get(&outpoint).cloned())
+        })
+      })
+      .collect::<Result<Vec<_>>>()?,
+    brc20: transaction.brc20_receipts.iter().map(Into::into).collect(),
+    brc20s: transaction
+      .brc20s_receipts
+      .iter()
+      .map(|receipt| brc20s::Receipt::from(receipt, index.clone()))
+      .collect::<Result<Vec<_>>>()?,
+  })))
+}
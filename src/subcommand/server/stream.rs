@@ -0,0 +1,234 @@
+use {
+  super::{types::ScriptPubkey, *},
+  crate::index::{
+    notification::{self, FilterError},
+    Notification,
+  },
+  axum::response::sse::{self, KeepAlive, Sse},
+  futures::stream::{self, Stream},
+  std::convert::Infallible,
+  tokio::sync::broadcast::error::RecvError,
+  utoipa::{IntoParams, ToSchema},
+};
+
+#[derive(Deserialize, IntoParams)]
+pub struct StreamFilter {
+  /// Only stream BRC20 receipts of the ticker.
+  pub tick: Option<String>,
+  /// Only stream BRC20S receipts of the ticker id.
+  pub tick_id: Option<String>,
+  /// Only stream BRC20S receipts of the pool.
+  pub pid: Option<String>,
+  /// Only stream inscriptions and receipts sent or received by the address.
+  pub address: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = stream::BlockNotification)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockNotification {
+  /// The height of the connected block.
+  pub height: u64,
+  /// The hash of the connected block.
+  pub blockhash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = stream::ReorgNotification)]
+#[serde(rename_all = "camelCase")]
+pub struct ReorgNotification {
+  /// The height of the first disconnected block.
+  pub height: u64,
+  /// The number of disconnected blocks.
+  pub depth: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = stream::InscriptionNotification)]
+#[serde(rename_all = "camelCase")]
+pub struct InscriptionNotification {
+  pub height: u64,
+  pub txid: String,
+  #[schema(value_type = ord::TxInscription)]
+  pub inscription: ord::TxInscription,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = stream::BRC20Notification)]
+#[serde(rename_all = "camelCase")]
+pub struct BRC20Notification {
+  pub height: u64,
+  pub txid: String,
+  #[schema(value_type = brc20::TxEvent)]
+  pub event: brc20::TxEvent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = stream::BRC20SNotification)]
+#[serde(rename_all = "camelCase")]
+pub struct BRC20SNotification {
+  pub height: u64,
+  pub txid: String,
+  #[schema(value_type = brc20s::Receipt)]
+  pub receipt: brc20s::Receipt,
+}
+
+pub(super) struct Filter(notification::Filter);
+
+impl Filter {
+  pub(super) fn new(filter: StreamFilter, index: &Index) -> Result<Self, ApiError> {
+    notification::Filter::new(
+      filter.tick.as_deref(),
+      filter.tick_id.as_deref(),
+      filter.pid.as_deref(),
+      filter.address.as_deref(),
+      index.get_chain_network(),
+    )
+    .map(Self)
+    .map_err(|err| match err {
+      FilterError::Tick => ApiError::bad_request(brc20::BRC20Error::IncorrectTickFormat),
+      FilterError::TickId => ApiError::bad_request(brc20s::BRC20SError::IncorrectTickIdFormat),
+      FilterError::Pid => ApiError::bad_request(brc20s::BRC20SError::IncorrectPidFormat),
+      FilterError::Address(err) => ApiError::bad_request(err),
+    })
+  }
+
+  /// Passes every notification.
+  pub(super) fn unscoped() -> Self {
+    Self(notification::Filter::default())
+  }
+
+  /// Whether a protocol or address filter is set.
+  pub(super) fn is_scoped(&self) -> bool {
+    self.0.is_scoped()
+  }
+
+  /// Converts the notification to an event name and its JSON data, or `None` if it doesn't pass the filter.
+  pub(super) fn apply(
+    &self,
+    notification: &Notification,
+    index: Arc<Index>,
+  ) -> Result<Option<(&'static str, String)>> {
+    Ok(Some(match notification {
+      Notification::BlockConnected { height, blockhash } => (
+        "block",
+        serde_json::to_string(&BlockNotification {
+          height: *height,
+          blockhash: blockhash.to_string(),
+        })?,
+      ),
+      Notification::Reorg { height, depth } => (
+        "reorg",
+        serde_json::to_string(&ReorgNotification {
+          height: *height,
+          depth: *depth,
+        })?,
+      ),
+      Notification::Inscription { height, op } => {
+        if self.0.is_protocol_scoped() {
+          return Ok(None);
+        }
+        let inscription = ord::TxInscription::new(op.clone(), index)?;
+        if let Some(script_key) = self.0.script_key() {
+          let script_pubkey = ScriptPubkey::from(script_key.clone());
+          if inscription.from != script_pubkey && inscription.to.as_ref() != Some(&script_pubkey) {
+            return Ok(None);
+          }
+        }
+        (
+          "inscription",
+          serde_json::to_string(&InscriptionNotification {
+            height: *height,
+            txid: op.txid.to_string(),
+            inscription,
+          })?,
+        )
+      }
+      Notification::BRC20Receipt {
+        height,
+        txid,
+        receipt,
+      } => {
+        if !self.0.matches_brc20(receipt) {
+          return Ok(None);
+        }
+        (
+          "brc20",
+          serde_json::to_string(&BRC20Notification {
+            height: *height,
+            txid: txid.to_string(),
+            event: receipt.into(),
+          })?,
+        )
+      }
+      Notification::BRC20SReceipt {
+        height,
+        txid,
+        receipt,
+      } => {
+        if !self.0.matches_brc20s(receipt) {
+          return Ok(None);
+        }
+        (
+          "brc20s",
+          serde_json::to_string(&BRC20SNotification {
+            height: *height,
+            txid: txid.to_string(),
+            receipt: brc20s::Receipt::from(receipt, index)?,
+          })?,
+        )
+      }
+    }))
+  }
+}
+
+/// Stream the index notifications.
+///
+/// Push `block`, `reorg`, `inscription`, `brc20` and `brc20s` server-sent events as the indexer commits them.
+/// Block and reorg events are always sent, the filters only apply to inscriptions and receipts.
+/// A `lagged` event carrying the number of skipped events is sent when the client falls behind.
+#[utoipa::path(
+    get,
+    path = "/api/v1/stream",
+    params(
+        StreamFilter
+  ),
+    responses(
+      (status = 200, description = "A stream of server-sent events.", content_type = "text/event-stream", body = String),
+      (status = 400, description = "Bad query.", body = ApiError, example = json!(&ApiError::bad_request("bad request"))),
+    )
+  )]
+pub(crate) async fn stream(
+  Extension(index): Extension<Arc<Index>>,
+  Query(filter): Query<StreamFilter>,
+) -> Result<Sse<impl Stream<Item = Result<sse::Event, Infallible>>>, ApiError> {
+  let filter = Filter::new(filter, &index)?;
+  let receiver = index.subscribe();
+
+  log::debug!("rpc: subscribe stream");
+
+  let events = stream::unfold(
+    (receiver, index, filter),
+    |(mut receiver, index, filter)| async move {
+      loop {
+        let event = match receiver.recv().await {
+          Ok(notification) => match filter.apply(&notification, index.clone()) {
+            Ok(Some((event, data))) => sse::Event::default().event(event).data(data),
+            Ok(None) => continue,
+            Err(err) => {
+              log::warn!("rpc: failed to stream notification: {err}");
+              continue;
+            }
+          },
+          Err(RecvError::Lagged(skipped)) => sse::Event::default()
+            .event("lagged")
+            .data(skipped.to_string()),
+          Err(RecvError::Closed) => return None,
+        };
+        return Some((Ok(event), (receiver, index, filter)));
+      }
+    },
+  );
+
+  Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
@@ -1,5 +1,5 @@
 use super::*;
-use crate::okx::datastore::ScriptKey;
+use crate::{index::mempool, okx::datastore::ScriptKey};
 use utoipa::ToSchema;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
@@ -24,6 +24,29 @@ impl From<ScriptKey> for ScriptPubkey {
     }
   }
 }
+
+/// A transferable inscription moved by a pending transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingSpend {
+  /// The inscription id.
+  pub inscription_id: String,
+  /// The pending transaction moving the inscription.
+  pub txid: String,
+  /// The earlier pending transactions moving the inscription that were replaced or evicted.
+  pub replaced: Vec<String>,
+}
+
+impl PendingSpend {
+  pub(crate) fn new(inscription_id: InscriptionId, spend: &mempool::PendingSpend) -> Self {
+    Self {
+      inscription_id: inscription_id.to_string(),
+      txid: spend.txid.to_string(),
+      replaced: spend.replaced.iter().map(ToString::to_string).collect(),
+    }
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
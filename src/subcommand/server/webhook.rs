@@ -0,0 +1,449 @@
+use {
+  super::{
+    stream::{Filter, StreamFilter},
+    *,
+  },
+  crate::index::{
+    webhook::{self as store, now, DeliveryStatus, Payload},
+    Notification,
+  },
+  hyper::{client::HttpConnector, Body, Client, Method, Request},
+  std::{collections::BTreeMap, sync::Mutex},
+  tokio::task,
+  utoipa::ToSchema,
+};
+
+/// How often the pending deliveries are polled.
+const DELIVERY_INTERVAL: Duration = Duration::from_secs(1);
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = webhook::WebhookRequest)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookRequest {
+  /// The `http://` URL the events are posted to.
+  pub url: String,
+  /// Only post receipts sent or received by the address.
+  pub address: Option<String>,
+  /// Only post BRC20 receipts of the ticker.
+  pub tick: Option<String>,
+  /// Only post BRC20S receipts of the ticker id.
+  pub tick_id: Option<String>,
+  /// Only post BRC20S receipts of the pool.
+  pub pid: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = webhook::Webhook)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+  #[schema(format = "uint64")]
+  pub id: u64,
+  pub url: String,
+  pub address: Option<String>,
+  pub tick: Option<String>,
+  pub tick_id: Option<String>,
+  pub pid: Option<String>,
+}
+
+impl Webhook {
+  fn new(id: u64, subscription: store::Subscription) -> Self {
+    Self {
+      id,
+      url: subscription.url,
+      address: subscription.address,
+      tick: subscription.tick,
+      tick_id: subscription.tick_id,
+      pid: subscription.pid,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = webhook::Webhooks)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhooks {
+  #[schema(value_type = Vec<webhook::Webhook>)]
+  pub webhooks: Vec<Webhook>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = webhook::Delivery)]
+#[serde(rename_all = "camelCase")]
+pub struct Delivery {
+  #[schema(format = "uint64")]
+  pub id: u64,
+  /// The height of the block the event was committed in.
+  #[schema(format = "uint64")]
+  pub height: u64,
+  /// The id of the delivery this revert notification compensates for.
+  #[schema(format = "uint64")]
+  pub reverts: Option<u64>,
+  /// Whether the block of the event was reorged out.
+  pub reverted: bool,
+  /// One of `pending`, `delivered`, `failed` or `cancelled`.
+  pub status: String,
+  pub attempts: u32,
+  /// Unix time in milliseconds of the last attempt.
+  #[schema(format = "uint64")]
+  pub last_attempt: Option<u64>,
+  pub last_error: Option<String>,
+  /// The posted JSON document.
+  #[schema(value_type = Object)]
+  pub payload: serde_json::Value,
+}
+
+impl Delivery {
+  fn new(id: u64, delivery: store::Delivery, index: &Arc<Index>) -> Self {
+    // receipts that were never attempted are rendered as they would be posted now.
+    let payload = render(index, &delivery)
+      .ok()
+      .and_then(|payload| serde_json::from_str(&payload).ok())
+      .unwrap_or_default();
+    Self {
+      id,
+      height: delivery.height,
+      reverts: delivery.reverts,
+      reverted: delivery.reverted,
+      status: match delivery.status {
+        DeliveryStatus::Pending => "pending",
+        DeliveryStatus::Delivered => "delivered",
+        DeliveryStatus::Failed => "failed",
+        DeliveryStatus::Cancelled => "cancelled",
+      }
+      .into(),
+      attempts: delivery.attempts,
+      last_attempt: delivery.last_attempt,
+      last_error: delivery.last_error,
+      payload,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = webhook::Deliveries)]
+#[serde(rename_all = "camelCase")]
+pub struct Deliveries {
+  #[schema(value_type = Vec<webhook::Delivery>)]
+  pub deliveries: Vec<Delivery>,
+  /// The total number of deliveries of the webhook.
+  pub total: usize,
+}
+
+impl From<store::Subscription> for StreamFilter {
+  fn from(subscription: store::Subscription) -> Self {
+    Self {
+      tick: subscription.tick,
+      tick_id: subscription.tick_id,
+      pid: subscription.pid,
+      address: subscription.address,
+    }
+  }
+}
+
+/// Register a webhook.
+///
+/// Post the BRC20 and BRC20S receipts matching all of the set filters to the URL as they are committed, retrying failed deliveries with exponential backoff.
+/// When a block is reorged out, a `revert` event is posted for every event of the block that was already attempted.
+#[utoipa::path(
+    post,
+    path = "/api/v1/webhooks",
+    request_body = webhook::WebhookRequest,
+    responses(
+      (status = 200, description = "Obtain the registered webhook.", body = WebhookInfo),
+      (status = 400, description = "Bad request.", body = ApiError, example = json!(&ApiError::bad_request("bad request"))),
+      (status = 500, description = "Internal server error.", body = ApiError, example = json!(&ApiError::internal("internal error"))),
+    )
+  )]
+pub(crate) async fn webhook_subscribe(
+  Extension(index): Extension<Arc<Index>>,
+  Json(request): Json<WebhookRequest>,
+) -> ApiResult<Webhook> {
+  log::debug!("rpc: post webhook_subscribe: {}", request.url);
+
+  let url = request
+    .url
+    .parse::<hyper::Uri>()
+    .map_err(ApiError::bad_request)?;
+  if url.scheme_str() != Some("http") || url.host().is_none() {
+    return Err(ApiError::bad_request("webhook url must be an http:// url"));
+  }
+
+  let subscription = store::Subscription {
+    url: request.url,
+    address: request.address,
+    tick: request.tick,
+    tick_id: request.tick_id,
+    pid: request.pid,
+  };
+
+  if !Filter::new(subscription.clone().into(), &index)?.is_scoped() {
+    return Err(ApiError::bad_request(
+      "webhook must filter by address, tick, tick id or pid",
+    ));
+  }
+
+  let id = index.webhook_subscribe(&subscription)?;
+
+  Ok(Json(ApiResponse::ok(Webhook::new(id, subscription))))
+}
+
+/// Get all webhooks.
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks",
+    responses(
+      (status = 200, description = "Obtain the registered webhooks.", body = WebhookList),
+      (status = 500, description = "Internal server error.", body = ApiError, example = json!(&ApiError::internal("internal error"))),
+    )
+  )]
+pub(crate) async fn webhooks(Extension(index): Extension<Arc<Index>>) -> ApiResult<Webhooks> {
+  log::debug!("rpc: get webhooks");
+
+  Ok(Json(ApiResponse::ok(Webhooks {
+    webhooks: index
+      .webhook_subscriptions()?
+      .into_iter()
+      .map(|(id, subscription)| Webhook::new(id, subscription))
+      .collect(),
+  })))
+}
+
+/// Get the webhook.
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks/{id}",
+    params(
+        ("id" = u64, Path, description = "Webhook id")
+  ),
+    responses(
+      (status = 200, description = "Obtain the webhook.", body = WebhookInfo),
+      (status = 404, description = "Webhook not found.", body = ApiError, example = json!(&ApiError::not_found("not found"))),
+      (status = 500, description = "Internal server error.", body = ApiError, example = json!(&ApiError::internal("internal error"))),
+    )
+  )]
+pub(crate) async fn webhook(
+  Extension(index): Extension<Arc<Index>>,
+  Path(id): Path<u64>,
+) -> ApiResult<Webhook> {
+  log::debug!("rpc: get webhook: {}", id);
+
+  let subscription = index
+    .webhook_subscription(id)?
+    .ok_or_api_not_found("webhook not found")?;
+
+  Ok(Json(ApiResponse::ok(Webhook::new(id, subscription))))
+}
+
+/// Remove the webhook.
+///
+/// The pending deliveries of the webhook are cancelled.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/webhooks/{id}",
+    params(
+        ("id" = u64, Path, description = "Webhook id")
+  ),
+    responses(
+      (status = 200, description = "Obtain the removed webhook.", body = WebhookInfo),
+      (status = 404, description = "Webhook not found.", body = ApiError, example = json!(&ApiError::not_found("not found"))),
+      (status = 500, description = "Internal server error.", body = ApiError, example = json!(&ApiError::internal("internal error"))),
+    )
+  )]
+pub(crate) async fn webhook_unsubscribe(
+  Extension(index): Extension<Arc<Index>>,
+  Path(id): Path<u64>,
+) -> ApiResult<Webhook> {
+  log::debug!("rpc: delete webhook_unsubscribe: {}", id);
+
+  let subscription = index
+    .webhook_subscription(id)?
+    .ok_or_api_not_found("webhook not found")?;
+
+  if !index.webhook_unsubscribe(id)? {
+    return Err(ApiError::not_found("webhook not found"));
+  }
+
+  Ok(Json(ApiResponse::ok(Webhook::new(id, subscription))))
+}
+
+/// Get the deliveries of the webhook.
+///
+/// Retrieve the events posted or queued for the webhook and their delivery state, newest first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks/{id}/deliveries",
+    params(
+        ("id" = u64, Path, description = "Webhook id"),
+        Pagination
+  ),
+    responses(
+      (status = 200, description = "Obtain the deliveries of the webhook.", body = WebhookDeliveries),
+      (status = 404, description = "Webhook not found.", body = ApiError, example = json!(&ApiError::not_found("not found"))),
+      (status = 500, description = "Internal server error.", body = ApiError, example = json!(&ApiError::internal("internal error"))),
+    )
+  )]
+pub(crate) async fn webhook_deliveries(
+  Extension(index): Extension<Arc<Index>>,
+  Path(id): Path<u64>,
+  Query(page): Query<Pagination>,
+) -> ApiResult<Deliveries> {
+  log::debug!("rpc: get webhook_deliveries: {}", id);
+
+  index
+    .webhook_subscription(id)?
+    .ok_or_api_not_found("webhook not found")?;
+
+  let (deliveries, total) =
+    index.webhook_deliveries(id, page.start.unwrap_or_default(), page.limit)?;
+
+  Ok(Json(ApiResponse::ok(Deliveries {
+    deliveries: deliveries
+      .into_iter()
+      .map(|(id, delivery)| Delivery::new(id, delivery, &index))
+      .collect(),
+    total,
+  })))
+}
+
+/// Renders the JSON document posted for the delivery.
+fn render(index: &Arc<Index>, delivery: &store::Delivery) -> Result<String> {
+  let notification = match &delivery.payload {
+    Payload::Rendered(payload) => return Ok(payload.clone()),
+    Payload::BRC20Receipt { txid, receipt } => Notification::BRC20Receipt {
+      height: delivery.height,
+      txid: *txid,
+      receipt: receipt.clone(),
+    },
+    Payload::BRC20SReceipt { txid, receipt } => Notification::BRC20SReceipt {
+      height: delivery.height,
+      txid: *txid,
+      receipt: receipt.clone(),
+    },
+  };
+
+  let (kind, data) = Filter::unscoped()
+    .apply(&notification, index.clone())?
+    .ok_or_else(|| anyhow!("receipt not rendered"))?;
+
+  Ok(
+    serde_json::json!({
+      "subscription": delivery.subscription,
+      "type": kind,
+      "height": delivery.height,
+      "data": serde_json::from_str::<serde_json::Value>(&data)?,
+    })
+    .to_string(),
+  )
+}
+
+async fn post(client: &Client<HttpConnector>, url: &str, id: u64, payload: String) -> Result {
+  let request = Request::builder()
+    .method(Method::POST)
+    .uri(url)
+    .header(header::CONTENT_TYPE, "application/json")
+    .header("X-Webhook-Delivery", id)
+    .body(Body::from(payload))?;
+
+  let response = tokio::time::timeout(DELIVERY_TIMEOUT, client.request(request))
+    .await
+    .map_err(|_| anyhow!("timed out"))??;
+
+  if !response.status().is_success() {
+    bail!("unexpected status {}", response.status());
+  }
+
+  Ok(())
+}
+
+/// Attempts the deliveries of an endpoint one by one, so a revert is never posted before the
+/// event it reverts.
+async fn deliver_to(
+  index: Arc<Index>,
+  client: Client<HttpConnector>,
+  url: Option<String>,
+  deliveries: Vec<(u64, store::Delivery)>,
+) -> Result {
+  for (id, delivery) in deliveries {
+    let (payload, error) = match &url {
+      Some(url) => {
+        let rendered = {
+          let index = index.clone();
+          task::spawn_blocking(move || render(&index, &delivery)).await?
+        };
+        match rendered {
+          Ok(payload) => {
+            let error = post(&client, url, id, payload.clone())
+              .await
+              .err()
+              .map(|err| err.to_string());
+            (Some(payload), error)
+          }
+          Err(err) => (None, Some(format!("failed to render payload: {err}"))),
+        }
+      }
+      None => (None, Some("webhook removed".into())),
+    };
+
+    if let Some(error) = &error {
+      log::info!("webhook delivery {id} failed: {error}");
+    }
+
+    let index = index.clone();
+    task::spawn_blocking(move || index.webhook_record_attempt(id, now(), payload, error)).await??;
+  }
+
+  Ok(())
+}
+
+/// Attempts the due deliveries, concurrently across endpoints. An endpoint still working through
+/// its deliveries is skipped until it's done, so a slow endpoint doesn't hold up the others.
+async fn deliver(
+  index: &Arc<Index>,
+  client: &Client<HttpConnector>,
+  busy: &Arc<Mutex<HashSet<Option<String>>>>,
+) -> Result {
+  let due = {
+    let index = index.clone();
+    task::spawn_blocking(move || index.webhook_due_deliveries(now())).await??
+  };
+
+  let mut endpoints = BTreeMap::<Option<String>, Vec<(u64, store::Delivery)>>::new();
+  for (id, delivery) in due {
+    let url = index
+      .webhook_subscription(delivery.subscription)?
+      .map(|subscription| subscription.url);
+    endpoints.entry(url).or_default().push((id, delivery));
+  }
+
+  for (url, deliveries) in endpoints {
+    if !busy.lock().unwrap().insert(url.clone()) {
+      continue;
+    }
+
+    let (index, client, busy) = (index.clone(), client.clone(), busy.clone());
+    tokio::spawn(async move {
+      if let Err(err) = deliver_to(index, client, url.clone(), deliveries).await {
+        log::error!("failed to deliver webhooks: {err}");
+      }
+      busy.lock().unwrap().remove(&url);
+    });
+  }
+
+  Ok(())
+}
+
+/// Spawns the task posting the deliveries the indexer queues.
+pub(super) fn spawn_dispatcher(index: Arc<Index>) {
+  tokio::spawn(async move {
+    let client = Client::new();
+    let busy = Arc::new(Mutex::new(HashSet::new()));
+    let mut interval = tokio::time::interval(DELIVERY_INTERVAL);
+    loop {
+      interval.tick().await;
+      if let Err(err) = deliver(&index, &client, &busy).await {
+        log::error!("failed to deliver webhooks: {err}");
+      }
+    }
+  });
+}
@@ -1,5 +1,125 @@
-use super::*;
+use {
+  super::*,
+  bitcoin::{
+    bip32::{ChildNumber, DerivationPath, ExtendedPrivKey, Fingerprint},
+    secp256k1::{All, Secp256k1},
+  },
+  bitcoincore_rpc::bitcoincore_rpc_json::{ImportDescriptors, Timestamp},
+  miniscript::descriptor::{Descriptor, DescriptorSecretKey, DescriptorXKey, Wildcard},
+};
 
+pub mod brc20;
+pub mod brc20s;
 pub mod create;
 pub mod inscribe;
 pub mod outputs;
+pub mod receive;
+pub(crate) mod transaction_builder;
+
+#[derive(Debug, Parser)]
+pub(crate) enum Wallet {
+  #[command(subcommand, about = "BRC20 commands")]
+  Brc20(brc20::Brc20),
+  #[command(subcommand, about = "BRC20S commands")]
+  Brc20s(brc20s::Brc20s),
+  #[command(about = "Create new wallet")]
+  Create(create::Create),
+  #[command(about = "Create inscription")]
+  Inscribe(inscribe::Inscribe),
+  #[command(about = "List wallet outputs")]
+  Outputs,
+  #[command(about = "Generate receive address")]
+  Receive,
+}
+
+impl Wallet {
+  pub(crate) fn run(self, options: Options) -> SubcommandResult {
+    match self {
+      Self::Brc20(brc20) => brc20.run(options),
+      Self::Brc20s(brc20s) => brc20s.run(options),
+      Self::Create(create) => create.run(options),
+      Self::Inscribe(inscribe) => inscribe.run(options),
+      Self::Outputs => outputs::run(options),
+      Self::Receive => receive::run(options),
+    }
+  }
+}
+
+fn get_change_address(client: &Client, chain: Chain) -> Result<Address> {
+  Ok(
+    client
+      .call::<Address<NetworkUnchecked>>("getrawchangeaddress", &["bech32m".into()])
+      .context("could not get change addresses from wallet")?
+      .require_network(chain.network())?,
+  )
+}
+
+pub(crate) fn initialize_wallet(options: &Options, seed: [u8; 64]) -> Result {
+  let client = options.bitcoin_rpc_client_for_wallet_command(true)?;
+  let network = options.chain().network();
+
+  client.create_wallet(&options.wallet, None, Some(true), None, None)?;
+
+  let secp = Secp256k1::new();
+
+  let master_private_key = ExtendedPrivKey::new_master(network, &seed)?;
+
+  let fingerprint = master_private_key.fingerprint(&secp);
+
+  let derivation_path = DerivationPath::master()
+    .child(ChildNumber::Hardened { index: 86 })
+    .child(ChildNumber::Hardened {
+      index: u32::from(network != Network::Bitcoin),
+    })
+    .child(ChildNumber::Hardened { index: 0 });
+
+  let derived_private_key = master_private_key.derive_priv(&secp, &derivation_path)?;
+
+  for change in [false, true] {
+    derive_and_import_descriptor(
+      &client,
+      &secp,
+      (fingerprint, derivation_path.clone()),
+      derived_private_key,
+      change,
+    )?;
+  }
+
+  Ok(())
+}
+
+fn derive_and_import_descriptor(
+  client: &Client,
+  secp: &Secp256k1<All>,
+  origin: (Fingerprint, DerivationPath),
+  derived_private_key: ExtendedPrivKey,
+  change: bool,
+) -> Result {
+  let secret_key = DescriptorSecretKey::XPrv(DescriptorXKey {
+    origin: Some(origin),
+    xkey: derived_private_key,
+    derivation_path: DerivationPath::master().child(ChildNumber::Normal {
+      index: change.into(),
+    }),
+    wildcard: Wildcard::Unhardened,
+  });
+
+  let public_key = secret_key.to_public(secp)?;
+
+  let mut key_map = std::collections::HashMap::new();
+  key_map.insert(public_key.clone(), secret_key);
+
+  let desc = Descriptor::new_tr(public_key, None)?;
+
+  client.import_descriptors(ImportDescriptors {
+    descriptor: desc.to_string_with_secret(&key_map),
+    timestamp: Timestamp::Now,
+    active: Some(true),
+    range: None,
+    next_index: None,
+    internal: Some(change),
+    label: None,
+  })?;
+
+  Ok(())
+}
@@ -0,0 +1,380 @@
+use {
+  super::{
+    inscribe::Params,
+    transaction_builder::{Target, TransactionBuilder},
+    *,
+  },
+  crate::okx::{
+    datastore::brc20 as brc20_store,
+    protocol::brc20::{serialize_brc20_operation, Deploy, Mint, Operation, Transfer},
+  },
+  std::collections::HashMap,
+};
+
+#[derive(Debug, Parser)]
+pub(crate) enum Brc20 {
+  #[command(about = "Deploy a BRC20 ticker")]
+  Deploy(DeployTick),
+  #[command(about = "Mint a BRC20 ticker")]
+  Mint(MintTick),
+  #[command(about = "Inscribe a BRC20 transfer")]
+  InscribeTransfer(InscribeTransfer),
+  #[command(about = "Inscribe a BRC20 transfer and send it to a recipient")]
+  Send(Send),
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct DeployTick {
+  #[arg(long, help = "Deploy <TICK>.")]
+  tick: String,
+  #[arg(long, help = "Allow a maximum supply of <MAX>.")]
+  max: String,
+  #[arg(long, help = "Allow at most <LIM> per mint. [default: <MAX>]")]
+  lim: Option<String>,
+  #[arg(
+    long,
+    help = "Use <DEC> decimals. [default: the maximum decimal width of the active BRC20 rules]"
+  )]
+  dec: Option<String>,
+  #[arg(
+    long,
+    help = "Deploy a 5 byte <TICK> that only children of the deploy inscription can mint."
+  )]
+  self_mint: bool,
+  #[command(flatten)]
+  params: Params,
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct MintTick {
+  #[arg(long, help = "Mint <TICK>.")]
+  tick: String,
+  #[arg(long, help = "Mint <AMOUNT>.")]
+  amount: String,
+  #[command(flatten)]
+  params: Params,
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct InscribeTransfer {
+  #[arg(long, help = "Inscribe a transfer of <TICK>.")]
+  tick: String,
+  #[arg(long, help = "Inscribe a transfer of <AMOUNT>.")]
+  amount: String,
+  #[command(flatten)]
+  params: Params,
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct Send {
+  #[arg(long, help = "Send <TICK>.")]
+  tick: String,
+  #[arg(long, help = "Send <AMOUNT>.")]
+  amount: String,
+  #[arg(
+    long,
+    default_value = "3600",
+    help = "Give up waiting for a confirmation after <TIMEOUT> seconds."
+  )]
+  timeout: u64,
+  #[arg(
+    long,
+    default_value = "10000",
+    help = "Poll the index every <POLL_INTERVAL> milliseconds while waiting for a confirmation."
+  )]
+  poll_interval: u64,
+  #[arg(help = "Send the transfer to <RECIPIENT>.")]
+  recipient: Address<NetworkUnchecked>,
+  #[command(flatten)]
+  params: Params,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SendOutput {
+  pub transfer: inscribe::Output,
+  pub send: Txid,
+  pub receipt: TransferReceipt,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TransferReceipt {
+  pub inscription: InscriptionId,
+  pub old_satpoint: SatPoint,
+  pub new_satpoint: SatPoint,
+  pub from: String,
+  pub to: String,
+  pub tick: String,
+  pub amount: String,
+  pub msg: Option<String>,
+}
+
+impl Brc20 {
+  pub(crate) fn run(self, options: Options) -> SubcommandResult {
+    if !options.enable_index_brc20 {
+      bail!("wallet brc20 commands require --enable-index-brc20");
+    }
+
+    let (operation, params) = match self {
+      Self::Deploy(deploy) => (
+        Operation::Deploy(Deploy {
+          tick: deploy.tick,
+          max_supply: deploy.max,
+          mint_limit: deploy.lim,
+          decimals: deploy.dec,
+          self_mint: deploy.self_mint.then(|| "true".into()),
+        }),
+        deploy.params,
+      ),
+      Self::Mint(mint) => {
+        // a self mint tick is minted by a child of its deploy inscription, which the wallet can't
+        // inscribe.
+        if mint
+          .tick
+          .parse::<brc20_store::Tick>()
+          .map_or(false, |tick| tick.self_mint())
+        {
+          bail!(
+            "{} is a self mint ticker, only children of its deploy inscription can mint it",
+            mint.tick
+          );
+        }
+        (
+          Operation::Mint(Mint {
+            tick: mint.tick,
+            amount: mint.amount,
+          }),
+          mint.params,
+        )
+      }
+      Self::InscribeTransfer(transfer) => {
+        if transfer.params.destination.is_none() {
+          bail!("inscribe-transfer requires --destination, the address holding the balance");
+        }
+        (
+          Operation::InscribeTransfer(Transfer {
+            tick: transfer.tick,
+            amount: transfer.amount,
+          }),
+          transfer.params,
+        )
+      }
+      Self::Send(send) => return Ok(Box::new(send.run(&options)?)),
+    };
+
+    Ok(Box::new(inscribe(&options, &params, &operation)?))
+  }
+}
+
+/// Inscribes `operation` once a simulation of the commit and reveal transactions on top of the
+/// current index state shows that the operation succeeds.
+pub(crate) fn inscribe(
+  options: &Options,
+  params: &Params,
+  operation: &Operation,
+) -> Result<inscribe::Output> {
+  let inscription = Inscription {
+    content_type: Some("text/plain;charset=utf-8".as_bytes().to_vec()),
+    body: Some(serialize_brc20_operation(operation).into_bytes()),
+    ..Default::default()
+  };
+
+  params.inscribe(options, inscription, |index, commit, reveal| {
+    validate(index, &[commit.clone(), reveal.clone()]).map(|_| ())
+  })
+}
+
+/// Simulates `transactions` in the next block and returns the receipt of the first BRC20
+/// operation of the last transaction, failing when that operation would fail.
+pub(crate) fn validate(
+  index: &Index,
+  transactions: &[Transaction],
+) -> Result<brc20_store::Receipt> {
+  let simulation = index.simulate(transactions, HashMap::new())?;
+
+  let receipt = simulation
+    .transactions
+    .into_iter()
+    .last()
+    .and_then(|transaction| transaction.brc20_receipts.into_iter().next())
+    .ok_or_else(|| {
+      anyhow!(
+        "inscription would not be indexed as a BRC20 operation at height {}",
+        simulation.height
+      )
+    })?;
+
+  if let Err(err) = &receipt.result {
+    bail!("BRC20 operation would fail: {err}");
+  }
+
+  Ok(receipt)
+}
+
+/// Updates the index every `interval` until `poll` returns a value or `deadline` passes.
+fn wait_for<T>(
+  index: &Index,
+  deadline: Instant,
+  interval: Duration,
+  what: &str,
+  mut poll: impl FnMut(&Index) -> Result<Option<T>>,
+) -> Result<T> {
+  loop {
+    index.update()?;
+
+    if let Some(value) = poll(index)? {
+      return Ok(value);
+    }
+
+    if Instant::now() >= deadline {
+      bail!("timed out waiting for {what}");
+    }
+
+    thread::sleep(interval);
+  }
+}
+
+/// Returns the receipt of `inscription_id` among the indexed BRC20 receipts of `txid`, or
+/// `None` while the transaction is unconfirmed.
+fn indexed_receipt(
+  index: &Index,
+  txid: Txid,
+  inscription_id: InscriptionId,
+) -> Result<Option<brc20_store::Receipt>> {
+  Ok(
+    index
+      .brc20_get_txs_events(&vec![txid])?
+      .into_iter()
+      .flat_map(|(_, receipts)| receipts)
+      .find(|receipt| receipt.inscription_id == inscription_id),
+  )
+}
+
+impl Send {
+  /// Inscribes the transfer to the address holding the balance, waits until the transfer is
+  /// indexed as transferable and then sends exactly the inscribed sat to the recipient. A
+  /// transfer inscription spent as fee is returned to its sender, so the send transaction is
+  /// built with the inscription as its first sat and simulated before it is signed.
+  fn run(self, options: &Options) -> Result<SendOutput> {
+    let chain = options.chain();
+
+    let Some(owner) = self.params.destination.clone() else {
+      bail!("send requires --destination, the address holding the balance");
+    };
+    let owner = owner.require_network(chain.network())?;
+
+    let recipient = self.recipient.require_network(chain.network())?;
+
+    if self.params.dry_run {
+      bail!("send does not support --dry-run");
+    }
+
+    let transfer = inscribe(
+      options,
+      &self.params,
+      &Operation::InscribeTransfer(Transfer {
+        tick: self.tick,
+        amount: self.amount,
+      }),
+    )?;
+
+    let index = Index::open(options)?;
+    let deadline = Instant::now() + Duration::from_secs(self.timeout);
+    let interval = Duration::from_millis(self.poll_interval);
+
+    wait_for(
+      &index,
+      deadline,
+      interval,
+      "the transfer inscription",
+      |index| {
+        let Some(receipt) = indexed_receipt(index, transfer.reveal, transfer.inscription)? else {
+          return Ok(None);
+        };
+
+        if let Err(err) = receipt.result {
+          bail!(
+            "transfer inscription {} is invalid: {err}",
+            transfer.inscription
+          );
+        }
+
+        index
+          .brc20_get_transferable_by_id(&owner, &transfer.inscription)?
+          .ok_or_else(|| {
+            anyhow!(
+              "transfer inscription {} is not transferable by {owner}",
+              transfer.inscription
+            )
+          })
+          .map(Some)
+      },
+    )?;
+
+    let satpoint = index
+      .get_inscription_satpoint_by_id(transfer.inscription)?
+      .ok_or_else(|| anyhow!("transfer inscription {} not found", transfer.inscription))?;
+
+    let client = options.bitcoin_rpc_client_for_wallet_command(false)?;
+
+    // blocks may have been mined while the wallet was loaded, so the index must catch up with the
+    // outputs the wallet lists.
+    index.update()?;
+
+    let utxos = index.get_unspent_outputs()?;
+
+    let inscriptions = index.get_inscriptions(utxos.clone())?;
+
+    let change = [
+      get_change_address(&client, chain)?,
+      get_change_address(&client, chain)?,
+    ];
+
+    let unsigned_tx = TransactionBuilder::new(
+      satpoint,
+      inscriptions,
+      utxos,
+      recipient,
+      change,
+      self.params.fee_rate,
+      Target::Postage,
+    )
+    .build_transaction()?;
+
+    validate(&index, &[unsigned_tx.clone()])?;
+
+    let signed_tx = client
+      .sign_raw_transaction_with_wallet(&unsigned_tx, None, None)?
+      .hex;
+
+    let send = client.send_raw_transaction(&signed_tx)?;
+
+    let receipt = wait_for(
+      &index,
+      deadline,
+      interval,
+      "the send transaction",
+      |index| indexed_receipt(index, send, transfer.inscription),
+    )?;
+
+    let event = match receipt.result {
+      Ok(brc20_store::Event::Transfer(event)) => event,
+      Ok(event) => bail!("send transaction {send} produced an unexpected event: {event:?}"),
+      Err(err) => bail!("send transaction {send} failed: {err}"),
+    };
+
+    Ok(SendOutput {
+      transfer,
+      send,
+      receipt: TransferReceipt {
+        inscription: receipt.inscription_id,
+        old_satpoint: receipt.old_satpoint,
+        new_satpoint: receipt.new_satpoint,
+        from: receipt.from.to_string(),
+        to: receipt.to.to_string(),
+        tick: event.tick.to_string(),
+        amount: event.amount.to_string(),
+        msg: event.msg,
+      },
+    })
+  }
+}
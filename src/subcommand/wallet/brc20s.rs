@@ -0,0 +1,326 @@
+use {
+  super::{inscribe::Params, *},
+  crate::{
+    index::simulate,
+    okx::{
+      datastore::{
+        balance::{
+          convert_amount_with_decimal, convert_amount_without_decimal,
+          convert_pledged_tick_with_decimal, convert_pledged_tick_without_decimal, get_stake_dec,
+          get_user_common_balance,
+        },
+        brc20 as brc20_store,
+        brc20s::{
+          self as brc20s_store, DataStoreReadOnly, Pid, PoolInfo, StakeInfo, TickId, UserInfo,
+        },
+        ScriptKey, StateRWriter,
+      },
+      protocol::brc20s::{
+        serialize_brc20s_operation, stakeable_balance, Mint, Num, Operation, Stake, UnStake,
+      },
+      reward,
+    },
+  },
+  std::collections::{BTreeSet, HashMap},
+};
+
+#[derive(Debug, Parser)]
+pub(crate) enum Brc20s {
+  #[command(about = "Deposit into a BRC20S pool")]
+  Deposit(PoolAmount),
+  #[command(about = "Withdraw a deposit from a BRC20S pool")]
+  Withdraw(PoolAmount),
+  #[command(about = "Mint the reward of a BRC20S pool")]
+  Mint(PoolAmount),
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct PoolAmount {
+  #[arg(long, help = "Use the pool with id <PID>.")]
+  pid: String,
+  #[arg(long, help = "Deposit, withdraw or mint <AMOUNT>.")]
+  amount: String,
+  #[command(flatten)]
+  params: Params,
+}
+
+impl Brc20s {
+  pub(crate) fn run(self, options: Options) -> SubcommandResult {
+    if !options.enable_index_brc20s {
+      bail!("wallet brc20s commands require --enable-index-brc20s");
+    }
+
+    let args = self.args();
+
+    let Some(owner) = args.params.destination.clone() else {
+      bail!("wallet brc20s commands require --destination, the address holding the deposit");
+    };
+    let owner = owner.require_network(options.chain().network())?;
+
+    let pid = Pid::from_str(&args.pid)?;
+
+    let (operation, satpoint) = {
+      let index = Index::open(&options)?;
+      index.update()?;
+
+      let height = index.block_count()?;
+      let script_key = ScriptKey::from_address(owner.clone());
+
+      let (_, operation) = simulate::run_with(&index, &[], HashMap::new(), |state, _| {
+        self.check(state, &pid, &script_key, height)
+      })?;
+
+      (
+        operation,
+        funding_satpoint(&options, &index, args.params.satpoint, &owner)?,
+      )
+    };
+
+    let params = Params {
+      satpoint: Some(satpoint),
+      ..args.params.clone()
+    };
+
+    Ok(Box::new(inscribe(&options, &params, &operation)?))
+  }
+
+  fn args(&self) -> &PoolAmount {
+    match self {
+      Self::Deposit(args) | Self::Withdraw(args) | Self::Mint(args) => args,
+    }
+  }
+
+  /// Checks the operation against the state the next block starts from, by the rules the
+  /// executor applies to it, and returns the operation.
+  fn check<S: StateRWriter>(
+    &self,
+    state: &S,
+    pid: &Pid,
+    script_key: &ScriptKey,
+    height: u64,
+  ) -> Result<Operation> {
+    let args = self.args();
+
+    let pool = brc20s_store::store(state)
+      .get_pid_to_poolinfo(pid)
+      .map_err(|e| anyhow!("failed to get pool info from state! error: {e}"))?
+      .ok_or_else(|| anyhow!("pool {} does not exist", pid.as_str()))?;
+
+    let user_info = brc20s_store::store(state)
+      .get_pid_to_use_info(script_key, pid)
+      .map_err(|e| anyhow!("failed to get user info from state! error: {e}"))?
+      .unwrap_or_else(|| UserInfo::default(pid));
+
+    match self {
+      Self::Deposit(_) => {
+        let amount = stake_amount(state, &pool, &args.amount)?;
+
+        let stake_balance = get_user_common_balance(
+          script_key,
+          &pool.stake,
+          &brc20s_store::store(state),
+          &brc20_store::store(state),
+        );
+
+        let stake_info = brc20s_store::store(state)
+          .get_user_stakeinfo(script_key, &pool.stake)
+          .map_err(|e| anyhow!("failed to get stake info from state! error: {e}"))?
+          .unwrap_or_else(|| StakeInfo::new(vec![], &pool.stake, 0, 0));
+
+        let stakeable = stakeable_balance(
+          &pool,
+          &stake_balance,
+          &stake_info,
+          &Num::from(user_info.staked),
+        )?;
+
+        if stakeable.lt(&amount) {
+          bail!(
+            "insufficient balance to deposit {} into pool {}, {} can be deposited",
+            args.amount,
+            pid.as_str(),
+            stake_display(state, &pool, &stakeable)?,
+          );
+        }
+
+        Ok(Operation::Stake(Stake {
+          pool_id: pid.as_str().to_string(),
+          amount: args.amount.clone(),
+        }))
+      }
+      Self::Withdraw(_) => {
+        let amount = stake_amount(state, &pool, &args.amount)?;
+
+        let staked = Num::from(user_info.staked);
+
+        if staked.lt(&amount) {
+          bail!(
+            "insufficient deposit to withdraw {} from pool {}, {} is deposited",
+            args.amount,
+            pid.as_str(),
+            stake_display(state, &pool, &staked)?,
+          );
+        }
+
+        Ok(Operation::UnStake(UnStake::new(pid.as_str(), &args.amount)))
+      }
+      Self::Mint(_) => {
+        let tick_id = TickId::from_str(pid.as_str().split('#').next().unwrap_or(""))?;
+
+        let tick_info = brc20s_store::store(state)
+          .get_tick_info(&tick_id)
+          .map_err(|e| anyhow!("failed to get tick info from state! error: {e}"))?
+          .ok_or_else(|| anyhow!("tick {} does not exist", tick_id.hex()))?;
+
+        let amount = convert_amount_with_decimal::<brc20s_store::Store<S::Tables>>(
+          &args.amount,
+          tick_info.decimal,
+        )
+        .map_err(|e| anyhow!("invalid amount {}: {e}", args.amount))?;
+
+        // the reward settled by earlier operations, plus the reward accrued since, if any.
+        let mut reward = Num::from(user_info.pending_reward);
+        if user_info.staked > 0 {
+          let dec = get_stake_dec(
+            &pool.stake,
+            &brc20s_store::store(state),
+            &brc20_store::store(state),
+          );
+          reward = reward.checked_add(&Num::from(reward::query_reward(
+            user_info, pool, height, dec,
+          )?))?;
+        }
+
+        if reward.lt(&amount) {
+          bail!(
+            "insufficient reward to mint {} from pool {}, {} can be minted",
+            args.amount,
+            pid.as_str(),
+            convert_amount_without_decimal::<brc20s_store::Store<S::Tables>>(
+              reward.checked_to_u128()?,
+              tick_info.decimal,
+            )
+            .map_err(|e| anyhow!("invalid reward {reward}: {e}"))?,
+          );
+        }
+
+        Ok(Operation::Mint(Mint {
+          tick: tick_info.name.as_str().to_string(),
+          pool_id: pid.as_str().to_string(),
+          amount: args.amount.clone(),
+        }))
+      }
+    }
+  }
+}
+
+fn stake_amount(state: &impl StateRWriter, pool: &PoolInfo, amount: &str) -> Result<Num> {
+  convert_pledged_tick_with_decimal(
+    &pool.stake,
+    amount,
+    &brc20s_store::store(state),
+    &brc20_store::store(state),
+  )
+  .map_err(|e| anyhow!("invalid amount {amount}: {e}"))
+}
+
+fn stake_display(state: &impl StateRWriter, pool: &PoolInfo, amount: &Num) -> Result<String> {
+  Ok(
+    convert_pledged_tick_without_decimal(
+      &pool.stake,
+      amount.checked_to_u128()?,
+      &brc20s_store::store(state),
+      &brc20_store::store(state),
+    )
+    .map_err(|e| anyhow!("invalid amount {amount}: {e}"))?
+    .to_string(),
+  )
+}
+
+/// Returns the satpoint the commit transaction spends first. A deposit, withdrawal or mint only
+/// succeeds when the commit input holding the inscribed sat belongs to the address receiving the
+/// reveal output, so that satpoint must be on an output of `owner`.
+fn funding_satpoint(
+  options: &Options,
+  index: &Index,
+  satpoint: Option<SatPoint>,
+  owner: &Address,
+) -> Result<SatPoint> {
+  let script_pubkey = owner.script_pubkey();
+
+  let owned = options
+    .bitcoin_rpc_client_for_wallet_command(false)?
+    .list_unspent(None, None, None, None, None)?
+    .into_iter()
+    .filter(|utxo| utxo.script_pub_key == script_pubkey)
+    .map(|utxo| (OutPoint::new(utxo.txid, utxo.vout), utxo.amount))
+    .collect::<BTreeMap<OutPoint, Amount>>();
+
+  if let Some(satpoint) = satpoint {
+    if !owned.contains_key(&satpoint.outpoint) {
+      bail!("satpoint {satpoint} is not on an output of {owner}");
+    }
+    return Ok(satpoint);
+  }
+
+  let inscribed = index
+    .get_inscriptions(owned.clone())?
+    .into_keys()
+    .map(|satpoint| satpoint.outpoint)
+    .collect::<BTreeSet<OutPoint>>();
+
+  owned
+    .into_iter()
+    .filter(|(outpoint, _)| !inscribed.contains(outpoint))
+    .max_by_key(|(_, amount)| *amount)
+    .map(|(outpoint, _)| SatPoint {
+      outpoint,
+      offset: 0,
+    })
+    .ok_or_else(|| anyhow!("{owner} has no cardinal outputs to fund the commit transaction"))
+}
+
+/// Inscribes `operation` once a simulation of the commit and reveal transactions on top of the
+/// current index state shows that the operation succeeds.
+pub(crate) fn inscribe(
+  options: &Options,
+  params: &Params,
+  operation: &Operation,
+) -> Result<inscribe::Output> {
+  let inscription = Inscription {
+    content_type: Some("text/plain;charset=utf-8".as_bytes().to_vec()),
+    body: Some(serialize_brc20s_operation(operation).into_bytes()),
+    ..Default::default()
+  };
+
+  params.inscribe(options, inscription, |index, commit, reveal| {
+    validate(index, &[commit.clone(), reveal.clone()]).map(|_| ())
+  })
+}
+
+/// Simulates `transactions` in the next block and returns the receipt of the first BRC20S
+/// operation of the last transaction, failing when that operation would fail.
+pub(crate) fn validate(
+  index: &Index,
+  transactions: &[Transaction],
+) -> Result<brc20s_store::Receipt> {
+  let simulation = index.simulate(transactions, HashMap::new())?;
+
+  let receipt = simulation
+    .transactions
+    .into_iter()
+    .last()
+    .and_then(|transaction| transaction.brc20s_receipts.into_iter().next())
+    .ok_or_else(|| {
+      anyhow!(
+        "inscription would not be indexed as a BRC20S operation at height {}",
+        simulation.height
+      )
+    })?;
+
+  if let Err(err) = &receipt.result {
+    bail!("BRC20S operation would fail: {err}");
+  }
+
+  Ok(receipt)
+}
@@ -1,4 +1,21 @@
-use super::*;
+use {
+  super::*,
+  crate::subcommand::wallet::transaction_builder::{Target, TransactionBuilder},
+  bitcoin::{
+    blockdata::{locktime::absolute::LockTime, opcodes, script},
+    key::{TapTweak, TweakedKeyPair, TweakedPublicKey, UntweakedKeyPair},
+    policy::MAX_STANDARD_TX_WEIGHT,
+    secp256k1::{self, rand, Secp256k1, XOnlyPublicKey},
+    sighash::{Prevouts, SighashCache, TapSighashType},
+    taproot::{ControlBlock, LeafVersion, Signature, TapLeafHash, TaprootBuilder},
+    PrivateKey, Witness,
+  },
+  bitcoincore_rpc::{
+    bitcoincore_rpc_json::{ImportDescriptors, Timestamp},
+    json::AddressType,
+  },
+  std::collections::BTreeSet,
+};
 
 #[derive(Serialize, Deserialize)]
 pub struct Output {
@@ -8,3 +25,493 @@ pub struct Output {
   pub reveal: Txid,
   pub total_fees: u64,
 }
+
+/// The arguments shared by the commands that create an inscription.
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct Params {
+  #[arg(long, help = "Inscribe <SATPOINT>.")]
+  pub(crate) satpoint: Option<SatPoint>,
+  #[arg(long, help = "Use fee rate of <FEE_RATE> sats/vB.")]
+  pub(crate) fee_rate: FeeRate,
+  #[arg(
+    long,
+    help = "Use <COMMIT_FEE_RATE> sats/vbyte for commit transaction.\nDefaults to <FEE_RATE> if unset."
+  )]
+  pub(crate) commit_fee_rate: Option<FeeRate>,
+  #[arg(long, help = "Send inscription to <DESTINATION>.")]
+  pub(crate) destination: Option<Address<NetworkUnchecked>>,
+  #[arg(long, help = "Don't sign or broadcast transactions.")]
+  pub(crate) dry_run: bool,
+  #[arg(long, help = "Do not back up recovery key.")]
+  pub(crate) no_backup: bool,
+  #[arg(
+    long,
+    help = "Do not check that transactions are equal to or below the MAX_STANDARD_TX_WEIGHT of 400,000 weight units. Transactions over this limit are currently nonstandard and will not be relayed by bitcoind in its default configuration. Do not use this flag unless you understand the implications."
+  )]
+  pub(crate) no_limit: bool,
+  #[arg(
+    long,
+    help = "Include <POSTAGE> sats in the inscription output. [default: 10000]"
+  )]
+  pub(crate) postage: Option<u64>,
+}
+
+#[derive(Debug, Parser)]
+pub(crate) struct Inscribe {
+  #[arg(help = "Inscribe sat with contents of <FILE>.")]
+  pub(crate) file: PathBuf,
+  #[command(flatten)]
+  pub(crate) params: Params,
+}
+
+impl Inscribe {
+  pub(crate) fn run(self, options: Options) -> SubcommandResult {
+    let inscription = Inscription::from_file(options.chain(), &self.file, None)?;
+
+    Ok(Box::new(self.params.inscribe(
+      &options,
+      inscription,
+      |_, _, _| Ok(()),
+    )?))
+  }
+}
+
+impl Params {
+  /// Creates the commit and reveal transactions of `inscription` and broadcasts them, unless
+  /// this is a dry run. `validate` is called with the updated index, the unsigned commit and
+  /// the signed reveal before the wallet signs or broadcasts anything.
+  pub(crate) fn inscribe(
+    &self,
+    options: &Options,
+    inscription: Inscription,
+    validate: impl FnOnce(&Index, &Transaction, &Transaction) -> Result,
+  ) -> Result<Output> {
+    let client = options.bitcoin_rpc_client_for_wallet_command(false)?;
+
+    let index = Index::open(options)?;
+    index.update()?;
+
+    let chain = options.chain();
+
+    let mut utxos = index.get_unspent_outputs()?;
+
+    let inscriptions = index.get_inscriptions(utxos.clone())?;
+
+    let destination = match self.destination.clone() {
+      Some(destination) => destination.require_network(chain.network())?,
+      None => client
+        .get_new_address(None, Some(AddressType::Bech32m))?
+        .require_network(chain.network())?,
+    };
+
+    let change = [
+      get_change_address(&client, chain)?,
+      get_change_address(&client, chain)?,
+    ];
+
+    let (unsigned_commit_tx, reveal_tx, recovery_key_pair) = Self::create_inscription_transactions(
+      self.satpoint,
+      &inscription,
+      inscriptions,
+      chain.network(),
+      utxos.clone(),
+      change,
+      destination,
+      self.commit_fee_rate.unwrap_or(self.fee_rate),
+      self.fee_rate,
+      self.no_limit,
+      self
+        .postage
+        .map(Amount::from_sat)
+        .unwrap_or(TransactionBuilder::TARGET_POSTAGE),
+    )?;
+
+    validate(&index, &unsigned_commit_tx, &reveal_tx)?;
+
+    utxos.insert(
+      reveal_tx.input[0].previous_output,
+      Amount::from_sat(
+        unsigned_commit_tx.output
+          [usize::try_from(reveal_tx.input[0].previous_output.vout).unwrap()]
+        .value,
+      ),
+    );
+
+    let total_fees =
+      Self::calculate_fee(&unsigned_commit_tx, &utxos) + Self::calculate_fee(&reveal_tx, &utxos);
+
+    if self.dry_run {
+      return Ok(Output {
+        commit: unsigned_commit_tx.txid(),
+        reveal: reveal_tx.txid(),
+        inscription: InscriptionId {
+          txid: reveal_tx.txid(),
+          index: 0,
+        },
+        parent: None,
+        total_fees,
+      });
+    }
+
+    if !self.no_backup {
+      Self::backup_recovery_key(&client, recovery_key_pair, chain.network())?;
+    }
+
+    let signed_commit_tx = client
+      .sign_raw_transaction_with_wallet(&unsigned_commit_tx, None, None)?
+      .hex;
+
+    let commit = client.send_raw_transaction(&signed_commit_tx)?;
+
+    let reveal = client
+      .send_raw_transaction(&reveal_tx)
+      .context("failed to send reveal transaction")?;
+
+    Ok(Output {
+      commit,
+      reveal,
+      inscription: InscriptionId {
+        txid: reveal,
+        index: 0,
+      },
+      parent: None,
+      total_fees,
+    })
+  }
+
+  fn calculate_fee(tx: &Transaction, utxos: &BTreeMap<OutPoint, Amount>) -> u64 {
+    tx.input
+      .iter()
+      .map(|txin| utxos.get(&txin.previous_output).unwrap().to_sat())
+      .sum::<u64>()
+      .checked_sub(tx.output.iter().map(|txout| txout.value).sum::<u64>())
+      .unwrap()
+  }
+
+  pub(crate) fn create_inscription_transactions(
+    satpoint: Option<SatPoint>,
+    inscription: &Inscription,
+    inscriptions: BTreeMap<SatPoint, InscriptionId>,
+    network: Network,
+    utxos: BTreeMap<OutPoint, Amount>,
+    change: [Address; 2],
+    destination: Address,
+    commit_fee_rate: FeeRate,
+    reveal_fee_rate: FeeRate,
+    no_limit: bool,
+    postage: Amount,
+  ) -> Result<(Transaction, Transaction, TweakedKeyPair)> {
+    let satpoint = if let Some(satpoint) = satpoint {
+      satpoint
+    } else {
+      let inscribed_utxos = inscriptions
+        .keys()
+        .map(|satpoint| satpoint.outpoint)
+        .collect::<BTreeSet<OutPoint>>();
+
+      utxos
+        .keys()
+        .find(|outpoint| !inscribed_utxos.contains(outpoint))
+        .map(|outpoint| SatPoint {
+          outpoint: *outpoint,
+          offset: 0,
+        })
+        .ok_or_else(|| anyhow!("wallet contains no cardinal utxos"))?
+    };
+
+    for (inscribed_satpoint, inscription_id) in &inscriptions {
+      if inscribed_satpoint == &satpoint {
+        bail!("sat at {} already inscribed", satpoint);
+      }
+
+      if inscribed_satpoint.outpoint == satpoint.outpoint {
+        bail!(
+          "utxo {} already inscribed with inscription {inscription_id} on sat {inscribed_satpoint}",
+          satpoint.outpoint,
+        );
+      }
+    }
+
+    let secp256k1 = Secp256k1::new();
+    let key_pair = UntweakedKeyPair::new(&secp256k1, &mut rand::thread_rng());
+    let (public_key, _parity) = XOnlyPublicKey::from_keypair(&key_pair);
+
+    let reveal_script = inscription.append_reveal_script(
+      script::Builder::new()
+        .push_slice(public_key.serialize())
+        .push_opcode(opcodes::all::OP_CHECKSIG),
+    );
+
+    let taproot_spend_info = TaprootBuilder::new()
+      .add_leaf(0, reveal_script.clone())
+      .expect("adding leaf should work")
+      .finalize(&secp256k1, public_key)
+      .expect("finalizing taproot builder should work");
+
+    let control_block = taproot_spend_info
+      .control_block(&(reveal_script.clone(), LeafVersion::TapScript))
+      .expect("should compute control block");
+
+    let commit_tx_address = Address::p2tr_tweaked(taproot_spend_info.output_key(), network);
+
+    let (_, reveal_fee) = Self::build_reveal_transaction(
+      &control_block,
+      reveal_fee_rate,
+      OutPoint::null(),
+      TxOut {
+        script_pubkey: destination.script_pubkey(),
+        value: 0,
+      },
+      &reveal_script,
+    );
+
+    let unsigned_commit_tx = TransactionBuilder::new(
+      satpoint,
+      inscriptions,
+      utxos,
+      commit_tx_address.clone(),
+      change,
+      commit_fee_rate,
+      Target::Value(reveal_fee + postage),
+    )
+    .build_transaction()?;
+
+    let (vout, output) = unsigned_commit_tx
+      .output
+      .iter()
+      .enumerate()
+      .find(|(_vout, output)| output.script_pubkey == commit_tx_address.script_pubkey())
+      .expect("should find sat commit/inscription output");
+
+    let (mut reveal_tx, fee) = Self::build_reveal_transaction(
+      &control_block,
+      reveal_fee_rate,
+      OutPoint {
+        txid: unsigned_commit_tx.txid(),
+        vout: vout.try_into().unwrap(),
+      },
+      TxOut {
+        script_pubkey: destination.script_pubkey(),
+        value: output.value,
+      },
+      &reveal_script,
+    );
+
+    reveal_tx.output[0].value = reveal_tx.output[0]
+      .value
+      .checked_sub(fee.to_sat())
+      .context("commit transaction output value insufficient to pay transaction fee")?;
+
+    if reveal_tx.output[0].value < reveal_tx.output[0].script_pubkey.dust_value().to_sat() {
+      bail!("commit transaction output would be dust");
+    }
+
+    let mut sighash_cache = SighashCache::new(&mut reveal_tx);
+
+    let sighash = sighash_cache
+      .taproot_script_spend_signature_hash(
+        0,
+        &Prevouts::All(&[output]),
+        TapLeafHash::from_script(&reveal_script, LeafVersion::TapScript),
+        TapSighashType::Default,
+      )
+      .expect("signature hash should compute");
+
+    let signature = secp256k1.sign_schnorr(
+      &secp256k1::Message::from_slice(sighash.as_ref())
+        .expect("should be cryptographically secure hash"),
+      &key_pair,
+    );
+
+    let witness = sighash_cache
+      .witness_mut(0)
+      .expect("getting mutable witness reference should work");
+
+    witness.push(
+      Signature {
+        sig: signature,
+        hash_ty: TapSighashType::Default,
+      }
+      .to_vec(),
+    );
+
+    witness.push(reveal_script);
+    witness.push(&control_block.serialize());
+
+    let recovery_key_pair = key_pair.tap_tweak(&secp256k1, taproot_spend_info.merkle_root());
+
+    let (x_only_pub_key, _parity) = recovery_key_pair.to_inner().x_only_public_key();
+    assert_eq!(
+      Address::p2tr_tweaked(
+        TweakedPublicKey::dangerous_assume_tweaked(x_only_pub_key),
+        network,
+      ),
+      commit_tx_address
+    );
+
+    let reveal_weight = reveal_tx.weight();
+
+    if !no_limit && reveal_weight > bitcoin::Weight::from_wu(MAX_STANDARD_TX_WEIGHT.into()) {
+      bail!(
+        "reveal transaction weight greater than {MAX_STANDARD_TX_WEIGHT} (MAX_STANDARD_TX_WEIGHT): {reveal_weight}"
+      );
+    }
+
+    Ok((unsigned_commit_tx, reveal_tx, recovery_key_pair))
+  }
+
+  fn backup_recovery_key(
+    client: &Client,
+    recovery_key_pair: TweakedKeyPair,
+    network: Network,
+  ) -> Result {
+    let recovery_private_key = PrivateKey::new(recovery_key_pair.to_inner().secret_key(), network);
+
+    let info = client.get_descriptor_info(&format!("rawtr({})", recovery_private_key.to_wif()))?;
+
+    let response = client.import_descriptors(ImportDescriptors {
+      descriptor: format!("rawtr({})#{}", recovery_private_key.to_wif(), info.checksum),
+      timestamp: Timestamp::Now,
+      active: Some(false),
+      range: None,
+      next_index: None,
+      internal: Some(false),
+      label: Some("commit tx recovery key".to_string()),
+    })?;
+
+    for result in response {
+      if !result.success {
+        return Err(anyhow!("commit tx recovery key import failed"));
+      }
+    }
+
+    Ok(())
+  }
+
+  fn build_reveal_transaction(
+    control_block: &ControlBlock,
+    fee_rate: FeeRate,
+    input: OutPoint,
+    output: TxOut,
+    script: &Script,
+  ) -> (Transaction, Amount) {
+    let reveal_tx = Transaction {
+      input: vec![TxIn {
+        previous_output: input,
+        script_sig: script::Builder::new().into_script(),
+        witness: Witness::new(),
+        sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+      }],
+      output: vec![output],
+      lock_time: LockTime::ZERO,
+      version: 1,
+    };
+
+    let fee = {
+      let mut reveal_tx = reveal_tx.clone();
+
+      reveal_tx.input[0].witness.push(
+        secp256k1::schnorr::Signature::from_slice(
+          &[0; secp256k1::constants::SCHNORR_SIGNATURE_SIZE],
+        )
+        .unwrap()
+        .as_ref(),
+      );
+      reveal_tx.input[0].witness.push(script);
+      reveal_tx.input[0].witness.push(control_block.serialize());
+
+      fee_rate.fee(reveal_tx.vsize())
+    };
+
+    (reveal_tx, fee)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reveal_transaction_pays_fee() {
+    let utxos = BTreeMap::from([(outpoint(1), Amount::from_sat(20_000))]);
+    let inscription = inscription("text/plain", "ord");
+    let commit_address = change(0);
+    let reveal_address = recipient();
+
+    let (commit_tx, reveal_tx, _private_key) = Params::create_inscription_transactions(
+      Some(satpoint(1, 0)),
+      &inscription,
+      BTreeMap::new(),
+      Network::Bitcoin,
+      utxos,
+      [commit_address, change(1)],
+      reveal_address,
+      FeeRate::try_from(1.0).unwrap(),
+      FeeRate::try_from(1.0).unwrap(),
+      false,
+      TransactionBuilder::TARGET_POSTAGE,
+    )
+    .unwrap();
+
+    assert_eq!(
+      reveal_tx.input[0].previous_output,
+      OutPoint {
+        txid: commit_tx.txid(),
+        vout: 0,
+      }
+    );
+    assert_eq!(reveal_tx.output[0].value, 10_000);
+    assert_eq!(
+      Inscription::from_transaction(&reveal_tx)[0].inscription,
+      inscription
+    );
+  }
+
+  #[test]
+  fn inscribed_satpoint_is_rejected() {
+    let utxos = BTreeMap::from([(outpoint(1), Amount::from_sat(20_000))]);
+
+    assert_eq!(
+      Params::create_inscription_transactions(
+        Some(satpoint(1, 0)),
+        &inscription("text/plain", "ord"),
+        BTreeMap::from([(satpoint(1, 0), inscription_id(1))]),
+        Network::Bitcoin,
+        utxos,
+        [change(0), change(1)],
+        recipient(),
+        FeeRate::try_from(1.0).unwrap(),
+        FeeRate::try_from(1.0).unwrap(),
+        false,
+        TransactionBuilder::TARGET_POSTAGE,
+      )
+      .unwrap_err()
+      .to_string(),
+      format!("sat at {} already inscribed", satpoint(1, 0))
+    );
+  }
+
+  #[test]
+  fn wallet_without_cardinal_utxos_is_an_error() {
+    let utxos = BTreeMap::from([(outpoint(1), Amount::from_sat(20_000))]);
+
+    assert_eq!(
+      Params::create_inscription_transactions(
+        None,
+        &inscription("text/plain", "ord"),
+        BTreeMap::from([(satpoint(1, 0), inscription_id(1))]),
+        Network::Bitcoin,
+        utxos,
+        [change(0), change(1)],
+        recipient(),
+        FeeRate::try_from(1.0).unwrap(),
+        FeeRate::try_from(1.0).unwrap(),
+        false,
+        TransactionBuilder::TARGET_POSTAGE,
+      )
+      .unwrap_err()
+      .to_string(),
+      "wallet contains no cardinal utxos"
+    );
+  }
+}
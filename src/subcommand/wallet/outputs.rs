@@ -5,3 +5,20 @@ pub struct Output {
   pub output: OutPoint,
   pub amount: u64,
 }
+
+pub(crate) fn run(options: Options) -> SubcommandResult {
+  options.bitcoin_rpc_client_for_wallet_command(false)?;
+
+  let index = Index::open(&options)?;
+  index.update()?;
+
+  let mut outputs = Vec::new();
+  for (output, amount) in index.get_unspent_outputs()? {
+    outputs.push(Output {
+      output,
+      amount: amount.to_sat(),
+    });
+  }
+
+  Ok(Box::new(outputs))
+}
@@ -0,0 +1,14 @@
+use {super::*, bitcoincore_rpc::json::AddressType};
+
+#[derive(Deserialize, Serialize)]
+pub struct Output {
+  pub address: Address<NetworkUnchecked>,
+}
+
+pub(crate) fn run(options: Options) -> SubcommandResult {
+  let address = options
+    .bitcoin_rpc_client_for_wallet_command(false)?
+    .get_new_address(None, Some(AddressType::Bech32m))?;
+
+  Ok(Box::new(Output { address }))
+}
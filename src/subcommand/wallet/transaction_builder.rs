@@ -0,0 +1,515 @@
+//! Builds transactions that move an outgoing sat to a recipient without losing any inscriptions.
+//!
+//! The outgoing sat always becomes the first sat of the recipient output. Sats in front of it are
+//! padded into a change output, cardinal UTXOs are added when the inputs don't cover the outputs
+//! and the fee, and whatever is left over goes to a second change output when it is above the dust
+//! value.
+
+use {
+  super::*,
+  bitcoin::{blockdata::locktime::absolute::LockTime, Witness},
+};
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum Error {
+  #[error("duplicate address: {0}")]
+  DuplicateAddress(Address),
+  #[error("output value is below dust value: {output_value} < {dust_value}")]
+  Dust {
+    output_value: Amount,
+    dust_value: Amount,
+  },
+  #[error("wallet does not contain enough cardinal UTXOs, please add additional funds to wallet.")]
+  NotEnoughCardinalUtxos,
+  #[error("outgoing satpoint {0} not in wallet")]
+  NotInWallet(SatPoint),
+  #[error("outgoing satpoint {0} is not in the range of its output")]
+  OutOfRange(SatPoint),
+  #[error("transaction would lose inscription {0}")]
+  LostInscription(InscriptionId),
+  #[error(
+    "cannot send {outgoing_satpoint} without also sending inscription {inscription_id} at {inscribed_satpoint}"
+  )]
+  UtxoContainsAdditionalInscription {
+    outgoing_satpoint: SatPoint,
+    inscribed_satpoint: SatPoint,
+    inscription_id: InscriptionId,
+  },
+}
+
+/// The value of the recipient output.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum Target {
+  /// Exactly this value.
+  Value(Amount),
+  /// The value of the outgoing output after the outgoing sat, capped at `MAX_POSTAGE`. The fee
+  /// is taken out of it when there is no change to pay it.
+  Postage,
+}
+
+pub(crate) struct TransactionBuilder {
+  amounts: BTreeMap<OutPoint, Amount>,
+  change_addresses: [Address; 2],
+  fee_rate: FeeRate,
+  inscriptions: BTreeMap<SatPoint, InscriptionId>,
+  outgoing: SatPoint,
+  recipient: Address,
+  target: Target,
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl TransactionBuilder {
+  pub(crate) const MAX_POSTAGE: Amount = Amount::from_sat(2 * 10_000);
+  pub(crate) const TARGET_POSTAGE: Amount = Amount::from_sat(10_000);
+
+  pub(crate) fn new(
+    outgoing: SatPoint,
+    inscriptions: BTreeMap<SatPoint, InscriptionId>,
+    amounts: BTreeMap<OutPoint, Amount>,
+    recipient: Address,
+    change: [Address; 2],
+    fee_rate: FeeRate,
+    target: Target,
+  ) -> Self {
+    Self {
+      amounts,
+      change_addresses: change,
+      fee_rate,
+      inscriptions,
+      outgoing,
+      recipient,
+      target,
+    }
+  }
+
+  pub(crate) fn build_transaction(self) -> Result<Transaction> {
+    let outgoing_value = *self
+      .amounts
+      .get(&self.outgoing.outpoint)
+      .ok_or(Error::NotInWallet(self.outgoing))?;
+
+    if self.outgoing.offset >= outgoing_value.to_sat() {
+      return Err(Error::OutOfRange(self.outgoing));
+    }
+
+    for (inscribed_satpoint, inscription_id) in &self.inscriptions {
+      if inscribed_satpoint.outpoint == self.outgoing.outpoint
+        && *inscribed_satpoint != self.outgoing
+      {
+        return Err(Error::UtxoContainsAdditionalInscription {
+          outgoing_satpoint: self.outgoing,
+          inscribed_satpoint: *inscribed_satpoint,
+          inscription_id: *inscription_id,
+        });
+      }
+    }
+
+    for address in &self.change_addresses {
+      if *address == self.recipient {
+        return Err(Error::DuplicateAddress(address.clone()));
+      }
+    }
+
+    if self.change_addresses[0] == self.change_addresses[1] {
+      return Err(Error::DuplicateAddress(self.change_addresses[0].clone()));
+    }
+
+    let padding = Amount::from_sat(self.outgoing.offset);
+    if padding > Amount::ZERO {
+      Self::check_dust(padding, &self.change_addresses[1])?;
+    }
+
+    let remaining = outgoing_value - padding;
+    let mut recipient_value = match self.target {
+      Target::Value(value) => value,
+      Target::Postage if remaining > Self::MAX_POSTAGE => Self::TARGET_POSTAGE,
+      Target::Postage => remaining,
+    };
+    Self::check_dust(recipient_value, &self.recipient)?;
+
+    let inscribed_outpoints = self
+      .inscriptions
+      .keys()
+      .map(|satpoint| satpoint.outpoint)
+      .collect::<HashSet<OutPoint>>();
+
+    let mut cardinals = self
+      .amounts
+      .iter()
+      .filter(|(outpoint, _)| {
+        **outpoint != self.outgoing.outpoint && !inscribed_outpoints.contains(outpoint)
+      })
+      .map(|(outpoint, amount)| (*outpoint, *amount))
+      .collect::<Vec<(OutPoint, Amount)>>();
+    cardinals.sort_by_key(|(_, amount)| cmp::Reverse(*amount));
+    let mut cardinals = cardinals.into_iter();
+
+    let mut inputs = vec![self.outgoing.outpoint];
+    let mut total = outgoing_value;
+
+    let outputs = loop {
+      let mut outputs = Vec::new();
+      if padding > Amount::ZERO {
+        outputs.push((self.change_addresses[1].clone(), padding));
+      }
+      outputs.push((self.recipient.clone(), recipient_value));
+
+      let fixed = padding + recipient_value;
+
+      let mut with_change = outputs.clone();
+      with_change.push((self.change_addresses[0].clone(), Amount::ZERO));
+      let fee = self.estimate_fee(&inputs, &with_change);
+      let change_dust = self.change_addresses[0].script_pubkey().dust_value();
+      if total >= fixed + fee + change_dust {
+        with_change.last_mut().unwrap().1 = total - fixed - fee;
+        break with_change;
+      }
+
+      let fee = self.estimate_fee(&inputs, &outputs);
+      if total >= fixed + fee {
+        break outputs;
+      }
+
+      if self.target == Target::Postage {
+        let deficit = fixed + fee - total;
+        if recipient_value >= deficit
+          && recipient_value - deficit >= self.recipient.script_pubkey().dust_value()
+        {
+          recipient_value -= deficit;
+          outputs.last_mut().unwrap().1 = recipient_value;
+          break outputs;
+        }
+      }
+
+      let (outpoint, amount) = cardinals.next().ok_or(Error::NotEnoughCardinalUtxos)?;
+      inputs.push(outpoint);
+      total += amount;
+    };
+
+    let transaction = Self::transaction(&inputs, &outputs, false);
+
+    self.check_inscriptions(&inputs, &outputs)?;
+
+    Ok(transaction)
+  }
+
+  fn check_dust(value: Amount, address: &Address) -> Result<()> {
+    let dust_value = address.script_pubkey().dust_value();
+    if value < dust_value {
+      return Err(Error::Dust {
+        output_value: value,
+        dust_value,
+      });
+    }
+    Ok(())
+  }
+
+  /// Checks that every inscription in the inputs ends up in an output, and the outgoing sat at
+  /// the start of the recipient output.
+  fn check_inscriptions(&self, inputs: &[OutPoint], outputs: &[(Address, Amount)]) -> Result<()> {
+    let output_end = outputs
+      .iter()
+      .map(|(_, amount)| amount.to_sat())
+      .sum::<u64>();
+
+    let mut offset = 0;
+    for input in inputs {
+      for (satpoint, inscription_id) in self
+        .inscriptions
+        .range(
+          SatPoint {
+            outpoint: *input,
+            offset: 0,
+          }..,
+        )
+        .take_while(|(satpoint, _)| satpoint.outpoint == *input)
+      {
+        if offset + satpoint.offset >= output_end {
+          return Err(Error::LostInscription(*inscription_id));
+        }
+      }
+      offset += self.amounts[input].to_sat();
+    }
+
+    let recipient_start = outputs
+      .iter()
+      .take_while(|(address, _)| *address != self.recipient)
+      .map(|(_, amount)| amount.to_sat())
+      .sum::<u64>();
+    assert_eq!(
+      recipient_start, self.outgoing.offset,
+      "outgoing sat should be the first sat of the recipient output"
+    );
+
+    Ok(())
+  }
+
+  fn estimate_fee(&self, inputs: &[OutPoint], outputs: &[(Address, Amount)]) -> Amount {
+    self
+      .fee_rate
+      .fee(Self::transaction(inputs, outputs, true).vsize())
+  }
+
+  fn transaction(
+    inputs: &[OutPoint],
+    outputs: &[(Address, Amount)],
+    estimate: bool,
+  ) -> Transaction {
+    Transaction {
+      version: 1,
+      lock_time: LockTime::ZERO,
+      input: inputs
+        .iter()
+        .map(|outpoint| TxIn {
+          previous_output: *outpoint,
+          script_sig: ScriptBuf::new(),
+          sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+          witness: if estimate {
+            // a taproot key path spend carries one schnorr signature.
+            Witness::from_slice(&[&[0; 64]])
+          } else {
+            Witness::new()
+          },
+        })
+        .collect(),
+      output: outputs
+        .iter()
+        .map(|(address, amount)| TxOut {
+          value: amount.to_sat(),
+          script_pubkey: address.script_pubkey(),
+        })
+        .collect(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fee_rate() -> FeeRate {
+    FeeRate::try_from(1.0).unwrap()
+  }
+
+  fn fee(transaction: &Transaction, amounts: &BTreeMap<OutPoint, Amount>) -> u64 {
+    transaction
+      .input
+      .iter()
+      .map(|input| amounts[&input.previous_output].to_sat())
+      .sum::<u64>()
+      - transaction
+        .output
+        .iter()
+        .map(|output| output.value)
+        .sum::<u64>()
+  }
+
+  #[test]
+  fn value_target_pays_change() {
+    let amounts = BTreeMap::from([(outpoint(1), Amount::from_sat(100_000))]);
+
+    let transaction = TransactionBuilder::new(
+      satpoint(1, 0),
+      BTreeMap::new(),
+      amounts.clone(),
+      recipient(),
+      [change(0), change(1)],
+      fee_rate(),
+      Target::Value(Amount::from_sat(20_000)),
+    )
+    .build_transaction()
+    .unwrap();
+
+    assert_eq!(transaction.input.len(), 1);
+    assert_eq!(transaction.output.len(), 2);
+    assert_eq!(transaction.output[0], tx_out(20_000, recipient()));
+    assert_eq!(
+      transaction.output[1].script_pubkey,
+      change(0).script_pubkey()
+    );
+    assert!(fee(&transaction, &amounts) > 0);
+    assert!(fee(&transaction, &amounts) < 1_000);
+  }
+
+  #[test]
+  fn postage_target_deducts_fee_from_postage() {
+    let amounts = BTreeMap::from([(outpoint(1), Amount::from_sat(10_000))]);
+
+    let transaction = TransactionBuilder::new(
+      satpoint(1, 0),
+      BTreeMap::from([(satpoint(1, 0), inscription_id(1))]),
+      amounts.clone(),
+      recipient(),
+      [change(0), change(1)],
+      fee_rate(),
+      Target::Postage,
+    )
+    .build_transaction()
+    .unwrap();
+
+    assert_eq!(transaction.input.len(), 1);
+    assert_eq!(transaction.output.len(), 1);
+    assert_eq!(
+      transaction.output[0].script_pubkey,
+      recipient().script_pubkey()
+    );
+    assert_eq!(
+      transaction.output[0].value + fee(&transaction, &amounts),
+      10_000
+    );
+  }
+
+  #[test]
+  fn postage_target_strips_excess_postage() {
+    let amounts = BTreeMap::from([(outpoint(1), Amount::from_sat(1_000_000))]);
+
+    let transaction = TransactionBuilder::new(
+      satpoint(1, 0),
+      BTreeMap::from([(satpoint(1, 0), inscription_id(1))]),
+      amounts,
+      recipient(),
+      [change(0), change(1)],
+      fee_rate(),
+      Target::Postage,
+    )
+    .build_transaction()
+    .unwrap();
+
+    assert_eq!(transaction.output.len(), 2);
+    assert_eq!(transaction.output[0], tx_out(10_000, recipient()));
+    assert_eq!(
+      transaction.output[1].script_pubkey,
+      change(0).script_pubkey()
+    );
+  }
+
+  #[test]
+  fn outgoing_offset_is_padded() {
+    let amounts = BTreeMap::from([(outpoint(1), Amount::from_sat(15_000))]);
+
+    let transaction = TransactionBuilder::new(
+      satpoint(1, 5_000),
+      BTreeMap::from([(satpoint(1, 5_000), inscription_id(1))]),
+      amounts,
+      recipient(),
+      [change(0), change(1)],
+      fee_rate(),
+      Target::Postage,
+    )
+    .build_transaction()
+    .unwrap();
+
+    assert_eq!(transaction.output[0], tx_out(5_000, change(1)));
+    assert_eq!(
+      transaction.output[1].script_pubkey,
+      recipient().script_pubkey()
+    );
+  }
+
+  #[test]
+  fn cardinal_utxos_are_added_without_spending_inscriptions() {
+    let amounts = BTreeMap::from([
+      (outpoint(1), Amount::from_sat(10_000)),
+      (outpoint(2), Amount::from_sat(1_000_000)),
+      (outpoint(3), Amount::from_sat(50_000)),
+    ]);
+
+    let transaction = TransactionBuilder::new(
+      satpoint(1, 0),
+      BTreeMap::from([(satpoint(2, 0), inscription_id(2))]),
+      amounts,
+      recipient(),
+      [change(0), change(1)],
+      fee_rate(),
+      Target::Value(Amount::from_sat(40_000)),
+    )
+    .build_transaction()
+    .unwrap();
+
+    assert_eq!(
+      transaction
+        .input
+        .iter()
+        .map(|input| input.previous_output)
+        .collect::<Vec<OutPoint>>(),
+      [outpoint(1), outpoint(3)]
+    );
+    assert_eq!(transaction.output[0], tx_out(40_000, recipient()));
+  }
+
+  #[test]
+  fn insufficient_cardinal_utxos() {
+    assert_eq!(
+      TransactionBuilder::new(
+        satpoint(1, 0),
+        BTreeMap::new(),
+        BTreeMap::from([(outpoint(1), Amount::from_sat(10_000))]),
+        recipient(),
+        [change(0), change(1)],
+        fee_rate(),
+        Target::Value(Amount::from_sat(10_000)),
+      )
+      .build_transaction(),
+      Err(Error::NotEnoughCardinalUtxos)
+    );
+  }
+
+  #[test]
+  fn additional_inscription_in_outgoing_utxo_is_an_error() {
+    assert_eq!(
+      TransactionBuilder::new(
+        satpoint(1, 0),
+        BTreeMap::from([
+          (satpoint(1, 0), inscription_id(1)),
+          (satpoint(1, 500), inscription_id(2)),
+        ]),
+        BTreeMap::from([(outpoint(1), Amount::from_sat(10_000))]),
+        recipient(),
+        [change(0), change(1)],
+        fee_rate(),
+        Target::Postage,
+      )
+      .build_transaction(),
+      Err(Error::UtxoContainsAdditionalInscription {
+        outgoing_satpoint: satpoint(1, 0),
+        inscribed_satpoint: satpoint(1, 500),
+        inscription_id: inscription_id(2),
+      })
+    );
+  }
+
+  #[test]
+  fn outgoing_satpoint_must_be_in_wallet() {
+    assert_eq!(
+      TransactionBuilder::new(
+        satpoint(2, 0),
+        BTreeMap::new(),
+        BTreeMap::from([(outpoint(1), Amount::from_sat(10_000))]),
+        recipient(),
+        [change(0), change(1)],
+        fee_rate(),
+        Target::Postage,
+      )
+      .build_transaction(),
+      Err(Error::NotInWallet(satpoint(2, 0)))
+    );
+  }
+
+  #[test]
+  fn recipient_cannot_be_a_change_address() {
+    assert_eq!(
+      TransactionBuilder::new(
+        satpoint(1, 0),
+        BTreeMap::new(),
+        BTreeMap::from([(outpoint(1), Amount::from_sat(10_000))]),
+        change(0),
+        [change(0), change(1)],
+        fee_rate(),
+        Target::Postage,
+      )
+      .build_transaction(),
+      Err(Error::DuplicateAddress(change(0)))
+    );
+  }
+}
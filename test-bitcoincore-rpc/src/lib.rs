@@ -209,6 +209,10 @@ impl Handle {
     self.state().mempool().to_vec()
   }
 
+  pub fn evict_tx(&self, txid: Txid) {
+    self.state().mempool.retain(|tx| tx.txid() != txid);
+  }
+
   pub fn descriptors(&self) -> Vec<String> {
     self.state().descriptors.clone()
   }
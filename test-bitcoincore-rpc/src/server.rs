@@ -298,17 +298,11 @@ impl Api for Server {
 
     let value = Amount::from_btc(amount).expect("error converting amount to sat");
 
-    let (outpoint, utxo_amount) = state
-      .utxos
-      .iter()
-      .find(|(outpoint, amount)| *amount >= &value && !locked.contains(outpoint))
-      .expect("failed to get a utxo");
-
     let mut transaction = Transaction {
       version: 1,
       lock_time: LockTime::ZERO,
       input: vec![TxIn {
-        previous_output: *outpoint,
+        previous_output: OutPoint::null(),
         script_sig: ScriptBuf::new(),
         sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
         witness: Witness::new(),
@@ -319,7 +313,7 @@ impl Api for Server {
           script_pubkey: address.payload.script_pubkey(),
         },
         TxOut {
-          value: (*utxo_amount - value).to_sat(),
+          value: 0,
           script_pubkey: address.payload.script_pubkey(),
         },
       ],
@@ -327,9 +321,18 @@ impl Api for Server {
 
     #[allow(clippy::cast_possible_truncation)]
     #[allow(clippy::cast_sign_loss)]
-    let fee = (fee_rate.unwrap_or(1.0) * transaction.vsize() as f64).round() as u64;
+    let fee =
+      Amount::from_sat((fee_rate.unwrap_or(1.0) * transaction.vsize() as f64).round() as u64);
 
-    transaction.output[1].value -= fee;
+    // the change output pays the fee, so the utxo must be worth more than `value`.
+    let (outpoint, utxo_amount) = state
+      .utxos
+      .iter()
+      .find(|(outpoint, amount)| **amount >= value + fee && !locked.contains(outpoint))
+      .expect("failed to get a utxo");
+
+    transaction.input[0].previous_output = *outpoint;
+    transaction.output[1].value = (*utxo_amount - value - fee).to_sat();
 
     state.mempool.push(transaction);
 
@@ -410,13 +413,22 @@ impl Api for Server {
         None => Err(Self::not_found()),
       }
     } else {
-      match self.state().transactions.get(&txid) {
+      let state = self.state();
+      match state
+        .transactions
+        .get(&txid)
+        .or_else(|| state.mempool.iter().find(|tx| tx.txid() == txid))
+      {
         Some(tx) => Ok(Value::String(hex::encode(serialize(tx)))),
         None => Err(Self::not_found()),
       }
     }
   }
 
+  fn get_raw_mempool(&self) -> Result<Vec<Txid>, jsonrpc_core::Error> {
+    Ok(self.state().mempool.iter().map(Transaction::txid).collect())
+  }
+
   fn list_unspent(
     &self,
     minconf: Option<usize>,
@@ -445,7 +457,15 @@ impl Api for Server {
           label: None,
           redeem_script: None,
           witness_script: None,
-          script_pub_key: ScriptBuf::new(),
+          script_pub_key: state
+            .transactions
+            .get(&outpoint.txid)
+            .map(|tx| {
+              tx.output[usize::try_from(outpoint.vout).unwrap()]
+                .script_pubkey
+                .clone()
+            })
+            .unwrap_or_default(),
           amount,
           confirmations: 0,
           spendable: true,
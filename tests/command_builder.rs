@@ -71,6 +71,13 @@ impl CommandBuilder {
     }
   }
 
+  pub(crate) fn stderr_regex(self, expected_stderr: impl AsRef<str>) -> Self {
+    Self {
+      expected_stderr: Expected::regex(expected_stderr.as_ref()),
+      ..self
+    }
+  }
+
   pub(crate) fn expected_stderr(self, expected_stderr: impl AsRef<str>) -> Self {
     Self {
       expected_stderr: Expected::String(expected_stderr.as_ref().to_owned()),
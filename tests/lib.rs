@@ -35,7 +35,6 @@ macro_rules! assert_regex_match {
   };
 }
 
-#[allow(unused)]
 type Inscribe = ord::subcommand::wallet::inscribe::Output;
 
 #[allow(unused)]
@@ -79,3 +78,4 @@ mod index;
 mod json_api;
 mod server;
 mod version;
+mod wallet;
@@ -0,0 +1,482 @@
+use {
+  super::*,
+  bitcoin::{
+    address::NetworkUnchecked,
+    hashes::{sha256, Hash, HashEngine},
+    Address, Network, OutPoint,
+  },
+  bitcoincore_rpc::{Auth, Client, RpcApi},
+  ord::subcommand::wallet::{brc20::SendOutput, create, receive},
+};
+
+const BRC20: &str = "--chain regtest --enable-index-brc20";
+
+fn create_wallet(rpc_server: &test_bitcoincore_rpc::Handle) -> Address<NetworkUnchecked> {
+  CommandBuilder::new(format!("{BRC20} wallet create"))
+    .rpc_server(rpc_server)
+    .run_and_deserialize_output::<create::Output>();
+
+  CommandBuilder::new(format!("{BRC20} wallet receive"))
+    .rpc_server(rpc_server)
+    .run_and_deserialize_output::<receive::Output>()
+    .address
+}
+
+#[test]
+fn create_loads_wallet() {
+  let rpc_server = test_bitcoincore_rpc::spawn();
+
+  CommandBuilder::new("wallet create")
+    .rpc_server(&rpc_server)
+    .run_and_deserialize_output::<create::Output>();
+
+  assert!(rpc_server.wallets().contains("ord"));
+  assert_eq!(rpc_server.descriptors().len(), 2);
+}
+
+#[test]
+fn inscribe_reveals_file() {
+  let rpc_server = test_bitcoincore_rpc::spawn();
+
+  CommandBuilder::new("wallet create")
+    .rpc_server(&rpc_server)
+    .run_and_deserialize_output::<create::Output>();
+
+  rpc_server.mine_blocks(1);
+
+  let Inscribe {
+    commit,
+    reveal,
+    inscription,
+    ..
+  } = CommandBuilder::new("wallet inscribe --fee-rate 1 foo.txt")
+    .write("foo.txt", "FOO")
+    .rpc_server(&rpc_server)
+    .run_and_deserialize_output();
+
+  assert_eq!(
+    rpc_server
+      .mempool()
+      .iter()
+      .map(|tx| tx.txid())
+      .collect::<Vec<_>>(),
+    [commit, reveal]
+  );
+
+  assert_eq!(
+    inscription,
+    InscriptionId {
+      txid: reveal,
+      index: 0
+    }
+  );
+}
+
+#[test]
+fn brc20_commands_require_brc20_index() {
+  let rpc_server = test_bitcoincore_rpc::spawn();
+
+  CommandBuilder::new("wallet brc20 mint --tick ordi --amount 1 --fee-rate 1")
+    .rpc_server(&rpc_server)
+    .expected_exit_code(1)
+    .expected_stderr("error: wallet brc20 commands require --enable-index-brc20\n")
+    .run_and_extract_stdout();
+}
+
+#[test]
+fn brc20_mint_rejects_self_mint_tickers() {
+  let rpc_server = test_bitcoincore_rpc::spawn();
+
+  CommandBuilder::new(format!(
+    "{BRC20} wallet brc20 mint --tick abcde --amount 1 --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .expected_exit_code(1)
+  .expected_stderr(
+    "error: abcde is a self mint ticker, only children of its deploy inscription can mint it\n",
+  )
+  .run_and_extract_stdout();
+}
+
+#[test]
+fn brc20_deploy_mint_and_inscribe_transfer() {
+  let rpc_server = test_bitcoincore_rpc::builder()
+    .network(Network::Regtest)
+    .build();
+
+  let address = create_wallet(&rpc_server);
+  let address = address.assume_checked().to_string();
+
+  rpc_server.mine_blocks(1);
+
+  CommandBuilder::new(format!(
+    "{BRC20} wallet brc20 deploy --tick ordi --max 1000 --lim 100 --dec 0 --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .run_and_deserialize_output::<Inscribe>();
+
+  rpc_server.mine_blocks(1);
+
+  CommandBuilder::new(format!(
+    "{BRC20} wallet brc20 deploy --tick ordi --max 1000 --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .expected_exit_code(1)
+  .stderr_regex("error: BRC20 operation would fail: .*")
+  .run_and_extract_stdout();
+
+  CommandBuilder::new(format!(
+    "{BRC20} wallet brc20 mint --tick ordi --amount 1000 --destination {address} --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .expected_exit_code(1)
+  .stderr_regex("error: BRC20 operation would fail: .*")
+  .run_and_extract_stdout();
+
+  CommandBuilder::new(format!(
+    "{BRC20} wallet brc20 mint --tick ordi --amount 100 --destination {address} --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .run_and_deserialize_output::<Inscribe>();
+
+  rpc_server.mine_blocks(1);
+
+  CommandBuilder::new(format!(
+    "{BRC20} wallet brc20 inscribe-transfer --tick ordi --amount 40 --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .expected_exit_code(1)
+  .expected_stderr(
+    "error: inscribe-transfer requires --destination, the address holding the balance\n",
+  )
+  .run_and_extract_stdout();
+
+  CommandBuilder::new(format!(
+    "{BRC20} wallet brc20 inscribe-transfer --tick ordi --amount 101 --destination {address} --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .expected_exit_code(1)
+  .stderr_regex("error: BRC20 operation would fail: .*")
+  .run_and_extract_stdout();
+
+  let Inscribe { inscription, .. } = CommandBuilder::new(format!(
+    "{BRC20} wallet brc20 inscribe-transfer --tick ordi --amount 40 --destination {address} --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .run_and_deserialize_output();
+
+  rpc_server.mine_blocks(1);
+
+  let server = TestServer::spawn_with_args(
+    &rpc_server,
+    &[
+      "--chain",
+      "regtest",
+      "--enable-index-brc20",
+      "--bitcoin-rpc-user",
+      "foo",
+      "--bitcoin-rpc-pass",
+      "bar",
+    ],
+  );
+
+  let response = server.json_request(format!("/api/v1/brc20/tick/ordi/address/{address}/balance"));
+  assert_eq!(response.status(), StatusCode::OK);
+  let balance: serde_json::Value = response.json().unwrap();
+  assert_eq!(balance["data"]["availableBalance"], "60");
+  assert_eq!(balance["data"]["transferableBalance"], "40");
+
+  let response = server.json_request(format!(
+    "/api/v1/brc20/tick/ordi/address/{address}/transferable"
+  ));
+  assert_eq!(response.status(), StatusCode::OK);
+  let transferable: serde_json::Value = response.json().unwrap();
+  assert_eq!(
+    transferable["data"]["inscriptions"][0]["inscriptionId"],
+    inscription.to_string()
+  );
+}
+
+#[test]
+fn brc20_send_transfers_inscribed_sat_to_recipient() {
+  let rpc_server = test_bitcoincore_rpc::builder()
+    .network(Network::Regtest)
+    .build();
+
+  let address = create_wallet(&rpc_server);
+  let address = address.assume_checked().to_string();
+
+  let recipient = CommandBuilder::new(format!("{BRC20} wallet receive"))
+    .rpc_server(&rpc_server)
+    .run_and_deserialize_output::<receive::Output>()
+    .address
+    .assume_checked()
+    .to_string();
+
+  rpc_server.mine_blocks(1);
+
+  CommandBuilder::new(format!(
+    "{BRC20} wallet brc20 deploy --tick ordi --max 1000 --lim 100 --dec 0 --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .run_and_deserialize_output::<Inscribe>();
+
+  rpc_server.mine_blocks(1);
+
+  CommandBuilder::new(format!(
+    "{BRC20} wallet brc20 mint --tick ordi --amount 100 --destination {address} --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .run_and_deserialize_output::<Inscribe>();
+
+  rpc_server.mine_blocks(1);
+
+  CommandBuilder::new(format!(
+    "{BRC20} wallet brc20 send --tick ordi --amount 40 --fee-rate 1 {recipient}"
+  ))
+  .rpc_server(&rpc_server)
+  .expected_exit_code(1)
+  .expected_stderr("error: send requires --destination, the address holding the balance\n")
+  .run_and_extract_stdout();
+
+  let command = CommandBuilder::new(format!(
+    "{BRC20} wallet brc20 send --tick ordi --amount 40 --destination {address} --fee-rate 1 --poll-interval 10 --timeout 60 {recipient}"
+  ))
+  .rpc_server(&rpc_server);
+
+  let SendOutput {
+    transfer,
+    send,
+    receipt,
+  } = thread::scope(|scope| {
+    let sender = scope.spawn(|| command.run_and_deserialize_output::<SendOutput>());
+
+    while !sender.is_finished() {
+      rpc_server.mine_blocks(1);
+      thread::sleep(Duration::from_millis(100));
+    }
+
+    sender.join().unwrap()
+  });
+
+  assert_eq!(receipt.inscription, transfer.inscription);
+  assert_eq!(
+    receipt.old_satpoint.to_string(),
+    format!("{}:0:0", transfer.reveal)
+  );
+  assert_eq!(receipt.new_satpoint.to_string(), format!("{send}:0:0"));
+  assert_eq!(receipt.from, address);
+  assert_eq!(receipt.to, recipient);
+  assert_eq!(receipt.tick, "ordi");
+  assert_eq!(receipt.amount, "40");
+
+  let server = TestServer::spawn_with_args(
+    &rpc_server,
+    &[
+      "--chain",
+      "regtest",
+      "--enable-index-brc20",
+      "--bitcoin-rpc-user",
+      "foo",
+      "--bitcoin-rpc-pass",
+      "bar",
+    ],
+  );
+
+  for (address, balance) in [(&address, "60"), (&recipient, "40")] {
+    let response =
+      server.json_request(format!("/api/v1/brc20/tick/ordi/address/{address}/balance"));
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: serde_json::Value = response.json().unwrap();
+    assert_eq!(json["data"]["overallBalance"], balance);
+    assert_eq!(json["data"]["transferableBalance"], "0");
+  }
+}
+
+fn fund(rpc_server: &test_bitcoincore_rpc::Handle, address: &str) -> OutPoint {
+  Client::new(&rpc_server.url(), Auth::None)
+    .unwrap()
+    .send_to_address(
+      &address
+        .parse::<Address<NetworkUnchecked>>()
+        .unwrap()
+        .assume_checked(),
+      bitcoin::Amount::from_btc(1.0).unwrap(),
+      None,
+      None,
+      None,
+      None,
+      None,
+      None,
+    )
+    .unwrap();
+
+  let txid = rpc_server.mempool()[0].txid();
+
+  rpc_server.mine_blocks(1);
+
+  OutPoint { txid, vout: 0 }
+}
+
+#[test]
+fn brc20s_deposit_mint_and_withdraw() {
+  const BRC20S: &str = "--chain regtest --enable-index-brc20 --enable-index-brc20s";
+
+  let rpc_server = test_bitcoincore_rpc::builder()
+    .network(Network::Regtest)
+    .build();
+
+  let address = create_wallet(&rpc_server);
+  let address = address.assume_checked().to_string();
+
+  rpc_server.mine_blocks(1);
+
+  CommandBuilder::new(format!(
+    "{BRC20S} wallet brc20 deploy --tick ordi --max 1000 --dec 0 --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .run_and_deserialize_output::<Inscribe>();
+
+  rpc_server.mine_blocks(1);
+
+  CommandBuilder::new(format!(
+    "{BRC20S} wallet brc20 mint --tick ordi --amount 100 --destination {address} --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .run_and_deserialize_output::<Inscribe>();
+
+  rpc_server.mine_blocks(1);
+
+  // the commit input and the reveal output of a pool deploy must both belong to the deployer.
+  let mut engine = sha256::Hash::engine();
+  engine.input(format!("earn{}{}{address}{address}", 21000000, 0).as_bytes());
+  let pid = format!(
+    "{}#01",
+    &sha256::Hash::from_engine(engine).to_string()[..10]
+  );
+
+  let outpoint = fund(&rpc_server, &address);
+
+  CommandBuilder::new(format!(
+    "{BRC20S} wallet inscribe --satpoint {outpoint}:0 --destination {address} --fee-rate 1 deploy.txt"
+  ))
+  .write(
+    "deploy.txt",
+    format!(
+      r#"{{"p":"brc20-s","op":"deploy","t":"pool","pid":"{pid}","stake":"ordi","earn":"earn","erate":"10","dmax":"1000","dec":"0","total":"21000000","only":"1"}}"#
+    ),
+  )
+  .rpc_server(&rpc_server)
+  .run_and_deserialize_output::<Inscribe>();
+
+  rpc_server.mine_blocks(1);
+
+  CommandBuilder::new(format!(
+    "{BRC20S} wallet brc20s deposit --pid {pid} --amount 100 --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .expected_exit_code(1)
+  .expected_stderr(
+    "error: wallet brc20s commands require --destination, the address holding the deposit\n",
+  )
+  .run_and_extract_stdout();
+
+  CommandBuilder::new(format!(
+    "{BRC20S} wallet brc20s deposit --pid 0000000000#01 --amount 100 --destination {address} --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .expected_exit_code(1)
+  .expected_stderr("error: pool 0000000000#01 does not exist\n")
+  .run_and_extract_stdout();
+
+  CommandBuilder::new(format!(
+    "{BRC20S} wallet brc20s deposit --pid {pid} --amount 101 --destination {address} --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .expected_exit_code(1)
+  .expected_stderr(format!(
+    "error: insufficient balance to deposit 101 into pool {pid}, 100 can be deposited\n"
+  ))
+  .run_and_extract_stdout();
+
+  CommandBuilder::new(format!(
+    "{BRC20S} wallet brc20s deposit --pid {pid} --amount 100 --satpoint {outpoint}:0 --destination {address} --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .expected_exit_code(1)
+  .expected_stderr(format!(
+    "error: satpoint {outpoint}:0 is not on an output of {address}\n"
+  ))
+  .run_and_extract_stdout();
+
+  CommandBuilder::new(format!(
+    "{BRC20S} wallet brc20s deposit --pid {pid} --amount 100 --destination {address} --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .run_and_deserialize_output::<Inscribe>();
+
+  rpc_server.mine_blocks(3);
+
+  CommandBuilder::new(format!(
+    "{BRC20S} wallet brc20s mint --pid {pid} --amount 1000 --destination {address} --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .expected_exit_code(1)
+  .stderr_regex(format!(
+    "error: insufficient reward to mint 1000 from pool {pid}, [0-9]+ can be minted\n"
+  ))
+  .run_and_extract_stdout();
+
+  fund(&rpc_server, &address);
+
+  CommandBuilder::new(format!(
+    "{BRC20S} wallet brc20s mint --pid {pid} --amount 10 --destination {address} --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .run_and_deserialize_output::<Inscribe>();
+
+  rpc_server.mine_blocks(1);
+
+  CommandBuilder::new(format!(
+    "{BRC20S} wallet brc20s withdraw --pid {pid} --amount 101 --destination {address} --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .expected_exit_code(1)
+  .expected_stderr(format!(
+    "error: insufficient deposit to withdraw 101 from pool {pid}, 100 is deposited\n"
+  ))
+  .run_and_extract_stdout();
+
+  fund(&rpc_server, &address);
+
+  CommandBuilder::new(format!(
+    "{BRC20S} wallet brc20s withdraw --pid {pid} --amount 40 --destination {address} --fee-rate 1"
+  ))
+  .rpc_server(&rpc_server)
+  .run_and_deserialize_output::<Inscribe>();
+
+  rpc_server.mine_blocks(1);
+
+  let server = TestServer::spawn_with_args(
+    &rpc_server,
+    &[
+      "--chain",
+      "regtest",
+      "--enable-index-brc20",
+      "--enable-index-brc20s",
+      "--bitcoin-rpc-user",
+      "foo",
+      "--bitcoin-rpc-pass",
+      "bar",
+    ],
+  );
+
+  let response = server.json_request(format!(
+    "/api/v1/brc20s/pool/{}/address/{address}/userinfo",
+    pid.replace('#', "%23")
+  ));
+  assert_eq!(response.status(), StatusCode::OK);
+  let json: serde_json::Value = response.json().unwrap();
+  assert_eq!(json["data"]["staked"], "60");
+  assert_eq!(json["data"]["minted"], "10");
+}